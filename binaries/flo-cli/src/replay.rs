@@ -1,3 +1,5 @@
+use flo_replay::capture::{replay_to_capture, ReplayToCaptureOptions};
+use flo_replay::{generate_replay, GenerateReplayOptions, ReplayChatPolicy};
 use flo_w3replay::replay::ReplayDecoder;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -6,9 +8,29 @@ use crate::Result;
 
 #[derive(Debug, StructOpt)]
 pub enum Command {
-  DumpHeader { path: PathBuf },
-  DumpGameInfo { path: PathBuf },
-  DumpSlotInfo { path: PathBuf },
+  DumpHeader {
+    path: PathBuf,
+  },
+  DumpGameInfo {
+    path: PathBuf,
+  },
+  DumpSlotInfo {
+    path: PathBuf,
+  },
+  /// Writes the replay as an observer archive `<path>.gz` and its game info `<path>.json`
+  ToCapture {
+    path: PathBuf,
+    #[structopt(long, default_value = "0")]
+    game_id: i32,
+    /// Defaults to the version in the replay header
+    #[structopt(long)]
+    game_version: Option<String>,
+  },
+  /// Writes an observer archive and its game info as `<archive>.w3g`
+  FromCapture {
+    archive: PathBuf,
+    game_info: PathBuf,
+  },
 }
 
 impl Command {
@@ -53,6 +75,49 @@ impl Command {
           }
         }
       }
+      Command::ToCapture {
+        ref path,
+        game_id,
+        ref game_version,
+      } => {
+        let capture = replay_to_capture(
+          ReplayToCaptureOptions {
+            game_id,
+            game_version: game_version.clone(),
+          },
+          std::fs::File::open(path)?,
+        )?;
+        tracing::info!(
+          "game name = {}, version = {}, records = {}",
+          capture.game.name,
+          capture.game.game_version,
+          capture.records.len()
+        );
+        let w = std::io::BufWriter::new(std::fs::File::create(path.with_extension("gz"))?);
+        flo_observer_fs::write_archive(game_id, &capture.records, w)?;
+        std::fs::write(
+          path.with_extension("json"),
+          serde_json::to_vec_pretty(&capture.game)?,
+        )?;
+      }
+      Command::FromCapture {
+        ref archive,
+        ref game_info,
+      } => {
+        let game: flo_types::observer::GameInfo =
+          serde_json::from_slice(&std::fs::read(game_info)?)?;
+        let data = std::fs::read(archive)?;
+        let file = std::fs::File::create(archive.with_extension("w3g"))?;
+        generate_replay(
+          GenerateReplayOptions {
+            game,
+            archive: data.into(),
+            chat_policy: ReplayChatPolicy::IncludeAllChats,
+          },
+          std::io::BufWriter::new(file),
+        )
+        .await?;
+      }
     }
     Ok(())
   }
@@ -1,4 +1,4 @@
-use flo_controller::{serve_grpc, serve_socket, ControllerState};
+use flo_controller::{serve_grpc, serve_metrics, serve_socket, ControllerState};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -24,15 +24,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         loop {
           stream.recv().await;
           tracing::info!("reloading");
-          if let Err(err) = state.reload().await {
-            tracing::error!("reload error: {}", err);
+          match state.reload().await {
+            Ok(report) => tracing::info!(
+              "reloaded: applied = {:?}, restart required = {:?}",
+              report.applied,
+              report.restart_required
+            ),
+            Err(err) => tracing::error!("reload error: {}", err),
           }
         }
       }
     });
   }
 
-  tokio::try_join!(serve_grpc(state.clone()), serve_socket(state.clone()))?;
+  tokio::try_join!(
+    serve_grpc(state.clone()),
+    serve_socket(state.clone()),
+    serve_metrics()
+  )?;
 
   Ok(())
 }
@@ -20,6 +20,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
   tracing::info!("starting.");
 
+  #[cfg(unix)]
+  {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut stream = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+      loop {
+        stream.recv().await;
+        tracing::info!("reloading");
+        match flo_node::reload_settings() {
+          Ok(report) => tracing::info!(
+            "reloaded: applied = {:?}, restart required = {:?}",
+            report.applied,
+            report.restart_required
+          ),
+          Err(err) => tracing::error!("reload error: {}", err),
+        }
+      }
+    });
+  }
+
   serve().await?;
 
   Ok(())
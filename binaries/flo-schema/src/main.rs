@@ -0,0 +1,30 @@
+//! Writes machine-readable schemas of the flo protocol to a directory:
+//!
+//! - `flo_net.pb`: `FileDescriptorSet` of the client, node and observer messages
+//! - `w3gs.pb`: `FileDescriptorSet` of the W3GS protobuf payloads
+//! - `game_event.schema.json`: JSON schema of the game event model
+//!
+//! The controller gRPC API is defined in the `flo-grpc` repository.
+
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() -> Result<()> {
+  let dir = PathBuf::from(
+    std::env::args()
+      .nth(1)
+      .unwrap_or_else(|| "schema".to_string()),
+  );
+  fs::create_dir_all(&dir)?;
+
+  fs::write(dir.join("flo_net.pb"), flo_net::proto::FILE_DESCRIPTOR_SET)?;
+  fs::write(dir.join("w3gs.pb"), flo_w3gs::protocol::FILE_DESCRIPTOR_SET)?;
+  fs::write(
+    dir.join("game_event.schema.json"),
+    serde_json::to_vec_pretty(&schemars::schema_for!(flo_events::GameEvent))?,
+  )?;
+
+  println!("{}", dir.display());
+  Ok(())
+}
@@ -56,15 +56,13 @@ fn main() {
       ptr: opt.ptr.clone(),
       ..Default::default()
     }))?;
-    let port = client.port();
-    rt.spawn(client.serve());
-    Ok((port, rt))
+    Ok((client, rt))
   })
   .map_err(|err| anyhow::format_err!("Start flo worker failed: {:?}", err))
   .and_then(std::convert::identity);
 
   match res {
-    Ok((port, rt)) => {
+    Ok((client, rt)) => {
       #[cfg(not(debug_assertions))]
       rt.spawn(async {
         log::start_log_vacuum("flo-logs").await.map_err(|err| {
@@ -74,13 +72,18 @@ fn main() {
 
       let msg = serde_json::to_string(&serde_json::json!({
         "version": flo_client::FLO_VERSION.to_string(),
-        "port": port
+        "port": client.port()
       }))
       .unwrap();
       let mut stdout = std::io::stdout();
       stdout.write(msg.as_bytes()).unwrap();
       stdout.flush().unwrap();
-      rt.block_on(tokio::signal::ctrl_c()).unwrap();
+      rt.block_on(async {
+        tokio::signal::ctrl_c().await.unwrap();
+        if let Err(err) = client.shutdown().await {
+          tracing::error!("shutdown: {}", err);
+        }
+      });
     }
     Err(err) => {
       let msg = serde_json::to_string(&serde_json::json!({
@@ -2,12 +2,7 @@
 async fn main() {
   flo_log_subscriber::init_env_override("debug");
 
-  let task = flo_client::start_ws(Default::default()).await.unwrap();
-  let join = tokio::spawn(task.serve());
-  let ctrl_c = tokio::signal::ctrl_c();
-
-  tokio::select! {
-    res = join => res.unwrap(),
-    _ = ctrl_c => {},
-  }
+  let client = flo_client::start_ws(Default::default()).await.unwrap();
+  tokio::signal::ctrl_c().await.unwrap();
+  client.shutdown().await.unwrap();
 }
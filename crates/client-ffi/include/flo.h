@@ -0,0 +1,54 @@
+/*
+ * C interface of the flo client, built as the `flo` shared library
+ * (`cargo build -p flo-client-ffi --release`).
+ *
+ * Messages and events use the JSON format of the websocket protocol,
+ * tagged by "type", e.g. {"type":"Connect","token":"..."}.
+ *
+ * Lobbies:
+ *   {"type":"ListLobbiesRequest","map":null,"region":null,"players_needed":null,
+ *    "cursor":null,"take":20}
+ *     replies with a "ListLobbies" event: {"lobbies":[...],"next_cursor":...}
+ *   {"type":"GameJoinRequest","game_id":1,"password":null,"invite_token":null}
+ *     the joined game is reported with a "CurrentGameInfo" event,
+ *     a failed join with a "GameJoinReject" event: {"game_id":1,"message":"..."}
+ */
+
+#ifndef FLO_H
+#define FLO_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FLO_OK 0
+#define FLO_ERR_INVALID_ARGUMENT -1
+#define FLO_ERR_INVALID_MESSAGE -2
+
+typedef struct FloClient FloClient;
+
+/* Called from a client thread, `event_json` is only valid during the call. */
+typedef void (*FloEventCallback)(const char *event_json, void *user_data);
+
+/*
+ * Starts a client, returns NULL on failure.
+ * `config_json` may be NULL, keys: token, installation_path, user_data_path,
+ * controller_host, stats_host, version, ptr, save_replay, user_battlenet_client_id.
+ * `user_data` must stay valid until flo_client_stop returns.
+ */
+FloClient *flo_client_start(const char *config_json, FloEventCallback callback, void *user_data);
+
+/* Queues a message, does not block and can be called from the callback. */
+int flo_client_send(FloClient *client, const char *message_json);
+
+/*
+ * Stops and frees the client, blocks until a running callback returns.
+ * The callback is not called after this returns, must not be called from the callback.
+ */
+void flo_client_stop(FloClient *client);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,275 @@
+//! C interface for embedding the flo client in non-Rust launchers.
+//! Messages and events are the JSON messages of the websocket protocol,
+//! see `include/flo.h`.
+
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+use flo_client::messages::{IncomingMessage, OutgoingMessage};
+use flo_client::{start_embed, FloEmbedClientHandle, StartConfig};
+
+pub const FLO_OK: c_int = 0;
+pub const FLO_ERR_INVALID_ARGUMENT: c_int = -1;
+pub const FLO_ERR_INVALID_MESSAGE: c_int = -2;
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Called with a NUL terminated JSON event and the `user_data` passed to `flo_client_start`
+pub type FloEventCallback = extern "C" fn(event_json: *const c_char, user_data: *mut c_void);
+
+pub struct FloClient {
+  handle: FloEmbedClientHandle,
+  rt: Runtime,
+  callback: CallbackGate,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+  token: Option<String>,
+  installation_path: Option<PathBuf>,
+  user_data_path: Option<PathBuf>,
+  controller_host: Option<String>,
+  stats_host: Option<String>,
+  version: Option<String>,
+  ptr: Option<bool>,
+  save_replay: bool,
+  user_battlenet_client_id: Option<String>,
+}
+
+impl From<Config> for StartConfig {
+  fn from(config: Config) -> Self {
+    StartConfig {
+      token: config.token,
+      installation_path: config.installation_path,
+      user_data_path: config.user_data_path,
+      controller_host: config.controller_host,
+      stats_host: config.stats_host,
+      version: config.version,
+      ptr: config.ptr,
+      save_replay: config.save_replay,
+      user_battlenet_client_id: config.user_battlenet_client_id,
+    }
+  }
+}
+
+struct EventCallback {
+  f: FloEventCallback,
+  user_data: *mut c_void,
+}
+
+// The caller guarantees `user_data` can be used from the client threads
+unsafe impl Send for EventCallback {}
+
+/// Runs the callback under a lock so `flo_client_stop` can wait for
+/// a running callback and prevent further calls
+#[derive(Clone)]
+struct CallbackGate(Arc<Mutex<Option<EventCallback>>>);
+
+impl CallbackGate {
+  fn new(callback: EventCallback) -> Self {
+    Self(Arc::new(Mutex::new(Some(callback))))
+  }
+
+  /// Returns false once the gate is closed
+  fn call(&self, msg: &OutgoingMessage) -> bool {
+    let lock = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+    match lock.as_ref() {
+      Some(callback) => {
+        callback.call(msg);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Blocks until a running callback returns
+  fn close(&self) {
+    self.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+  }
+}
+
+impl EventCallback {
+  fn call(&self, msg: &OutgoingMessage) {
+    let json = match msg.serialize() {
+      Ok(json) => json,
+      Err(err) => {
+        tracing::error!("serialize event: {}", err);
+        return;
+      }
+    };
+    match CString::new(json) {
+      Ok(json) => (self.f)(json.as_ptr(), self.user_data),
+      Err(err) => tracing::error!("event contains NUL: {}", err),
+    }
+  }
+}
+
+/// Starts a client, returns NULL on failure.
+///
+/// `config_json` may be NULL to use the default config.
+/// `callback` is called from a client thread for every event until the client is stopped.
+///
+/// # Safety
+///
+/// `config_json` must be NULL or a NUL terminated string.
+/// `user_data` must stay valid until `flo_client_stop` returns.
+#[no_mangle]
+pub unsafe extern "C" fn flo_client_start(
+  config_json: *const c_char,
+  callback: FloEventCallback,
+  user_data: *mut c_void,
+) -> *mut FloClient {
+  let config: Config = if config_json.is_null() {
+    Config::default()
+  } else {
+    match CStr::from_ptr(config_json)
+      .to_str()
+      .map_err(|err| err.to_string())
+      .and_then(|s| serde_json::from_str(s).map_err(|err| err.to_string()))
+    {
+      Ok(config) => config,
+      Err(err) => {
+        tracing::error!("invalid config: {}", err);
+        return std::ptr::null_mut();
+      }
+    }
+  };
+
+  let rt = match Runtime::new() {
+    Ok(rt) => rt,
+    Err(err) => {
+      tracing::error!("create runtime: {}", err);
+      return std::ptr::null_mut();
+    }
+  };
+
+  let mut client = match rt.block_on(start_embed(config.into())) {
+    Ok(client) => client,
+    Err(err) => {
+      tracing::error!("start client: {}", err);
+      return std::ptr::null_mut();
+    }
+  };
+  let handle = client.handle();
+  let callback = CallbackGate::new(EventCallback {
+    f: callback,
+    user_data,
+  });
+  rt.spawn({
+    let callback = callback.clone();
+    async move {
+      while let Some(msg) = client.recv().await {
+        if !callback.call(&msg) {
+          break;
+        }
+      }
+    }
+  });
+
+  Box::into_raw(Box::new(FloClient {
+    handle,
+    rt,
+    callback,
+  }))
+}
+
+/// Queues a JSON message, e.g. `{"type":"ListLobbiesRequest"}`.
+/// Does not block and can be called from the event callback.
+///
+/// # Safety
+///
+/// `client` must be returned by `flo_client_start` and not stopped,
+/// `message_json` must be a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn flo_client_send(
+  client: *mut FloClient,
+  message_json: *const c_char,
+) -> c_int {
+  if client.is_null() || message_json.is_null() {
+    return FLO_ERR_INVALID_ARGUMENT;
+  }
+  let client = &*client;
+  let msg: IncomingMessage = match CStr::from_ptr(message_json)
+    .to_str()
+    .map_err(|err| err.to_string())
+    .and_then(|s| {
+      s.parse()
+        .map_err(|err: flo_client::error::Error| err.to_string())
+    }) {
+    Ok(msg) => msg,
+    Err(err) => {
+      tracing::error!("invalid message: {}", err);
+      return FLO_ERR_INVALID_MESSAGE;
+    }
+  };
+  let handle = client.handle.clone();
+  client.rt.spawn(async move {
+    if let Err(err) = handle.send(msg).await {
+      tracing::error!("send message: {}", err);
+    }
+  });
+  FLO_OK
+}
+
+/// Stops the client and frees it.
+/// Blocks until a running event callback returns, the callback is not called
+/// after this function returns. Must not be called from the event callback.
+///
+/// # Safety
+///
+/// `client` must be NULL or returned by `flo_client_start` and not stopped.
+#[no_mangle]
+pub unsafe extern "C" fn flo_client_stop(client: *mut FloClient) {
+  if client.is_null() {
+    return;
+  }
+  let client = Box::from_raw(client);
+  let FloClient {
+    handle,
+    rt,
+    callback,
+  } = *client;
+  callback.close();
+  drop(handle);
+  rt.shutdown_timeout(SHUTDOWN_TIMEOUT);
+}
+
+#[test]
+fn test_config() {
+  let config: StartConfig = serde_json::from_str::<Config>(r#"{"token":"t","save_replay":true}"#)
+    .unwrap()
+    .into();
+  assert_eq!(config.token.as_deref(), Some("t"));
+  assert!(config.save_replay);
+  assert_eq!(config.controller_host, None);
+}
+
+#[test]
+fn test_callback_gate() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  extern "C" fn count(_: *const c_char, user_data: *mut c_void) {
+    let calls = unsafe { &*(user_data as *const AtomicUsize) };
+    calls.fetch_add(1, Ordering::SeqCst);
+  }
+
+  let calls = AtomicUsize::new(0);
+  let gate = CallbackGate::new(EventCallback {
+    f: count,
+    user_data: &calls as *const AtomicUsize as *mut c_void,
+  });
+  let msg = OutgoingMessage::GameDisconnect;
+  assert!(gate.call(&msg));
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+  gate.close();
+  assert!(!gate.call(&msg));
+  assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
@@ -2,13 +2,15 @@ mod stream;
 #[cfg(test)]
 mod stream_test;
 
+use crate::controller::stream::Close;
 pub use crate::controller::stream::GameReceivedEvent;
 use crate::controller::stream::{ControllerEvent, ControllerEventData, PlayerSessionUpdateEvent};
 pub use crate::controller::stream::{ControllerStream, SendFrame};
+use crate::credential::{Credential, CredentialStore};
 use crate::error::*;
 use crate::lan::{
-  KillLanGame, Lan, LanEvent, ReplaceLanGame, StopLanGame, UpdateLanGamePlayerStatus,
-  UpdateLanGameStatus,
+  KillLanGame, Lan, LanEvent, LanLobbyChat, ReplaceLanGame, StopLanGame, UpdateLanGameMuteList,
+  UpdateLanGamePlayerStatus, UpdateLanGameStatus,
 };
 use crate::message::messages::{self, OutgoingMessage};
 use crate::message::ConnectController;
@@ -20,13 +22,18 @@ use crate::node::{
 use crate::platform::{GetClientConfig, Platform};
 use crate::StartConfig;
 use flo_config::ClientConfig;
+use flo_net::error_code::ErrorCode;
 use flo_net::packet::FloPacket;
 use flo_net::packet::Frame;
 use flo_state::{async_trait, Actor, Addr, Context, Handler, Message, Owner, RegistryRef, Service};
 use flo_types::game::PlayerSession;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::WeakSender;
+use tokio::task::JoinHandle;
+
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub struct ControllerClient {
   config: ClientConfig,
@@ -38,6 +45,7 @@ pub struct ControllerClient {
   message_session: Option<Session>,
   current_session: Option<PlayerSession>,
   initial_token: Option<String>,
+  credentials: CredentialStore,
   mute_list: Vec<i32>,
 }
 
@@ -59,6 +67,20 @@ impl ControllerClient {
     self.conn.take();
   }
 
+  /// Stores or deletes (`None`) a credential in the background
+  fn update_credential(&self, credential: Credential, value: Option<String>) {
+    let credentials = self.credentials.clone();
+    tokio::task::spawn_blocking(move || {
+      let res = match value {
+        Some(value) => credentials.set(credential, &value),
+        None => credentials.delete(credential),
+      };
+      if let Err(err) = res {
+        tracing::error!("update credential {:?}: {}", credential, err);
+      }
+    });
+  }
+
   async fn ws_send(&self, message: OutgoingMessage) {
     if let Some(sender) = self.message_session.as_ref().map(|v| v.sender()) {
       sender.send_or_discard(message).await;
@@ -92,11 +114,17 @@ impl ControllerClient {
       return;
     };
 
+    self.update_credential(
+      Credential::ReconnectToken { game_id },
+      Some(base64::encode(&event.player_token)),
+    );
+
     let game_name = event.game_info.name.clone();
     let msg = ReplaceLanGame {
       my_player_id: player_session.player.id,
       node: Arc::new(node_info),
       player_token: event.player_token,
+      traceparent: event.traceparent,
       game: event.game_info,
     };
 
@@ -149,6 +177,19 @@ impl Service<StartConfig> for ControllerClient {
   async fn create(registry: &mut RegistryRef<StartConfig>) -> Result<Self, Self::Error> {
     let platform = registry.resolve::<Platform>().await?;
     let config = platform.send(GetClientConfig).await?;
+    let credentials = CredentialStore::open();
+    let initial_token = match registry.data().token.clone() {
+      Some(token) => Some(token),
+      None => {
+        let credentials = credentials.clone();
+        tokio::task::spawn_blocking(move || credentials.get(Credential::AuthToken))
+          .await?
+          .unwrap_or_else(|err| {
+            tracing::error!("load auth token: {}", err);
+            None
+          })
+      }
+    };
     Ok(Self {
       config,
       platform,
@@ -158,7 +199,8 @@ impl Service<StartConfig> for ControllerClient {
       conn_id: 0,
       message_session: None,
       current_session: None,
-      initial_token: registry.data().token.clone(),
+      initial_token,
+      credentials,
       mute_list: vec![],
     })
   }
@@ -213,6 +255,9 @@ impl Handler<ControllerEvent> for ControllerClient {
           ControllerEventData::Connected => {}
           ControllerEventData::ConnectionError(err) => {
             tracing::error!("connection error: {}", err);
+            if err.code() == ErrorCode::InvalidToken {
+              self.update_credential(Credential::AuthToken, None);
+            }
             if let Some(stream) = self.conn.take() {
               ctx.spawn(async move {
                 stream.shutdown().await.ok();
@@ -321,6 +366,7 @@ impl Handler<MessageEvent> for ControllerClient {
   ) -> <MessageEvent as Message>::Result {
     match message {
       MessageEvent::ConnectController(ConnectController { token }) => {
+        self.update_credential(Credential::AuthToken, Some(token.clone()));
         self.connect(ctx, token);
       }
       MessageEvent::Disconnect => self.disconnect(),
@@ -380,6 +426,7 @@ impl Handler<LanEvent> for ControllerClient {
   ) -> <LanEvent as Message>::Result {
     match message {
       LanEvent::LanGameDisconnected { game_id } => {
+        self.update_credential(Credential::ReconnectToken { game_id }, None);
         self.lan.notify(StopLanGame { game_id }).await.ok();
       }
       LanEvent::NodeStreamEvent { game_id, inner } => match inner {
@@ -455,6 +502,23 @@ impl Handler<LanEvent> for ControllerClient {
             );
           }
         }
+        NodeStreamEvent::IdleWarning(warning) => {
+          self
+            .ws_send(OutgoingMessage::GameIdleWarning(warning.clone()))
+            .await;
+          self
+            .lan
+            .notify(LanLobbyChat {
+              game_id,
+              player_id: warning.player_id,
+              message: format!(
+                "[FLO] You haven't loaded the game, you will be removed in {} seconds.",
+                warning.kick_in_secs
+              ),
+            })
+            .await
+            .ok();
+        }
         NodeStreamEvent::Disconnected => {
           self.lan.notify(StopLanGame { game_id }).await.ok();
         }
@@ -478,7 +542,41 @@ impl Handler<UpdateMuteList> for ControllerClient {
     _: &mut Context<Self>,
     UpdateMuteList { mute_list }: UpdateMuteList,
   ) -> <UpdateMuteList as Message>::Result {
-    self.mute_list = mute_list;
+    self.mute_list = mute_list.clone();
+    self
+      .lan
+      .notify(UpdateLanGameMuteList { mute_list })
+      .await
+      .ok();
+  }
+}
+
+pub struct LobbyChatReceived(pub flo_net::proto::flo_connect::PacketGameLobbyChat);
+
+impl Message for LobbyChatReceived {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<LobbyChatReceived> for ControllerClient {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LobbyChatReceived(packet): LobbyChatReceived,
+  ) -> <LobbyChatReceived as Message>::Result {
+    if self.mute_list.contains(&packet.player_id) {
+      return;
+    }
+    self
+      .lan
+      .notify(LanLobbyChat {
+        game_id: packet.game_id,
+        player_id: packet.player_id,
+        message: packet.message.clone(),
+      })
+      .await
+      .ok();
+    self.ws_send(OutgoingMessage::GameLobbyChat(packet)).await;
   }
 }
 
@@ -623,3 +721,44 @@ impl Handler<GetWeakOutgoingMessageSender> for ControllerClient {
       .map(|s| s.sender().downgrade())
   }
 }
+
+/// Leaves the current game and closes the controller stream,
+/// the returned task completes once the stream is closed
+pub struct Shutdown;
+
+impl Message for Shutdown {
+  type Result = Option<JoinHandle<()>>;
+}
+
+#[async_trait]
+impl Handler<Shutdown> for ControllerClient {
+  async fn handle(&mut self, _: &mut Context<Self>, _: Shutdown) -> Option<JoinHandle<()>> {
+    self.message_session.take();
+    let stream = self.conn.take()?;
+
+    let mut frames = vec![];
+    if let Some(game_id) = self.current_session.as_ref().and_then(|s| s.game_id) {
+      match (flo_net::proto::flo_connect::PacketGameLeaveRequest { game_id }).encode_as_frame() {
+        Ok(frame) => frames.push(frame),
+        Err(err) => tracing::error!(game_id, "encode leave request: {}", err),
+      }
+    }
+
+    let closed = stream
+      .send(Close(frames))
+      .await
+      .map_err(Error::from)
+      .and_then(std::convert::identity)
+      .map_err(|err| tracing::debug!("close controller stream: {}", err))
+      .ok();
+
+    Some(tokio::spawn(async move {
+      if let Some(closed) = closed {
+        if tokio::time::timeout(CLOSE_TIMEOUT, closed).await.is_err() {
+          tracing::warn!("close controller stream: timeout");
+        }
+      }
+      stream.shutdown().await.ok();
+    }))
+  }
+}
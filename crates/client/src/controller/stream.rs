@@ -1,4 +1,4 @@
-use crate::controller::{ControllerClient, SendWs, UpdateMuteList};
+use crate::controller::{ControllerClient, LobbyChatReceived, SendWs, UpdateMuteList};
 use crate::error::*;
 use crate::game::local_game_from_game_info;
 //use crate::game::LocalGameInfo;
@@ -17,6 +17,7 @@ use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
 use tokio::time::sleep;
 use tracing_futures::Instrument;
 
@@ -27,6 +28,7 @@ pub struct ControllerStream {
   parent: Addr<ControllerClient>,
   frame_tx: Sender<Frame>,
   frame_rx: Option<Receiver<Frame>>,
+  close_tx: Option<oneshot::Sender<CloseRequest>>,
   current_game_info: Option<Arc<LocalGameInfo>>,
   platform: Addr<Platform>,
   nodes: Addr<NodeRegistry>,
@@ -49,6 +51,7 @@ impl ControllerStream {
       parent,
       frame_tx,
       frame_rx: Some(frame_rx),
+      close_tx: None,
       current_game_info: None,
       platform,
       nodes,
@@ -92,6 +95,7 @@ impl ControllerStream {
     domain: &str,
     token: String,
     mut frame_receiver: Receiver<Frame>,
+    mut close_receiver: oneshot::Receiver<CloseRequest>,
     owner: Addr<Self>,
     parent: Addr<ControllerClient>,
     nodes_reg: Addr<NodeRegistry>,
@@ -107,6 +111,7 @@ impl ControllerStream {
       .send(proto::PacketClientConnect {
         connect_version: Some(crate::version::FLO_VERSION.into()),
         token,
+        fingerprints: crate::fingerprint::get_fingerprints(),
       })
       .await?;
 
@@ -156,6 +161,15 @@ impl ControllerStream {
 
     loop {
       tokio::select! {
+        Ok(CloseRequest { frames, done }) = &mut close_receiver => {
+          tracing::debug!("exiting: closed");
+          if let Err(e) = stream.send_frames(frames).await {
+            tracing::debug!("send close frames: {}", e);
+          }
+          done.send(()).ok();
+          disconnect_handled = true;
+          break;
+        }
         next_send = frame_receiver.recv() => {
           if let Some(frame) = next_send {
             match stream.send_frame_timeout(frame).await {
@@ -302,6 +316,8 @@ impl ControllerStream {
               if let Some(slot) = info.slots.get_mut(p.slot_index as usize) {
                 slot.player = p.player.map(PlayerInfo::unpack).transpose()?;
                 slot.settings = SlotSettings::unpack(p.slot_settings.clone())?;
+                slot.locked = p.locked;
+                slot.reserved_player_id = p.reserved_player_id;
                 Ok(())
               } else {
                 tracing::error!("PacketGamePlayerEnter: invalid slot index: {}", p.slot_index);
@@ -356,6 +372,51 @@ impl ControllerStream {
             OutgoingMessage::GamePlayerPingMapSnapshot(p)
           ).notify(parent).await?;
         }
+        p: proto::PacketGameLobbyChat => {
+          parent.notify(LobbyChatReceived(p)).await?;
+        }
+        p: proto::PacketGameResult => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameResult(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketMapVetoStatus => {
+          SendWs::new(
+            id,
+            OutgoingMessage::MapVetoStatus(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameRehostReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameRehostReject(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameSlotManageReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameSlotManageReject(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketListLobbies => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ListLobbies(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameJoinReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameJoinReject(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketMatchmakingQueueStatus => {
+          SendWs::new(
+            id,
+            OutgoingMessage::MatchmakingQueueStatus(p)
+          ).notify(parent).await?;
+        }
         p: proto::PacketGameStartReject => {
           SendWs::new(
             id,
@@ -386,6 +447,7 @@ impl ControllerStream {
                 node_id: p.node_id,
                 game_info: info,
                 player_token: p.player_token,
+                traceparent: p.traceparent,
               }).wrap(id)).await?;
             } else {
               tracing::warn!("received player for game#{} but the active game id is {}", p.game_id, info.game_id);
@@ -439,6 +501,8 @@ impl Actor for ControllerStream {
       self.frame_tx = frame_tx;
       frame_rx
     };
+    let (close_tx, close_rx) = oneshot::channel();
+    self.close_tx.replace(close_tx);
 
     ctx.spawn({
       let id = self.id;
@@ -465,20 +529,31 @@ impl Actor for ControllerStream {
         let parent = self.parent.clone();
         let nodes = self.nodes.clone();
         async move {
-          if let Err(err) =
-            Self::connect_and_serve(id, &domain, token, frame_rx, owner, parent.clone(), nodes)
-              .await
+          if let Err(err) = Self::connect_and_serve(
+            id,
+            &domain,
+            token,
+            frame_rx,
+            close_rx,
+            owner,
+            parent.clone(),
+            nodes,
+          )
+          .await
           {
             tracing::error!("controller stream error: {}", err);
 
             SendWs::new(
               id,
-              OutgoingMessage::ConnectRejected(messages::ErrorMessage::new(match &err {
-                Error::ConnectionRequestRejected(reason) => {
-                  format!("server rejected: {:?}", reason)
-                }
-                other => other.to_string(),
-              })),
+              OutgoingMessage::ConnectRejected(
+                messages::ErrorMessage::new(match &err {
+                  Error::ConnectionRequestRejected(reason) => {
+                    format!("server rejected: {:?}", reason)
+                  }
+                  other => other.to_string(),
+                })
+                .with_code(err.code()),
+              ),
             )
             .notify(&parent)
             .await
@@ -687,6 +762,36 @@ impl Handler<SendFrame> for ControllerStream {
   }
 }
 
+struct CloseRequest {
+  frames: Vec<Frame>,
+  done: oneshot::Sender<()>,
+}
+
+/// Sends `frames` and closes the stream,
+/// the receiver resolves once the frames are flushed
+pub struct Close(pub Vec<Frame>);
+
+impl Message for Close {
+  type Result = Result<oneshot::Receiver<()>>;
+}
+
+#[async_trait]
+impl Handler<Close> for ControllerStream {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    Close(frames): Close,
+  ) -> <Close as Message>::Result {
+    let (done, rx) = oneshot::channel();
+    self
+      .close_tx
+      .take()
+      .and_then(|tx| tx.send(CloseRequest { frames, done }).ok())
+      .ok_or_else(|| Error::TaskCancelled(anyhow::format_err!("controller stream worker gone")))?;
+    Ok(rx)
+  }
+}
+
 #[derive(Debug)]
 pub struct ControllerEvent {
   pub id: u64,
@@ -730,4 +835,5 @@ pub struct GameReceivedEvent {
   pub node_id: i32,
   pub game_info: Arc<LocalGameInfo>,
   pub player_token: Vec<u8>,
+  pub traceparent: String,
 }
@@ -0,0 +1,294 @@
+//! Storage of the player's auth token and the node reconnect tokens.
+//!
+//! Credentials are kept in the OS keychain: the Windows Credential Manager (DPAPI),
+//! the macOS Keychain or the Secret Service on Linux.
+//! Without a keychain they are encrypted with ChaCha20-Poly1305 and written to
+//! `flo_credentials` next to `flo.toml`, readable only by the current user on Unix.
+//! The file key is derived from the install id and the machine name, which are not
+//! secret: the encryption keeps the tokens out of plain sight, but anyone who can
+//! read the user's files can decrypt them. Use the keychain where it's available.
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use parking_lot::Mutex;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::fingerprint;
+
+const SERVICE: &str = "flo";
+const CREDENTIALS_FILE: &str = "flo_credentials";
+const KEY_SALT: &[u8] = b"flo-credentials-v1";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Credential {
+  /// Token used to connect to the controller
+  AuthToken,
+  /// Token used to connect to the node of a game
+  ReconnectToken { game_id: i32 },
+}
+
+impl Credential {
+  fn name(&self) -> String {
+    match *self {
+      Credential::AuthToken => "auth_token".to_string(),
+      Credential::ReconnectToken { game_id } => format!("reconnect_token_{}", game_id),
+    }
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum CredentialError {
+  #[error("Keychain: {0}")]
+  Keychain(#[from] keyring::Error),
+  #[error("Credential file: {0}")]
+  Io(#[from] io::Error),
+  #[error("Credential file: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("Credential file corrupted or created on another machine")]
+  Decrypt,
+}
+
+pub type Result<T, E = CredentialError> = std::result::Result<T, E>;
+
+/// Credential storage backed by the OS keychain or an encrypted file.
+/// All methods block, call them with `spawn_blocking` from async code.
+#[derive(Clone)]
+pub struct CredentialStore {
+  backend: Arc<Backend>,
+}
+
+enum Backend {
+  Keychain,
+  File(FileStore),
+}
+
+impl CredentialStore {
+  /// Uses the OS keychain if it's available, the encrypted file otherwise
+  pub fn open() -> Self {
+    match probe_keychain() {
+      Ok(()) => Self {
+        backend: Arc::new(Backend::Keychain),
+      },
+      Err(err) => {
+        tracing::warn!(
+          "keychain unavailable, credentials are stored in a file that is not protected by the OS: {}",
+          err
+        );
+        Self::file(CREDENTIALS_FILE, &file_key())
+      }
+    }
+  }
+
+  fn file(path: impl Into<PathBuf>, key: &[u8; 32]) -> Self {
+    Self {
+      backend: Arc::new(Backend::File(FileStore {
+        path: path.into(),
+        cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        lock: Mutex::new(()),
+      })),
+    }
+  }
+
+  pub fn get(&self, credential: Credential) -> Result<Option<String>> {
+    match *self.backend {
+      Backend::Keychain => match keyring::Entry::new(SERVICE, &credential.name())?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(err.into()),
+      },
+      Backend::File(ref store) => store.get(&credential.name()),
+    }
+  }
+
+  pub fn set(&self, credential: Credential, value: &str) -> Result<()> {
+    match *self.backend {
+      Backend::Keychain => {
+        keyring::Entry::new(SERVICE, &credential.name())?.set_password(value)?;
+        Ok(())
+      }
+      Backend::File(ref store) => store.set(&credential.name(), Some(value)),
+    }
+  }
+
+  pub fn delete(&self, credential: Credential) -> Result<()> {
+    match *self.backend {
+      Backend::Keychain => {
+        match keyring::Entry::new(SERVICE, &credential.name())?.delete_password() {
+          Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+          Err(err) => Err(err.into()),
+        }
+      }
+      Backend::File(ref store) => store.set(&credential.name(), None),
+    }
+  }
+}
+
+fn probe_keychain() -> Result<()> {
+  match keyring::Entry::new(SERVICE, "probe")?.get_password() {
+    Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// Obfuscation key of the credential file, not a secret
+fn file_key() -> [u8; 32] {
+  let mut hasher = Sha256::new();
+  hasher.update(KEY_SALT);
+  hasher.update(fingerprint::get_install_id().unwrap_or_default().as_bytes());
+  hasher.update(&[0]);
+  hasher.update(
+    fingerprint::get_machine_name()
+      .unwrap_or_default()
+      .as_bytes(),
+  );
+  hasher.finalize().into()
+}
+
+/// Creates the file readable only by the current user on Unix
+fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create(true).truncate(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+  options.open(path)?.write_all(bytes)
+}
+
+/// JSON object of `name: base64(nonce + ciphertext)`,
+/// the name is authenticated so entries can't be swapped
+struct FileStore {
+  path: PathBuf,
+  cipher: ChaCha20Poly1305,
+  lock: Mutex<()>,
+}
+
+impl FileStore {
+  fn get(&self, name: &str) -> Result<Option<String>> {
+    let _guard = self.lock.lock();
+    match self.load()?.get(name) {
+      Some(value) => self.decrypt(name, value).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  fn set(&self, name: &str, value: Option<&str>) -> Result<()> {
+    let _guard = self.lock.lock();
+    let mut map = self.load()?;
+    match value {
+      Some(value) => {
+        map.insert(name.to_string(), self.encrypt(name, value));
+      }
+      None => {
+        if map.remove(name).is_none() {
+          return Ok(());
+        }
+      }
+    }
+    write_private(&self.path, &serde_json::to_vec(&map)?)?;
+    Ok(())
+  }
+
+  fn load(&self) -> Result<BTreeMap<String, String>> {
+    match fs::read(&self.path) {
+      Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  fn encrypt(&self, name: &str, value: &str) -> String {
+    let mut nonce = [0_u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = self
+      .cipher
+      .encrypt(
+        Nonce::from_slice(&nonce),
+        Payload {
+          msg: value.as_bytes(),
+          aad: name.as_bytes(),
+        },
+      )
+      .expect("plaintext size within limit");
+    base64::encode([&nonce[..], &ciphertext].concat())
+  }
+
+  fn decrypt(&self, name: &str, value: &str) -> Result<String> {
+    let bytes = base64::decode(value).map_err(|_| CredentialError::Decrypt)?;
+    if bytes.len() < NONCE_LEN {
+      return Err(CredentialError::Decrypt);
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = self
+      .cipher
+      .decrypt(
+        Nonce::from_slice(nonce),
+        Payload {
+          msg: ciphertext,
+          aad: name.as_bytes(),
+        },
+      )
+      .map_err(|_| CredentialError::Decrypt)?;
+    String::from_utf8(plaintext).map_err(|_| CredentialError::Decrypt)
+  }
+}
+
+#[test]
+fn test_file_store() {
+  let path = std::env::temp_dir().join(format!("flo_credentials_test_{}", std::process::id()));
+  let store = CredentialStore::file(&path, &[1; 32]);
+  let reconnect = Credential::ReconnectToken { game_id: 1 };
+
+  assert_eq!(store.get(Credential::AuthToken).unwrap(), None);
+  store.set(Credential::AuthToken, "auth-secret").unwrap();
+  store.set(reconnect, "reconnect").unwrap();
+  assert_eq!(
+    store.get(Credential::AuthToken).unwrap().as_deref(),
+    Some("auth-secret")
+  );
+  assert_eq!(store.get(reconnect).unwrap().as_deref(), Some("reconnect"));
+
+  // not stored in plaintext
+  assert!(!fs::read_to_string(&path).unwrap().contains("auth-secret"));
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    assert_eq!(
+      fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+      0o600
+    );
+  }
+
+  // swapped entries
+  let mut map: BTreeMap<String, String> =
+    serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+  let auth = map[&Credential::AuthToken.name()].clone();
+  map.insert(reconnect.name(), auth);
+  fs::write(&path, serde_json::to_vec(&map).unwrap()).unwrap();
+  assert!(matches!(
+    store.get(reconnect),
+    Err(CredentialError::Decrypt)
+  ));
+
+  // other key
+  let other = CredentialStore::file(&path, &[2; 32]);
+  assert!(matches!(
+    other.get(Credential::AuthToken),
+    Err(CredentialError::Decrypt)
+  ));
+
+  store.delete(Credential::AuthToken).unwrap();
+  store.delete(Credential::AuthToken).unwrap();
+  assert_eq!(store.get(Credential::AuthToken).unwrap(), None);
+
+  fs::remove_file(&path).ok();
+}
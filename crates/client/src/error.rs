@@ -1,5 +1,7 @@
 use crate::{ping::PingError, platform::PlatformStateError};
+use flo_net::error_code::ErrorCode;
 use flo_types::node::NodeGameStatus;
+use s2_grpc_utils::S2ProtoEnum;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,6 +22,8 @@ pub enum Error {
   GameVersionMismatch,
   #[error("FLO observer slot occupied")]
   FloObserverSlotOccupied,
+  #[error("Observers are disabled in this game")]
+  GameObserversDisabled,
   #[error("Unexpected w3gs packet: {0:?}")]
   UnexpectedW3GSPacket(flo_w3gs::packet::Packet),
   #[error("Slot not resolved")]
@@ -32,6 +36,8 @@ pub enum Error {
   InvalidMapInfo,
   #[error("Invalid observer data frame")]
   InvalidObserverDataFrame,
+  #[error("Credential store: {0}")]
+  Credential(#[from] crate::credential::CredentialError),
   #[error("Ping: {0}")]
   Ping(#[from] PingError),
   #[error("Warcraft III not located")]
@@ -85,6 +91,56 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+  /// Decides whether a failed node or controller request is retried
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      Error::NodeConnectionRejected(reason, _) => (*reason).into(),
+      Error::ConnectionRequestRejected(reason) => reason.into_proto_enum().into(),
+      Error::ObserverConnectionRequestRejected(reason) => (*reason).into(),
+      Error::Net(err) => err.code(),
+      Error::Timeout(_) => ErrorCode::Timeout,
+      Error::StreamClosed
+      | Error::ControllerDisconnected
+      | Error::Io(_)
+      | Error::Ping(_)
+      | Error::TaskCancelled(_)
+      | Error::EmbedMessageStreamBroken
+      | Error::W3GS(flo_w3gs::error::Error::StreamClosed)
+      | Error::W3GS(flo_w3gs::error::Error::Io(_)) => ErrorCode::Unavailable,
+      #[cfg(feature = "ws")]
+      Error::Websocket(_) => ErrorCode::Unavailable,
+      Error::InvalidNodeToken => ErrorCode::InvalidToken,
+      Error::LocalGameInfoNotFound => ErrorCode::GameNotReady,
+      Error::MapChecksumMismatch
+      | Error::GameVersionMismatch
+      | Error::InvalidNodeConfig
+      | Error::InvalidNodeAddr(_)
+      | Error::InvalidMapInfo => ErrorCode::InvalidArgument,
+      Error::UnexpectedNodeGameStatus(_)
+      | Error::NotInGame
+      | Error::SlotNotResolved
+      | Error::FloObserverSlotOccupied => ErrorCode::InvalidStatus,
+      Error::GameObserversDisabled => ErrorCode::PermissionDenied,
+      Error::War3NotLocated | Error::ReplayFolderNotFound => ErrorCode::NotFound,
+      Error::UnexpectedW3GSPacket(_)
+      | Error::InvalidObserverDataFrame
+      | Error::ObserverRecord(_)
+      | Error::PacketConversion(_)
+      | Error::W3GS(_) => ErrorCode::Protocol,
+      Error::Credential(_)
+      | Error::GetClientPlatformInfo(_)
+      | Error::Lan(_)
+      | Error::ObserverFs(_)
+      | Error::War3Map(_)
+      | Error::War3Data(_)
+      | Error::Platform(_)
+      | Error::TaskJoinError(_)
+      | Error::Json(_) => ErrorCode::Internal,
+    }
+  }
+}
+
 impl From<flo_state::error::Error> for Error {
   fn from(err: flo_state::error::Error) -> Self {
     match err {
@@ -0,0 +1,81 @@
+use lazy_static::lazy_static;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use flo_net::proto::flo_connect::{ClientFingerprint, ClientFingerprintKind};
+
+/// Stored next to `flo.toml`
+const INSTALL_ID_FILE: &str = "flo_install_id";
+const SALT: &[u8] = b"flo-fingerprint-v1";
+
+lazy_static! {
+  static ref INSTALL_ID: Option<String> = match get_or_create_install_id(Path::new(INSTALL_ID_FILE))
+  {
+    Ok(id) => Some(id),
+    Err(err) => {
+      tracing::warn!("install id: {}", err);
+      None
+    }
+  };
+  static ref FINGERPRINTS: Vec<ClientFingerprint> = collect();
+}
+
+/// Salted hashes of the install id and the machine name, sent to the controller at login
+pub fn get_fingerprints() -> Vec<ClientFingerprint> {
+  FINGERPRINTS.clone()
+}
+
+fn collect() -> Vec<ClientFingerprint> {
+  let mut fingerprints = vec![];
+  if let Some(id) = get_install_id() {
+    fingerprints.push(make_fingerprint(ClientFingerprintKind::Install, &id));
+  }
+  if let Some(name) = get_machine_name() {
+    fingerprints.push(make_fingerprint(ClientFingerprintKind::Machine, &name));
+  }
+  fingerprints
+}
+
+/// Random id generated on first run
+pub(crate) fn get_install_id() -> Option<String> {
+  INSTALL_ID.clone()
+}
+
+fn get_or_create_install_id(path: &Path) -> std::io::Result<String> {
+  if let Ok(id) = fs::read_to_string(path) {
+    let id = id.trim();
+    if !id.is_empty() {
+      return Ok(id.to_string());
+    }
+  }
+  let mut bytes = [0_u8; 16];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  let id = to_hex(&bytes);
+  fs::write(path, &id)?;
+  Ok(id)
+}
+
+pub(crate) fn get_machine_name() -> Option<String> {
+  std::env::var("COMPUTERNAME")
+    .or_else(|_| std::env::var("HOSTNAME"))
+    .ok()
+    .map(|v| v.trim().to_lowercase())
+    .filter(|v| !v.is_empty())
+}
+
+fn make_fingerprint(kind: ClientFingerprintKind, value: &str) -> ClientFingerprint {
+  let mut hasher = Sha256::new();
+  hasher.update(SALT);
+  hasher.update(&[kind as u8]);
+  hasher.update(value.as_bytes());
+  ClientFingerprint {
+    kind: kind.into(),
+    hash: to_hex(&hasher.finalize()),
+  }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -1,4 +1,5 @@
 use crate::error::{Error, Result};
+use flo_types::feature::FeatureFlags;
 use flo_types::game::{GameInfo, LocalGameInfo};
 
 pub fn local_game_from_game_info(player_id: i32, game: &GameInfo) -> Result<LocalGameInfo> {
@@ -26,6 +27,8 @@ pub fn local_game_from_game_info(player_id: i32, game: &GameInfo) -> Result<Loca
     slots: game.slots.clone(),
     host_player: game.created_by.clone(),
     mask_player_names: game.mask_player_names,
+    map_config: game.map_config.clone().unwrap_or_default(),
     map_twelve_p: game.map.twelve_p,
+    feature_flags: FeatureFlags::from_bits(game.feature_flags),
   })
 }
@@ -16,7 +16,6 @@ use flo_w3map::MapChecksum;
 use futures::TryStreamExt;
 use std::sync::Arc;
 use tokio::sync::mpsc::WeakSender;
-use tokio::sync::watch::channel;
 
 pub async fn run_test_lobby(
   game_version: String,
@@ -34,6 +33,7 @@ pub async fn run_test_lobby(
     id: 1,
     name: "Player 1".to_string(),
     source: PlayerSource::Test,
+    battletag: None,
   };
   let game = GameInfo {
     id: 0,
@@ -53,6 +53,8 @@ pub async fn run_test_lobby(
         ..Default::default()
       },
       client_status: SlotClientStatus::Pending,
+      locked: false,
+      reserved_player_id: None,
     }],
     node: None,
     is_private: false,
@@ -60,11 +62,19 @@ pub async fn run_test_lobby(
     random_seed: 0,
     created_by: None,
     mask_player_names: false,
+    map_config: None,
+    feature_flags: 0,
   };
 
   let info = LanGameInfo {
     game: Arc::new(local_game_from_game_info(1, &game)?),
-    slot_info: crate::lan::game::slot::build_player_slot_info(1, game.random_seed, &game.slots, map_twelve_p)?,
+    slot_info: crate::lan::game::slot::build_player_slot_info(
+      1,
+      game.random_seed,
+      &game.slots,
+      map_twelve_p,
+      &Default::default(),
+    )?,
     map_checksum,
     game_settings: GameSettings {
       game_setting_flags: GameSettingFlags::SPEED_FAST
@@ -84,7 +94,7 @@ pub async fn run_test_lobby(
     lan_game_name_override: Some(name.to_string()),
   };
 
-  let (_tx, mut rx) = channel(None);
+  let (_tx, mut rx) = crate::lan::game::status::channel();
 
   let mut listener = W3GSListener::bind().await.unwrap();
   tracing::debug!("listening on {}", listener.local_addr());
@@ -1,34 +1,43 @@
 use crate::controller::{ControllerClient, GetMuteList, MutePlayer, UnmutePlayer};
 use crate::error::*;
+use crate::lan::game::status::GameStatusReceiver;
 use crate::lan::game::{GameEndReason, LanGameInfo};
-use crate::node::stream::NodeStreamSender;
+use crate::node::stream::{GameProgress, NodeStreamSender};
 use crate::node::NodeInfo;
 use flo_net::w3gs::W3GSPacket;
 use flo_replay::generate_replay_from_packets;
 use flo_state::Addr;
+use flo_types::feature::FeatureFlag;
 use flo_types::node::NodeGameStatus;
 use flo_util::chat::{parse_chat_command, ChatCommand};
 #[cfg(feature = "blacklist")]
 use flo_w3c::blacklist;
 use flo_w3c::stats::get_stats;
 use flo_w3gs::chat::ChatFromHost;
+use flo_w3gs::inspect;
 use flo_w3gs::leave::LeaveReq;
 use flo_w3gs::net::W3GSStream;
 use flo_w3gs::packet::*;
-use flo_w3gs::protocol::action::{OutgoingAction, OutgoingKeepAlive};
+use flo_w3gs::protocol::action::{IncomingAction, OutgoingAction, OutgoingKeepAlive};
 use flo_w3gs::protocol::chat::{ChatMessage, ChatToHost};
 use flo_w3gs::protocol::constants::PacketTypeId;
 use flo_w3gs::protocol::leave::LeaveAck;
 use flo_w3gs::protocol::ping::PingFromHost;
+use futures::FutureExt;
 use parking_lot::Mutex;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
 use std::fs;
 use std::path::Path;
-use std::time::Duration;
-use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::watch::Receiver as WatchReceiver;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 use tokio::time::interval;
 
+/// Max game packets coalesced into a node frame
+const NODE_BATCH_MAX_PACKETS: usize = 32;
+/// Max chat responses waiting to be sent to the game
+const CHAT_OUTBOX_SIZE: usize = 64;
+
 #[derive(Debug)]
 pub enum GameResult {
   Disconnected,
@@ -40,16 +49,27 @@ pub struct GameHandler<'a> {
   node: &'a NodeInfo,
   w3gs_stream: &'a mut W3GSStream,
   node_stream: &'a mut NodeStreamSender,
-  status_rx: &'a mut WatchReceiver<Option<NodeGameStatus>>,
-  w3gs_tx: &'a mut Sender<Packet>,
+  status_rx: &'a mut GameStatusReceiver,
   w3gs_rx: &'a mut Receiver<Packet>,
   client: &'a mut Addr<ControllerClient>,
   muted_players: BTreeSet<u8>,
+  /// Players muted by the mute list, by player id
+  list_muted_players: BTreeSet<i32>,
+  mute_list_rx: watch::Receiver<Vec<i32>>,
   end_reason: &'a Mutex<Option<GameEndReason>>,
   saved_packets: Vec<Packet>,
   save_replay: bool,
   game_version_string: String,
   user_replay_path: String,
+  /// Packets to the node received in the current poll cycle
+  node_outbox: Vec<Packet>,
+  /// Chat responses to the game, sent by the main loop
+  chat_outbox: ChatOutbox,
+  /// Chat responses of spawned tasks, moved into `chat_outbox` by the main loop
+  chat_tx: Sender<(u8, Vec<String>)>,
+  chat_rx: Receiver<(u8, Vec<String>)>,
+  stall_timeout: Duration,
+  started_at: Instant,
 }
 
 impl<'a> GameHandler<'a> {
@@ -58,30 +78,39 @@ impl<'a> GameHandler<'a> {
     node: &'a NodeInfo,
     stream: &'a mut W3GSStream,
     node_stream: &'a mut NodeStreamSender,
-    status_rx: &'a mut WatchReceiver<Option<NodeGameStatus>>,
-    w3gs_tx: &'a mut Sender<Packet>,
+    status_rx: &'a mut GameStatusReceiver,
     w3gs_rx: &'a mut Receiver<Packet>,
     client: &'a mut Addr<ControllerClient>,
     end_reason: &'a Mutex<Option<GameEndReason>>,
     game_version_string: String,
     save_replay: bool,
     user_replay_path: String,
+    mute_list_rx: watch::Receiver<Vec<i32>>,
+    stall_timeout: Duration,
   ) -> Self {
+    let (chat_tx, chat_rx) = channel(CHAT_OUTBOX_SIZE);
     GameHandler {
       info,
       node,
       w3gs_stream: stream,
       node_stream,
       status_rx,
-      w3gs_tx,
       w3gs_rx,
       client,
       muted_players: BTreeSet::new(),
+      list_muted_players: BTreeSet::new(),
+      mute_list_rx,
       end_reason,
       saved_packets: vec![],
       save_replay,
       game_version_string,
       user_replay_path,
+      node_outbox: vec![],
+      chat_outbox: ChatOutbox::default(),
+      chat_tx,
+      chat_rx,
+      stall_timeout,
+      started_at: Instant::now(),
     }
   }
 
@@ -105,6 +134,7 @@ impl<'a> GameHandler<'a> {
         if mute_list.contains(&p.player_id) {
           muted_names.push(p.name.clone());
           self.muted_players.insert(p.slot_player_id);
+          self.list_muted_players.insert(p.player_id);
         }
         #[cfg(feature = "blacklist")]
         if let Some(r) = blacklist::read(&p.name).unwrap_or(None) {
@@ -139,49 +169,97 @@ impl<'a> GameHandler<'a> {
     let mut ping = interval(Duration::from_secs(15));
     let ping_packet = Packet::simple(PingFromHost::with_payload(0))?;
 
+    // runs until the game loop returns and drops `progress`
+    let (progress, mut watchdog) = flo_task::watchdog(self.stall_timeout, GameProgress::default());
+    tokio::spawn({
+      let mut node_stream = self.node_stream.clone();
+      async move {
+        while let Some(last) = watchdog.stalled().await {
+          tracing::error!(
+            ticks = last.ticks,
+            "game stalled for {:?}, last game packet: {:?}, last node packet: {:?}",
+            watchdog.timeout(),
+            last.last_game_packet,
+            last.last_node_packet
+          );
+          if let Err(err) = node_stream
+            .report_game_stalled(watchdog.timeout(), last)
+            .await
+          {
+            tracing::error!("report game stalled: {}", err);
+            break;
+          }
+        }
+      }
+    });
+
     loop {
+      self.flush_chat_outbox().await?;
+
       tokio::select! {
         _ = ping.tick() => {
           self.w3gs_stream.send(ping_packet.clone()).await?;
         }
         next = self.w3gs_stream.recv() => {
-          let pkt = match next {
-            Ok(pkt) => pkt,
-            Err(err) => {
-              tracing::error!("game connection: {}", err);
+          let mut next = next;
+          // coalesces packets already decoded in this poll cycle
+          loop {
+            let pkt = match next {
+              Ok(pkt) => pkt,
+              Err(err) => {
+                tracing::error!("game connection: {}", err);
+                self.flush_node_outbox().await?;
+                return Ok(GameResult::Disconnected)
+              },
+            };
+            if let Some(pkt) = pkt {
+              progress.feed(|p| p.last_game_packet = Some(pkt.type_id()));
+              if pkt.type_id() == LeaveAck::PACKET_TYPE_ID {
+                tracing::info!("game leave ack received");
+                self.flush_node_outbox().await?;
+                self.w3gs_stream.send(Packet::simple(LeaveAck)?).await?;
+                self.w3gs_stream.flush().await?;
+                return Ok(GameResult::Leave)
+              }
+
+              self.handle_game_packet(pkt).await?;
+            } else {
+              tracing::info!("game stream closed");
+              self.flush_node_outbox().await?;
               return Ok(GameResult::Disconnected)
-            },
-          };
-          if let Some(pkt) = pkt {
-            if pkt.type_id() == LeaveAck::PACKET_TYPE_ID {
-              tracing::info!("game leave ack received");
-              self.w3gs_stream.send(Packet::simple(LeaveAck)?).await?;
-              self.w3gs_stream.flush().await?;
-              return Ok(GameResult::Leave)
             }
 
-            self.handle_game_packet(pkt).await?;
-          } else {
-            tracing::info!("game stream closed");
-            return Ok(GameResult::Disconnected)
+            if self.node_outbox.len() >= NODE_BATCH_MAX_PACKETS {
+              break;
+            }
+            next = match self.w3gs_stream.recv().now_or_never() {
+              Some(next) => next,
+              None => break,
+            };
           }
+          self.flush_node_outbox().await?;
         }
-        changed = self.status_rx.changed() => {
-          let next =
-            if changed.is_ok() {
-              self.status_rx.borrow().clone()
-            } else {
-              return Err(Error::TaskCancelled(anyhow::format_err!("game status tx dropped")))
-            };
-          match next {
-            Some(status) => {
-              self.handle_game_status_change(status).await?;
-            },
-            None => {},
+        status = self.status_rx.changed() => {
+          self.handle_game_status_change(status?).await?;
+        }
+        Some((player_id, messages)) = self.chat_rx.recv() => {
+          self.send_chats_to_self(player_id, messages);
+        }
+        res = self.mute_list_rx.changed() => {
+          if res.is_err() {
+            return Err(Error::TaskCancelled(anyhow::format_err!("mute list tx dropped")))
           }
+          let mute_list = self.mute_list_rx.borrow_and_update().clone();
+          self.reconcile_mute_list(mute_list);
         }
         next = self.w3gs_rx.recv() => {
           if let Some(pkt) = next {
+            progress.feed(|p| {
+              if pkt.type_id() == IncomingAction::PACKET_TYPE_ID {
+                p.ticks += 1;
+              }
+              p.last_node_packet = Some(pkt.type_id());
+            });
             self.handle_incoming_w3gs(pkt).await?;
           } else {
             return Err(Error::TaskCancelled(anyhow::format_err!("W3GS tx dropped")))
@@ -191,13 +269,14 @@ impl<'a> GameHandler<'a> {
     }
   }
 
-  pub fn start_save_replay(&self) {
+  /// Writes the replay in the background, the returned task completes once it's saved
+  pub fn start_save_replay(&self) -> Option<tokio::task::JoinHandle<Result<()>>> {
     if self.save_replay {
       let game_info =
         flo_types::observer::GameInfo::from((&*self.info.game, self.game_version_string.clone()));
       let packet_copy = self.saved_packets.clone();
       let mut user_replay_path = self.user_replay_path.clone();
-      tokio::task::spawn(async move {
+      Some(tokio::task::spawn(async move {
         let now = chrono::Utc::now();
         let now_timestamp_str = format!("w3c-{}.w3g", now.format("%Y%m%d%H%M%S"));
         user_replay_path.push_str(&now_timestamp_str);
@@ -236,7 +315,9 @@ impl<'a> GameHandler<'a> {
           };
         }
         Ok::<(), Error>(())
-      });
+      }))
+    } else {
+      None
     }
   }
 
@@ -247,22 +328,16 @@ impl<'a> GameHandler<'a> {
       OutgoingAction::PACKET_TYPE_ID => {}
       ChatFromHost::PACKET_TYPE_ID => {
         if !self.muted_players.is_empty() {
-          let pkt: ChatFromHost = pkt.decode_simple()?;
-          if let ChatToHost {
-            message: ChatMessage::Scoped { .. },
-            ..
-          } = pkt.0
-          {
-            if self.muted_players.contains(&pkt.from_player()) {
-              return Ok(());
-            }
+          let header = ChatFromHost::peek_header(&pkt.payload)?;
+          if header.scope.is_some() && self.muted_players.contains(&header.from_player) {
+            return Ok(());
           }
         }
       }
       _other => {}
     }
 
-    // tracing::debug!("send: {:?}", pkt.type_id());
+    tracing::trace!(target: inspect::TRACE_TARGET, "send: {}", inspect::inspect(&pkt));
     if self.save_replay {
       self.saved_packets.push(pkt.clone())
     }
@@ -277,6 +352,7 @@ impl<'a> GameHandler<'a> {
   }
 
   async fn handle_game_packet(&mut self, pkt: Packet) -> Result<()> {
+    tracing::trace!(target: inspect::TRACE_TARGET, "recv: {}", inspect::inspect(&pkt));
     match pkt.type_id() {
       PacketTypeId::PongToHost => return Ok(()),
       ChatToHost::PACKET_TYPE_ID => {
@@ -312,6 +388,7 @@ impl<'a> GameHandler<'a> {
           .lock()
           .replace(GameEndReason::LeaveReq(payload.reason()));
 
+        self.flush_node_outbox().await?;
         if let Err(err) = self.node_stream.send_w3gs(pkt).await {
           tracing::error!("report request to leave: {}", err);
         }
@@ -324,17 +401,49 @@ impl<'a> GameHandler<'a> {
       }
     }
 
-    self.node_stream.send_w3gs(pkt).await?;
+    self.node_outbox.push(pkt);
 
     Ok(())
   }
 
+  async fn flush_node_outbox(&mut self) -> Result<()> {
+    match self.node_outbox.len() {
+      0 => {}
+      1 => {
+        if let Some(pkt) = self.node_outbox.pop() {
+          self.node_stream.send_w3gs(pkt).await?;
+        }
+      }
+      _ => {
+        let pkts = std::mem::replace(&mut self.node_outbox, vec![]);
+        if self
+          .info
+          .game
+          .feature_flags
+          .contains(FeatureFlag::NoW3GSBatch)
+        {
+          for pkt in pkts {
+            self.node_stream.send_w3gs(pkt).await?;
+          }
+        } else {
+          self.node_stream.send_w3gs_batch(pkts).await?;
+        }
+      }
+    }
+    Ok(())
+  }
+
   fn handle_chat_command(&mut self, cmd: ChatCommand) -> bool {
     let is_ffa = self.info.game.mask_player_names;
+    let experimental_commands = self
+      .info
+      .game
+      .feature_flags
+      .contains(FeatureFlag::ChatCommands);
 
     match cmd.raw() {
       "flo" => {
-        let messages = vec![
+        let mut messages = vec![
           "-game: print game information.".to_string(),
           "-muteall: Mute all players.".to_string(),
           "-muteopps: Mute all opponents.".to_string(),
@@ -347,6 +456,9 @@ impl<'a> GameHandler<'a> {
           "-stats: Print opponent/opponents statistics.".to_string(),
           "-stats <ID>: Print player statistics, or display a player list.".to_string(),
         ];
+        if experimental_commands {
+          messages.push("-time: Print the time since the game started.".to_string());
+        }
         self.send_chats_to_self(self.info.slot_info.my_slot_player_id, messages)
       }
       "game" => {
@@ -366,9 +478,13 @@ impl<'a> GameHandler<'a> {
 
           for slot in &self.info.game.slots {
             if let Some(ref player) = slot.player.as_ref() {
+              let name = match player.battletag {
+                Some(ref battletag) => format!("{} ({})", player.name, battletag),
+                None => player.name.clone(),
+              };
               messages.push(format!(
                 "  {}: Team {}, {:?}",
-                player.name, slot.settings.team, slot.settings.race
+                name, slot.settings.team, slot.settings.race
               ));
             }
           }
@@ -802,6 +918,18 @@ impl<'a> GameHandler<'a> {
           }
         }
       }
+      "time" if experimental_commands => {
+        let secs = self.started_at.elapsed().as_secs();
+        self.send_chats_to_self(
+          self.info.slot_info.my_slot_player_id,
+          vec![format!(
+            "Game time: {:02}:{:02}:{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+          )],
+        );
+      }
       cmd if cmd.starts_with("rtt") && is_ffa => {
         self.send_chats_to_self(
           self.info.slot_info.my_slot_player_id,
@@ -817,25 +945,74 @@ impl<'a> GameHandler<'a> {
   }
 
   fn send_stats_to_self(&self, player_id: u8, targets: Vec<(String, u32)>, solo: bool) {
-    let mut tx = self.w3gs_tx.clone();
+    let chat_tx = self.chat_tx.clone();
     tokio::spawn(async move {
       for (name, race) in targets {
         if let Ok(Ok(target_stats_results)) =
           tokio::task::spawn_blocking(move || get_stats(name.as_str(), race, solo)).await
         {
-          send_chats_to_self(&mut tx, player_id, vec![target_stats_results]).await
+          chat_tx
+            .send((player_id, vec![target_stats_results]))
+            .await
+            .ok();
         }
       }
     });
   }
 
-  fn send_chats_to_self(&self, player_id: u8, messages: Vec<String>) {
-    let mut tx = self.w3gs_tx.clone();
-    tokio::spawn(async move { send_chats_to_self(&mut tx, player_id, messages).await });
+  fn send_chats_to_self(&mut self, player_id: u8, messages: Vec<String>) {
+    self.chat_outbox.push_chats_to_self(player_id, messages);
+  }
+
+  async fn flush_chat_outbox(&mut self) -> Result<()> {
+    while let Some(pkt) = self.chat_outbox.pop() {
+      self.handle_incoming_w3gs(pkt).await?;
+    }
+    Ok(())
+  }
+
+  /// Applies a mute list received after the game started, e.g. after reconnecting to the controller.
+  /// Slot ids are resolved again from the player ids, players muted in game are kept.
+  fn reconcile_mute_list(&mut self, mute_list: Vec<i32>) {
+    if self.info.game.mask_player_names {
+      return;
+    }
+
+    let mute_list: BTreeSet<i32> = mute_list.into_iter().collect();
+    let mut muted = vec![];
+    let mut unmuted = vec![];
+    for p in &self.info.slot_info.player_infos {
+      if p.slot_player_id == self.info.slot_info.my_slot_player_id {
+        continue;
+      }
+      let listed = mute_list.contains(&p.player_id);
+      if listed == self.list_muted_players.contains(&p.player_id) {
+        continue;
+      }
+      if listed {
+        if self.muted_players.insert(p.slot_player_id) {
+          muted.push(p.name.clone());
+        }
+      } else if self.muted_players.remove(&p.slot_player_id) {
+        unmuted.push(p.name.clone());
+      }
+    }
+    self.list_muted_players = mute_list;
+
+    let mut messages = vec![];
+    if !muted.is_empty() {
+      messages.push(format!("Auto muted: {}", muted.join(", ")));
+    }
+    if !unmuted.is_empty() {
+      messages.push(format!("Auto un-muted: {}", unmuted.join(", ")));
+    }
+    if !messages.is_empty() {
+      self.send_chats_to_self(self.info.slot_info.my_slot_player_id, messages);
+    }
   }
 
   fn save_mute(&self, player_id: i32, name: String, muted: bool) {
-    let mut tx = self.w3gs_tx.clone();
+    let chat_tx = self.chat_tx.clone();
     let client = self.client.clone();
     let my_slot_player_id = self.info.slot_info.my_slot_player_id;
     tokio::spawn(async move {
@@ -846,35 +1023,59 @@ impl<'a> GameHandler<'a> {
         client.send(UnmutePlayer { player_id }).await
       }
       .map_err(Error::from);
-      if let Err(err) = send.and_then(std::convert::identity) {
+      let message = if let Err(err) = send.and_then(std::convert::identity) {
         tracing::error!("save mute failed: {}", err);
-        send_chats_to_self(
-          &mut tx,
-          my_slot_player_id,
-          vec![format!("{} temporary: {}", action, name)],
-        )
-        .await;
+        format!("{} temporary: {}", action, name)
       } else {
-        send_chats_to_self(
-          &mut tx,
-          my_slot_player_id,
-          vec![format!("{} forever: {}", action, name)],
-        )
-        .await;
-      }
+        format!("{} forever: {}", action, name)
+      };
+      chat_tx.send((my_slot_player_id, vec![message])).await.ok();
     });
   }
 }
 
-async fn send_chats_to_self(tx: &mut Sender<Packet>, player_id: u8, messages: Vec<String>) {
-  for message in messages {
-    match Packet::simple(ChatFromHost::private_to_self(player_id, message)) {
-      Ok(pkt) => {
-        tx.send(pkt).await.ok();
+/// Chat responses to the game, messages beyond `CHAT_OUTBOX_SIZE` are dropped
+#[derive(Default)]
+struct ChatOutbox(VecDeque<Packet>);
+
+impl ChatOutbox {
+  fn push_chats_to_self(&mut self, player_id: u8, messages: Vec<String>) {
+    for message in messages {
+      if self.0.len() >= CHAT_OUTBOX_SIZE {
+        tracing::warn!("chat outbox full, message dropped");
+        break;
       }
-      Err(err) => {
-        tracing::error!("encode chat packet: {}", err);
+      match Packet::simple(ChatFromHost::private_to_self(player_id, message)) {
+        Ok(pkt) => self.0.push_back(pkt),
+        Err(err) => {
+          tracing::error!("encode chat packet: {}", err);
+        }
       }
     }
   }
+
+  fn pop(&mut self) -> Option<Packet> {
+    self.0.pop_front()
+  }
+}
+
+#[test]
+fn test_chat_outbox() {
+  let mut outbox = ChatOutbox::default();
+  outbox.push_chats_to_self(1, vec!["a".to_string(), "b".to_string()]);
+  outbox.push_chats_to_self(1, (0..CHAT_OUTBOX_SIZE).map(|i| i.to_string()).collect());
+
+  let mut messages = vec![];
+  while let Some(pkt) = outbox.pop() {
+    let chat: ChatFromHost = pkt.decode_simple().unwrap();
+    assert_eq!(chat.0.to_players, vec![1]);
+    match chat.0.message {
+      ChatMessage::Scoped { message, .. } => messages.push(message.to_string_lossy().to_string()),
+      other => panic!("unexpected message: {:?}", other),
+    }
+  }
+
+  let mut expected = vec!["a".to_string(), "b".to_string()];
+  expected.extend((0..CHAT_OUTBOX_SIZE - 2).map(|i| i.to_string()));
+  assert_eq!(messages, expected);
 }
@@ -1,12 +1,14 @@
 use std::net::SocketAddr;
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc::WeakSender;
-use tokio::sync::watch::Receiver;
+use tokio::sync::mpsc::{Receiver, WeakSender};
 use tokio::time::{interval_at, sleep};
 
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::PacketGameLobbyChatRequest;
+use flo_state::Addr;
 use flo_util::binary::SockAddr;
 use flo_w3gs::net::W3GSStream;
-use flo_w3gs::protocol::chat::{ChatFromHost, ChatToHost};
+use flo_w3gs::protocol::chat::{ChatFromHost, ChatMessage, ChatToHost};
 use flo_w3gs::protocol::game::{CountDownEnd, CountDownStart};
 use flo_w3gs::protocol::join::{ReqJoin, SlotInfoJoin};
 use flo_w3gs::protocol::leave::{LeaveAck, LeaveReq};
@@ -15,8 +17,10 @@ use flo_w3gs::protocol::packet::*;
 use flo_w3gs::protocol::ping::{PingFromHost, PongToHost};
 use flo_w3gs::protocol::player::{PlayerInfo, PlayerProfileMessage, PlayerSkinsMessage};
 
+use crate::controller::{ControllerClient, SendFrame};
 use crate::error::*;
 use crate::lan::game::slot::index_to_player_id;
+use crate::lan::game::status::GameStatusReceiver;
 use crate::lan::game::LanGameInfo;
 use crate::lan::get_lan_game_name;
 use crate::messages::{LanGameJoined, OutgoingMessage};
@@ -32,14 +36,22 @@ pub enum LobbyAction {
   Leave,
 }
 
+#[derive(Debug)]
+pub struct LobbyChatMessage {
+  pub player_id: i32,
+  pub message: String,
+}
+
 #[derive(Debug)]
 pub struct LobbyHandler<'a> {
   info: &'a LanGameInfo,
   stream: &'a mut W3GSStream,
   node_stream: Option<&'a mut NodeStreamSender>,
-  status_rx: &'a mut Receiver<Option<NodeGameStatus>>,
+  status_rx: &'a mut GameStatusReceiver,
   starting: bool,
   weak_outgoing_tx: Option<WeakSender<OutgoingMessage>>,
+  chat_rx: Option<&'a mut Receiver<LobbyChatMessage>>,
+  client: Option<Addr<ControllerClient>>,
 }
 
 impl<'a> LobbyHandler<'a> {
@@ -47,7 +59,7 @@ impl<'a> LobbyHandler<'a> {
     info: &'a LanGameInfo,
     stream: &'a mut W3GSStream,
     node_stream: Option<&'a mut NodeStreamSender>,
-    status_rx: &'a mut Receiver<Option<NodeGameStatus>>,
+    status_rx: &'a mut GameStatusReceiver,
     weak_outgoing_tx: Option<WeakSender<OutgoingMessage>>,
   ) -> Self {
     LobbyHandler {
@@ -57,11 +69,24 @@ impl<'a> LobbyHandler<'a> {
       status_rx,
       starting: false,
       weak_outgoing_tx,
+      chat_rx: None,
+      client: None,
     }
   }
 
+  /// Relays lobby chat between the local game and the controller
+  pub fn with_chat(
+    mut self,
+    chat_rx: &'a mut Receiver<LobbyChatMessage>,
+    client: Addr<ControllerClient>,
+  ) -> Self {
+    self.chat_rx = Some(chat_rx);
+    self.client = Some(client);
+    self
+  }
+
   pub async fn run(&mut self) -> Result<LobbyAction> {
-    let initial_game_state = { self.status_rx.borrow().clone() };
+    let initial_game_state = self.status_rx.current().node_status;
     let mut join_state = JoinPacketRecvState::new(initial_game_state, {
       self.info.slot_info.player_infos.len()
         + if self.info.slot_info.stream_ob_slot.is_some() {
@@ -118,24 +143,14 @@ impl<'a> LobbyHandler<'a> {
         _ = ping_interval.tick() => {
           self.stream.send(Packet::simple(PingFromHost::with_payload_since(base_t))?).await?;
         }
-        ch = self.status_rx.changed() => {
-          match ch {
-            Ok(_) => {
-              let next = self.status_rx.borrow().clone();
-              match next {
-                Some(status) => {
-                  join_state.status = Some(status);
-                  if join_state.should_start() {
-                    self.send_start().await?;
-                    return Ok(LobbyAction::Start)
-                  }
-                },
-                None => {},
-              }
-            },
-            Err(_why) => {
-              return Err(Error::TaskCancelled(anyhow::format_err!("game status tx dropped")))
-            }
+        Some(msg) = recv_chat(&mut self.chat_rx) => {
+          self.show_chat(msg).await?;
+        }
+        status = self.status_rx.changed() => {
+          join_state.status = Some(status?);
+          if join_state.should_start() {
+            self.send_start().await?;
+            return Ok(LobbyAction::Start)
           }
         }
       }
@@ -154,6 +169,31 @@ impl<'a> LobbyHandler<'a> {
     Ok(())
   }
 
+  async fn show_chat(&mut self, msg: LobbyChatMessage) -> Result<()> {
+    let slot_info = &self.info.slot_info;
+    let from = slot_info
+      .player_infos
+      .iter()
+      .find(|info| info.player_id == msg.player_id)
+      .map(|info| info.slot_player_id);
+    if let Some(from) = from {
+      self
+        .stream
+        .send(Packet::simple(ChatFromHost::lobby(
+          from,
+          &[slot_info.my_slot_player_id],
+          msg.message.as_str(),
+        ))?)
+        .await?;
+    } else {
+      tracing::debug!(
+        player_id = msg.player_id,
+        "lobby chat: sender slot not found"
+      );
+    }
+    Ok(())
+  }
+
   async fn handle_packet(
     &mut self,
     state: &mut JoinPacketRecvState,
@@ -268,14 +308,43 @@ impl<'a> LobbyHandler<'a> {
         tracing::debug!("<- map size: {:?}", payload);
       }
       ChatToHost::PACKET_TYPE_ID => {
-        self
-          .stream
-          .send(Packet::simple(ChatFromHost::lobby(
-            slot_info.my_slot_player_id,
-            &[slot_info.my_slot_player_id],
-            "Setting changes and chat are disabled.",
-          ))?)
-          .await?;
+        let payload: ChatToHost = pkt.decode_simple()?;
+        match (payload.message, self.client.as_ref()) {
+          (ChatMessage::Chat(message), Some(client)) => {
+            // the message is shown after the controller broadcasts it back
+            let frame = PacketGameLobbyChatRequest {
+              game_id: self.info.game.game_id,
+              message: message.to_string_lossy().into_owned(),
+            }
+            .encode_as_frame()?;
+            // a lost chat message must not end the lobby
+            match client.send(SendFrame(frame)).await {
+              Ok(Ok(())) => {}
+              Ok(Err(err)) => tracing::warn!("send lobby chat: {}", err),
+              Err(err) => tracing::warn!("send lobby chat: {}", err),
+            }
+          }
+          (ChatMessage::Chat(_), None) => {
+            self
+              .stream
+              .send(Packet::simple(ChatFromHost::lobby(
+                slot_info.my_slot_player_id,
+                &[slot_info.my_slot_player_id],
+                "Setting changes and chat are disabled.",
+              ))?)
+              .await?;
+          }
+          _ => {
+            self
+              .stream
+              .send(Packet::simple(ChatFromHost::lobby(
+                slot_info.my_slot_player_id,
+                &[slot_info.my_slot_player_id],
+                "Setting changes are disabled.",
+              ))?)
+              .await?;
+          }
+        }
       }
       PongToHost::PACKET_TYPE_ID => {
         let payload: PongToHost = pkt.decode_simple()?;
@@ -332,6 +401,13 @@ impl<'a> LobbyHandler<'a> {
   }
 }
 
+async fn recv_chat(rx: &mut Option<&mut Receiver<LobbyChatMessage>>) -> Option<LobbyChatMessage> {
+  match rx.as_mut() {
+    Some(rx) => rx.recv().await,
+    None => futures::future::pending().await,
+  }
+}
+
 #[derive(Debug)]
 struct JoinPacketRecvState {
   total_players: usize,
@@ -1,9 +1,10 @@
 mod game;
 mod lobby;
 mod proxy;
+pub mod status;
 pub mod slot;
 
-pub use self::lobby::{LobbyAction, LobbyHandler};
+pub use self::lobby::{LobbyAction, LobbyChatMessage, LobbyHandler};
 pub use self::proxy::GameEndReason;
 use crate::controller::ControllerClient;
 use crate::error::*;
@@ -12,6 +13,7 @@ use crate::lan::game::slot::LanSlotInfo;
 use crate::lan::get_lan_game_name;
 use crate::node::stream::NodeConnectToken;
 use crate::node::NodeInfo;
+use flo_config::NodeStreamConfig;
 use flo_lan::{GameInfo, MdnsPublisher};
 use flo_state::Addr;
 use flo_task::SpawnScope;
@@ -20,6 +22,7 @@ use flo_types::game::LocalGameInfo;
 use flo_w3gs::protocol::game::GameSettings;
 use flo_w3map::MapChecksum;
 use proxy::LanProxy;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Notify;
@@ -31,6 +34,11 @@ pub struct LanGame {
   state: Arc<State>,
   proxy: LanProxy,
   mdns_shutdown_notify: Arc<Notify>,
+  game: Arc<LocalGameInfo>,
+  map_checksum: MapChecksum,
+  game_settings: GameSettings,
+  /// Last known client status of each player
+  player_statuses: HashMap<i32, SlotClientStatus>,
 }
 
 #[derive(Debug)]
@@ -48,11 +56,13 @@ impl LanGame {
     my_player_id: i32,
     node: Arc<NodeInfo>,
     player_token: Vec<u8>,
+    traceparent: String,
     game: Arc<LocalGameInfo>,
     map_checksum: MapChecksum,
     client: Addr<ControllerClient>,
     save_replay: bool,
     user_replay_path: String,
+    node_stream_config: NodeStreamConfig,
   ) -> Result<Self> {
     let mdns_shutdown_notify = Arc::new(Notify::new());
 
@@ -65,27 +75,39 @@ impl LanGame {
       game.map_sha1,
       map_checksum.xoro,
     )?;
+    game_info.data.settings.game_setting_flags = game
+      .map_config
+      .apply_game_setting_flags(game_info.data.settings.game_setting_flags);
     let token = NodeConnectToken::from_vec(player_token).ok_or_else(|| Error::InvalidNodeToken)?;
 
+    let player_statuses = game
+      .slots
+      .iter()
+      .filter_map(|slot| slot.player.as_ref().map(|p| (p.id, slot.client_status)))
+      .collect();
+
     let proxy = LanProxy::start(
       LanGameInfo {
         slot_info: crate::lan::game::slot::build_player_slot_info(
           my_player_id,
           game.random_seed,
           &game.slots,
-          game.map_twelve_p
+          game.map_twelve_p,
+          &game.map_config,
         )?,
-        game,
-        map_checksum,
+        game: game.clone(),
+        map_checksum: map_checksum.clone(),
         game_settings: game_info.data.settings.clone(),
         lan_game_name_override: None,
       },
       node,
       token,
+      traceparent,
       client.clone(),
       game_version.clone(),
       save_replay,
       user_replay_path,
+      node_stream_config,
     )
     .await?;
     game_info.set_port(proxy.port());
@@ -94,6 +116,7 @@ impl LanGame {
       game_id,
       my_player_id,
     });
+    let game_settings = game_info.data.settings.clone();
     tokio::spawn(
       {
         let mut scope = scope.handle();
@@ -119,6 +142,10 @@ impl LanGame {
       proxy,
       state,
       mdns_shutdown_notify,
+      game,
+      map_checksum,
+      game_settings,
+      player_statuses,
     })
   }
 
@@ -139,22 +166,72 @@ impl LanGame {
     self.proxy.dispatch_game_status_change(status).await;
   }
 
-  pub async fn update_player_status(&mut self, player_id: i32, status: SlotClientStatus) {
+  /// Returns `false` if the status didn't change
+  pub async fn update_player_status(&mut self, player_id: i32, status: SlotClientStatus) -> bool {
+    if self.player_statuses.insert(player_id, status) == Some(status) {
+      return false;
+    }
     self
       .proxy
       .dispatch_player_event(PlayerEvent::PlayerStatusChange { player_id, status })
       .await;
+    true
+  }
+
+  /// Replaces the mute list of the game in progress
+  pub fn update_mute_list(&self, mute_list: Vec<i32>) {
+    self.proxy.update_mute_list(mute_list)
+  }
+
+  /// Indexes of the slots that differ from `game`
+  pub fn diff_slots(&self, game: &LocalGameInfo) -> Vec<usize> {
+    slot::diff_slots(&self.game.slots, &game.slots)
+  }
+
+  /// Replaces the slots of a game that hasn't started loading,
+  /// LAN clients joining the lobby afterwards get the new layout
+  pub fn update_game(&mut self, game: Arc<LocalGameInfo>) -> Result<()> {
+    let slot_info = slot::build_player_slot_info(
+      self.state.my_player_id,
+      game.random_seed,
+      &game.slots,
+      game.map_twelve_p,
+      &game.map_config,
+    )?;
+    self.proxy.update_info(LanGameInfo {
+      game: game.clone(),
+      slot_info,
+      map_checksum: self.map_checksum.clone(),
+      game_settings: self.game_settings.clone(),
+      lan_game_name_override: None,
+    });
+    self.game = game;
+    Ok(())
+  }
+
+  /// The node started loading the game, the slots can't change anymore
+  pub fn is_started(&self) -> bool {
+    self.proxy.is_started()
+  }
+
+  pub fn dispatch_lobby_chat(&self, msg: LobbyChatMessage) {
+    self.proxy.dispatch_lobby_chat(msg);
   }
 
   pub fn is_same_game(&self, game_id: i32, my_player_id: i32) -> bool {
     self.state.game_id == game_id && self.state.my_player_id == my_player_id
   }
 
+  /// Stops the LAN advertisement, then leaves the game on the node
+  /// and waits for the proxy to exit
+  pub async fn close(self) {
+    self.mdns_shutdown_notify.notify_one();
+    self.proxy.shutdown().await;
+  }
+
   pub fn shutdown(self) {
     tokio::spawn(async move {
-      if let Err(_) =
-        tokio::time::timeout(std::time::Duration::from_secs(10), self.proxy.shutdown()).await
-      {
+      if let Err(_) = tokio::time::timeout(std::time::Duration::from_secs(10), self.close()).await {
         tracing::error!("shutdown last lan game timeout.");
       }
     });
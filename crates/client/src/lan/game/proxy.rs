@@ -1,13 +1,15 @@
 use crate::controller::{ControllerClient, GetWeakOutgoingMessageSender};
 use crate::error::*;
 use crate::lan::game::game::GameHandler;
-use crate::lan::game::lobby::{LobbyAction, LobbyHandler};
+use crate::lan::game::lobby::{LobbyAction, LobbyChatMessage, LobbyHandler};
 use crate::lan::game::slot::index_to_player_id;
+use crate::lan::game::status::{self, GameStatusReceiver, GameStatusSender};
 use crate::lan::game::LanGameInfo;
 use crate::lan::LanEvent;
 use crate::messages::OutgoingMessage;
 use crate::node::stream::{NodeConnectToken, NodeStream, NodeStreamSender};
 use crate::node::NodeInfo;
+use flo_config::NodeStreamConfig;
 use flo_state::Addr;
 use flo_task::{SpawnScope, SpawnScopeHandle};
 use flo_types::node::{NodeGameStatus, SlotClientStatus};
@@ -25,6 +27,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{channel, Receiver, Sender, WeakSender};
 use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
 use tokio::time::interval;
 use tokio_stream::StreamExt;
 use tracing_futures::Instrument;
@@ -38,11 +41,15 @@ pub enum GameEndReason {
 }
 
 pub struct LanProxy {
-  _scope: SpawnScope,
+  scope: SpawnScope,
   node_stream: NodeStream,
   port: u16,
-  status_tx: watch::Sender<Option<NodeGameStatus>>,
+  status_tx: GameStatusSender,
   event_tx: Sender<PlayerEvent>,
+  chat_tx: Sender<LobbyChatMessage>,
+  mute_list_tx: watch::Sender<Vec<i32>>,
+  info_tx: watch::Sender<Arc<LanGameInfo>>,
+  worker: JoinHandle<()>,
 }
 
 impl LanProxy {
@@ -50,16 +57,20 @@ impl LanProxy {
     info: LanGameInfo,
     node: Arc<NodeInfo>,
     token: NodeConnectToken,
+    traceparent: String,
     client: Addr<ControllerClient>,
     game_version_string: String,
     save_replay: bool,
     user_replay_path: String,
+    node_stream_config: NodeStreamConfig,
   ) -> Result<Self> {
     let scope = SpawnScope::new();
     let listener = W3GSListener::bind().await?;
     let port = listener.port();
-    let (status_tx, status_rx) = watch::channel(None);
+    let (status_tx, status_rx) = status::channel();
     let (event_tx, event_rx) = channel(10);
+    let (chat_tx, chat_rx) = channel(10);
+    let (mute_list_tx, mute_list_rx) = watch::channel(vec![]);
     let (w3gs_tx, w3gs_rx) = channel(32);
     let game_id = info.game.game_id;
 
@@ -71,21 +82,26 @@ impl LanProxy {
       &info,
       node.client_socket_addr(),
       token,
+      traceparent,
       client.clone(),
       w3gs_tx.clone(),
       end_reason.clone(),
+      &node_stream_config,
     )
     .await?;
 
     tracing::debug!("listening on port {}", port);
 
+    let (info_tx, info_rx) = watch::channel(Arc::new(info));
     let state = Arc::new(State {
-      info,
+      info_rx,
       stream: node_stream.sender(),
       game_status_rx: status_rx,
+      mute_list_rx,
+      game_stall_timeout: node_stream_config.game_stall_timeout(),
     });
 
-    tokio::spawn({
+    let worker = tokio::spawn({
       let state = state.clone();
       let scope = scope.handle();
       let node = node.clone();
@@ -95,6 +111,7 @@ impl LanProxy {
           .serve(
             listener,
             event_rx,
+            chat_rx,
             w3gs_tx,
             w3gs_rx,
             end_reason,
@@ -121,36 +138,70 @@ impl LanProxy {
     });
 
     Ok(LanProxy {
-      _scope: scope,
+      scope,
       node_stream,
       port,
       status_tx,
       event_tx,
+      chat_tx,
+      mute_list_tx,
+      info_tx,
+      worker,
     })
   }
 
   pub async fn dispatch_game_status_change(&self, status: NodeGameStatus) {
-    self.status_tx.send(Some(status)).ok();
+    self.status_tx.send(status);
   }
 
   pub async fn dispatch_player_event(&mut self, evt: PlayerEvent) {
     self.event_tx.send(evt).await.ok();
   }
 
+  pub fn dispatch_lobby_chat(&self, msg: LobbyChatMessage) {
+    if let Err(err) = self.chat_tx.try_send(msg) {
+      tracing::debug!("lobby chat dropped: {}", err);
+    }
+  }
+
+  /// Replaces the mute list of the game in progress
+  pub fn update_mute_list(&self, mute_list: Vec<i32>) {
+    self.mute_list_tx.send_replace(mute_list);
+  }
+
+  /// Replaces the slot layout used by LAN clients joining the lobby later
+  pub fn update_info(&self, info: LanGameInfo) {
+    self.info_tx.send_replace(Arc::new(info));
+  }
+
+  /// The node started loading the game
+  pub fn is_started(&self) -> bool {
+    match self.status_tx.current().node_status {
+      None | Some(NodeGameStatus::Created) | Some(NodeGameStatus::Waiting) => false,
+      Some(_) => true,
+    }
+  }
+
   pub fn port(&self) -> u16 {
     self.port
   }
 
+  /// Leaves the game on the node, then stops the worker
+  /// and waits for it to save the replay
   pub async fn shutdown(self) {
     self.node_stream.shutdown().await;
+    drop(self.scope);
+    self.worker.await.ok();
   }
 }
 
 #[derive(Debug)]
 struct State {
-  info: LanGameInfo,
+  info_rx: watch::Receiver<Arc<LanGameInfo>>,
   stream: NodeStreamSender,
-  game_status_rx: watch::Receiver<Option<NodeGameStatus>>,
+  game_status_rx: GameStatusReceiver,
+  mute_list_rx: watch::Receiver<Vec<i32>>,
+  game_stall_timeout: Duration,
 }
 
 impl State {
@@ -158,7 +209,9 @@ impl State {
     self: Arc<Self>,
     mut listener: W3GSListener,
     event_rx: Receiver<PlayerEvent>,
-    mut w3gs_tx: Sender<Packet>,
+    mut chat_rx: Receiver<LobbyChatMessage>,
+    // keeps the W3GS channel open until the game loop returns
+    _w3gs_tx: Sender<Packet>,
     mut w3gs_rx: Receiver<Packet>,
     end_reason: Arc<Mutex<Option<GameEndReason>>>,
     mut scope: SpawnScopeHandle,
@@ -171,14 +224,15 @@ impl State {
     let mut node_stream = self.stream.clone();
     let mut status_rx = self.game_status_rx.clone();
     let (stop_collect_player_events_tx, stop_rx) = oneshot::channel();
+    let initial_info = self.info_rx.borrow().clone();
 
     tokio::pin! {
       let dropped = scope.left();
-      let collect_player_events = self.collect_player_events(event_rx, stop_rx, &self.info);
+      let collect_player_events = self.collect_player_events(event_rx, stop_rx, &initial_info);
     }
 
     // Lobby
-    let mut stream = loop {
+    let (mut stream, info) = loop {
       let mut incoming = listener.incoming();
 
       let next = tokio::select! {
@@ -204,12 +258,17 @@ impl State {
         }
       };
 
+      // the slots may have been updated after reconnecting to the controller
+      let info = self.info_rx.borrow().clone();
       let weak_outgoing_tx = client.send(GetWeakOutgoingMessageSender).await?;
       let lobby_action = {
         let lobby = self.handle_lobby_stream(
+          &info,
           &mut stream,
           &mut node_stream,
           &mut status_rx,
+          &mut chat_rx,
+          client.clone(),
           weak_outgoing_tx,
         );
         tokio::pin!(lobby);
@@ -227,7 +286,7 @@ impl State {
         }
       };
       match lobby_action {
-        LobbyAction::Start => break stream,
+        LobbyAction::Start => break (stream, info),
         LobbyAction::Leave => continue,
       }
     };
@@ -246,7 +305,7 @@ impl State {
     // Load Screen
     {
       let load_screen = self.handle_load_screen(
-        &self.info,
+        &info,
         &mut stream,
         &mut node_stream,
         &mut event_rx,
@@ -272,18 +331,19 @@ impl State {
 
     // Game Loop
     let mut game_handler = GameHandler::new(
-      &self.info,
+      &info,
       &node,
       &mut stream,
       &mut node_stream,
       &mut status_rx,
-      &mut w3gs_tx,
       &mut w3gs_rx,
       &mut client,
       &end_reason,
       game_version_string,
       save_replay,
       user_replay_path,
+      self.mute_list_rx.clone(),
+      self.game_stall_timeout,
     );
     tokio::select! {
       _ = &mut dropped => {}
@@ -304,8 +364,11 @@ impl State {
         guard.replace(GameEndReason::Unknown);
       }
     }
-    game_handler.start_save_replay();
+    let save_replay = game_handler.start_save_replay();
     stream.flush().await.ok();
+    if let Some(task) = save_replay {
+      task.await.ok();
+    }
 
     Ok(())
   }
@@ -351,18 +414,17 @@ impl State {
 
   async fn handle_lobby_stream(
     &self,
+    info: &LanGameInfo,
     stream: &mut W3GSStream,
     node_stream: &mut NodeStreamSender,
-    status_rx: &mut watch::Receiver<Option<NodeGameStatus>>,
+    status_rx: &mut GameStatusReceiver,
+    chat_rx: &mut Receiver<LobbyChatMessage>,
+    client: Addr<ControllerClient>,
     weak_outgoing_tx: Option<WeakSender<OutgoingMessage>>,
   ) -> Result<LobbyAction> {
-    let mut lobby_handler = LobbyHandler::new(
-      &self.info,
-      stream,
-      Some(node_stream),
-      status_rx,
-      weak_outgoing_tx,
-    );
+    let mut lobby_handler =
+      LobbyHandler::new(info, stream, Some(node_stream), status_rx, weak_outgoing_tx)
+        .with_chat(chat_rx, client);
     let action = lobby_handler.run().await?;
     Ok(action)
   }
@@ -373,7 +435,7 @@ impl State {
     stream: &mut W3GSStream,
     node_stream: &mut NodeStreamSender,
     event_rx: &mut Receiver<PlayerEvent>,
-    status_rx: &mut watch::Receiver<Option<NodeGameStatus>>,
+    status_rx: &mut GameStatusReceiver,
     w3gs_rx: &mut Receiver<Packet>,
     initial_status_map: HashMap<i32, SlotClientStatus>,
     deferred_in_packets: &mut Vec<Packet>,
@@ -407,6 +469,7 @@ impl State {
           }
           SlotClientStatus::Disconnected => {}
           SlotClientStatus::Left => {}
+          SlotClientStatus::LoadFailed => {}
         }
       }
       if !packets.is_empty() {
@@ -424,14 +487,21 @@ impl State {
         }
         // war3 packets
         res = stream.recv() => {
-          match res? {
+          let next = match res {
+            Ok(next) => next,
+            Err(err) => {
+              tracing::error!("game connection lost while loading: {}", err);
+              None
+            }
+          };
+          match next {
             Some(pkt) => {
               tracing::debug!("load screen => {:?}", pkt.type_id());
               match pkt.type_id() {
                 GameLoadedSelf::PACKET_TYPE_ID => {
                   tracing::debug!("self loaded: {}", my_slot_player_id);
 
-                  if let Some(idx) = self.info.slot_info.stream_ob_slot.clone() {
+                  if let Some(idx) = info.slot_info.stream_ob_slot.clone() {
                     stream.send(Packet::simple(PlayerLoaded {
                       player_id: index_to_player_id(idx)
                     })?).await?;
@@ -457,6 +527,8 @@ impl State {
               }
             },
             None => {
+              // the game exited, let the node abort the game instead of waiting for a timeout
+              node_stream.report_slot_status(SlotClientStatus::LoadFailed).await.ok();
               return Err(Error::StreamClosed)
             },
           }
@@ -471,32 +543,21 @@ impl State {
           }
         }
         // node status ack
-        changed = status_rx.changed() => {
-          let next =
-            if changed.is_ok() {
-              status_rx.borrow().clone()
-            } else {
-              return Err(Error::TaskCancelled(anyhow::format_err!("game status tx dropped")))
-            };
-          match next {
-            Some(status) => {
-              match status {
-                NodeGameStatus::Loading => {},
-                NodeGameStatus::Running => {
-                  event_rx.close();
-
-                  while let Some(event) = event_rx.recv().await {
-                    handle_player_event(info, my_player_id, &mut loaded_sent, stream, event).await?;
-                  }
-
-                  return Ok(())
-                },
-                other => {
-                  return Err(Error::UnexpectedNodeGameStatus(other))
-                }
+        status = status_rx.changed() => {
+          match status? {
+            NodeGameStatus::Loading => {},
+            NodeGameStatus::Running => {
+              event_rx.close();
+
+              while let Some(event) = event_rx.recv().await {
+                handle_player_event(info, my_player_id, &mut loaded_sent, stream, event).await?;
               }
+
+              return Ok(())
             },
-            None => {},
+            other => {
+              return Err(Error::UnexpectedNodeGameStatus(other))
+            }
           }
         }
         next = w3gs_rx.recv() => {
@@ -545,6 +606,9 @@ async fn handle_player_event(
       }
       SlotClientStatus::Disconnected => {}
       SlotClientStatus::Left => {}
+      SlotClientStatus::LoadFailed => {
+        tracing::warn!(player_id, "player failed to load");
+      }
     },
   }
   Ok(())
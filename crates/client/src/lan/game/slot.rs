@@ -1,7 +1,7 @@
-use flo_w3gs::slot::{RacePref, SlotData, SlotInfo};
+use flo_w3gs::slot::{RacePref, SlotData, SlotInfo, SlotLayout};
 
 use crate::error::*;
-use flo_types::game::{LanGameSlot, SlotStatus};
+use flo_types::game::{LanGameSlot, MapConfigOverrides, Slot, SlotStatus};
 
 #[derive(Debug)]
 pub struct LanSlotInfo {
@@ -31,11 +31,14 @@ impl From<i32> for SelfPlayer {
   }
 }
 
+/// Builds the lobby slots, `map_config` must be the overrides used for the game settings
+/// so the slot layout agrees with the stat string
 pub fn build_player_slot_info<'a, P, S>(
   self_player: P,
   random_seed: i32,
   slots: &'a [S],
-  map_twelve_p: bool
+  map_twelve_p: bool,
+  map_config: &MapConfigOverrides,
 ) -> Result<LanSlotInfo>
 where
   P: Into<SelfPlayer>,
@@ -62,7 +65,12 @@ where
     .find(|(idx, _)| *idx == flo_ob_slot)
     .is_some();
 
-  let stream_ob_slot = if let SelfPlayer::StreamObserver = self_player {
+  let stream_ob_slot = if !map_config.observers_allowed() {
+    if let SelfPlayer::StreamObserver = self_player {
+      return Err(Error::GameObserversDisabled);
+    }
+    None
+  } else if let SelfPlayer::StreamObserver = self_player {
     if occupied_slots.len() > (if map_twelve_p {11} else {23}) {
       return Err(Error::FloObserverSlotOccupied);
     }
@@ -79,6 +87,11 @@ where
     let mut b = SlotInfo::build();
     b.random_seed(random_seed)
       .num_slots(if map_twelve_p {12} else {24})
+      .slot_layout(if map_config.fixed_teams == Some(true) {
+        SlotLayout::CustomForces
+      } else {
+        SlotLayout::Melee
+      })
       .num_players(
         occupied_slots
           .iter()
@@ -169,3 +182,81 @@ where
 pub fn index_to_player_id(index: usize) -> u8 {
   return (index + 1) as u8;
 }
+
+/// Indexes of the slots whose player or settings differ,
+/// client statuses are owned by the node and not compared
+pub fn diff_slots(local: &[Slot], remote: &[Slot]) -> Vec<usize> {
+  (0..local.len().max(remote.len()))
+    .filter(|&idx| match (local.get(idx), remote.get(idx)) {
+      (Some(l), Some(r)) => {
+        l.player.as_ref().map(|p| p.id) != r.player.as_ref().map(|p| p.id)
+          || l.settings != r.settings
+      }
+      _ => true,
+    })
+    .collect()
+}
+
+#[test]
+fn test_diff_slots() {
+  use flo_types::node::SlotClientStatus;
+
+  let local = vec![Slot::default(); 3];
+  let mut remote = local.clone();
+  remote[0].client_status = SlotClientStatus::Loaded;
+  assert!(diff_slots(&local, &remote).is_empty());
+
+  remote[1].settings.team = 1;
+  assert_eq!(diff_slots(&local, &remote), vec![1]);
+
+  remote.pop();
+  assert_eq!(diff_slots(&local, &remote), vec![1, 2]);
+}
+
+#[cfg(test)]
+fn test_slots() -> Vec<Slot> {
+  use flo_types::game::{PlayerInfo, PlayerSource};
+
+  (0..2)
+    .map(|i| {
+      let mut slot = Slot::default();
+      slot.player = Some(PlayerInfo {
+        id: i + 1,
+        name: format!("player{}", i + 1),
+        source: PlayerSource::Test,
+        battletag: None,
+      });
+      slot.settings.status = SlotStatus::Occupied;
+      slot.settings.team = i;
+      slot
+    })
+    .collect()
+}
+
+#[test]
+fn test_build_player_slot_info_map_config() {
+  use flo_types::game::MapObserverMode;
+
+  let slots = test_slots();
+
+  let info = build_player_slot_info(1, 0, &slots, false, &Default::default()).unwrap();
+  assert_eq!(info.slot_info.slot_layout, SlotLayout::Melee);
+  assert_eq!(info.stream_ob_slot, Some(23));
+  assert_eq!(info.slot_info.slots()[23].team, 24);
+
+  let map_config = MapConfigOverrides {
+    fixed_teams: Some(true),
+    observers: MapObserverMode::None,
+    ..Default::default()
+  };
+  let info = build_player_slot_info(1, 0, &slots, false, &map_config).unwrap();
+  assert_eq!(info.slot_info.slot_layout, SlotLayout::CustomForces);
+  assert_eq!(info.stream_ob_slot, None);
+  assert!(info.slot_info.slots().iter().all(|slot| slot.team != 24));
+  assert_eq!(info.player_infos.len(), 2);
+
+  assert!(matches!(
+    build_player_slot_info(SelfPlayer::StreamObserver, 0, &slots, false, &map_config),
+    Err(Error::GameObserversDisabled)
+  ));
+}
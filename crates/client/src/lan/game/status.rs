@@ -0,0 +1,96 @@
+//! Node game status shared by the LAN game tasks.
+//! The status is published through a watch channel,
+//! receivers are only woken up if the status actually changed.
+
+use tokio::sync::watch;
+
+use flo_types::node::NodeGameStatus;
+
+use crate::error::*;
+
+/// The latest game status reported by the node
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GameStatusState {
+  /// `None` until the node reports a status
+  pub node_status: Option<NodeGameStatus>,
+}
+
+pub fn channel() -> (GameStatusSender, GameStatusReceiver) {
+  let (tx, rx) = watch::channel(GameStatusState::default());
+  (GameStatusSender(tx), GameStatusReceiver(rx))
+}
+
+#[derive(Debug)]
+pub struct GameStatusSender(watch::Sender<GameStatusState>);
+
+impl GameStatusSender {
+  pub fn current(&self) -> GameStatusState {
+    *self.0.borrow()
+  }
+
+  /// Returns `false` if the status didn't change
+  pub fn send(&self, status: NodeGameStatus) -> bool {
+    self.0.send_if_modified(|state| {
+      if state.node_status == Some(status) {
+        false
+      } else {
+        state.node_status = Some(status);
+        true
+      }
+    })
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameStatusReceiver(watch::Receiver<GameStatusState>);
+
+impl GameStatusReceiver {
+  pub fn current(&self) -> GameStatusState {
+    *self.0.borrow()
+  }
+
+  /// Waits for the next status reported by the node,
+  /// a status already seen by this receiver is not returned again.
+  /// Cancel safe.
+  pub async fn changed(&mut self) -> Result<NodeGameStatus> {
+    loop {
+      self
+        .0
+        .changed()
+        .await
+        .map_err(|_| Error::TaskCancelled(anyhow::format_err!("game status tx dropped")))?;
+      if let Some(status) = self.0.borrow().node_status {
+        return Ok(status);
+      }
+    }
+  }
+}
+
+#[test]
+fn test_game_status_channel() {
+  use futures::FutureExt;
+
+  let (tx, mut rx) = channel();
+  assert_eq!(rx.current().node_status, None);
+  assert!(rx.changed().now_or_never().is_none());
+
+  assert!(tx.send(NodeGameStatus::Loading));
+  assert!(!tx.send(NodeGameStatus::Loading));
+  assert_eq!(
+    rx.changed().now_or_never().map(Result::ok),
+    Some(Some(NodeGameStatus::Loading))
+  );
+  // not woken up by the repeated status
+  assert!(rx.changed().now_or_never().is_none());
+
+  assert!(tx.send(NodeGameStatus::Running));
+  drop(tx);
+  assert_eq!(
+    rx.changed().now_or_never().map(Result::ok),
+    Some(Some(NodeGameStatus::Running))
+  );
+  assert!(matches!(
+    rx.changed().now_or_never(),
+    Some(Err(Error::TaskCancelled(_)))
+  ));
+}
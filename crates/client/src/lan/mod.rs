@@ -4,13 +4,13 @@ pub mod game;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use game::LanGame;
+use game::{LanGame, LobbyChatMessage};
 
 use crate::controller::ControllerClient;
 use crate::error::*;
 use crate::node::stream::NodeStreamEvent;
 use crate::node::NodeInfo;
-use crate::platform::{CalcMapChecksum, GetClientPlatformInfo, Platform, GetSaveReplayStartConfig};
+use crate::platform::{CalcMapChecksum, GetClientConfig, GetClientPlatformInfo, Platform, GetSaveReplayStartConfig};
 use crate::StartConfig;
 use flo_state::{
   async_trait, Actor, Addr, Context, Deferred, Handler, Message, RegistryRef, Service,
@@ -44,6 +44,8 @@ pub struct ReplaceLanGame {
   pub my_player_id: i32,
   pub node: Arc<NodeInfo>,
   pub player_token: Vec<u8>,
+  /// Trace context of the game, sent to the node on connect
+  pub traceparent: String,
   pub game: Arc<LocalGameInfo>,
 }
 
@@ -60,18 +62,32 @@ impl Handler<ReplaceLanGame> for Lan {
       my_player_id,
       node,
       player_token,
+      traceparent,
       game,
     }: ReplaceLanGame,
   ) -> <ReplaceLanGame as Message>::Result {
     let game_id = game.game_id;
-    if self
+    // the game is received again after reconnecting to the controller
+    if let Some(active) = self
       .active_game
-      .as_ref()
-      .map(|g| g.is_same_game(game_id, my_player_id))
-      == Some(true)
+      .as_mut()
+      .filter(|g| g.is_same_game(game_id, my_player_id))
     {
-      tracing::debug!("skip create: same game");
-      return Ok(());
+      let diverged = active.diff_slots(&game);
+      if diverged.is_empty() {
+        tracing::debug!("skip create: same game");
+        return Ok(());
+      }
+      if active.is_started() {
+        tracing::error!(
+          game_id,
+          "slots {:?} diverged after the game started",
+          diverged
+        );
+        return Ok(());
+      }
+      tracing::warn!(game_id, "slots {:?} diverged, updating lan game", diverged);
+      return active.update_game(game);
     }
 
     let checksum = self
@@ -106,16 +122,20 @@ impl Handler<ReplaceLanGame> for Lan {
           .await?
           .map_err(|err| { tracing::error!("Could not get replay save config: {}", err); Error::LocalGameInfoNotFound })?;
 
+      let node_stream_config = self.platform.send(GetClientConfig).await?.node_stream;
+
       let lan_game = LanGame::create(
         game_version,
         my_player_id,
         node,
         player_token,
+        traceparent,
         game,
         checksum,
         self.client.resolve().await?,
         save_replay,
         user_replay_path,
+        node_stream_config,
       )
       .await?;
       tracing::info!(player_id = my_player_id, game_id, "lan game created.");
@@ -161,7 +181,9 @@ impl Handler<UpdateLanGameStatus> for Lan {
     }
 
     for (player_id, status) in updated_player_game_client_status_map {
-      game.update_player_status(player_id, status).await;
+      if game.update_player_status(player_id, status).await {
+        tracing::debug!(game_id, player_id, "player status updated: {:?}", status);
+      }
     }
     game.update_game_status(status).await;
 
@@ -207,6 +229,35 @@ impl Handler<UpdateLanGamePlayerStatus> for Lan {
   }
 }
 
+pub struct LanLobbyChat {
+  pub game_id: i32,
+  pub player_id: i32,
+  pub message: String,
+}
+
+impl Message for LanLobbyChat {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<LanLobbyChat> for Lan {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LanLobbyChat {
+      game_id,
+      player_id,
+      message,
+    }: LanLobbyChat,
+  ) -> <LanLobbyChat as Message>::Result {
+    if let Some(game) = self.active_game.as_ref() {
+      if game.game_id() == game_id {
+        game.dispatch_lobby_chat(LobbyChatMessage { player_id, message });
+      }
+    }
+  }
+}
+
 pub struct StopLanGame {
   pub game_id: i32,
 }
@@ -230,6 +281,27 @@ impl Handler<StopLanGame> for Lan {
   }
 }
 
+pub struct UpdateLanGameMuteList {
+  pub mute_list: Vec<i32>,
+}
+
+impl Message for UpdateLanGameMuteList {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<UpdateLanGameMuteList> for Lan {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    UpdateLanGameMuteList { mute_list }: UpdateLanGameMuteList,
+  ) -> <UpdateLanGameMuteList as Message>::Result {
+    if let Some(game) = self.active_game.as_ref() {
+      game.update_mute_list(mute_list);
+    }
+  }
+}
+
 pub struct KillLanGame;
 
 impl Message for KillLanGame {
@@ -247,6 +319,24 @@ impl Handler<KillLanGame> for Lan {
   }
 }
 
+/// Removes the active game, to be closed by the caller
+pub struct TakeLanGame;
+
+impl Message for TakeLanGame {
+  type Result = Option<LanGame>;
+}
+
+#[async_trait]
+impl Handler<TakeLanGame> for Lan {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: TakeLanGame,
+  ) -> <TakeLanGame as Message>::Result {
+    self.active_game.take()
+  }
+}
+
 pub fn get_lan_game_name(game_name: &str, player_id: i32) -> String {
   format!("{}-{}", game_name, player_id)
 }
@@ -1,5 +1,7 @@
 mod controller;
+pub mod credential;
 pub mod error;
+mod fingerprint;
 mod game;
 mod lan;
 mod message;
@@ -7,6 +9,7 @@ mod node;
 pub mod observer;
 mod ping;
 pub mod platform;
+mod shutdown;
 mod version;
 pub use version::FLO_VERSION;
 
@@ -28,5 +31,10 @@ pub struct StartConfig {
 pub use crate::message::embed::{start_embed, FloEmbedClient, FloEmbedClientHandle};
 pub use message::messages;
 
+#[cfg(feature = "mock-node")]
+pub use crate::node::mock::{MockNode, MockNodeConfig, MockNodeEvent, MockPlayer};
+#[cfg(feature = "mock-node")]
+pub use crate::node::stream::NodeConnectToken;
+
 #[cfg(feature = "ws")]
 pub use crate::message::ws::{start_ws, FloWsClient};
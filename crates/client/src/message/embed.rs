@@ -3,6 +3,7 @@ use super::stream::MessageStream;
 use super::Session;
 use crate::controller::{ControllerClient, ReplaceSession};
 use crate::error::{Error, Result};
+use crate::lan::Lan;
 use crate::observer::{ObserverClient, ObserverHostShared, WatchGame};
 use crate::platform::Platform;
 use crate::StartConfig;
@@ -14,6 +15,7 @@ pub struct FloEmbedClient {
   platform: Addr<Platform>,
   controller_client: Addr<ControllerClient>,
   observer_client: Addr<ObserverClient>,
+  lan: Addr<Lan>,
   tx: mpsc::Sender<IncomingMessage>,
   rx: mpsc::Receiver<OutgoingMessage>,
   _registry: Registry<StartConfig>,
@@ -24,8 +26,9 @@ impl FloEmbedClient {
     FloEmbedClientHandle {
       tx: self.tx.clone(),
       platform: self.platform.clone(),
-      _controller_client: self.controller_client.clone(),
+      controller_client: self.controller_client.clone(),
       observer_client: self.observer_client.clone(),
+      lan: self.lan.clone(),
     }
   }
 
@@ -38,8 +41,9 @@ impl FloEmbedClient {
 pub struct FloEmbedClientHandle {
   tx: mpsc::Sender<IncomingMessage>,
   platform: Addr<Platform>,
-  _controller_client: Addr<ControllerClient>,
+  controller_client: Addr<ControllerClient>,
   observer_client: Addr<ObserverClient>,
+  lan: Addr<Lan>,
 }
 
 impl FloEmbedClientHandle {
@@ -53,7 +57,15 @@ impl FloEmbedClientHandle {
   }
 
   pub async fn watch(&self, token: String) -> Result<ObserverHostShared> {
-    Ok(self.observer_client.send(WatchGame { token }).await??)
+    Ok(
+      self
+        .observer_client
+        .send(WatchGame {
+          token,
+          node_addr: None,
+        })
+        .await??,
+    )
   }
 
   pub async fn get_client_platform_info(&self, force_reload: bool) -> Result<ClientPlatformInfo> {
@@ -64,6 +76,11 @@ impl FloEmbedClientHandle {
       .await??;
     Ok(info)
   }
+
+  /// Leaves the current game and closes the LAN game and server connections
+  pub async fn shutdown(&self) {
+    crate::shutdown::shutdown(&self.lan, &self.controller_client).await
+  }
 }
 
 pub async fn start_embed(config: StartConfig) -> Result<FloEmbedClient> {
@@ -73,6 +90,7 @@ pub async fn start_embed(config: StartConfig) -> Result<FloEmbedClient> {
   let platform = registry.resolve().await?;
   let controller_client = registry.resolve().await?;
   let observer_client = registry.resolve().await?;
+  let lan = registry.resolve().await?;
 
   let (outgoing_tx, outgoing_rx) = mpsc::channel(100);
   let (incoming_tx, incoming_rx) = mpsc::channel(100);
@@ -95,6 +113,7 @@ pub async fn start_embed(config: StartConfig) -> Result<FloEmbedClient> {
     platform,
     controller_client,
     observer_client,
+    lan,
     tx: incoming_tx,
     rx: outgoing_rx,
     _registry: registry,
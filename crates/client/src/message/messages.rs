@@ -4,21 +4,30 @@ use serde_json::Value;
 use std::borrow::Cow;
 use std::str::FromStr;
 
+use flo_net::error_code::{ErrorCode, Recovery};
 use flo_net::proto::flo_connect::{
-  PacketGamePlayerLeave, PacketGamePlayerPingMapSnapshot, PacketGamePlayerPingMapSnapshotRequest,
-  PacketGameSelectNode, PacketGameSelectNodeRequest, PacketGameStartReject, PacketGameStartRequest,
-  PacketGameStarting, PacketPlayerPingMapUpdate,
+  PacketGameJoinReject, PacketGameJoinRequest, PacketGameLobbyChat, PacketGameLobbyChatRequest,
+  PacketGamePlayerLeave,
+  PacketGamePlayerPingMapSnapshot, PacketGamePlayerPingMapSnapshotRequest, PacketGameRehostReject,
+  PacketGameRehostRequest, PacketGameResult, PacketGameSelectNode, PacketGameSelectNodeRequest,
+  PacketGameSlotLockRequest, PacketGameSlotManageReject, PacketGameSlotReserveRequest,
+  PacketGameSlotSwapRequest, PacketGameStartReject, PacketGameStartRequest, PacketGameStarting, PacketMapVetoBanRequest,
+  PacketMapVetoStatus, PacketMatchmakingQueueJoinRequest, PacketMatchmakingQueueStatus,
+  PacketListLobbies, PacketListLobbiesRequest, PacketPlayerPingMapUpdate,
 };
+use flo_net::proto::flo_node::PacketClientIdleWarning;
 
 use crate::error::{Error, Result};
 use crate::observer::WatchGame;
 use crate::ping::PingUpdate;
 use crate::platform::PlatformStateError;
+use flo_events::GameEvent;
 pub use flo_types::game::{
-  DisconnectReason, MapDetail, MapForceOwned, MapPlayerOwned, PlayerSession, PlayerSessionUpdate,
-  RejectReason,
+  DisconnectReason, MapDetail, MapForceOwned, MapPlayerOwned, MapScanEntry, PlayerSession,
+  PlayerSessionUpdate, RejectReason,
 };
 use flo_types::game::{GameInfo, GameStatusUpdate, PlayerInfo, Slot, SlotSettings};
+use flo_types::observer::LiveStats;
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(tag = "type")]
@@ -28,6 +37,7 @@ pub enum IncomingMessage {
   Disconnect,
   ListMaps,
   GetMapDetail(MapPath),
+  ScanMaps(ScanMaps),
   GameSlotUpdateRequest(GameSlotUpdateRequest),
   GameSelectNodeRequest(PacketGameSelectNodeRequest),
   GamePlayerPingMapSnapshotRequest(PacketGamePlayerPingMapSnapshotRequest),
@@ -39,6 +49,18 @@ pub enum IncomingMessage {
   ClearNodeAddrOverrides,
   WatchGame(WatchGame),
   WatchGameSetSpeed(WatchGameSetSpeed),
+  WatchGameGetLiveStats,
+  WatchGameGetEvents(WatchGameGetEvents),
+  MatchmakingQueueJoinRequest(PacketMatchmakingQueueJoinRequest),
+  MatchmakingQueueLeaveRequest,
+  GameLobbyChatRequest(PacketGameLobbyChatRequest),
+  MapVetoBanRequest(PacketMapVetoBanRequest),
+  GameRehostRequest(PacketGameRehostRequest),
+  GameSlotSwapRequest(PacketGameSlotSwapRequest),
+  GameSlotLockRequest(PacketGameSlotLockRequest),
+  GameSlotReserveRequest(PacketGameSlotReserveRequest),
+  ListLobbiesRequest(PacketListLobbiesRequest),
+  GameJoinRequest(PacketGameJoinRequest),
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -53,6 +75,8 @@ pub enum OutgoingMessage {
   ListMapsError(ErrorMessage),
   GetMapDetail(MapDetail),
   GetMapDetailError(ErrorMessage),
+  ScanMaps(MapScanList),
+  ScanMapsError(ErrorMessage),
   CurrentGameInfo(GameInfo),
   GamePlayerEnter(GamePlayerEnter),
   GamePlayerLeave(PacketGamePlayerLeave),
@@ -74,7 +98,20 @@ pub enum OutgoingMessage {
   WatchGame(WatchGameInfo),
   WatchGameError(ErrorMessage),
   WatchGameSetSpeedError(ErrorMessage),
+  WatchGameLiveStats(LiveStats),
+  WatchGameLiveStatsError(ErrorMessage),
+  WatchGameEvents(WatchGameEvents),
+  WatchGameEventsError(ErrorMessage),
   LanGameJoined(LanGameJoined),
+  MatchmakingQueueStatus(PacketMatchmakingQueueStatus),
+  GameLobbyChat(PacketGameLobbyChat),
+  GameResult(PacketGameResult),
+  MapVetoStatus(PacketMapVetoStatus),
+  GameRehostReject(PacketGameRehostReject),
+  GameSlotManageReject(PacketGameSlotManageReject),
+  GameIdleWarning(PacketClientIdleWarning),
+  ListLobbies(PacketListLobbies),
+  GameJoinReject(PacketGameJoinReject),
 }
 
 impl FromStr for IncomingMessage {
@@ -111,14 +148,31 @@ pub struct War3Info {
 #[derive(Debug, Serialize, Clone)]
 pub struct ErrorMessage {
   pub message: String,
+  /// Set for errors received from the controller or a node
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub code: Option<ErrorCode>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub recovery: Option<Recovery>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hint: Option<&'static str>,
 }
 
 impl ErrorMessage {
   pub fn new<T: ToString>(v: T) -> Self {
     ErrorMessage {
       message: v.to_string(),
+      code: None,
+      recovery: None,
+      hint: None,
     }
   }
+
+  pub fn with_code(mut self, code: ErrorCode) -> Self {
+    self.code = Some(code);
+    self.recovery = Some(code.recovery());
+    self.hint = Some(code.hint());
+    self
+  }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -142,6 +196,17 @@ pub struct MapPath {
   pub path: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScanMaps {
+  #[serde(default)]
+  pub paths: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MapScanList {
+  pub maps: Vec<MapScanEntry>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct NodeList {
   pub nodes: Vec<Node>,
@@ -177,6 +242,8 @@ pub struct GameSlotUpdate {
   pub slot_index: i32,
   pub slot_settings: SlotSettings,
   pub player: Option<PlayerInfo>,
+  pub locked: bool,
+  pub reserved_player_id: Option<i32>,
 }
 
 use crate::controller::SetNodeAddrOverrides;
@@ -203,6 +270,19 @@ pub struct WatchGameSetSpeed {
   pub speed: f64,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct WatchGameGetEvents {
+  /// Index of the first event to return
+  #[serde(default)]
+  pub since: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WatchGameEvents {
+  pub since: usize,
+  pub events: Vec<GameEvent>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct StartTestGame {
   pub name: String,
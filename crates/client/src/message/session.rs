@@ -1,6 +1,6 @@
 use super::messages::{
-  ClientInfo, ErrorMessage, IncomingMessage, MapList, MapPath, OutgoingMessage, War3Info,
-  WatchGameInfo,
+  ClientInfo, ErrorMessage, IncomingMessage, MapList, MapPath, MapScanList, OutgoingMessage,
+  ScanMaps, War3Info, WatchGameEvents, WatchGameInfo,
 };
 use super::{ConnectController, MessageEvent};
 use crate::controller::{
@@ -16,7 +16,7 @@ use crate::platform::{
 use flo_net::packet::FloPacket;
 use flo_net::proto::flo_connect::{
   PacketGamePlayerPingMapSnapshotRequest, PacketGameSlotUpdateRequest, PacketGameStartRequest,
-  PacketListNodesRequest,
+  PacketListNodesRequest, PacketMatchmakingQueueLeaveRequest,
 };
 use flo_platform::ClientPlatformInfo;
 use flo_state::Addr;
@@ -163,6 +163,9 @@ impl Worker {
           .handle_get_map_detail(reply_sender.clone(), payload)
           .await?;
       }
+      IncomingMessage::ScanMaps(payload) => {
+        self.handle_scan_maps(reply_sender.clone(), payload).await?;
+      }
       IncomingMessage::GameSlotUpdateRequest(req) => {
         self
           .send_frame::<PacketGameSlotUpdateRequest>(req.pack()?)
@@ -182,6 +185,38 @@ impl Worker {
       IncomingMessage::GameStartRequest(req) => {
         self.send_frame::<PacketGameStartRequest>(req).await?;
       }
+      IncomingMessage::MatchmakingQueueJoinRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::MatchmakingQueueLeaveRequest => {
+        self
+          .send_frame(PacketMatchmakingQueueLeaveRequest {})
+          .await?;
+      }
+      IncomingMessage::GameLobbyChatRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::MapVetoBanRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::GameRehostRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::GameSlotSwapRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::GameSlotLockRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::GameSlotReserveRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::ListLobbiesRequest(req) => {
+        self.send_frame(req).await?;
+      }
+      IncomingMessage::GameJoinRequest(req) => {
+        self.send_frame(req).await?;
+      }
       IncomingMessage::StartTestGame(msg) => {
         self
           .platform
@@ -235,7 +270,9 @@ impl Worker {
           Err(err) => {
             tracing::error!("watch game: {}", err);
             reply_sender
-              .send(OutgoingMessage::WatchGameError(ErrorMessage::new(err)))
+              .send(OutgoingMessage::WatchGameError(
+                ErrorMessage::new(&err).with_code(err.code()),
+              ))
               .await?;
           }
         }
@@ -256,6 +293,35 @@ impl Worker {
         };
         reply_sender.send(reply).await?;
       }
+      IncomingMessage::WatchGameGetEvents(msg) => {
+        let reply = {
+          let host = self.current_observer_host.lock();
+          if let Some(host) = host.as_ref() {
+            OutgoingMessage::WatchGameEvents(WatchGameEvents {
+              since: msg.since,
+              events: host.events(msg.since),
+            })
+          } else {
+            OutgoingMessage::WatchGameEventsError(ErrorMessage::new("No active stream."))
+          }
+        };
+        reply_sender.send(reply).await?;
+      }
+      IncomingMessage::WatchGameGetLiveStats => {
+        let reply = {
+          let host = self.current_observer_host.lock();
+          match host.as_ref().map(|host| host.live_stats()) {
+            Some(Some(stats)) => OutgoingMessage::WatchGameLiveStats(stats),
+            Some(None) => OutgoingMessage::WatchGameLiveStatsError(ErrorMessage::new(
+              "Live stats are not available.",
+            )),
+            None => {
+              OutgoingMessage::WatchGameLiveStatsError(ErrorMessage::new("No active stream."))
+            }
+          }
+        };
+        reply_sender.send(reply).await?;
+      }
     }
     Ok(())
   }
@@ -334,6 +400,30 @@ impl Worker {
     Ok(())
   }
 
+  async fn handle_scan_maps(
+    &self,
+    sender: Sender<OutgoingMessage>,
+    ScanMaps { paths }: ScanMaps,
+  ) -> Result<()> {
+    let res = self
+      .platform
+      .send(crate::platform::ScanMaps { paths })
+      .await?;
+    match res {
+      Ok(maps) => {
+        sender
+          .send(OutgoingMessage::ScanMaps(MapScanList { maps }))
+          .await?
+      }
+      Err(e) => {
+        sender
+          .send(OutgoingMessage::ScanMapsError(ErrorMessage::new(e)))
+          .await?
+      }
+    }
+    Ok(())
+  }
+
   async fn send_frame<T: FloPacket>(&self, pkt: T) -> Result<()> {
     self
       .controller_client
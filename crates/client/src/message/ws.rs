@@ -3,6 +3,7 @@ use super::stream::MessageStream;
 use super::Session;
 use crate::controller::{ControllerClient, ReplaceSession};
 use crate::error::{Error, Result};
+use crate::lan::Lan;
 use crate::message::MessageEvent;
 use crate::observer::{ObserverClient, WatchGame};
 use crate::platform::Platform;
@@ -34,11 +35,24 @@ impl FloWsClient {
   pub async fn watch(&self, token: String) -> Result<()> {
     let obs = self._registry.resolve::<ObserverClient>().await?;
 
-    obs.send(WatchGame { token }).await??;
+    obs
+      .send(WatchGame {
+        token,
+        node_addr: None,
+      })
+      .await??;
 
     Ok(())
   }
 
+  /// Leaves the current game and closes the LAN game and server connections
+  pub async fn shutdown(&self) -> Result<()> {
+    let lan = self._registry.resolve::<Lan>().await?;
+    let controller = self._registry.resolve::<ControllerClient>().await?;
+    crate::shutdown::shutdown(&lan, &controller).await;
+    Ok(())
+  }
+
   pub async fn serve(self) {
     futures::future::pending().await
   }
@@ -0,0 +1,573 @@
+//! A node that speaks the client protocol with scripted behaviors.
+//!
+//! Client features driven by the node, e.g. the lag screen or a player
+//! dropping mid game, can be developed against `MockNode` without a node
+//! deployment. Available in tests and with the `mock-node` feature.
+
+use futures::StreamExt;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, Instant};
+use tokio_util::sync::CancellationToken;
+
+use flo_net::listener::FloListener;
+use flo_net::packet::{Frame, PacketTypeId};
+use flo_net::proto::flo_node::{
+  ClientConnectRejectReason, NodeGameStatus, PacketClientConnect, PacketClientConnectAccept,
+  PacketClientConnectReject, PacketClientUpdateSlotClientStatus,
+  PacketClientUpdateSlotClientStatusRequest, PacketNodeGameStatusUpdate, SlotClientStatus,
+};
+use flo_net::stream::FloStream;
+use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
+use flo_w3gs::action::{IncomingAction, OutgoingAction, PlayerAction, TimeSlot};
+use flo_w3gs::lag::{LagPlayer, StartLag, StopLag};
+use flo_w3gs::leave::{LeaveReason, PlayerLeft};
+
+use crate::error::Result;
+use crate::node::stream::NodeConnectToken;
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct MockPlayer {
+  pub player_id: i32,
+  /// Player id in the W3GS game
+  pub slot_player_id: u8,
+  pub token: NodeConnectToken,
+}
+
+#[derive(Debug, Clone)]
+pub enum MockNodeEvent {
+  /// Acks the client packets `delay` after receiving them, zero acks immediately
+  DelayAcks(Duration),
+  /// Holds back ticks, the game shows the lag screen for the player
+  Lag {
+    slot_player_id: u8,
+    duration: Duration,
+  },
+  /// The player leaves the game
+  DropPlayer { player_id: i32, reason: LeaveReason },
+  /// Closes the connection, the client reconnects
+  Disconnect,
+  /// Game status changes to `Ended`, ticks stop
+  EndGame,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockNodeConfig {
+  pub game_id: i32,
+  /// Players without a connected client are loaded with the first client
+  pub players: Vec<MockPlayer>,
+  pub tick_interval: Duration,
+  /// Events by the tick they run after, ticks are counted per player
+  pub script: BTreeMap<u32, Vec<MockNodeEvent>>,
+}
+
+impl MockNodeConfig {
+  pub fn new(game_id: i32, players: Vec<MockPlayer>) -> Self {
+    Self {
+      game_id,
+      players,
+      tick_interval: Duration::from_millis(100),
+      script: BTreeMap::new(),
+    }
+  }
+
+  pub fn at_tick(mut self, tick: u32, event: MockNodeEvent) -> Self {
+    self.script.entry(tick).or_default().push(event);
+    self
+  }
+}
+
+pub struct MockNode {
+  addr: SocketAddr,
+  shared: Arc<Shared>,
+  ct: CancellationToken,
+}
+
+impl MockNode {
+  /// Listens on a random local port
+  pub async fn start(config: MockNodeConfig) -> Result<Self> {
+    let mut listener = FloListener::bind_v4(0).await?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, listener.port()));
+    let ct = CancellationToken::new();
+    let shared = Arc::new(Shared {
+      state: Mutex::new(State {
+        game_status: NodeGameStatus::Created,
+        player_status: config
+          .players
+          .iter()
+          .map(|p| (p.player_id, SlotClientStatus::Pending))
+          .collect(),
+        peers: HashMap::new(),
+      }),
+      config,
+    });
+
+    tokio::spawn({
+      let shared = shared.clone();
+      let ct = ct.clone();
+      async move {
+        loop {
+          let stream = tokio::select! {
+            _ = ct.cancelled() => break,
+            next = listener.incoming().next() => match next {
+              Some(Ok(stream)) => stream,
+              Some(Err(err)) => {
+                tracing::error!("mock node accept: {}", err);
+                continue;
+              }
+              None => break,
+            }
+          };
+          let shared = shared.clone();
+          let ct = ct.clone();
+          tokio::spawn(async move {
+            if let Err(err) = serve(shared, ct, stream).await {
+              tracing::error!("mock node: {}", err);
+            }
+          });
+        }
+      }
+    });
+
+    Ok(Self { addr, shared, ct })
+  }
+
+  pub fn addr(&self) -> SocketAddr {
+    self.addr
+  }
+
+  pub fn game_status(&self) -> NodeGameStatus {
+    self.shared.state.lock().game_status
+  }
+
+  pub fn player_status(&self, player_id: i32) -> Option<SlotClientStatus> {
+    self
+      .shared
+      .state
+      .lock()
+      .player_status
+      .get(&player_id)
+      .cloned()
+  }
+}
+
+impl Drop for MockNode {
+  fn drop(&mut self) {
+    self.ct.cancel();
+  }
+}
+
+struct Shared {
+  config: MockNodeConfig,
+  state: Mutex<State>,
+}
+
+struct State {
+  game_status: NodeGameStatus,
+  player_status: HashMap<i32, SlotClientStatus>,
+  /// Peers of disconnected clients, kept for reconnects
+  peers: HashMap<i32, Peer>,
+}
+
+impl Shared {
+  fn status_update(
+    &self,
+    player_id: i32,
+    status: SlotClientStatus,
+  ) -> PacketClientUpdateSlotClientStatus {
+    self.state.lock().player_status.insert(player_id, status);
+    let mut pkt = PacketClientUpdateSlotClientStatus {
+      player_id,
+      game_id: self.config.game_id,
+      ..Default::default()
+    };
+    pkt.set_status(status);
+    pkt
+  }
+
+  /// Everyone loaded with the first client, the game starts running
+  fn load_all(&self) -> PacketNodeGameStatusUpdate {
+    let mut state = self.state.lock();
+    state.game_status = NodeGameStatus::Running;
+    for status in state.player_status.values_mut() {
+      if *status != SlotClientStatus::Left {
+        *status = SlotClientStatus::Loaded;
+      }
+    }
+    let mut pkt = PacketNodeGameStatusUpdate {
+      game_id: self.config.game_id,
+      updated_player_game_client_status_map: state
+        .player_status
+        .iter()
+        .map(|(id, status)| (*id, *status as i32))
+        .collect(),
+      ..Default::default()
+    };
+    pkt.set_status(NodeGameStatus::Running);
+    pkt
+  }
+}
+
+async fn serve(shared: Arc<Shared>, ct: CancellationToken, mut stream: FloStream) -> Result<()> {
+  let connect: PacketClientConnect = stream.recv().await?;
+  let player = shared
+    .config
+    .players
+    .iter()
+    .find(|p| p.token.to_vec() == connect.token)
+    .cloned();
+  let player = match player {
+    Some(player) => player,
+    None => {
+      let mut reject = PacketClientConnectReject {
+        message: "invalid token".to_string(),
+        ..Default::default()
+      };
+      reject.set_reason(ClientConnectRejectReason::InvalidToken);
+      stream.send(reject).await?;
+      return Ok(());
+    }
+  };
+
+  if connect.retry_shutdown {
+    stream
+      .send_frame(Frame::new_empty(PacketTypeId::ClientShutdownAck))
+      .await?;
+    return Ok(());
+  }
+
+  let mut peer = shared
+    .state
+    .lock()
+    .peers
+    .remove(&player.player_id)
+    .unwrap_or_else(Peer::new);
+
+  let accept = {
+    let state = shared.state.lock();
+    let mut accept = PacketClientConnectAccept {
+      version: Some(crate::version::FLO_VERSION.into()),
+      game_id: shared.config.game_id,
+      player_id: player.player_id,
+      player_game_client_status_map: state
+        .player_status
+        .iter()
+        .map(|(id, status)| (*id, *status as i32))
+        .collect(),
+      w3gs_batch: false,
+      ..Default::default()
+    };
+    accept.set_game_status(state.game_status);
+    accept
+  };
+  stream.send(accept).await?;
+
+  let res = peer.run(&shared, &ct, &player, &mut stream).await;
+  shared.state.lock().peers.insert(player.player_id, peer);
+  res
+}
+
+struct Peer {
+  ack_q: W3GSAckQueue,
+  /// Client sids waiting for their ack
+  acks: VecDeque<(Instant, u32)>,
+  ack_delay: Duration,
+  tick: u32,
+  actions: Vec<PlayerAction>,
+  lag: Option<(LagPlayer, Instant)>,
+}
+
+impl Peer {
+  fn new() -> Self {
+    Self {
+      ack_q: W3GSAckQueue::new(),
+      acks: VecDeque::new(),
+      ack_delay: Duration::ZERO,
+      tick: 0,
+      actions: vec![],
+      lag: None,
+    }
+  }
+
+  async fn run(
+    &mut self,
+    shared: &Shared,
+    ct: &CancellationToken,
+    player: &MockPlayer,
+    stream: &mut FloStream,
+  ) -> Result<()> {
+    let mut tick_timer = interval(shared.config.tick_interval);
+    let mut ping_timer = interval(PING_INTERVAL);
+
+    loop {
+      tokio::select! {
+        _ = ct.cancelled() => return Ok(()),
+        _ = ping_timer.tick() => {
+          stream.send_frame(Frame::new(PacketTypeId::Ping, 0_u32.to_be_bytes())).await?;
+        }
+        _ = tick_timer.tick() => {
+          if shared.state.lock().game_status != NodeGameStatus::Running {
+            continue;
+          }
+          if let Some((lag_player, until)) = self.lag.take() {
+            if Instant::now() < until {
+              self.lag = Some((lag_player, until));
+              continue;
+            }
+            self.send_w3gs(stream, W3GSPacket::simple(StopLag(lag_player))?).await?;
+          }
+          self.tick += 1;
+          let tick = W3GSPacket::with_payload(IncomingAction(TimeSlot {
+            time_increment_ms: shared.config.tick_interval.as_millis() as u16,
+            actions: std::mem::take(&mut self.actions),
+          }))?;
+          self.send_w3gs(stream, tick).await?;
+
+          let events = shared.config.script.get(&self.tick).cloned().unwrap_or_default();
+          for event in events {
+            if !self.run_event(shared, stream, event).await? {
+              return Ok(());
+            }
+          }
+        }
+        next = stream.recv_frame() => {
+          let frame = match next {
+            Ok(frame) => frame,
+            Err(flo_net::error::Error::StreamClosed) => return Ok(()),
+            Err(err) => return Err(err.into()),
+          };
+          match frame.type_id {
+            PacketTypeId::Pong => {}
+            PacketTypeId::ClientShutdown => {
+              stream.send_frame(Frame::new_empty(PacketTypeId::ClientShutdownAck)).await?;
+              return Ok(());
+            }
+            PacketTypeId::W3GS => {
+              let (meta, pkt) = frame.try_into_w3gs()?;
+              if !self.recv_w3gs(player, meta, pkt)? {
+                stream.send_frame(Frame::new_empty(PacketTypeId::ClientShutdownAck)).await?;
+                return Ok(());
+              }
+            }
+            PacketTypeId::W3GSBatch => {
+              for (meta, pkt) in frame.try_into_w3gs_batch()? {
+                if !self.recv_w3gs(player, meta, pkt)? {
+                  stream.send_frame(Frame::new_empty(PacketTypeId::ClientShutdownAck)).await?;
+                  return Ok(());
+                }
+              }
+            }
+            PacketTypeId::ClientUpdateSlotClientStatusRequest => {
+              let req: PacketClientUpdateSlotClientStatusRequest = frame.decode()?;
+              let status = req.status();
+              stream.send(shared.status_update(player.player_id, status)).await?;
+              if status == SlotClientStatus::Loaded
+                && shared.state.lock().game_status != NodeGameStatus::Running
+              {
+                stream.send(shared.load_all()).await?;
+              }
+            }
+            _ => {}
+          }
+        }
+      }
+    }
+  }
+
+  /// Returns `false` if the client left
+  fn recv_w3gs(
+    &mut self,
+    player: &MockPlayer,
+    meta: W3GSMetadata,
+    pkt: W3GSPacket,
+  ) -> Result<bool> {
+    if !self.ack_q.ack_received(meta.sid()) {
+      return Ok(true);
+    }
+    self.acks.push_back((Instant::now(), meta.sid()));
+    if let Some(ack_sid) = meta.ack_sid() {
+      self.ack_q.ack_sent(ack_sid);
+    }
+    match pkt.type_id() {
+      W3GSPacketTypeId::OutgoingAction => {
+        let action: OutgoingAction = pkt.decode_payload()?;
+        self.actions.push(PlayerAction {
+          player_id: player.slot_player_id,
+          data: action.data,
+        });
+      }
+      W3GSPacketTypeId::LeaveReq => return Ok(false),
+      _ => {}
+    }
+    Ok(true)
+  }
+
+  /// Returns `false` if the connection should be closed
+  async fn run_event(
+    &mut self,
+    shared: &Shared,
+    stream: &mut FloStream,
+    event: MockNodeEvent,
+  ) -> Result<bool> {
+    match event {
+      MockNodeEvent::DelayAcks(delay) => {
+        self.ack_delay = delay;
+      }
+      MockNodeEvent::Lag {
+        slot_player_id,
+        duration,
+      } => {
+        let lag_player = LagPlayer {
+          player_id: slot_player_id,
+          lag_duration_ms: duration.as_millis() as u32,
+        };
+        let start = StartLag::new(vec![LagPlayer {
+          player_id: slot_player_id,
+          lag_duration_ms: 0,
+        }]);
+        self.send_w3gs(stream, W3GSPacket::simple(start)?).await?;
+        self.lag = Some((lag_player, Instant::now() + duration));
+      }
+      MockNodeEvent::DropPlayer { player_id, reason } => {
+        let update = shared.status_update(player_id, SlotClientStatus::Left);
+        if let Some(slot_player_id) = shared
+          .config
+          .players
+          .iter()
+          .find(|p| p.player_id == player_id)
+          .map(|p| p.slot_player_id)
+        {
+          let left = PlayerLeft {
+            player_id: slot_player_id,
+            reason,
+          };
+          self.send_w3gs(stream, W3GSPacket::simple(left)?).await?;
+        }
+        stream.send(update).await?;
+      }
+      MockNodeEvent::Disconnect => return Ok(false),
+      MockNodeEvent::EndGame => {
+        shared.state.lock().game_status = NodeGameStatus::Ended;
+        let mut pkt = PacketNodeGameStatusUpdate {
+          game_id: shared.config.game_id,
+          ..Default::default()
+        };
+        pkt.set_status(NodeGameStatus::Ended);
+        stream.send(pkt).await?;
+      }
+    }
+    Ok(true)
+  }
+
+  async fn send_w3gs(&mut self, stream: &mut FloStream, pkt: W3GSPacket) -> Result<()> {
+    let sid = self.ack_q.gen_next_send_sid();
+    let meta = W3GSMetadata::new(pkt.type_id(), sid, self.take_ack());
+    stream.send_frame(Frame::from_w3gs(meta, pkt)).await?;
+    Ok(())
+  }
+
+  /// The last client sid received at least `ack_delay` ago
+  fn take_ack(&mut self) -> Option<u32> {
+    let now = Instant::now();
+    let mut ack = None;
+    while let Some((received_at, sid)) = self.acks.front().cloned() {
+      if now.saturating_duration_since(received_at) < self.ack_delay {
+        break;
+      }
+      self.acks.pop_front();
+      ack = Some(sid);
+    }
+    ack
+  }
+}
+
+#[tokio::test]
+async fn test_mock_node_script() {
+  use tokio::time::timeout;
+
+  let players: Vec<_> = (1..=2)
+    .map(|id| MockPlayer {
+      player_id: id,
+      slot_player_id: id as u8,
+      token: NodeConnectToken::from_vec(vec![id as u8; 16]).unwrap(),
+    })
+    .collect();
+  let mut config = MockNodeConfig::new(1, players.clone())
+    .at_tick(1, MockNodeEvent::DelayAcks(Duration::from_millis(50)))
+    .at_tick(
+      2,
+      MockNodeEvent::Lag {
+        slot_player_id: 2,
+        duration: Duration::from_millis(200),
+      },
+    )
+    .at_tick(
+      3,
+      MockNodeEvent::DropPlayer {
+        player_id: 2,
+        reason: LeaveReason::LeaveDisconnect,
+      },
+    )
+    .at_tick(3, MockNodeEvent::EndGame)
+    .at_tick(4, MockNodeEvent::Disconnect);
+  config.tick_interval = Duration::from_millis(20);
+  let node = MockNode::start(config).await.unwrap();
+
+  let mut stream = FloStream::connect(node.addr()).await.unwrap();
+  stream
+    .send(PacketClientConnect {
+      token: players[0].token.to_vec(),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  let accept: PacketClientConnectAccept = stream.recv().await.unwrap();
+  assert_eq!(accept.player_id, 1);
+  assert_eq!(accept.game_status(), NodeGameStatus::Created);
+
+  let mut req = PacketClientUpdateSlotClientStatusRequest::default();
+  req.set_status(SlotClientStatus::Loaded);
+  stream.send(req).await.unwrap();
+
+  let mut w3gs = vec![];
+  timeout(Duration::from_secs(10), async {
+    loop {
+      let frame = stream.recv_frame().await.unwrap();
+      match frame.type_id {
+        PacketTypeId::W3GS => {
+          let (_, pkt) = frame.try_into_w3gs().unwrap();
+          w3gs.push(pkt.type_id());
+        }
+        PacketTypeId::NodeGameStatusUpdate => {
+          let update: PacketNodeGameStatusUpdate = frame.decode().unwrap();
+          if update.status() == NodeGameStatus::Ended {
+            break;
+          }
+        }
+        _ => {}
+      }
+    }
+  })
+  .await
+  .unwrap();
+
+  assert_eq!(
+    w3gs,
+    vec![
+      W3GSPacketTypeId::IncomingAction,
+      W3GSPacketTypeId::IncomingAction,
+      W3GSPacketTypeId::StartLag,
+      W3GSPacketTypeId::StopLag,
+      W3GSPacketTypeId::IncomingAction,
+      W3GSPacketTypeId::PlayerLeft,
+    ]
+  );
+  assert_eq!(node.game_status(), NodeGameStatus::Ended);
+  assert_eq!(node.player_status(1), Some(SlotClientStatus::Loaded));
+  assert_eq!(node.player_status(2), Some(SlotClientStatus::Left));
+}
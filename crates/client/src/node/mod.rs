@@ -1,4 +1,7 @@
+#[cfg(any(test, feature = "mock-node"))]
+pub mod mock;
 mod registry;
+pub mod session_log;
 pub mod stream;
 pub use registry::{
   AddNode, ClearNodeAddrOverrides, GetNode, GetNodePingMap, NodeInfo, NodeRegistry, RemoveNode,
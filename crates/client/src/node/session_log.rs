@@ -0,0 +1,162 @@
+//! Structured log of the game-critical events of a node session.
+//! Each game gets its own JSON lines file, one object per event,
+//! e.g. `{"time":"..","game_id":1,"tick":120,"type":"NodeDisconnected","reason":"timeout"}`.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use flo_types::node::{NodeGameStatus, SlotClientStatus};
+
+/// Shared with the worker logs, which are vacuumed after 3 days
+pub const SESSION_LOG_DIR: &str = "flo-logs";
+
+/// Path of the session log of a game
+pub fn path(game_id: i32) -> PathBuf {
+  PathBuf::from(SESSION_LOG_DIR).join(format!("session-{}.jsonl", game_id))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum SessionEvent {
+  NodeConnected {
+    game_status: NodeGameStatus,
+  },
+  NodeConnectFailed {
+    error: String,
+  },
+  NodeDisconnected {
+    reason: String,
+  },
+  GameStatus {
+    status: NodeGameStatus,
+  },
+  PlayerStatus {
+    player_id: i32,
+    status: SlotClientStatus,
+  },
+  /// Neither the game nor the node sent a packet for `idle_ms`
+  GameStalled {
+    idle_ms: u64,
+    game_ticks: u32,
+    last_game_packet: Option<String>,
+    last_node_packet: Option<String>,
+  },
+  /// The last slot status was sent again after reconnecting,
+  /// the node was behind it
+  SlotStatusResent {
+    status: SlotClientStatus,
+    node_status: Option<SlotClientStatus>,
+  },
+  /// Node clock minus local clock, accurate to `dispersion_us`,
+  /// for correlating the times of this log with the node's
+  TimeSync {
+    offset_us: i64,
+    dispersion_us: i64,
+    rtt_us: i64,
+  },
+  /// Reconnected from another local address, e.g. after switching networks
+  LocalAddressChanged {
+    from: SocketAddr,
+    to: SocketAddr,
+  },
+  LeaveAck,
+  Shutdown {
+    acked: bool,
+  },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+  time: chrono::DateTime<chrono::Utc>,
+  game_id: i32,
+  tick: u32,
+  #[serde(flatten)]
+  event: &'a SessionEvent,
+}
+
+/// Appends to the session log of a game.
+/// Events are logged with `tracing` if the file can't be written.
+#[derive(Debug, Clone)]
+pub struct SessionLog {
+  game_id: i32,
+  file: Option<Arc<Mutex<File>>>,
+}
+
+impl SessionLog {
+  pub fn open(game_id: i32) -> Self {
+    let path = path(game_id);
+    let file = fs::create_dir_all(SESSION_LOG_DIR)
+      .and_then(|_| OpenOptions::new().create(true).append(true).open(&path));
+    match file {
+      Ok(file) => Self {
+        game_id,
+        file: Some(Arc::new(Mutex::new(file))),
+      },
+      Err(err) => {
+        tracing::error!(game_id, "open session log {}: {}", path.display(), err);
+        Self {
+          game_id,
+          file: None,
+        }
+      }
+    }
+  }
+
+  pub fn write(&self, tick: u32, event: SessionEvent) {
+    let line = match serde_json::to_string(&Record {
+      time: chrono::Utc::now(),
+      game_id: self.game_id,
+      tick,
+      event: &event,
+    }) {
+      Ok(line) => line,
+      Err(err) => {
+        tracing::error!(game_id = self.game_id, "serialize session event: {}", err);
+        return;
+      }
+    };
+
+    if let Some(file) = self.file.as_ref() {
+      match writeln!(file.lock(), "{}", line) {
+        Ok(()) => return,
+        Err(err) => tracing::error!(game_id = self.game_id, "write session log: {}", err),
+      }
+    }
+    tracing::info!(game_id = self.game_id, "{}", line);
+  }
+}
+
+#[test]
+fn test_session_event_json() {
+  let event = SessionEvent::PlayerStatus {
+    player_id: 2,
+    status: SlotClientStatus::Left,
+  };
+  let value = serde_json::to_value(&Record {
+    time: chrono::Utc::now(),
+    game_id: 1,
+    tick: 10,
+    event: &event,
+  })
+  .unwrap();
+  assert_eq!(value["game_id"], 1);
+  assert_eq!(value["tick"], 10);
+  assert_eq!(value["type"], "PlayerStatus");
+  assert_eq!(value["player_id"], 2);
+  assert_eq!(value["status"], "Left");
+  assert!(value["time"].is_string());
+
+  let value = serde_json::to_value(&Record {
+    time: chrono::Utc::now(),
+    game_id: 1,
+    tick: 0,
+    event: &SessionEvent::LeaveAck,
+  })
+  .unwrap();
+  assert_eq!(value["type"], "LeaveAck");
+}
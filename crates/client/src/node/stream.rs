@@ -3,16 +3,21 @@ use crate::error::*;
 use crate::lan::game::GameEndReason;
 use crate::lan::game::LanGameInfo;
 use crate::lan::LanEvent;
+use crate::node::session_log::{SessionEvent, SessionLog};
 use backoff::backoff::Backoff;
 use backoff::{self, ExponentialBackoff};
+use flo_config::NodeStreamConfig;
 use flo_net::packet::*;
 use flo_net::proto::flo_node as proto;
 use flo_net::stream::FloStream;
-use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
+use flo_net::time::{unix_time_micros, TimeSyncEstimator, TimeSyncSample};
+use flo_net::w3gs::{
+  W3GSAckQueue, W3GSBatch, W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId,
+};
 use flo_state::Addr;
 use flo_types::game::GameStatusUpdate;
-use flo_types::node::NodeGameStatusSnapshot;
 use flo_types::node::SlotClientStatus;
+use flo_types::node::{NodeGameStatus, NodeGameStatusSnapshot};
 use flo_w3gs::action::IncomingAction;
 use flo_w3gs::protocol::chat::ChatFromHost;
 use futures::FutureExt;
@@ -21,10 +26,10 @@ use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::Notify;
-use tokio::time::{sleep, Sleep};
+use tokio::time::{interval, sleep, Instant, MissedTickBehavior, Sleep};
 use tokio_util::sync::CancellationToken;
 use tracing_futures::Instrument;
 
@@ -52,20 +57,27 @@ impl NodeStream {
     game: &LanGameInfo,
     addr: SocketAddr,
     token: NodeConnectToken,
+    traceparent: String,
     client: Addr<ControllerClient>,
     game_tx: Sender<W3GSPacket>,
     end_reason: Arc<Mutex<Option<GameEndReason>>>,
+    config: &NodeStreamConfig,
   ) -> Result<Self> {
     let ct = CancellationToken::new();
     let shutdown_notify = Arc::new(Notify::new());
     let (tx, rx) = channel(10);
 
+    let span = tracing::debug_span!("worker", game_id = game.game.game_id);
+    flo_log::trace::set_parent(&span, &traceparent);
+
     let session = Session {
       game_id: game.game.game_id,
       player_id: game.game.player_id,
       slot_player_id: game.slot_info.my_slot_player_id,
       addr,
+      standby_addr: None,
       token,
+      traceparent,
       client,
       game_tx,
       rx,
@@ -75,7 +87,12 @@ impl NodeStream {
       ack: 0,
       time: 0,
       last_connected_at: None,
+      local_addr: None,
       end_reason,
+      w3gs_batch: false,
+      slot_status: None,
+      time_sync: TimeSyncEstimator::new(),
+      log: SessionLog::open(game.game.game_id),
     };
 
     tokio::spawn(
@@ -87,11 +104,16 @@ impl NodeStream {
             shutdown_notify.notify_one();
           }
         })
-        .instrument(tracing::debug_span!("worker", game_id = game.game.game_id)),
+        .instrument(span),
     );
 
     Ok(Self {
-      tx: NodeStreamSender { tx },
+      tx: NodeStreamSender {
+        tx,
+        ct: ct.clone(),
+        report_slot_status_timeout: config.report_slot_status_timeout(),
+        send_w3gs_timeout: config.send_w3gs_timeout(),
+      },
       ct,
       shutdown_notify,
     })
@@ -104,11 +126,13 @@ impl NodeStream {
 
 struct Session {
   game_id: i32,
-  #[allow(unused)]
   player_id: i32,
   slot_player_id: u8,
   addr: SocketAddr,
+  /// The other node of a primary and standby pair, tried if `addr` can't be connected
+  standby_addr: Option<SocketAddr>,
   token: NodeConnectToken,
+  traceparent: String,
   client: Addr<ControllerClient>,
   game_tx: Sender<W3GSPacket>,
   rx: Receiver<WorkerMsg>,
@@ -118,7 +142,16 @@ struct Session {
   time: u32,
   ack: u32,
   last_connected_at: Option<Instant>,
+  /// Local address of the last connection
+  local_addr: Option<SocketAddr>,
   end_reason: Arc<Mutex<Option<GameEndReason>>>,
+  /// The connected node accepts `W3GSBatch` frames
+  w3gs_batch: bool,
+  /// Last slot status sent to the node
+  slot_status: Option<SlotClientStatus>,
+  /// Clock offset to the node, kept across reconnects
+  time_sync: TimeSyncEstimator,
+  log: SessionLog,
 }
 
 impl Session {
@@ -131,6 +164,7 @@ impl Session {
     };
     let ct = self.ct.clone();
     let mut leave_ack_received = false;
+    let mut swap_addr = false;
 
     let stream = 'main: loop {
       let (mut stream, conn): (FloStream, Connection) = {
@@ -143,6 +177,10 @@ impl Session {
         }
 
         loop {
+          if std::mem::take(&mut swap_addr) {
+            self.swap_standby_addr();
+          }
+
           if self.last_connected_at.is_some() {
             self
               .send_private_message("Reconnecting to the server...")
@@ -161,21 +199,21 @@ impl Session {
                   break pair;
                 }
                 Err(err) => {
-                  tracing::error!("connect node: {}", err);
-                  use flo_net::proto::flo_node::ClientConnectRejectReason;
-                  match err {
-                    Error::NodeConnectionRejected(reason, _) if reason != ClientConnectRejectReason::Multi => {
-                      break 'main None;
-                    },
-                    _ => {
-                      if let Some(delay) = reconnect_backoff.next_backoff() {
-                        tracing::error!("connect node error: {:?}", err);
-                        sleep(delay).await;
-                      } else {
-                        tracing::error!("connect node: timeout");
-                        break 'main None;
-                      }
-                    }
+                  self.log.write(self.tick, SessionEvent::NodeConnectFailed {
+                    error: err.to_string(),
+                  });
+                  if !should_retry_connect(&err) {
+                    break 'main None;
+                  }
+                  // the game may have been taken over by the standby node
+                  swap_addr = true;
+                  if let Some(delay) = reconnect_backoff.next_backoff() {
+                    sleep(delay).await;
+                  } else {
+                    self.log.write(self.tick, SessionEvent::NodeDisconnected {
+                      reason: "reconnect timeout".to_string(),
+                    });
+                    break 'main None;
                   }
                 }
               }
@@ -185,7 +223,24 @@ impl Session {
       };
 
       self.last_connected_at.replace(Instant::now());
-      tracing::info!("node connected");
+      self.w3gs_batch = conn.w3gs_batch;
+      if let Some(addr) = conn.standby_addr {
+        self.standby_addr.replace(addr);
+      }
+      // the node accepts the token from the new address and replaces the old stream
+      if let Ok(local_addr) = stream.local_addr() {
+        if let Some(from) = self.local_addr.replace(local_addr) {
+          if from.ip() != local_addr.ip() {
+            self.log.write(
+              self.tick,
+              SessionEvent::LocalAddressChanged {
+                from,
+                to: local_addr,
+              },
+            );
+          }
+        }
+      }
 
       let res = conn.run(&mut stream, &mut self).await;
       match res {
@@ -228,9 +283,11 @@ impl Session {
         self.rx.close();
         while let Some(msg) = self.rx.recv().await {
           match self.encode_worker_msg(msg) {
-            Ok(frame) => {
-              tracing::info!("flush frame: {:?}", frame);
-              flush_frames.push(frame)
+            Ok(frames) => {
+              for frame in frames {
+                tracing::info!("flush frame: {:?}", frame);
+                flush_frames.push(frame)
+              }
             }
             Err(err) => {
               tracing::error!("encode worker msg: {}", err);
@@ -254,7 +311,6 @@ impl Session {
           match stream.recv_frame().await {
             Ok(frame) => {
               if frame.type_id == PacketTypeId::ClientShutdownAck {
-                tracing::info!("session shutdown ack received");
                 shutdown_ok = true;
                 break;
               }
@@ -266,6 +322,10 @@ impl Session {
           }
         }
 
+        self
+          .log
+          .write(self.tick, SessionEvent::Shutdown { acked: shutdown_ok });
+
         if !shutdown_ok {
           let mut shutdown_backoff = ExponentialBackoff {
             initial_interval: Duration::from_secs(1),
@@ -295,6 +355,13 @@ impl Session {
     self.notify_disconnected().await;
   }
 
+  fn swap_standby_addr(&mut self) {
+    if let Some(standby_addr) = self.standby_addr.as_mut() {
+      std::mem::swap(&mut self.addr, standby_addr);
+      tracing::info!(addr = %self.addr, "connecting to the other node of the standby pair");
+    }
+  }
+
   async fn connect(&self) -> Result<(FloStream, Connection)> {
     let mut stream = FloStream::connect_no_delay(self.addr).await?;
 
@@ -302,13 +369,20 @@ impl Session {
       .send(proto::PacketClientConnect {
         version: Some(crate::version::FLO_VERSION.into()),
         token: self.token.to_vec(),
+        traceparent: self.traceparent.clone(),
         ..Default::default()
       })
       .await?;
 
     let frame = stream.recv_frame().await?;
 
-    let (player_id, status_snapshot): (i32, NodeGameStatusSnapshot) = flo_net::try_flo_packet! {
+    let (player_id, w3gs_batch, time_sync, standby_addr, status_snapshot): (
+      i32,
+      bool,
+      bool,
+      Option<SocketAddr>,
+      NodeGameStatusSnapshot,
+    ) = flo_net::try_flo_packet! {
       frame => {
         p: proto::PacketClientConnectAccept => {
           let game_id = p.game_id;
@@ -320,8 +394,11 @@ impl Session {
             p.version,
             p.game_status,
           );
+          let w3gs_batch = p.w3gs_batch;
+          let time_sync = p.time_sync;
+          let standby_addr = p.standby_addr.parse().ok();
           let status = NodeGameStatusSnapshot::unpack(p)?;
-          (player_id, status)
+          (player_id, w3gs_batch, time_sync, standby_addr, status)
         }
         p: proto::PacketClientConnectReject => {
           return Err(Error::NodeConnectionRejected(p.reason(), p.message))
@@ -339,7 +416,31 @@ impl Session {
       stream.send_frames(frames).await?;
     }
 
+    // a status sent before the disconnect may not have reached the node
+    if let Some(status) = self.slot_status {
+      let node_status = status_snapshot
+        .player_game_client_status_map
+        .get(&self.player_id)
+        .cloned();
+      if should_resend_slot_status(status, node_status) {
+        self.log.write(
+          self.tick,
+          SessionEvent::SlotStatusResent {
+            status,
+            node_status,
+          },
+        );
+        stream.send_frame(encode_slot_status(status)?).await?;
+      }
+    }
+
     let game_id = status_snapshot.game_id;
+    self.log.write(
+      self.tick,
+      SessionEvent::NodeConnected {
+        game_status: status_snapshot.game_status,
+      },
+    );
 
     if self
       .client
@@ -360,6 +461,9 @@ impl Session {
       Connection {
         game_id,
         _player_id: player_id,
+        w3gs_batch,
+        time_sync,
+        standby_addr,
       },
     ))
   }
@@ -380,6 +484,7 @@ impl Session {
         token: self.token.to_vec(),
         retry_shutdown: true,
         leave_reason,
+        traceparent: self.traceparent.clone(),
       })
       .await?;
 
@@ -399,64 +504,100 @@ impl Session {
     };
   }
 
-  fn encode_worker_msg(&mut self, msg: WorkerMsg) -> Result<Frame> {
-    let frame = match msg {
+  fn encode_worker_msg(&mut self, msg: WorkerMsg) -> Result<Vec<Frame>> {
+    let frames = match msg {
       WorkerMsg::StatusUpdate(status) => {
-        let mut pkt =
-          flo_net::proto::flo_node::PacketClientUpdateSlotClientStatusRequest::default();
-        pkt.set_status(status.into_proto_enum());
-        pkt.encode_as_frame()?
-      }
-      WorkerMsg::W3GS(pkt) => {
-        // if pkt.type_id() == W3GSPacketTypeId::ChatToHost {
-        //   use flo_util::chat::parse_chat_command;
-        //   use flo_w3gs::protocol::chat::{ChatToHost};
-        //   let pkt: ChatToHost = pkt.decode_simple()?;
-        //   if let Some(cmd) = pkt.chat_message().and_then(|v| parse_chat_command(v)) {
-        //     match cmd.name() {
-        //       "conn" => {
-        //         session
-        //           .game_tx
-        //           .send(W3GSPacket::simple(ChatFromHost::private_to_self(
-        //             pkt.from_player,
-        //             format!(
-        //               "local: last_ack_received = {:?}, len = {}",
-        //               session.ack_q.last_ack_received(),
-        //               session.ack_q.pending_ack_len()
-        //             ),
-        //           ))?)
-        //           .await
-        //           .ok();
-        //       }
-        //       _ => {}
-        //     }
-        //   }
-        // }
-
-        let sid = self.ack_q.gen_next_send_sid();
-        let ack_id = self.ack_q.take_ack_received();
-        let meta = W3GSMetadata::new(pkt.type_id(), sid, ack_id);
-
-        match pkt.type_id() {
-          W3GSPacketTypeId::OutgoingKeepAlive => {
-            self.ack += 1;
+        if let Some(prev) = self.slot_status {
+          if !prev.client_can_transition_to(status) {
+            tracing::error!("invalid slot status transition: {:?} => {:?}", prev, status);
+            return Ok(vec![]);
           }
-          _ => {}
         }
+        self.slot_status.replace(status);
+        vec![encode_slot_status(status)?]
+      }
+      WorkerMsg::W3GS(pkt) => {
+        let (meta, pkt) = self.encode_w3gs(pkt);
+        vec![Frame::from_w3gs(meta, pkt)]
+      }
+      WorkerMsg::W3GSBatch(pkts) => self.encode_w3gs_batch(pkts),
+      WorkerMsg::GameStalled { .. } => vec![],
+    };
+    Ok(frames)
+  }
+
+  // Falls back to a frame per packet if the node doesn't accept batches
+  fn encode_w3gs_batch(&mut self, pkts: Vec<W3GSPacket>) -> Vec<Frame> {
+    if !self.w3gs_batch {
+      return pkts
+        .into_iter()
+        .map(|pkt| {
+          let (meta, pkt) = self.encode_w3gs(pkt);
+          Frame::from_w3gs(meta, pkt)
+        })
+        .collect();
+    }
 
-        // tracing::debug!(
-        //   "send#{} tick = {}, time = {}, ack = {}",
-        //   meta.sid(),
-        //   session.tick,
-        //   session.time,
-        //   session.ack,
-        // );
+    let mut frames = vec![];
+    let mut batch = W3GSBatch::new();
+    for pkt in pkts {
+      let (meta, pkt) = self.encode_w3gs(pkt);
+      if !batch.can_push(&meta, &pkt) {
+        frames.extend(std::mem::take(&mut batch).into_frame());
+      }
+      batch.push(meta, pkt);
+    }
+    frames.extend(batch.into_frame());
+    frames
+  }
 
-        self.ack_q.push_send(meta.clone(), pkt.clone());
-        Frame::from_w3gs(meta, pkt)
+  fn encode_w3gs(&mut self, pkt: W3GSPacket) -> (W3GSMetadata, W3GSPacket) {
+    // if pkt.type_id() == W3GSPacketTypeId::ChatToHost {
+    //   use flo_util::chat::parse_chat_command;
+    //   use flo_w3gs::protocol::chat::{ChatToHost};
+    //   let pkt: ChatToHost = pkt.decode_simple()?;
+    //   if let Some(cmd) = pkt.chat_message().and_then(|v| parse_chat_command(v)) {
+    //     match cmd.name() {
+    //       "conn" => {
+    //         session
+    //           .game_tx
+    //           .send(W3GSPacket::simple(ChatFromHost::private_to_self(
+    //             pkt.from_player,
+    //             format!(
+    //               "local: last_ack_received = {:?}, len = {}",
+    //               session.ack_q.last_ack_received(),
+    //               session.ack_q.pending_ack_len()
+    //             ),
+    //           ))?)
+    //           .await
+    //           .ok();
+    //       }
+    //       _ => {}
+    //     }
+    //   }
+    // }
+
+    let sid = self.ack_q.gen_next_send_sid();
+    let ack_id = self.ack_q.take_ack_received();
+    let meta = W3GSMetadata::new(pkt.type_id(), sid, ack_id);
+
+    match pkt.type_id() {
+      W3GSPacketTypeId::OutgoingKeepAlive => {
+        self.ack += 1;
       }
-    };
-    Ok(frame)
+      _ => {}
+    }
+
+    // tracing::debug!(
+    //   "send#{} tick = {}, time = {}, ack = {}",
+    //   meta.sid(),
+    //   session.tick,
+    //   session.time,
+    //   session.ack,
+    // );
+
+    self.ack_q.push_send(meta.clone(), pkt.clone());
+    (meta, pkt)
   }
 
   async fn send_private_message<T: AsRef<str>>(&self, msg: T) -> Result<()> {
@@ -488,14 +629,20 @@ impl Session {
 struct Connection {
   game_id: i32,
   _player_id: i32,
+  w3gs_batch: bool,
+  /// The connected node answers time sync requests
+  time_sync: bool,
+  /// Address of the standby node of the connected node
+  standby_addr: Option<SocketAddr>,
 }
 
 impl Connection {
   const MIN_DURATION: Duration = Duration::from_secs(3);
   const HOST_PING_TIMEOUT: Duration = Duration::from_secs(3);
+  const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(15);
 
   fn reset_timeout(t: Pin<&mut Sleep>) {
-    t.reset((Instant::now() + Self::HOST_PING_TIMEOUT).into())
+    t.reset(Instant::now() + Self::HOST_PING_TIMEOUT)
   }
 
   async fn run(
@@ -505,11 +652,16 @@ impl Connection {
   ) -> Result<ConnectionRunResult> {
     let ping_timeout = sleep(Self::HOST_PING_TIMEOUT);
     tokio::pin!(ping_timeout);
+    // the first tick completes at once, so the offset is known right after connecting
+    let mut time_sync_interval = interval(Self::TIME_SYNC_INTERVAL);
+    time_sync_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     let res = loop {
       tokio::select! {
         _ = &mut ping_timeout => {
-          tracing::error!("node stream timeout");
+          session.log.write(session.tick, SessionEvent::NodeDisconnected {
+            reason: "timeout".to_string(),
+          });
           break ConnectionRunResult::NodeDisconnected
         }
 
@@ -523,6 +675,7 @@ impl Connection {
         next = stream.recv_frame() => {
           match next {
             Ok(mut frame) => {
+              flo_task::yield_point().await;
               match frame.type_id {
                 PacketTypeId::Ping => {
                   Self::reset_timeout(ping_timeout.as_mut());
@@ -565,7 +718,7 @@ impl Connection {
                   }
                 }
                 PacketTypeId::ClientShutdownAck => {
-                  tracing::info!("player left ack received");
+                  session.log.write(session.tick, SessionEvent::LeaveAck);
                   break ConnectionRunResult::NodeLeft;
                 }
                 _ => {
@@ -577,22 +730,45 @@ impl Connection {
               }
             },
             Err(flo_net::error::Error::StreamClosed) => {
-              tracing::error!("node stream closed");
+              session.log.write(session.tick, SessionEvent::NodeDisconnected {
+                reason: "stream closed".to_string(),
+              });
               break ConnectionRunResult::NodeDisconnected;
             },
             Err(err) => {
-              tracing::error!("node stream recv: {}", err);
+              session.log.write(session.tick, SessionEvent::NodeDisconnected {
+                reason: err.to_string(),
+              });
               break ConnectionRunResult::NodeDisconnected;
             }
           }
         }
 
+        _ = time_sync_interval.tick(), if self.time_sync => {
+          let frame = proto::PacketClientTimeSyncRequest {
+            client_send_time: unix_time_micros(),
+          }.encode_as_frame()?;
+          if let Err(err) = stream.send_frame(frame).await {
+            tracing::error!("send time sync request: {}", err);
+            break ConnectionRunResult::NodeDisconnected;
+          }
+        }
+
         // worker msgs
         next = session.rx.recv() => {
           match next {
+            Some(WorkerMsg::GameStalled { idle, last }) => {
+              session.log.write(session.tick, SessionEvent::GameStalled {
+                idle_ms: idle.as_millis() as u64,
+                game_ticks: last.ticks,
+                last_game_packet: last.last_game_packet.map(|v| format!("{:?}", v)),
+                last_node_packet: last.last_node_packet.map(|v| format!("{:?}", v)),
+              });
+              break ConnectionRunResult::NodeDisconnected;
+            },
             Some(msg) => {
-              let frame = session.encode_worker_msg(msg)?;
-              if let Err(err) = stream.send_frame(frame).await {
+              let frames = session.encode_worker_msg(msg)?;
+              if let Err(err) = stream.send_frames(frames).await {
                 tracing::error!("handle_worker_msg: {}", err);
                 break ConnectionRunResult::NodeDisconnected;
               }
@@ -615,7 +791,10 @@ impl Connection {
     flo_net::try_flo_packet! {
       frame => {
         p: proto::PacketClientUpdateSlotClientStatus => {
-          tracing::debug!(game_id, player_id = p.player_id, "update slot client status: {:?}", p.status());
+          session.log.write(session.tick, SessionEvent::PlayerStatus {
+            player_id: p.player_id,
+            status: SlotClientStatus::unpack_enum(p.status()),
+          });
           flo_log::result_ok!(
             "send NodeStreamEvent::SlotClientStatusUpdate",
             client.notify(LanEvent::NodeStreamEvent {
@@ -634,8 +813,25 @@ impl Connection {
             }).await
           );
         }
+        p: proto::PacketClientTimeSync => {
+          let sample = TimeSyncSample {
+            client_send_time: p.client_send_time,
+            node_recv_time: p.node_recv_time,
+            node_send_time: p.node_send_time,
+            client_recv_time: unix_time_micros(),
+          };
+          if let Some(estimate) = session.time_sync.push(sample) {
+            session.log.write(session.tick, SessionEvent::TimeSync {
+              offset_us: estimate.offset,
+              dispersion_us: estimate.dispersion,
+              rtt_us: estimate.rtt,
+            });
+          }
+        }
         p: flo_net::proto::flo_node::PacketNodeGameStatusUpdate => {
-          tracing::debug!(game_id = p.game_id, "update game status: {:?}", p);
+          session.log.write(session.tick, SessionEvent::GameStatus {
+            status: NodeGameStatus::unpack_enum(p.status()),
+          });
           flo_log::result_ok!(
             "send NodeStreamEvent::GameStatusUpdate",
             client.notify(LanEvent::NodeStreamEvent {
@@ -644,32 +840,96 @@ impl Connection {
             }).await
           );
         }
+        p: proto::PacketClientIdleWarning => {
+          tracing::warn!(game_id, "not loaded, removed from the game in {}s", p.kick_in_secs);
+          flo_log::result_ok!(
+            "send NodeStreamEvent::IdleWarning",
+            client.notify(LanEvent::NodeStreamEvent {
+              game_id,
+              inner: NodeStreamEvent::IdleWarning(p)
+            }).await
+          );
+        }
       }
     }
     Ok(())
   }
 }
 
+/// Calls fail if the worker doesn't accept them in time or the stream is shut down,
+/// so a hung node stream can't stall the game loop
 #[derive(Debug, Clone)]
 pub struct NodeStreamSender {
   tx: Sender<WorkerMsg>,
+  ct: CancellationToken,
+  report_slot_status_timeout: Duration,
+  send_w3gs_timeout: Duration,
 }
 
 impl NodeStreamSender {
-  pub async fn report_slot_status(&mut self, status: SlotClientStatus) -> Result<()> {
-    if let Err(_err) = self.tx.send(WorkerMsg::StatusUpdate(status)).await {
-      tracing::error!("report_slot_status failed");
+  /// Uses `timeout` for every call instead of the configured timeouts
+  pub fn with_timeout(&self, timeout: Duration) -> Self {
+    Self {
+      report_slot_status_timeout: timeout,
+      send_w3gs_timeout: timeout,
+      ..self.clone()
     }
-    Ok(())
+  }
+
+  pub async fn report_slot_status(&mut self, status: SlotClientStatus) -> Result<()> {
+    let timeout = self.report_slot_status_timeout;
+    self
+      .send(
+        WorkerMsg::StatusUpdate(status),
+        timeout,
+        "report_slot_status",
+      )
+      .await
   }
 
   #[inline]
   pub async fn send_w3gs(&mut self, pkt: W3GSPacket) -> Result<()> {
-    let type_id = pkt.type_id();
-    self.tx.send(WorkerMsg::W3GS(pkt)).await.err().map(|_err| {
-      tracing::error!("node stream send cancelled: {:?}", type_id);
-    });
-    Ok(())
+    let timeout = self.send_w3gs_timeout;
+    self.send(WorkerMsg::W3GS(pkt), timeout, "send_w3gs").await
+  }
+
+  /// Sends packets received from the game in the same poll cycle
+  pub async fn send_w3gs_batch(&mut self, pkts: Vec<W3GSPacket>) -> Result<()> {
+    let timeout = self.send_w3gs_timeout;
+    self
+      .send(WorkerMsg::W3GSBatch(pkts), timeout, "send_w3gs_batch")
+      .await
+  }
+
+  /// Resets the node connection after the game loop made no progress for `idle`
+  pub async fn report_game_stalled(&mut self, idle: Duration, last: GameProgress) -> Result<()> {
+    let timeout = self.report_slot_status_timeout;
+    self
+      .send(
+        WorkerMsg::GameStalled { idle, last },
+        timeout,
+        "report_game_stalled",
+      )
+      .await
+  }
+
+  /// A stopped worker is only logged, the session reports how it ended
+  async fn send(&mut self, msg: WorkerMsg, timeout: Duration, name: &'static str) -> Result<()> {
+    tokio::select! {
+      biased;
+      res = self.tx.send(msg) => {
+        if res.is_err() {
+          tracing::error!("node stream {} cancelled", name);
+        }
+        Ok(())
+      }
+      _ = self.ct.cancelled() => {
+        Err(Error::TaskCancelled(anyhow::format_err!("node stream {}: shut down", name)))
+      }
+      _ = sleep(timeout) => {
+        Err(Error::Timeout(anyhow::format_err!("node stream {}: {:?}", name, timeout)))
+      }
+    }
   }
 }
 
@@ -680,9 +940,57 @@ enum ConnectionRunResult {
   NodeLeft,
 }
 
+fn encode_slot_status(status: SlotClientStatus) -> Result<Frame> {
+  let mut pkt = flo_net::proto::flo_node::PacketClientUpdateSlotClientStatusRequest::default();
+  pkt.set_status(status.into_proto_enum());
+  Ok(pkt.encode_as_frame()?)
+}
+
+/// Position of a status in the lobby and load screen, `None` if the player is gone.
+/// The node marks a player disconnected until the player reconnects.
+fn slot_status_rank(status: SlotClientStatus) -> Option<u8> {
+  match status {
+    SlotClientStatus::Pending | SlotClientStatus::Disconnected => Some(0),
+    SlotClientStatus::Connected => Some(1),
+    SlotClientStatus::Joined => Some(2),
+    SlotClientStatus::Loading => Some(3),
+    SlotClientStatus::Loaded => Some(4),
+    SlotClientStatus::Left | SlotClientStatus::LoadFailed => None,
+  }
+}
+
+/// A failed connect to the node is retried, possibly on the standby address
+fn should_retry_connect(err: &Error) -> bool {
+  err.code().is_retryable()
+}
+
+/// The node is behind the last status sent to it
+fn should_resend_slot_status(
+  status: SlotClientStatus,
+  node_status: Option<SlotClientStatus>,
+) -> bool {
+  match (
+    slot_status_rank(status),
+    node_status.and_then(slot_status_rank),
+  ) {
+    (Some(local), Some(node)) => node < local,
+    _ => false,
+  }
+}
+
 enum WorkerMsg {
   StatusUpdate(SlotClientStatus),
   W3GS(W3GSPacket),
+  W3GSBatch(Vec<W3GSPacket>),
+  GameStalled { idle: Duration, last: GameProgress },
+}
+
+/// Last packets seen by a game loop, reported if it stalls
+#[derive(Debug, Clone, Default)]
+pub struct GameProgress {
+  pub ticks: u32,
+  pub last_game_packet: Option<W3GSPacketTypeId>,
+  pub last_node_packet: Option<W3GSPacketTypeId>,
 }
 
 #[derive(Debug, PartialEq, Hash, Eq, Clone)]
@@ -708,6 +1016,7 @@ pub enum NodeStreamEvent {
   SlotClientStatusUpdate(SlotClientStatusUpdate),
   GameStatusSnapshot(NodeGameStatusSnapshot),
   GameStatusUpdate(GameStatusUpdate),
+  IdleWarning(proto::PacketClientIdleWarning),
   Disconnected,
 }
 
@@ -722,3 +1031,88 @@ pub struct SlotClientStatusUpdate {
   #[s2_grpc(proto_enum)]
   pub status: SlotClientStatus,
 }
+
+#[tokio::test]
+async fn test_node_stream_sender_timeout() {
+  let (tx, _rx) = channel(1);
+  let ct = CancellationToken::new();
+  let mut sender = NodeStreamSender {
+    tx,
+    ct: ct.clone(),
+    report_slot_status_timeout: Duration::from_secs(60),
+    send_w3gs_timeout: Duration::from_secs(60),
+  };
+  sender
+    .report_slot_status(SlotClientStatus::Joined)
+    .await
+    .unwrap();
+
+  // the worker doesn't read, the channel is full
+  let res = sender
+    .with_timeout(Duration::from_millis(10))
+    .report_slot_status(SlotClientStatus::Loading)
+    .await;
+  assert!(matches!(res, Err(Error::Timeout(_))));
+
+  ct.cancel();
+  let res = sender.report_slot_status(SlotClientStatus::Loading).await;
+  assert!(matches!(res, Err(Error::TaskCancelled(_))));
+}
+
+#[test]
+fn test_should_resend_slot_status() {
+  use SlotClientStatus::*;
+  assert!(should_resend_slot_status(Loaded, Some(Loading)));
+  assert!(should_resend_slot_status(Joined, Some(Connected)));
+  assert!(should_resend_slot_status(Loaded, Some(Disconnected)));
+  assert!(!should_resend_slot_status(Loading, Some(Loading)));
+  assert!(!should_resend_slot_status(Loading, Some(Loaded)));
+  assert!(!should_resend_slot_status(Loaded, Some(Left)));
+  assert!(!should_resend_slot_status(LoadFailed, Some(Loading)));
+  assert!(!should_resend_slot_status(Loaded, None));
+}
+
+#[test]
+fn test_should_retry_connect() {
+  use flo_net::error::Error as NetError;
+  use flo_net::proto::flo_node::ClientConnectRejectReason::*;
+
+  let retry = |err: Error| should_retry_connect(&err);
+  let rejected = |reason| Error::NodeConnectionRejected(reason, String::new());
+
+  assert!(retry(Error::Io(
+    std::io::ErrorKind::ConnectionRefused.into()
+  )));
+  assert!(retry(Error::Net(NetError::StreamTimeout)));
+  assert!(retry(Error::StreamClosed));
+  assert!(retry(rejected(Multi)));
+  assert!(retry(rejected(Standby)));
+
+  assert!(!retry(rejected(InvalidToken)));
+  assert!(!retry(rejected(Maintenance)));
+  assert!(!retry(rejected(Unknown)));
+  assert!(!retry(Error::Net(NetError::PacketFieldNotPresent)));
+  assert!(!retry(Error::Net(NetError::unexpected_packet_type_id(
+    PacketTypeId::Ping
+  ))));
+  assert!(!retry(Error::InvalidObserverDataFrame));
+}
+
+#[test]
+fn test_slot_status_transitions() {
+  use SlotClientStatus::*;
+  assert!(Pending.can_transition_to(Connected));
+  assert!(Disconnected.can_transition_to(Loaded));
+  assert!(Loading.can_transition_to(LoadFailed));
+  assert!(LoadFailed.can_transition_to(Left));
+  assert!(!Left.can_transition_to(Connected));
+  assert!(!Loaded.can_transition_to(Loading));
+  assert!(!Connected.can_transition_to(Loading));
+
+  assert!(Connected.client_can_transition_to(Joined));
+  assert!(Joined.client_can_transition_to(Connected));
+  assert!(Loading.client_can_transition_to(Loaded));
+  assert!(!Loading.client_can_transition_to(Connected));
+  assert!(!Disconnected.client_can_transition_to(Loaded));
+  assert!(!Loaded.client_can_transition_to(Disconnected));
+}
@@ -2,10 +2,11 @@ use super::send_queue::SendQueue;
 use crate::error::{Error, Result};
 use crate::lan::game::slot::{LanSlotInfo, SelfPlayer};
 use crate::platform::{GetClientPlatformInfo, OpenMap, Platform};
+use flo_events::{GameEvent, GameEventBuilder};
 use flo_lan::MdnsPublisher;
 use flo_observer::record::GameRecordData;
 use flo_state::Addr;
-use flo_types::observer::GameInfo;
+use flo_types::observer::{GameInfo, LiveStats};
 use flo_util::binary::SockAddr;
 use flo_w3gs::action::IncomingAction;
 use flo_w3gs::chat::ChatFromHost;
@@ -24,12 +25,13 @@ use flo_w3gs::protocol::packet::ProtoBufPayload;
 use flo_w3gs::protocol::player::{PlayerInfo, PlayerProfileMessage, PlayerSkinsMessage};
 use flo_w3map::MapChecksum;
 use futures::Stream;
+use parking_lot::Mutex;
 use std::collections::VecDeque;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{atomic::AtomicU64, Arc};
 use std::time::{Duration, Instant, SystemTime};
-use tokio::sync::Notify;
+use tokio::sync::{watch, Notify};
 use tokio::time::sleep;
 use tokio_stream::StreamExt;
 
@@ -45,6 +47,7 @@ pub struct ObserverGameHost<S> {
   source: S,
   shared: ObserverHostShared,
   game_version: String,
+  events: GameEventBuilder,
 }
 
 impl<S> ObserverGameHost<S>
@@ -97,6 +100,14 @@ where
     );
 
     let game_id = info.id;
+    let events = GameEventBuilder::new(
+      info
+        .slots
+        .iter()
+        .enumerate()
+        .filter(|(_, slot)| slot.settings.team != 24)
+        .filter_map(|(idx, slot)| Some(((idx + 1) as u8, slot.player.as_ref()?.id))),
+    );
 
     Ok(Self {
       map_checksum: map.checksum,
@@ -107,6 +118,7 @@ where
       source,
       shared: ObserverHostShared::new(game_id, delay_secs),
       game_version: client_info.version,
+      events,
     })
   }
 
@@ -129,7 +141,8 @@ where
       SelfPlayer::StreamObserver,
       self.info.random_seed,
       &self.info.slots,
-      self.info.map.twelve_p
+      self.info.map.twelve_p,
+      &Default::default(),
     )?;
 
     let mut stream: W3GSStream = loop {
@@ -179,6 +192,16 @@ where
 
     tracing::debug!("started duration = {}", started_duration_millis);
 
+    // joined mid-game, fast-forward until caught up to the delay point
+    if start_time_millis > 0
+      && started_duration_millis - self.delay_millis.unwrap_or_default()
+        > BUFFER_DURATION.as_millis() as i64
+    {
+      self
+        .shared
+        .set_speed(flo_constants::OBSERVER_FAST_FORWARDING_SPEED);
+    }
+
     let get_aprox_game_time = || {
       start_time_millis
         + started_duration_millis
@@ -382,6 +405,12 @@ where
     checksums: &mut VecDeque<u32>,
     left_players: &mut Vec<u8>,
   ) -> Result<()> {
+    self.events.put_record(&record);
+    let events = self.events.take_events();
+    if !events.is_empty() {
+      self.shared.events.lock().extend(events);
+    }
+
     match record {
       GameRecordData::W3GS(pkt) => match pkt.type_id() {
         PacketTypeId::IncomingAction | PacketTypeId::IncomingAction2 => {
@@ -664,6 +693,8 @@ pub struct ObserverHostShared {
   stream_finished: Arc<AtomicBool>,
   stream_total_millis: Arc<AtomicU64>,
   finished: Arc<AtomicBool>,
+  live_stats: Option<watch::Receiver<Option<LiveStats>>>,
+  events: Arc<Mutex<Vec<GameEvent>>>,
 }
 
 impl ObserverHostShared {
@@ -708,6 +739,21 @@ impl ObserverHostShared {
   pub fn finished_notify(&self) -> &Notify {
     self.finished_notify.as_ref()
   }
+
+  pub fn with_live_stats(mut self, live_stats: watch::Receiver<Option<LiveStats>>) -> Self {
+    self.live_stats = Some(live_stats);
+    self
+  }
+
+  /// Latest live stats, `None` if the source doesn't provide them
+  pub fn live_stats(&self) -> Option<LiveStats> {
+    self.live_stats.as_ref().and_then(|rx| rx.borrow().clone())
+  }
+
+  /// Events of the records sent to the game so far, starting at index `since`
+  pub fn events(&self, since: usize) -> Vec<GameEvent> {
+    self.events.lock().iter().skip(since).cloned().collect()
+  }
 }
 
 impl ObserverHostShared {
@@ -724,6 +770,8 @@ impl ObserverHostShared {
       stream_finished: Arc::new(AtomicBool::new(false)),
       stream_total_millis: Arc::new(AtomicU64::new(0)),
       finished: Arc::new(AtomicBool::new(false)),
+      live_stats: None,
+      events: Arc::new(Mutex::new(vec![])),
     }
   }
 }
@@ -41,6 +41,9 @@ impl Service<StartConfig> for ObserverClient {
 #[derive(Debug, Deserialize, Clone)]
 pub struct WatchGame {
   pub token: String,
+  /// Streams from the node hosting the game instead of the stats host
+  #[serde(default)]
+  pub node_addr: Option<String>,
 }
 
 impl Message for WatchGame {
@@ -52,23 +55,26 @@ impl Handler<WatchGame> for ObserverClient {
   async fn handle(
     &mut self,
     ctx: &mut flo_state::Context<Self>,
-    WatchGame { token }: WatchGame,
+    WatchGame { token, node_addr }: WatchGame,
   ) -> Result<ObserverHostShared> {
-    let config = self.platform.send(GetClientConfig).await?;
-    tracing::debug!("stats host: {}", config.stats_host);
-
-    let (game, source) = NetworkSource::connect(
-      &format!(
+    let addr = if let Some(node_addr) = node_addr {
+      tracing::debug!("node: {}", node_addr);
+      format!("{}:{}", node_addr, flo_constants::NODE_OBSERVER_PORT)
+    } else {
+      let config = self.platform.send(GetClientConfig).await?;
+      tracing::debug!("stats host: {}", config.stats_host);
+      format!(
         "{}:{}",
         config.stats_host,
         flo_constants::OBSERVER_SOCKET_PORT
-      ),
-      token,
-    )
-    .await?;
+      )
+    };
+
+    let (game, source) = NetworkSource::connect(&addr, token).await?;
+    let live_stats = source.live_stats();
     let host =
       ObserverGameHost::new(game, source.delay_secs(), source, self.platform.clone()).await?;
-    let shared = host.shared();
+    let shared = host.shared().with_live_stats(live_stats);
     let ct = CancellationToken::new();
     self.playing.replace(Playing { ct: ct.clone() });
     ctx.spawn(async move {
@@ -1,11 +1,14 @@
 use crate::error::{Error, Result};
 use bytes::Buf;
 use flo_net::{
-  observer::{PacketObserverConnect, PacketObserverConnectAccept, PacketObserverConnectReject},
+  observer::{
+    PacketObserverConnect, PacketObserverConnectAccept, PacketObserverConnectReject,
+    PacketObserverLiveStats,
+  },
   stream::FloStream,
 };
 use flo_observer::record::GameRecordData;
-use flo_types::observer::GameInfo;
+use flo_types::observer::{GameInfo, LiveStats};
 use futures::Stream;
 use s2_grpc_utils::S2ProtoUnpack;
 use std::{
@@ -13,13 +16,14 @@ use std::{
   task::{Context, Poll},
 };
 use tokio::net::ToSocketAddrs;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio_util::sync::CancellationToken;
 
 pub struct NetworkSource {
   rx: mpsc::UnboundedReceiver<Result<GameRecordData>>,
   ct: CancellationToken,
   delay_secs: Option<i64>,
+  live_stats: watch::Receiver<Option<LiveStats>>,
 }
 
 impl Drop for NetworkSource {
@@ -55,27 +59,35 @@ impl NetworkSource {
     };
 
     let (tx, rx) = mpsc::unbounded_channel();
+    let (live_stats_tx, live_stats) = watch::channel(None);
 
     tokio::spawn(
       Worker {
         transport,
         tx,
+        live_stats_tx,
         ct: ct.clone(),
       }
       .run(),
     );
 
-    Ok((game, Self { rx, ct, delay_secs}))
+    Ok((game, Self { rx, ct, delay_secs, live_stats }))
   }
 
   pub fn delay_secs(&self) -> Option<i64> {
     self.delay_secs.clone()
   }
+
+  /// Latest live stats pushed by the server, only sent by nodes
+  pub fn live_stats(&self) -> watch::Receiver<Option<LiveStats>> {
+    self.live_stats.clone()
+  }
 }
 
 struct Worker {
   transport: FloStream,
   tx: mpsc::UnboundedSender<Result<GameRecordData>>,
+  live_stats_tx: watch::Sender<Option<LiveStats>>,
   ct: CancellationToken,
 }
 
@@ -126,6 +138,16 @@ impl Worker {
                       },
                   };
                 },
+                PacketTypeId::ObserverLiveStats => {
+                  match frame.decode::<PacketObserverLiveStats>().map_err(Error::from).and_then(|p| Ok(LiveStats::unpack(p)?)) {
+                    Ok(stats) => {
+                      self.live_stats_tx.send(Some(stats)).ok();
+                    },
+                    Err(err) => {
+                      tracing::warn!("invalid live stats: {}", err)
+                    },
+                  }
+                },
                 PacketTypeId::ObserverDataEnd => {
                   tracing::debug!("observer data stream ended: {} bytes", total_bytes);
                   break;
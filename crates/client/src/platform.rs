@@ -5,13 +5,15 @@ use flo_config::ClientConfig;
 use flo_platform::error::Error as PlatformError;
 use flo_platform::ClientPlatformInfo;
 use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
-use flo_types::game::{MapDetail, MapForceOwned, MapPlayerOwned};
+use flo_types::game::{MapDetail, MapForceOwned, MapPlayerOwned, MapScanEntry};
+use flo_w3map::scan::MapScanner;
 use flo_w3map::{MapChecksum, W3Map};
 use flo_w3storage::W3Storage;
 use futures::future::{abortable, AbortHandle};
 use futures::FutureExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::sync::Arc;
 use tokio::sync::mpsc::WeakSender;
 
 #[derive(Debug)]
@@ -21,6 +23,7 @@ pub struct Platform {
   info: Result<ClientPlatformInfo, PlatformStateError>,
   storage: Option<W3Storage>,
   maps: Option<Value>,
+  map_scanner: Arc<MapScanner>,
   test_game_abort_handle: Option<AbortHandle>,
 }
 
@@ -33,6 +36,7 @@ impl Platform {
       info,
       storage: None,
       maps: None,
+      map_scanner: Arc::new(MapScanner::new()),
       test_game_abort_handle: None,
     })
   }
@@ -72,6 +76,7 @@ impl Handler<Reload> for Platform {
     self.config = config;
     self.info = info;
     self.maps.take();
+    self.map_scanner.clear();
     Ok(())
   }
 }
@@ -110,13 +115,7 @@ impl Handler<GetMapList> for Platform {
       }
     }
 
-    let paths = self
-      .with_storage(move |storage| storage.list_storage_files("maps\\*").map_err(Into::into))
-      .await?;
-    let paths: Vec<_> = paths
-      .into_iter()
-      .filter(|v| !v.contains("\\scenario\\"))
-      .collect();
+    let paths = self.list_map_paths().await?;
     let tree = flo_w3storage::path_tree::PathTree::from_paths(&paths)?;
     let value = serde_json::to_value(&tree)?;
     self.maps = Some(value.clone());
@@ -124,6 +123,69 @@ impl Handler<GetMapList> for Platform {
   }
 }
 
+/// Parses metadata and checksums of the given maps, or all local maps if
+/// `paths` is `None`.
+pub struct ScanMaps {
+  pub paths: Option<Vec<String>>,
+}
+
+impl Message for ScanMaps {
+  type Result = Result<Vec<MapScanEntry>>;
+}
+
+#[async_trait]
+impl Handler<ScanMaps> for Platform {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ScanMaps { paths }: ScanMaps,
+  ) -> <ScanMaps as Message>::Result {
+    let paths = match paths {
+      Some(paths) => paths,
+      None => self.list_map_paths().await?,
+    };
+    let scanner = self.map_scanner.clone();
+    let items = self
+      .with_storage(move |storage| Ok(scanner.scan(storage, &paths)))
+      .await?;
+    Ok(
+      items
+        .iter()
+        .map(|item| match item.result {
+          Ok(ref map) => MapScanEntry {
+            path: item.path.clone(),
+            sha1: Some(map.checksum.get_sha1_hex_string()),
+            checksum: Some(map.checksum.xoro),
+            name: map.name.clone(),
+            author: map.author.clone(),
+            suggested_players: map.suggested_players.clone(),
+            width: map.width,
+            height: map.height,
+            num_players: map.num_players,
+            twelve_p: map.twelve_p,
+            melee: map.melee,
+            error: None,
+          },
+          Err(ref err) => MapScanEntry {
+            path: item.path.clone(),
+            sha1: None,
+            checksum: None,
+            name: String::new(),
+            author: String::new(),
+            suggested_players: String::new(),
+            width: 0,
+            height: 0,
+            num_players: 0,
+            twelve_p: false,
+            melee: false,
+            error: Some(err.clone()),
+          },
+        })
+        .collect(),
+    )
+  }
+}
+
 #[derive(Default)]
 pub struct GetClientPlatformInfo {
   pub force_reload: bool,
@@ -351,6 +413,18 @@ impl Platform {
     })
   }
 
+  async fn list_map_paths(&mut self) -> Result<Vec<String>> {
+    let paths = self
+      .with_storage(move |storage| storage.list_files("maps\\*").map_err(Into::into))
+      .await?;
+    Ok(
+      paths
+        .into_iter()
+        .filter(|v| !v.contains("\\scenario\\"))
+        .collect(),
+    )
+  }
+
   async fn start_test_game(
     &mut self,
     ctx: &mut Context<Self>,
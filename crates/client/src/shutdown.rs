@@ -0,0 +1,125 @@
+//! Ordered shutdown of the client.
+//!
+//! The LAN game stops advertising first so it can't be joined anymore,
+//! then the node stream leaves the game and the replay is saved, and
+//! last the controller stream leaves the lobby before it closes,
+//! so closing the client doesn't leave a ghost lobby on the controller.
+
+use crate::controller::{ControllerClient, Shutdown};
+use crate::lan::{Lan, TakeLanGame};
+use flo_state::{async_trait, Addr};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const LAN_GAME_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+const CONTROLLER_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The parts of the client closed by [`shutdown`], in order.
+#[async_trait]
+trait ShutdownTarget {
+  /// Stops the LAN game, leaves the node and saves the replay.
+  async fn close_lan_game(&self);
+  /// Leaves the lobby and closes the controller stream.
+  async fn close_controller(&self);
+}
+
+struct Client<'a> {
+  lan: &'a Addr<Lan>,
+  controller: &'a Addr<ControllerClient>,
+}
+
+#[async_trait]
+impl<'a> ShutdownTarget for Client<'a> {
+  async fn close_lan_game(&self) {
+    match self.lan.send(TakeLanGame).await {
+      Ok(Some(game)) => {
+        tracing::info!(game_id = game.game_id(), "close lan game");
+        game.close().await
+      }
+      Ok(None) => {}
+      Err(err) => tracing::error!("take lan game: {}", err),
+    }
+  }
+
+  async fn close_controller(&self) {
+    match self.controller.send(Shutdown).await {
+      Ok(Some(closed)) => {
+        closed.await.ok();
+      }
+      Ok(None) => {}
+      Err(err) => tracing::error!("close controller stream: {}", err),
+    }
+  }
+}
+
+pub(crate) async fn shutdown(lan: &Addr<Lan>, controller: &Addr<ControllerClient>) {
+  shutdown_with(&Client { lan, controller }).await
+}
+
+async fn shutdown_with<T: ShutdownTarget + Sync>(target: &T) {
+  tracing::info!("shutting down");
+
+  if timeout(LAN_GAME_CLOSE_TIMEOUT, target.close_lan_game())
+    .await
+    .is_err()
+  {
+    tracing::error!("close lan game timeout");
+  }
+
+  if timeout(CONTROLLER_CLOSE_TIMEOUT, target.close_controller())
+    .await
+    .is_err()
+  {
+    tracing::error!("close controller stream timeout");
+  }
+
+  tracing::info!("shutdown completed");
+}
+
+#[cfg(test)]
+struct Recorder {
+  events: parking_lot::Mutex<Vec<&'static str>>,
+  lan_delay: Duration,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl ShutdownTarget for Recorder {
+  async fn close_lan_game(&self) {
+    self.events.lock().push("lan start");
+    tokio::time::sleep(self.lan_delay).await;
+    self.events.lock().push("lan end");
+  }
+
+  async fn close_controller(&self) {
+    self.events.lock().push("controller start");
+    tokio::task::yield_now().await;
+    self.events.lock().push("controller end");
+  }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_shutdown_order() {
+  let target = Recorder {
+    events: Default::default(),
+    lan_delay: Duration::from_secs(1),
+  };
+  shutdown_with(&target).await;
+  assert_eq!(
+    *target.events.lock(),
+    vec!["lan start", "lan end", "controller start", "controller end"]
+  );
+
+  // a stuck LAN game doesn't keep the controller stream open
+  let target = Recorder {
+    events: Default::default(),
+    lan_delay: LAN_GAME_CLOSE_TIMEOUT * 2,
+  };
+  let started = tokio::time::Instant::now();
+  shutdown_with(&target).await;
+  assert_eq!(
+    *target.events.lock(),
+    vec!["lan start", "controller start", "controller end"]
+  );
+  assert_eq!(started.elapsed(), LAN_GAME_CLOSE_TIMEOUT);
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub mod error;
 
@@ -16,6 +17,42 @@ pub struct ClientConfig {
   pub version: Option<String>,
   pub ptr: Option<bool>,
   pub user_battlenet_client_id: Option<String>,
+  pub node_stream: NodeStreamConfig,
+}
+
+/// Timeouts of the calls from a game to its node stream worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeStreamConfig {
+  pub report_slot_status_timeout_ms: u64,
+  pub send_w3gs_timeout_ms: u64,
+  /// The node connection is reset if neither the game nor the node sent a packet for this long
+  pub game_stall_timeout_ms: u64,
+}
+
+impl Default for NodeStreamConfig {
+  fn default() -> Self {
+    // the worker stops reading while reconnecting, which gives up after 60s
+    NodeStreamConfig {
+      report_slot_status_timeout_ms: 90_000,
+      send_w3gs_timeout_ms: 90_000,
+      game_stall_timeout_ms: 120_000,
+    }
+  }
+}
+
+impl NodeStreamConfig {
+  pub fn report_slot_status_timeout(&self) -> Duration {
+    Duration::from_millis(self.report_slot_status_timeout_ms)
+  }
+
+  pub fn send_w3gs_timeout(&self) -> Duration {
+    Duration::from_millis(self.send_w3gs_timeout_ms)
+  }
+
+  pub fn game_stall_timeout(&self) -> Duration {
+    Duration::from_millis(self.game_stall_timeout_ms)
+  }
 }
 
 impl Default for ClientConfig {
@@ -29,6 +66,7 @@ impl Default for ClientConfig {
       version: None,
       ptr: None,
       user_battlenet_client_id: None,
+      node_stream: NodeStreamConfig::default(),
     }
   }
 }
@@ -51,7 +89,8 @@ impl ClientConfig {
       pub controller_host: Option<String>,
       pub stats_host: Option<String>,
       pub version: Option<String>,
-      pub ptr: Option<bool>
+      pub ptr: Option<bool>,
+      pub node_stream: Option<NodeStreamConfig>,
     }
 
     let config: TomlConfig = toml::from_str(&fs::read_to_string("flo.toml")?)?;
@@ -68,6 +107,7 @@ impl ClientConfig {
       version: config.version,
       ptr: config.ptr,
       user_battlenet_client_id: None,
+      node_stream: config.node_stream.unwrap_or_default(),
     };
 
     config.apply_env();
@@ -5,6 +5,7 @@ pub const CONTROLLER_HOST: &str = "service.w3flo.com";
 pub const STATS_HOST: &str = "stats.w3flo.com";
 pub const CONTROLLER_GRPC_PORT: u16 = 3549;
 pub const CONTROLLER_SOCKET_PORT: u16 = 3550;
+pub const CONTROLLER_HTTP_PORT: u16 = 3561;
 pub const CLIENT_WS_PORT: u16 = 3551;
 pub const CLIENT_ORIGINS: &[&str] = &[
   "http://localhost:3000",
@@ -21,6 +22,12 @@ pub const NODE_CLIENT_PORT: u16 = 3554;
 pub const NODE_CLIENT_PORT_OFFSET: u16 = NODE_CLIENT_PORT - NODE_ECHO_PORT;
 pub const NODE_HTTP_PORT: u16 = 3555;
 pub const NODE_HTTP_PORT_OFFSET: u16 = NODE_HTTP_PORT - NODE_ECHO_PORT;
+pub const NODE_OBSERVER_PORT: u16 = 3559;
+pub const NODE_OBSERVER_PORT_OFFSET: u16 = NODE_OBSERVER_PORT - NODE_ECHO_PORT;
+pub const NODE_OBSERVER_HTTP_PORT: u16 = 3560;
+pub const NODE_OBSERVER_HTTP_PORT_OFFSET: u16 = NODE_OBSERVER_HTTP_PORT - NODE_ECHO_PORT;
+pub const NODE_STANDBY_PORT: u16 = 3562;
+pub const NODE_STANDBY_PORT_OFFSET: u16 = NODE_STANDBY_PORT - NODE_ECHO_PORT;
 pub const MIN_FLO_VERSION: version::Version = Version {
   major: 0,
   minor: 9,
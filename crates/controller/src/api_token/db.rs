@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::api_token::{hash_secret, ApiScopes, ApiToken, ApiTokenRow};
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::api_token;
+
+const TOKEN_PREFIX: &str = "flo_";
+const TOKEN_LEN: usize = 40;
+
+pub fn list(conn: &DbConn, api_client_id: i32) -> Result<Vec<ApiToken>> {
+  api_token::table
+    .select(ApiToken::COLUMNS)
+    .filter(api_token::api_client_id.eq(api_client_id))
+    .order(api_token::id)
+    .load::<ApiTokenRow>(conn)
+    .map(|rows| rows.into_iter().map(Into::into).collect())
+    .map_err(Into::into)
+}
+
+/// The secret is not stored, it can only be read once after issuing
+pub struct IssuedApiToken {
+  pub token: ApiToken,
+  pub secret: String,
+}
+
+pub fn create(
+  conn: &DbConn,
+  api_client_id: i32,
+  name: &str,
+  scopes: ApiScopes,
+  expires_at: Option<DateTime<Utc>>,
+) -> Result<IssuedApiToken> {
+  #[derive(Insertable)]
+  #[table_name = "api_token"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    name: &'a str,
+    token_hash: &'a str,
+    scopes: i32,
+    expires_at: Option<DateTime<Utc>>,
+  }
+
+  if scopes.bits() == 0 || scopes.is_all() {
+    return Err(Error::ApiTokenScopesInvalid);
+  }
+
+  let secret = generate_secret();
+  let token = diesel::insert_into(api_token::table)
+    .values(&Insert {
+      api_client_id,
+      name,
+      token_hash: &hash_secret(secret.as_bytes()),
+      scopes: scopes.bits(),
+      expires_at,
+    })
+    .returning(ApiToken::COLUMNS)
+    .get_result::<ApiTokenRow>(conn)?
+    .into();
+
+  Ok(IssuedApiToken { token, secret })
+}
+
+/// Replaces the token secret, the old secret stops working immediately
+pub fn rotate(conn: &DbConn, api_client_id: i32, id: i32) -> Result<IssuedApiToken> {
+  let secret = generate_secret();
+  let token = diesel::update(
+    api_token::table.filter(
+      api_token::id
+        .eq(id)
+        .and(api_token::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .set(api_token::token_hash.eq(hash_secret(secret.as_bytes())))
+  .returning(ApiToken::COLUMNS)
+  .get_result::<ApiTokenRow>(conn)
+  .optional()?
+  .ok_or_else(|| Error::ApiTokenNotFound)?
+  .into();
+
+  Ok(IssuedApiToken { token, secret })
+}
+
+pub fn remove(conn: &DbConn, api_client_id: i32, id: i32) -> Result<()> {
+  let n = diesel::delete(
+    api_token::table.filter(
+      api_token::id
+        .eq(id)
+        .and(api_token::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .execute(conn)?;
+  if n == 0 {
+    return Err(Error::ApiTokenNotFound);
+  }
+  Ok(())
+}
+
+#[derive(Debug, Queryable)]
+pub struct ApiTokenCredential {
+  pub id: i32,
+  pub api_client_id: i32,
+  pub token_hash: String,
+  pub scopes: i32,
+  pub expires_at: Option<DateTime<Utc>>,
+}
+
+pub fn get_active_credentials(conn: &DbConn) -> Result<Vec<ApiTokenCredential>> {
+  use diesel::dsl::sql;
+  api_token::table
+    .select((
+      api_token::id,
+      api_token::api_client_id,
+      api_token::token_hash,
+      api_token::scopes,
+      api_token::expires_at,
+    ))
+    .filter(
+      api_token::expires_at
+        .gt(sql("now()"))
+        .or(api_token::expires_at.is_null()),
+    )
+    .load(conn)
+    .map_err(Into::into)
+}
+
+fn generate_secret() -> String {
+  let body: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(TOKEN_LEN)
+    .map(char::from)
+    .collect();
+  format!("{}{}", TOKEN_PREFIX, body)
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_token_secret_hashed() {
+  use crate::api_token::ApiTokenScope;
+  crate::db::test_transaction(|conn| {
+    let api_client_id = crate::db::insert_test_api_client(conn, "organizer")?;
+    let scopes = vec![ApiTokenScope::ReadResults].into_iter().collect();
+    let issued = create(conn, api_client_id, "test", scopes, None)?;
+    let stored_hash = |id: i32| -> Result<String> {
+      api_token::table
+        .find(id)
+        .select(api_token::token_hash)
+        .first(conn)
+        .map_err(Into::into)
+    };
+    assert_eq!(
+      stored_hash(issued.token.id)?,
+      hash_secret(issued.secret.as_bytes())
+    );
+
+    let rotated = rotate(conn, api_client_id, issued.token.id)?;
+    assert_ne!(rotated.secret, issued.secret);
+    assert_eq!(
+      stored_hash(issued.token.id)?,
+      hash_secret(rotated.secret.as_bytes())
+    );
+    Ok(())
+  })
+  .await;
+}
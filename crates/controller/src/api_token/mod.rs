@@ -0,0 +1,177 @@
+pub mod db;
+
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::schema::api_token;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::ApiTokenScope")]
+pub enum ApiTokenScope {
+  CreateGames = 0,
+  ReadResults = 1,
+  ManageBans = 2,
+}
+
+/// Set of scopes granted to a credential.
+/// The API client secret itself is granted every scope.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct ApiScopes(i32);
+
+impl ApiScopes {
+  pub const ALL: ApiScopes = ApiScopes(-1);
+
+  pub fn from_bits(bits: i32) -> Self {
+    ApiScopes(bits)
+  }
+
+  pub fn bits(self) -> i32 {
+    self.0
+  }
+
+  pub fn contains(self, scope: ApiTokenScope) -> bool {
+    self.0 & Self::scope_bit(scope) != 0
+  }
+
+  pub fn insert(&mut self, scope: ApiTokenScope) {
+    self.0 |= Self::scope_bit(scope)
+  }
+
+  pub fn is_all(self) -> bool {
+    self == Self::ALL
+  }
+
+  pub fn to_vec(self) -> Vec<ApiTokenScope> {
+    [
+      ApiTokenScope::CreateGames,
+      ApiTokenScope::ReadResults,
+      ApiTokenScope::ManageBans,
+    ]
+    .iter()
+    .cloned()
+    .filter(|scope| self.contains(*scope))
+    .collect()
+  }
+
+  fn scope_bit(scope: ApiTokenScope) -> i32 {
+    1 << (scope as i32)
+  }
+}
+
+impl std::iter::FromIterator<ApiTokenScope> for ApiScopes {
+  fn from_iter<I: IntoIterator<Item = ApiTokenScope>>(iter: I) -> Self {
+    let mut scopes = ApiScopes::default();
+    for scope in iter {
+      scopes.insert(scope);
+    }
+    scopes
+  }
+}
+
+/// Secrets are only stored and compared as hex encoded SHA-256 digests
+pub fn hash_secret(secret: &[u8]) -> String {
+  Sha256::digest(secret)
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect()
+}
+
+#[derive(Debug)]
+pub struct ApiToken {
+  pub id: i32,
+  pub name: String,
+  pub scopes: Vec<ApiTokenScope>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+pub(crate) type ApiTokenRow = (
+  i32,
+  String,
+  i32,
+  Option<DateTime<Utc>>,
+  DateTime<Utc>,
+  DateTime<Utc>,
+);
+
+pub(crate) type ApiTokenColumns = (
+  api_token::id,
+  api_token::name,
+  api_token::scopes,
+  api_token::expires_at,
+  api_token::created_at,
+  api_token::updated_at,
+);
+
+impl ApiToken {
+  pub(crate) const COLUMNS: ApiTokenColumns = (
+    api_token::id,
+    api_token::name,
+    api_token::scopes,
+    api_token::expires_at,
+    api_token::created_at,
+    api_token::updated_at,
+  );
+}
+
+impl From<ApiTokenRow> for ApiToken {
+  fn from((id, name, scopes, expires_at, created_at, updated_at): ApiTokenRow) -> Self {
+    ApiToken {
+      id,
+      name,
+      scopes: ApiScopes::from_bits(scopes).to_vec(),
+      expires_at,
+      created_at,
+      updated_at,
+    }
+  }
+}
+
+impl S2ProtoPack<flo_grpc::controller::ApiToken> for ApiToken {
+  fn pack(self) -> Result<flo_grpc::controller::ApiToken, s2_grpc_utils::result::Error> {
+    Ok(flo_grpc::controller::ApiToken {
+      id: self.id,
+      name: self.name,
+      scopes: self
+        .scopes
+        .into_iter()
+        .map(|scope| {
+          let scope: flo_grpc::controller::ApiTokenScope = scope.into_proto_enum();
+          scope as i32
+        })
+        .collect(),
+      expires_at: self.expires_at.pack()?,
+      created_at: self.created_at.pack()?,
+      updated_at: self.updated_at.pack()?,
+    })
+  }
+}
+
+#[test]
+fn test_api_scopes() {
+  let scopes: ApiScopes = vec![ApiTokenScope::CreateGames, ApiTokenScope::ManageBans]
+    .into_iter()
+    .collect();
+  assert!(scopes.contains(ApiTokenScope::CreateGames));
+  assert!(!scopes.contains(ApiTokenScope::ReadResults));
+  assert!(scopes.contains(ApiTokenScope::ManageBans));
+  assert_eq!(
+    scopes.to_vec(),
+    vec![ApiTokenScope::CreateGames, ApiTokenScope::ManageBans]
+  );
+  assert!(ApiScopes::ALL.contains(ApiTokenScope::ReadResults));
+  assert!(!ApiScopes::default().contains(ApiTokenScope::ReadResults));
+}
+
+#[test]
+fn test_hash_secret() {
+  assert_eq!(
+    hash_secret(b"flo_secret"),
+    "fc1b24b227ac38f4ce90392e9d3669bd762275f1765f5605477a03df3bed9e6a"
+  );
+}
@@ -0,0 +1,176 @@
+use diesel::prelude::*;
+use serde_json::Value;
+
+use crate::audit::{AuditAction, AuditActor, AuditLog, AuditTarget, AuditTargetType};
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::audit_log;
+
+pub fn record(
+  conn: &DbConn,
+  actor: &AuditActor,
+  action: AuditAction,
+  target: AuditTarget,
+  detail: Value,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "audit_log"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    api_token_id: Option<i32>,
+    actor: Option<&'a str>,
+    action: AuditAction,
+    target_type: AuditTargetType,
+    target_id: Option<i32>,
+    detail: Value,
+  }
+
+  let (target_type, target_id) = target.split();
+  diesel::insert_into(audit_log::table)
+    .values(&Insert {
+      api_client_id: actor.api_client_id,
+      api_token_id: actor.api_token_id,
+      actor: actor.name.as_deref(),
+      action,
+      target_type,
+      target_id,
+      detail,
+    })
+    .execute(conn)?;
+  Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct ListAuditLogParams {
+  pub action: Option<AuditAction>,
+  pub target_type: Option<AuditTargetType>,
+  pub target_id: Option<i32>,
+  pub next_id: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct ListAuditLog {
+  pub logs: Vec<AuditLog>,
+  pub next_id: Option<i32>,
+}
+
+/// Most recent first
+pub fn list(
+  conn: &DbConn,
+  api_client_id: i32,
+  params: &ListAuditLogParams,
+) -> Result<ListAuditLog> {
+  const PAGE_SIZE: i64 = 200;
+  let mut q = audit_log::table
+    .select(AuditLog::COLUMNS)
+    .filter(audit_log::api_client_id.eq(api_client_id))
+    .order(audit_log::id.desc())
+    .limit(PAGE_SIZE + 1)
+    .into_boxed();
+
+  if let Some(action) = params.action {
+    q = q.filter(audit_log::action.eq(action));
+  }
+
+  if let Some(target_type) = params.target_type {
+    q = q.filter(audit_log::target_type.eq(target_type));
+  }
+
+  if let Some(target_id) = params.target_id {
+    q = q.filter(audit_log::target_id.eq(target_id));
+  }
+
+  if let Some(id) = params.next_id {
+    q = q.filter(audit_log::id.le(id));
+  }
+
+  let mut logs = q.load::<AuditLog>(conn)?;
+  let next_id = if logs.len() > PAGE_SIZE as usize {
+    let id = logs.last().map(|row| row.id);
+    logs.truncate(PAGE_SIZE as usize);
+    id
+  } else {
+    None
+  };
+
+  Ok(ListAuditLog { logs, next_id })
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_audit_log() {
+  use crate::db::insert_test_api_client;
+  use serde_json::json;
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "audit")?;
+    let other_api_client_id = insert_test_api_client(conn, "other")?;
+    let actor = |api_client_id| AuditActor {
+      api_client_id,
+      api_token_id: None,
+      name: Some("admin".to_string()),
+    };
+
+    record(
+      conn,
+      &actor(api_client_id),
+      AuditAction::PlayerBanCreate,
+      AuditTarget::Player(1),
+      json!({ "ban_type": "Chat" }),
+    )?;
+    record(
+      conn,
+      &actor(api_client_id),
+      AuditAction::GameAbort,
+      AuditTarget::Game(2),
+      json!({}),
+    )?;
+    record(
+      conn,
+      &actor(other_api_client_id),
+      AuditAction::GameAbort,
+      AuditTarget::Game(2),
+      json!({}),
+    )?;
+
+    let actions = |params: ListAuditLogParams| -> Result<Vec<(AuditAction, Option<i32>)>> {
+      Ok(
+        list(conn, api_client_id, &params)?
+          .logs
+          .into_iter()
+          .map(|log| (log.action, log.target_id))
+          .collect(),
+      )
+    };
+    assert_eq!(
+      actions(Default::default())?,
+      vec![
+        (AuditAction::GameAbort, Some(2)),
+        (AuditAction::PlayerBanCreate, Some(1))
+      ]
+    );
+    assert_eq!(
+      actions(ListAuditLogParams {
+        target_type: Some(AuditTargetType::Player),
+        ..Default::default()
+      })?,
+      vec![(AuditAction::PlayerBanCreate, Some(1))]
+    );
+    assert_eq!(
+      actions(ListAuditLogParams {
+        action: Some(AuditAction::GameAbort),
+        target_id: Some(2),
+        ..Default::default()
+      })?,
+      vec![(AuditAction::GameAbort, Some(2))]
+    );
+
+    let log = list(conn, api_client_id, &Default::default())?
+      .logs
+      .pop()
+      .unwrap();
+    assert_eq!(log.actor.as_deref(), Some("admin"));
+    assert_eq!(log.detail, json!({ "ban_type": "Chat" }));
+    Ok(())
+  })
+  .await;
+}
@@ -0,0 +1,142 @@
+pub mod db;
+
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::schema::audit_log;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::AuditAction")]
+pub enum AuditAction {
+  PlayerBanCreate = 0,
+  PlayerBanRemove = 1,
+  OrganizerBanCreate = 2,
+  OrganizerBanRemove = 3,
+  PenaltyAppealResolve = 4,
+  GameTerminate = 5,
+  SettingsReload = 6,
+  SeasonCreate = 7,
+  ApiTokenCreate = 8,
+  ApiTokenRotate = 9,
+  ApiTokenRemove = 10,
+  PlayerLinkConfirm = 11,
+  PlayerLinkDismiss = 12,
+  DiscordChannelCreate = 13,
+  DiscordChannelRemove = 14,
+  GameResultRule = 15,
+  GameAbort = 16,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::AuditTargetType")]
+pub enum AuditTargetType {
+  None = 0,
+  Player = 1,
+  Game = 2,
+  PlayerBan = 3,
+  OrganizerBan = 4,
+  Penalty = 5,
+  Season = 6,
+  ApiToken = 7,
+  PlayerLink = 8,
+  DiscordChannel = 9,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuditTarget {
+  None,
+  Player(i32),
+  Game(i32),
+  PlayerBan(i32),
+  OrganizerBan(i32),
+  Penalty(i32),
+  Season(i32),
+  ApiToken(i32),
+  PlayerLink(i32),
+  DiscordChannel(i32),
+}
+
+impl AuditTarget {
+  pub fn split(self) -> (AuditTargetType, Option<i32>) {
+    match self {
+      AuditTarget::None => (AuditTargetType::None, None),
+      AuditTarget::Player(id) => (AuditTargetType::Player, Some(id)),
+      AuditTarget::Game(id) => (AuditTargetType::Game, Some(id)),
+      AuditTarget::PlayerBan(id) => (AuditTargetType::PlayerBan, Some(id)),
+      AuditTarget::OrganizerBan(id) => (AuditTargetType::OrganizerBan, Some(id)),
+      AuditTarget::Penalty(id) => (AuditTargetType::Penalty, Some(id)),
+      AuditTarget::Season(id) => (AuditTargetType::Season, Some(id)),
+      AuditTarget::ApiToken(id) => (AuditTargetType::ApiToken, Some(id)),
+      AuditTarget::PlayerLink(id) => (AuditTargetType::PlayerLink, Some(id)),
+      AuditTarget::DiscordChannel(id) => (AuditTargetType::DiscordChannel, Some(id)),
+    }
+  }
+}
+
+/// The credential that performed an action.
+/// `name` is supplied by the caller to identify the admin behind a shared credential.
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+  pub api_client_id: i32,
+  /// `None` if the API client secret was used
+  pub api_token_id: Option<i32>,
+  pub name: Option<String>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct AuditLog {
+  pub id: i32,
+  pub api_token_id: Option<i32>,
+  pub actor: Option<String>,
+  pub action: AuditAction,
+  pub target_type: AuditTargetType,
+  pub target_id: Option<i32>,
+  pub detail: Value,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type AuditLogColumns = (
+  audit_log::id,
+  audit_log::api_token_id,
+  audit_log::actor,
+  audit_log::action,
+  audit_log::target_type,
+  audit_log::target_id,
+  audit_log::detail,
+  audit_log::created_at,
+);
+
+impl AuditLog {
+  pub(crate) const COLUMNS: AuditLogColumns = (
+    audit_log::id,
+    audit_log::api_token_id,
+    audit_log::actor,
+    audit_log::action,
+    audit_log::target_type,
+    audit_log::target_id,
+    audit_log::detail,
+    audit_log::created_at,
+  );
+}
+
+impl S2ProtoPack<flo_grpc::controller::AuditLog> for AuditLog {
+  fn pack(self) -> Result<flo_grpc::controller::AuditLog, s2_grpc_utils::result::Error> {
+    let action: flo_grpc::controller::AuditAction = self.action.into_proto_enum();
+    let target_type: flo_grpc::controller::AuditTargetType = self.target_type.into_proto_enum();
+    Ok(flo_grpc::controller::AuditLog {
+      id: self.id,
+      api_token_id: self.api_token_id,
+      actor: self.actor,
+      action: action as i32,
+      target_type: target_type as i32,
+      target_id: self.target_id,
+      detail: self.detail.to_string(),
+      created_at: self.created_at.pack()?,
+    })
+  }
+}
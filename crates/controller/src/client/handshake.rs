@@ -5,6 +5,7 @@ use flo_net::stream::FloStream;
 use crate::error::*;
 use crate::game::Game;
 use crate::player::token::validate_player_token;
+use crate::smurf::{Fingerprint, MAX_FINGERPRINTS};
 use flo_constants::version::Version;
 
 pub async fn handle_handshake(stream: &mut FloStream) -> Result<ConnectState> {
@@ -17,8 +18,16 @@ pub async fn handle_handshake(stream: &mut FloStream) -> Result<ConnectState> {
 
   tracing::debug!(token.player_id);
 
+  let fingerprints = req
+    .fingerprints
+    .into_iter()
+    .take(MAX_FINGERPRINTS)
+    .filter_map(Fingerprint::from_packet)
+    .collect();
+
   Ok(ConnectState {
     player_id: token.player_id,
+    fingerprints,
     joined_game: None,
     client_version: Version {
       major: client_version.major,
@@ -31,6 +40,7 @@ pub async fn handle_handshake(stream: &mut FloStream) -> Result<ConnectState> {
 #[derive(Debug)]
 pub struct ConnectState {
   pub player_id: i32,
+  pub fingerprints: Vec<Fingerprint>,
   pub joined_game: Option<Game>,
   pub client_version: Version,
 }
@@ -13,12 +13,21 @@ use crate::state::{ActorMapExt, ControllerStateRef};
 
 mod handshake;
 mod sender;
-use crate::game::messages::{ResolveGamePlayerPingBroadcastTargets, UpdateSlot};
+use crate::game::access::JoinCredential;
+use crate::game::db::ListLobbiesParams;
+use crate::game::messages::{
+  LobbyChat, LockSlot, PlayerJoin, PlayerLeave, ReserveSlot, ResolveGamePlayerPingBroadcastTargets,
+  SwapSlot, UpdateSlot,
+};
+use crate::game::state::chat::SlotCommand;
+use crate::game::state::create::RehostGame;
 use crate::game::state::node::SelectNode;
 use crate::game::state::player::GetGamePlayers;
-use crate::game::state::registry::UpdateGameNodeCache;
+use crate::game::state::registry::{AddGamePlayer, Remove, RemoveGamePlayer, UpdateGameNodeCache};
 use crate::game::state::start::{StartGameCheck, StartGamePlayerAck};
 use crate::game::SlotSettings;
+use crate::map::state::BanMap;
+use crate::matchmaking::messages::{GetQueueStatus, JoinQueue, LeaveQueue};
 use crate::node::messages::ListNode;
 use crate::player::state::conn::{Connect, Disconnect};
 use crate::player::state::ping::{GetPlayersPingSnapshot, UpdatePing};
@@ -74,11 +83,43 @@ pub async fn serve(state: ControllerStateRef) -> Result<()> {
         return Ok(());
       }
 
-      if let Err(err) = handle_stream(state.clone(), player_id, stream).await {
-        tracing::debug!("stream error: {}", err);
+      let fingerprints = accepted.fingerprints;
+      match state
+        .db
+        .exec(move |conn| crate::smurf::db::record_fingerprints(conn, player_id, &fingerprints))
+        .await
+      {
+        Ok(flagged) if !flagged.is_empty() => {
+          tracing::info!(player_id, "shared fingerprints with players: {:?}", flagged);
+        }
+        Ok(_) => {}
+        Err(err) => {
+          tracing::error!(player_id, "record fingerprints: {}", err);
+        }
       }
 
-      state.players.send(Disconnect { player_id }).await?;
+      let (sender, receiver) = PlayerSender::new(player_id);
+      let conn_id = sender.conn_id();
+
+      crate::metrics::PLAYER_CONNECTIONS.inc();
+      if let Err(err) = handle_stream(state.clone(), player_id, stream, sender, receiver).await {
+        if let Error::Net(ref err) = err {
+          if err.is_decode() {
+            crate::metrics::DECODE_ERRORS.inc();
+          }
+        }
+        tracing::debug!("stream error: {}", err);
+      }
+      crate::metrics::PLAYER_CONNECTIONS.dec();
+
+      // skipped if a newer connection of the player has taken over the session
+      if state
+        .players
+        .send(Disconnect { player_id, conn_id })
+        .await?
+      {
+        state.matchmaking.send(LeaveQueue { player_id }).await??;
+      }
       tracing::debug!("exiting: player_id = {}", player_id);
       Ok::<_, crate::error::Error>(())
     });
@@ -89,14 +130,14 @@ pub async fn serve(state: ControllerStateRef) -> Result<()> {
   Ok(())
 }
 
-#[tracing::instrument(target = "player_stream", skip(state, stream))]
+#[tracing::instrument(target = "player_stream", skip(state, stream, sender, receiver))]
 async fn handle_stream(
   state: ControllerStateRef,
   player_id: i32,
   mut stream: FloStream,
+  sender: PlayerSender,
+  mut receiver: sender::PlayerReceiver,
 ) -> Result<()> {
-  let (sender, mut receiver) = PlayerSender::new(player_id);
-
   send_initial_state(state.clone(), &mut stream, sender).await?;
 
   let mut ping = PingStream::interval(PING_INTERVAL, PING_TIMEOUT);
@@ -175,6 +216,39 @@ async fn handle_stream(
             packet: proto::flo_connect::PacketPlayerMuteRemoveRequest => {
               handle_player_mute_list_update_request(state.clone(), player_id, packet.into()).await?;
             }
+            packet: proto::flo_connect::PacketGameLobbyChatRequest => {
+              handle_game_lobby_chat_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketMatchmakingQueueJoinRequest => {
+              handle_matchmaking_queue_join_request(state.clone(), player_id, packet).await?;
+            }
+            _packet: proto::flo_connect::PacketMatchmakingQueueLeaveRequest => {
+              handle_matchmaking_queue_leave_request(state.clone(), player_id).await?;
+            }
+            packet: proto::flo_connect::PacketMapVetoBanRequest => {
+              handle_map_veto_ban_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameRehostRequest => {
+              handle_game_rehost_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameLeaveRequest => {
+              handle_game_leave_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameSlotSwapRequest => {
+              handle_game_slot_swap_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameSlotLockRequest => {
+              handle_game_slot_lock_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameSlotReserveRequest => {
+              handle_game_slot_reserve_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketListLobbiesRequest => {
+              handle_list_lobbies_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameJoinRequest => {
+              handle_game_join_request(state.clone(), player_id, packet).await?;
+            }
           }
         }
       }
@@ -232,9 +306,18 @@ async fn send_initial_state(
   let mut frames = vec![frame_accept];
 
   if let Some(game_id) = game_id {
-    let (mut game, node_player_token) = state
+    let (mut game, node_player_token, mute_list) = state
       .db
-      .exec(move |conn| crate::game::db::get_full_and_node_token(conn, game_id, player_id))
+      .exec(move |conn| -> Result<_> {
+        let (game, node_player_token) =
+          crate::game::db::get_full_and_node_token(conn, game_id, player_id)?;
+        let mut mute_list_map = crate::player::db::get_mute_list_map(conn, &game.get_player_ids())?;
+        Ok((
+          game,
+          node_player_token,
+          mute_list_map.remove(&player_id).unwrap_or_default(),
+        ))
+      })
       .await?;
 
     let node_id = game.node.as_ref().map(|node| node.id);
@@ -258,6 +341,10 @@ async fn send_initial_state(
 
     let game = game.pack()?;
 
+    // the mute list may have changed while the player was disconnected
+    let frame = connect::PacketPlayerMuteListUpdate { mute_list }.encode_as_frame()?;
+    frames.push(frame);
+
     let frame = connect::PacketGameInfo { game: Some(game) }.encode_as_frame()?;
     frames.push(frame);
 
@@ -267,6 +354,8 @@ async fn send_initial_state(
         game_id,
         player_id,
         player_token: player_token.to_vec(),
+        // the trace of a running game is not resumed after reconnecting
+        ..Default::default()
       }
       .encode_as_frame()?;
       frames.push(frame);
@@ -478,3 +567,350 @@ async fn handle_player_mute_list_update_request(
     .await?;
   Ok(())
 }
+
+async fn handle_game_lobby_chat_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameLobbyChatRequest,
+) -> Result<()> {
+  if let Some(cmd) = SlotCommand::parse(&packet.message) {
+    let game_id = packet.game_id;
+    let res = match cmd {
+      SlotCommand::Swap(slot_index, other_slot_index) => {
+        state
+          .games
+          .send_to(
+            game_id,
+            SwapSlot {
+              player_id,
+              slot_index,
+              other_slot_index,
+            },
+          )
+          .await
+      }
+      SlotCommand::Lock(slot_index, locked) => {
+        state
+          .games
+          .send_to(
+            game_id,
+            LockSlot {
+              player_id,
+              slot_index,
+              locked,
+            },
+          )
+          .await
+      }
+      SlotCommand::Reserve(slot_index, reserved_player_id) => {
+        state
+          .games
+          .send_to(
+            game_id,
+            ReserveSlot {
+              player_id,
+              slot_index,
+              reserved_player_id,
+            },
+          )
+          .await
+      }
+    };
+    return send_game_slot_manage_reject(&state, player_id, game_id, res.err()).await;
+  }
+
+  if let Err(err) = state
+    .games
+    .send_to(
+      packet.game_id,
+      LobbyChat {
+        player_id,
+        message: packet.message,
+      },
+    )
+    .await
+  {
+    tracing::debug!(player_id, game_id = packet.game_id, "lobby chat: {}", err);
+  }
+  Ok(())
+}
+
+async fn handle_matchmaking_queue_join_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketMatchmakingQueueJoinRequest,
+) -> Result<()> {
+  use s2_grpc_utils::S2ProtoEnum;
+  let mode = crate::matchmaking::LadderMode::unpack_enum(packet.mode());
+  if let Err(err) = state
+    .matchmaking
+    .send(JoinQueue { player_id, mode })
+    .await?
+  {
+    tracing::debug!(player_id, "join matchmaking queue: {}", err);
+    let status = state.matchmaking.send(GetQueueStatus { player_id }).await?;
+    let packet = status.map(|s| s.to_packet()).unwrap_or_else(|| {
+      proto::flo_connect::PacketMatchmakingQueueStatus {
+        mode: packet.mode,
+        ..Default::default()
+      }
+    });
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
+
+async fn handle_matchmaking_queue_leave_request(
+  state: ControllerStateRef,
+  player_id: i32,
+) -> Result<()> {
+  state.matchmaking.send(LeaveQueue { player_id }).await??;
+  Ok(())
+}
+
+async fn handle_map_veto_ban_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketMapVetoBanRequest,
+) -> Result<()> {
+  if let Err(err) = state
+    .map_vetos
+    .send(BanMap {
+      veto_id: packet.veto_id,
+      player_id,
+      map_index: packet.map_index,
+    })
+    .await?
+  {
+    tracing::debug!(player_id, veto_id = packet.veto_id, "map veto ban: {}", err);
+  }
+  Ok(())
+}
+
+async fn handle_game_rehost_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameRehostRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  if let Err(err) = state
+    .games
+    .send(RehostGame {
+      game_id,
+      player_id,
+      node_id: None,
+    })
+    .await?
+  {
+    tracing::debug!(player_id, game_id, "rehost game: {}", err);
+    let packet = proto::flo_connect::PacketGameRehostReject {
+      game_id,
+      message: err.to_string(),
+    };
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
+
+async fn handle_game_leave_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameLeaveRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  let res = match state
+    .games
+    .send_to(game_id, PlayerLeave { player_id })
+    .await
+  {
+    Ok(res) => res,
+    Err(err) => {
+      tracing::debug!(player_id, game_id, "game leave: {}", err);
+      return Ok(());
+    }
+  };
+
+  if res.game_ended {
+    tracing::debug!(game_id, "shutting down: reason: PlayerLeave");
+    state.games.send(Remove { game_id }).await?;
+  } else {
+    state
+      .games
+      .send(RemoveGamePlayer { game_id, player_id })
+      .await?;
+  }
+  Ok(())
+}
+
+async fn handle_list_lobbies_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketListLobbiesRequest,
+) -> Result<()> {
+  let params = ListLobbiesParams {
+    map: packet.map,
+    region: packet.region,
+    game_type: None,
+    players_needed: packet.players_needed,
+    cursor: packet.cursor,
+    take: packet.take.map(Into::into),
+  };
+  let r = state
+    .db
+    .exec(move |conn| crate::game::db::list_lobbies(conn, &params))
+    .await?;
+  let packet = proto::flo_connect::PacketListLobbies {
+    lobbies: r
+      .lobbies
+      .into_iter()
+      .map(|lobby| {
+        Ok(proto::flo_connect::LobbyEntry {
+          id: lobby.id,
+          name: lobby.name,
+          map_name: lobby.map_name,
+          max_players: lobby.max_players,
+          open_slots: lobby.open_slots,
+          has_password: lobby.has_password,
+          region: lobby.region,
+          created_by: lobby.created_by.map(|p| p.pack()).transpose()?,
+        })
+      })
+      .collect::<Result<_>>()?,
+    next_cursor: r.next_cursor,
+  };
+  state
+    .player_packet_sender
+    .send(player_id, packet.encode_as_frame()?)
+    .await?;
+  Ok(())
+}
+
+async fn handle_game_join_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameJoinRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  let credential = match (packet.invite_token, packet.password) {
+    (Some(token), _) => Some(JoinCredential::InviteToken(token)),
+    (None, Some(password)) => Some(JoinCredential::Password(password)),
+    (None, None) => None,
+  };
+  // the game info is sent to the player by the game actor
+  match state
+    .games
+    .send_to(
+      game_id,
+      PlayerJoin {
+        player_id,
+        credential,
+      },
+    )
+    .await
+  {
+    Ok(_) => {
+      state
+        .games
+        .send(AddGamePlayer { game_id, player_id })
+        .await?;
+    }
+    Err(err) => {
+      tracing::debug!(player_id, game_id, "game join: {}", err);
+      let packet = proto::flo_connect::PacketGameJoinReject {
+        game_id,
+        message: err.to_string(),
+      };
+      state
+        .player_packet_sender
+        .send(player_id, packet.encode_as_frame()?)
+        .await?;
+    }
+  }
+  Ok(())
+}
+
+async fn handle_game_slot_swap_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameSlotSwapRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  let res = state
+    .games
+    .send_to(
+      game_id,
+      SwapSlot {
+        player_id,
+        slot_index: packet.slot_index,
+        other_slot_index: packet.other_slot_index,
+      },
+    )
+    .await;
+  send_game_slot_manage_reject(&state, player_id, game_id, res.err()).await
+}
+
+async fn handle_game_slot_lock_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameSlotLockRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  let res = state
+    .games
+    .send_to(
+      game_id,
+      LockSlot {
+        player_id,
+        slot_index: packet.slot_index,
+        locked: packet.locked,
+      },
+    )
+    .await;
+  send_game_slot_manage_reject(&state, player_id, game_id, res.err()).await
+}
+
+async fn handle_game_slot_reserve_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameSlotReserveRequest,
+) -> Result<()> {
+  let game_id = packet.game_id;
+  let res = state
+    .games
+    .send_to(
+      game_id,
+      ReserveSlot {
+        player_id,
+        slot_index: packet.slot_index,
+        reserved_player_id: packet.player_id,
+      },
+    )
+    .await;
+  send_game_slot_manage_reject(&state, player_id, game_id, res.err()).await
+}
+
+async fn send_game_slot_manage_reject(
+  state: &ControllerStateRef,
+  player_id: i32,
+  game_id: i32,
+  err: Option<Error>,
+) -> Result<()> {
+  if let Some(err) = err {
+    tracing::debug!(player_id, game_id, "manage slot: {}", err);
+    let packet = proto::flo_connect::PacketGameSlotManageReject {
+      game_id,
+      message: err.to_string(),
+    };
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
@@ -1,5 +1,6 @@
 use flo_net::packet::*;
 use flo_net::proto::flo_connect::*;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
@@ -14,19 +15,34 @@ pub enum PlayerSenderMessage {
 #[derive(Debug, Clone)]
 pub struct PlayerSender {
   player_id: i32,
+  conn_id: u64,
   sender: Sender<PlayerSenderMessage>,
 }
 
 impl PlayerSender {
   pub fn new(player_id: i32) -> (Self, PlayerReceiver) {
+    static CONN_ID_GEN: AtomicU64 = AtomicU64::new(0);
+
     let (sender, receiver) = channel(8);
-    (PlayerSender { player_id, sender }, receiver)
+    (
+      PlayerSender {
+        player_id,
+        conn_id: CONN_ID_GEN.fetch_add(1, Ordering::Relaxed),
+        sender,
+      },
+      receiver,
+    )
   }
 
   pub fn player_id(&self) -> i32 {
     self.player_id
   }
 
+  /// Unique id of the connection this sender belongs to
+  pub fn conn_id(&self) -> u64 {
+    self.conn_id
+  }
+
   pub async fn disconnect_multi(&mut self) {
     self.disconnect(ClientDisconnectReason::Multi).await;
   }
@@ -8,9 +8,11 @@ use std::env;
 use std::sync::Arc;
 use tonic::{metadata::MetadataValue, service::Interceptor, Request, Status};
 
+use crate::api_token::{hash_secret, ApiScopes, ApiTokenScope};
+use crate::audit::AuditActor;
 use crate::error::*;
-
 use crate::player::PlayerSource;
+use crate::rate_limit::{RateLimitScope, RateLimitSubject, RateLimiter};
 use crate::schema::{api_client, player};
 use crate::state::{Data, Reload};
 use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
@@ -27,9 +29,24 @@ pub struct ApiClient {
   player_id: i32,
 }
 
+/// A secret accepted by the gRPC interceptor,
+/// either an API client secret or a scoped API token
+#[derive(Debug)]
+struct ApiCredential {
+  api_client_id: i32,
+  api_token_id: Option<i32>,
+  player_id: i32,
+  scopes: ApiScopes,
+  expires_at: Option<DateTime<Utc>>,
+}
+
+/// Keyed by the hash of the secret, see [`hash_secret`]
+type ApiCredentialMap = BTreeMap<String, ApiCredential>;
+
 pub struct ConfigStorage {
   db: ExecutorRef,
-  api_client_map: Arc<ArcSwap<BTreeMap<Vec<u8>, ApiClient>>>,
+  api_client_map: Arc<ArcSwap<ApiCredentialMap>>,
+  rate_limiter: Arc<RateLimiter>,
 }
 
 impl Actor for ConfigStorage {}
@@ -45,6 +62,7 @@ impl Service<Data> for ConfigStorage {
     let storage = ConfigStorage {
       db,
       api_client_map: Arc::new(ArcSwap::new(Arc::new(map))),
+      rate_limiter: Arc::new(RateLimiter::default()),
     };
 
     Ok(storage)
@@ -74,6 +92,7 @@ impl Handler<GetInterceptor> for ConfigStorage {
   ) -> <GetInterceptor as Message>::Result {
     FloGrpcInterceptor {
       api_client_map: self.api_client_map.clone(),
+      rate_limiter: self.rate_limiter.clone(),
     }
   }
 }
@@ -82,26 +101,76 @@ pub const REQUEST_META_SECRET: &str = "x-flo-secret";
 pub const REQUEST_META_API_CLIENT_ID: &str = "x-flo-api-client-id-bin";
 pub const REQUEST_META_API_PLAYER_ID: &str = "x-flo-api-player-id-bin";
 
+pub const REQUEST_META_API_SCOPES: &str = "x-flo-api-scopes-bin";
+pub const REQUEST_META_API_TOKEN_ID: &str = "x-flo-api-token-id-bin";
+/// Optional name of the admin behind the credential, recorded in the audit log
+pub const REQUEST_META_ACTOR: &str = "x-flo-actor";
+const ACTOR_MAX_LEN: usize = 100;
+
 #[derive(Clone)]
 pub struct FloGrpcInterceptor {
-  api_client_map: Arc<ArcSwap<BTreeMap<Vec<u8>, ApiClient>>>,
+  api_client_map: Arc<ArcSwap<ApiCredentialMap>>,
+  rate_limiter: Arc<RateLimiter>,
+}
+
+/// Subjects of the request, inserted into the request extensions by the interceptor
+#[derive(Clone)]
+struct RequestRateLimit {
+  rate_limiter: Arc<RateLimiter>,
+  subjects: Vec<RateLimitSubject>,
 }
 
 impl Interceptor for FloGrpcInterceptor {
   fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
-    let secret = req.metadata().get(REQUEST_META_SECRET);
-    match secret {
-      Some(secret) => match self.api_client_map.load().get(secret.as_bytes()) {
-        Some(client) => {
+    let secret_hash = req
+      .metadata()
+      .get(REQUEST_META_SECRET)
+      .map(|secret| hash_secret(secret.as_bytes()));
+    match secret_hash {
+      Some(secret_hash) => match self.api_client_map.load().get(&secret_hash) {
+        Some(credential) => {
+          if credential
+            .expires_at
+            .map(|t| t <= Utc::now())
+            .unwrap_or(false)
+          {
+            return Err(Status::unauthenticated("token expired"));
+          }
+          let mut subjects = vec![RateLimitSubject::Credential {
+            api_client_id: credential.api_client_id,
+            api_token_id: credential.api_token_id,
+          }];
+          if let Some(addr) = req.remote_addr() {
+            subjects.push(RateLimitSubject::Ip(addr.ip()));
+          }
+          self
+            .rate_limiter
+            .check(RateLimitScope::Request, &subjects)?;
+          req.extensions_mut().insert(RequestRateLimit {
+            rate_limiter: self.rate_limiter.clone(),
+            subjects,
+          });
           let meta = req.metadata_mut();
           meta.insert_bin(
             REQUEST_META_API_CLIENT_ID,
-            MetadataValue::from_bytes(&client.id.to_le_bytes()),
+            MetadataValue::from_bytes(&credential.api_client_id.to_le_bytes()),
           );
           meta.insert_bin(
             REQUEST_META_API_PLAYER_ID,
-            MetadataValue::from_bytes(&client.player_id.to_le_bytes()),
+            MetadataValue::from_bytes(&credential.player_id.to_le_bytes()),
           );
+          meta.insert_bin(
+            REQUEST_META_API_SCOPES,
+            MetadataValue::from_bytes(&credential.scopes.bits().to_le_bytes()),
+          );
+          if let Some(id) = credential.api_token_id {
+            meta.insert_bin(
+              REQUEST_META_API_TOKEN_ID,
+              MetadataValue::from_bytes(&id.to_le_bytes()),
+            );
+          } else {
+            meta.remove_bin(REQUEST_META_API_TOKEN_ID);
+          }
           Ok(req)
         }
         None => Err(Status::unauthenticated("invalid secret")),
@@ -114,10 +183,10 @@ impl Interceptor for FloGrpcInterceptor {
 }
 
 impl ConfigStorage {
-  async fn load_map(db: &ExecutorRef) -> Result<BTreeMap<Vec<u8>, ApiClient>> {
+  async fn load_map(db: &ExecutorRef) -> Result<ApiCredentialMap> {
     let mut map = BTreeMap::new();
 
-    let (api_player_map, items) = db
+    let (api_player_map, items, tokens) = db
       .exec(|conn| -> Result<_> {
         create_api_players(conn)?;
 
@@ -141,7 +210,8 @@ impl ConfigStorage {
             diesel::dsl::sql::<diesel::sql_types::Integer>("0"),
           ))
           .load::<ApiClient>(conn)?;
-        Ok((api_player_map, items))
+        let tokens = crate::api_token::db::get_active_credentials(conn)?;
+        Ok((api_player_map, items, tokens))
       })
       .await?;
 
@@ -152,7 +222,34 @@ impl ConfigStorage {
         tracing::error!(id = item.id, "api player not found");
         continue;
       };
-      map.insert(item.secret_key.as_bytes().to_vec(), item);
+      map.insert(
+        hash_secret(item.secret_key.as_bytes()),
+        ApiCredential {
+          api_client_id: item.id,
+          api_token_id: None,
+          player_id: item.player_id,
+          scopes: ApiScopes::ALL,
+          expires_at: None,
+        },
+      );
+    }
+
+    for token in tokens {
+      let player_id = if let Some(player_id) = api_player_map.get(&token.api_client_id).cloned() {
+        player_id
+      } else {
+        continue;
+      };
+      map.insert(
+        token.token_hash,
+        ApiCredential {
+          api_client_id: token.api_client_id,
+          api_token_id: Some(token.id),
+          player_id,
+          scopes: ApiScopes::from_bits(token.scopes),
+          expires_at: token.expires_at,
+        },
+      );
     }
 
     Ok(map)
@@ -184,6 +281,26 @@ fn create_api_players(conn: &DbConn) -> Result<()> {
 pub trait ApiRequestExt {
   fn get_api_client_id(&self) -> i32;
   fn get_api_player_id(&self) -> i32;
+  fn get_api_scopes(&self) -> ApiScopes;
+  fn get_audit_actor(&self) -> AuditActor;
+  fn check_rate_limit(&self, scope: RateLimitScope) -> Result<()>;
+
+  fn check_api_scope(&self, scope: ApiTokenScope) -> Result<()> {
+    if self.get_api_scopes().contains(scope) {
+      Ok(())
+    } else {
+      Err(Error::ApiTokenScopeDenied(scope))
+    }
+  }
+
+  /// Only the API client secret can access endpoints without a scope
+  fn check_api_client_secret(&self) -> Result<()> {
+    if self.get_api_scopes().is_all() {
+      Ok(())
+    } else {
+      Err(Error::ApiClientSecretRequired)
+    }
+  }
 }
 
 impl<T> ApiRequestExt for Request<T> {
@@ -206,4 +323,67 @@ impl<T> ApiRequestExt for Request<T> {
       .unwrap();
     i32::from_le_bytes([value[0], value[1], value[2], value[3]])
   }
+
+  fn get_api_scopes(&self) -> ApiScopes {
+    let value = self
+      .metadata()
+      .get_bin(REQUEST_META_API_SCOPES)
+      .unwrap()
+      .to_bytes()
+      .unwrap();
+    ApiScopes::from_bits(i32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+  }
+
+  fn get_audit_actor(&self) -> AuditActor {
+    let api_token_id = self
+      .metadata()
+      .get_bin(REQUEST_META_API_TOKEN_ID)
+      .and_then(|v| v.to_bytes().ok())
+      .filter(|v| v.len() == 4)
+      .map(|value| i32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+    let name = self
+      .metadata()
+      .get(REQUEST_META_ACTOR)
+      .and_then(|v| v.to_str().ok())
+      .map(|v| v.chars().take(ACTOR_MAX_LEN).collect());
+    AuditActor {
+      api_client_id: self.get_api_client_id(),
+      api_token_id,
+      name,
+    }
+  }
+
+  fn check_rate_limit(&self, scope: RateLimitScope) -> Result<()> {
+    match self.extensions().get::<RequestRateLimit>() {
+      Some(limit) => limit.rate_limiter.check(scope, &limit.subjects),
+      None => Ok(()),
+    }
+  }
+}
+
+#[test]
+fn test_get_audit_actor() {
+  let mut req = Request::new(());
+  let meta = req.metadata_mut();
+  meta.insert_bin(
+    REQUEST_META_API_CLIENT_ID,
+    MetadataValue::from_bytes(&1_i32.to_le_bytes()),
+  );
+  let actor = req.get_audit_actor();
+  assert_eq!(actor.api_client_id, 1);
+  assert_eq!(actor.api_token_id, None);
+  assert_eq!(actor.name, None);
+
+  let meta = req.metadata_mut();
+  meta.insert_bin(
+    REQUEST_META_API_TOKEN_ID,
+    MetadataValue::from_bytes(&2_i32.to_le_bytes()),
+  );
+  meta.insert(
+    REQUEST_META_ACTOR,
+    "a".repeat(ACTOR_MAX_LEN + 1).parse().unwrap(),
+  );
+  let actor = req.get_audit_actor();
+  assert_eq!(actor.api_token_id, Some(2));
+  assert_eq!(actor.name.map(|v| v.len()), Some(ACTOR_MAX_LEN));
 }
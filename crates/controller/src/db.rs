@@ -1 +1,89 @@
 pub use bs_diesel_utils::{lock::transaction_with_advisory_lock, DbConn, Executor, ExecutorRef};
+
+/// Runs `f` in a transaction that is always rolled back, migrations are run once before.
+/// Tests using it need `DATABASE_URL` and are `#[ignore]`d, CI runs them against Postgres.
+#[cfg(test)]
+pub(crate) async fn test_transaction<F, T>(f: F) -> T
+where
+  F: FnOnce(&DbConn) -> crate::error::Result<T> + Send + 'static,
+  T: Send + 'static,
+{
+  use diesel::Connection;
+  static MIGRATIONS: std::sync::Once = std::sync::Once::new();
+  dotenv::dotenv().ok();
+  Executor::env()
+    .into_ref()
+    .exec(move |conn| -> crate::error::Result<T> {
+      MIGRATIONS.call_once(|| crate::migration::run(conn).unwrap());
+      Ok(conn.test_transaction(|| f(conn)))
+    })
+    .await
+    .unwrap()
+}
+
+#[cfg(test)]
+pub(crate) fn insert_test_api_client(conn: &DbConn, name: &str) -> crate::error::Result<i32> {
+  use crate::schema::api_client;
+  use diesel::prelude::*;
+  diesel::insert_into(api_client::table)
+    .values((
+      api_client::name.eq(name),
+      api_client::secret_key.eq(format!("{}-secret", name)),
+    ))
+    .returning(api_client::id)
+    .get_result(conn)
+    .map_err(Into::into)
+}
+
+#[cfg(test)]
+pub(crate) fn insert_test_player(
+  conn: &DbConn,
+  api_client_id: i32,
+  name: &str,
+) -> crate::error::Result<i32> {
+  use crate::player::db::UpsertPlayer;
+  use crate::player::PlayerSource;
+  let player = crate::player::db::upsert(
+    conn,
+    &UpsertPlayer {
+      api_client_id,
+      name: name.to_string(),
+      source: PlayerSource::Test,
+      source_id: name.to_string(),
+      source_state: None,
+      realm: None,
+    },
+  )?;
+  Ok(player.id)
+}
+
+#[cfg(test)]
+pub(crate) fn test_map(num_players: usize) -> crate::map::Map {
+  use crate::map::{Map, MapForce, MapPlayer, MapSha1};
+  Map {
+    sha1: MapSha1([0; 20]),
+    checksum: 0,
+    name: "test".to_string(),
+    description: String::new(),
+    author: String::new(),
+    path: "maps\\test.w3x".to_string(),
+    width: 64,
+    height: 64,
+    players: (0..num_players)
+      .map(|i| MapPlayer {
+        name: format!("Player {}", i + 1),
+        r#type: 1,
+        race: 0,
+        flags: 0,
+      })
+      .collect(),
+    forces: (0..num_players)
+      .map(|i| MapForce {
+        name: format!("Force {}", i + 1),
+        flags: 0,
+        player_set: 1 << i,
+      })
+      .collect(),
+    twelve_p: false,
+  }
+}
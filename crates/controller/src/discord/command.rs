@@ -0,0 +1,56 @@
+use crate::error::*;
+
+#[derive(Debug, PartialEq)]
+pub enum DiscordCommand {
+  /// `/host <map>`, the map is matched by name in the channel's map pool
+  Host { map: String },
+}
+
+pub fn parse_command(text: &str) -> Result<DiscordCommand> {
+  let text = text.trim();
+  let (name, args) = match text.find(char::is_whitespace) {
+    Some(idx) => (&text[..idx], text[idx..].trim()),
+    None => (text, ""),
+  };
+  match name {
+    "/host" => {
+      if args.is_empty() {
+        return Err(Error::DiscordCommandInvalid(
+          "usage: /host <map>".to_string(),
+        ));
+      }
+      Ok(DiscordCommand::Host {
+        map: args.to_string(),
+      })
+    }
+    other => Err(Error::DiscordCommandInvalid(format!(
+      "unknown command: {}",
+      other
+    ))),
+  }
+}
+
+/// Finds a map by name, an exact match is preferred over a prefix match
+pub(crate) fn find_map<'a>(maps: &'a [crate::map::Map], name: &str) -> Option<&'a crate::map::Map> {
+  let name = name.to_lowercase();
+  maps
+    .iter()
+    .find(|map| map.name.to_lowercase() == name)
+    .or_else(|| {
+      maps
+        .iter()
+        .find(|map| map.name.to_lowercase().starts_with(&name))
+    })
+}
+
+#[test]
+fn test_parse_command() {
+  assert_eq!(
+    parse_command(" /host  Echo Isles ").unwrap(),
+    DiscordCommand::Host {
+      map: "Echo Isles".to_string()
+    }
+  );
+  assert!(parse_command("/host").is_err());
+  assert!(parse_command("/kick someone").is_err());
+}
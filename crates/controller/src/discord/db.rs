@@ -0,0 +1,181 @@
+use diesel::prelude::*;
+
+use crate::db::DbConn;
+use crate::discord::{DiscordChannel, DiscordChannelRow, DiscordEvent, DiscordEvents};
+use crate::error::*;
+use crate::schema::{api_token, discord_channel, game, game_result, game_used_slot, player};
+
+pub fn list(conn: &DbConn, api_client_id: i32) -> Result<Vec<DiscordChannel>> {
+  discord_channel::table
+    .select(DiscordChannel::COLUMNS)
+    .filter(discord_channel::api_client_id.eq(api_client_id))
+    .order(discord_channel::id)
+    .load::<DiscordChannelRow>(conn)
+    .map(|rows| rows.into_iter().map(Into::into).collect())
+    .map_err(Into::into)
+}
+
+#[derive(Debug)]
+pub struct CreateDiscordChannelParams {
+  pub api_token_id: i32,
+  pub channel_id: String,
+  pub webhook_url: String,
+  pub events: DiscordEvents,
+  pub host_map_pool_id: Option<i32>,
+  pub host_node_id: Option<i32>,
+}
+
+pub fn create(
+  conn: &DbConn,
+  api_client_id: i32,
+  params: CreateDiscordChannelParams,
+) -> Result<DiscordChannel> {
+  #[derive(Insertable)]
+  #[table_name = "discord_channel"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    api_token_id: i32,
+    channel_id: &'a str,
+    webhook_url: &'a str,
+    events: i32,
+    host_map_pool_id: Option<i32>,
+    host_node_id: Option<i32>,
+  }
+
+  if !params.webhook_url.starts_with("https://") {
+    return Err(Error::DiscordWebhookUrlInvalid);
+  }
+
+  let token_exists: bool = diesel::select(diesel::dsl::exists(
+    api_token::table.filter(
+      api_token::id
+        .eq(params.api_token_id)
+        .and(api_token::api_client_id.eq(api_client_id)),
+    ),
+  ))
+  .get_result(conn)?;
+  if !token_exists {
+    return Err(Error::ApiTokenNotFound);
+  }
+
+  if let Some(id) = params.host_map_pool_id {
+    crate::map::db::get_pool(conn, api_client_id, id)?;
+  }
+
+  diesel::insert_into(discord_channel::table)
+    .values(&Insert {
+      api_client_id,
+      api_token_id: params.api_token_id,
+      channel_id: &params.channel_id,
+      webhook_url: &params.webhook_url,
+      events: params.events.bits(),
+      host_map_pool_id: params.host_map_pool_id,
+      host_node_id: params.host_node_id,
+    })
+    .returning(DiscordChannel::COLUMNS)
+    .get_result::<DiscordChannelRow>(conn)
+    .map(Into::into)
+    .map_err(Into::into)
+}
+
+pub fn remove(conn: &DbConn, api_client_id: i32, id: i32) -> Result<()> {
+  let n = diesel::delete(
+    discord_channel::table.filter(
+      discord_channel::id
+        .eq(id)
+        .and(discord_channel::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .execute(conn)?;
+  if n == 0 {
+    return Err(Error::DiscordChannelNotFound);
+  }
+  Ok(())
+}
+
+/// The channel a command was sent from, it must be linked to the token of the request
+pub fn get_command_channel(
+  conn: &DbConn,
+  api_token_id: Option<i32>,
+  channel_id: &str,
+) -> Result<DiscordChannel> {
+  let channel: DiscordChannel = discord_channel::table
+    .select(DiscordChannel::COLUMNS)
+    .filter(discord_channel::channel_id.eq(channel_id))
+    .first::<DiscordChannelRow>(conn)
+    .optional()?
+    .ok_or_else(|| Error::DiscordChannelNotFound)?
+    .into();
+  if Some(channel.api_token_id) != api_token_id {
+    return Err(Error::DiscordChannelNotFound);
+  }
+  Ok(channel)
+}
+
+#[derive(Debug)]
+pub struct GameNotification {
+  pub game_name: String,
+  pub map_name: String,
+  /// (team, player name), referees excluded
+  pub players: Vec<(i32, String)>,
+  pub winner_team: Option<i32>,
+  pub webhook_urls: Vec<String>,
+}
+
+/// Loads the game summary and the channels of the game's API client subscribed to `event`
+pub fn get_game_notification(
+  conn: &DbConn,
+  game_id: i32,
+  event: DiscordEvent,
+) -> Result<Option<GameNotification>> {
+  let (game_name, map_name, api_client_id) = game::table
+    .inner_join(player::table)
+    .select((game::name, game::map_name, player::api_client_id))
+    .filter(game::id.eq(game_id))
+    .first::<(String, String, i32)>(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+
+  let webhook_urls: Vec<String> = discord_channel::table
+    .select((discord_channel::webhook_url, discord_channel::events))
+    .filter(discord_channel::api_client_id.eq(api_client_id))
+    .load::<(String, i32)>(conn)?
+    .into_iter()
+    .filter(|(_, events)| DiscordEvents::from_bits(*events).contains(event))
+    .map(|(url, _)| url)
+    .collect();
+
+  if webhook_urls.is_empty() {
+    return Ok(None);
+  }
+
+  let players = game_used_slot::table
+    .inner_join(player::table)
+    .select((game_used_slot::team, player::name))
+    .filter(
+      game_used_slot::game_id
+        .eq(game_id)
+        .and(game_used_slot::team.ne(24)),
+    )
+    .order(game_used_slot::slot_index)
+    .load::<(i32, String)>(conn)?;
+
+  let winner_team = if event == DiscordEvent::GameResult {
+    game_result::table
+      .select(game_result::winner_team)
+      .find(game_id)
+      .first::<Option<i32>>(conn)
+      .optional()?
+      .flatten()
+  } else {
+    None
+  };
+
+  Ok(Some(GameNotification {
+    game_name,
+    map_name,
+    players,
+    winner_team,
+    webhook_urls,
+  }))
+}
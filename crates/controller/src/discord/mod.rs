@@ -0,0 +1,192 @@
+mod command;
+pub mod db;
+pub(crate) mod notifier;
+
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::discord_channel;
+
+pub(crate) use command::find_map;
+pub use command::{parse_command, DiscordCommand};
+pub use notifier::DiscordNotifier;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::DiscordEvent")]
+pub enum DiscordEvent {
+  LobbyCreated = 0,
+  GameStarted = 1,
+  GameResult = 2,
+}
+
+/// Set of events posted into a channel
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct DiscordEvents(i32);
+
+impl DiscordEvents {
+  pub fn from_bits(bits: i32) -> Self {
+    DiscordEvents(bits)
+  }
+
+  pub fn bits(self) -> i32 {
+    self.0
+  }
+
+  pub fn contains(self, event: DiscordEvent) -> bool {
+    self.0 & Self::event_bit(event) != 0
+  }
+
+  pub fn insert(&mut self, event: DiscordEvent) {
+    self.0 |= Self::event_bit(event)
+  }
+
+  pub fn to_vec(self) -> Vec<DiscordEvent> {
+    [
+      DiscordEvent::LobbyCreated,
+      DiscordEvent::GameStarted,
+      DiscordEvent::GameResult,
+    ]
+    .iter()
+    .cloned()
+    .filter(|event| self.contains(*event))
+    .collect()
+  }
+
+  fn event_bit(event: DiscordEvent) -> i32 {
+    1 << (event as i32)
+  }
+}
+
+impl std::iter::FromIterator<DiscordEvent> for DiscordEvents {
+  fn from_iter<I: IntoIterator<Item = DiscordEvent>>(iter: I) -> Self {
+    let mut events = DiscordEvents::default();
+    for event in iter {
+      events.insert(event);
+    }
+    events
+  }
+}
+
+/// A Discord channel linked to an API client.
+/// Commands from the channel are executed with the scopes of `api_token_id`.
+#[derive(Debug, Clone)]
+pub struct DiscordChannel {
+  pub id: i32,
+  pub api_client_id: i32,
+  pub api_token_id: i32,
+  pub channel_id: String,
+  pub webhook_url: String,
+  pub events: DiscordEvents,
+  pub host_map_pool_id: Option<i32>,
+  pub host_node_id: Option<i32>,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type DiscordChannelRow = (
+  i32,
+  i32,
+  i32,
+  String,
+  String,
+  i32,
+  Option<i32>,
+  Option<i32>,
+  DateTime<Utc>,
+);
+
+pub(crate) type DiscordChannelColumns = (
+  discord_channel::id,
+  discord_channel::api_client_id,
+  discord_channel::api_token_id,
+  discord_channel::channel_id,
+  discord_channel::webhook_url,
+  discord_channel::events,
+  discord_channel::host_map_pool_id,
+  discord_channel::host_node_id,
+  discord_channel::created_at,
+);
+
+impl DiscordChannel {
+  pub(crate) const COLUMNS: DiscordChannelColumns = (
+    discord_channel::id,
+    discord_channel::api_client_id,
+    discord_channel::api_token_id,
+    discord_channel::channel_id,
+    discord_channel::webhook_url,
+    discord_channel::events,
+    discord_channel::host_map_pool_id,
+    discord_channel::host_node_id,
+    discord_channel::created_at,
+  );
+
+  /// `/host` is accepted only if the channel has a map pool to pick maps from
+  pub fn host_enabled(&self) -> bool {
+    self.host_map_pool_id.is_some()
+  }
+}
+
+impl From<DiscordChannelRow> for DiscordChannel {
+  fn from(
+    (
+      id,
+      api_client_id,
+      api_token_id,
+      channel_id,
+      webhook_url,
+      events,
+      host_map_pool_id,
+      host_node_id,
+      created_at,
+    ): DiscordChannelRow,
+  ) -> Self {
+    DiscordChannel {
+      id,
+      api_client_id,
+      api_token_id,
+      channel_id,
+      webhook_url,
+      events: DiscordEvents::from_bits(events),
+      host_map_pool_id,
+      host_node_id,
+      created_at,
+    }
+  }
+}
+
+impl S2ProtoPack<flo_grpc::controller::DiscordChannel> for DiscordChannel {
+  fn pack(self) -> Result<flo_grpc::controller::DiscordChannel, s2_grpc_utils::result::Error> {
+    Ok(flo_grpc::controller::DiscordChannel {
+      id: self.id,
+      api_token_id: self.api_token_id,
+      channel_id: self.channel_id,
+      events: self
+        .events
+        .to_vec()
+        .into_iter()
+        .map(|event| {
+          let event: flo_grpc::controller::DiscordEvent = event.into_proto_enum();
+          event as i32
+        })
+        .collect(),
+      host_map_pool_id: self.host_map_pool_id,
+      host_node_id: self.host_node_id,
+      created_at: self.created_at.pack()?,
+    })
+  }
+}
+
+#[test]
+fn test_discord_events() {
+  let events: DiscordEvents = vec![DiscordEvent::LobbyCreated, DiscordEvent::GameResult]
+    .into_iter()
+    .collect();
+  assert!(events.contains(DiscordEvent::LobbyCreated));
+  assert!(!events.contains(DiscordEvent::GameStarted));
+  assert_eq!(
+    events.to_vec(),
+    vec![DiscordEvent::LobbyCreated, DiscordEvent::GameResult]
+  );
+}
@@ -0,0 +1,128 @@
+use bs_diesel_utils::ExecutorRef;
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::discord::db::GameNotification;
+use crate::discord::DiscordEvent;
+use crate::error::*;
+use crate::state::Data;
+
+/// Posts game events into the Discord channels of the game's API client
+pub struct DiscordNotifier {
+  db: ExecutorRef,
+}
+
+impl Actor for DiscordNotifier {}
+
+#[async_trait]
+impl Service<Data> for DiscordNotifier {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    Ok(DiscordNotifier {
+      db: registry.data().db.clone(),
+    })
+  }
+}
+
+pub struct NotifyDiscord {
+  pub game_id: i32,
+  pub event: DiscordEvent,
+}
+
+impl Message for NotifyDiscord {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<NotifyDiscord> for DiscordNotifier {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    NotifyDiscord { game_id, event }: NotifyDiscord,
+  ) {
+    let notification = match self
+      .db
+      .exec(move |conn| crate::discord::db::get_game_notification(conn, game_id, event))
+      .await
+    {
+      Ok(Some(v)) => v,
+      Ok(None) => return,
+      Err(err) => {
+        tracing::error!(game_id, "load discord notification: {}", err);
+        return;
+      }
+    };
+
+    let content = format_content(game_id, event, &notification);
+    ctx.spawn(async move {
+      let res = tokio::task::spawn_blocking(move || {
+        for url in &notification.webhook_urls {
+          if let Err(err) = ureq::post(url).send_json(json!({ "content": content })) {
+            tracing::warn!(game_id, "post discord webhook: {}", err);
+          }
+        }
+      })
+      .await;
+      if let Err(err) = res {
+        tracing::error!(game_id, "post discord webhook: {}", err);
+      }
+    });
+  }
+}
+
+fn format_content(game_id: i32, event: DiscordEvent, notification: &GameNotification) -> String {
+  let mut teams: BTreeMap<i32, Vec<&str>> = BTreeMap::new();
+  for (team, name) in &notification.players {
+    teams.entry(*team).or_default().push(name.as_str());
+  }
+  let teams_text = teams
+    .iter()
+    .map(|(team, names)| {
+      let names = names.join(", ");
+      if notification.winner_team == Some(*team) {
+        format!("**{}** :trophy:", names)
+      } else {
+        names
+      }
+    })
+    .collect::<Vec<_>>()
+    .join(" vs ");
+
+  let title = match event {
+    DiscordEvent::LobbyCreated => "Lobby created",
+    DiscordEvent::GameStarted => "Game started",
+    DiscordEvent::GameResult => {
+      if notification.winner_team.is_some() {
+        "Game ended"
+      } else {
+        "Game ended without a result"
+      }
+    }
+  };
+
+  format!(
+    "{} #{}: **{}** ({})\n{}",
+    title, game_id, notification.game_name, notification.map_name, teams_text
+  )
+}
+
+#[test]
+fn test_format_content() {
+  let notification = GameNotification {
+    game_name: "FLO".to_string(),
+    map_name: "Echo Isles".to_string(),
+    players: vec![
+      (0, "A".to_string()),
+      (1, "B".to_string()),
+      (0, "C".to_string()),
+    ],
+    winner_team: Some(1),
+    webhook_urls: vec![],
+  };
+  assert_eq!(
+    format_content(1, DiscordEvent::GameResult, &notification),
+    "Game ended #1: **FLO** (Echo Isles)\nA, C vs **B** :trophy:"
+  );
+}
@@ -1,4 +1,5 @@
 use bs_diesel_utils::executor::ExecutorError;
+use flo_net::error_code::{ErrorCode, GRPC_METADATA_KEY};
 use flo_state::RegistryError;
 use thiserror::Error;
 use tonic::Status;
@@ -36,10 +37,36 @@ pub enum Error {
   PlayerNotHost,
   #[error("Player not found")]
   PlayerNotFound,
+  #[error("Battle.net OAuth is not configured")]
+  BNetOAuthDisabled,
+  #[error("Invalid Battle.net OAuth state")]
+  BNetOAuthStateInvalid,
+  #[error("Battle.net OAuth request: {0}")]
+  BNetOAuthRequest(String),
+  #[error("This Battle.net account is linked to another player")]
+  BNetAccountLinked,
   #[error("Game not found")]
   GameNotFound,
   #[error("Only games with `Preparing` or `Created` status are cancellable")]
   GameNotCancellable,
+  #[error("Only games with `Ended` or `Terminated` status can be rehosted")]
+  GameNotEnded,
+  #[error("Only games with `Created` or `Running` status can be aborted")]
+  GameNotAbortable,
+  #[error("This game is private, an invite or password is required")]
+  GamePrivate,
+  #[error("Invite token is invalid or already used")]
+  GameInviteInvalid,
+  #[error("Incorrect game password")]
+  GamePasswordIncorrect,
+  #[error("Replay not found")]
+  GameReplayNotFound,
+  #[error("Game timeline not found")]
+  GameTimelineNotFound,
+  #[error("Game desync report not found")]
+  GameDesyncReportNotFound,
+  #[error("Replays of private games are only available to the API clients of its players")]
+  GameReplayAccessDenied,
   #[error("Invalid game data, please re-create")]
   GameDataInvalid,
   #[error("The game you are trying to join is full")]
@@ -50,6 +77,8 @@ pub enum Error {
   GameCreateReject(flo_net::proto::flo_node::ControllerCreateGameRejectReason),
   #[error("Create game request rejected: {0:?}")]
   GameLeaveRejected(flo_net::proto::flo_node::UpdateSlotClientStatusRejectReason),
+  #[error("Abort game request rejected: {0:?}")]
+  GameAbortRejected(flo_net::proto::flo_node::ControllerAbortGameRejectReason),
   #[error("Game node not selected")]
   GameNodeNotSelected,
   #[error("Slot update denied")]
@@ -84,8 +113,97 @@ pub enum Error {
   PlayerColorConflict,
   #[error("Invalid player team value")]
   PlayerTeamInvalid,
+  #[error("Observers are disabled in this game")]
+  GameObserversDisabled,
+  #[error("This game only accepts players with a reserved slot")]
+  GameReservedOnly,
+  #[error("Game speed must be between 50% and 200%")]
+  GameSpeedInvalid,
+  #[error("Invalid auto start policy")]
+  GameAutoStartInvalid,
+  #[error("Matchmaking game not found")]
+  MatchmakingGameNotFound,
+  #[error("Matchmaking game result already reported")]
+  MatchmakingResultReported,
+  #[error("Winner team not found in the matchmaking game")]
+  MatchmakingTeamInvalid,
+  #[error("Player #{player_id} is banned from this organizer's games: {reason}")]
+  PlayerBannedByOrganizer { player_id: i32, reason: String },
+  #[error("Organizer ban not found")]
+  OrganizerBanNotFound,
+  #[error("Player #{player_id} is penalized until {expires_at}")]
+  PlayerPenalized {
+    player_id: i32,
+    expires_at: chrono::DateTime<chrono::Utc>,
+  },
+  #[error("Season not found")]
+  SeasonNotFound,
+  #[error("Invalid season")]
+  SeasonInvalid,
+  #[error("Invalid leaderboard cursor")]
+  LeaderboardCursorInvalid,
+  #[error("Penalty not found")]
+  PenaltyNotFound,
+  #[error("Penalty appeal is not allowed in the current state")]
+  PenaltyAppealInvalid,
+  #[error("Player link not found")]
+  PlayerLinkNotFound,
+  #[error("A player cannot be linked to itself")]
+  PlayerLinkInvalid,
   #[error("Player not belongs to the current API client")]
   PlayerOwnerCheckFailed,
+  #[error("API token not found")]
+  ApiTokenNotFound,
+  #[error("API token must have at least one scope")]
+  ApiTokenScopesInvalid,
+  #[error("API token does not have the required scope: {0:?}")]
+  ApiTokenScopeDenied(crate::api_token::ApiTokenScope),
+  #[error("API client secret required")]
+  ApiClientSecretRequired,
+  #[error("Rate limit exceeded, retry after {}ms", retry_after.as_millis())]
+  RateLimited { retry_after: std::time::Duration },
+  #[error("Teams cannot be balanced with the given players and constraints")]
+  TeamBalanceInvalid,
+  #[error("Tournament not found")]
+  TournamentNotFound,
+  #[error("Tournament match not found")]
+  TournamentMatchNotFound,
+  #[error("Tournament match is not ready")]
+  TournamentMatchNotReady,
+  #[error("Tournament match already finished")]
+  TournamentMatchFinished,
+  #[error("Tournament check-in is closed")]
+  TournamentCheckInClosed,
+  #[error("Tournament requires at least 2 distinct players")]
+  TournamentPlayersInvalid,
+  #[error("Game result already reported")]
+  GameResultReported,
+  #[error("Game result not found")]
+  GameResultNotFound,
+  #[error("Ruled winner team is not in the game")]
+  GameRulingTeamInvalid,
+  #[error("Map pool not found")]
+  MapPoolNotFound,
+  #[error("Map pool requires at least 1 map")]
+  MapPoolEmpty,
+  #[error("Map veto not found")]
+  MapVetoNotFound,
+  #[error("It is not your turn to ban a map")]
+  MapVetoNotYourTurn,
+  #[error("Invalid map to ban")]
+  MapVetoMapInvalid,
+  #[error("Discord channel not found")]
+  DiscordChannelNotFound,
+  #[error("Discord webhook URL must use https")]
+  DiscordWebhookUrlInvalid,
+  #[error("Discord command is not enabled in this channel")]
+  DiscordHostDisabled,
+  #[error("Invalid Discord command: {0}")]
+  DiscordCommandInvalid(String),
+  #[error("Invalid setting `{name}`: {value}")]
+  SettingInvalid { name: String, value: String },
+  #[error("Read settings file: {0}")]
+  SettingsFile(std::io::Error),
   #[error("Operation timeout: {0}")]
   Timeout(anyhow::Error),
   #[error("net: {0}")]
@@ -98,14 +216,120 @@ pub enum Error {
   Json(#[from] serde_json::Error),
   #[error("json web token: {0}")]
   JsonWebToken(#[from] jsonwebtoken::errors::Error),
+  #[error("replay: {0}")]
+  Replay(#[from] flo_replay::error::Error),
+  #[error("replay analysis: {0}")]
+  ReplayAnalysis(#[from] flo_w3replay::error::Error),
   #[error("proto: {0}")]
   Proto(#[from] s2_grpc_utils::result::Error),
   #[error("gRPC transport: {0}")]
   GrpcTransport(#[from] tonic::transport::Error),
+  #[error("http: {0}")]
+  Http(#[from] hyper::Error),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+  /// Code sent with the gRPC status, see `flo_net::error_code`
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      Error::NodeConnectionRejected { reason, .. } => (*reason).into(),
+      Error::GameCreateReject(reason) => (*reason).into(),
+      Error::GameLeaveRejected(reason) => (*reason).into(),
+      Error::GameAbortRejected(reason) => (*reason).into(),
+      Error::Net(err) => err.code(),
+      Error::RateLimited { .. } => ErrorCode::RateLimited,
+      Error::Timeout(_) | Error::NodeRequestTimeout | Error::PlayerChannelSendTimeout => {
+        ErrorCode::Timeout
+      }
+      Error::TaskCancelled
+      | Error::NodeNotReady
+      | Error::NodeRequestCancelled
+      | Error::PlayerStreamClosed
+      | Error::PlayerChannelClosed
+      | Error::GrpcTransport(_)
+      | Error::Http(_) => ErrorCode::Unavailable,
+      Error::PlayerTokenExpired | Error::JsonWebToken(_) => ErrorCode::InvalidToken,
+      Error::GameNotFound | Error::MatchmakingGameNotFound => ErrorCode::GameNotFound,
+      Error::NodeNotFound
+      | Error::PlayerNotFound
+      | Error::PlayerSlotNotFound
+      | Error::GameReplayNotFound
+      | Error::GameTimelineNotFound
+      | Error::GameDesyncReportNotFound
+      | Error::GameResultNotFound
+      | Error::ApiTokenNotFound
+      | Error::TournamentNotFound
+      | Error::TournamentMatchNotFound
+      | Error::MapPoolNotFound
+      | Error::MapVetoNotFound
+      | Error::SeasonNotFound
+      | Error::PenaltyNotFound
+      | Error::PlayerLinkNotFound
+      | Error::OrganizerBanNotFound
+      | Error::DiscordChannelNotFound => ErrorCode::NotFound,
+      Error::GameStarted => ErrorCode::GameExists,
+      Error::PlayerAlreadyInGame => ErrorCode::PlayerBusy,
+      Error::GameNotStarting
+      | Error::GameNotCancellable
+      | Error::GameNotEnded
+      | Error::GameNotAbortable
+      | Error::GameCreating
+      | Error::PlayerNotInGame
+      | Error::TournamentMatchNotReady
+      | Error::TournamentMatchFinished
+      | Error::TournamentCheckInClosed
+      | Error::MatchmakingResultReported
+      | Error::GameResultReported
+      | Error::PenaltyAppealInvalid => ErrorCode::InvalidStatus,
+      Error::PlayerBannedByOrganizer { .. }
+      | Error::PlayerPenalized { .. }
+      | Error::PlayerNotHost
+      | Error::PlayerOwnerCheckFailed
+      | Error::GamePrivate
+      | Error::GameInviteInvalid
+      | Error::GamePasswordIncorrect
+      | Error::GameReplayAccessDenied
+      | Error::GameSlotUpdateDenied
+      | Error::GameObserversDisabled
+      | Error::GameReservedOnly
+      | Error::MapVetoNotYourTurn
+      | Error::ApiTokenScopeDenied(_)
+      | Error::ApiClientSecretRequired => ErrorCode::PermissionDenied,
+      Error::BNetOAuthDisabled | Error::DiscordHostDisabled => ErrorCode::Rejected,
+      Error::JoinTokenExpired
+      | Error::BNetOAuthStateInvalid
+      | Error::BNetAccountLinked
+      | Error::MapHasNoPlayer
+      | Error::GameFull
+      | Error::TooManyPlayers
+      | Error::GameHasNoPlayer
+      | Error::PlayerColorConflict
+      | Error::PlayerTeamInvalid
+      | Error::MatchmakingTeamInvalid
+      | Error::ApiTokenScopesInvalid
+      | Error::TeamBalanceInvalid
+      | Error::TournamentPlayersInvalid
+      | Error::MapPoolEmpty
+      | Error::MapVetoMapInvalid
+      | Error::SeasonInvalid
+      | Error::LeaderboardCursorInvalid
+      | Error::PlayerLinkInvalid
+      | Error::GameRulingTeamInvalid
+      | Error::GameSpeedInvalid
+      | Error::GameAutoStartInvalid
+      | Error::DiscordWebhookUrlInvalid
+      | Error::DiscordCommandInvalid(_)
+      | Error::InvalidNodeAddress(_)
+      | Error::PlayerSourceIdInvalid
+      | Error::InvalidPlayerSourceState
+      | Error::SettingInvalid { .. } => ErrorCode::InvalidArgument,
+      _ => ErrorCode::Internal,
+    }
+  }
+}
+
 impl From<Error> for String {
   fn from(e: Error) -> Self {
     format!("{}", e)
@@ -120,17 +344,84 @@ impl From<diesel::result::Error> for Error {
 
 impl From<Error> for Status {
   fn from(e: Error) -> Status {
-    match e {
+    let code = e.code();
+    let mut status = match e {
       e @ Error::GameNotFound
       | e @ Error::PlayerNotFound
       | e @ Error::MapHasNoPlayer
       | e @ Error::GameFull
       | e @ Error::GameNotCancellable
-      | e @ Error::JoinTokenExpired => Status::invalid_argument(e.to_string()),
-      e @ Error::PlayerTokenExpired => Status::unauthenticated(e.to_string()),
+      | e @ Error::GameNotEnded
+      | e @ Error::GameNotAbortable
+      | e @ Error::MatchmakingGameNotFound
+      | e @ Error::MatchmakingResultReported
+      | e @ Error::MatchmakingTeamInvalid
+      | e @ Error::ApiTokenNotFound
+      | e @ Error::ApiTokenScopesInvalid
+      | e @ Error::TeamBalanceInvalid
+      | e @ Error::TournamentNotFound
+      | e @ Error::TournamentMatchNotFound
+      | e @ Error::TournamentMatchNotReady
+      | e @ Error::TournamentMatchFinished
+      | e @ Error::TournamentCheckInClosed
+      | e @ Error::TournamentPlayersInvalid
+      | e @ Error::MapPoolNotFound
+      | e @ Error::MapPoolEmpty
+      | e @ Error::BNetAccountLinked
+      | e @ Error::SeasonNotFound
+      | e @ Error::SeasonInvalid
+      | e @ Error::LeaderboardCursorInvalid
+      | e @ Error::PenaltyNotFound
+      | e @ Error::PenaltyAppealInvalid
+      | e @ Error::PlayerLinkNotFound
+      | e @ Error::PlayerLinkInvalid
+      | e @ Error::OrganizerBanNotFound
+      | e @ Error::GameReplayNotFound
+      | e @ Error::GameTimelineNotFound
+      | e @ Error::GameDesyncReportNotFound
+      | e @ Error::GameResultNotFound
+      | e @ Error::GameRulingTeamInvalid
+      | e @ Error::GameAutoStartInvalid
+      | e @ Error::DiscordChannelNotFound
+      | e @ Error::DiscordWebhookUrlInvalid
+      | e @ Error::DiscordCommandInvalid(_)
+      | e @ Error::JoinTokenExpired
+      | e @ Error::SettingInvalid { .. } => Status::invalid_argument(e.to_string()),
+      e @ Error::PlayerBannedByOrganizer { .. }
+      | e @ Error::PlayerPenalized { .. }
+      | e @ Error::GamePrivate
+      | e @ Error::GameInviteInvalid
+      | e @ Error::GamePasswordIncorrect
+      | e @ Error::GameReplayAccessDenied
+      | e @ Error::GameReservedOnly
+      | e @ Error::ApiTokenScopeDenied(_)
+      | e @ Error::ApiClientSecretRequired => Status::permission_denied(e.to_string()),
+      e @ Error::PlayerTokenExpired | e @ Error::BNetOAuthStateInvalid => {
+        Status::unauthenticated(e.to_string())
+      }
+      e @ Error::BNetOAuthDisabled | e @ Error::DiscordHostDisabled => {
+        Status::failed_precondition(e.to_string())
+      }
+      Error::RateLimited { retry_after } => {
+        let retry_after_ms = retry_after.as_millis() as u64;
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        // whole seconds, rounded up
+        metadata.insert("retry-after", ((retry_after_ms + 999) / 1000).into());
+        metadata.insert("x-flo-retry-after-ms", retry_after_ms.into());
+        Status::with_metadata(
+          tonic::Code::ResourceExhausted,
+          Error::RateLimited { retry_after }.to_string(),
+          metadata,
+        )
+      }
       Error::JsonWebToken(e) => Status::unauthenticated(e.to_string()),
       e => Status::internal(e.to_string()),
-    }
+    };
+    status.metadata_mut().insert(
+      GRPC_METADATA_KEY,
+      tonic::metadata::MetadataValue::from_static(code.as_str()),
+    );
+    status
   }
 }
 
@@ -169,6 +460,16 @@ impl From<flo_state::RegistryError> for Error {
   }
 }
 
+impl From<flo_util::settings::SettingsError> for Error {
+  fn from(err: flo_util::settings::SettingsError) -> Self {
+    use flo_util::settings::SettingsError;
+    match err {
+      SettingsError::Invalid { name, value } => Self::SettingInvalid { name, value },
+      SettingsError::File(err) => Self::SettingsFile(err),
+    }
+  }
+}
+
 /// Helper trait to convert Option<Result<T>> to Result<T>
 pub trait TaskCancelledExt<T> {
   fn or_cancelled(self) -> Result<T>;
@@ -0,0 +1,266 @@
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Nullable, Text};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::GameStatus;
+use crate::schema::{game, game_invite};
+
+const INVITE_TOKEN_LEN: usize = 24;
+
+/// Proof that a player is allowed to join a private game
+#[derive(Debug, Clone)]
+pub enum JoinCredential {
+  /// Join link issued to the host
+  JoinToken,
+  /// Single-use invite token
+  InviteToken(String),
+  Password(String),
+}
+
+/// Public games can be joined without a credential.
+/// Consumes the invite token, must be called in the join transaction.
+pub(crate) fn check(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  credential: Option<&JoinCredential>,
+) -> Result<()> {
+  let (is_private, has_password): (bool, bool) = game::table
+    .find(game_id)
+    .select((game::is_private, game::password_hash.is_not_null()))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+
+  match required_check(is_private, has_password, credential)? {
+    RequiredCheck::None => Ok(()),
+    RequiredCheck::InviteToken(token) => {
+      let updated = diesel::update(
+        game_invite::table.filter(
+          game_invite::token
+            .eq(token)
+            .and(game_invite::game_id.eq(game_id))
+            .and(game_invite::used_by.is_null()),
+        ),
+      )
+      .set((
+        game_invite::used_by.eq(player_id),
+        game_invite::used_at.eq(sql("now()")),
+      ))
+      .execute(conn)?;
+      if updated == 0 {
+        return Err(Error::GameInviteInvalid);
+      }
+      Ok(())
+    }
+    RequiredCheck::Password(password) => {
+      let matched: bool = game::table
+        .find(game_id)
+        .select(
+          sql::<Bool>("password_hash = crypt(")
+            .bind::<Text, _>(password)
+            .sql(", password_hash)"),
+        )
+        .first(conn)?;
+      if !matched {
+        return Err(Error::GamePasswordIncorrect);
+      }
+      Ok(())
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum RequiredCheck<'a> {
+  None,
+  InviteToken(&'a str),
+  Password(&'a str),
+}
+
+/// Decides how the credential is verified, passwords are only accepted if the game has one
+fn required_check<'a>(
+  is_private: bool,
+  has_password: bool,
+  credential: Option<&'a JoinCredential>,
+) -> Result<RequiredCheck<'a>> {
+  if !is_private {
+    return Ok(RequiredCheck::None);
+  }
+
+  match credential {
+    Some(JoinCredential::JoinToken) => Ok(RequiredCheck::None),
+    Some(JoinCredential::InviteToken(token)) => Ok(RequiredCheck::InviteToken(token)),
+    Some(JoinCredential::Password(password)) if has_password => {
+      Ok(RequiredCheck::Password(password))
+    }
+    _ => Err(Error::GamePrivate),
+  }
+}
+
+/// Issues a single-use invite token, only the host can invite players
+pub fn create_invite(conn: &DbConn, game_id: i32, player_id: i32) -> Result<String> {
+  check_host(conn, game_id, player_id)?;
+
+  let token: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(INVITE_TOKEN_LEN)
+    .map(char::from)
+    .collect();
+
+  diesel::insert_into(game_invite::table)
+    .values((
+      game_invite::token.eq(&token),
+      game_invite::game_id.eq(game_id),
+      game_invite::created_by.eq(player_id),
+    ))
+    .execute(conn)?;
+
+  Ok(token)
+}
+
+/// Sets or clears the game password, only the host can change it
+pub fn set_password(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  password: Option<String>,
+) -> Result<()> {
+  check_host(conn, game_id, player_id)?;
+  update_password(conn, game_id, password)
+}
+
+pub(crate) fn update_password(conn: &DbConn, game_id: i32, password: Option<String>) -> Result<()> {
+  let target = game::table.find(game_id);
+  match password.filter(|v| !v.is_empty()) {
+    Some(password) => diesel::update(target)
+      .set(
+        game::password_hash.eq(
+          sql::<Nullable<Text>>("crypt(")
+            .bind::<Text, _>(password)
+            .sql(", gen_salt('bf'))"),
+        ),
+      )
+      .execute(conn)?,
+    None => diesel::update(target)
+      .set(game::password_hash.eq(None::<String>))
+      .execute(conn)?,
+  };
+  Ok(())
+}
+
+fn check_host(conn: &DbConn, game_id: i32, player_id: i32) -> Result<()> {
+  let (created_by, status): (i32, GameStatus) = game::table
+    .find(game_id)
+    .select((game::created_by, game::status))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  if created_by != player_id {
+    return Err(Error::PlayerNotHost);
+  }
+  if status != GameStatus::Preparing {
+    return Err(Error::GameStarted);
+  }
+  Ok(())
+}
+
+#[test]
+fn test_required_check() {
+  let invite = JoinCredential::InviteToken("token".to_string());
+  let password = JoinCredential::Password("secret".to_string());
+
+  for credential in &[None, Some(&invite), Some(&password)] {
+    assert_eq!(
+      required_check(false, true, *credential).unwrap(),
+      RequiredCheck::None
+    );
+  }
+  assert_eq!(
+    required_check(true, false, Some(&JoinCredential::JoinToken)).unwrap(),
+    RequiredCheck::None
+  );
+  assert_eq!(
+    required_check(true, false, Some(&invite)).unwrap(),
+    RequiredCheck::InviteToken("token")
+  );
+  assert_eq!(
+    required_check(true, true, Some(&password)).unwrap(),
+    RequiredCheck::Password("secret")
+  );
+  assert!(matches!(
+    required_check(true, false, Some(&password)),
+    Err(Error::GamePrivate)
+  ));
+  assert!(matches!(
+    required_check(true, true, None),
+    Err(Error::GamePrivate)
+  ));
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_invite_token_single_use() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  use crate::game::db::CreateGameParams;
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let host_id = insert_test_player(conn, api_client_id, "host")?;
+    let player_id = insert_test_player(conn, api_client_id, "player")?;
+    let game = crate::game::db::create(
+      conn,
+      CreateGameParams {
+        player_id: host_id,
+        name: "test".to_string(),
+        map: test_map(4),
+        is_private: true,
+        is_live: false,
+        map_config: None,
+        password: Some("secret".to_string()),
+        speed_percent: None,
+        reserved_slots: vec![],
+        reserved_only: false,
+        feature_flags: None,
+        auto_start: None,
+      },
+    )?;
+
+    assert!(matches!(
+      create_invite(conn, game.id, player_id),
+      Err(Error::PlayerNotHost)
+    ));
+    let token = create_invite(conn, game.id, host_id)?;
+    let invite = JoinCredential::InviteToken(token);
+    assert!(matches!(
+      check(
+        conn,
+        game.id,
+        player_id,
+        Some(&JoinCredential::InviteToken("invalid".to_string()))
+      ),
+      Err(Error::GameInviteInvalid)
+    ));
+    check(conn, game.id, player_id, Some(&invite))?;
+    assert!(matches!(
+      check(conn, game.id, player_id, Some(&invite)),
+      Err(Error::GameInviteInvalid)
+    ));
+
+    let password = |v: &str| JoinCredential::Password(v.to_string());
+    check(conn, game.id, player_id, Some(&password("secret")))?;
+    assert!(matches!(
+      check(conn, game.id, player_id, Some(&password("wrong"))),
+      Err(Error::GamePasswordIncorrect)
+    ));
+    set_password(conn, game.id, host_id, None)?;
+    assert!(matches!(
+      check(conn, game.id, player_id, Some(&password("secret"))),
+      Err(Error::GamePrivate)
+    ));
+    Ok(())
+  })
+  .await;
+}
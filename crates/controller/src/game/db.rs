@@ -8,16 +8,24 @@ use std::collections::HashMap;
 
 use crate::db::DbConn;
 use crate::error::*;
-use crate::game::slots::{UsedSlot, UsedSlotInfo};
+use crate::game::access::JoinCredential;
+use crate::game::result::{
+  self, GameResolution, GameResultReport, GameRuling, InvalidReason, PlayerRatingDelta,
+  SavedGameResult,
+};
+use crate::game::slots::{SlotControl, UsedSlot, UsedSlotInfo};
 use crate::game::state::GameStatusUpdate;
 use crate::game::{
-  Computer, CreateGameSlot, Game, GameEntry, GameStatus, Race, Slot, SlotClientStatus,
-  SlotSettings, SlotStatus, Slots,
+  AutoStartPolicy, ChatRules, Computer, CreateGameSlot, Game, GameEntry, GameStatus, LobbyEntry,
+  LobbyGameType, MapConfigOverrides, Race, Slot, SlotClientStatus, SlotReservation, SlotSettings,
+  SlotStatus, Slots,
 };
 use crate::map::Map;
 use crate::node::{NodeRef, NodeRefColumns, PlayerToken};
 use crate::player::{PlayerRef, PlayerRefColumns};
-use crate::schema::{game, game_used_slot, node, player};
+use crate::schema::{
+  game, game_result, game_slot_control, game_used_slot, matchmaking_game, node, player,
+};
 use diesel::pg::expression::dsl::{all, any};
 
 pub fn get(conn: &DbConn, id: i32) -> Result<GameRowWithRelated> {
@@ -144,6 +152,104 @@ pub fn query(conn: &DbConn, params: &QueryGameParams) -> Result<QueryGame> {
   Ok(QueryGame { games, has_more })
 }
 
+const LOBBY_GAME_TYPE_SQL: &str = "(case \
+  when exists (select 1 from matchmaking_game m where m.game_id = game.id) then 1 \
+  when exists (select 1 from tournament_match t where t.game_id = game.id) then 2 \
+  else 0 end)";
+
+const LOBBY_OPEN_SLOTS_SQL: &str = "(game.max_players - (select count(*) \
+  from game_used_slot s where s.game_id = game.id and s.team <> 24)::int4)";
+
+#[derive(Debug, Default)]
+pub struct ListLobbiesParams {
+  /// Matches a part of the map name
+  pub map: Option<String>,
+  pub region: Option<String>,
+  pub game_type: Option<LobbyGameType>,
+  /// Minimum number of open player slots
+  pub players_needed: Option<i32>,
+  pub cursor: Option<i32>,
+  pub take: Option<i64>,
+}
+
+#[derive(Debug, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::ListLobbiesReply")]
+pub struct ListLobbies {
+  pub lobbies: Vec<LobbyEntry>,
+  pub next_cursor: Option<i32>,
+}
+
+/// Lists public games in preparation, newest first
+pub fn list_lobbies(conn: &DbConn, params: &ListLobbiesParams) -> Result<ListLobbies> {
+  use diesel::sql_types::{Bool, Integer};
+  use game::dsl;
+
+  let take = std::cmp::min(100, params.take.clone().unwrap_or(30));
+
+  let mut q = game::table
+    .left_outer_join(node::table)
+    .left_outer_join(player::table)
+    .select((
+      dsl::id,
+      dsl::name,
+      dsl::map_name,
+      sql::<Integer>(LOBBY_GAME_TYPE_SQL),
+      dsl::max_players,
+      sql::<Integer>(LOBBY_OPEN_SLOTS_SQL),
+      dsl::password_hash.is_not_null(),
+      node::region.nullable(),
+      dsl::created_at,
+      NodeRef::COLUMNS.nullable(),
+      PlayerRef::COLUMNS.nullable(),
+    ))
+    .filter(
+      dsl::status
+        .eq(GameStatus::Preparing)
+        .and(dsl::is_private.eq(false)),
+    )
+    .order(dsl::id.desc())
+    .limit(take + 1)
+    .into_boxed();
+
+  if let Some(ref map) = params.map {
+    q = q.filter(dsl::map_name.ilike(format!("%{}%", map.trim())));
+  }
+
+  if let Some(ref region) = params.region {
+    q = q.filter(node::region.eq(region));
+  }
+
+  if let Some(game_type) = params.game_type {
+    q = q.filter(
+      sql::<Bool>(&format!("{} = ", LOBBY_GAME_TYPE_SQL)).bind::<Integer, _>(game_type as i32),
+    );
+  }
+
+  if let Some(players_needed) = params.players_needed {
+    q = q.filter(
+      sql::<Bool>(&format!("{} >= ", LOBBY_OPEN_SLOTS_SQL)).bind::<Integer, _>(players_needed),
+    );
+  }
+
+  if let Some(id) = params.cursor {
+    q = q.filter(dsl::id.lt(id));
+  }
+
+  let mut lobbies: Vec<LobbyEntry> = q.load(conn)?;
+
+  let next_cursor = if lobbies.len() > take as usize {
+    lobbies.truncate(take as usize);
+    lobbies.last().map(|lobby| lobby.id)
+  } else {
+    None
+  };
+
+  Ok(ListLobbies {
+    lobbies,
+    next_cursor,
+  })
+}
+
 pub fn cancel(conn: &DbConn, game_id: i32, created_by: Option<i32>) -> Result<()> {
   use game::dsl;
 
@@ -173,6 +279,41 @@ pub struct CreateGameParams {
   pub map: Map,
   pub is_private: bool,
   pub is_live: bool,
+  pub map_config: Option<MapConfigOverrides>,
+  /// Private games only
+  pub password: Option<String>,
+  /// Practice games run at a modified tick rate and are unranked
+  pub speed_percent: Option<i32>,
+  #[serde(default)]
+  pub reserved_slots: Vec<SlotReservation>,
+  /// Reject joins by players without a reservation, for tournament seeding
+  #[serde(default)]
+  pub reserved_only: bool,
+  /// Rolled out by `FLO_FEATURE_<NAME>_PERCENT` if not set
+  #[serde(default)]
+  pub feature_flags: Option<u32>,
+  #[serde(default)]
+  pub auto_start: Option<AutoStartPolicy>,
+}
+
+pub const GAME_SPEED_PERCENT_RANGE: std::ops::RangeInclusive<i32> = 50..=200;
+
+fn validate_auto_start(policy: &AutoStartPolicy, max_players: usize) -> Result<()> {
+  let max_players = max_players as i32;
+  if let Some(value) = policy.min_players {
+    if value < 1 || value > max_players {
+      return Err(Error::GameAutoStartInvalid);
+    }
+  }
+  if let Some(value) = policy.min_ready_players {
+    if value < 1 || value > max_players {
+      return Err(Error::GameAutoStartInvalid);
+    }
+  }
+  if policy.min_players.is_none() && policy.start_at.is_none() {
+    return Err(Error::GameAutoStartInvalid);
+  }
+  Ok(())
 }
 
 /// Creates a game, make the creator as the first player
@@ -183,13 +324,49 @@ pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
     return Err(Error::MapHasNoPlayer);
   }
 
+  let observers_allowed = params
+    .map_config
+    .as_ref()
+    .map(|v| v.observers_allowed())
+    .unwrap_or(true);
+
+  let speed_percent = params.speed_percent.filter(|v| *v != 100);
+  if let Some(value) = speed_percent {
+    if !GAME_SPEED_PERCENT_RANGE.contains(&value) {
+      return Err(Error::GameSpeedInvalid);
+    }
+  }
+
+  if let Some(policy) = params.auto_start.as_ref() {
+    validate_auto_start(policy, max_players)?;
+  }
+
+  crate::penalty::db::check_ban_penalty(conn, &[params.player_id])?;
+
   let player = crate::player::db::get_ref(conn, params.player_id)?;
-  let mut slots = Slots::new(max_players);
+  let mut slots = Slots::new(max_players).with_observers(observers_allowed);
   slots.join(&player);
 
+  for item in &params.reserved_slots {
+    crate::player::db::get_ref(conn, item.player_id)?;
+  }
+  let reserved_indexes = reserve_slots(&mut slots, &params.reserved_slots)?;
+
+  let reserved_player_ids = if params.reserved_only {
+    reserved_only_player_ids(params.player_id, &params.reserved_slots)
+  } else {
+    vec![]
+  };
+
   let meta = Meta {
     map: params.map,
     created_by: player.into(),
+    map_config: params.map_config,
+    speed_percent,
+    chat_rules: None,
+    reserved_player_ids,
+    feature_flags: crate::game::feature::resolve(params.feature_flags),
+    auto_start: params.auto_start,
   };
 
   let meta_value = serde_json::to_value(&meta)?;
@@ -211,18 +388,55 @@ pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
     map_twelve_p: meta.map.twelve_p,
   };
 
+  let password = if params.is_private {
+    params.password
+  } else {
+    None
+  };
   let row = conn.transaction(|| -> Result<_> {
     let id: i32 = diesel::insert_into(game::table)
       .values(&insert)
       .returning(game::dsl::id)
       .get_result(conn)?;
+    crate::game::access::update_password(conn, id, password)?;
     let row = get(conn, id)?;
     upsert_used_slots(conn, row.id, slots.as_used())?;
+    for index in &reserved_indexes {
+      sync_slot_control_at(conn, id, *index, &slots[*index as usize])?;
+    }
     Ok(row)
   })?;
   Ok(row.into_game(meta, slots.into_inner())?)
 }
 
+/// Reserves the slots requested at creation, returns the updated slot indexes
+fn reserve_slots(slots: &mut Slots, reservations: &[SlotReservation]) -> Result<Vec<i32>> {
+  let mut indexes = vec![];
+  for item in reservations {
+    let updated_indexes = slots
+      .reserve(item.slot_index, Some(item.player_id))
+      .ok_or_else(|| Error::GameSlotUpdateDenied)?;
+    indexes.extend(updated_indexes);
+  }
+  indexes.sort();
+  indexes.dedup();
+  Ok(indexes)
+}
+
+/// Players allowed in a reserved-only game, the host is always allowed
+fn reserved_only_player_ids(host_id: i32, reservations: &[SlotReservation]) -> Vec<i32> {
+  let mut ids: Vec<i32> = reservations.iter().map(|v| v.player_id).collect();
+  ids.push(host_id);
+  ids.sort();
+  ids.dedup();
+  ids
+}
+
+/// Returns `true` if the player can join, an empty list accepts anyone
+fn is_reserved_player(reserved_player_ids: &[i32], player_id: i32) -> bool {
+  reserved_player_ids.is_empty() || reserved_player_ids.contains(&player_id)
+}
+
 #[derive(Debug, Deserialize, S2ProtoUnpack)]
 #[s2_grpc(message_type = "flo_grpc::controller::CreateGameAsBotRequest")]
 pub struct CreateGameAsBotParams {
@@ -235,6 +449,12 @@ pub struct CreateGameAsBotParams {
   pub mask_player_names: bool,
   pub enable_ping_equalizer: bool,
   pub flo_tv_delay_override_secs: Option<i32>,
+  pub map_config: Option<MapConfigOverrides>,
+  /// Rolled out by `FLO_FEATURE_<NAME>_PERCENT` if not set
+  #[serde(default)]
+  pub feature_flags: Option<u32>,
+  #[serde(default)]
+  pub auto_start: Option<AutoStartPolicy>,
 }
 
 /// Creates a full game and lock it
@@ -266,6 +486,25 @@ pub fn create_as_bot(
     return Err(Error::TooManyPlayers);
   }
 
+  if let Some(policy) = params.auto_start.as_ref() {
+    validate_auto_start(policy, max_players)?;
+  }
+
+  let observers_allowed = params
+    .map_config
+    .as_ref()
+    .map(|v| v.observers_allowed())
+    .unwrap_or(true);
+
+  if !observers_allowed && !referee_slots.is_empty() {
+    return Err(Error::GameObserversDisabled);
+  }
+
+  // computers can only take player slots
+  if referee_slots.iter().any(|(_, s)| s.player_id.is_none()) {
+    return Err(Error::PlayerTeamInvalid);
+  }
+
   let mut player_ids: Vec<i32> = params
     .slots
     .iter()
@@ -280,6 +519,9 @@ pub fn create_as_bot(
   player_ids.sort();
   player_ids.dedup();
 
+  crate::player::db::check_organizer_ban(conn, api_client_id, &player_ids)?;
+  crate::penalty::db::check_ban_penalty(conn, &player_ids)?;
+
   let mut players: BTreeMap<_, _> =
     crate::player::db::get_client_refs_by_ids(conn, api_client_id, &player_ids)?
       .into_iter()
@@ -327,7 +569,7 @@ pub fn create_as_bot(
     });
   }
 
-  let slots = Slots::from_used(max_players, slots);
+  let slots = Slots::from_used(max_players, slots).with_observers(observers_allowed);
 
   let meta = Meta {
     map: params.map,
@@ -335,6 +577,12 @@ pub fn create_as_bot(
       .remove(&api_player_id)
       .ok_or_else(|| Error::PlayerNotFound)?
       .into(),
+    map_config: params.map_config,
+    speed_percent: None,
+    chat_rules: None,
+    reserved_player_ids: vec![],
+    feature_flags: crate::game::feature::resolve(params.feature_flags),
+    auto_start: params.auto_start,
   };
 
   let meta_value = serde_json::to_value(&meta)?;
@@ -369,8 +617,227 @@ pub fn create_as_bot(
   Ok(row.into_game(meta, slots.into_inner())?)
 }
 
+pub struct CreateGameForPlayersParams {
+  pub name: String,
+  pub map: Map,
+  pub node_id: i32,
+  pub slots: Vec<CreateGameSlot>,
+  pub chat_rules: Option<ChatRules>,
+}
+
+/// Creates a locked game for a fixed set of players, the first player becomes the host
+pub fn create_for_players(conn: &DbConn, params: CreateGameForPlayersParams) -> Result<Game> {
+  use std::collections::BTreeMap;
+  let max_players = params.map.players.len();
+
+  if max_players == 0 {
+    return Err(Error::MapHasNoPlayer);
+  }
+
+  if params.slots.len() > max_players {
+    return Err(Error::TooManyPlayers);
+  }
+
+  let player_ids: Vec<i32> = params.slots.iter().filter_map(|s| s.player_id).collect();
+  let host_player_id = player_ids
+    .first()
+    .cloned()
+    .ok_or_else(|| Error::GameHasNoPlayer)?;
+
+  let mut players: BTreeMap<_, _> = crate::player::db::get_refs_by_ids(conn, &player_ids)?
+    .into_iter()
+    .map(|p| (p.id, p))
+    .collect();
+  let created_by = players
+    .get(&host_player_id)
+    .cloned()
+    .ok_or_else(|| Error::PlayerNotFound)?;
+
+  let api_client_id: i32 = player::table
+    .find(host_player_id)
+    .select(player::api_client_id)
+    .first(conn)?;
+  crate::player::db::check_organizer_ban(conn, api_client_id, &player_ids)?;
+
+  let mut slots = vec![];
+  for (i, slot) in params.slots.into_iter().enumerate() {
+    let player = slot.player_id.and_then(|id| players.remove(&id));
+    if slot.player_id.is_some() && player.is_none() {
+      return Err(Error::PlayerNotFound);
+    }
+    slots.push(UsedSlot {
+      slot_index: i as i32,
+      settings: slot.settings,
+      client_status: SlotClientStatus::Pending,
+      player,
+    });
+  }
+  let slots = Slots::from_used(max_players, slots);
+
+  let meta = Meta {
+    map: params.map,
+    created_by: created_by.into(),
+    map_config: None,
+    speed_percent: None,
+    chat_rules: params.chat_rules,
+    reserved_player_ids: vec![],
+    feature_flags: crate::game::feature::resolve(None),
+    auto_start: None,
+  };
+
+  let meta_value = serde_json::to_value(&meta)?;
+
+  let insert = GameInsert {
+    name: &params.name,
+    map_name: &meta.map.name,
+    is_private: true,
+    is_live: false,
+    max_players: max_players as i32,
+    created_by: Some(host_player_id),
+    meta: meta_value,
+    random_seed: rand::random(),
+    locked: true,
+    node_id: Some(params.node_id),
+    mask_player_names: false,
+    enable_ping_equalizer: false,
+    flo_tv_delay_override_secs: None,
+    map_twelve_p: meta.map.twelve_p,
+  };
+
+  let row = conn.transaction(|| -> Result<_> {
+    let id: i32 = diesel::insert_into(game::table)
+      .values(&insert)
+      .returning(game::dsl::id)
+      .get_result(conn)?;
+    let row = get(conn, id)?;
+    upsert_used_slots(conn, row.id, slots.as_used())?;
+    Ok(row)
+  })?;
+
+  Ok(row.into_game(meta, slots.into_inner())?)
+}
+
+pub struct RehostGameParams {
+  pub game_id: i32,
+  pub player_id: i32,
+  /// Overrides the node of the original game
+  pub node_id: Option<i32>,
+  /// Ids of nodes that are currently available
+  pub online_node_ids: Vec<i32>,
+}
+
+/// Creates a copy of an ended game with the same settings and slot assignments.
+/// The original node is preferred if it's still available.
+/// The requested node, or the node of the original game if it's still online
+fn rehost_node_id(
+  requested: Option<i32>,
+  original: Option<i32>,
+  online_node_ids: &[i32],
+) -> Option<i32> {
+  requested.or_else(|| original.filter(|id| online_node_ids.contains(id)))
+}
+
+/// Keeps players and slot settings of the original game, client statuses start over
+fn rehost_slots(max_players: usize, used_slots: Vec<UsedSlot>, observers_allowed: bool) -> Slots {
+  let used_slots = used_slots
+    .into_iter()
+    .map(|slot| UsedSlot {
+      client_status: SlotClientStatus::Pending,
+      ..slot
+    })
+    .collect();
+  Slots::from_used(max_players, used_slots).with_observers(observers_allowed)
+}
+
+pub fn rehost(conn: &DbConn, params: RehostGameParams) -> Result<Game> {
+  let InspectId { status, locked } = inspect_id(conn, params.game_id)?;
+  if status != GameStatus::Ended && status != GameStatus::Terminated {
+    return Err(Error::GameNotEnded);
+  }
+
+  let game = get_full(conn, params.game_id)?;
+  if game.created_by.id != params.player_id {
+    return Err(Error::PlayerNotHost);
+  }
+
+  let node_id = rehost_node_id(
+    params.node_id,
+    game.node.as_ref().map(|node| node.id),
+    &params.online_node_ids,
+  );
+  if locked && node_id.is_none() {
+    return Err(Error::GameNodeNotSelected);
+  }
+
+  let observers_allowed = game
+    .map_config
+    .as_ref()
+    .map(|v| v.observers_allowed())
+    .unwrap_or(true);
+  let slots = rehost_slots(
+    game.max_players as usize,
+    get_used_slots(conn, params.game_id)?,
+    observers_allowed,
+  );
+
+  let meta = Meta {
+    map: game.map,
+    created_by: Some(game.created_by),
+    map_config: game.map_config,
+    speed_percent: game.speed_percent,
+    chat_rules: game.chat_rules,
+    reserved_player_ids: game.reserved_player_ids,
+    feature_flags: game.feature_flags,
+    auto_start: None,
+  };
+
+  let meta_value = serde_json::to_value(&meta)?;
+
+  let insert = GameInsert {
+    name: &game.name,
+    map_name: &meta.map.name,
+    is_private: game.is_private,
+    is_live: game.is_live,
+    max_players: game.max_players,
+    created_by: Some(params.player_id),
+    meta: meta_value,
+    random_seed: rand::random(),
+    locked,
+    node_id,
+    mask_player_names: game.mask_player_names,
+    enable_ping_equalizer: game.enable_ping_equalizer,
+    flo_tv_delay_override_secs: game.flo_tv_delay_override_secs,
+    map_twelve_p: meta.map.twelve_p,
+  };
+
+  let password_hash: Option<String> = game::table
+    .find(params.game_id)
+    .select(game::password_hash)
+    .first(conn)?;
+
+  let row = conn.transaction(|| -> Result<_> {
+    let id: i32 = diesel::insert_into(game::table)
+      .values(&insert)
+      .returning(game::dsl::id)
+      .get_result(conn)?;
+    diesel::update(game::table.find(id))
+      .set(game::password_hash.eq(password_hash))
+      .execute(conn)?;
+    let row = get(conn, id)?;
+    upsert_used_slots(conn, row.id, slots.as_used())?;
+    Ok(row)
+  })?;
+
+  Ok(row.into_game(meta, slots.into_inner())?)
+}
+
 /// Adds a player into a game
-pub fn add_player(conn: &DbConn, game_id: i32, player_id: i32) -> Result<Vec<Slot>> {
+pub fn add_player(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  credential: Option<&JoinCredential>,
+) -> Result<Vec<Slot>> {
   let InspectId { status, locked } = inspect_id(conn, game_id)?;
 
   if locked {
@@ -387,19 +854,62 @@ pub fn add_player(conn: &DbConn, game_id: i32, player_id: i32) -> Result<Vec<Slo
     return Err(Error::PlayerAlreadyInGame);
   }
 
-  if slots.is_full() {
+  if slots.is_full(player_id) {
     return Err(Error::GameFull);
   }
 
+  let meta = get_meta(conn, game_id)?;
+  if !is_reserved_player(&meta.reserved_player_ids, player_id) {
+    return Err(Error::GameReservedOnly);
+  }
+
+  crate::game::access::check(conn, game_id, player_id, credential)?;
+
+  if let Some(api_client_id) = get_organizer_id(conn, game_id)? {
+    crate::player::db::check_organizer_ban(conn, api_client_id, &[player_id])?;
+  }
+
+  crate::penalty::db::check_ban_penalty(conn, &[player_id])?;
+
   let player = crate::player::db::get_ref(conn, player_id)?;
 
   slots.join(&player);
 
   upsert_used_slots(conn, game_id, slots.as_used())?;
+  diesel::update(
+    game_slot_control::table.filter(
+      game_slot_control::game_id
+        .eq(game_id)
+        .and(game_slot_control::reserved_player_id.eq(player_id)),
+    ),
+  )
+  .set(game_slot_control::reserved_player_id.eq(None::<i32>))
+  .execute(conn)?;
 
   Ok(slots.into_inner())
 }
 
+fn get_meta(conn: &DbConn, game_id: i32) -> Result<Meta> {
+  let meta: Value = game::table
+    .find(game_id)
+    .select(game::meta)
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  Ok(serde_json::from_value(meta)?)
+}
+
+/// Returns the API client that owns the game creator
+fn get_organizer_id(conn: &DbConn, game_id: i32) -> Result<Option<i32>> {
+  game::table
+    .inner_join(player::table.on(game::created_by.eq(player::id.nullable())))
+    .filter(game::id.eq(game_id))
+    .select(player::api_client_id)
+    .first::<i32>(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
 #[derive(Debug)]
 pub struct LeaveGame {
   pub game_ended: bool,
@@ -554,6 +1064,7 @@ pub fn update_slot_settings(
   if let Some(slots) = slots.update_slot_at(slot_index, &settings) {
     for (index, slot) in slots {
       sync_slot_at(conn, game_id, index as i32, &slot)?;
+      sync_slot_control_at(conn, game_id, index as i32, &slot)?;
       updated_indexes.push(index);
     }
   }
@@ -563,6 +1074,175 @@ pub fn update_slot_settings(
   })
 }
 
+/// Returns the slots of a game in preparation if the player is the host
+fn get_slots_as_host(conn: &DbConn, game_id: i32, player_id: i32) -> Result<Slots> {
+  let InspectId { status, locked } = inspect_id(conn, game_id)?;
+
+  if locked {
+    return Err(Error::GameSlotUpdateDenied);
+  }
+
+  if status != GameStatus::Preparing {
+    return Err(Error::GameStarted);
+  }
+
+  let GetSlots {
+    slots,
+    host_player_id,
+  } = get_slots(conn, game_id)?;
+  if host_player_id != player_id {
+    return Err(Error::GameSlotUpdateDenied);
+  }
+  Ok(slots)
+}
+
+/// Swaps the occupants of two slots, only the host can swap slots
+pub fn swap_slots(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  slot_index: i32,
+  other_slot_index: i32,
+) -> Result<UpdateSlotSettings> {
+  use game_used_slot::dsl;
+
+  let mut slots = get_slots_as_host(conn, game_id, player_id)?;
+  let updated_indexes = slots
+    .swap(slot_index, other_slot_index)
+    .ok_or_else(|| Error::GameSlotUpdateDenied)?;
+
+  // release players first to avoid violating the (game_id, player_id) constraint
+  diesel::update(
+    game_used_slot::table.filter(
+      dsl::game_id
+        .eq(game_id)
+        .and(dsl::slot_index.eq(any(&updated_indexes))),
+    ),
+  )
+  .set(dsl::player_id.eq(None::<i32>))
+  .execute(conn)?;
+
+  for index in &updated_indexes {
+    let slot = &slots[*index as usize];
+    sync_slot_at(conn, game_id, *index, slot)?;
+    sync_slot_control_at(conn, game_id, *index, slot)?;
+  }
+
+  Ok(UpdateSlotSettings {
+    slots: slots.into_inner(),
+    updated_indexes,
+  })
+}
+
+pub fn set_slot_locked(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  slot_index: i32,
+  locked: bool,
+) -> Result<UpdateSlotSettings> {
+  let mut slots = get_slots_as_host(conn, game_id, player_id)?;
+  let slot = slots
+    .set_locked(slot_index, locked)
+    .ok_or_else(|| Error::GameSlotUpdateDenied)?;
+  sync_slot_control_at(conn, game_id, slot_index, slot)?;
+  Ok(UpdateSlotSettings {
+    slots: slots.into_inner(),
+    updated_indexes: vec![slot_index],
+  })
+}
+
+/// Reserves an open slot for a player who hasn't joined the game yet
+pub fn reserve_slot(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  slot_index: i32,
+  reserved_player_id: Option<i32>,
+) -> Result<UpdateSlotSettings> {
+  let mut slots = get_slots_as_host(conn, game_id, player_id)?;
+  if let Some(id) = reserved_player_id {
+    crate::player::db::get_ref(conn, id)?;
+    // reserved-only games also accept the new reservation
+    let mut meta = get_meta(conn, game_id)?;
+    if !is_reserved_player(&meta.reserved_player_ids, id) {
+      meta.reserved_player_ids.push(id);
+      diesel::update(game::table.find(game_id))
+        .set(game::meta.eq(serde_json::to_value(&meta)?))
+        .execute(conn)?;
+    }
+  }
+  let updated_indexes = slots
+    .reserve(slot_index, reserved_player_id)
+    .ok_or_else(|| Error::GameSlotUpdateDenied)?;
+  for index in &updated_indexes {
+    sync_slot_control_at(conn, game_id, *index, &slots[*index as usize])?;
+  }
+  Ok(UpdateSlotSettings {
+    slots: slots.into_inner(),
+    updated_indexes,
+  })
+}
+
+/// Replaces the race of every player with a random race if the game enforces random races,
+/// called when the game starts so the races can't be swapped at the last second
+pub fn assign_random_races(conn: &DbConn, game_id: i32) -> Result<Option<UpdateSlotSettings>> {
+  use rand::Rng;
+  const RACES: [Race; 4] = [Race::Human, Race::Orc, Race::NightElf, Race::Undead];
+
+  conn.transaction(|| {
+    let meta: Value = game::table
+      .find(game_id)
+      .select(game::meta)
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::GameNotFound)?;
+    let meta: Meta = serde_json::from_value(meta)?;
+    if !meta.map_config.map(|v| v.random_races).unwrap_or(false) {
+      return Ok(None);
+    }
+
+    let mut slots = get_slots(conn, game_id)?.slots;
+    let mut rng = rand::thread_rng();
+    let updated_indexes = slots.assign_races(|| RACES[rng.gen_range(0..RACES.len())]);
+    for index in &updated_indexes {
+      sync_slot_at(conn, game_id, *index, &slots[*index as usize])?;
+    }
+    Ok(Some(UpdateSlotSettings {
+      slots: slots.into_inner(),
+      updated_indexes,
+    }))
+  })
+}
+
+fn sync_slot_control_at(conn: &DbConn, game_id: i32, slot_index: i32, slot: &Slot) -> Result<()> {
+  use game_slot_control::dsl;
+
+  if slot.locked || slot.reserved_player_id.is_some() {
+    diesel::insert_into(game_slot_control::table)
+      .values((
+        dsl::game_id.eq(game_id),
+        dsl::slot_index.eq(slot_index),
+        dsl::locked.eq(slot.locked),
+        dsl::reserved_player_id.eq(slot.reserved_player_id),
+      ))
+      .on_conflict((dsl::game_id, dsl::slot_index))
+      .do_update()
+      .set((
+        dsl::locked.eq(slot.locked),
+        dsl::reserved_player_id.eq(slot.reserved_player_id),
+      ))
+      .execute(conn)?;
+  } else {
+    diesel::delete(
+      game_slot_control::table.filter(dsl::game_id.eq(game_id).and(dsl::slot_index.eq(slot_index))),
+    )
+    .execute(conn)?;
+  }
+
+  Ok(())
+}
+
 fn sync_slot_at(conn: &DbConn, game_id: i32, slot_index: i32, slot: &Slot) -> Result<()> {
   use game_used_slot::dsl;
 
@@ -611,7 +1291,9 @@ pub fn update_status(conn: &DbConn, update: &GameStatusUpdate) -> Result<()> {
   let game_id = update.game_id;
   let game_status = GameStatus::from(update.status);
   conn.transaction(|| {
+    // aborted games stay terminated when the node reports the end of the game
     diesel::update(game::table.find(update.game_id))
+      .filter(game::dsl::status.ne(GameStatus::Terminated))
       .set(game::dsl::status.eq(game_status))
       .execute(conn)?;
 
@@ -692,15 +1374,16 @@ struct GetSlots {
 fn get_slots(conn: &DbConn, game_id: i32) -> Result<GetSlots> {
   use game_used_slot::dsl;
 
-  let (host_player_id, max_players): (i32, i32) = {
+  let (host_player_id, max_players, meta): (i32, i32, Value) = {
     use game::dsl;
     game::table
       .find(game_id)
-      .select((dsl::created_by, dsl::max_players))
+      .select((dsl::created_by, dsl::max_players, dsl::meta))
       .first(conn)
       .optional()?
       .ok_or_else(|| Error::GameNotFound)?
   };
+  let meta: Meta = serde_json::from_value(meta)?;
 
   let used_slots: Vec<UsedSlot> = game_used_slot::table
     .left_outer_join(player::table)
@@ -708,13 +1391,29 @@ fn get_slots(conn: &DbConn, game_id: i32) -> Result<GetSlots> {
     .filter(dsl::game_id.eq(game_id))
     .load(conn)?;
 
-  let slots = Slots::from_used(max_players as usize, used_slots);
+  let slots = Slots::from_used(max_players as usize, used_slots)
+    .with_observers(
+      meta
+        .map_config
+        .as_ref()
+        .map(|v| v.observers_allowed())
+        .unwrap_or(true),
+    )
+    .with_controls(get_slot_controls(conn, game_id)?);
   Ok(GetSlots {
     host_player_id,
     slots,
   })
 }
 
+fn get_slot_controls(conn: &DbConn, game_id: i32) -> Result<Vec<SlotControl>> {
+  game_slot_control::table
+    .select(SlotControl::COLUMNS)
+    .filter(game_slot_control::game_id.eq(game_id))
+    .load(conn)
+    .map_err(Into::into)
+}
+
 fn get_used_slots(conn: &DbConn, game_id: i32) -> Result<Vec<UsedSlot>> {
   use game_used_slot::dsl;
   game_used_slot::table
@@ -738,11 +1437,11 @@ pub fn get_player_active_slots(conn: &DbConn, player_id: i32) -> Result<Vec<Play
     .select((game_used_slot::game_id, UsedSlotInfo::columns()))
     .filter(game::status.eq(any(GameStatus::active_variants())))
     .filter(game_used_slot::player_id.eq(player_id))
-    .filter(
-      game_used_slot::client_status.ne(all(
-        &[SlotClientStatus::Disconnected, SlotClientStatus::Left] as &[_],
-      )),
-    )
+    .filter(game_used_slot::client_status.ne(all(&[
+      SlotClientStatus::Disconnected,
+      SlotClientStatus::Left,
+      SlotClientStatus::LoadFailed,
+    ] as &[_])))
     .order(game_used_slot::created_at)
     .load(conn)?;
   Ok(rows)
@@ -759,7 +1458,9 @@ pub fn get_full(conn: &DbConn, id: i32) -> Result<Game> {
     .ok_or_else(|| Error::GameNotFound)?;
   let meta: Meta = serde_json::from_value(row.meta.clone())?;
   let used_slots = get_used_slots(conn, id)?;
-  let slots: Vec<Slot> = Slots::from_used(row.max_players as usize, used_slots).into_inner();
+  let slots: Vec<Slot> = Slots::from_used(row.max_players as usize, used_slots)
+    .with_controls(get_slot_controls(conn, id)?)
+    .into_inner();
   Ok(row.into_game(meta, slots)?)
 }
 
@@ -785,17 +1486,38 @@ pub fn get_full_and_node_token(
     .first(conn)
     .optional()?
     .ok_or_else(|| Error::PlayerNotInGame)?;
+  let player_token = match get_organizer_id(conn, game_id)? {
+    Some(api_client_id) => withhold_banned_node_token(
+      player_token,
+      crate::player::db::check_organizer_ban(conn, api_client_id, &[player_id]),
+    )?,
+    None => player_token,
+  };
   let max_players = row.max_players;
 
   Ok((
     row.into_game(
       meta,
-      Slots::from_used(max_players as usize, used_slots).into_inner(),
+      Slots::from_used(max_players as usize, used_slots)
+        .with_controls(get_slot_controls(conn, game_id)?)
+        .into_inner(),
     )?,
     player_token.and_then(|bytes| PlayerToken::from_vec(player_id, bytes)),
   ))
 }
 
+/// Players banned by the organizer after joining can't connect to the node
+fn withhold_banned_node_token(
+  token: Option<Vec<u8>>,
+  organizer_ban: Result<()>,
+) -> Result<Option<Vec<u8>>> {
+  match organizer_ban {
+    Ok(()) => Ok(token),
+    Err(Error::PlayerBannedByOrganizer { .. }) => Ok(None),
+    Err(err) => Err(err),
+  }
+}
+
 #[derive(Debug)]
 pub struct GameStateFromDb {
   pub id: i32,
@@ -803,6 +1525,7 @@ pub struct GameStateFromDb {
   pub players: Vec<(i32, Option<Vec<u8>>)>,
   pub node_id: Option<i32>,
   pub created_by: i32,
+  pub auto_start: Option<AutoStartPolicy>,
 }
 
 /// Loads game players info from database
@@ -810,7 +1533,7 @@ pub struct GameStateFromDb {
 pub fn get_all_active_game_state(conn: &DbConn) -> Result<Vec<GameStateFromDb>> {
   use game::dsl;
 
-  let rows: Vec<(i32, GameStatus, Option<i32>, i32)> = game::table
+  let rows: Vec<(i32, GameStatus, Option<i32>, i32, Value)> = game::table
     .left_outer_join(node::table)
     .filter(dsl::status.eq_any(&[
       GameStatus::Preparing,
@@ -818,10 +1541,16 @@ pub fn get_all_active_game_state(conn: &DbConn) -> Result<Vec<GameStateFromDb>>
       GameStatus::Running,
     ]))
     .order(dsl::created_at)
-    .select((dsl::id, dsl::status, dsl::node_id, dsl::created_by))
+    .select((
+      dsl::id,
+      dsl::status,
+      dsl::node_id,
+      dsl::created_by,
+      dsl::meta,
+    ))
     .load(conn)?;
 
-  let game_ids: Vec<_> = rows.iter().map(|(id, _, _, _)| *id).collect();
+  let game_ids: Vec<_> = rows.iter().map(|(id, _, _, _, _)| *id).collect();
   let mut game_players_map: HashMap<i32, Vec<(i32, Option<Vec<u8>>)>> = {
     use game_used_slot::dsl;
     let rows: Vec<(i32, Option<i32>, Option<Vec<u8>>)> = game_used_slot::table
@@ -830,9 +1559,11 @@ pub fn get_all_active_game_state(conn: &DbConn) -> Result<Vec<GameStateFromDb>>
         dsl::game_id
           .eq(any(game_ids))
           .and(dsl::player_id.is_not_null())
-          .and(dsl::client_status.ne(all(
-            &[SlotClientStatus::Disconnected, SlotClientStatus::Left] as &[SlotClientStatus],
-          ))),
+          .and(dsl::client_status.ne(all(&[
+            SlotClientStatus::Disconnected,
+            SlotClientStatus::Left,
+            SlotClientStatus::LoadFailed,
+          ] as &[SlotClientStatus]))),
       )
       .load(conn)?;
     let mut map = HashMap::new();
@@ -848,21 +1579,24 @@ pub fn get_all_active_game_state(conn: &DbConn) -> Result<Vec<GameStateFromDb>>
   };
 
   let mut games = Vec::with_capacity(rows.len());
-  for (id, status, node_id, created_by) in rows {
+  for (id, status, node_id, created_by, meta) in rows {
     let players = game_players_map.remove(&id).unwrap_or_default();
+    let meta: Meta = serde_json::from_value(meta)?;
     games.push(GameStateFromDb {
       id,
       status,
       players,
       node_id,
       created_by,
+      auto_start: meta.auto_start,
     });
   }
   Ok(games)
 }
 
-pub fn get_expired_games(conn: &DbConn) -> Result<Vec<i32>> {
-  let t = Utc::now() - chrono::Duration::minutes(30);
+/// Lobbies and created games without updates for `idle_minutes`
+pub fn get_expired_games(conn: &DbConn, idle_minutes: i64) -> Result<Vec<i32>> {
+  let t = Utc::now() - chrono::Duration::minutes(idle_minutes);
   game::table
     .select(game::id)
     .filter(game::status.eq_any(&[GameStatus::Preparing, GameStatus::Created]))
@@ -917,6 +1651,194 @@ pub fn terminate_game(conn: &DbConn, id: i32) -> Result<()> {
   Ok(())
 }
 
+/// Stores a void result for a game aborted by an admin and terminates it
+pub fn abort_game(conn: &DbConn, game_id: i32, reason: &str) -> Result<SavedGameResult> {
+  conn.transaction(|| {
+    let resolution = GameResolution::Invalid(InvalidReason::Aborted);
+    let inserted = diesel::insert_into(game_result::table)
+      .values((
+        game_result::game_id.eq(game_id),
+        game_result::valid.eq(false),
+        game_result::invalid_reason.eq(resolution.invalid_reason().map(|v| format!("{:?}", v))),
+        game_result::duration_ms.eq(0),
+        game_result::report.eq(serde_json::json!({ "abort_reason": reason })),
+        game_result::ranked.eq(false),
+      ))
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    if inserted == 0 {
+      return Err(Error::GameResultReported);
+    }
+
+    terminate_game(conn, game_id)?;
+
+    Ok(SavedGameResult {
+      game_id,
+      resolution,
+      ratings: vec![],
+      ranked: false,
+    })
+  })
+}
+
+/// Validates the node-reported result, stores it and applies rating changes
+pub fn save_result(conn: &DbConn, report: GameResultReport) -> Result<SavedGameResult> {
+  #[derive(Insertable)]
+  #[table_name = "game_result"]
+  struct Insert {
+    game_id: i32,
+    valid: bool,
+    winner_team: Option<i32>,
+    source: Option<String>,
+    invalid_reason: Option<String>,
+    duration_ms: i32,
+    report: Value,
+    ranked: bool,
+  }
+
+  let game_id = report.game_id;
+  conn.transaction(|| {
+    let slots = get_result_slots(conn, game_id)?;
+    let meta: Value = game::table
+      .find(game_id)
+      .select(game::meta)
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::GameNotFound)?;
+    let meta: Meta = serde_json::from_value(meta)?;
+    let ranked = meta.speed_percent.is_none();
+    let condition = meta.map_config.map(|v| v.win_condition).unwrap_or_default();
+
+    let resolution = result::resolve(condition, &slots, &report);
+
+    let inserted = diesel::insert_into(game_result::table)
+      .values(&Insert {
+        game_id,
+        valid: resolution.is_valid(),
+        winner_team: resolution.winner_team(),
+        source: resolution.source().map(|v| format!("{:?}", v)),
+        invalid_reason: resolution.invalid_reason().map(|v| format!("{:?}", v)),
+        duration_ms: report.duration_ms as i32,
+        report: serde_json::to_value(&report)?,
+        ranked,
+      })
+      .on_conflict_do_nothing()
+      .execute(conn)?;
+    if inserted == 0 {
+      return Err(Error::GameResultReported);
+    }
+
+    let ratings = if ranked {
+      apply_resolution(conn, game_id, &slots, &resolution)?
+    } else {
+      vec![]
+    };
+
+    Ok(SavedGameResult {
+      game_id,
+      resolution,
+      ratings,
+      ranked,
+    })
+  })
+}
+
+/// Overrides the stored result with an admin ruling.
+/// Ratings are only applied if they weren't applied for the game yet.
+pub fn rule_result(conn: &DbConn, game_id: i32, ruling: GameRuling) -> Result<SavedGameResult> {
+  conn.transaction(|| {
+    let slots = get_result_slots(conn, game_id)?;
+    if let GameRuling::Winner(team) = ruling {
+      if !slots.iter().any(|(_, t)| *t == team) {
+        return Err(Error::GameRulingTeamInvalid);
+      }
+    }
+
+    let resolution = ruling.into_resolution();
+    let ranked: bool = diesel::update(game_result::table.find(game_id))
+      .set((
+        game_result::valid.eq(resolution.is_valid()),
+        game_result::winner_team.eq(resolution.winner_team()),
+        game_result::source.eq(resolution.source().map(|v| format!("{:?}", v))),
+        game_result::invalid_reason.eq(resolution.invalid_reason().map(|v| format!("{:?}", v))),
+      ))
+      .returning(game_result::ranked)
+      .get_result(conn)
+      .optional()?
+      .ok_or_else(|| Error::GameResultNotFound)?;
+
+    let ratings = if ranked {
+      apply_resolution(conn, game_id, &slots, &resolution)?
+    } else {
+      vec![]
+    };
+
+    Ok(SavedGameResult {
+      game_id,
+      resolution,
+      ratings,
+      ranked,
+    })
+  })
+}
+
+// (player_id, team) of non-observer players
+fn get_result_slots(conn: &DbConn, game_id: i32) -> Result<Vec<(i32, i32)>> {
+  let slots: Vec<(Option<i32>, i32)> = game_used_slot::table
+    .select((game_used_slot::player_id, game_used_slot::team))
+    .filter(
+      game_used_slot::game_id
+        .eq(game_id)
+        .and(game_used_slot::team.ne(24)),
+    )
+    .load(conn)?;
+  Ok(
+    slots
+      .into_iter()
+      .filter_map(|(player_id, team)| player_id.map(|id| (id, team)))
+      .collect(),
+  )
+}
+
+fn apply_resolution(
+  conn: &DbConn,
+  game_id: i32,
+  slots: &[(i32, i32)],
+  resolution: &GameResolution,
+) -> Result<Vec<PlayerRatingDelta>> {
+  let mut ratings = vec![];
+  if let Some(winner_team) = resolution.winner_team() {
+    let winner_ids: Vec<i32> = slots
+      .iter()
+      .filter(|(_, team)| *team == winner_team)
+      .map(|(id, _)| *id)
+      .collect();
+
+    let mode: Option<crate::matchmaking::LadderMode> = matchmaking_game::table
+      .find(game_id)
+      .select(matchmaking_game::mode)
+      .filter(matchmaking_game::reported_at.is_null())
+      .first(conn)
+      .optional()?;
+    if let Some(mode) = mode {
+      let player_ids: Vec<i32> = slots.iter().map(|(id, _)| *id).collect();
+      let before = crate::matchmaking::db::get_rating_values(conn, &player_ids, mode)?;
+      let after = crate::matchmaking::db::report_result(conn, game_id, winner_team)?;
+      ratings = after
+        .into_iter()
+        .map(|r| PlayerRatingDelta {
+          player_id: r.player_id,
+          rating_before: before.get(&r.player_id).cloned().unwrap_or(r.rating),
+          rating_after: r.rating,
+        })
+        .collect();
+    }
+
+    crate::tournament::db::report_game_result(conn, game_id, &winner_ids)?;
+  }
+  Ok(ratings)
+}
+
 pub fn update_created(
   conn: &DbConn,
   id: i32,
@@ -996,6 +1918,18 @@ pub fn get_node_active_game_ids(conn: &DbConn, node_id: i32) -> Result<Vec<i32>>
 pub struct Meta {
   pub map: Map,
   pub created_by: Option<PlayerRef>,
+  #[serde(default)]
+  pub map_config: Option<MapConfigOverrides>,
+  #[serde(default)]
+  pub speed_percent: Option<i32>,
+  #[serde(default)]
+  pub chat_rules: Option<ChatRules>,
+  #[serde(default)]
+  pub reserved_player_ids: Vec<i32>,
+  #[serde(default)]
+  pub feature_flags: u32,
+  #[serde(default)]
+  pub auto_start: Option<AutoStartPolicy>,
 }
 
 #[derive(Debug, Queryable)]
@@ -1098,6 +2032,12 @@ impl GameRowWithRelated {
       game_version: self.game_version,
       enable_ping_equalizer: self.enable_ping_equalizer,
       flo_tv_delay_override_secs: self.flo_tv_delay_override_secs,
+      map_config: meta.map_config,
+      speed_percent: meta.speed_percent,
+      chat_rules: meta.chat_rules,
+      reserved_player_ids: meta.reserved_player_ids,
+      feature_flags: meta.feature_flags,
+      auto_start: meta.auto_start,
     })
   }
 }
@@ -1181,3 +2121,331 @@ impl UsedSlotUpdate {
     }
   }
 }
+
+#[test]
+fn test_rehost_node_id() {
+  assert_eq!(rehost_node_id(Some(2), Some(1), &[1]), Some(2));
+  assert_eq!(rehost_node_id(None, Some(1), &[1, 3]), Some(1));
+  assert_eq!(rehost_node_id(None, Some(1), &[3]), None);
+  assert_eq!(rehost_node_id(None, None, &[1]), None);
+}
+
+#[test]
+fn test_rehost_slots() {
+  use crate::player::{PlayerRef, PlayerSource};
+  let used = |slot_index, player_id: i32, team| UsedSlot {
+    slot_index,
+    settings: SlotSettings {
+      team,
+      color: slot_index,
+      status: SlotStatus::Occupied,
+      race: Race::Orc,
+      ..Default::default()
+    },
+    client_status: SlotClientStatus::Left,
+    player: Some(PlayerRef {
+      id: player_id,
+      name: format!("player{}", player_id),
+      source: PlayerSource::Test,
+      realm: None,
+      battletag: None,
+    }),
+  };
+
+  let slots = rehost_slots(
+    2,
+    vec![used(1, 10, 1), used(0, 20, 0), used(5, 30, 24)],
+    true,
+  );
+  assert_eq!(slots.get_player_ids(), vec![20, 10, 30]);
+  for (index, team) in &[(0, 0), (1, 1), (5, 24)] {
+    let slot = &slots[*index];
+    assert_eq!(slot.settings.team, *team);
+    assert_eq!(slot.settings.color, *index as i32);
+    assert_eq!(slot.settings.race, Race::Orc);
+    assert_eq!(slot.client_status, SlotClientStatus::Pending);
+  }
+  assert_eq!(slots[2].settings.status, SlotStatus::Open);
+  assert!(!slots.is_full(40));
+
+  let slots = rehost_slots(2, vec![used(1, 10, 1), used(0, 20, 0)], false);
+  assert!(slots.is_full(40));
+}
+
+#[test]
+fn test_withhold_banned_node_token() {
+  let token = || Some(vec![1_u8; 16]);
+  let banned = || {
+    Err(Error::PlayerBannedByOrganizer {
+      player_id: 1,
+      reason: "spam".to_string(),
+    })
+  };
+  assert_eq!(
+    withhold_banned_node_token(token(), Ok(())).unwrap(),
+    token()
+  );
+  assert_eq!(withhold_banned_node_token(token(), banned()).unwrap(), None);
+  assert!(matches!(
+    withhold_banned_node_token(token(), Err(Error::GameNotFound)),
+    Err(Error::GameNotFound)
+  ));
+}
+
+#[test]
+fn test_reserved_slots() {
+  let reservation = |slot_index, player_id| SlotReservation {
+    slot_index,
+    player_id,
+  };
+  let mut slots = Slots::new(4);
+  slots.join(&PlayerRef {
+    id: 1,
+    name: "player1".to_string(),
+    source: crate::player::PlayerSource::Test,
+    realm: None,
+    battletag: None,
+  });
+  let reservations = vec![reservation(2, 3), reservation(1, 2)];
+  assert_eq!(
+    reserve_slots(&mut slots, &reservations).unwrap(),
+    vec![1, 2]
+  );
+  assert_eq!(slots[1].reserved_player_id, Some(2));
+  assert_eq!(slots[2].reserved_player_id, Some(3));
+
+  // the host slot can't be reserved
+  assert!(matches!(
+    reserve_slots(&mut slots, &[reservation(0, 4)]),
+    Err(Error::GameSlotUpdateDenied)
+  ));
+
+  let ids = reserved_only_player_ids(1, &[reservation(1, 3), reservation(2, 1)]);
+  assert_eq!(ids, vec![1, 3]);
+  assert!(is_reserved_player(&ids, 3));
+  assert!(!is_reserved_player(&ids, 2));
+  assert!(is_reserved_player(&[], 2));
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_organizer_ban_create_for_players() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let host_id = insert_test_player(conn, api_client_id, "host")?;
+    let banned_id = insert_test_player(conn, api_client_id, "banned")?;
+    crate::player::db::create_organizer_ban(conn, api_client_id, banned_id, "spam", None)?;
+
+    let slot = |player_id| CreateGameSlot {
+      player_id: Some(player_id),
+      settings: SlotSettings::default(),
+    };
+    let res = create_for_players(
+      conn,
+      CreateGameForPlayersParams {
+        name: "test".to_string(),
+        map: test_map(2),
+        node_id: 0,
+        slots: vec![slot(host_id), slot(banned_id)],
+        chat_rules: None,
+      },
+    );
+    assert!(matches!(
+      res,
+      Err(Error::PlayerBannedByOrganizer { player_id, .. }) if player_id == banned_id
+    ));
+    Ok(())
+  })
+  .await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_organizer_ban_node_token() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let host_id = insert_test_player(conn, api_client_id, "host")?;
+    let player_id = insert_test_player(conn, api_client_id, "player")?;
+    let game = create(
+      conn,
+      CreateGameParams {
+        player_id: host_id,
+        name: "test".to_string(),
+        map: test_map(2),
+        is_private: false,
+        is_live: false,
+        map_config: None,
+        password: None,
+        speed_percent: None,
+        reserved_slots: vec![],
+        reserved_only: false,
+        feature_flags: None,
+        auto_start: None,
+      },
+    )?;
+    add_player(conn, game.id, player_id, None)?;
+    diesel::update(game_used_slot::table.filter(game_used_slot::game_id.eq(game.id)))
+      .set(game_used_slot::node_token.eq(Some(vec![1_u8; 16])))
+      .execute(conn)?;
+
+    let (_, token) = get_full_and_node_token(conn, game.id, player_id)?;
+    assert!(token.is_some());
+    crate::player::db::create_organizer_ban(conn, api_client_id, player_id, "spam", None)?;
+    let (_, token) = get_full_and_node_token(conn, game.id, player_id)?;
+    assert!(token.is_none());
+    let (_, token) = get_full_and_node_token(conn, game.id, host_id)?;
+    assert!(token.is_some());
+    Ok(())
+  })
+  .await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_list_lobbies() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let player_id = insert_test_player(conn, api_client_id, "host")?;
+    let create_lobby = |num_players: usize, is_private: bool| -> Result<i32> {
+      let mut map = test_map(num_players);
+      map.name = "lobby-test".to_string();
+      let game = create(
+        conn,
+        CreateGameParams {
+          player_id,
+          name: "test".to_string(),
+          map,
+          is_private,
+          is_live: false,
+          map_config: None,
+          password: None,
+          speed_percent: None,
+          reserved_slots: vec![],
+          reserved_only: false,
+          feature_flags: None,
+          auto_start: None,
+        },
+      )?;
+      Ok(game.id)
+    };
+    let list = |params: ListLobbiesParams| -> Result<(Vec<i32>, Option<i32>)> {
+      let res = list_lobbies(
+        conn,
+        &ListLobbiesParams {
+          map: Some("LOBBY-TEST".to_string()),
+          ..params
+        },
+      )?;
+      Ok((
+        res.lobbies.into_iter().map(|lobby| lobby.id).collect(),
+        res.next_cursor,
+      ))
+    };
+
+    let small_id = create_lobby(2, false)?;
+    let large_id = create_lobby(4, false)?;
+    create_lobby(4, true)?;
+
+    assert_eq!(list(Default::default())?, (vec![large_id, small_id], None));
+    assert_eq!(
+      list(ListLobbiesParams {
+        players_needed: Some(2),
+        ..Default::default()
+      })?,
+      (vec![large_id], None)
+    );
+    assert_eq!(
+      list(ListLobbiesParams {
+        game_type: Some(LobbyGameType::Custom),
+        ..Default::default()
+      })?
+      .0
+      .len(),
+      2
+    );
+    assert!(list(ListLobbiesParams {
+      game_type: Some(LobbyGameType::Matchmaking),
+      ..Default::default()
+    })?
+    .0
+    .is_empty());
+    assert!(list(ListLobbiesParams {
+      region: Some("lobby-test-region".to_string()),
+      ..Default::default()
+    })?
+    .0
+    .is_empty());
+
+    assert_eq!(
+      list(ListLobbiesParams {
+        take: Some(1),
+        ..Default::default()
+      })?,
+      (vec![large_id], Some(large_id))
+    );
+    assert_eq!(
+      list(ListLobbiesParams {
+        cursor: Some(large_id),
+        take: Some(1),
+        ..Default::default()
+      })?,
+      (vec![small_id], None)
+    );
+    Ok(())
+  })
+  .await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_abort_game_rejects_node_result() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let host_id = insert_test_player(conn, api_client_id, "host")?;
+    let game = create(
+      conn,
+      CreateGameParams {
+        player_id: host_id,
+        name: "test".to_string(),
+        map: test_map(2),
+        is_private: false,
+        is_live: false,
+        map_config: None,
+        password: None,
+        speed_percent: None,
+        reserved_slots: vec![],
+        reserved_only: false,
+        feature_flags: None,
+        auto_start: None,
+      },
+    )?;
+
+    let saved = abort_game(conn, game.id, "wrong map")?;
+    assert!(!saved.ranked);
+    assert!(saved.ratings.is_empty());
+    let status: GameStatus = game::table.find(game.id).select(game::status).first(conn)?;
+    assert_eq!(status, GameStatus::Terminated);
+
+    let report = GameResultReport {
+      game_id: game.id,
+      duration_ms: 60_000,
+      players: vec![],
+      desyncs: vec![],
+      traceparent: String::new(),
+    };
+    assert!(matches!(
+      save_result(conn, report),
+      Err(Error::GameResultReported)
+    ));
+    assert!(matches!(
+      abort_game(conn, game.id, "again"),
+      Err(Error::GameResultReported)
+    ));
+    Ok(())
+  })
+  .await;
+}
@@ -0,0 +1,109 @@
+use diesel::prelude::*;
+use s2_grpc_utils::S2ProtoUnpack;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::Game;
+use crate::schema::game_desync_report;
+
+/// Desync forensic bundle of a game, for map makers and developers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameDesyncBundle {
+  pub game_id: i32,
+  pub game_version: Option<String>,
+  pub map_path: String,
+  /// Hex encoded
+  pub map_sha1: String,
+  pub map_checksum: u32,
+  pub reports: Vec<DesyncReport>,
+}
+
+/// Collected by the node when a desync is detected,
+/// player ids are flo player ids
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::GameDesyncReport))]
+pub struct DesyncReport {
+  /// The first tick players disagree on
+  pub tick: u32,
+  pub time_ms: u32,
+  /// Players with the minority checksum
+  pub player_ids: Vec<i32>,
+  pub checksums: Vec<DesyncPlayerChecksums>,
+  /// Actions of the last ticks before the desync was detected
+  pub time_slots: Vec<DesyncTimeSlot>,
+  pub node_version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::DesyncPlayerChecksums))]
+pub struct DesyncPlayerChecksums {
+  pub player_id: i32,
+  pub items: Vec<DesyncChecksum>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::DesyncChecksum))]
+pub struct DesyncChecksum {
+  pub tick: u32,
+  pub checksum: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::DesyncTimeSlot))]
+pub struct DesyncTimeSlot {
+  pub tick: u32,
+  pub time_ms: u32,
+  pub actions: Vec<DesyncPlayerAction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::DesyncPlayerAction))]
+pub struct DesyncPlayerAction {
+  pub player_id: i32,
+  pub data: Vec<u8>,
+}
+
+impl GameDesyncBundle {
+  pub fn new(game: &Game, reports: Vec<DesyncReport>) -> Self {
+    Self {
+      game_id: game.id,
+      game_version: game.game_version.clone(),
+      map_path: game.map.path.clone(),
+      map_sha1: game
+        .map
+        .sha1
+        .0
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect(),
+      map_checksum: game.map.checksum,
+      reports,
+    }
+  }
+}
+
+pub fn save(conn: &DbConn, bundle: &GameDesyncBundle) -> Result<()> {
+  let data = serde_json::to_value(bundle)?;
+  diesel::insert_into(game_desync_report::table)
+    .values((
+      game_desync_report::game_id.eq(bundle.game_id),
+      game_desync_report::data.eq(&data),
+    ))
+    .on_conflict(game_desync_report::game_id)
+    .do_update()
+    .set(game_desync_report::data.eq(&data))
+    .execute(conn)?;
+  Ok(())
+}
+
+/// Returns the bundle as a JSON document
+pub fn get(conn: &DbConn, game_id: i32) -> Result<Option<Value>> {
+  game_desync_report::table
+    .find(game_id)
+    .select(game_desync_report::data)
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
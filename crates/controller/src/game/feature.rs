@@ -0,0 +1,96 @@
+use flo_types::feature::{FeatureFlag, FeatureFlags};
+use rand::Rng;
+
+use crate::error::*;
+use crate::settings::SettingsSource;
+
+/// Percent of new games each feature flag is enabled for,
+/// configured by `FLO_FEATURE_<NAME>_PERCENT`
+#[derive(Debug, Default, PartialEq)]
+pub struct FeatureRollout {
+  percents: Vec<(FeatureFlag, u8)>,
+}
+
+impl FeatureRollout {
+  pub(crate) fn from_source(source: &SettingsSource) -> Result<Self> {
+    let mut percents = vec![];
+    for flag in FeatureFlag::ALL.iter().cloned() {
+      let name = Self::setting_name(flag);
+      match source.parse::<u8>(&name)? {
+        Some(percent) if percent > 100 => {
+          return Err(Error::SettingInvalid {
+            name,
+            value: percent.to_string(),
+          })
+        }
+        Some(0) | None => {}
+        Some(percent) => percents.push((flag, percent)),
+      }
+    }
+    Ok(FeatureRollout { percents })
+  }
+
+  pub(crate) fn setting_name(flag: FeatureFlag) -> String {
+    format!("FLO_FEATURE_{}_PERCENT", flag.name().to_uppercase())
+  }
+
+  pub fn percent(&self, flag: FeatureFlag) -> u8 {
+    self
+      .percents
+      .iter()
+      .find(|(item, _)| *item == flag)
+      .map(|(_, percent)| *percent)
+      .unwrap_or_default()
+  }
+
+  /// Picks the flags of a new game
+  pub fn roll(&self) -> FeatureFlags {
+    let mut rng = rand::thread_rng();
+    let mut flags = FeatureFlags::default();
+    for (flag, percent) in &self.percents {
+      if rng.gen_range(0..100) < *percent {
+        flags.insert(*flag);
+      }
+    }
+    flags
+  }
+
+  pub(crate) fn diff(&self, other: &FeatureRollout) -> Vec<String> {
+    FeatureFlag::ALL
+      .iter()
+      .cloned()
+      .filter(|flag| self.percent(*flag) != other.percent(*flag))
+      .map(Self::setting_name)
+      .collect()
+  }
+}
+
+/// Flags of a new game, explicitly set by the API caller or rolled out by percentage
+pub fn resolve(explicit: Option<u32>) -> u32 {
+  match explicit {
+    Some(bits) => bits,
+    None => crate::settings::current().feature_rollout.roll().bits(),
+  }
+}
+
+#[test]
+fn test_feature_rollout() {
+  let rollout = FeatureRollout {
+    percents: vec![
+      (FeatureFlag::PingEqualizer, 100),
+      (FeatureFlag::ChatCommands, 50),
+    ],
+  };
+  for _ in 0..10 {
+    let flags = rollout.roll();
+    assert!(flags.contains(FeatureFlag::PingEqualizer));
+    assert!(!flags.contains(FeatureFlag::NoW3GSBatch));
+  }
+  assert_eq!(
+    FeatureRollout::default().diff(&rollout),
+    vec![
+      "FLO_FEATURE_PING_EQUALIZER_PERCENT",
+      "FLO_FEATURE_CHAT_COMMANDS_PERCENT"
+    ]
+  );
+}
@@ -0,0 +1,29 @@
+use crate::error::*;
+use crate::settings::SettingsSource;
+
+/// Lobbies without slot or player changes for this long are cancelled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LobbyTimeout {
+  /// `FLO_LOBBY_IDLE_TIMEOUT_MINUTES`
+  pub idle_minutes: i64,
+}
+
+impl Default for LobbyTimeout {
+  fn default() -> Self {
+    LobbyTimeout { idle_minutes: 30 }
+  }
+}
+
+impl LobbyTimeout {
+  pub(crate) fn from_source(source: &SettingsSource) -> Result<Self> {
+    const NAME: &str = "FLO_LOBBY_IDLE_TIMEOUT_MINUTES";
+    match source.parse::<i64>(NAME)? {
+      Some(idle_minutes) if idle_minutes > 0 => Ok(LobbyTimeout { idle_minutes }),
+      Some(value) => Err(Error::SettingInvalid {
+        name: NAME.to_string(),
+        value: value.to_string(),
+      }),
+      None => Ok(LobbyTimeout::default()),
+    }
+  }
+}
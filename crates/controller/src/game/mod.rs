@@ -1,12 +1,21 @@
+pub mod access;
 pub mod db;
+pub mod desync;
+pub mod feature;
+pub mod lobby;
+pub mod replay;
+pub mod result;
 mod slots;
 pub(crate) mod state;
+pub mod timeline;
 pub mod token;
 mod types;
+pub mod win_condition;
 
 pub mod messages {
   pub use super::state::cancel::CancelGame;
-  pub use super::state::create::CreateGame;
+  pub use super::state::chat::LobbyChat;
+  pub use super::state::create::{CreateGame, CreateGameAsBot};
   pub use super::state::join::PlayerJoin;
   pub use super::state::leave::PlayerLeave;
   pub use super::state::node::SelectNode;
@@ -14,8 +23,10 @@ pub mod messages {
   pub use super::state::registry::{
     AddGamePlayer, Register, Remove, RemoveGamePlayer, ResolveGamePlayerPingBroadcastTargets,
   };
-  pub use super::state::slot::UpdateSlot;
-  pub use super::state::start::{StartGameCheck, StartGamePlayerAck};
+  pub use super::state::slot::{LockSlot, ReserveSlot, SwapSlot, UpdateSlot};
+  pub use super::state::start::{
+    StartGameCheck, StartGameCheckAsBot, StartGameCheckAsBotResult, StartGamePlayerAck,
+  };
 }
 
 pub use slots::Slots;
@@ -0,0 +1,387 @@
+mod retention;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use flo_observer::record::GameRecordData;
+use flo_replay::{FloMetadata, FloMetadataPlayer, ReplayChatPolicy};
+use flo_w3replay::analysis::{ProductionKind, ReplayAnalysis};
+use flo_w3replay::W3Replay;
+use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::{Game, Slot};
+use crate::matchmaking::LadderMode;
+use crate::schema::{
+  game, game_replay, game_used_slot, matchmaking_game, node, player, player_rating_history,
+};
+
+pub use retention::{prune, ReplayJanitor, ReplayRetention};
+
+/// Converts the game log recorded by the node into a `.w3g` replay
+pub fn generate(
+  game: Game,
+  records: Vec<GameRecordData>,
+  metadata: FloMetadata,
+) -> Result<Vec<u8>> {
+  let game = flo_types::observer::GameInfo::unpack(game.pack()?)?;
+  let mut buf = Cursor::new(vec![]);
+  flo_replay::generate_replay_from_records(
+    game,
+    records,
+    ReplayChatPolicy::IncludeChatVisibleToObservers,
+    Some(&metadata),
+    &mut buf,
+  )?;
+  Ok(buf.into_inner())
+}
+
+/// Flo details embedded in the replay, ratings are the values before the game
+pub fn get_metadata(conn: &DbConn, game: &Game) -> Result<FloMetadata> {
+  let mode: Option<LadderMode> = matchmaking_game::table
+    .find(game.id)
+    .select(matchmaking_game::mode)
+    .first(conn)
+    .optional()?;
+  let node_region: Option<String> = match game.node {
+    Some(ref node) => node::table
+      .find(node.id)
+      .select(node::region)
+      .first(conn)
+      .optional()?,
+    None => None,
+  };
+
+  let player_ids: Vec<i32> = game
+    .slots
+    .iter()
+    .filter_map(|slot| slot.player.as_ref().map(|p| p.id))
+    .collect();
+  let mut ratings = BTreeMap::new();
+  if let Some(mode) = mode {
+    ratings = crate::matchmaking::db::get_rating_values(conn, &player_ids, mode)?;
+    // the result might have been reported already
+    let before: Vec<(i32, i32)> = player_rating_history::table
+      .filter(player_rating_history::game_id.eq(game.id))
+      .select((
+        player_rating_history::player_id,
+        player_rating_history::rating_before,
+      ))
+      .load(conn)?;
+    ratings.extend(before);
+  }
+
+  let players = game
+    .slots
+    .iter()
+    .enumerate()
+    .filter_map(|(idx, slot)| {
+      let flo_player_id = slot.player.as_ref()?.id;
+      Some(FloMetadataPlayer {
+        player_id: (idx + 1) as u8,
+        flo_player_id,
+        rating: ratings.get(&flo_player_id).cloned(),
+      })
+    })
+    .collect();
+
+  Ok(FloMetadata {
+    game_id: game.id,
+    ladder: mode.map(|mode| format!("{:?}", mode)),
+    node_region,
+    players,
+  })
+}
+
+/// Per-player statistics extracted from a stored replay
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayStats {
+  pub duration_ms: u32,
+  pub players: Vec<ReplayPlayerStats>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayPlayerStats {
+  pub player_id: i32,
+  pub action_count: u32,
+  pub apm: f32,
+  /// Action counts per minute
+  pub apm_curve: Vec<u32>,
+  pub production: Vec<ReplayProduction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayProduction {
+  pub time_ms: u32,
+  pub kind: ReplayProductionKind,
+  pub item_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ReplayProductionKind {
+  Build,
+  Train,
+  Cancel,
+}
+
+impl From<ProductionKind> for ReplayProductionKind {
+  fn from(kind: ProductionKind) -> Self {
+    match kind {
+      ProductionKind::Build => ReplayProductionKind::Build,
+      ProductionKind::Train => ReplayProductionKind::Train,
+      ProductionKind::Cancel => ReplayProductionKind::Cancel,
+    }
+  }
+}
+
+/// Replays generated by flo use `slot index + 1` as the in-game player id
+pub fn analyze(game: &Game, data: &[u8]) -> Result<ReplayStats> {
+  let analysis = flo_w3replay::analysis::analyze(W3Replay::from_buf(data)?)?;
+  Ok(get_stats(&game.slots, &analysis))
+}
+
+fn get_stats(slots: &[Slot], analysis: &ReplayAnalysis) -> ReplayStats {
+  let players = slots
+    .iter()
+    .enumerate()
+    .filter(|(_, slot)| slot.settings.team != 24)
+    .filter_map(|(idx, slot)| {
+      let player_id = slot.player.as_ref()?.id;
+      let player = analysis.player((idx + 1) as u8)?;
+      Some(ReplayPlayerStats {
+        player_id,
+        action_count: player.action_count,
+        apm: player.apm(analysis.duration_ms),
+        apm_curve: player.apm_curve.clone(),
+        production: player
+          .production
+          .iter()
+          .map(|item| ReplayProduction {
+            time_ms: item.time_ms,
+            kind: item.kind.into(),
+            item_id: item.item_id.to_string(),
+          })
+          .collect(),
+      })
+    })
+    .collect();
+  ReplayStats {
+    duration_ms: analysis.duration_ms,
+    players,
+  }
+}
+
+pub fn save(conn: &DbConn, game_id: i32, data: &[u8], stats: Option<&ReplayStats>) -> Result<()> {
+  let stats = stats.map(serde_json::to_value).transpose()?;
+  diesel::insert_into(game_replay::table)
+    .values((
+      game_replay::game_id.eq(game_id),
+      game_replay::data.eq(data),
+      game_replay::size.eq(data.len() as i32),
+      game_replay::stats.eq(&stats),
+    ))
+    .on_conflict(game_replay::game_id)
+    .do_update()
+    .set((
+      game_replay::data.eq(data),
+      game_replay::size.eq(data.len() as i32),
+      game_replay::stats.eq(&stats),
+    ))
+    .execute(conn)?;
+  Ok(())
+}
+
+#[derive(Debug, S2ProtoPack)]
+#[s2_grpc(message_type(flo_grpc::controller::GameReplayInfo))]
+pub struct ReplayInfo {
+  pub game_id: i32,
+  pub size: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+pub fn get_info(conn: &DbConn, game_id: i32) -> Result<ReplayInfo> {
+  let (game_id, size, created_at) = game_replay::table
+    .find(game_id)
+    .select((
+      game_replay::game_id,
+      game_replay::size,
+      game_replay::created_at,
+    ))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameReplayNotFound)?;
+  Ok(ReplayInfo {
+    game_id,
+    size,
+    created_at,
+  })
+}
+
+/// Replays of public games are available to all API clients,
+/// private ones only to the clients owning the host or a player of the game
+pub fn check_access(conn: &DbConn, game_id: i32, api_client_id: i32) -> Result<()> {
+  let (is_private, host_api_client_id): (bool, i32) = game::table
+    .inner_join(player::table)
+    .filter(game::id.eq(game_id))
+    .select((game::is_private, player::api_client_id))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+
+  if !is_private || host_api_client_id == api_client_id {
+    return Ok(());
+  }
+
+  let is_player: bool = diesel::select(exists(
+    game_used_slot::table.inner_join(player::table).filter(
+      game_used_slot::game_id
+        .eq(game_id)
+        .and(player::api_client_id.eq(api_client_id)),
+    ),
+  ))
+  .get_result(conn)?;
+  if !is_player {
+    return Err(Error::GameReplayAccessDenied);
+  }
+  Ok(())
+}
+
+pub fn get(conn: &DbConn, game_id: i32) -> Result<Option<Vec<u8>>> {
+  game_replay::table
+    .find(game_id)
+    .select(game_replay::data)
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn get_stats_by_game_ids(conn: &DbConn, game_ids: &[i32]) -> Result<Vec<(i32, ReplayStats)>> {
+  let rows: Vec<(i32, Option<Value>)> = game_replay::table
+    .filter(game_replay::game_id.eq_any(game_ids))
+    .select((game_replay::game_id, game_replay::stats))
+    .load(conn)?;
+  rows
+    .into_iter()
+    .filter_map(|(game_id, stats)| Some((game_id, stats?)))
+    .map(|(game_id, stats)| Ok((game_id, serde_json::from_value(stats)?)))
+    .collect()
+}
+
+#[test]
+fn test_get_stats() {
+  use crate::player::{PlayerRef, PlayerSource};
+  use flo_w3replay::analysis::{ItemId, PlayerAnalysis, ProductionEvent};
+
+  let slot = |player_id: i32, team: i32| {
+    let mut slot = Slot::default();
+    slot.player = Some(PlayerRef {
+      id: player_id,
+      name: format!("player{}", player_id),
+      source: PlayerSource::Test,
+      realm: None,
+      battletag: None,
+    });
+    slot.settings.team = team;
+    slot
+  };
+  let player = |player_id: u8, action_count: u32| PlayerAnalysis {
+    player_id,
+    name: String::new(),
+    observer: false,
+    action_count,
+    apm_curve: vec![action_count],
+    production: vec![ProductionEvent {
+      time_ms: 1000,
+      kind: ProductionKind::Train,
+      item_id: ItemId(u32::from_be_bytes(*b"hpea")),
+    }],
+    left_at_ms: None,
+  };
+  // slot 3 is empty, slot 4 is an observer
+  let slots = vec![slot(10, 0), slot(20, 1), Slot::default(), slot(30, 24)];
+  let analysis = ReplayAnalysis {
+    game_name: String::new(),
+    duration_ms: 60_000,
+    players: vec![player(1, 120), player(2, 60), player(4, 10)],
+    chat: vec![],
+  };
+
+  let stats = get_stats(&slots, &analysis);
+  assert_eq!(stats.duration_ms, 60_000);
+  let players: Vec<_> = stats
+    .players
+    .iter()
+    .map(|p| (p.player_id, p.action_count, p.apm))
+    .collect();
+  assert_eq!(players, vec![(10, 120, 120.0), (20, 60, 60.0)]);
+  assert_eq!(stats.players[0].production[0].item_id, "hpea");
+  assert_eq!(
+    stats.players[0].production[0].kind,
+    ReplayProductionKind::Train
+  );
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_check_access() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  use crate::game::db::CreateGameParams;
+  crate::db::test_transaction(|conn| {
+    let host_api_client_id = insert_test_api_client(conn, "host")?;
+    let player_api_client_id = insert_test_api_client(conn, "player")?;
+    let other_api_client_id = insert_test_api_client(conn, "other")?;
+    let host_id = insert_test_player(conn, host_api_client_id, "host")?;
+    let player_id = insert_test_player(conn, player_api_client_id, "player")?;
+    let create = |is_private: bool| {
+      crate::game::db::create(
+        conn,
+        CreateGameParams {
+          player_id: host_id,
+          name: "test".to_string(),
+          map: test_map(2),
+          is_private,
+          is_live: false,
+          map_config: None,
+          password: None,
+          speed_percent: None,
+          reserved_slots: vec![],
+          reserved_only: false,
+          feature_flags: None,
+          auto_start: None,
+        },
+      )
+    };
+
+    let public = create(false)?;
+    check_access(conn, public.id, other_api_client_id)?;
+
+    let private = create(true)?;
+    check_access(conn, private.id, host_api_client_id)?;
+    assert!(matches!(
+      check_access(conn, private.id, player_api_client_id),
+      Err(Error::GameReplayAccessDenied)
+    ));
+    crate::game::db::add_player(
+      conn,
+      private.id,
+      player_id,
+      Some(&crate::game::access::JoinCredential::JoinToken),
+    )?;
+    check_access(conn, private.id, player_api_client_id)?;
+    assert!(matches!(
+      check_access(conn, private.id, other_api_client_id),
+      Err(Error::GameReplayAccessDenied)
+    ));
+    assert!(matches!(
+      check_access(conn, -1, host_api_client_id),
+      Err(Error::GameNotFound)
+    ));
+    Ok(())
+  })
+  .await;
+}
@@ -0,0 +1,190 @@
+use bs_diesel_utils::ExecutorRef;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::game_replay;
+use crate::settings::SettingsSource;
+use crate::state::Data;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How long stored replays are kept, replays are kept forever if nothing is configured
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReplayRetention {
+  /// `FLO_REPLAY_RETENTION_DAYS`
+  pub max_age_days: Option<i64>,
+  /// `FLO_REPLAY_RETENTION_MAX_SIZE_MB`, the oldest replays are removed first
+  pub max_total_size: Option<i64>,
+}
+
+impl ReplayRetention {
+  pub(crate) fn from_source(source: &SettingsSource) -> Result<Self> {
+    let get =
+      |name: &str| -> Result<Option<i64>> { Ok(source.parse::<i64>(name)?.filter(|v| *v > 0)) };
+    Ok(ReplayRetention {
+      max_age_days: get("FLO_REPLAY_RETENTION_DAYS")?,
+      max_total_size: get("FLO_REPLAY_RETENTION_MAX_SIZE_MB")?.map(|v| v * 1024 * 1024),
+    })
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.max_age_days.is_some() || self.max_total_size.is_some()
+  }
+}
+
+/// Removes replays outside the retention policy, returns the number of removed replays
+pub fn prune(conn: &DbConn, retention: &ReplayRetention) -> Result<usize> {
+  let mut removed = 0;
+  if let Some(days) = retention.max_age_days {
+    removed += diesel::delete(
+      game_replay::table
+        .filter(game_replay::created_at.lt(Utc::now() - ChronoDuration::days(days))),
+    )
+    .execute(conn)?;
+  }
+  if let Some(max_total_size) = retention.max_total_size {
+    removed += diesel::sql_query(
+      r#"
+      delete from game_replay
+      where game_id in (
+          select game_id from (
+              select
+                  game_id,
+                  sum(size) over (order by created_at desc, game_id desc) as total_size
+              from game_replay
+          ) t
+          where total_size > $1
+      )
+      "#,
+    )
+    .bind::<BigInt, _>(max_total_size)
+    .execute(conn)?;
+  }
+  Ok(removed)
+}
+
+/// Applies the deployment's retention policy periodically
+pub struct ReplayJanitor {
+  db: ExecutorRef,
+}
+
+#[async_trait]
+impl Actor for ReplayJanitor {
+  async fn started(&mut self, ctx: &mut Context<Self>) {
+    let retention = crate::settings::current().replay_retention;
+    if retention.is_enabled() {
+      tracing::info!("replay retention: {:?}", retention);
+    }
+    self.handle(ctx, PruneTick).await;
+  }
+}
+
+#[async_trait]
+impl Service<Data> for ReplayJanitor {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    Ok(ReplayJanitor {
+      db: registry.data().db.clone(),
+    })
+  }
+}
+
+struct PruneTick;
+
+impl Message for PruneTick {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<PruneTick> for ReplayJanitor {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: PruneTick) {
+    // the policy can be changed by a settings reload
+    let retention = crate::settings::current().replay_retention;
+    if retention.is_enabled() {
+      match self.db.exec(move |conn| prune(conn, &retention)).await {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!(removed, "replays pruned"),
+        Err(err) => tracing::error!("prune replays: {}", err),
+      }
+    }
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(PRUNE_INTERVAL).await;
+      addr.notify(PruneTick).await.ok();
+    });
+  }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_prune() {
+  use crate::db::{insert_test_api_client, insert_test_player, test_map};
+  use crate::game::db::CreateGameParams;
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "replay")?;
+    let player_id = insert_test_player(conn, api_client_id, "host")?;
+    let save = |age_days: i64, size: usize| -> Result<i32> {
+      let game = crate::game::db::create(
+        conn,
+        CreateGameParams {
+          player_id,
+          name: "test".to_string(),
+          map: test_map(2),
+          is_private: false,
+          is_live: false,
+          map_config: None,
+          password: None,
+          speed_percent: None,
+          reserved_slots: vec![],
+          reserved_only: false,
+          feature_flags: None,
+          auto_start: None,
+        },
+      )?;
+      crate::game::replay::save(conn, game.id, &vec![0; size], None)?;
+      diesel::update(game_replay::table.find(game.id))
+        .set(game_replay::created_at.eq(Utc::now() - ChronoDuration::days(age_days)))
+        .execute(conn)?;
+      Ok(game.id)
+    };
+    let remaining = |ids: &[i32]| -> Result<Vec<i32>> {
+      Ok(
+        game_replay::table
+          .select(game_replay::game_id)
+          .filter(game_replay::game_id.eq_any(ids))
+          .order(game_replay::game_id)
+          .load(conn)?,
+      )
+    };
+
+    let ids = vec![save(40, 10)?, save(20, 10)?, save(10, 10)?, save(0, 10)?];
+    assert_eq!(prune(conn, &ReplayRetention::default())?, 0);
+    prune(
+      conn,
+      &ReplayRetention {
+        max_age_days: Some(30),
+        max_total_size: None,
+      },
+    )?;
+    assert_eq!(remaining(&ids)?, ids[1..].to_vec());
+
+    // the newest replays that fit are kept
+    prune(
+      conn,
+      &ReplayRetention {
+        max_age_days: None,
+        max_total_size: Some(25),
+      },
+    )?;
+    assert_eq!(remaining(&ids)?, ids[2..].to_vec());
+    Ok(())
+  })
+  .await;
+}
@@ -0,0 +1,339 @@
+use crate::game::desync::DesyncReport;
+use crate::game::WinCondition;
+#[cfg(test)]
+use flo_w3gs::protocol::constants::LeaveReason;
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Game outcome reported by the node when a game ends
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::PacketNodeGameResult))]
+pub struct GameResultReport {
+  pub game_id: i32,
+  pub duration_ms: u32,
+  pub players: Vec<GameResultPlayer>,
+  /// Saved separately from the report
+  #[serde(skip)]
+  pub desyncs: Vec<DesyncReport>,
+  /// Trace context of the game on the node
+  #[serde(skip)]
+  pub traceparent: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, S2ProtoUnpack)]
+#[s2_grpc(message_type(flo_net::proto::flo_node::GameResultPlayer))]
+pub struct GameResultPlayer {
+  pub player_id: i32,
+  pub leave_reason: Option<u32>,
+  pub left_at_ms: u32,
+  #[s2_grpc(proto_enum)]
+  pub mmd_flag: MmdFlag,
+  pub desync: bool,
+  #[serde(default)]
+  pub action_count: u32,
+  #[serde(default)]
+  pub rtt_avg_ms: u32,
+  #[serde(default)]
+  pub rtt_p95_ms: u32,
+  #[serde(default)]
+  pub lag_caused_ms: u32,
+  #[serde(default)]
+  pub disconnect_count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_node::GameResultMmdFlag))]
+pub enum MmdFlag {
+  None = 0,
+  Winner = 1,
+  Loser = 2,
+  Drawer = 3,
+  Leaver = 4,
+  Practicing = 5,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameResolution {
+  Winner { team: i32, source: ResolutionSource },
+  Draw,
+  Invalid(InvalidReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResolutionSource {
+  Mmd,
+  LeaveReason,
+  LeaveOrder,
+  Ruling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidReason {
+  Desync,
+  PlayerMismatch,
+  NotEnoughTeams,
+  ConflictingMmdFlags,
+  Undetermined,
+  AwaitingRuling,
+  /// Invalidated by an admin ruling
+  Ruled,
+  /// Ended by an admin before the game finished
+  Aborted,
+}
+
+impl GameResolution {
+  pub fn winner_team(&self) -> Option<i32> {
+    match *self {
+      GameResolution::Winner { team, .. } => Some(team),
+      _ => None,
+    }
+  }
+
+  pub fn is_valid(&self) -> bool {
+    match *self {
+      GameResolution::Invalid(_) => false,
+      _ => true,
+    }
+  }
+
+  pub fn source(&self) -> Option<ResolutionSource> {
+    match *self {
+      GameResolution::Winner { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+
+  pub fn invalid_reason(&self) -> Option<InvalidReason> {
+    match *self {
+      GameResolution::Invalid(reason) => Some(reason),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SavedGameResult {
+  pub game_id: i32,
+  pub resolution: GameResolution,
+  pub ratings: Vec<PlayerRatingDelta>,
+  /// `false` for practice games, ratings and tournaments are not updated
+  pub ranked: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerRatingDelta {
+  pub player_id: i32,
+  pub rating_before: i32,
+  pub rating_after: i32,
+}
+
+impl SavedGameResult {
+  pub fn to_packet(&self) -> flo_net::proto::flo_connect::PacketGameResult {
+    use flo_net::proto::flo_connect;
+    flo_connect::PacketGameResult {
+      game_id: self.game_id,
+      winner_team: self.resolution.winner_team(),
+      valid: self.resolution.is_valid(),
+      ratings: self
+        .ratings
+        .iter()
+        .map(|r| flo_connect::PlayerRatingDelta {
+          player_id: r.player_id,
+          rating_before: r.rating_before,
+          rating_after: r.rating_after,
+        })
+        .collect(),
+      unranked: !self.ranked,
+    }
+  }
+}
+
+/// An admin decision overriding the resolved result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameRuling {
+  Winner(i32),
+  Draw,
+  Invalid,
+}
+
+impl GameRuling {
+  pub fn into_resolution(self) -> GameResolution {
+    match self {
+      GameRuling::Winner(team) => GameResolution::Winner {
+        team,
+        source: ResolutionSource::Ruling,
+      },
+      GameRuling::Draw => GameResolution::Draw,
+      GameRuling::Invalid => GameResolution::Invalid(InvalidReason::Ruled),
+    }
+  }
+}
+
+/// Resolves the winning team of a game.
+///
+/// `slots` are the `(player_id, team)` pairs of non-observer players.
+/// Any desync invalidates the result, otherwise the outcome is decided by the game's win condition.
+pub fn resolve(
+  condition: WinCondition,
+  slots: &[(i32, i32)],
+  report: &GameResultReport,
+) -> GameResolution {
+  let team_map: BTreeMap<i32, i32> = slots.iter().cloned().collect();
+  let players: Vec<(i32, &GameResultPlayer)> = report
+    .players
+    .iter()
+    .filter_map(|p| team_map.get(&p.player_id).map(|team| (*team, p)))
+    .collect();
+
+  if players.len() != team_map.len() || players.len() != report.players.len() {
+    return GameResolution::Invalid(InvalidReason::PlayerMismatch);
+  }
+
+  if players.iter().any(|(_, p)| p.desync) {
+    return GameResolution::Invalid(InvalidReason::Desync);
+  }
+
+  let teams: BTreeSet<i32> = team_map.values().cloned().collect();
+  if teams.len() < 2 {
+    return GameResolution::Invalid(InvalidReason::NotEnoughTeams);
+  }
+
+  condition.evaluator().evaluate(&players)
+}
+
+#[cfg(test)]
+fn player(player_id: i32, leave_reason: Option<LeaveReason>, left_at_ms: u32) -> GameResultPlayer {
+  GameResultPlayer {
+    player_id,
+    leave_reason: leave_reason.map(u32::from),
+    left_at_ms,
+    mmd_flag: MmdFlag::None,
+    desync: false,
+    action_count: 0,
+    rtt_avg_ms: 0,
+    rtt_p95_ms: 0,
+    lag_caused_ms: 0,
+    disconnect_count: 0,
+  }
+}
+
+#[test]
+fn test_resolve() {
+  let slots = [(1, 0), (2, 1)];
+  let report = |players: Vec<GameResultPlayer>| GameResultReport {
+    game_id: 1,
+    duration_ms: 10000,
+    players,
+    desyncs: vec![],
+    traceparent: String::new(),
+  };
+
+  let mut players = vec![
+    player(1, Some(LeaveReason::LeaveLost), 5000),
+    player(2, Some(LeaveReason::LeaveWon), 6000),
+  ];
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players.clone())),
+    GameResolution::Winner {
+      team: 1,
+      source: ResolutionSource::LeaveReason
+    }
+  );
+
+  players[0].mmd_flag = MmdFlag::Winner;
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players.clone())),
+    GameResolution::Winner {
+      team: 0,
+      source: ResolutionSource::Mmd
+    }
+  );
+
+  players[1].mmd_flag = MmdFlag::Winner;
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players.clone())),
+    GameResolution::Invalid(InvalidReason::ConflictingMmdFlags)
+  );
+
+  players[0].mmd_flag = MmdFlag::None;
+  players[1].mmd_flag = MmdFlag::None;
+  players[1].desync = true;
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players.clone())),
+    GameResolution::Invalid(InvalidReason::Desync)
+  );
+
+  let players = vec![player(1, None, 5000), player(2, None, 9000)];
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players)),
+    GameResolution::Winner {
+      team: 1,
+      source: ResolutionSource::LeaveOrder
+    }
+  );
+
+  let players = vec![player(1, None, 5000), player(2, None, 5000)];
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players)),
+    GameResolution::Invalid(InvalidReason::Undetermined)
+  );
+
+  let players = vec![player(1, None, 5000)];
+  assert_eq!(
+    resolve(WinCondition::Melee, &slots, &report(players)),
+    GameResolution::Invalid(InvalidReason::PlayerMismatch)
+  );
+}
+
+#[test]
+fn test_resolve_win_condition() {
+  let slots = [(1, 0), (2, 1)];
+  let report = |players: Vec<GameResultPlayer>| GameResultReport {
+    game_id: 1,
+    duration_ms: 10000,
+    players,
+    desyncs: vec![],
+    traceparent: String::new(),
+  };
+
+  // leaving doesn't decide DotA-style games
+  let players = vec![
+    player(1, Some(LeaveReason::LeaveLost), 5000),
+    player(2, Some(LeaveReason::LeaveWon), 6000),
+  ];
+  assert_eq!(
+    resolve(WinCondition::Mmd, &slots, &report(players.clone())),
+    GameResolution::Invalid(InvalidReason::Undetermined)
+  );
+
+  let mut mmd_players = players.clone();
+  mmd_players[0].mmd_flag = MmdFlag::Winner;
+  assert_eq!(
+    resolve(WinCondition::Mmd, &slots, &report(mmd_players)),
+    GameResolution::Winner {
+      team: 0,
+      source: ResolutionSource::Mmd
+    }
+  );
+
+  let mut players = players;
+  players[0].left_at_ms = 7000;
+  assert_eq!(
+    resolve(
+      WinCondition::LastTeamStanding,
+      &slots,
+      &report(players.clone())
+    ),
+    GameResolution::Winner {
+      team: 0,
+      source: ResolutionSource::LeaveOrder
+    }
+  );
+
+  assert_eq!(
+    resolve(WinCondition::Ruling, &slots, &report(players)),
+    GameResolution::Invalid(InvalidReason::AwaitingRuling)
+  );
+}
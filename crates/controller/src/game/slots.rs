@@ -3,15 +3,16 @@ use diesel::prelude::*;
 use std::collections::HashMap;
 
 use crate::game::{
-  Computer, Slot, SlotClientStatus, SlotSettings, SlotSettingsColumns, SlotStatus,
+  Computer, Race, Slot, SlotClientStatus, SlotSettings, SlotSettingsColumns, SlotStatus,
 };
 use crate::player::{PlayerRef, PlayerRefColumns};
-use crate::schema::game_used_slot;
+use crate::schema::{game_slot_control, game_used_slot};
 
 #[derive(Debug)]
 pub struct Slots {
   inner: Vec<Slot>,
   map_players: usize,
+  observers_allowed: bool,
 }
 
 impl Slots {
@@ -22,7 +23,11 @@ impl Slots {
       .map(|(idx, _)| Self::make_unused_slot(map_players, idx))
       .collect();
 
-    Self { inner, map_players }
+    Self {
+      inner,
+      map_players,
+      observers_allowed: true,
+    }
   }
 
   pub fn from_used(map_players: usize, slots: Vec<UsedSlot>) -> Self {
@@ -39,13 +44,34 @@ impl Slots {
             player: used.player,
             settings: used.settings,
             client_status: used.client_status,
+            ..Default::default()
           }
         } else {
           Self::make_unused_slot(map_players, idx)
         }
       })
       .collect();
-    Slots { map_players, inner }
+    Slots {
+      map_players,
+      inner,
+      observers_allowed: true,
+    }
+  }
+
+  /// Disallows players to join or move to referee slots
+  pub fn with_observers(mut self, allowed: bool) -> Self {
+    self.observers_allowed = allowed;
+    self
+  }
+
+  pub fn with_controls(mut self, controls: Vec<SlotControl>) -> Self {
+    for control in controls {
+      if let Some(slot) = self.inner.get_mut(control.slot_index as usize) {
+        slot.locked = control.locked;
+        slot.reserved_player_id = control.reserved_player_id;
+      }
+    }
+    self
   }
 
   pub fn as_used(&self) -> Vec<UsedSlot> {
@@ -99,11 +125,10 @@ impl Slots {
       .collect()
   }
 
-  pub fn is_full(&self) -> bool {
-    !self
-      .inner
-      .iter()
-      .any(|s| s.settings.status == SlotStatus::Open)
+  pub fn is_full(&self, player_id: i32) -> bool {
+    !self.inner.iter().any(|s| {
+      s.is_available_for(Some(player_id)) && (self.observers_allowed || s.settings.team != 24)
+    })
   }
 
   pub fn is_empty(&self) -> bool {
@@ -111,7 +136,7 @@ impl Slots {
   }
 
   pub fn join(&mut self, player: &PlayerRef) -> Option<&mut Slot> {
-    self.acquire_slot_mut().map(|s| {
+    self.acquire_slot_mut(player.id).map(|s| {
       s.player = Some(player.clone());
       s
     })
@@ -124,9 +149,13 @@ impl Slots {
       .find(|s| s.player.as_ref().map(|p| p.id) == Some(player_id))
   }
 
-  /// Find next open slot, update team, color and status then return it
-  pub fn acquire_slot_mut(&mut self) -> Option<&mut Slot> {
-    let mut open_slot_idx = None;
+  /// Find the slot reserved for the player or the next available slot,
+  /// update team, color and status then return it
+  pub fn acquire_slot_mut(&mut self, player_id: i32) -> Option<&mut Slot> {
+    let mut open_slot_idx = self
+      .inner
+      .iter()
+      .position(|s| s.reserved_player_id == Some(player_id) && s.is_available_for(Some(player_id)));
     let mut color_set = [false; 24];
     let mut occupied_player_slots = 0;
     for (i, slot) in self.inner.iter().enumerate() {
@@ -140,7 +169,7 @@ impl Slots {
           }
         }
         SlotStatus::Open => {
-          if let None = open_slot_idx {
+          if open_slot_idx.is_none() && slot.is_available_for(Some(player_id)) {
             open_slot_idx = Some(i)
           }
         }
@@ -155,7 +184,16 @@ impl Slots {
       }
     }
 
+    if occupied_player_slots >= self.map_players && !self.observers_allowed {
+      return None;
+    }
+
     if let Some(idx) = open_slot_idx {
+      for slot in &mut self.inner {
+        if slot.reserved_player_id == Some(player_id) {
+          slot.reserved_player_id = None;
+        }
+      }
       let slot = &mut self.inner[idx];
       slot.settings.team = if occupied_player_slots >= self.map_players {
         24
@@ -185,7 +223,10 @@ impl Slots {
     });
     match slot {
       Some(slot) => {
-        *slot = Default::default();
+        *slot = Slot {
+          locked: slot.locked,
+          ..Default::default()
+        };
         true
       }
       None => false,
@@ -198,7 +239,10 @@ impl Slots {
     for slot in &mut self.inner {
       if let Some(id) = slot.player.as_ref().map(|p| p.id) {
         player_ids.push(id);
-        *slot = Default::default();
+        *slot = Slot {
+          locked: slot.locked,
+          ..Default::default()
+        };
       }
     }
     player_ids
@@ -216,6 +260,14 @@ impl Slots {
       return None;
     }
 
+    if self.inner[slot_index as usize].locked {
+      return None;
+    }
+
+    let moving_player_id = self.inner[slot_index as usize]
+      .player
+      .as_ref()
+      .map(|p| p.id);
     let mut updated_slots = vec![];
 
     // handle team change first
@@ -224,6 +276,10 @@ impl Slots {
         return None;
       }
 
+      if settings.team == 24 && !self.observers_allowed {
+        return None;
+      }
+
       let mut target_index = slot_index;
       let current_settings = self.inner[slot_index as usize].settings.clone();
       let new_team = settings.team;
@@ -239,7 +295,7 @@ impl Slots {
             .inner
             .iter_mut()
             .enumerate()
-            .find(|(_index, s)| s.settings.team != 24 && s.settings.status == SlotStatus::Open)
+            .find(|(_index, s)| s.settings.team != 24 && s.is_available_for(moving_player_id))
           {
             target_index = index as i32;
             self.inner[index].player = self.inner[slot_index as usize].player.clone();
@@ -262,12 +318,19 @@ impl Slots {
         } else if current_settings.team != 24 && new_team == 24 {
           // players -> referees:
 
+          // computers can't be referees
+          if current_settings.status == SlotStatus::Occupied
+            && self.inner[slot_index as usize].player.is_none()
+          {
+            return None;
+          }
+
           // find an open referee slot
           if let Some((index, _player_slot)) = self
             .inner
             .iter_mut()
             .enumerate()
-            .find(|(_index, s)| s.settings.team == 24 && s.settings.status == SlotStatus::Open)
+            .find(|(_index, s)| s.settings.team == 24 && s.is_available_for(moving_player_id))
           {
             target_index = index as i32;
             self.inner[index].player = self.inner[slot_index as usize].player.clone();
@@ -295,6 +358,9 @@ impl Slots {
       if slot.player.is_none() {
         if slot.settings.status != settings.status {
           slot.settings.status = settings.status;
+          if settings.status != SlotStatus::Open {
+            slot.reserved_player_id = None;
+          }
           match settings.status {
             SlotStatus::Open => {
               slot.settings.computer = Computer::Easy;
@@ -314,6 +380,9 @@ impl Slots {
               slot.settings.computer = settings.computer;
             }
           }
+        } else if slot.settings.status == SlotStatus::Occupied {
+          // computer difficulty change
+          slot.settings.computer = settings.computer;
         }
       }
 
@@ -340,6 +409,144 @@ impl Slots {
     Some(updated_slots)
   }
 
+  /// Swap the occupants of two slots, teams stay with the slots.
+  /// Returns the updated slot indexes.
+  pub fn swap(&mut self, slot_index: i32, other_slot_index: i32) -> Option<Vec<i32>> {
+    if slot_index < 0 || slot_index > 23 || other_slot_index < 0 || other_slot_index > 23 {
+      return None;
+    }
+
+    if slot_index == other_slot_index {
+      return None;
+    }
+
+    let (a, b) = (slot_index as usize, other_slot_index as usize);
+    if !self.inner[a].is_used() && !self.inner[b].is_used() {
+      return None;
+    }
+
+    for &(from, to) in &[(a, b), (b, a)] {
+      let src = &self.inner[from];
+      let dst = &self.inner[to];
+      if src.locked {
+        return None;
+      }
+
+      if src.is_used() && dst.settings.team == 24 {
+        // computers can't be referees
+        if !self.observers_allowed
+          || (src.settings.status == SlotStatus::Occupied && src.player.is_none())
+        {
+          return None;
+        }
+      }
+
+      if src.is_used() && dst.reserved_player_id.is_some() {
+        let player_id = src.player.as_ref().map(|p| p.id);
+        if dst.reserved_player_id != player_id {
+          return None;
+        }
+      }
+    }
+
+    let (left, right) = self.inner.split_at_mut(b.max(a));
+    let (x, y) = (&mut left[a.min(b)], &mut right[0]);
+    std::mem::swap(&mut x.player, &mut y.player);
+    std::mem::swap(&mut x.client_status, &mut y.client_status);
+    std::mem::swap(&mut x.settings.color, &mut y.settings.color);
+    std::mem::swap(&mut x.settings.computer, &mut y.settings.computer);
+    std::mem::swap(&mut x.settings.handicap, &mut y.settings.handicap);
+    std::mem::swap(&mut x.settings.status, &mut y.settings.status);
+    std::mem::swap(&mut x.settings.race, &mut y.settings.race);
+
+    for &index in &[a, b] {
+      let slot = &mut self.inner[index];
+      if slot.is_used() {
+        slot.reserved_player_id = None;
+      }
+      if slot.settings.team == 24 {
+        slot.settings.color = 0;
+      }
+    }
+
+    for &index in &[a, b] {
+      let slot = &self.inner[index];
+      if slot.settings.team == 24 || slot.settings.status != SlotStatus::Occupied {
+        continue;
+      }
+      let color = slot.settings.color;
+      let taken = self.inner.iter().enumerate().any(|(i, s)| {
+        i != index
+          && s.settings.status == SlotStatus::Occupied
+          && s.settings.team != 24
+          && s.settings.color == color
+      });
+      if taken {
+        let color_set = self.get_color_set();
+        if let Some(color) = color_set.iter().position(|v| !*v) {
+          self.inner[index].settings.color = color as i32;
+        }
+      }
+    }
+
+    Some(vec![slot_index, other_slot_index])
+  }
+
+  pub fn set_locked(&mut self, slot_index: i32, locked: bool) -> Option<&Slot> {
+    let slot = self.inner.get_mut(slot_index as usize)?;
+    slot.locked = locked;
+    Some(slot)
+  }
+
+  /// Reserve an open slot for a player who hasn't joined yet, `None` removes the reservation.
+  /// Returns the updated slot indexes.
+  pub fn reserve(&mut self, slot_index: i32, player_id: Option<i32>) -> Option<Vec<i32>> {
+    if slot_index < 0 || slot_index > 23 {
+      return None;
+    }
+
+    let player_id = match player_id {
+      Some(player_id) => player_id,
+      None => {
+        self.inner[slot_index as usize].reserved_player_id = None;
+        return Some(vec![slot_index]);
+      }
+    };
+
+    let slot = &self.inner[slot_index as usize];
+    if slot.locked || slot.settings.status != SlotStatus::Open {
+      return None;
+    }
+
+    if self.find_player_slot(player_id).is_some() {
+      return None;
+    }
+
+    let mut updated_indexes = vec![slot_index];
+    for (index, slot) in self.inner.iter_mut().enumerate() {
+      if index != slot_index as usize && slot.reserved_player_id == Some(player_id) {
+        slot.reserved_player_id = None;
+        updated_indexes.push(index as i32);
+      }
+    }
+    self.inner[slot_index as usize].reserved_player_id = Some(player_id);
+
+    Some(updated_indexes)
+  }
+
+  /// Replaces the race of every occupied non-observer slot.
+  /// Returns the updated slot indexes.
+  pub fn assign_races(&mut self, mut pick: impl FnMut() -> Race) -> Vec<i32> {
+    let mut updated_indexes = vec![];
+    for (index, slot) in self.inner.iter_mut().enumerate() {
+      if slot.settings.status == SlotStatus::Occupied && slot.settings.team != 24 {
+        slot.settings.race = pick();
+        updated_indexes.push(index as i32);
+      }
+    }
+    updated_indexes
+  }
+
   fn get_color_set(&self) -> [bool; 24] {
     let mut set = [false; 24];
     for slot in &self.inner {
@@ -390,6 +597,27 @@ impl UsedSlot {
   }
 }
 
+#[derive(Debug, Queryable)]
+pub struct SlotControl {
+  pub slot_index: i32,
+  pub locked: bool,
+  pub reserved_player_id: Option<i32>,
+}
+
+pub(crate) type SlotControlColumns = (
+  game_slot_control::dsl::slot_index,
+  game_slot_control::dsl::locked,
+  game_slot_control::dsl::reserved_player_id,
+);
+
+impl SlotControl {
+  pub(crate) const COLUMNS: SlotControlColumns = (
+    game_slot_control::dsl::slot_index,
+    game_slot_control::dsl::locked,
+    game_slot_control::dsl::reserved_player_id,
+  );
+}
+
 #[derive(Debug, Queryable)]
 pub struct UsedSlotInfo {
   pub slot_index: i32,
@@ -412,3 +640,144 @@ impl UsedSlotInfo {
     )
   }
 }
+
+#[test]
+fn test_update_computer_slot() {
+  let mut slots = Slots::new(2);
+  let settings = SlotSettings {
+    team: 1,
+    color: 1,
+    computer: Computer::Normal,
+    handicap: 100,
+    status: SlotStatus::Occupied,
+    race: Race::Orc,
+  };
+  assert!(slots.update_slot_at(1, &settings).is_some());
+  assert_eq!(slots.inner[1].settings.computer, Computer::Normal);
+
+  let settings = SlotSettings {
+    computer: Computer::Insane,
+    ..settings
+  };
+  assert!(slots.update_slot_at(1, &settings).is_some());
+  assert_eq!(slots.inner[1].settings.computer, Computer::Insane);
+  assert_eq!(slots.inner[1].settings.race, Race::Orc);
+
+  let settings = SlotSettings {
+    team: 24,
+    ..settings
+  };
+  assert!(slots.update_slot_at(1, &settings).is_none());
+}
+
+#[cfg(test)]
+fn test_player(id: i32) -> PlayerRef {
+  use crate::player::PlayerSource;
+  PlayerRef {
+    id,
+    name: format!("player{}", id),
+    source: PlayerSource::Test,
+    realm: None,
+    battletag: None,
+  }
+}
+
+#[test]
+fn test_swap_slots() {
+  let mut slots = Slots::new(3);
+  slots.join(&test_player(1));
+  slots.join(&test_player(2));
+
+  assert_eq!(slots.swap(0, 1), Some(vec![0, 1]));
+  assert_eq!(slots.inner[0].player.as_ref().map(|p| p.id), Some(2));
+  assert_eq!(slots.inner[0].settings.team, 0);
+  assert_eq!(slots.inner[0].settings.color, 1);
+  assert_eq!(slots.inner[1].player.as_ref().map(|p| p.id), Some(1));
+  assert_eq!(slots.inner[1].settings.team, 1);
+
+  // player -> referee
+  assert_eq!(slots.swap(0, 3), Some(vec![0, 3]));
+  assert_eq!(slots.inner[0].settings.status, SlotStatus::Open);
+  assert_eq!(slots.inner[3].player.as_ref().map(|p| p.id), Some(2));
+  assert_eq!(slots.inner[3].settings.team, 24);
+  assert_eq!(slots.inner[3].settings.color, 0);
+
+  // referee -> player, color 0 is taken by player 1
+  assert_eq!(slots.swap(3, 2), Some(vec![3, 2]));
+  assert_eq!(slots.inner[2].player.as_ref().map(|p| p.id), Some(2));
+  assert_eq!(slots.inner[2].settings.color, 1);
+  assert_eq!(slots.inner[3].settings.status, SlotStatus::Open);
+
+  assert!(slots.swap(4, 5).is_none());
+  assert!(slots.swap(1, 1).is_none());
+  assert!(slots.swap(1, 24).is_none());
+
+  slots.set_locked(1, true);
+  assert!(slots.swap(1, 2).is_none());
+  let settings = slots.inner[1].settings.clone();
+  assert!(slots.update_slot_at(1, &settings).is_none());
+}
+
+#[test]
+fn test_reserve_slot() {
+  let mut slots = Slots::new(3);
+  slots.join(&test_player(1));
+
+  assert!(slots.reserve(0, Some(2)).is_none());
+  assert_eq!(slots.reserve(2, Some(2)), Some(vec![2]));
+  assert_eq!(slots.reserve(1, Some(2)), Some(vec![1, 2]));
+  assert_eq!(slots.inner[2].reserved_player_id, None);
+
+  slots.set_locked(2, true);
+  assert!(slots.reserve(2, Some(3)).is_none());
+
+  // other players skip reserved and locked slots
+  slots.join(&test_player(3));
+  assert_eq!(slots.inner[3].player.as_ref().map(|p| p.id), Some(3));
+  assert!(!slots.is_full(4));
+
+  slots.join(&test_player(2));
+  assert_eq!(slots.inner[1].player.as_ref().map(|p| p.id), Some(2));
+  assert_eq!(slots.inner[1].reserved_player_id, None);
+
+  assert!(slots.release_player_slot(2));
+  assert!(slots.release_player_slot(1));
+  assert!(slots.inner[2].locked);
+}
+
+#[test]
+fn test_assign_races() {
+  let mut slots = Slots::new(3);
+  slots.join(&test_player(1));
+  slots.join(&test_player(2));
+
+  let mut races = vec![Race::Orc, Race::Undead].into_iter();
+  assert_eq!(slots.assign_races(|| races.next().unwrap()), vec![0, 1]);
+  assert_eq!(slots.inner[0].settings.race, Race::Orc);
+  assert_eq!(slots.inner[1].settings.race, Race::Undead);
+  assert_eq!(slots.inner[2].settings.race, Race::Human);
+}
+
+#[test]
+fn test_observers() {
+  let mut slots = Slots::new(2);
+  slots.join(&test_player(1));
+  slots.join(&test_player(2));
+  assert!(!slots.is_full(3));
+  let slot = slots.join(&test_player(3)).unwrap();
+  assert_eq!(slot.settings.team, 24);
+  assert_eq!(slots.inner[2].player.as_ref().map(|p| p.id), Some(3));
+
+  let mut slots = Slots::new(2).with_observers(false);
+  slots.join(&test_player(1));
+  assert!(!slots.is_full(2));
+  slots.join(&test_player(2));
+  assert!(slots.is_full(3));
+  assert!(slots.join(&test_player(3)).is_none());
+  assert_eq!(slots.get_player_ids(), vec![1, 2]);
+  assert!(slots.inner[..2].iter().all(|s| s.settings.team != 24));
+
+  assert!(slots.release_player_slot(2));
+  assert!(!slots.is_full(3));
+  assert_eq!(slots.join(&test_player(3)).unwrap().settings.team, 1);
+}
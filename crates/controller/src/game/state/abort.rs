@@ -0,0 +1,67 @@
+use crate::error::*;
+use crate::game::db;
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use crate::node::messages::NodeAbortGame;
+use crate::state::ActorMapExt;
+use flo_net::packet::FloPacket;
+use flo_state::{async_trait, Context, Handler, Message};
+
+/// Ends a live game on the node and voids its result
+pub struct AbortGame {
+  pub reason: String,
+}
+
+impl Message for AbortGame {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<AbortGame> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, AbortGame { reason }: AbortGame) -> Result<()> {
+    let game_id = self.game_id;
+
+    match self.status {
+      GameStatus::Created | GameStatus::Running => {}
+      _ => return Err(Error::GameNotAbortable),
+    }
+
+    let node_id = self
+      .selected_node_id
+      .ok_or_else(|| Error::GameNodeNotSelected)?;
+
+    // void the result first, a result reported by the node after this is rejected
+    let saved = self
+      .db
+      .exec({
+        let reason = reason.clone();
+        move |conn| db::abort_game(conn, game_id, &reason)
+      })
+      .await?;
+
+    tracing::info!(game_id, "game aborted: {}", reason);
+
+    self.status = GameStatus::Terminated;
+
+    let res = self
+      .nodes
+      .send_to(node_id, NodeAbortGame { game_id, reason })
+      .await;
+    let res = match res {
+      Ok(deferred) => deferred.await.or_cancelled(),
+      Err(err) => Err(err),
+    };
+    if let Err(err) = res {
+      tracing::error!(game_id, node_id, "abort game node error: {}", err);
+    }
+
+    let frame = saved.to_packet().encode_as_frame()?;
+    self.publish(&frame);
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    Ok(())
+  }
+}
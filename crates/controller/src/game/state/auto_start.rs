@@ -0,0 +1,183 @@
+use crate::error::*;
+use crate::game::state::cancel::CancelGame;
+use crate::game::state::registry::Remove;
+use crate::game::state::{GameActor, GameRegistry};
+use crate::game::{AutoStartPolicy, GameStatus};
+use chrono::{DateTime, Utc};
+use flo_state::{async_trait, Context, Handler, Message};
+use std::time::Duration;
+use tokio::time::sleep;
+
+const AUTO_START_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoStartDecision {
+  Wait,
+  Start,
+  /// Not enough players at the scheduled time
+  Cancel,
+}
+
+impl AutoStartPolicy {
+  pub fn decide(&self, players: usize, now: DateTime<Utc>) -> AutoStartDecision {
+    let players = players as i32;
+    if let Some(min_players) = self.min_players {
+      if players >= min_players {
+        return AutoStartDecision::Start;
+      }
+    }
+    if self.is_due(now) {
+      if players >= self.min_ready_players.unwrap_or(1) {
+        AutoStartDecision::Start
+      } else {
+        AutoStartDecision::Cancel
+      }
+    } else {
+      AutoStartDecision::Wait
+    }
+  }
+
+  /// The scheduled time has been reached
+  pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+    self.start_at.map(|t| t <= now).unwrap_or(false)
+  }
+}
+
+/// Starts a lobby on behalf of the host
+pub struct AutoStartGame;
+
+impl Message for AutoStartGame {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<AutoStartGame> for GameActor {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: AutoStartGame) -> Result<()> {
+    if self.status != GameStatus::Preparing {
+      return Err(Error::GameStarted);
+    }
+    self.start_check(ctx, None).await
+  }
+}
+
+impl GameRegistry {
+  async fn check_auto_starts(&mut self, ctx: &mut Context<Self>) {
+    let now = Utc::now();
+    let mut cancelled = vec![];
+    let ids: Vec<i32> = self.auto_start.keys().cloned().collect();
+    for id in ids {
+      let (policy, addr) = match (self.auto_start.get(&id), self.map.get(&id)) {
+        (Some(policy), Some(game)) => (policy.clone(), game.addr()),
+        _ => {
+          self.auto_start.remove(&id);
+          continue;
+        }
+      };
+      let players = self
+        .game_players_map
+        .get(&id)
+        .map(Vec::len)
+        .unwrap_or_default();
+      let due = policy.is_due(now);
+      match policy.decide(players, now) {
+        AutoStartDecision::Wait => {}
+        AutoStartDecision::Start => match addr.send(AutoStartGame).await {
+          Ok(Ok(())) => {
+            tracing::info!(game_id = id, "game auto started");
+            self.auto_start.remove(&id);
+          }
+          // the host can still select a node before the scheduled time
+          Ok(Err(Error::GameNodeNotSelected)) if !due => {}
+          Ok(Err(Error::GameStarted)) => {
+            self.auto_start.remove(&id);
+          }
+          Ok(Err(err)) => {
+            tracing::warn!(game_id = id, "auto start: {}", err);
+            self.auto_start.remove(&id);
+            if due {
+              cancelled.push(id);
+            }
+          }
+          Err(err) => {
+            tracing::error!(game_id = id, "auto start: {}", err);
+            self.auto_start.remove(&id);
+          }
+        },
+        AutoStartDecision::Cancel => {
+          self.auto_start.remove(&id);
+          cancelled.push(id);
+        }
+      }
+    }
+
+    for id in &cancelled {
+      if let Some(game) = self.map.get(id) {
+        if let Err(err) = game.addr().send(CancelGame { player_id: None }).await {
+          tracing::error!(game_id = id, "cancel auto start game: {}", err);
+        } else {
+          tracing::info!(game_id = id, "auto start game cancelled");
+        }
+      }
+    }
+
+    if !cancelled.is_empty() {
+      let addr = ctx.addr();
+      ctx.spawn(async move {
+        for game_id in cancelled {
+          if let Err(err) = addr.send(Remove { game_id }).await {
+            tracing::error!(game_id, "remove cancelled game: {}", err);
+          }
+        }
+      })
+    }
+  }
+}
+
+pub(crate) struct CheckAutoStarts;
+
+impl Message for CheckAutoStarts {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<CheckAutoStarts> for GameRegistry {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: CheckAutoStarts) {
+    self.check_auto_starts(ctx).await;
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(AUTO_START_CHECK_INTERVAL).await;
+      addr.notify(CheckAutoStarts).await.ok();
+    });
+  }
+}
+
+#[test]
+fn test_auto_start_decide() {
+  let now = Utc::now();
+  let policy = AutoStartPolicy {
+    min_players: Some(4),
+    start_at: None,
+    min_ready_players: None,
+  };
+  assert_eq!(policy.decide(3, now), AutoStartDecision::Wait);
+  assert_eq!(policy.decide(4, now), AutoStartDecision::Start);
+
+  let policy = AutoStartPolicy {
+    min_players: None,
+    start_at: Some(now),
+    min_ready_players: Some(2),
+  };
+  let before = now - chrono::Duration::seconds(1);
+  assert_eq!(policy.decide(2, before), AutoStartDecision::Wait);
+  assert_eq!(policy.decide(1, now), AutoStartDecision::Cancel);
+  assert_eq!(policy.decide(2, now), AutoStartDecision::Start);
+
+  let policy = AutoStartPolicy {
+    min_players: Some(4),
+    start_at: Some(now),
+    min_ready_players: None,
+  };
+  assert_eq!(policy.decide(1, before), AutoStartDecision::Wait);
+  assert_eq!(policy.decide(4, before), AutoStartDecision::Start);
+  assert_eq!(policy.decide(1, now), AutoStartDecision::Start);
+}
@@ -0,0 +1,164 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use crate::rate_limit::{RateLimitScope, RateLimitSubject};
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::PacketGameLobbyChat;
+use flo_state::{async_trait, Context, Handler, Message};
+
+const MAX_MESSAGE_LEN: usize = 255;
+
+/// Slot management commands typed by the host in the lobby chat, slot numbers start from 1
+#[derive(Debug, PartialEq)]
+pub enum SlotCommand {
+  Swap(i32, i32),
+  Lock(i32, bool),
+  Reserve(i32, Option<i32>),
+}
+
+impl SlotCommand {
+  pub fn parse(message: &str) -> Option<Self> {
+    let mut parts = message.trim().split_whitespace();
+    let name = parts.next()?;
+    let mut next_index = || -> Option<i32> {
+      parts
+        .next()?
+        .parse::<i32>()
+        .ok()
+        .filter(|v| *v > 0)
+        .map(|v| v - 1)
+    };
+    let cmd = match name {
+      "-swap" => SlotCommand::Swap(next_index()?, next_index()?),
+      "-lock" => SlotCommand::Lock(next_index()?, true),
+      "-unlock" => SlotCommand::Lock(next_index()?, false),
+      "-reserve" => {
+        let slot_index = next_index()?;
+        SlotCommand::Reserve(slot_index, Some(parts.next()?.parse().ok()?))
+      }
+      "-unreserve" => SlotCommand::Reserve(next_index()?, None),
+      _ => return None,
+    };
+    if parts.next().is_some() {
+      return None;
+    }
+    Some(cmd)
+  }
+}
+
+pub struct LobbyChat {
+  pub player_id: i32,
+  pub message: String,
+}
+
+impl Message for LobbyChat {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<LobbyChat> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LobbyChat { player_id, message }: LobbyChat,
+  ) -> Result<()> {
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    match self.status {
+      GameStatus::Preparing | GameStatus::Created => {}
+      _ => return Err(Error::GameStarted),
+    }
+
+    let message = match truncate_message(&message) {
+      Some(v) => v,
+      None => return Ok(()),
+    };
+
+    self.lobby_chat_limiter.check(
+      RateLimitScope::LobbyChat,
+      &[RateLimitSubject::Player(player_id)],
+    )?;
+
+    let frame = PacketGameLobbyChat {
+      game_id: self.game_id,
+      player_id,
+      message,
+    }
+    .encode_as_frame()?;
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    Ok(())
+  }
+}
+
+/// Trims the message and caps it at `MAX_MESSAGE_LEN` characters, `None` if it is blank
+fn truncate_message(message: &str) -> Option<String> {
+  let message: String = message.trim().chars().take(MAX_MESSAGE_LEN).collect();
+  if message.is_empty() {
+    None
+  } else {
+    Some(message)
+  }
+}
+
+#[test]
+fn test_truncate_message() {
+  assert_eq!(truncate_message("  "), None);
+  assert_eq!(truncate_message(" gl hf "), Some("gl hf".to_string()));
+  let long = "é".repeat(MAX_MESSAGE_LEN + 10);
+  assert_eq!(
+    truncate_message(&long).map(|v| v.chars().count()),
+    Some(MAX_MESSAGE_LEN)
+  );
+}
+
+#[test]
+fn test_lobby_chat_rate_limit() {
+  use crate::rate_limit::RateLimiter;
+
+  let limiter = RateLimiter::default();
+  let check = |player_id| {
+    limiter.check(
+      RateLimitScope::LobbyChat,
+      &[RateLimitSubject::Player(player_id)],
+    )
+  };
+  for _ in 0..5 {
+    assert!(check(1).is_ok());
+  }
+  assert!(matches!(check(1), Err(Error::RateLimited { .. })));
+  assert!(check(2).is_ok());
+}
+
+#[test]
+fn test_parse_slot_command() {
+  assert_eq!(
+    SlotCommand::parse("-swap 1 2"),
+    Some(SlotCommand::Swap(0, 1))
+  );
+  assert_eq!(
+    SlotCommand::parse(" -lock 3 "),
+    Some(SlotCommand::Lock(2, true))
+  );
+  assert_eq!(
+    SlotCommand::parse("-unlock 3"),
+    Some(SlotCommand::Lock(2, false))
+  );
+  assert_eq!(
+    SlotCommand::parse("-reserve 2 42"),
+    Some(SlotCommand::Reserve(1, Some(42)))
+  );
+  assert_eq!(
+    SlotCommand::parse("-unreserve 2"),
+    Some(SlotCommand::Reserve(1, None))
+  );
+  assert_eq!(SlotCommand::parse("-swap 0 2"), None);
+  assert_eq!(SlotCommand::parse("-swap 1"), None);
+  assert_eq!(SlotCommand::parse("-lock 1 2"), None);
+  assert_eq!(SlotCommand::parse("gl hf"), None);
+}
@@ -1,8 +1,12 @@
+use crate::discord::DiscordEvent;
 use crate::error::{Error, Result};
-use crate::game::db::{CreateGameAsBotParams, CreateGameParams};
+use crate::game::db::{
+  CreateGameAsBotParams, CreateGameForPlayersParams, CreateGameParams, RehostGameParams,
+};
 use crate::game::state::registry::Register;
-use crate::game::state::GameRegistry;
+use crate::game::state::{notify_discord, GameRegistry};
 use crate::game::{Game, GameStatus};
+use crate::node::messages::ListNode;
 use flo_state::{async_trait, Context, Handler, Message};
 
 pub struct CreateGame {
@@ -32,6 +36,7 @@ impl Handler<CreateGame> for GameRegistry {
       host_player: game.created_by.id,
       players: game.get_player_ids(),
       node_id: None,
+      auto_start: game.auto_start.clone(),
     });
 
     self
@@ -39,6 +44,8 @@ impl Handler<CreateGame> for GameRegistry {
       .player_replace_game(player_id, game.clone(), vec![])
       .await?;
 
+    notify_discord(&self.discord, game.id, DiscordEvent::LobbyCreated).await;
+
     Ok(game)
   }
 }
@@ -80,6 +87,52 @@ impl Handler<CreateGameAsBot> for GameRegistry {
       host_player: game.created_by.id,
       players: player_ids.clone(),
       node_id: game.node.as_ref().map(|v| v.id),
+      auto_start: game.auto_start.clone(),
+    });
+
+    self
+      .players
+      .players_replace_game(player_ids, game.clone(), mute_list_map)
+      .await?;
+
+    notify_discord(&self.discord, game.id, DiscordEvent::LobbyCreated).await;
+
+    Ok(game)
+  }
+}
+
+pub struct CreateGameForPlayers {
+  pub params: CreateGameForPlayersParams,
+}
+
+impl Message for CreateGameForPlayers {
+  type Result = Result<Game>;
+}
+
+#[async_trait]
+impl Handler<CreateGameForPlayers> for GameRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    CreateGameForPlayers { params }: CreateGameForPlayers,
+  ) -> <CreateGameForPlayers as Message>::Result {
+    let (game, player_ids, mute_list_map) = self
+      .db
+      .exec(move |conn| {
+        let game = crate::game::db::create_for_players(conn, params)?;
+        let player_ids = game.get_player_ids();
+        let mute_list_map = crate::player::db::get_mute_list_map(conn, &player_ids)?;
+        Ok::<_, Error>((game, player_ids, mute_list_map))
+      })
+      .await?;
+
+    self.register(Register {
+      id: game.id,
+      status: GameStatus::Preparing,
+      host_player: game.created_by.id,
+      players: player_ids.clone(),
+      node_id: game.node.as_ref().map(|v| v.id),
+      auto_start: game.auto_start.clone(),
     });
 
     self
@@ -87,6 +140,72 @@ impl Handler<CreateGameAsBot> for GameRegistry {
       .players_replace_game(player_ids, game.clone(), mute_list_map)
       .await?;
 
+    notify_discord(&self.discord, game.id, DiscordEvent::LobbyCreated).await;
+
+    Ok(game)
+  }
+}
+
+pub struct RehostGame {
+  pub game_id: i32,
+  pub player_id: i32,
+  pub node_id: Option<i32>,
+}
+
+impl Message for RehostGame {
+  type Result = Result<Game>;
+}
+
+#[async_trait]
+impl Handler<RehostGame> for GameRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    RehostGame {
+      game_id,
+      player_id,
+      node_id,
+    }: RehostGame,
+  ) -> <RehostGame as Message>::Result {
+    let online_node_ids = self
+      .nodes
+      .send(ListNode)
+      .await?
+      .into_iter()
+      .map(|node| node.id)
+      .collect();
+    let params = RehostGameParams {
+      game_id,
+      player_id,
+      node_id,
+      online_node_ids,
+    };
+    let (game, player_ids, mute_list_map) = self
+      .db
+      .exec(move |conn| {
+        let game = crate::game::db::rehost(conn, params)?;
+        let player_ids = game.get_player_ids();
+        let mute_list_map = crate::player::db::get_mute_list_map(conn, &player_ids)?;
+        Ok::<_, Error>((game, player_ids, mute_list_map))
+      })
+      .await?;
+
+    self.register(Register {
+      id: game.id,
+      status: GameStatus::Preparing,
+      host_player: game.created_by.id,
+      players: player_ids.clone(),
+      node_id: game.node.as_ref().map(|v| v.id),
+      auto_start: game.auto_start.clone(),
+    });
+
+    self
+      .players
+      .players_replace_game(player_ids, game.clone(), mute_list_map)
+      .await?;
+
+    notify_discord(&self.discord, game.id, DiscordEvent::LobbyCreated).await;
+
     Ok(game)
   }
 }
@@ -1,15 +1,19 @@
 use crate::error::*;
+use crate::game::access::JoinCredential;
 use crate::game::state::GameActor;
 use crate::game::Game;
 use diesel::prelude::*;
 use flo_net::packet::FloPacket;
 use flo_net::proto;
 use flo_state::{async_trait, Context, Handler, Message};
+use tracing_futures::Instrument;
 
 use s2_grpc_utils::S2ProtoPack;
 
 pub struct PlayerJoin {
   pub player_id: i32,
+  /// Required to join private games
+  pub credential: Option<JoinCredential>,
 }
 
 impl Message for PlayerJoin {
@@ -21,14 +25,24 @@ impl Handler<PlayerJoin> for GameActor {
   async fn handle(
     &mut self,
     _: &mut Context<Self>,
-    PlayerJoin { player_id }: PlayerJoin,
+    PlayerJoin {
+      player_id,
+      credential,
+    }: PlayerJoin,
   ) -> Result<Game> {
+    let span = tracing::info_span!(parent: &self.span, "join", player_id);
+    self.join(player_id, credential).instrument(span).await
+  }
+}
+
+impl GameActor {
+  async fn join(&mut self, player_id: i32, credential: Option<JoinCredential>) -> Result<Game> {
     let game_id = self.game_id;
     let (game, mute_list) = self
       .db
       .exec(move |conn| {
         conn.transaction(|| {
-          crate::game::db::add_player(conn, game_id, player_id)?;
+          crate::game::db::add_player(conn, game_id, player_id, credential.as_ref())?;
           let game = crate::game::db::get_full(conn, game_id)?;
           let mut mute_list_map =
             crate::player::db::get_mute_list_map(conn, &game.get_player_ids())?;
@@ -68,6 +82,7 @@ impl Handler<PlayerJoin> for GameActor {
         }
       }
       .encode_as_frame()?;
+      self.publish(&frame);
       self.player_reg.broadcast(players, frame).await?;
     }
 
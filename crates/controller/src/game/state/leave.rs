@@ -32,6 +32,11 @@ impl Handler<PlayerLeave> for GameActor {
     PlayerLeave { player_id }: PlayerLeave,
   ) -> Result<PlayerLeaveResult> {
     let game_id = self.game_id;
+
+    if let Err(err) = self.record_leave_penalty(player_id).await {
+      tracing::error!(game_id, player_id, "record leave penalty: {}", err);
+    }
+
     let result = match self.status {
       GameStatus::Preparing => leave_game_lobby(self, game_id, player_id).await?,
       GameStatus::Created | GameStatus::Running | GameStatus::Paused => {
@@ -65,6 +70,27 @@ impl Handler<PlayerLeave> for GameActor {
   }
 }
 
+impl GameActor {
+  /// Dodges and early leaves of matchmaking games are penalized
+  pub(crate) async fn record_leave_penalty(&self, player_id: i32) -> Result<()> {
+    let (game_id, status) = (self.game_id, self.status);
+    let penalty = self
+      .db
+      .exec(move |conn| crate::penalty::db::record_leave(conn, game_id, player_id, status))
+      .await?;
+    if let Some(penalty) = penalty {
+      tracing::info!(
+        game_id,
+        player_id,
+        "penalty applied: {:?} {:?}",
+        penalty.reason,
+        penalty.penalty_type
+      );
+    }
+    Ok(())
+  }
+}
+
 #[tracing::instrument(skip(state))]
 async fn leave_game_lobby(
   state: &mut GameActor,
@@ -169,6 +195,7 @@ async fn leave_game_abort(
     pkt
   }
   .encode_as_frame()?;
+  state.publish(&frame);
   state
     .player_reg
     .broadcast(active_player_ids.clone(), frame)
@@ -214,6 +241,7 @@ async fn broadcast(
       reason: proto::flo_connect::PlayerLeaveReason::Left.into(),
     }
     .encode_as_frame()?;
+    state.publish(&frame_player_leave);
 
     for id in recipient_players {
       frame_map.insert(*id, frame_player_leave.clone().into());
@@ -1,44 +1,61 @@
+pub mod abort;
+pub mod auto_start;
 pub mod cancel;
+pub mod chat;
 pub mod create;
 pub mod join;
 pub mod leave;
 pub mod node;
 pub mod player;
 pub mod registry;
+pub mod replay;
+pub mod result;
 pub mod slot;
 pub mod start;
 pub mod status;
+pub mod subscribe;
 
 pub use status::{GameSlotClientStatusUpdate, GameStatusUpdate};
 
+use crate::discord::notifier::NotifyDiscord;
+use crate::discord::{DiscordEvent, DiscordNotifier};
 use crate::error::*;
 use crate::game::db::{get_all_active_game_state, get_expired_games};
-use crate::game::{GameStatus, SlotClientStatus};
+use crate::game::timeline::ClientStatusChange;
+use crate::game::{AutoStartPolicy, GameStatus, SlotClientStatus};
 use crate::node::{NodeRegistry, PlayerToken};
 use crate::player::state::sender::PlayerRegistryHandle;
+use crate::rate_limit::RateLimiter;
 
+use crate::game::state::auto_start::CheckAutoStarts;
 use crate::game::state::cancel::CancelGame;
 use crate::game::state::registry::Remove;
 use crate::player::state::PlayerRegistry;
 use crate::state::{Data, GetActorEntry};
 use bs_diesel_utils::ExecutorRef;
+use flo_net::packet::Frame;
 use flo_state::*;
 use start::StartGameState;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::time::Duration;
+use tokio::sync::broadcast;
 use tokio::time::sleep;
 
-const GAME_INACTIVE_CHECK_INTERVAL: Duration = Duration::from_secs(3600 * 30);
+const GAME_INACTIVE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const GAME_UPDATES_CAPACITY: usize = 64;
 
 pub struct GameRegistry {
   db: ExecutorRef,
   players: PlayerRegistryHandle,
   nodes: Addr<NodeRegistry>,
+  discord: Addr<DiscordNotifier>,
   map: BTreeMap<i32, Owner<GameActor>>,
   player_games_map: BTreeMap<i32, Vec<i32>>,
   game_players_map: BTreeMap<i32, Vec<i32>>,
   game_node_map: BTreeMap<i32, i32>,
+  /// Lobbies waiting to be started by the controller
+  auto_start: BTreeMap<i32, AutoStartPolicy>,
 }
 
 impl GameRegistry {
@@ -46,12 +63,14 @@ impl GameRegistry {
     db: ExecutorRef,
     player_packet_sender: PlayerRegistryHandle,
     nodes: Addr<NodeRegistry>,
+    discord: Addr<DiscordNotifier>,
   ) -> Result<GameRegistry> {
     let games = db.exec(|conn| get_all_active_game_state(conn)).await?;
     let mut map = BTreeMap::new();
     let mut player_games_map = BTreeMap::new();
     let mut game_players_map = BTreeMap::new();
     let mut game_node_map = BTreeMap::new();
+    let mut auto_start = BTreeMap::new();
 
     for game in games {
       let mut players = Vec::with_capacity(game.players.len());
@@ -73,6 +92,12 @@ impl GameRegistry {
         game_node_map.insert(game.id, node_id);
       }
 
+      if game.status == GameStatus::Preparing {
+        if let Some(policy) = game.auto_start {
+          auto_start.insert(game.id, policy);
+        }
+      }
+
       map.insert(
         game.id,
         Owner::new(GameActor {
@@ -80,6 +105,7 @@ impl GameRegistry {
           db: db.clone(),
           player_reg: player_packet_sender.clone(),
           nodes: nodes.clone(),
+          discord: discord.clone(),
           status: game.status,
           host_player: game.created_by,
           players,
@@ -87,32 +113,52 @@ impl GameRegistry {
           start_state: None,
           player_tokens,
           player_client_status_map: Default::default(),
+          client_status_changes: vec![],
+          updates: game_updates_sender(),
+          span: game_span(game.id),
+          lobby_chat_limiter: Default::default(),
         }),
       );
     }
 
+    crate::metrics::GAMES.set(map.len() as i64);
+
     let state = GameRegistry {
       db: db.clone(),
       players: player_packet_sender.clone(),
       nodes: nodes.clone(),
+      discord,
       map,
       player_games_map,
       game_players_map,
       game_node_map,
+      auto_start,
     };
 
     Ok(state)
   }
 
   async fn remove_expired_games(&mut self, ctx: &mut Context<Self>) -> Result<()> {
-    let ids = self.db.exec(|conn| get_expired_games(conn)).await?;
+    let idle_minutes = crate::settings::current().lobby_timeout.idle_minutes;
+    let ids = self
+      .db
+      .exec(move |conn| get_expired_games(conn, idle_minutes))
+      .await?;
 
+    let now = chrono::Utc::now();
     let mut cancelled = vec![];
     for id in ids {
+      // scheduled lobbies are cancelled by the auto start check
+      if let Some(policy) = self.auto_start.get(&id) {
+        if policy.start_at.is_some() && !policy.is_due(now) {
+          continue;
+        }
+      }
       if let Some(c) = self.map.get_mut(&id) {
         if let Err(err) = c.send(CancelGame { player_id: None }).await {
           tracing::error!(game_id = id, "cancel expired game: {}", err);
         } else {
+          tracing::info!(game_id = id, "idle game cancelled");
           cancelled.push(id)
         }
       }
@@ -137,6 +183,7 @@ impl GameRegistry {
 impl Actor for GameRegistry {
   async fn started(&mut self, ctx: &mut Context<Self>) {
     self.handle(ctx, RemoveExpiredGames).await;
+    self.handle(ctx, CheckAutoStarts).await;
   }
 }
 
@@ -147,7 +194,8 @@ impl Service<Data> for GameRegistry {
   async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
     let players = registry.resolve::<PlayerRegistry>().await?;
     let nodes = registry.resolve::<NodeRegistry>().await?;
-    Self::init(registry.data().db.clone(), players.into(), nodes).await
+    let discord = registry.resolve::<DiscordNotifier>().await?;
+    Self::init(registry.data().db.clone(), players.into(), nodes, discord).await
   }
 }
 
@@ -191,6 +239,7 @@ pub struct GameActor {
   pub db: ExecutorRef,
   pub player_reg: PlayerRegistryHandle,
   pub nodes: Addr<NodeRegistry>,
+  pub discord: Addr<DiscordNotifier>,
   pub status: GameStatus,
   pub host_player: i32,
   pub players: Vec<i32>,
@@ -198,6 +247,12 @@ pub struct GameActor {
   pub start_state: Option<Owner<StartGameState>>,
   pub player_tokens: HashMap<i32, [u8; 16]>,
   pub player_client_status_map: HashMap<i32, SlotClientStatus>,
+  /// Recorded for the game timeline
+  pub client_status_changes: Vec<ClientStatusChange>,
+  pub updates: broadcast::Sender<Frame>,
+  /// Root span of the game, continued by the node and the clients
+  pub span: tracing::Span,
+  pub lobby_chat_limiter: RateLimiter,
 }
 
 impl Actor for GameActor {}
@@ -206,4 +261,25 @@ impl GameActor {
   fn started(&self) -> bool {
     self.start_state.is_some() || !self.player_tokens.is_empty()
   }
+
+  /// Forwards a frame sent to the players to update subscribers
+  fn publish(&self, frame: &Frame) {
+    self.updates.send(frame.clone()).ok();
+  }
+
+  async fn notify_discord(&self, event: DiscordEvent) {
+    notify_discord(&self.discord, self.game_id, event).await
+  }
+}
+
+async fn notify_discord(discord: &Addr<DiscordNotifier>, game_id: i32, event: DiscordEvent) {
+  discord.notify(NotifyDiscord { game_id, event }).await.ok();
+}
+
+fn game_span(game_id: i32) -> tracing::Span {
+  tracing::info_span!("game", game_id)
+}
+
+fn game_updates_sender() -> broadcast::Sender<Frame> {
+  broadcast::channel(GAME_UPDATES_CAPACITY).0
 }
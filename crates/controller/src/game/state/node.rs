@@ -35,6 +35,7 @@ impl Handler<SelectNode> for GameActor {
     self.selected_node_id = node_id;
 
     let frame = proto::flo_connect::PacketGameSelectNode { game_id, node_id }.encode_as_frame()?;
+    self.publish(&frame);
     self
       .player_reg
       .broadcast(self.players.clone(), frame)
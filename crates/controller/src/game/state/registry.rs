@@ -1,6 +1,6 @@
 use crate::error::*;
-use crate::game::state::{GameActor, GameRegistry};
-use crate::game::GameStatus;
+use crate::game::state::{game_span, game_updates_sender, GameActor, GameRegistry};
+use crate::game::{AutoStartPolicy, GameStatus};
 use flo_state::{async_trait, Context, Handler, Message, Owner};
 use std::collections::btree_map::Entry;
 
@@ -11,6 +11,7 @@ pub struct Register {
   pub host_player: i32,
   pub players: Vec<i32>,
   pub node_id: Option<i32>,
+  pub auto_start: Option<AutoStartPolicy>,
 }
 
 impl Message for Register {
@@ -33,11 +34,15 @@ impl GameRegistry {
       host_player,
       players,
       node_id,
+      auto_start,
     }: Register,
   ) {
     for player in &players {
       self.add_game_player(id, *player);
     }
+    if let Some(policy) = auto_start {
+      self.auto_start.insert(id, policy);
+    }
     self.map.insert(
       id,
       Owner::new(GameActor {
@@ -45,6 +50,7 @@ impl GameRegistry {
         db: self.db.clone(),
         player_reg: self.players.clone(),
         nodes: self.nodes.clone(),
+        discord: self.discord.clone(),
         status,
         host_player,
         players,
@@ -52,8 +58,13 @@ impl GameRegistry {
         start_state: None,
         player_tokens: Default::default(),
         player_client_status_map: Default::default(),
+        client_status_changes: vec![],
+        updates: game_updates_sender(),
+        span: game_span(id),
+        lobby_chat_limiter: Default::default(),
       }),
     );
+    crate::metrics::GAMES.set(self.map.len() as i64);
   }
 }
 
@@ -70,8 +81,10 @@ impl Message for Remove {
 impl Handler<Remove> for GameRegistry {
   async fn handle(&mut self, ctx: &mut Context<Self>, Remove { game_id: id }: Remove) {
     if let Some(owner) = self.map.remove(&id) {
+      crate::metrics::GAMES.set(self.map.len() as i64);
       self.game_players_map.remove(&id);
       self.game_node_map.remove(&id);
+      self.auto_start.remove(&id);
 
       let addr = ctx.addr();
       ctx.spawn(async move {
@@ -165,6 +178,26 @@ impl Handler<UpdateGameNodeCache> for GameRegistry {
   }
 }
 
+/// The node hosting the game
+pub struct GetGameNode {
+  pub game_id: i32,
+}
+
+impl Message for GetGameNode {
+  type Result = Option<i32>;
+}
+
+#[async_trait]
+impl Handler<GetGameNode> for GameRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetGameNode { game_id }: GetGameNode,
+  ) -> Option<i32> {
+    self.game_node_map.get(&game_id).cloned()
+  }
+}
+
 pub struct ResolveGamePlayerPingBroadcastTargets {
   pub player_id: i32,
   pub node_ids: Vec<i32>,
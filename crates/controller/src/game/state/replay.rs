@@ -0,0 +1,69 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::timeline::ClientStatusChange;
+use bytes::Bytes;
+use flo_state::{async_trait, Context, Handler, Message};
+
+/// The complete game log sent by the node after the game ended
+pub struct GameLogReceived {
+  pub log: Bytes,
+}
+
+impl Message for GameLogReceived {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<GameLogReceived> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, GameLogReceived { log }: GameLogReceived) {
+    let game_id = self.game_id;
+    let db = self.db.clone();
+    let status_changes = self.client_status_changes.clone();
+    tokio::spawn(async move {
+      if let Err(err) = save_replay(db, game_id, log, status_changes).await {
+        tracing::error!(game_id, "save replay: {}", err);
+      }
+    });
+  }
+}
+
+async fn save_replay(
+  db: bs_diesel_utils::ExecutorRef,
+  game_id: i32,
+  log: Bytes,
+  status_changes: Vec<ClientStatusChange>,
+) -> Result<()> {
+  let (game, result, metadata) = db
+    .exec(move |conn| -> Result<_> {
+      let game = crate::game::db::get_full(conn, game_id)?;
+      let metadata = crate::game::replay::get_metadata(conn, &game)?;
+      Ok((
+        game,
+        crate::game::timeline::get_result(conn, game_id)?,
+        metadata,
+      ))
+    })
+    .await?;
+  let records = flo_replay::decode_game_log(log)?;
+
+  // saved separately so it's kept even if the replay can't be generated
+  let timeline = crate::game::timeline::build(&game, &status_changes, &records, result);
+  db.exec(move |conn| crate::game::timeline::save(conn, &timeline))
+    .await?;
+
+  let (data, stats) = tokio::task::spawn_blocking(move || -> Result<_> {
+    let data = crate::game::replay::generate(game.clone(), records, metadata)?;
+    // a replay without stats is still worth keeping
+    let stats = crate::game::replay::analyze(&game, &data)
+      .map_err(|err| tracing::warn!(game_id, "analyze replay: {}", err))
+      .ok();
+    Ok((data, stats))
+  })
+  .await
+  .map_err(|_| Error::TaskCancelled)??;
+  let size = data.len();
+  db.exec(move |conn| crate::game::replay::save(conn, game_id, &data, stats.as_ref()))
+    .await?;
+  tracing::info!(game_id, size, "replay saved");
+  Ok(())
+}
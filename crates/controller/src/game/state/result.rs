@@ -0,0 +1,68 @@
+use crate::discord::DiscordEvent;
+use crate::error::*;
+use crate::game::db;
+use crate::game::desync::{self, DesyncReport, GameDesyncBundle};
+use crate::game::result::GameResultReport;
+use crate::game::state::GameActor;
+use flo_net::packet::FloPacket;
+use flo_state::{async_trait, Context, Handler, Message};
+use tracing_futures::Instrument;
+
+impl Message for GameResultReport {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<GameResultReport> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, report: GameResultReport) -> Result<()> {
+    let span = tracing::info_span!(parent: &self.span, "result");
+    flo_log::trace::set_parent(&span, &report.traceparent);
+    self.save_result(report).instrument(span).await
+  }
+}
+
+impl GameActor {
+  async fn save_result(&mut self, mut report: GameResultReport) -> Result<()> {
+    let game_id = self.game_id;
+    let desyncs = std::mem::take(&mut report.desyncs);
+    if !desyncs.is_empty() {
+      tracing::warn!(game_id, "desync reports received: {}", desyncs.len());
+      let db = self.db.clone();
+      tokio::spawn(async move {
+        if let Err(err) = save_desync_reports(db, game_id, desyncs).await {
+          tracing::error!(game_id, "save desync reports: {}", err);
+        }
+      });
+    }
+
+    let saved = self
+      .db
+      .exec(move |conn| db::save_result(conn, report))
+      .await?;
+
+    tracing::info!(game_id, "game result: {:?}", saved.resolution);
+
+    let frame = saved.to_packet().encode_as_frame()?;
+    self.publish(&frame);
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    self.notify_discord(DiscordEvent::GameResult).await;
+
+    Ok(())
+  }
+}
+
+async fn save_desync_reports(
+  db: bs_diesel_utils::ExecutorRef,
+  game_id: i32,
+  reports: Vec<DesyncReport>,
+) -> Result<()> {
+  db.exec(move |conn| {
+    let game = db::get_full(conn, game_id)?;
+    desync::save(conn, &GameDesyncBundle::new(&game, reports))
+  })
+  .await
+}
@@ -47,16 +47,161 @@ impl Handler<UpdateSlot> for GameActor {
       })
       .await?;
 
+    self
+      .broadcast_slot_updates(&slots, &updated_indexes)
+      .await?;
+
+    Ok(slots)
+  }
+}
+
+pub struct SwapSlot {
+  pub player_id: i32,
+  pub slot_index: i32,
+  pub other_slot_index: i32,
+}
+
+impl Message for SwapSlot {
+  type Result = Result<Vec<Slot>>;
+}
+
+#[async_trait]
+impl Handler<SwapSlot> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    SwapSlot {
+      player_id,
+      slot_index,
+      other_slot_index,
+    }: SwapSlot,
+  ) -> Result<Vec<Slot>> {
+    let game_id = self.game_id;
+
+    let UpdateSlotSettings {
+      slots,
+      updated_indexes,
+    } = self
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::game::db::swap_slots(conn, game_id, player_id, slot_index, other_slot_index)
+        })
+      })
+      .await?;
+
+    self
+      .broadcast_slot_updates(&slots, &updated_indexes)
+      .await?;
+
+    Ok(slots)
+  }
+}
+
+pub struct LockSlot {
+  pub player_id: i32,
+  pub slot_index: i32,
+  pub locked: bool,
+}
+
+impl Message for LockSlot {
+  type Result = Result<Vec<Slot>>;
+}
+
+#[async_trait]
+impl Handler<LockSlot> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LockSlot {
+      player_id,
+      slot_index,
+      locked,
+    }: LockSlot,
+  ) -> Result<Vec<Slot>> {
+    let game_id = self.game_id;
+
+    let UpdateSlotSettings {
+      slots,
+      updated_indexes,
+    } = self
+      .db
+      .exec(move |conn| {
+        crate::game::db::set_slot_locked(conn, game_id, player_id, slot_index, locked)
+      })
+      .await?;
+
+    self
+      .broadcast_slot_updates(&slots, &updated_indexes)
+      .await?;
+
+    Ok(slots)
+  }
+}
+
+pub struct ReserveSlot {
+  pub player_id: i32,
+  pub slot_index: i32,
+  pub reserved_player_id: Option<i32>,
+}
+
+impl Message for ReserveSlot {
+  type Result = Result<Vec<Slot>>;
+}
+
+#[async_trait]
+impl Handler<ReserveSlot> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ReserveSlot {
+      player_id,
+      slot_index,
+      reserved_player_id,
+    }: ReserveSlot,
+  ) -> Result<Vec<Slot>> {
+    let game_id = self.game_id;
+
+    let UpdateSlotSettings {
+      slots,
+      updated_indexes,
+    } = self
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::game::db::reserve_slot(conn, game_id, player_id, slot_index, reserved_player_id)
+        })
+      })
+      .await?;
+
+    self
+      .broadcast_slot_updates(&slots, &updated_indexes)
+      .await?;
+
+    Ok(slots)
+  }
+}
+
+impl GameActor {
+  /// Sends the updated slots to the players and the subscribers
+  pub(super) async fn broadcast_slot_updates(
+    &mut self,
+    slots: &[Slot],
+    updated_indexes: &[i32],
+  ) -> Result<()> {
+    let game_id = self.game_id;
     let mut frames_slot_update = Vec::with_capacity(updated_indexes.len());
 
     for index in updated_indexes {
-      let slot = &slots[index as usize];
+      let slot = &slots[*index as usize];
       let settings: proto::flo_connect::SlotSettings = slot.settings.clone().pack()?;
       let frame = proto::flo_connect::PacketGameSlotUpdate {
         game_id,
-        slot_index: index,
+        slot_index: *index,
         slot_settings: settings.into(),
         player: slot.player.clone().map(|p| p.pack()).transpose()?,
+        locked: slot.locked,
+        reserved_player_id: slot.reserved_player_id,
       }
       .encode_as_frame()?;
       frames_slot_update.push(frame);
@@ -66,11 +211,14 @@ impl Handler<UpdateSlot> for GameActor {
       .iter()
       .filter_map(|s| s.player.as_ref().map(|p| p.id))
       .collect();
+    for frame in &frames_slot_update {
+      self.publish(frame);
+    }
     self
       .player_reg
       .broadcast(players, frames_slot_update)
       .await?;
 
-    Ok(slots)
+    Ok(())
   }
 }
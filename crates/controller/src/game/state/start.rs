@@ -1,6 +1,6 @@
 use crate::error::*;
 use crate::game::state::GameActor;
-use crate::game::{GameStatus, SlotClientStatus};
+use crate::game::{GameStatus, Race, Slot, SlotClientStatus};
 use crate::node::messages::NodeCreateGame;
 use crate::player::state::sender::PlayerFrames;
 use crate::state::ActorMapExt;
@@ -10,6 +10,7 @@ use flo_state::{async_trait, Actor, Addr, Context, Handler, Message};
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::oneshot;
+use tracing_futures::Instrument;
 
 use tokio::time::sleep;
 
@@ -30,12 +31,23 @@ impl Handler<StartGameCheck> for GameActor {
     ctx: &mut Context<Self>,
     StartGameCheck { player_id }: StartGameCheck,
   ) -> Result<()> {
-    let game_id = self.game_id;
-
     if self.host_player != player_id {
       return Err(Error::PlayerNotHost);
     }
 
+    self.start_check(ctx, None).await
+  }
+}
+
+impl GameActor {
+  /// Collects the client info of all players before the game is created on the node
+  pub(crate) async fn start_check(
+    &mut self,
+    ctx: &mut Context<Self>,
+    api_tx: Option<oneshot::Sender<StartGameCheckAsBotResult>>,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
     if self.selected_node_id.is_none() {
       return Err(Error::GameNodeNotSelected);
     }
@@ -45,11 +57,12 @@ impl Handler<StartGameCheck> for GameActor {
       return Err(Error::GameStarted);
     }
 
-    self.start_state = StartGameState::new(game_id, ctx.addr(), players, None)
+    self.start_state = StartGameState::new(game_id, ctx.addr(), players, api_tx)
       .start()
       .into();
 
     let frame = proto::flo_connect::PacketGameStarting { game_id }.encode_as_frame()?;
+    self.publish(&frame);
     self
       .player_reg
       .broadcast(self.players.clone(), frame)
@@ -98,6 +111,7 @@ impl GameActor {
         player_client_info_map: map.clone(),
       };
       let frame = pkt.encode_as_frame()?;
+      self.publish(&frame);
       self
         .player_reg
         .broadcast(self.players.clone(), frame)
@@ -111,6 +125,19 @@ impl GameActor {
       return Ok(Err(pkt));
     }
 
+    let random_races = self
+      .db
+      .exec(move |conn| crate::game::db::assign_random_races(conn, game_id))
+      .await?;
+    if let Some(update) = random_races {
+      self
+        .broadcast_slot_updates(&update.slots, &update.updated_indexes)
+        .await?;
+      self
+        .send_random_races(&update.slots, &update.updated_indexes)
+        .await?;
+    }
+
     let (game, ban_list_map) = self
       .db
       .exec(move |conn| {
@@ -126,9 +153,17 @@ impl GameActor {
       return Err(Error::GameNodeNotSelected);
     };
 
+    let traceparent = flo_log::trace::current_traceparent();
     let created = self
       .nodes
-      .send_to(node_id, NodeCreateGame { game, ban_list_map })
+      .send_to(
+        node_id,
+        NodeCreateGame {
+          game,
+          ban_list_map,
+          traceparent: traceparent.clone(),
+        },
+      )
       .await?
       .await
       .or_cancelled();
@@ -158,6 +193,12 @@ impl GameActor {
                 ControllerCreateGameRejectReason::Maintenance => {
                   format!("Create game request rejected: Server Maintenance.")
                 }
+                ControllerCreateGameRejectReason::PlayerNotReserved => {
+                  format!("Create game request rejected: Player without a reserved slot.")
+                }
+                ControllerCreateGameRejectReason::NodeFull => {
+                  format!("Create game request rejected: Server is full.")
+                }
               },
               ..Default::default()
             }
@@ -200,6 +241,7 @@ impl GameActor {
             game_id,
             player_id: *player_id,
             player_token: token.to_vec(),
+            traceparent: traceparent.clone(),
           })
         } else {
           tracing::error!(game_id, player_id, "player token was not found");
@@ -226,6 +268,37 @@ impl GameActor {
   }
 }
 
+impl GameActor {
+  // announces the assigned races in the lobby chat
+  async fn send_random_races(&mut self, slots: &[Slot], updated_indexes: &[i32]) -> Result<()> {
+    let mut frames = Vec::with_capacity(updated_indexes.len());
+    for index in updated_indexes {
+      let slot = &slots[*index as usize];
+      if let Some(player) = slot.player.as_ref() {
+        let race = match slot.settings.race {
+          Race::Human => "Human",
+          Race::Orc => "Orc",
+          Race::NightElf => "Night Elf",
+          Race::Undead => "Undead",
+          Race::Random => "Random",
+        };
+        let frame = proto::flo_connect::PacketGameLobbyChat {
+          game_id: self.game_id,
+          player_id: player.id,
+          message: format!("[FLO] Random race: {}", race),
+        }
+        .encode_as_frame()?;
+        frames.push(frame);
+      }
+    }
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frames)
+      .await?;
+    Ok(())
+  }
+}
+
 pub struct StartGameCheckTimeout {
   pub map: HashMap<i32, proto::flo_connect::PacketGameStartPlayerClientInfoRequest>,
 }
@@ -272,6 +345,7 @@ impl GameActor {
       start_state.reply_api(StartGameCheckAsBotResult::Rejected(pkt));
     }
 
+    self.publish(&frame);
     self
       .player_reg
       .broadcast(self.players.clone(), frame)
@@ -480,7 +554,8 @@ impl Handler<StartGamePlayerAck> for GameActor {
         .ok_or_else(|| Error::GameNotStarting)?;
       let start_state = start_state.shutdown().await?;
 
-      match self.start_game_proceed(proceed).await {
+      let span = tracing::info_span!(parent: &self.span, "start");
+      match self.start_game_proceed(proceed).instrument(span).await {
         Ok(Ok(_)) => {
           if start_state.by_api() {
             let map = start_state.get_map();
@@ -562,27 +637,6 @@ impl Handler<StartGameCheckAsBot> for GameActor {
     ctx: &mut Context<Self>,
     StartGameCheckAsBot { tx }: StartGameCheckAsBot,
   ) -> <StartGameCheckAsBot as Message>::Result {
-    let game_id = self.game_id;
-
-    if self.selected_node_id.is_none() {
-      return Err(Error::GameNodeNotSelected);
-    }
-
-    let players = self.players.clone();
-    if self.start_state.is_some() {
-      return Err(Error::GameStarted);
-    }
-
-    self.start_state = StartGameState::new(game_id, ctx.addr(), players, Some(tx))
-      .start()
-      .into();
-
-    let frame = proto::flo_connect::PacketGameStarting { game_id }.encode_as_frame()?;
-    self
-      .player_reg
-      .broadcast(self.players.clone(), frame)
-      .await?;
-
-    Ok(())
+    self.start_check(ctx, Some(tx)).await
   }
 }
@@ -1,7 +1,10 @@
+use crate::discord::DiscordEvent;
 use crate::error::*;
 use crate::game::state::GameActor;
+use crate::game::timeline::ClientStatusChange;
 use crate::game::{db, GameStatus, NodeGameStatus, SlotClientStatus};
 use crate::player::state::sender::PlayerFrames;
+use chrono::Utc;
 use flo_net::packet::FloPacket;
 use flo_net::proto;
 use flo_state::{async_trait, Context, Handler, Message};
@@ -51,12 +54,25 @@ impl Handler<GameSlotClientStatusUpdate> for GameActor {
     };
     pkt.set_status(status.into_proto_enum());
 
+    let frame = pkt.encode_as_frame()?;
+    self.publish(&frame);
     self
       .player_reg
-      .broadcast(self.players.clone(), pkt.encode_as_frame()?)
+      .broadcast(self.players.clone(), frame)
       .await?;
 
     self.player_client_status_map.insert(player_id, status);
+    self.client_status_changes.push(ClientStatusChange {
+      at: Utc::now(),
+      player_id,
+      status,
+    });
+
+    if status == SlotClientStatus::Left {
+      if let Err(err) = self.record_leave_penalty(player_id).await {
+        tracing::error!(game_id, player_id, "record leave penalty: {}", err);
+      }
+    }
 
     Ok(())
   }
@@ -92,8 +108,14 @@ impl Handler<GameStatusUpdate> for GameActor {
       .await?;
 
     let frame_game_status = message.to_packet().encode_as_frame()?;
+    self.publish(&frame_game_status);
+    let previous_status = self.status;
     self.status = GameStatus::from(message.status);
 
+    if self.status == GameStatus::Running && previous_status != GameStatus::Running {
+      self.notify_discord(DiscordEvent::GameStarted).await;
+    }
+
     let ended = match self.status {
       GameStatus::Ended | GameStatus::Terminated => true,
       _ => false,
@@ -105,6 +127,19 @@ impl Handler<GameStatusUpdate> for GameActor {
       .map(|player_id| (*player_id, PlayerFrames::from(frame_game_status.clone())))
       .collect::<Vec<_>>();
 
+    let now = Utc::now();
+    self
+      .client_status_changes
+      .extend(
+        message
+          .updated_player_game_client_status_map
+          .iter()
+          .map(|(player_id, status)| ClientStatusChange {
+            at: now,
+            player_id: *player_id,
+            status: *status,
+          }),
+      );
     self
       .player_client_status_map
       .extend(message.updated_player_game_client_status_map);
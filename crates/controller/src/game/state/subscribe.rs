@@ -0,0 +1,136 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use flo_net::packet::Frame;
+use flo_state::{async_trait, Context, Handler, Message};
+use std::future::Future;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+
+/// Subscribes to the lobby and game updates sent to the players of a game.
+/// The receiver is closed after the game has been removed.
+pub struct SubscribeGameUpdates;
+
+impl Message for SubscribeGameUpdates {
+  type Result = Result<broadcast::Receiver<Frame>>;
+}
+
+#[async_trait]
+impl Handler<SubscribeGameUpdates> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: SubscribeGameUpdates,
+  ) -> Result<broadcast::Receiver<Frame>> {
+    Ok(self.updates.subscribe())
+  }
+}
+
+/// Sends `snapshot` followed by the updates until the game is removed or the subscriber is gone.
+/// A lagging subscriber gets a fresh snapshot from `resync` instead of the dropped updates.
+pub async fn forward_updates<F, Fut>(
+  snapshot: Frame,
+  mut updates: broadcast::Receiver<Frame>,
+  tx: mpsc::Sender<Result<Frame>>,
+  mut resync: F,
+) where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<Frame>>,
+{
+  if tx.send(Ok(snapshot)).await.is_err() {
+    return;
+  }
+  loop {
+    let frame = match updates.recv().await {
+      Ok(frame) => frame,
+      Err(RecvError::Lagged(_)) => match resync().await {
+        Ok(frame) => frame,
+        Err(err) => {
+          tx.send(Err(err)).await.ok();
+          break;
+        }
+      },
+      Err(RecvError::Closed) => break,
+    };
+    if tx.send(Ok(frame)).await.is_err() {
+      break;
+    }
+  }
+}
+
+#[tokio::test]
+async fn test_forward_updates() {
+  use flo_net::packet::PacketTypeId;
+
+  let collect = |mut rx: mpsc::Receiver<Result<Frame>>| async move {
+    let mut type_ids = vec![];
+    while let Some(frame) = rx.recv().await {
+      type_ids.push(frame.map(|frame| frame.type_id));
+    }
+    type_ids
+  };
+
+  let (updates_tx, updates) = broadcast::channel(4);
+  let (tx, rx) = mpsc::channel(4);
+  updates_tx
+    .send(Frame::new_empty(PacketTypeId::GamePlayerEnter))
+    .unwrap();
+  drop(updates_tx);
+  forward_updates(
+    Frame::new_empty(PacketTypeId::GameInfo),
+    updates,
+    tx,
+    || async { Ok(Frame::new_empty(PacketTypeId::GameInfo)) },
+  )
+  .await;
+  let type_ids: Vec<_> = collect(rx).await.into_iter().map(Result::unwrap).collect();
+  assert_eq!(
+    type_ids,
+    vec![PacketTypeId::GameInfo, PacketTypeId::GamePlayerEnter]
+  );
+
+  // the dropped updates are replaced by a snapshot
+  let (updates_tx, updates) = broadcast::channel(1);
+  let (tx, rx) = mpsc::channel(4);
+  for _ in 0..3 {
+    updates_tx
+      .send(Frame::new_empty(PacketTypeId::GameSlotUpdate))
+      .unwrap();
+  }
+  drop(updates_tx);
+  forward_updates(
+    Frame::new_empty(PacketTypeId::GameInfo),
+    updates,
+    tx,
+    || async { Ok(Frame::new_empty(PacketTypeId::GameInfo)) },
+  )
+  .await;
+  let type_ids: Vec<_> = collect(rx).await.into_iter().map(Result::unwrap).collect();
+  assert_eq!(
+    type_ids,
+    vec![
+      PacketTypeId::GameInfo,
+      PacketTypeId::GameInfo,
+      PacketTypeId::GameSlotUpdate
+    ]
+  );
+
+  // a failed resync ends the stream with the error
+  let (updates_tx, updates) = broadcast::channel(1);
+  let (tx, rx) = mpsc::channel(4);
+  for _ in 0..2 {
+    updates_tx
+      .send(Frame::new_empty(PacketTypeId::GameSlotUpdate))
+      .unwrap();
+  }
+  forward_updates(
+    Frame::new_empty(PacketTypeId::GameInfo),
+    updates,
+    tx,
+    || async { Err(Error::GameNotFound) },
+  )
+  .await;
+  let res = collect(rx).await;
+  assert_eq!(res.len(), 2);
+  assert!(matches!(res[1], Err(Error::GameNotFound)));
+  drop(updates_tx);
+}
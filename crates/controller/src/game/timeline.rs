@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use flo_events::{GameEvent, GameEventBuilder, GameEventKind};
+use flo_observer::record::GameRecordData;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::{Game, SlotClientStatus};
+use crate::schema::{game_result, game_timeline};
+
+const ACTION_SUMMARY_INTERVAL_MS: u32 = 60_000;
+
+/// Everything that happened in a game, for match pages and dispute handling
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameTimeline {
+  pub game_id: i32,
+  pub events: Vec<GameEvent>,
+}
+
+/// A slot client status change reported by the node before or during the game
+#[derive(Debug, Clone)]
+pub struct ClientStatusChange {
+  pub at: DateTime<Utc>,
+  pub player_id: i32,
+  pub status: SlotClientStatus,
+}
+
+/// `result` is the `(valid, winner_team)` pair saved with the game result
+pub fn build(
+  game: &Game,
+  status_changes: &[ClientStatusChange],
+  records: &[GameRecordData],
+  result: Option<(bool, Option<i32>)>,
+) -> GameTimeline {
+  let slot_players = game
+    .slots
+    .iter()
+    .enumerate()
+    .filter_map(|(idx, slot)| Some(((idx + 1) as u8, slot.player.as_ref()?.id)));
+  let mut builder =
+    GameEventBuilder::new(slot_players).with_action_summary(ACTION_SUMMARY_INTERVAL_MS);
+  for change in status_changes {
+    let player_id = change.player_id;
+    let kind = match change.status {
+      SlotClientStatus::Joined => GameEventKind::Join { player_id },
+      SlotClientStatus::Loading => GameEventKind::Loading { player_id },
+      SlotClientStatus::Loaded => GameEventKind::Loaded { player_id },
+      _ => continue,
+    };
+    builder.push_at(change.at, kind);
+  }
+  for record in records {
+    builder.put_record(record);
+  }
+  if let Some((valid, winner_team)) = result {
+    builder.push(GameEventKind::Result { valid, winner_team });
+  }
+  GameTimeline {
+    game_id: game.id,
+    events: builder.finish(),
+  }
+}
+
+pub fn save(conn: &DbConn, timeline: &GameTimeline) -> Result<()> {
+  let data = serde_json::to_value(timeline)?;
+  diesel::insert_into(game_timeline::table)
+    .values((
+      game_timeline::game_id.eq(timeline.game_id),
+      game_timeline::data.eq(&data),
+    ))
+    .on_conflict(game_timeline::game_id)
+    .do_update()
+    .set(game_timeline::data.eq(&data))
+    .execute(conn)?;
+  Ok(())
+}
+
+/// Returns the timeline as a JSON document
+pub fn get(conn: &DbConn, game_id: i32) -> Result<Option<Value>> {
+  game_timeline::table
+    .find(game_id)
+    .select(game_timeline::data)
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn get_result(conn: &DbConn, game_id: i32) -> Result<Option<(bool, Option<i32>)>> {
+  game_result::table
+    .find(game_id)
+    .select((game_result::valid, game_result::winner_team))
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
+#[test]
+fn test_timeline_event_json() {
+  let event = GameEvent {
+    at: None,
+    time_ms: Some(1000),
+    kind: GameEventKind::Leave {
+      player_id: Some(1),
+      reason: "LeaveLost".to_string(),
+    },
+  };
+  assert_eq!(
+    serde_json::to_value(&event).unwrap(),
+    serde_json::json!({
+      "time_ms": 1000,
+      "type": "leave",
+      "player_id": 1,
+      "reason": "LeaveLost",
+    })
+  );
+}
@@ -33,6 +33,17 @@ pub struct Game {
   pub game_version: Option<String>,
   pub enable_ping_equalizer: bool,
   pub flo_tv_delay_override_secs: Option<i32>,
+  pub map_config: Option<MapConfigOverrides>,
+  /// Tick rate of a practice game in percent of the normal speed
+  pub speed_percent: Option<i32>,
+  /// Enforced by the node, set for tournament games
+  pub chat_rules: Option<ChatRules>,
+  /// Only these players can join if not empty
+  pub reserved_player_ids: Vec<i32>,
+  /// Bits of `flo_types::feature::FeatureFlag`
+  pub feature_flags: u32,
+  /// Started by the controller without a host action
+  pub auto_start: Option<AutoStartPolicy>,
 }
 
 impl S2ProtoPack<flo_net::proto::flo_connect::GameInfo> for Game {
@@ -56,6 +67,8 @@ impl S2ProtoPack<flo_net::proto::flo_connect::GameInfo> for Game {
       random_seed: self.random_seed,
       created_by: self.created_by.pack()?,
       mask_player_names: self.mask_player_names,
+      map_config: self.map_config.pack()?,
+      feature_flags: self.feature_flags,
     })
   }
 }
@@ -92,6 +105,147 @@ impl Game {
   }
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone)]
+#[s2_grpc(message_type(
+  flo_grpc::game::MapConfigOverrides,
+  flo_net::proto::flo_connect::MapConfigOverrides
+))]
+pub struct MapConfigOverrides {
+  pub fixed_teams: Option<bool>,
+  pub teams_together: Option<bool>,
+  #[s2_grpc(proto_enum)]
+  pub observers: MapObserverMode,
+  #[s2_grpc(proto_enum)]
+  pub visibility: MapVisibility,
+  #[serde(default)]
+  #[s2_grpc(proto_enum)]
+  pub win_condition: WinCondition,
+  /// Full shared unit control between allies, for co-op and 2v2 AT formats
+  pub shared_control: Option<bool>,
+  /// Races are picked randomly when the game starts, see [`crate::game::db::assign_random_races`]
+  #[serde(default)]
+  pub random_races: bool,
+  #[serde(default)]
+  pub random_hero: bool,
+}
+
+impl MapConfigOverrides {
+  pub fn observers_allowed(&self) -> bool {
+    self.observers != MapObserverMode::None
+  }
+}
+
+/// In-game chat moderation set by tournament organizers
+#[derive(Debug, Default, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone)]
+#[s2_grpc(message_type(flo_grpc::game::ChatRules, flo_net::proto::flo_node::ChatRules))]
+pub struct ChatRules {
+  #[serde(default)]
+  pub all_chat_disabled: bool,
+  #[serde(default)]
+  #[s2_grpc(proto_enum)]
+  pub observer_chat: ObserverChatRule,
+  /// Matched case-insensitively
+  #[serde(default)]
+  pub banned_phrases: Vec<String>,
+}
+
+/// When a lobby is started without a host action, see [`crate::game::state::auto_start`]
+#[derive(Debug, Default, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone, PartialEq)]
+#[s2_grpc(message_type(flo_grpc::game::AutoStartPolicy))]
+pub struct AutoStartPolicy {
+  /// Start once this many players joined
+  #[serde(default)]
+  pub min_players: Option<i32>,
+  /// Start at this time, or cancel the game if there are not enough players
+  #[serde(default)]
+  pub start_at: Option<DateTime<Utc>>,
+  /// Players required to start at `start_at`, defaults to 1
+  #[serde(default)]
+  pub min_ready_players: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(
+  flo_grpc::game::ObserverChatRule,
+  flo_net::proto::flo_node::ObserverChatRule
+))]
+pub enum ObserverChatRule {
+  Default = 0,
+  /// Observer messages are only sent to other observers
+  ObserversOnly = 1,
+  Disabled = 2,
+}
+
+impl Default for ObserverChatRule {
+  fn default() -> Self {
+    ObserverChatRule::Default
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(
+  flo_grpc::game::MapObserverMode,
+  flo_net::proto::flo_connect::MapObserverMode
+))]
+pub enum MapObserverMode {
+  Default = 0,
+  None = 1,
+  OnDefeat = 2,
+  Full = 3,
+  Referees = 4,
+}
+
+impl Default for MapObserverMode {
+  fn default() -> Self {
+    MapObserverMode::Default
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(
+  flo_grpc::game::MapVisibility,
+  flo_net::proto::flo_connect::MapVisibility
+))]
+pub enum MapVisibility {
+  Default = 0,
+  Hidden = 1,
+  Explored = 2,
+  AlwaysVisible = 3,
+}
+
+impl Default for MapVisibility {
+  fn default() -> Self {
+    MapVisibility::Default
+  }
+}
+
+/// How the winner of a game is determined, see [`crate::game::win_condition`]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(
+  flo_grpc::game::WinCondition,
+  flo_net::proto::flo_connect::WinCondition
+))]
+pub enum WinCondition {
+  /// W3MMD flags, then leave reasons, then the leave order
+  Melee = 0,
+  /// W3MMD flags only, for DotA-style maps where leavers don't decide the game
+  Mmd = 1,
+  /// The team that stayed in the game longest
+  LastTeamStanding = 2,
+  /// Decided by an admin ruling
+  Ruling = 3,
+}
+
+impl Default for WinCondition {
+  fn default() -> Self {
+    WinCondition::Melee
+  }
+}
+
 #[derive(Debug)]
 pub struct PlayerSlotInfo<'a> {
   pub slot_index: usize,
@@ -157,6 +311,34 @@ impl GameEntry {
   }
 }
 
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_grpc::game::LobbyGameType))]
+pub enum LobbyGameType {
+  Custom = 0,
+  Matchmaking = 1,
+  Tournament = 2,
+}
+
+/// A public game in preparation, listed by the game browser
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Queryable)]
+#[s2_grpc(message_type(flo_grpc::game::LobbyEntry))]
+pub struct LobbyEntry {
+  pub id: i32,
+  pub name: String,
+  pub map_name: String,
+  #[s2_grpc(proto_enum)]
+  pub game_type: LobbyGameType,
+  pub max_players: i32,
+  /// Player slots that are neither occupied nor closed
+  pub open_slots: i32,
+  pub has_password: bool,
+  pub region: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub node: Option<NodeRef>,
+  pub created_by: Option<PlayerRef>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
 #[repr(i32)]
 #[s2_grpc(proto_enum_type(flo_grpc::game::GameStatus, flo_net::proto::flo_connect::GameStatus))]
@@ -209,18 +391,40 @@ pub struct CreateGameSlot {
   pub settings: SlotSettings,
 }
 
+/// A slot reserved for a player when the game is created
+#[derive(Debug, Serialize, Deserialize, S2ProtoUnpack, Clone)]
+#[s2_grpc(message_type(flo_grpc::controller::SlotReservation))]
+pub struct SlotReservation {
+  pub slot_index: i32,
+  pub player_id: i32,
+}
+
 #[derive(Debug, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone)]
 #[s2_grpc(message_type(flo_grpc::game::Slot, flo_net::proto::flo_connect::Slot))]
 pub struct Slot {
   pub player: Option<PlayerRef>,
   pub settings: SlotSettings,
   pub client_status: SlotClientStatus,
+  /// Locked slots can only be changed after being unlocked by the host
+  pub locked: bool,
+  /// Only the reserved player can join an open reserved slot
+  pub reserved_player_id: Option<i32>,
 }
 
 impl Slot {
   pub fn is_used(&self) -> bool {
     self.settings.status != SlotStatus::Open
   }
+
+  /// Returns true if the player can join or move into this slot
+  pub fn is_available_for(&self, player_id: Option<i32>) -> bool {
+    self.settings.status == SlotStatus::Open
+      && !self.locked
+      && self
+        .reserved_player_id
+        .map(|id| Some(id) == player_id)
+        .unwrap_or(true)
+  }
 }
 
 impl Default for Slot {
@@ -229,6 +433,8 @@ impl Default for Slot {
       player: None,
       settings: Default::default(),
       client_status: SlotClientStatus::Pending,
+      locked: false,
+      reserved_player_id: None,
     }
   }
 }
@@ -323,6 +529,7 @@ pub enum SlotClientStatus {
   Loaded = 4,
   Disconnected = 5,
   Left = 6,
+  LoadFailed = 7,
 }
 
 impl SlotClientStatus {
@@ -334,7 +541,7 @@ impl SlotClientStatus {
       | SlotClientStatus::Loading
       | SlotClientStatus::Loaded
       | SlotClientStatus::Disconnected => true,
-      SlotClientStatus::Left => false,
+      SlotClientStatus::Left | SlotClientStatus::LoadFailed => false,
     }
   }
 }
@@ -0,0 +1,122 @@
+use flo_w3gs::protocol::constants::LeaveReason;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::game::result::{
+  GameResolution, GameResultPlayer, InvalidReason, MmdFlag, ResolutionSource,
+};
+use crate::game::WinCondition;
+
+/// Decides the outcome of a game type from the players' results
+pub trait WinConditionEvaluator: Send + Sync {
+  /// `players` are `(team, player)` pairs of at least 2 teams, none of them desynced
+  fn evaluate(&self, players: &[(i32, &GameResultPlayer)]) -> GameResolution;
+}
+
+impl WinCondition {
+  pub fn evaluator(self) -> &'static dyn WinConditionEvaluator {
+    match self {
+      WinCondition::Melee => &Melee,
+      WinCondition::Mmd => &Mmd,
+      WinCondition::LastTeamStanding => &LastTeamStanding,
+      WinCondition::Ruling => &Ruling,
+    }
+  }
+}
+
+/// W3MMD flags take precedence over leave reasons, which take precedence over the leave order
+pub struct Melee;
+
+impl WinConditionEvaluator for Melee {
+  fn evaluate(&self, players: &[(i32, &GameResultPlayer)]) -> GameResolution {
+    resolve_mmd(players)
+      .or_else(|| resolve_leave_reason(players))
+      .unwrap_or_else(|| resolve_leave_order(players))
+  }
+}
+
+/// Only trusts W3MMD flags written by the map
+pub struct Mmd;
+
+impl WinConditionEvaluator for Mmd {
+  fn evaluate(&self, players: &[(i32, &GameResultPlayer)]) -> GameResolution {
+    resolve_mmd(players).unwrap_or(GameResolution::Invalid(InvalidReason::Undetermined))
+  }
+}
+
+pub struct LastTeamStanding;
+
+impl WinConditionEvaluator for LastTeamStanding {
+  fn evaluate(&self, players: &[(i32, &GameResultPlayer)]) -> GameResolution {
+    resolve_leave_order(players)
+  }
+}
+
+/// Leaves the result to an admin
+pub struct Ruling;
+
+impl WinConditionEvaluator for Ruling {
+  fn evaluate(&self, _: &[(i32, &GameResultPlayer)]) -> GameResolution {
+    GameResolution::Invalid(InvalidReason::AwaitingRuling)
+  }
+}
+
+fn resolve_mmd(players: &[(i32, &GameResultPlayer)]) -> Option<GameResolution> {
+  let mmd_teams = |flag: MmdFlag| -> BTreeSet<i32> {
+    players
+      .iter()
+      .filter(|(_, p)| p.mmd_flag == flag)
+      .map(|(team, _)| *team)
+      .collect()
+  };
+  let winner_teams = mmd_teams(MmdFlag::Winner);
+  let loser_teams = mmd_teams(MmdFlag::Loser);
+  if !winner_teams.is_empty() {
+    if winner_teams.len() > 1 || winner_teams.iter().any(|team| loser_teams.contains(team)) {
+      return Some(GameResolution::Invalid(InvalidReason::ConflictingMmdFlags));
+    }
+    return Some(GameResolution::Winner {
+      team: winner_teams.into_iter().next()?,
+      source: ResolutionSource::Mmd,
+    });
+  }
+  if !mmd_teams(MmdFlag::Drawer).is_empty() {
+    return Some(GameResolution::Draw);
+  }
+  None
+}
+
+// leave reasons reported by the game client
+fn resolve_leave_reason(players: &[(i32, &GameResultPlayer)]) -> Option<GameResolution> {
+  let won_teams: BTreeSet<i32> = players
+    .iter()
+    .filter(|(_, p)| p.leave_reason.map(LeaveReason::from) == Some(LeaveReason::LeaveWon))
+    .map(|(team, _)| *team)
+    .collect();
+  match won_teams.len() {
+    0 => None,
+    1 => Some(GameResolution::Winner {
+      team: won_teams.into_iter().next()?,
+      source: ResolutionSource::LeaveReason,
+    }),
+    _ => Some(GameResolution::Invalid(InvalidReason::Undetermined)),
+  }
+}
+
+// the team that stayed in the game longest
+fn resolve_leave_order(players: &[(i32, &GameResultPlayer)]) -> GameResolution {
+  let mut last_left: BTreeMap<i32, u32> = BTreeMap::new();
+  for (team, p) in players {
+    let v = last_left.entry(*team).or_default();
+    *v = std::cmp::max(*v, p.left_at_ms);
+  }
+  let mut sorted: Vec<(i32, u32)> = last_left.into_iter().collect();
+  sorted.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+  if sorted.len() > 1 && sorted[0].1 > sorted[1].1 {
+    GameResolution::Winner {
+      team: sorted[0].0,
+      source: ResolutionSource::LeaveOrder,
+    }
+  } else {
+    GameResolution::Invalid(InvalidReason::Undetermined)
+  }
+}
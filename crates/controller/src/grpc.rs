@@ -1,28 +1,56 @@
+use crate::api_token::{ApiScopes, ApiTokenScope};
+use crate::audit::db::ListAuditLogParams;
+use crate::audit::{AuditAction, AuditTarget, AuditTargetType};
 use crate::config::{ApiRequestExt, GetInterceptor};
+use crate::discord::db::CreateDiscordChannelParams;
+use crate::discord::{parse_command, DiscordChannel, DiscordCommand, DiscordEvent};
 use crate::error::{Error, Result};
-use crate::game::db::{CreateGameAsBotParams, CreateGameParams};
-use crate::game::messages::{CreateGame, PlayerJoin, PlayerLeave};
+use crate::game::access::JoinCredential;
+use crate::game::db::{CreateGameAsBotParams, CreateGameParams, ListLobbiesParams};
+use crate::game::messages::{CreateGame, LockSlot, PlayerJoin, PlayerLeave, ReserveSlot, SwapSlot};
+use crate::game::result::GameRuling;
+use crate::game::state::abort::AbortGame;
 use crate::game::state::cancel::CancelGame;
-use crate::game::state::create::CreateGameAsBot;
+use crate::game::state::create::{CreateGameAsBot, RehostGame};
 use crate::game::state::node::SelectNode;
 use crate::game::state::registry::{AddGamePlayer, Remove, RemoveGamePlayer, UpdateGameNodeCache};
 use crate::game::state::start::{StartGameCheckAsBot, StartGameCheckAsBotResult};
+use crate::game::state::subscribe::{forward_updates, SubscribeGameUpdates};
+use crate::game::{Game, LobbyGameType, Race};
+use crate::matchmaking::balance::{balance_teams, team_slots};
+use crate::matchmaking::LadderMode;
 use crate::node::messages::ListNode;
+use crate::node::policy::NodeDecision;
 use crate::player::state::ping::GetPlayersPingSnapshot;
 use crate::player::{PlayerBanType, PlayerSource, SourceState};
+use crate::rate_limit::RateLimitScope;
+use crate::season::db::{CreateSeasonParams, GetLeaderboardParams};
+use crate::smurf::db::ListPlayerLinksParams;
+use crate::smurf::PlayerLinkStatus;
 use crate::state::{ActorMapExt, ControllerStateRef};
+use crate::stats::db::ListStatsAggregatesParams;
+use crate::tournament::db::CreateTournamentParams;
 use bs_diesel_utils::executor::ExecutorError;
 use chrono::{DateTime, Utc};
+use diesel::Connection;
 use flo_grpc::controller::flo_controller_server::*;
 use flo_grpc::controller::*;
+use flo_net::packet::{FloPacket, Frame, FramePayload};
+use futures::{Stream, StreamExt};
 use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
+use serde_json::json;
 use std::net::{Ipv4Addr, SocketAddrV4};
+use std::pin::Pin;
 use std::time::Duration;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status};
 use tower_http::trace::TraceLayer;
 use tracing::Span;
 
+const GAME_UPDATES_BUFFER: usize = 16;
+const REPLAY_CHUNK_SIZE: usize = 1024 * 1024;
+
 pub async fn serve(state: ControllerStateRef) -> Result<()> {
   let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, flo_constants::CONTROLLER_GRPC_PORT);
   let server_impl = FloControllerService::new(state.clone());
@@ -66,6 +94,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<GetPlayerRequest>,
   ) -> Result<Response<GetPlayerReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let player_id = request.into_inner().player_id;
     let player = self
       .state
@@ -82,6 +111,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<GetPlayerByTokenRequest>,
   ) -> Result<Response<GetPlayerReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let token = request.into_inner().token;
     let player_id = crate::player::token::validate_player_token(&token)?.player_id;
     let player = self
@@ -99,6 +129,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<UpdateAndGetPlayerRequest>,
   ) -> Result<Response<UpdateAndGetPlayerReply>, Status> {
+    request.check_api_client_secret()?;
     use crate::player::db;
     let api_client_id = request.get_api_client_id();
     let mut req = request.into_inner();
@@ -131,7 +162,59 @@ impl FloController for FloControllerService {
     }))
   }
 
-  async fn list_nodes(&self, _request: Request<()>) -> Result<Response<ListNodesReply>, Status> {
+  async fn create_bnet_auth_url(
+    &self,
+    request: Request<CreateBnetAuthUrlRequest>,
+  ) -> Result<Response<CreateBnetAuthUrlReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let player_id = request.into_inner().player_id;
+    let url = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::player::db::get_client_refs_by_ids(conn, api_client_id, &[player_id])?
+          .first()
+          .ok_or_else(|| Error::PlayerNotFound)?;
+        crate::player::bnet::create_authorize_url(conn, player_id)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(CreateBnetAuthUrlReply { url }))
+  }
+
+  async fn verify_bnet_auth(
+    &self,
+    request: Request<VerifyBnetAuthRequest>,
+  ) -> Result<Response<GetPlayerReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let req = request.into_inner();
+    let oauth_state = req.state;
+    let player_id = self
+      .state
+      .db
+      .exec(move |conn| crate::player::bnet::validate_state(conn, &oauth_state))
+      .await
+      .map_err(Error::from)?;
+    let info = tokio::task::spawn_blocking(move || crate::player::bnet::get_user_info(&req.code))
+      .await
+      .map_err(|err| Error::BNetOAuthRequest(err.to_string()))??;
+    let player = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::player::db::set_battletag(conn, api_client_id, player_id, info.id, &info.battletag)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(GetPlayerReply {
+      player: player.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn list_nodes(&self, request: Request<()>) -> Result<Response<ListNodesReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let nodes = self.state.nodes.send(ListNode).await.map_err(Error::from)?;
     Ok(Response::new(ListNodesReply {
       nodes: nodes.pack().map_err(Error::from)?,
@@ -142,6 +225,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<ListGamesRequest>,
   ) -> Result<Response<ListGamesReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let params =
       crate::game::db::QueryGameParams::unpack(request.into_inner()).map_err(Status::internal)?;
     let r = self
@@ -154,10 +238,39 @@ impl FloController for FloControllerService {
     Ok(Response::new(r.pack().map_err(Error::from)?))
   }
 
+  /// Used by game browsers, requires the `ReadResults` scope
+  async fn list_lobbies(
+    &self,
+    request: Request<ListLobbiesRequest>,
+  ) -> Result<Response<ListLobbiesReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let req = request.into_inner();
+    let params = ListLobbiesParams {
+      game_type: req
+        .game_type
+        .and_then(flo_grpc::game::LobbyGameType::from_i32)
+        .map(LobbyGameType::unpack_enum),
+      map: req.map,
+      region: req.region,
+      players_needed: req.players_needed,
+      cursor: req.cursor,
+      take: req.take,
+    };
+    let r = self
+      .state
+      .db
+      .exec(move |conn| crate::game::db::list_lobbies(conn, &params))
+      .await
+      .map_err(Error::from)?;
+
+    Ok(Response::new(r.pack().map_err(Status::internal)?))
+  }
+
   async fn get_game(
     &self,
     request: Request<GetGameRequest>,
   ) -> Result<Response<GetGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let game_id = request.into_inner().game_id;
     let game = self
       .state
@@ -173,10 +286,140 @@ impl FloController for FloControllerService {
     }))
   }
 
+  type SubscribeGameStream =
+    Pin<Box<dyn Stream<Item = Result<GameUpdate, Status>> + Send + 'static>>;
+
+  async fn subscribe_game(
+    &self,
+    request: Request<SubscribeGameRequest>,
+  ) -> Result<Response<Self::SubscribeGameStream>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let game_id = request.into_inner().game_id;
+    // subscribe before loading the snapshot so no update is missed
+    let updates = self
+      .state
+      .games
+      .send_to(game_id, SubscribeGameUpdates)
+      .await?;
+    let snapshot = get_game_info_frame(&self.state, game_id).await?;
+
+    let state = self.state.clone();
+    let (tx, rx) = tokio::sync::mpsc::channel(GAME_UPDATES_BUFFER);
+    tokio::spawn(forward_updates(snapshot, updates, tx, move || {
+      let state = state.clone();
+      async move { get_game_info_frame(&state, game_id).await }
+    }));
+
+    let stream = ReceiverStream::new(rx).map(move |res| {
+      res
+        .map(|frame| game_update(game_id, frame))
+        .map_err(Into::into)
+    });
+    Ok(Response::new(Box::pin(stream)))
+  }
+
+  async fn get_game_replay_info(
+    &self,
+    request: Request<GetGameReplayInfoRequest>,
+  ) -> Result<Response<GetGameReplayInfoReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let api_client_id = request.get_api_client_id();
+    let game_id = request.into_inner().game_id;
+    let replay = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::game::replay::check_access(conn, game_id, api_client_id)?;
+        crate::game::replay::get_info(conn, game_id)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(GetGameReplayInfoReply {
+      replay: Some(replay.pack().map_err(Status::internal)?),
+    }))
+  }
+
+  async fn get_game_timeline(
+    &self,
+    request: Request<GetGameTimelineRequest>,
+  ) -> Result<Response<GetGameTimelineReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let api_client_id = request.get_api_client_id();
+    let game_id = request.into_inner().game_id;
+    let timeline = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::game::replay::check_access(conn, game_id, api_client_id)?;
+        crate::game::timeline::get(conn, game_id)
+      })
+      .await
+      .map_err(Error::from)?
+      .ok_or_else(|| Error::GameTimelineNotFound)?;
+    Ok(Response::new(GetGameTimelineReply {
+      json: timeline.to_string(),
+    }))
+  }
+
+  async fn get_game_desync_report(
+    &self,
+    request: Request<GetGameDesyncReportRequest>,
+  ) -> Result<Response<GetGameDesyncReportReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let api_client_id = request.get_api_client_id();
+    let game_id = request.into_inner().game_id;
+    let report = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::game::replay::check_access(conn, game_id, api_client_id)?;
+        crate::game::desync::get(conn, game_id)
+      })
+      .await
+      .map_err(Error::from)?
+      .ok_or_else(|| Error::GameDesyncReportNotFound)?;
+    Ok(Response::new(GetGameDesyncReportReply {
+      json: report.to_string(),
+    }))
+  }
+
+  type DownloadGameReplayStream =
+    Pin<Box<dyn Stream<Item = Result<GameReplayChunk, Status>> + Send + 'static>>;
+
+  async fn download_game_replay(
+    &self,
+    request: Request<DownloadGameReplayRequest>,
+  ) -> Result<Response<Self::DownloadGameReplayStream>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let api_client_id = request.get_api_client_id();
+    let game_id = request.into_inner().game_id;
+    let data = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::game::replay::check_access(conn, game_id, api_client_id)?;
+        crate::game::replay::get(conn, game_id)
+      })
+      .await
+      .map_err(Error::from)?
+      .ok_or_else(|| Error::GameReplayNotFound)?;
+    let chunks: Vec<_> = data
+      .chunks(REPLAY_CHUNK_SIZE)
+      .map(|chunk| {
+        Ok(GameReplayChunk {
+          data: chunk.to_vec(),
+        })
+      })
+      .collect();
+    Ok(Response::new(Box::pin(futures::stream::iter(chunks))))
+  }
+
   async fn create_game(
     &self,
     request: Request<CreateGameRequest>,
   ) -> Result<Response<CreateGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
     let game = self
       .state
       .games
@@ -195,7 +438,13 @@ impl FloController for FloControllerService {
     &self,
     request: Request<JoinGameRequest>,
   ) -> Result<Response<JoinGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let params = request.into_inner();
+    let credential = match (params.invite_token, params.password) {
+      (Some(token), _) => Some(JoinCredential::InviteToken(token)),
+      (None, Some(password)) => Some(JoinCredential::Password(password)),
+      (None, None) => None,
+    };
 
     let game = self
       .state
@@ -204,6 +453,7 @@ impl FloController for FloControllerService {
         params.game_id,
         PlayerJoin {
           player_id: params.player_id,
+          credential,
         },
       )
       .await?;
@@ -227,6 +477,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<CreateJoinGameTokenRequest>,
   ) -> Result<Response<CreateJoinGameTokenReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let params = request.into_inner();
     let game_id = params.game_id;
 
@@ -250,6 +501,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<JoinGameByTokenRequest>,
   ) -> Result<Response<JoinGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let params = request.into_inner();
     let join_token = crate::game::token::validate_join_token(&params.token)?;
 
@@ -260,6 +512,7 @@ impl FloController for FloControllerService {
         join_token.game_id,
         PlayerJoin {
           player_id: params.player_id,
+          credential: Some(JoinCredential::JoinToken),
         },
       )
       .await?;
@@ -279,7 +532,40 @@ impl FloController for FloControllerService {
     }))
   }
 
+  async fn create_game_invite(
+    &self,
+    request: Request<CreateGameInviteRequest>,
+  ) -> Result<Response<CreateGameInviteReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let params = request.into_inner();
+    let token = self
+      .state
+      .db
+      .exec(move |conn| crate::game::access::create_invite(conn, params.game_id, params.player_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(CreateGameInviteReply { token }))
+  }
+
+  async fn set_game_password(
+    &self,
+    request: Request<SetGamePasswordRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let params = request.into_inner();
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::game::access::set_password(conn, params.game_id, params.player_id, params.password)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
   async fn leave_game(&self, request: Request<LeaveGameRequest>) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let params = request.into_inner();
 
     let res = self
@@ -326,6 +612,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<SelectGameNodeRequest>,
   ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let SelectGameNodeRequest {
       game_id,
       player_id,
@@ -355,6 +642,7 @@ impl FloController for FloControllerService {
   }
 
   async fn cancel_game(&self, request: Request<CancelGameRequest>) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let req = request.into_inner();
     let game_id = req.game_id;
     let player_id = req.player_id;
@@ -381,10 +669,80 @@ impl FloController for FloControllerService {
     Ok(Response::new(()))
   }
 
+  async fn swap_game_slots(
+    &self,
+    request: Request<SwapGameSlotsRequest>,
+  ) -> Result<Response<UpdateGameSlotsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let req = request.into_inner();
+    let slots = self
+      .state
+      .games
+      .send_to(
+        req.game_id,
+        SwapSlot {
+          player_id: req.player_id,
+          slot_index: req.slot_index,
+          other_slot_index: req.other_slot_index,
+        },
+      )
+      .await?;
+    Ok(Response::new(UpdateGameSlotsReply {
+      slots: slots.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn lock_game_slot(
+    &self,
+    request: Request<LockGameSlotRequest>,
+  ) -> Result<Response<UpdateGameSlotsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let req = request.into_inner();
+    let slots = self
+      .state
+      .games
+      .send_to(
+        req.game_id,
+        LockSlot {
+          player_id: req.player_id,
+          slot_index: req.slot_index,
+          locked: req.locked,
+        },
+      )
+      .await?;
+    Ok(Response::new(UpdateGameSlotsReply {
+      slots: slots.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn reserve_game_slot(
+    &self,
+    request: Request<ReserveGameSlotRequest>,
+  ) -> Result<Response<UpdateGameSlotsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let req = request.into_inner();
+    let slots = self
+      .state
+      .games
+      .send_to(
+        req.game_id,
+        ReserveSlot {
+          player_id: req.player_id,
+          slot_index: req.slot_index,
+          reserved_player_id: req.reserved_player_id,
+        },
+      )
+      .await?;
+    Ok(Response::new(UpdateGameSlotsReply {
+      slots: slots.pack().map_err(Status::internal)?,
+    }))
+  }
+
   async fn import_map_checksums(
     &self,
     request: Request<ImportMapChecksumsRequest>,
   ) -> Result<Response<ImportMapChecksumsReply>, Status> {
+    request.check_api_client_secret()?;
     let items =
       Vec::<crate::map::db::ImportItem>::unpack(request.into_inner().items).map_err(Error::from)?;
     let updated = self
@@ -402,6 +760,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<SearchMapChecksumRequest>,
   ) -> Result<Response<SearchMapChecksumReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let sha1 = request.into_inner().sha1;
     let checksum = self
       .state
@@ -412,10 +771,82 @@ impl FloController for FloControllerService {
     Ok(Response::new(SearchMapChecksumReply { checksum }))
   }
 
+  async fn list_map_pools(
+    &self,
+    request: Request<()>,
+  ) -> Result<Response<ListMapPoolsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let pools = self
+      .state
+      .db
+      .exec(move |conn| crate::map::db::list_pools(conn, api_client_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListMapPoolsReply {
+      pools: pools.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn create_map_pool(
+    &self,
+    request: Request<CreateMapPoolRequest>,
+  ) -> Result<Response<MapPoolReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let req = request.into_inner();
+    let maps = Vec::<crate::map::Map>::unpack(req.maps).map_err(Error::from)?;
+    let pool = self
+      .state
+      .db
+      .exec(move |conn| crate::map::db::create_pool(conn, api_client_id, req.name, maps))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(MapPoolReply {
+      pool: pool.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn update_map_pool(
+    &self,
+    request: Request<UpdateMapPoolRequest>,
+  ) -> Result<Response<MapPoolReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let req = request.into_inner();
+    let maps = Vec::<crate::map::Map>::unpack(req.maps).map_err(Error::from)?;
+    let pool = self
+      .state
+      .db
+      .exec(move |conn| crate::map::db::update_pool(conn, api_client_id, req.id, req.name, maps))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(MapPoolReply {
+      pool: pool.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn remove_map_pool(
+    &self,
+    request: Request<RemoveMapPoolRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let id = request.into_inner().id;
+    self
+      .state
+      .db
+      .exec(move |conn| crate::map::db::remove_pool(conn, api_client_id, id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
   async fn get_players_by_source_ids(
     &self,
     request: Request<GetPlayersBySourceIdsRequest>,
   ) -> Result<Response<GetPlayersBySourceIdsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     let api_client_id = request.get_api_client_id();
     let source_ids = request.into_inner().source_ids;
     let map = self
@@ -435,6 +866,7 @@ impl FloController for FloControllerService {
     &self,
     request: Request<GetPlayerPingMapsRequest>,
   ) -> Result<Response<GetPlayerPingMapsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
     use flo_grpc::player::PlayerPingMap;
     use std::collections::HashMap;
 
@@ -468,6 +900,8 @@ impl FloController for FloControllerService {
     &self,
     request: Request<CreateGameAsBotRequest>,
   ) -> Result<Response<CreateGameAsBotReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
     let game = self
       .state
       .games
@@ -484,10 +918,53 @@ impl FloController for FloControllerService {
     }))
   }
 
+  async fn create_balanced_game_as_bot(
+    &self,
+    request: Request<CreateBalancedGameAsBotRequest>,
+  ) -> Result<Response<CreateGameAsBotReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
+    let api_client_id = request.get_api_client_id();
+    let api_player_id = request.get_api_player_id();
+    let req = request.into_inner();
+    let mode = LadderMode::unpack_enum(req.mode());
+    let num_teams = req.num_teams as usize;
+    let fixed_groups: Vec<Vec<i32>> = req.fixed_groups.into_iter().map(|g| g.player_ids).collect();
+    let mut params =
+      CreateGameAsBotParams::unpack(req.game.unwrap_or_default()).map_err(Error::from)?;
+
+    let player_ids = req.player_ids;
+    let ratings = self
+      .state
+      .db
+      .exec(move |conn| crate::matchmaking::db::get_rating_values(conn, &player_ids, mode))
+      .await
+      .map_err(Error::from)?;
+    let players: Vec<(i32, i32)> = ratings.into_iter().collect();
+    let teams = balance_teams(&players, &fixed_groups, num_teams)?;
+    params.slots = team_slots(&teams);
+
+    let game = self
+      .state
+      .games
+      .send(CreateGameAsBot {
+        api_client_id,
+        api_player_id,
+        params,
+      })
+      .await
+      .map_err(Error::from)??;
+
+    Ok(Response::new(CreateGameAsBotReply {
+      game: game.pack().map_err(Status::internal)?,
+    }))
+  }
+
   async fn start_game_as_bot(
     &self,
     request: Request<StartGameAsBotRequest>,
   ) -> Result<Response<StartGameAsBotReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     use flo_net::proto::flo_connect::PacketGameStartPlayerClientInfoRequest;
     use std::collections::HashMap;
     use tokio::sync::oneshot;
@@ -536,47 +1013,131 @@ impl FloController for FloControllerService {
     &self,
     request: Request<CancelGameAsBotRequest>,
   ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
     let player_id = request.get_api_player_id();
+    let actor = request.get_audit_actor();
+    let game_id = request.into_inner().game_id;
     self
-      .cancel_game(Request::new(CancelGameRequest {
-        game_id: request.into_inner().game_id,
-        player_id,
-      }))
+      .cancel_game(Request::new(CancelGameRequest { game_id, player_id }))
       .await?;
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::audit::db::record(
+          conn,
+          &actor,
+          AuditAction::GameTerminate,
+          AuditTarget::Game(game_id),
+          json!({}),
+        )
+      })
+      .await
+      .map_err(Error::from)?;
 
     Ok(Response::new(()))
   }
 
-  async fn reload(&self, _request: Request<()>) -> Result<Response<()>, Status> {
-    self.state.reload().await?;
-    Ok(Response::new(()))
-  }
-
-  async fn list_player_bans(
+  async fn rehost_game(
     &self,
-    request: Request<ListPlayerBansRequest>,
-  ) -> Result<Response<ListPlayerBansReply>, Status> {
-    let api_client_id = request.get_api_client_id();
-    let params = request.into_inner();
-    let res = self
+    request: Request<RehostGameRequest>,
+  ) -> Result<Response<RehostGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
+    let req = request.into_inner();
+    let game = self
       .state
-      .db
-      .exec(move |conn| {
-        crate::player::db::list_ban(conn, api_client_id, params.query.as_deref(), params.next_id)
+      .games
+      .send(RehostGame {
+        game_id: req.game_id,
+        player_id: req.player_id,
+        node_id: req.node_id,
       })
       .await
-      .map_err(Error::from)?;
-    Ok(Response::new(ListPlayerBansReply {
-      player_bans: res.player_bans.pack().map_err(Status::internal)?,
-      next_id: res.next_id,
+      .map_err(Error::from)??;
+
+    Ok(Response::new(RehostGameReply {
+      game: game.pack().map_err(Status::internal)?,
     }))
   }
 
-  async fn create_player_ban(
+  async fn rehost_game_as_bot(
     &self,
-    request: Request<CreatePlayerBanRequest>,
-  ) -> Result<Response<()>, Status> {
-    let api_client_id = request.get_api_client_id();
+    request: Request<RehostGameAsBotRequest>,
+  ) -> Result<Response<RehostGameReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
+    let player_id = request.get_api_player_id();
+    let req = request.into_inner();
+    let game = self
+      .state
+      .games
+      .send(RehostGame {
+        game_id: req.game_id,
+        player_id,
+        node_id: req.node_id,
+      })
+      .await
+      .map_err(Error::from)??;
+
+    Ok(Response::new(RehostGameReply {
+      game: game.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn reload(&self, request: Request<()>) -> Result<Response<ReloadReply>, Status> {
+    request.check_api_client_secret()?;
+    let actor = request.get_audit_actor();
+    let report = self.state.reload().await?;
+    let payload = json!(report);
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::audit::db::record(
+          conn,
+          &actor,
+          AuditAction::SettingsReload,
+          AuditTarget::None,
+          payload,
+        )
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ReloadReply {
+      applied: report.applied,
+      restart_required: report.restart_required,
+    }))
+  }
+
+  async fn list_player_bans(
+    &self,
+    request: Request<ListPlayerBansRequest>,
+  ) -> Result<Response<ListPlayerBansReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let params = request.into_inner();
+    let res = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::player::db::list_ban(conn, api_client_id, params.query.as_deref(), params.next_id)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListPlayerBansReply {
+      player_bans: res.player_bans.pack().map_err(Status::internal)?,
+      next_id: res.next_id,
+    }))
+  }
+
+  async fn create_player_ban(
+    &self,
+    request: Request<CreatePlayerBanRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
     let params = request.into_inner();
     let ban_expires_at = params
       .ban_expires_at
@@ -584,17 +1145,22 @@ impl FloController for FloControllerService {
       .map(|t| DateTime::<Utc>::unpack(t))
       .transpose()
       .map_err(Status::internal)?;
+    let ban_type = PlayerBanType::unpack_enum(params.ban_type());
     self
       .state
       .db
       .exec(move |conn| {
         crate::player::db::check_player_api_client_id(conn, api_client_id, params.player_id)?;
-        crate::player::db::create_ban(
-          conn,
-          params.player_id,
-          PlayerBanType::unpack_enum(params.ban_type()),
-          ban_expires_at,
-        )
+        conn.transaction(|| {
+          crate::player::db::create_ban(conn, params.player_id, ban_type, ban_expires_at)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::PlayerBanCreate,
+            AuditTarget::Player(params.player_id),
+            json!({ "ban_type": ban_type, "ban_expires_at": ban_expires_at }),
+          )
+        })
       })
       .await
       .map_err(Error::from)?;
@@ -605,17 +1171,1012 @@ impl FloController for FloControllerService {
     &self,
     request: Request<RemovePlayerBanRequest>,
   ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
     let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
     let params = request.into_inner();
     self
       .state
       .db
       .exec(move |conn| {
         crate::player::db::check_ban_api_client_id(conn, api_client_id, params.id)?;
-        crate::player::db::remove_ban(conn, params.id)
+        let ban = crate::player::db::get_ban(conn, params.id)?;
+        conn.transaction(|| {
+          crate::player::db::remove_ban(conn, params.id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::PlayerBanRemove,
+            AuditTarget::PlayerBan(params.id),
+            json!({ "player_id": ban.player.id, "ban_type": ban.ban_type }),
+          )
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
+  async fn list_organizer_bans(
+    &self,
+    request: Request<ListOrganizerBansRequest>,
+  ) -> Result<Response<ListOrganizerBansReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let params = request.into_inner();
+    let res = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::player::db::list_organizer_ban(
+          conn,
+          api_client_id,
+          params.query.as_deref(),
+          params.next_id,
+        )
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListOrganizerBansReply {
+      organizer_bans: res.bans.pack().map_err(Status::internal)?,
+      next_id: res.next_id,
+    }))
+  }
+
+  async fn create_organizer_ban(
+    &self,
+    request: Request<CreateOrganizerBanRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let params = request.into_inner();
+    let ban_expires_at = params
+      .ban_expires_at
+      .clone()
+      .map(|t| DateTime::<Utc>::unpack(t))
+      .transpose()
+      .map_err(Status::internal)?;
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::player::db::create_organizer_ban(
+            conn,
+            api_client_id,
+            params.player_id,
+            &params.reason,
+            ban_expires_at,
+          )?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::OrganizerBanCreate,
+            AuditTarget::Player(params.player_id),
+            json!({ "reason": params.reason, "ban_expires_at": ban_expires_at }),
+          )
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
+  async fn remove_organizer_ban(
+    &self,
+    request: Request<RemoveOrganizerBanRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let params = request.into_inner();
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::player::db::remove_organizer_ban(conn, api_client_id, params.id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::OrganizerBanRemove,
+            AuditTarget::OrganizerBan(params.id),
+            json!({}),
+          )
+        })
       })
       .await
       .map_err(Error::from)?;
     Ok(Response::new(()))
   }
+
+  async fn list_player_links(
+    &self,
+    request: Request<ListPlayerLinksRequest>,
+  ) -> Result<Response<ListPlayerLinksReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let req = request.into_inner();
+    let params = ListPlayerLinksParams {
+      player_id: req.player_id,
+      status: req
+        .status
+        .and_then(flo_grpc::controller::PlayerLinkStatus::from_i32)
+        .map(PlayerLinkStatus::unpack_enum),
+      next_id: req.next_id,
+    };
+    let r = self
+      .state
+      .db
+      .exec(move |conn| crate::smurf::db::list(conn, api_client_id, &params))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListPlayerLinksReply {
+      links: r.links.pack().map_err(Status::internal)?,
+      next_id: r.next_id,
+    }))
+  }
+
+  async fn link_players(
+    &self,
+    request: Request<LinkPlayersRequest>,
+  ) -> Result<Response<PlayerLinkReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let req = request.into_inner();
+    let link = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let link =
+            crate::smurf::db::link(conn, api_client_id, req.player_id, req.linked_player_id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::PlayerLinkConfirm,
+            AuditTarget::PlayerLink(link.id),
+            json!({ "player_id": link.player_id, "linked_player_id": link.linked_player_id }),
+          )?;
+          Ok::<_, Error>(link)
+        })
+      })
+      .await
+      .map_err(Error::from)??;
+    Ok(Response::new(PlayerLinkReply {
+      link: link.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn unlink_players(
+    &self,
+    request: Request<UnlinkPlayersRequest>,
+  ) -> Result<Response<PlayerLinkReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let req = request.into_inner();
+    let link = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let link =
+            crate::smurf::db::unlink(conn, api_client_id, req.player_id, req.linked_player_id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::PlayerLinkDismiss,
+            AuditTarget::PlayerLink(link.id),
+            json!({ "player_id": link.player_id, "linked_player_id": link.linked_player_id }),
+          )?;
+          Ok::<_, Error>(link)
+        })
+      })
+      .await
+      .map_err(Error::from)??;
+    Ok(Response::new(PlayerLinkReply {
+      link: link.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn list_player_penalties(
+    &self,
+    request: Request<ListPlayerPenaltiesRequest>,
+  ) -> Result<Response<ListPlayerPenaltiesReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let player_id = request.into_inner().player_id;
+    let penalties = self
+      .state
+      .db
+      .exec(move |conn| crate::penalty::db::list(conn, player_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListPlayerPenaltiesReply {
+      penalties: penalties.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn appeal_player_penalty(
+    &self,
+    request: Request<AppealPlayerPenaltyRequest>,
+  ) -> Result<Response<PlayerPenaltyReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let params = request.into_inner();
+    let penalty = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::penalty::db::appeal(conn, params.id, params.player_id, &params.message)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(PlayerPenaltyReply {
+      penalty: penalty.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn resolve_player_penalty_appeal(
+    &self,
+    request: Request<ResolvePlayerPenaltyAppealRequest>,
+  ) -> Result<Response<PlayerPenaltyReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ManageBans)?;
+    let actor = request.get_audit_actor();
+    let params = request.into_inner();
+    let penalty = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let penalty = crate::penalty::db::resolve_appeal(conn, params.id, params.accepted)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::PenaltyAppealResolve,
+            AuditTarget::Penalty(params.id),
+            json!({ "player_id": penalty.player.id, "accepted": params.accepted }),
+          )?;
+          Ok::<_, Error>(penalty)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(PlayerPenaltyReply {
+      penalty: penalty.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn get_player_ratings(
+    &self,
+    request: Request<GetPlayerRatingsRequest>,
+  ) -> Result<Response<GetPlayerRatingsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let params = request.into_inner();
+    let ratings = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::matchmaking::db::get_ratings(conn, params.season_id, &params.player_ids)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(GetPlayerRatingsReply {
+      ratings: ratings.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn get_player_stats(
+    &self,
+    request: Request<GetPlayerStatsRequest>,
+  ) -> Result<Response<GetPlayerStatsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let player_id = request.into_inner().player_id;
+    let stats = self
+      .state
+      .db
+      .exec(move |conn| crate::player::stats::get(conn, player_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(GetPlayerStatsReply {
+      stats: Some(stats.pack().map_err(Status::internal)?),
+    }))
+  }
+
+  async fn list_stats_aggregates(
+    &self,
+    request: Request<ListStatsAggregatesRequest>,
+  ) -> Result<Response<ListStatsAggregatesReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let params = ListStatsAggregatesParams::unpack(request.into_inner()).map_err(Error::from)?;
+    let aggregates = self
+      .state
+      .db
+      .exec(move |conn| crate::stats::db::list(conn, &params))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListStatsAggregatesReply {
+      aggregates: aggregates.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn list_seasons(&self, request: Request<()>) -> Result<Response<ListSeasonsReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let seasons = self
+      .state
+      .db
+      .exec(move |conn| crate::season::db::list(conn))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListSeasonsReply {
+      seasons: seasons.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn create_season(
+    &self,
+    request: Request<CreateSeasonRequest>,
+  ) -> Result<Response<SeasonReply>, Status> {
+    request.check_api_client_secret()?;
+    let actor = request.get_audit_actor();
+    let params = CreateSeasonParams::unpack(request.into_inner()).map_err(Error::from)?;
+    let season = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let season = crate::season::db::create(conn, params)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::SeasonCreate,
+            AuditTarget::Season(season.id),
+            json!({
+              "name": season.name,
+              "starts_at": season.starts_at,
+              "carry_over_percent": season.carry_over_percent,
+            }),
+          )?;
+          Ok::<_, Error>(season)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(SeasonReply {
+      season: season.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn get_leaderboard(
+    &self,
+    request: Request<GetLeaderboardRequest>,
+  ) -> Result<Response<GetLeaderboardReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let req = request.into_inner();
+    let params = GetLeaderboardParams {
+      season_id: req.season_id,
+      mode: LadderMode::unpack_enum(req.mode()),
+      region: req.region,
+      race: req
+        .race
+        .and_then(flo_grpc::game::Race::from_i32)
+        .map(Race::unpack_enum),
+      cursor: req.cursor,
+      take: req.take,
+    };
+    let leaderboard = self
+      .state
+      .db
+      .exec(move |conn| crate::season::db::get_leaderboard(conn, &params))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(GetLeaderboardReply {
+      season_id: leaderboard.season_id,
+      entries: leaderboard.entries.pack().map_err(Status::internal)?,
+      next_cursor: leaderboard.next_cursor,
+    }))
+  }
+
+  async fn report_matchmaking_result(
+    &self,
+    request: Request<ReportMatchmakingResultRequest>,
+  ) -> Result<Response<ReportMatchmakingResultReply>, Status> {
+    request.check_api_client_secret()?;
+    let params = request.into_inner();
+    let ratings = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::matchmaking::db::report_result(conn, params.game_id, params.winner_team)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ReportMatchmakingResultReply {
+      ratings: ratings.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn rule_game_result(
+    &self,
+    request: Request<RuleGameResultRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_client_secret()?;
+    let actor = request.get_audit_actor();
+    let params = request.into_inner();
+    let ruling = match params.winner_team {
+      Some(team) => GameRuling::Winner(team),
+      None if params.invalid => GameRuling::Invalid,
+      None => GameRuling::Draw,
+    };
+    let saved = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let saved = crate::game::db::rule_result(conn, params.game_id, ruling)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::GameResultRule,
+            AuditTarget::Game(params.game_id),
+            json!({ "winner_team": params.winner_team, "invalid": params.invalid }),
+          )?;
+          Ok::<_, Error>(saved)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    tracing::info!(
+      game_id = saved.game_id,
+      "game result ruled: {:?}",
+      saved.resolution
+    );
+    Ok(Response::new(()))
+  }
+
+  async fn abort_game(&self, request: Request<AbortGameRequest>) -> Result<Response<()>, Status> {
+    request.check_api_client_secret()?;
+    let actor = request.get_audit_actor();
+    let AbortGameRequest { game_id, reason } = request.into_inner();
+    self
+      .state
+      .games
+      .send_to(
+        game_id,
+        AbortGame {
+          reason: reason.clone(),
+        },
+      )
+      .await?;
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::audit::db::record(
+          conn,
+          &actor,
+          AuditAction::GameAbort,
+          AuditTarget::Game(game_id),
+          json!({ "reason": reason }),
+        )
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
+  async fn list_api_tokens(
+    &self,
+    request: Request<()>,
+  ) -> Result<Response<ListApiTokensReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let tokens = self
+      .state
+      .db
+      .exec(move |conn| crate::api_token::db::list(conn, api_client_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListApiTokensReply {
+      tokens: tokens.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn create_api_token(
+    &self,
+    request: Request<CreateApiTokenRequest>,
+  ) -> Result<Response<IssueApiTokenReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let params = request.into_inner();
+    let scopes: ApiScopes = params.scopes().map(ApiTokenScope::unpack_enum).collect();
+    let expires_at = params
+      .expires_at
+      .clone()
+      .map(|t| DateTime::<Utc>::unpack(t))
+      .transpose()
+      .map_err(Status::internal)?;
+    let issued = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let issued =
+            crate::api_token::db::create(conn, api_client_id, &params.name, scopes, expires_at)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::ApiTokenCreate,
+            AuditTarget::ApiToken(issued.token.id),
+            json!({
+              "name": issued.token.name,
+              "scopes": issued.token.scopes,
+              "expires_at": issued.token.expires_at,
+            }),
+          )?;
+          Ok::<_, Error>(issued)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    self.state.reload_config().await?;
+    Ok(Response::new(IssueApiTokenReply {
+      token: issued.token.pack().map_err(Status::internal)?,
+      secret: issued.secret,
+    }))
+  }
+
+  async fn rotate_api_token(
+    &self,
+    request: Request<RotateApiTokenRequest>,
+  ) -> Result<Response<IssueApiTokenReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let id = request.into_inner().id;
+    let issued = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let issued = crate::api_token::db::rotate(conn, api_client_id, id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::ApiTokenRotate,
+            AuditTarget::ApiToken(id),
+            json!({}),
+          )?;
+          Ok::<_, Error>(issued)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    self.state.reload_config().await?;
+    Ok(Response::new(IssueApiTokenReply {
+      token: issued.token.pack().map_err(Status::internal)?,
+      secret: issued.secret,
+    }))
+  }
+
+  async fn remove_api_token(
+    &self,
+    request: Request<RemoveApiTokenRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let id = request.into_inner().id;
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::api_token::db::remove(conn, api_client_id, id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::ApiTokenRemove,
+            AuditTarget::ApiToken(id),
+            json!({}),
+          )
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    self.state.reload_config().await?;
+    Ok(Response::new(()))
+  }
+
+  async fn list_audit_logs(
+    &self,
+    request: Request<ListAuditLogsRequest>,
+  ) -> Result<Response<ListAuditLogsReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let req = request.into_inner();
+    let params = ListAuditLogParams {
+      action: req
+        .action
+        .and_then(flo_grpc::controller::AuditAction::from_i32)
+        .map(AuditAction::unpack_enum),
+      target_type: req
+        .target_type
+        .and_then(flo_grpc::controller::AuditTargetType::from_i32)
+        .map(AuditTargetType::unpack_enum),
+      target_id: req.target_id,
+      next_id: req.next_id,
+    };
+    let res = self
+      .state
+      .db
+      .exec(move |conn| crate::audit::db::list(conn, api_client_id, &params))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListAuditLogsReply {
+      logs: res.logs.pack().map_err(Status::internal)?,
+      next_id: res.next_id,
+    }))
+  }
+
+  async fn list_discord_channels(
+    &self,
+    request: Request<()>,
+  ) -> Result<Response<ListDiscordChannelsReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let channels = self
+      .state
+      .db
+      .exec(move |conn| crate::discord::db::list(conn, api_client_id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(ListDiscordChannelsReply {
+      channels: channels.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn create_discord_channel(
+    &self,
+    request: Request<CreateDiscordChannelRequest>,
+  ) -> Result<Response<DiscordChannelReply>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let req = request.into_inner();
+    let params = CreateDiscordChannelParams {
+      events: req.events().map(DiscordEvent::unpack_enum).collect(),
+      api_token_id: req.api_token_id,
+      channel_id: req.channel_id,
+      webhook_url: req.webhook_url,
+      host_map_pool_id: req.host_map_pool_id,
+      host_node_id: req.host_node_id,
+    };
+    let channel = self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          let channel = crate::discord::db::create(conn, api_client_id, params)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::DiscordChannelCreate,
+            AuditTarget::DiscordChannel(channel.id),
+            json!({
+              "api_token_id": channel.api_token_id,
+              "channel_id": channel.channel_id,
+              "events": channel.events.to_vec(),
+            }),
+          )?;
+          Ok::<_, Error>(channel)
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(DiscordChannelReply {
+      channel: channel.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn remove_discord_channel(
+    &self,
+    request: Request<RemoveDiscordChannelRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_client_secret()?;
+    let api_client_id = request.get_api_client_id();
+    let actor = request.get_audit_actor();
+    let id = request.into_inner().id;
+    self
+      .state
+      .db
+      .exec(move |conn| {
+        conn.transaction(|| {
+          crate::discord::db::remove(conn, api_client_id, id)?;
+          crate::audit::db::record(
+            conn,
+            &actor,
+            AuditAction::DiscordChannelRemove,
+            AuditTarget::DiscordChannel(id),
+            json!({}),
+          )
+        })
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
+  async fn handle_discord_command(
+    &self,
+    request: Request<DiscordCommandRequest>,
+  ) -> Result<Response<DiscordCommandReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    request.check_rate_limit(RateLimitScope::CreateGame)?;
+    let api_client_id = request.get_api_client_id();
+    let api_player_id = request.get_api_player_id();
+    let api_token_id = request.get_audit_actor().api_token_id;
+    let req = request.into_inner();
+    let command = parse_command(&req.text)?;
+    let channel_id = req.channel_id;
+    let channel = self
+      .state
+      .db
+      .exec(move |conn| crate::discord::db::get_command_channel(conn, api_token_id, &channel_id))
+      .await
+      .map_err(Error::from)?;
+
+    match command {
+      DiscordCommand::Host { map } => {
+        let game = discord_host(
+          &self.state,
+          &channel,
+          api_client_id,
+          api_player_id,
+          map,
+          req.player_source_ids,
+        )
+        .await?;
+        Ok(Response::new(DiscordCommandReply {
+          content: format!("Lobby #{} created: **{}**", game.id, game.name),
+          game: game.pack().map_err(Status::internal)?,
+        }))
+      }
+    }
+  }
+
+  async fn create_tournament(
+    &self,
+    request: Request<CreateTournamentRequest>,
+  ) -> Result<Response<TournamentReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let params = CreateTournamentParams::unpack(request.into_inner()).map_err(Error::from)?;
+    let tournament = self
+      .state
+      .db
+      .exec(move |conn| crate::tournament::db::create(conn, api_client_id, params))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(TournamentReply {
+      tournament: tournament.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn get_tournament(
+    &self,
+    request: Request<GetTournamentRequest>,
+  ) -> Result<Response<TournamentReply>, Status> {
+    request.check_api_scope(ApiTokenScope::ReadResults)?;
+    let api_client_id = request.get_api_client_id();
+    let id = request.into_inner().tournament_id;
+    let tournament = self
+      .state
+      .db
+      .exec(move |conn| crate::tournament::db::get(conn, api_client_id, id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(TournamentReply {
+      tournament: tournament.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn cancel_tournament(
+    &self,
+    request: Request<CancelTournamentRequest>,
+  ) -> Result<Response<()>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let id = request.into_inner().tournament_id;
+    self
+      .state
+      .db
+      .exec(move |conn| crate::tournament::db::cancel(conn, api_client_id, id))
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(()))
+  }
+
+  async fn schedule_tournament_match(
+    &self,
+    request: Request<ScheduleTournamentMatchRequest>,
+  ) -> Result<Response<TournamentMatchReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let params = request.into_inner();
+    let scheduled_at = params
+      .scheduled_at
+      .clone()
+      .map(|t| DateTime::<Utc>::unpack(t))
+      .transpose()
+      .map_err(Status::internal)?
+      .ok_or_else(|| Status::invalid_argument("scheduled_at is required"))?;
+    let m = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::tournament::db::schedule_match(conn, api_client_id, params.match_id, scheduled_at)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(TournamentMatchReply {
+      tournament_match: m.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn check_in_tournament_match(
+    &self,
+    request: Request<CheckInTournamentMatchRequest>,
+  ) -> Result<Response<TournamentMatchReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let params = request.into_inner();
+    let m = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::tournament::db::check_in(conn, api_client_id, params.match_id, params.player_id)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(TournamentMatchReply {
+      tournament_match: m.pack().map_err(Status::internal)?,
+    }))
+  }
+
+  async fn report_tournament_match_result(
+    &self,
+    request: Request<ReportTournamentMatchResultRequest>,
+  ) -> Result<Response<TournamentMatchReply>, Status> {
+    request.check_api_scope(ApiTokenScope::CreateGames)?;
+    let api_client_id = request.get_api_client_id();
+    let params = request.into_inner();
+    let m = self
+      .state
+      .db
+      .exec(move |conn| {
+        crate::tournament::db::report_result(conn, api_client_id, params.match_id, params.winner_id)
+      })
+      .await
+      .map_err(Error::from)?;
+    Ok(Response::new(TournamentMatchReply {
+      tournament_match: m.pack().map_err(Status::internal)?,
+    }))
+  }
+}
+
+async fn get_game_info_frame(state: &ControllerStateRef, game_id: i32) -> Result<Frame> {
+  let game = state
+    .db
+    .exec(move |conn| crate::game::db::get_full(conn, game_id))
+    .await?;
+  let frame = flo_net::proto::flo_connect::PacketGameInfo {
+    game: Some(game.pack()?),
+  }
+  .encode_as_frame()?;
+  Ok(frame)
+}
+
+/// Lobby and game updates are the packets sent to the players in the game
+fn game_update(game_id: i32, frame: Frame) -> GameUpdate {
+  let payload = match frame.payload {
+    FramePayload::Bytes(bytes) => bytes.to_vec(),
+    FramePayload::W3GS { payload, .. } => payload.to_vec(),
+  };
+  GameUpdate {
+    game_id,
+    type_id: u8::from(frame.type_id) as u32,
+    payload,
+  }
+}
+
+/// Creates a lobby for `/host`, the first source id is the player who sent the command
+async fn discord_host(
+  state: &ControllerStateRef,
+  channel: &DiscordChannel,
+  api_client_id: i32,
+  api_player_id: i32,
+  map_name: String,
+  source_ids: Vec<String>,
+) -> Result<Game> {
+  let map_pool_id = channel
+    .host_map_pool_id
+    .ok_or_else(|| Error::DiscordHostDisabled)?;
+  let (map, players) = state
+    .db
+    .exec({
+      let source_ids = source_ids.clone();
+      move |conn| {
+        let pool = crate::map::db::get_pool(conn, api_client_id, map_pool_id)?;
+        let map = crate::discord::find_map(&pool.maps, &map_name)
+          .cloned()
+          .ok_or_else(|| Error::DiscordCommandInvalid(format!("map not found: {}", map_name)))?;
+        let players =
+          crate::player::db::get_player_map_by_api_source_ids(conn, api_client_id, source_ids)?;
+        Ok::<_, Error>((map, players))
+      }
+    })
+    .await?;
+
+  let mut player_ids = vec![];
+  for source_id in &source_ids {
+    let player = players
+      .get(source_id)
+      .ok_or_else(|| Error::PlayerNotFound)?;
+    if !player_ids.contains(&player.id) {
+      player_ids.push(player.id);
+    }
+  }
+  let host = source_ids
+    .first()
+    .and_then(|id| players.get(id))
+    .ok_or_else(|| Error::GameHasNoPlayer)?;
+
+  let decision = match channel.host_node_id {
+    Some(id) => NodeDecision::pinned(id),
+    None => {
+      let snapshot = state
+        .players
+        .send(GetPlayersPingSnapshot {
+          players: player_ids.clone(),
+        })
+        .await?;
+      let nodes = state.nodes.send(ListNode).await?;
+      crate::node::policy::evaluate(None, &nodes, &snapshot.map, &player_ids)
+        .ok_or_else(|| Error::NodeNotFound)?
+    }
+  };
+
+  let teams: Vec<Vec<i32>> = player_ids.iter().map(|id| vec![*id]).collect();
+  let game = state
+    .games
+    .send(CreateGameAsBot {
+      api_client_id,
+      api_player_id,
+      params: CreateGameAsBotParams {
+        name: format!("{} ({})", map.name, host.name),
+        map,
+        is_private: false,
+        is_live: false,
+        node_id: decision.node_id,
+        slots: team_slots(&teams),
+        mask_player_names: false,
+        enable_ping_equalizer: false,
+        flo_tv_delay_override_secs: None,
+        map_config: None,
+        feature_flags: None,
+        auto_start: None,
+      },
+    })
+    .await??;
+
+  let game_id = game.id;
+  state
+    .db
+    .exec(move |conn| crate::node::db::add_assignment(conn, game_id, None, None, &decision))
+    .await?;
+
+  Ok(game)
 }
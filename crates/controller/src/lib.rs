@@ -14,17 +14,30 @@ extern crate diesel;
 mod db;
 mod schema;
 
+pub mod api_token;
+pub mod audit;
 mod client;
 mod config;
+pub mod discord;
 pub mod error;
 pub mod game;
 mod grpc;
 pub mod host;
 pub mod map;
+pub mod matchmaking;
+mod metrics;
 pub mod node;
+pub mod penalty;
 pub mod player;
+mod rate_limit;
+pub mod season;
+mod settings;
+pub mod smurf;
 mod state;
+pub mod stats;
+pub mod tournament;
 
 pub use client::serve as serve_socket;
 pub use grpc::serve as serve_grpc;
-pub use state::{ControllerState, ControllerStateRef};
+pub use metrics::serve as serve_metrics;
+pub use state::{ActorMapExt, ControllerState, ControllerStateRef};
@@ -1,10 +1,13 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use s2_grpc_utils::S2ProtoUnpack;
 use serde::Deserialize;
+use serde_json::Value;
 
 use crate::db::DbConn;
 use crate::error::*;
-use crate::schema::map_checksum;
+use crate::map::{Map, MapPool};
+use crate::schema::{map_checksum, map_pool};
 
 pub fn search_checksum(conn: &DbConn, sha1: String) -> Result<Option<u32>> {
   use map_checksum::dsl;
@@ -62,3 +65,117 @@ struct Insert<'a> {
   sha1: &'a str,
   checksum: Vec<u8>,
 }
+
+type MapPoolRow = (i32, String, Value, DateTime<Utc>, DateTime<Utc>);
+
+const MAP_POOL_COLUMNS: (
+  map_pool::id,
+  map_pool::name,
+  map_pool::maps,
+  map_pool::created_at,
+  map_pool::updated_at,
+) = (
+  map_pool::id,
+  map_pool::name,
+  map_pool::maps,
+  map_pool::created_at,
+  map_pool::updated_at,
+);
+
+fn map_pool_from_row((id, name, maps, created_at, updated_at): MapPoolRow) -> Result<MapPool> {
+  Ok(MapPool {
+    id,
+    name,
+    maps: serde_json::from_value(maps)?,
+    created_at,
+    updated_at,
+  })
+}
+
+pub fn list_pools(conn: &DbConn, api_client_id: i32) -> Result<Vec<MapPool>> {
+  map_pool::table
+    .select(MAP_POOL_COLUMNS)
+    .filter(map_pool::api_client_id.eq(api_client_id))
+    .order(map_pool::id)
+    .load::<MapPoolRow>(conn)?
+    .into_iter()
+    .map(map_pool_from_row)
+    .collect()
+}
+
+pub fn get_pool(conn: &DbConn, api_client_id: i32, id: i32) -> Result<MapPool> {
+  let row = map_pool::table
+    .select(MAP_POOL_COLUMNS)
+    .filter(
+      map_pool::id
+        .eq(id)
+        .and(map_pool::api_client_id.eq(api_client_id)),
+    )
+    .first::<MapPoolRow>(conn)
+    .optional()?
+    .ok_or_else(|| Error::MapPoolNotFound)?;
+  map_pool_from_row(row)
+}
+
+pub fn create_pool(
+  conn: &DbConn,
+  api_client_id: i32,
+  name: String,
+  maps: Vec<Map>,
+) -> Result<MapPool> {
+  if maps.is_empty() {
+    return Err(Error::MapPoolEmpty);
+  }
+  let row = diesel::insert_into(map_pool::table)
+    .values((
+      map_pool::api_client_id.eq(api_client_id),
+      map_pool::name.eq(name),
+      map_pool::maps.eq(serde_json::to_value(&maps)?),
+    ))
+    .returning(MAP_POOL_COLUMNS)
+    .get_result::<MapPoolRow>(conn)?;
+  map_pool_from_row(row)
+}
+
+pub fn update_pool(
+  conn: &DbConn,
+  api_client_id: i32,
+  id: i32,
+  name: String,
+  maps: Vec<Map>,
+) -> Result<MapPool> {
+  if maps.is_empty() {
+    return Err(Error::MapPoolEmpty);
+  }
+  let row = diesel::update(
+    map_pool::table.filter(
+      map_pool::id
+        .eq(id)
+        .and(map_pool::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .set((
+    map_pool::name.eq(name),
+    map_pool::maps.eq(serde_json::to_value(&maps)?),
+  ))
+  .returning(MAP_POOL_COLUMNS)
+  .get_result::<MapPoolRow>(conn)
+  .optional()?
+  .ok_or_else(|| Error::MapPoolNotFound)?;
+  map_pool_from_row(row)
+}
+
+pub fn remove_pool(conn: &DbConn, api_client_id: i32, id: i32) -> Result<()> {
+  let removed = diesel::delete(
+    map_pool::table.filter(
+      map_pool::id
+        .eq(id)
+        .and(map_pool::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .execute(conn)?;
+  if removed == 0 {
+    return Err(Error::MapPoolNotFound);
+  }
+  Ok(())
+}
@@ -1,9 +1,14 @@
 pub mod db;
+pub(crate) mod state;
+mod veto;
 
+use chrono::{DateTime, Utc};
 use s2_grpc_utils::result::Error as ProtoError;
 use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
 use serde::{Deserialize, Serialize};
 
+pub use state::MapVetoRegistry;
+
 #[derive(Debug, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone)]
 #[s2_grpc(message_type = "flo_grpc::game::Map")]
 pub struct Map {
@@ -29,6 +34,10 @@ impl MapSha1 {
   pub fn to_vec(&self) -> Vec<u8> {
     self.0.to_vec()
   }
+
+  pub fn to_hex(&self) -> String {
+    self.0.iter().map(|b| format!("{:02x}", b)).collect()
+  }
 }
 
 impl S2ProtoUnpack<Vec<u8>> for MapSha1 {
@@ -65,3 +74,14 @@ pub struct MapForce {
   pub flags: u32,
   pub player_set: u32,
 }
+
+/// Maps defined by an organizer, used to run map vetoes
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone)]
+#[s2_grpc(message_type = "flo_grpc::game::MapPool")]
+pub struct MapPool {
+  pub id: i32,
+  pub name: String,
+  pub maps: Vec<Map>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
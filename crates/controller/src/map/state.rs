@@ -0,0 +1,192 @@
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::{MapVetoItem, PacketMapVetoStatus};
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::sleep;
+
+use crate::error::*;
+use crate::map::veto::MapVeto;
+use crate::map::Map;
+use crate::player::state::sender::PlayerRegistryHandle;
+use crate::player::state::PlayerRegistry;
+use crate::state::Data;
+
+const TURN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs map vetoes between two players before a lobby is created
+pub struct MapVetoRegistry {
+  player_packet_sender: PlayerRegistryHandle,
+  next_id: i32,
+  sessions: BTreeMap<i32, Session>,
+}
+
+struct Session {
+  veto: MapVeto,
+  maps: Vec<Map>,
+  tx: Option<oneshot::Sender<Map>>,
+}
+
+impl Session {
+  fn to_packet(&self, veto_id: i32) -> PacketMapVetoStatus {
+    PacketMapVetoStatus {
+      veto_id,
+      maps: self
+        .maps
+        .iter()
+        .zip(self.veto.banned_by())
+        .map(|(map, banned_by)| MapVetoItem {
+          name: map.name.clone(),
+          path: map.path.clone(),
+          banned_by: banned_by.clone(),
+        })
+        .collect(),
+      next_player_id: self.veto.next_player_id(),
+      turn_timeout_secs: TURN_TIMEOUT.as_secs() as u32,
+      selected_index: self.veto.selected().map(|idx| idx as i32),
+    }
+  }
+}
+
+impl MapVetoRegistry {
+  /// Notifies both players and completes the veto if only one map remains
+  async fn update(&mut self, ctx: &mut Context<Self>, veto_id: i32) -> Result<()> {
+    let session = self
+      .sessions
+      .get_mut(&veto_id)
+      .ok_or_else(|| Error::MapVetoNotFound)?;
+    let frame = session.to_packet(veto_id).encode_as_frame()?;
+    let player_ids = session.veto.player_ids().to_vec();
+
+    if let Some(idx) = session.veto.selected() {
+      let map = session.maps[idx].clone();
+      if let Some(tx) = session.tx.take() {
+        tx.send(map).ok();
+      }
+      self.sessions.remove(&veto_id);
+    } else {
+      let turn = session.veto.turn();
+      let addr = ctx.addr();
+      ctx.spawn(async move {
+        sleep(TURN_TIMEOUT).await;
+        addr.notify(TurnTimeout { veto_id, turn }).await.ok();
+      });
+    }
+
+    self
+      .player_packet_sender
+      .broadcast(player_ids, frame)
+      .await?;
+    Ok(())
+  }
+}
+
+impl Actor for MapVetoRegistry {}
+
+#[async_trait]
+impl Service<Data> for MapVetoRegistry {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    let players = registry.resolve::<PlayerRegistry>().await?;
+    Ok(MapVetoRegistry {
+      player_packet_sender: players.into(),
+      next_id: 0,
+      sessions: BTreeMap::new(),
+    })
+  }
+}
+
+/// Starts a veto, the reply resolves to the selected map
+pub struct StartMapVeto {
+  /// The first player bans first
+  pub player_ids: [i32; 2],
+  pub maps: Vec<Map>,
+}
+
+impl Message for StartMapVeto {
+  type Result = Result<oneshot::Receiver<Map>>;
+}
+
+#[async_trait]
+impl Handler<StartMapVeto> for MapVetoRegistry {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    StartMapVeto { player_ids, maps }: StartMapVeto,
+  ) -> Result<oneshot::Receiver<Map>> {
+    if maps.is_empty() {
+      return Err(Error::MapPoolEmpty);
+    }
+    self.next_id += 1;
+    let veto_id = self.next_id;
+    let (tx, rx) = oneshot::channel();
+    self.sessions.insert(
+      veto_id,
+      Session {
+        veto: MapVeto::new(player_ids, maps.len()),
+        maps,
+        tx: Some(tx),
+      },
+    );
+    self.update(ctx, veto_id).await?;
+    Ok(rx)
+  }
+}
+
+pub struct BanMap {
+  pub veto_id: i32,
+  pub player_id: i32,
+  pub map_index: i32,
+}
+
+impl Message for BanMap {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<BanMap> for MapVetoRegistry {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    BanMap {
+      veto_id,
+      player_id,
+      map_index,
+    }: BanMap,
+  ) -> Result<()> {
+    let session = self
+      .sessions
+      .get_mut(&veto_id)
+      .ok_or_else(|| Error::MapVetoNotFound)?;
+    if map_index < 0 {
+      return Err(Error::MapVetoMapInvalid);
+    }
+    session.veto.ban(player_id, map_index as usize)?;
+    self.update(ctx, veto_id).await
+  }
+}
+
+struct TurnTimeout {
+  veto_id: i32,
+  turn: usize,
+}
+
+impl Message for TurnTimeout {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<TurnTimeout> for MapVetoRegistry {
+  async fn handle(&mut self, ctx: &mut Context<Self>, TurnTimeout { veto_id, turn }: TurnTimeout) {
+    let banned = match self.sessions.get_mut(&veto_id) {
+      Some(session) if session.veto.turn() == turn => session.veto.ban_random(),
+      _ => return,
+    };
+    tracing::debug!(veto_id, "map veto turn timeout, banned: {:?}", banned);
+    if let Err(err) = self.update(ctx, veto_id).await {
+      tracing::error!(veto_id, "map veto update: {}", err);
+    }
+  }
+}
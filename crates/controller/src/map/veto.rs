@@ -0,0 +1,102 @@
+use crate::error::*;
+
+/// Two players alternately ban maps until one remains
+#[derive(Debug)]
+pub struct MapVeto {
+  player_ids: [i32; 2],
+  banned_by: Vec<Option<i32>>,
+  turn: usize,
+}
+
+impl MapVeto {
+  /// `player_ids[0]` bans first
+  pub fn new(player_ids: [i32; 2], num_maps: usize) -> Self {
+    Self {
+      player_ids,
+      banned_by: vec![None; num_maps],
+      turn: 0,
+    }
+  }
+
+  pub fn player_ids(&self) -> &[i32; 2] {
+    &self.player_ids
+  }
+
+  pub fn turn(&self) -> usize {
+    self.turn
+  }
+
+  pub fn banned_by(&self) -> &[Option<i32>] {
+    &self.banned_by
+  }
+
+  fn remaining(&self) -> impl Iterator<Item = usize> + '_ {
+    self
+      .banned_by
+      .iter()
+      .enumerate()
+      .filter(|(_, v)| v.is_none())
+      .map(|(idx, _)| idx)
+  }
+
+  pub fn next_player_id(&self) -> Option<i32> {
+    if self.selected().is_some() {
+      None
+    } else {
+      Some(self.player_ids[self.turn % 2])
+    }
+  }
+
+  /// Index of the remaining map once the veto is done
+  pub fn selected(&self) -> Option<usize> {
+    let mut remaining = self.remaining();
+    match (remaining.next(), remaining.next()) {
+      (Some(idx), None) => Some(idx),
+      _ => None,
+    }
+  }
+
+  pub fn ban(&mut self, player_id: i32, index: usize) -> Result<()> {
+    if self.next_player_id() != Some(player_id) {
+      return Err(Error::MapVetoNotYourTurn);
+    }
+    match self.banned_by.get_mut(index) {
+      Some(slot @ None) => {
+        slot.replace(player_id);
+      }
+      _ => return Err(Error::MapVetoMapInvalid),
+    }
+    self.turn += 1;
+    Ok(())
+  }
+
+  /// Bans a random remaining map for the player whose turn timed out
+  pub fn ban_random(&mut self) -> Option<usize> {
+    use rand::seq::IteratorRandom;
+    let player_id = self.next_player_id()?;
+    let index = self.remaining().choose(&mut rand::thread_rng())?;
+    self.ban(player_id, index).ok()?;
+    Some(index)
+  }
+}
+
+#[test]
+fn test_map_veto() {
+  let mut veto = MapVeto::new([1, 2], 4);
+  assert_eq!(veto.next_player_id(), Some(1));
+  assert!(matches!(veto.ban(2, 0), Err(Error::MapVetoNotYourTurn)));
+  veto.ban(1, 0).unwrap();
+  assert!(matches!(veto.ban(2, 0), Err(Error::MapVetoMapInvalid)));
+  assert!(matches!(veto.ban(2, 4), Err(Error::MapVetoMapInvalid)));
+  veto.ban(2, 3).unwrap();
+  assert_eq!(veto.selected(), None);
+  assert!(veto.ban_random().is_some());
+  assert!(veto.selected().is_some());
+  assert_eq!(veto.next_player_id(), None);
+  assert_eq!(veto.ban_random(), None);
+  assert_eq!(veto.banned_by()[0], Some(1));
+  assert_eq!(veto.banned_by()[3], Some(2));
+
+  let veto = MapVeto::new([1, 2], 1);
+  assert_eq!(veto.selected(), Some(0));
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use crate::error::*;
+use crate::game::{Computer, CreateGameSlot, Race, SlotSettings, SlotStatus};
+
+// above this number of units the exhaustive search is too expensive
+const MAX_EXHAUSTIVE_UNITS: usize = 16;
+
+#[derive(Debug)]
+struct Unit {
+  player_ids: Vec<i32>,
+  rating: i32,
+}
+
+/// Splits players into `num_teams` equally sized teams with the closest total ratings.
+/// `players` are `(player_id, rating)` pairs, players in the same fixed group
+/// are always placed in the same team.
+pub fn balance_teams(
+  players: &[(i32, i32)],
+  fixed_groups: &[Vec<i32>],
+  num_teams: usize,
+) -> Result<Vec<Vec<i32>>> {
+  if num_teams < 2 || players.is_empty() || players.len() % num_teams != 0 {
+    return Err(Error::TeamBalanceInvalid);
+  }
+  let team_size = players.len() / num_teams;
+
+  let mut ratings: BTreeMap<i32, i32> = players.iter().cloned().collect();
+  if ratings.len() != players.len() {
+    return Err(Error::TeamBalanceInvalid);
+  }
+
+  let mut units = vec![];
+  for group in fixed_groups {
+    if group.is_empty() {
+      continue;
+    }
+    if group.len() > team_size {
+      return Err(Error::TeamBalanceInvalid);
+    }
+    let mut rating = 0;
+    for player_id in group {
+      rating += ratings
+        .remove(player_id)
+        .ok_or_else(|| Error::TeamBalanceInvalid)?;
+    }
+    units.push(Unit {
+      player_ids: group.clone(),
+      rating,
+    });
+  }
+  for (player_id, rating) in ratings {
+    units.push(Unit {
+      player_ids: vec![player_id],
+      rating,
+    });
+  }
+  units.sort_by_key(|u| (-u.rating, u.player_ids[0]));
+
+  let assignment = if num_teams == 2 && units.len() <= MAX_EXHAUSTIVE_UNITS {
+    search_two_teams(&units, team_size)
+  } else {
+    assign_greedy(&units, num_teams, team_size)
+  }
+  .ok_or_else(|| Error::TeamBalanceInvalid)?;
+
+  let player_ratings: BTreeMap<i32, i32> = players.iter().cloned().collect();
+  let mut teams = vec![vec![]; num_teams];
+  for (unit, team) in units.iter().zip(assignment) {
+    teams[team].extend(unit.player_ids.iter().cloned());
+  }
+  for team in &mut teams {
+    team.sort_by_key(|id| -player_ratings[id]);
+  }
+  Ok(teams)
+}
+
+/// Occupied slots for the teams, in team order
+pub fn team_slots(teams: &[Vec<i32>]) -> Vec<CreateGameSlot> {
+  let mut slots = vec![];
+  for (team, player_ids) in teams.iter().enumerate() {
+    for player_id in player_ids {
+      slots.push(CreateGameSlot {
+        player_id: Some(*player_id),
+        settings: SlotSettings {
+          team: team as i32,
+          color: slots.len() as i32,
+          computer: Computer::Easy,
+          handicap: 100,
+          status: SlotStatus::Occupied,
+          race: Race::Random,
+        },
+      });
+    }
+  }
+  slots
+}
+
+// the first unit always goes to team 0, the mirrored splits are equivalent
+fn search_two_teams(units: &[Unit], team_size: usize) -> Option<Vec<usize>> {
+  let total: i32 = units.iter().map(|u| u.rating).sum();
+  let mut best: Option<(i32, u32)> = None;
+  for rest in 0..(1u32 << (units.len() - 1)) {
+    let mask = (rest << 1) | 1;
+    let (mut size, mut rating) = (0, 0);
+    for (i, unit) in units.iter().enumerate() {
+      if mask & (1 << i) != 0 {
+        size += unit.player_ids.len();
+        rating += unit.rating;
+      }
+    }
+    if size != team_size {
+      continue;
+    }
+    let diff = (total - rating * 2).abs();
+    if best.map(|(d, _)| diff < d).unwrap_or(true) {
+      best = Some((diff, mask));
+    }
+  }
+  best.map(|(_, mask)| {
+    (0..units.len())
+      .map(|i| if mask & (1 << i) != 0 { 0 } else { 1 })
+      .collect()
+  })
+}
+
+// larger units first, each goes to the weakest team that still has room
+fn assign_greedy(units: &[Unit], num_teams: usize, team_size: usize) -> Option<Vec<usize>> {
+  let mut order: Vec<usize> = (0..units.len()).collect();
+  order.sort_by_key(|i| std::cmp::Reverse(units[*i].player_ids.len()));
+
+  let mut sizes = vec![0; num_teams];
+  let mut ratings = vec![0; num_teams];
+  let mut assignment = vec![0; units.len()];
+  for i in order {
+    let unit = &units[i];
+    let team = (0..num_teams)
+      .filter(|t| sizes[*t] + unit.player_ids.len() <= team_size)
+      .min_by_key(|t| (ratings[*t], *t))?;
+    sizes[team] += unit.player_ids.len();
+    ratings[team] += unit.rating;
+    assignment[i] = team;
+  }
+  Some(assignment)
+}
+
+#[test]
+fn test_balance_teams() {
+  let players = vec![(1, 1500), (2, 1800), (3, 1600), (4, 1700)];
+  assert_eq!(
+    balance_teams(&players, &[], 2).unwrap(),
+    vec![vec![2, 1], vec![4, 3]]
+  );
+
+  // the duo is kept together even if it is not the best split
+  assert_eq!(
+    balance_teams(&players, &[vec![2, 4]], 2).unwrap(),
+    vec![vec![2, 4], vec![3, 1]]
+  );
+
+  let players = vec![
+    (1, 2000),
+    (2, 1000),
+    (3, 1000),
+    (4, 1000),
+    (5, 1000),
+    (6, 1000),
+  ];
+  let teams = balance_teams(&players, &[], 3).unwrap();
+  assert_eq!(teams.len(), 3);
+  assert!(teams.iter().all(|t| t.len() == 2));
+
+  assert!(balance_teams(&players, &[vec![1, 2, 3, 4]], 2).is_err());
+  assert!(balance_teams(&players, &[vec![1, 7]], 2).is_err());
+  assert!(balance_teams(&players[..5], &[], 2).is_err());
+}
@@ -0,0 +1,302 @@
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::Race;
+use crate::map::Map;
+use crate::matchmaking::rating::{team_rating_delta, DEFAULT_RATING};
+use crate::matchmaking::{LadderMode, PlayerRating, PlayerRatingChange};
+use crate::schema::{
+  game, game_used_slot, ladder_map, matchmaking_game, node, player_rating, player_rating_history,
+};
+use crate::season::db::get_active_id;
+
+/// Ratings of players in a season, defaults to the active season
+pub fn get_ratings(
+  conn: &DbConn,
+  season_id: Option<i32>,
+  player_ids: &[i32],
+) -> Result<Vec<PlayerRating>> {
+  let season_id = match season_id {
+    Some(id) => id,
+    None => get_active_id(conn)?,
+  };
+  player_rating::table
+    .select(PlayerRating::COLUMNS)
+    .filter(
+      player_rating::season_id
+        .eq(season_id)
+        .and(player_rating::player_id.eq(any(player_ids))),
+    )
+    .order((player_rating::player_id, player_rating::mode))
+    .load(conn)
+    .map_err(Into::into)
+}
+
+/// Returns the rating of a player in a mode, or the default rating if the player is unranked
+pub fn get_rating_value(conn: &DbConn, player_id: i32, mode: LadderMode) -> Result<i32> {
+  let season_id = get_active_id(conn)?;
+  let value = player_rating::table
+    .select(player_rating::rating)
+    .filter(
+      player_rating::season_id
+        .eq(season_id)
+        .and(player_rating::player_id.eq(player_id))
+        .and(player_rating::mode.eq(mode)),
+    )
+    .first(conn)
+    .optional()?;
+  Ok(value.unwrap_or(DEFAULT_RATING))
+}
+
+/// Returns the ratings of players in a mode, unranked players get the default rating
+pub fn get_rating_values(
+  conn: &DbConn,
+  player_ids: &[i32],
+  mode: LadderMode,
+) -> Result<BTreeMap<i32, i32>> {
+  let season_id = get_active_id(conn)?;
+  let mut map: BTreeMap<i32, i32> = player_rating::table
+    .select((player_rating::player_id, player_rating::rating))
+    .filter(
+      player_rating::season_id
+        .eq(season_id)
+        .and(player_rating::player_id.eq(any(player_ids)))
+        .and(player_rating::mode.eq(mode)),
+    )
+    .load::<(i32, i32)>(conn)?
+    .into_iter()
+    .collect();
+  for player_id in player_ids {
+    map.entry(*player_id).or_insert(DEFAULT_RATING);
+  }
+  Ok(map)
+}
+
+pub fn get_ladder_maps(conn: &DbConn) -> Result<BTreeMap<LadderMode, Vec<Map>>> {
+  let rows: Vec<(LadderMode, Value)> = ladder_map::table
+    .select((ladder_map::mode, ladder_map::map))
+    .filter(ladder_map::enabled.eq(true))
+    .order(ladder_map::id)
+    .load(conn)?;
+  let mut map = BTreeMap::new();
+  for (mode, value) in rows {
+    map
+      .entry(mode)
+      .or_insert_with(|| vec![])
+      .push(serde_json::from_value(value)?);
+  }
+  Ok(map)
+}
+
+pub fn add_game(conn: &DbConn, game_id: i32, mode: LadderMode) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "matchmaking_game"]
+  struct Insert {
+    game_id: i32,
+    mode: LadderMode,
+  }
+
+  diesel::insert_into(matchmaking_game::table)
+    .values(&Insert { game_id, mode })
+    .execute(conn)?;
+
+  Ok(())
+}
+
+/// Records the winner of a matchmaking game and updates the ratings of all players
+pub fn report_result(conn: &DbConn, game_id: i32, winner_team: i32) -> Result<Vec<PlayerRating>> {
+  #[derive(Insertable)]
+  #[table_name = "player_rating"]
+  struct Upsert {
+    season_id: i32,
+    player_id: i32,
+    mode: LadderMode,
+    rating: i32,
+    wins: i32,
+    losses: i32,
+    race: Option<Race>,
+    region: Option<String>,
+  }
+
+  #[derive(Insertable)]
+  #[table_name = "player_rating_history"]
+  struct InsertHistory {
+    season_id: i32,
+    player_id: i32,
+    mode: LadderMode,
+    game_id: i32,
+    rating_before: i32,
+    rating_after: i32,
+  }
+
+  conn.transaction(|| {
+    let (mode, reported_at): (LadderMode, Option<DateTime<Utc>>) = matchmaking_game::table
+      .find(game_id)
+      .select((matchmaking_game::mode, matchmaking_game::reported_at))
+      .for_update()
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::MatchmakingGameNotFound)?;
+
+    if reported_at.is_some() {
+      return Err(Error::MatchmakingResultReported);
+    }
+
+    let slots: Vec<(Option<i32>, i32, Race)> = game_used_slot::table
+      .select((
+        game_used_slot::player_id,
+        game_used_slot::team,
+        game_used_slot::race,
+      ))
+      .filter(
+        game_used_slot::game_id
+          .eq(game_id)
+          .and(game_used_slot::team.ne(24)),
+      )
+      .load(conn)?;
+    let races: BTreeMap<i32, Race> = slots
+      .iter()
+      .filter_map(|(player_id, _, race)| player_id.map(|id| (id, *race)))
+      .collect();
+    let slots: Vec<(i32, i32)> = slots
+      .into_iter()
+      .filter_map(|(player_id, team, _)| player_id.map(|id| (id, team)))
+      .collect();
+
+    if !slots.iter().any(|(_, team)| *team == winner_team) {
+      return Err(Error::MatchmakingTeamInvalid);
+    }
+
+    let region: Option<String> = game::table
+      .find(game_id)
+      .left_outer_join(node::table)
+      .select(node::region.nullable())
+      .first(conn)?;
+
+    let season_id = get_active_id(conn)?;
+    let player_ids: Vec<i32> = slots.iter().map(|(id, _)| *id).collect();
+    let current: BTreeMap<i32, PlayerRating> = get_ratings(conn, Some(season_id), &player_ids)?
+      .into_iter()
+      .filter(|r| r.mode == mode)
+      .map(|r| (r.player_id, r))
+      .collect();
+    let rating_of = |player_id: i32| {
+      current
+        .get(&player_id)
+        .map(|r| r.rating)
+        .unwrap_or(DEFAULT_RATING)
+    };
+
+    let (winners, losers): (Vec<_>, Vec<_>) =
+      slots.iter().partition(|(_, team)| *team == winner_team);
+    let delta = team_rating_delta(
+      &winners
+        .iter()
+        .map(|(id, _)| rating_of(*id))
+        .collect::<Vec<_>>(),
+      &losers
+        .iter()
+        .map(|(id, _)| rating_of(*id))
+        .collect::<Vec<_>>(),
+    );
+
+    let upserts: Vec<_> = slots
+      .iter()
+      .map(|(player_id, team)| {
+        let won = *team == winner_team;
+        let (wins, losses) = current
+          .get(player_id)
+          .map(|r| (r.wins, r.losses))
+          .unwrap_or_default();
+        Upsert {
+          season_id,
+          player_id: *player_id,
+          mode,
+          rating: if won {
+            rating_of(*player_id) + delta
+          } else {
+            rating_of(*player_id) - delta
+          },
+          wins: if won { wins + 1 } else { wins },
+          losses: if won { losses } else { losses + 1 },
+          race: races.get(player_id).cloned(),
+          region: region.clone(),
+        }
+      })
+      .collect();
+
+    diesel::insert_into(player_rating_history::table)
+      .values(
+        &upserts
+          .iter()
+          .map(|u| InsertHistory {
+            season_id,
+            player_id: u.player_id,
+            mode,
+            game_id,
+            rating_before: rating_of(u.player_id),
+            rating_after: u.rating,
+          })
+          .collect::<Vec<_>>(),
+      )
+      .execute(conn)?;
+
+    {
+      use diesel::pg::upsert::excluded;
+      use player_rating::dsl;
+      diesel::insert_into(player_rating::table)
+        .values(&upserts)
+        .on_conflict((dsl::season_id, dsl::player_id, dsl::mode))
+        .do_update()
+        .set((
+          dsl::rating.eq(excluded(dsl::rating)),
+          dsl::wins.eq(excluded(dsl::wins)),
+          dsl::losses.eq(excluded(dsl::losses)),
+          dsl::race.eq(excluded(dsl::race)),
+          dsl::region.eq(excluded(dsl::region)),
+          dsl::updated_at.eq(sql("now()")),
+        ))
+        .execute(conn)?;
+    }
+
+    diesel::update(matchmaking_game::table.find(game_id))
+      .set((
+        matchmaking_game::winner_team.eq(winner_team),
+        matchmaking_game::reported_at.eq(sql("now()")),
+      ))
+      .execute(conn)?;
+
+    Ok(
+      get_ratings(conn, Some(season_id), &player_ids)?
+        .into_iter()
+        .filter(|r| r.mode == mode)
+        .collect(),
+    )
+  })
+}
+
+/// Rating changes of a player in a mode, most recent first
+pub fn get_rating_history(
+  conn: &DbConn,
+  player_id: i32,
+  mode: LadderMode,
+  limit: i64,
+) -> Result<Vec<PlayerRatingChange>> {
+  player_rating_history::table
+    .select(PlayerRatingChange::COLUMNS)
+    .filter(
+      player_rating_history::player_id
+        .eq(player_id)
+        .and(player_rating_history::mode.eq(mode)),
+    )
+    .order(player_rating_history::id.desc())
+    .limit(limit)
+    .load(conn)
+    .map_err(Into::into)
+}
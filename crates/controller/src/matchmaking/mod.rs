@@ -0,0 +1,13 @@
+pub mod balance;
+pub mod db;
+mod rating;
+pub(crate) mod state;
+mod types;
+
+pub mod messages {
+  pub use super::state::{GetQueueStatus, JoinQueue, LeaveQueue};
+}
+
+pub use rating::DEFAULT_RATING;
+pub use state::MatchmakingRegistry;
+pub use types::*;
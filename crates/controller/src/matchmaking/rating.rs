@@ -0,0 +1,35 @@
+pub const DEFAULT_RATING: i32 = 1500;
+const K_FACTOR: f64 = 32.0;
+
+/// Probability of a player with `rating` beating a player with `opponent`
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+  1.0 / (1.0 + 10_f64.powf((opponent - rating) / 400.0))
+}
+
+fn average(ratings: &[i32]) -> f64 {
+  if ratings.is_empty() {
+    return DEFAULT_RATING as f64;
+  }
+  ratings.iter().map(|v| *v as f64).sum::<f64>() / ratings.len() as f64
+}
+
+/// Rating points gained by every player of the winning team,
+/// players of the losing team lose the same amount.
+pub fn team_rating_delta(winners: &[i32], losers: &[i32]) -> i32 {
+  let expected = expected_score(average(winners), average(losers));
+  std::cmp::max(1, (K_FACTOR * (1.0 - expected)).round() as i32)
+}
+
+#[test]
+fn test_team_rating_delta() {
+  assert_eq!(team_rating_delta(&[1500], &[1500]), 16);
+  assert_eq!(team_rating_delta(&[1500, 1500], &[1500, 1500]), 16);
+
+  let favorite = team_rating_delta(&[1800], &[1400]);
+  let upset = team_rating_delta(&[1400], &[1800]);
+  assert!(favorite < 16);
+  assert!(upset > 16);
+  assert_eq!(favorite + upset, 32);
+
+  assert_eq!(team_rating_delta(&[3000], &[100]), 1);
+}
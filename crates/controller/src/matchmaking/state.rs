@@ -0,0 +1,633 @@
+use bs_diesel_utils::ExecutorRef;
+use flo_net::packet::FloPacket;
+use flo_state::{async_trait, Actor, Addr, Context, Handler, Message, RegistryRef, Service};
+use rand::seq::SliceRandom;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use crate::error::*;
+use crate::game::db::CreateGameForPlayersParams;
+use crate::game::state::create::CreateGameForPlayers;
+use crate::game::state::GameRegistry;
+use crate::map::state::StartMapVeto;
+use crate::map::{Map, MapVetoRegistry};
+use crate::matchmaking::balance::{balance_teams, team_slots};
+use crate::matchmaking::{LadderMode, QueueStatus};
+use crate::node::messages::ListNode;
+use crate::node::policy::{NodeDecision, NodePolicy};
+use crate::node::NodeRegistry;
+use crate::player::state::ping::GetPlayersPingSnapshot;
+use crate::player::state::sender::PlayerRegistryHandle;
+use crate::player::state::PlayerRegistry;
+use crate::state::{Data, Reload};
+
+const MATCH_INTERVAL: Duration = Duration::from_secs(5);
+const RATING_WINDOW_BASE: u64 = 50;
+const RATING_WINDOW_STEP_PER_SEC: u64 = 5;
+const RATING_WINDOW_MAX: u64 = 500;
+
+pub struct MatchmakingRegistry {
+  db: ExecutorRef,
+  games: Addr<GameRegistry>,
+  players: Addr<PlayerRegistry>,
+  map_vetos: Addr<MapVetoRegistry>,
+  nodes: Addr<NodeRegistry>,
+  player_packet_sender: PlayerRegistryHandle,
+  maps: BTreeMap<LadderMode, Vec<Map>>,
+  policies: BTreeMap<LadderMode, NodePolicy>,
+  queues: BTreeMap<LadderMode, Vec<QueueEntry>>,
+}
+
+#[derive(Debug, Clone)]
+struct QueueEntry {
+  player_id: i32,
+  rating: i32,
+  joined_at: Instant,
+}
+
+impl QueueEntry {
+  /// The accepted rating difference grows the longer a player waits
+  fn rating_window(&self, now: Instant) -> i32 {
+    let waited = now.saturating_duration_since(self.joined_at).as_secs();
+    std::cmp::min(
+      RATING_WINDOW_BASE + waited * RATING_WINDOW_STEP_PER_SEC,
+      RATING_WINDOW_MAX,
+    ) as i32
+  }
+}
+
+impl MatchmakingRegistry {
+  fn find_entry(&self, player_id: i32) -> Option<(LadderMode, &QueueEntry)> {
+    self.queues.iter().find_map(|(mode, entries)| {
+      entries
+        .iter()
+        .find(|e| e.player_id == player_id)
+        .map(|e| (*mode, e))
+    })
+  }
+
+  fn remove_entry(&mut self, player_id: i32) -> Option<(LadderMode, QueueEntry)> {
+    for (mode, entries) in self.queues.iter_mut() {
+      if let Some(idx) = entries.iter().position(|e| e.player_id == player_id) {
+        return Some((*mode, entries.remove(idx)));
+      }
+    }
+    None
+  }
+
+  fn get_status(&self, player_id: i32, mode: LadderMode, rating: i32) -> QueueStatus {
+    let queue_size = self.queues.get(&mode).map(|q| q.len()).unwrap_or_default();
+    match self.find_entry(player_id) {
+      Some((mode, entry)) => QueueStatus {
+        player_id,
+        queued: true,
+        mode,
+        rating: entry.rating,
+        queue_size,
+        wait_secs: entry.joined_at.elapsed().as_secs(),
+        game_id: None,
+      },
+      None => QueueStatus {
+        player_id,
+        queued: false,
+        mode,
+        rating,
+        queue_size,
+        wait_secs: 0,
+        game_id: None,
+      },
+    }
+  }
+
+  async fn send_status(&self, status: &QueueStatus) -> Result<()> {
+    self
+      .player_packet_sender
+      .send(status.player_id, status.to_packet().encode_as_frame()?)
+      .await
+  }
+
+  async fn run_matching(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+    let now = Instant::now();
+    let modes: Vec<LadderMode> = self.queues.keys().cloned().collect();
+    for mode in modes {
+      let entries = self.queues.get(&mode).cloned().unwrap_or_default();
+      if entries.len() < mode.num_players() {
+        continue;
+      }
+
+      let maps: Vec<Map> = self
+        .maps
+        .get(&mode)
+        .map(|maps| {
+          maps
+            .iter()
+            .filter(|m| m.players.len() >= mode.num_players())
+            .cloned()
+            .collect()
+        })
+        .unwrap_or_default();
+      if maps.is_empty() {
+        tracing::debug!("no ladder map available for {:?}", mode);
+        continue;
+      }
+
+      let snapshot = self
+        .players
+        .send(GetPlayersPingSnapshot {
+          players: entries.iter().map(|e| e.player_id).collect(),
+        })
+        .await?;
+      let nodes = self.nodes.send(ListNode).await?;
+      let policy = self.policies.get(&mode);
+
+      for group in find_groups(&entries, mode.team_size(), now) {
+        let teams = split_teams(&entries, &group)?;
+        let player_ids: Vec<i32> = teams.iter().flatten().cloned().collect();
+        let decision =
+          match crate::node::policy::evaluate(policy, &nodes, &snapshot.map, &player_ids) {
+            Some(decision) => decision,
+            None => {
+              tracing::debug!("no acceptable node: {:?}", player_ids);
+              continue;
+            }
+          };
+
+        for player_id in &player_ids {
+          self.remove_entry(*player_id);
+        }
+
+        let group_entries: Vec<QueueEntry> =
+          group.iter().map(|idx| entries[*idx].clone()).collect();
+
+        // 1v1 players ban maps until one remains
+        if player_ids.len() == 2 && maps.len() > 1 {
+          let res = self
+            .map_vetos
+            .send(StartMapVeto {
+              player_ids: [player_ids[0], player_ids[1]],
+              maps: maps.clone(),
+            })
+            .await
+            .map_err(Error::from)
+            .and_then(|res| res);
+          let rx = match res {
+            Ok(rx) => rx,
+            Err(err) => {
+              tracing::error!("start map veto: {}", err);
+              self.requeue(mode, group_entries).await;
+              continue;
+            }
+          };
+          let addr = ctx.addr();
+          ctx.spawn(async move {
+            match rx.await {
+              Ok(map) => {
+                addr
+                  .notify(MapVetoDone {
+                    mode,
+                    map,
+                    decision,
+                    teams,
+                    entries: group_entries,
+                  })
+                  .await
+                  .ok();
+              }
+              Err(_) => {
+                tracing::error!("map veto cancelled: {:?}", player_ids);
+                addr
+                  .notify(MapVetoCancelled {
+                    mode,
+                    entries: group_entries,
+                  })
+                  .await
+                  .ok();
+              }
+            }
+          });
+          continue;
+        }
+
+        let map = maps
+          .choose(&mut rand::thread_rng())
+          .cloned()
+          .ok_or_else(|| Error::MapHasNoPlayer)?;
+        self
+          .start_game(mode, map, decision, &teams, &group_entries)
+          .await;
+      }
+    }
+    Ok(())
+  }
+
+  /// Puts players back into the queue after their match fell through
+  async fn requeue(&mut self, mode: LadderMode, entries: Vec<QueueEntry>) {
+    let entries = requeue_entries(&mut self.queues, mode, entries);
+    for entry in entries {
+      let status = self.get_status(entry.player_id, mode, entry.rating);
+      self.send_status(&status).await.ok();
+    }
+  }
+
+  async fn start_game(
+    &self,
+    mode: LadderMode,
+    map: Map,
+    decision: NodeDecision,
+    teams: &[Vec<i32>],
+    entries: &[QueueEntry],
+  ) {
+    let res = self.create_game(mode, map, decision, teams).await;
+    if let Err(ref err) = res {
+      tracing::error!("create matchmaking game: {}", err);
+    }
+    for entry in entries {
+      let mut status = self.get_status(entry.player_id, mode, entry.rating);
+      status.game_id = res.as_ref().ok().cloned();
+      self.send_status(&status).await.ok();
+    }
+  }
+
+  async fn create_game(
+    &self,
+    mode: LadderMode,
+    map: Map,
+    decision: NodeDecision,
+    teams: &[Vec<i32>],
+  ) -> Result<i32> {
+    let slots = team_slots(teams);
+
+    let game = self
+      .games
+      .send(CreateGameForPlayers {
+        params: CreateGameForPlayersParams {
+          name: format!("Ladder {:?} #{}", mode, rand::random::<u16>()),
+          map,
+          node_id: decision.node_id,
+          slots,
+          chat_rules: None,
+        },
+      })
+      .await??;
+
+    let game_id = game.id;
+    self
+      .db
+      .exec(move |conn| -> Result<_> {
+        crate::matchmaking::db::add_game(conn, game_id, mode)?;
+        crate::node::db::add_assignment(conn, game_id, Some(mode), None, &decision)
+      })
+      .await?;
+
+    Ok(game_id)
+  }
+}
+
+/// Greedily forms disjoint groups of `team_size * 2` players,
+/// the longest waiting players are matched first.
+fn find_groups(entries: &[QueueEntry], team_size: usize, now: Instant) -> Vec<Vec<usize>> {
+  let group_size = team_size * 2;
+  let mut order: Vec<usize> = (0..entries.len()).collect();
+  order.sort_by_key(|idx| entries[*idx].joined_at);
+
+  let mut used = vec![false; entries.len()];
+  let mut groups = vec![];
+  for anchor in order {
+    if used[anchor] {
+      continue;
+    }
+    let mut candidates: Vec<usize> = (0..entries.len())
+      .filter(|idx| *idx != anchor && !used[*idx])
+      .collect();
+    candidates.sort_by_key(|idx| (entries[*idx].rating - entries[anchor].rating).abs());
+
+    let mut group = vec![anchor];
+    for idx in candidates {
+      if group.len() == group_size {
+        break;
+      }
+      let mut next = group.clone();
+      next.push(idx);
+      if is_group_acceptable(entries, &next, now) {
+        group = next;
+      }
+    }
+
+    if group.len() == group_size {
+      for idx in &group {
+        used[*idx] = true;
+      }
+      groups.push(group);
+    }
+  }
+  groups
+}
+
+/// Re-inserts entries with their original join time,
+/// players that joined a queue again in the meantime are skipped.
+fn requeue_entries(
+  queues: &mut BTreeMap<LadderMode, Vec<QueueEntry>>,
+  mode: LadderMode,
+  entries: Vec<QueueEntry>,
+) -> Vec<QueueEntry> {
+  let entries: Vec<QueueEntry> = entries
+    .into_iter()
+    .filter(|entry| {
+      !queues
+        .values()
+        .any(|q| q.iter().any(|e| e.player_id == entry.player_id))
+    })
+    .collect();
+  let queue = queues.entry(mode).or_default();
+  queue.extend(entries.iter().cloned());
+  queue.sort_by_key(|e| e.joined_at);
+  entries
+}
+
+fn is_group_acceptable(entries: &[QueueEntry], group: &[usize], now: Instant) -> bool {
+  let ratings = group.iter().map(|idx| entries[*idx].rating);
+  let spread = ratings.clone().max().unwrap_or_default() - ratings.min().unwrap_or_default();
+  group
+    .iter()
+    .all(|idx| spread <= entries[*idx].rating_window(now))
+}
+
+/// Splits a group into two teams with the closest total ratings
+fn split_teams(entries: &[QueueEntry], group: &[usize]) -> Result<Vec<Vec<i32>>> {
+  let players: Vec<(i32, i32)> = group
+    .iter()
+    .map(|idx| (entries[*idx].player_id, entries[*idx].rating))
+    .collect();
+  balance_teams(&players, &[], 2)
+}
+
+#[async_trait]
+impl Actor for MatchmakingRegistry {
+  async fn started(&mut self, ctx: &mut Context<Self>) {
+    self.handle(ctx, MatchTick).await;
+  }
+}
+
+#[async_trait]
+impl Service<Data> for MatchmakingRegistry {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    let db = registry.data().db.clone();
+    let games = registry.resolve::<GameRegistry>().await?;
+    let players = registry.resolve::<PlayerRegistry>().await?;
+    let map_vetos = registry.resolve::<MapVetoRegistry>().await?;
+    let nodes = registry.resolve::<NodeRegistry>().await?;
+    let maps = db
+      .exec(|conn| crate::matchmaking::db::get_ladder_maps(conn))
+      .await?;
+    let policies = db.exec(|conn| crate::node::db::get_policies(conn)).await?;
+    Ok(MatchmakingRegistry {
+      db,
+      games,
+      player_packet_sender: players.clone().into(),
+      players,
+      map_vetos,
+      nodes,
+      maps,
+      policies,
+      queues: BTreeMap::new(),
+    })
+  }
+}
+
+#[async_trait]
+impl Handler<Reload> for MatchmakingRegistry {
+  async fn handle(&mut self, _: &mut Context<Self>, _: Reload) -> <Reload as Message>::Result {
+    self.maps = self
+      .db
+      .exec(|conn| crate::matchmaking::db::get_ladder_maps(conn))
+      .await?;
+    self.policies = self
+      .db
+      .exec(|conn| crate::node::db::get_policies(conn))
+      .await?;
+    Ok(())
+  }
+}
+
+pub struct JoinQueue {
+  pub player_id: i32,
+  pub mode: LadderMode,
+}
+
+impl Message for JoinQueue {
+  type Result = Result<QueueStatus>;
+}
+
+#[async_trait]
+impl Handler<JoinQueue> for MatchmakingRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    JoinQueue { player_id, mode }: JoinQueue,
+  ) -> <JoinQueue as Message>::Result {
+    let rating = self
+      .db
+      .exec(move |conn| -> Result<_> {
+        if !crate::game::db::get_player_active_slots(conn, player_id)?.is_empty() {
+          return Err(Error::PlayerAlreadyInGame);
+        }
+        crate::penalty::db::check_queue_penalty(conn, &[player_id])?;
+        crate::matchmaking::db::get_rating_value(conn, player_id, mode)
+      })
+      .await?;
+
+    self.remove_entry(player_id);
+    self.queues.entry(mode).or_default().push(QueueEntry {
+      player_id,
+      rating,
+      joined_at: Instant::now(),
+    });
+
+    let status = self.get_status(player_id, mode, rating);
+    self.send_status(&status).await?;
+    Ok(status)
+  }
+}
+
+pub struct LeaveQueue {
+  pub player_id: i32,
+}
+
+impl Message for LeaveQueue {
+  type Result = Result<Option<QueueStatus>>;
+}
+
+#[async_trait]
+impl Handler<LeaveQueue> for MatchmakingRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LeaveQueue { player_id }: LeaveQueue,
+  ) -> <LeaveQueue as Message>::Result {
+    if let Some((mode, entry)) = self.remove_entry(player_id) {
+      let status = self.get_status(player_id, mode, entry.rating);
+      self.send_status(&status).await.ok();
+      Ok(Some(status))
+    } else {
+      Ok(None)
+    }
+  }
+}
+
+pub struct GetQueueStatus {
+  pub player_id: i32,
+}
+
+impl Message for GetQueueStatus {
+  type Result = Option<QueueStatus>;
+}
+
+#[async_trait]
+impl Handler<GetQueueStatus> for MatchmakingRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetQueueStatus { player_id }: GetQueueStatus,
+  ) -> <GetQueueStatus as Message>::Result {
+    self
+      .find_entry(player_id)
+      .map(|(mode, entry)| (mode, entry.rating))
+      .map(|(mode, rating)| self.get_status(player_id, mode, rating))
+  }
+}
+
+struct MatchTick;
+
+impl Message for MatchTick {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<MatchTick> for MatchmakingRegistry {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: MatchTick) {
+    if let Err(err) = self.run_matching(ctx).await {
+      tracing::error!("matchmaking: {}", err);
+    }
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(MATCH_INTERVAL).await;
+      addr.notify(MatchTick).await.ok();
+    });
+  }
+}
+
+struct MapVetoDone {
+  mode: LadderMode,
+  map: Map,
+  decision: NodeDecision,
+  teams: Vec<Vec<i32>>,
+  entries: Vec<QueueEntry>,
+}
+
+impl Message for MapVetoDone {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<MapVetoDone> for MatchmakingRegistry {
+  async fn handle(&mut self, _: &mut Context<Self>, message: MapVetoDone) {
+    self
+      .start_game(
+        message.mode,
+        message.map,
+        message.decision,
+        &message.teams,
+        &message.entries,
+      )
+      .await;
+  }
+}
+
+struct MapVetoCancelled {
+  mode: LadderMode,
+  entries: Vec<QueueEntry>,
+}
+
+impl Message for MapVetoCancelled {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<MapVetoCancelled> for MatchmakingRegistry {
+  async fn handle(&mut self, _: &mut Context<Self>, message: MapVetoCancelled) {
+    self.requeue(message.mode, message.entries).await;
+  }
+}
+
+#[cfg(test)]
+fn entry(player_id: i32, rating: i32, waited_secs: u64, now: Instant) -> QueueEntry {
+  QueueEntry {
+    player_id,
+    rating,
+    joined_at: now - Duration::from_secs(waited_secs),
+  }
+}
+
+#[test]
+fn test_find_groups_widening_window() {
+  let now = Instant::now();
+  let entries = vec![entry(1, 1500, 0, now), entry(2, 1700, 0, now)];
+  assert!(find_groups(&entries, 1, now).is_empty());
+
+  let entries = vec![entry(1, 1500, 40, now), entry(2, 1700, 40, now)];
+  assert_eq!(find_groups(&entries, 1, now), vec![vec![0, 1]]);
+
+  let entries = vec![
+    entry(1, 1500, 60, now),
+    entry(2, 2000, 0, now),
+    entry(3, 1520, 0, now),
+    entry(4, 1490, 0, now),
+  ];
+  assert_eq!(find_groups(&entries, 1, now), vec![vec![0, 3]]);
+  assert_eq!(find_groups(&entries, 2, now), vec![]);
+}
+
+#[test]
+fn test_split_teams() {
+  let now = Instant::now();
+  let entries = vec![
+    entry(1, 1500, 0, now),
+    entry(2, 1800, 0, now),
+    entry(3, 1600, 0, now),
+    entry(4, 1700, 0, now),
+  ];
+  assert_eq!(
+    split_teams(&entries, &[0, 1, 2, 3]).unwrap(),
+    vec![vec![2, 1], vec![4, 3]]
+  );
+}
+
+#[test]
+fn test_requeue_entries_after_cancelled_veto() {
+  let now = Instant::now();
+  let mut queues = BTreeMap::new();
+  queues.insert(LadderMode::Solo, vec![entry(3, 1500, 10, now)]);
+  queues.insert(LadderMode::Team2v2, vec![entry(2, 1500, 0, now)]);
+
+  let requeued = requeue_entries(
+    &mut queues,
+    LadderMode::Solo,
+    vec![entry(1, 1600, 30, now), entry(2, 1550, 30, now)],
+  );
+
+  assert_eq!(
+    requeued.iter().map(|e| e.player_id).collect::<Vec<_>>(),
+    vec![1]
+  );
+  assert_eq!(
+    queues[&LadderMode::Solo]
+      .iter()
+      .map(|e| e.player_id)
+      .collect::<Vec<_>>(),
+    vec![1, 3]
+  );
+  assert_eq!(queues[&LadderMode::Team2v2].len(), 1);
+}
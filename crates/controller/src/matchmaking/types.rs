@@ -0,0 +1,136 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{player_rating, player_rating_history};
+
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  BSDieselEnum,
+  S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(
+  flo_grpc::controller::LadderMode,
+  flo_net::proto::flo_connect::LadderMode
+))]
+pub enum LadderMode {
+  Solo = 0,
+  Team2v2 = 1,
+}
+
+impl LadderMode {
+  pub const ALL: &'static [LadderMode] = &[LadderMode::Solo, LadderMode::Team2v2];
+
+  pub fn team_size(&self) -> usize {
+    match *self {
+      LadderMode::Solo => 1,
+      LadderMode::Team2v2 => 2,
+    }
+  }
+
+  pub fn num_players(&self) -> usize {
+    self.team_size() * 2
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone, Queryable)]
+#[s2_grpc(message_type(flo_grpc::controller::PlayerRating))]
+pub struct PlayerRating {
+  pub season_id: i32,
+  pub player_id: i32,
+  #[s2_grpc(proto_enum)]
+  pub mode: LadderMode,
+  pub rating: i32,
+  pub wins: i32,
+  pub losses: i32,
+  pub updated_at: DateTime<Utc>,
+}
+
+pub(crate) type PlayerRatingColumns = (
+  player_rating::dsl::season_id,
+  player_rating::dsl::player_id,
+  player_rating::dsl::mode,
+  player_rating::dsl::rating,
+  player_rating::dsl::wins,
+  player_rating::dsl::losses,
+  player_rating::dsl::updated_at,
+);
+
+impl PlayerRating {
+  pub(crate) const COLUMNS: PlayerRatingColumns = (
+    player_rating::dsl::season_id,
+    player_rating::dsl::player_id,
+    player_rating::dsl::mode,
+    player_rating::dsl::rating,
+    player_rating::dsl::wins,
+    player_rating::dsl::losses,
+    player_rating::dsl::updated_at,
+  );
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone, Queryable)]
+#[s2_grpc(message_type(flo_grpc::controller::PlayerRatingChange))]
+pub struct PlayerRatingChange {
+  pub season_id: i32,
+  pub game_id: i32,
+  #[s2_grpc(proto_enum)]
+  pub mode: LadderMode,
+  pub rating_before: i32,
+  pub rating_after: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type PlayerRatingChangeColumns = (
+  player_rating_history::dsl::season_id,
+  player_rating_history::dsl::game_id,
+  player_rating_history::dsl::mode,
+  player_rating_history::dsl::rating_before,
+  player_rating_history::dsl::rating_after,
+  player_rating_history::dsl::created_at,
+);
+
+impl PlayerRatingChange {
+  pub(crate) const COLUMNS: PlayerRatingChangeColumns = (
+    player_rating_history::dsl::season_id,
+    player_rating_history::dsl::game_id,
+    player_rating_history::dsl::mode,
+    player_rating_history::dsl::rating_before,
+    player_rating_history::dsl::rating_after,
+    player_rating_history::dsl::created_at,
+  );
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueStatus {
+  pub player_id: i32,
+  pub queued: bool,
+  pub mode: LadderMode,
+  pub rating: i32,
+  pub queue_size: usize,
+  pub wait_secs: u64,
+  pub game_id: Option<i32>,
+}
+
+impl QueueStatus {
+  pub fn to_packet(&self) -> flo_net::proto::flo_connect::PacketMatchmakingQueueStatus {
+    let mode: flo_net::proto::flo_connect::LadderMode = self.mode.into_proto_enum();
+    flo_net::proto::flo_connect::PacketMatchmakingQueueStatus {
+      queued: self.queued,
+      mode: mode.into(),
+      rating: self.rating,
+      queue_size: self.queue_size as i32,
+      wait_secs: self.wait_secs as i32,
+      game_id: self.game_id,
+    }
+  }
+}
@@ -0,0 +1,147 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use once_cell::sync::Lazy;
+use prometheus::{
+  register_int_counter, register_int_gauge, Encoder, IntCounter, IntGauge, TextEncoder,
+};
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use utoipa::OpenApi;
+
+use crate::error::*;
+
+pub static GAMES: Lazy<IntGauge> =
+  Lazy::new(|| register_int_gauge!("flocontroller_games", "Number of active games").unwrap());
+pub static PLAYER_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+  register_int_gauge!(
+    "flocontroller_player_connections",
+    "Number of connected players"
+  )
+  .unwrap()
+});
+pub static PLAYER_RECONNECTS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flocontroller_player_reconnects_total",
+    "Number of player connections replacing an existing one"
+  )
+  .unwrap()
+});
+pub static DECODE_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flocontroller_decode_errors_total",
+    "Number of player connections closed for sending malformed packets"
+  )
+  .unwrap()
+});
+
+#[derive(OpenApi)]
+#[openapi(paths(get_metrics, get_openapi))]
+pub struct ApiDoc;
+
+/// Serves the routes of `ApiDoc`, other paths respond with 404
+pub async fn serve() -> Result<()> {
+  async fn serve_req(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    Ok(route(&req))
+  }
+
+  let addr = SocketAddr::from(SocketAddrV4::new(
+    Ipv4Addr::UNSPECIFIED,
+    flo_constants::CONTROLLER_HTTP_PORT,
+  ));
+  tracing::info!("metrics listening on port {}", addr.port());
+
+  Server::bind(&addr)
+    .serve(make_service_fn(|_| async {
+      Ok::<_, Infallible>(service_fn(serve_req))
+    }))
+    .await?;
+  Ok(())
+}
+
+fn route<B>(req: &Request<B>) -> Response<Body> {
+  match (req.method(), req.uri().path()) {
+    (&Method::GET, "/metrics") => get_metrics(),
+    (&Method::GET, "/openapi.json") => get_openapi(),
+    (_, "/metrics") | (_, "/openapi.json") => empty(StatusCode::METHOD_NOT_ALLOWED),
+    _ => empty(StatusCode::NOT_FOUND),
+  }
+}
+
+/// Prometheus metrics of the controller
+#[utoipa::path(
+  get,
+  path = "/metrics",
+  responses(
+    (
+      status = 200,
+      description = "Metrics in the Prometheus text format",
+      body = String,
+      content_type = "text/plain; version=0.0.4"
+    ),
+  )
+)]
+fn get_metrics() -> Response<Body> {
+  let encoder = TextEncoder::new();
+  let mut buffer = vec![];
+  encoder.encode(&prometheus::gather(), &mut buffer).unwrap();
+
+  Response::builder()
+    .status(200)
+    .header(CONTENT_TYPE, encoder.format_type())
+    .body(Body::from(buffer))
+    .unwrap()
+}
+
+/// OpenAPI document of this server
+#[utoipa::path(
+  get,
+  path = "/openapi.json",
+  responses(
+    (
+      status = 200,
+      description = "OpenAPI 3 document",
+      body = Object,
+      content_type = "application/json"
+    ),
+  )
+)]
+fn get_openapi() -> Response<Body> {
+  match ApiDoc::openapi().to_json() {
+    Ok(json) => Response::builder()
+      .status(200)
+      .header(CONTENT_TYPE, "application/json")
+      .body(Body::from(json))
+      .unwrap(),
+    Err(err) => {
+      tracing::error!("serialize openapi: {}", err);
+      empty(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+  }
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+  Response::builder()
+    .status(status)
+    .body(Body::empty())
+    .unwrap()
+}
+
+#[test]
+fn test_route() {
+  let spec = ApiDoc::openapi();
+  let paths: Vec<_> = spec.paths.paths.keys().cloned().collect();
+  assert_eq!(paths, vec!["/metrics", "/openapi.json"]);
+
+  for path in &paths {
+    let req = Request::get(path.as_str()).body(()).unwrap();
+    assert_eq!(route(&req).status(), StatusCode::OK);
+    let req = Request::post(path.as_str()).body(()).unwrap();
+    assert_eq!(route(&req).status(), StatusCode::METHOD_NOT_ALLOWED);
+  }
+
+  for path in &["/", "/metrics/", "/unknown"] {
+    let req = Request::get(*path).body(()).unwrap();
+    assert_eq!(route(&req).status(), StatusCode::NOT_FOUND);
+  }
+}
@@ -1,9 +1,13 @@
 use diesel::prelude::*;
+use serde_json::Value;
+use std::collections::BTreeMap;
 
 use crate::db::DbConn;
 use crate::error::*;
+use crate::matchmaking::LadderMode;
+use crate::node::policy::{NodeAssignmentSource, NodeDecision, NodePolicy};
 use crate::node::types::Node;
-use crate::schema::node;
+use crate::schema::{node, node_assignment, node_policy};
 
 pub fn get_all_nodes(conn: &DbConn) -> Result<Vec<Node>> {
   use node::dsl;
@@ -22,3 +26,61 @@ pub fn get_node(conn: &DbConn, node_id: i32) -> Result<Node> {
     .ok_or_else(|| Error::NodeNotFound)
     .map_err(Into::into)
 }
+
+pub fn get_policies(conn: &DbConn) -> Result<BTreeMap<LadderMode, NodePolicy>> {
+  let rows: Vec<(LadderMode, Value, Option<i32>, Value)> = node_policy::table
+    .select((
+      node_policy::mode,
+      node_policy::allowed_regions,
+      node_policy::max_worst_ping,
+      node_policy::preferred_providers,
+    ))
+    .load(conn)?;
+  let mut map = BTreeMap::new();
+  for (mode, allowed_regions, max_worst_ping, preferred_providers) in rows {
+    map.insert(
+      mode,
+      NodePolicy {
+        mode,
+        allowed_regions: serde_json::from_value(allowed_regions)?,
+        max_worst_ping: max_worst_ping.map(|v| v as u32),
+        preferred_providers: serde_json::from_value(preferred_providers)?,
+      },
+    );
+  }
+  Ok(map)
+}
+
+pub fn add_assignment(
+  conn: &DbConn,
+  game_id: i32,
+  mode: Option<LadderMode>,
+  tournament_id: Option<i32>,
+  decision: &NodeDecision,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "node_assignment"]
+  struct Insert {
+    game_id: i32,
+    node_id: i32,
+    source: NodeAssignmentSource,
+    mode: Option<LadderMode>,
+    tournament_id: Option<i32>,
+    worst_ping: Option<i32>,
+    candidates: Value,
+  }
+
+  diesel::insert_into(node_assignment::table)
+    .values(&Insert {
+      game_id,
+      node_id: decision.node_id,
+      source: decision.source,
+      mode,
+      tournament_id,
+      worst_ping: decision.worst_ping.map(|v| v as i32),
+      candidates: serde_json::to_value(&decision.candidates)?,
+    })
+    .execute(conn)?;
+
+  Ok(())
+}
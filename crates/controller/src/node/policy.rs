@@ -0,0 +1,269 @@
+use bs_diesel_utils::BSDieselEnum;
+use flo_types::ping::PingStats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::matchmaking::LadderMode;
+use crate::node::Node;
+
+/// Node selection rules of a ladder mode
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NodePolicy {
+  pub mode: LadderMode,
+  /// Empty means every region is allowed
+  pub allowed_regions: Vec<String>,
+  pub max_worst_ping: Option<u32>,
+  /// Ordered by preference
+  pub preferred_providers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[repr(i32)]
+pub enum NodeAssignmentSource {
+  Policy = 0,
+  /// Selected by the organizer
+  Pinned = 1,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRejectReason {
+  /// At least one player has no ping to the node
+  Unreachable,
+  RegionNotAllowed,
+  PingTooHigh,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NodeCandidate {
+  pub node_id: i32,
+  pub worst_ping: Option<u32>,
+  pub preferred: bool,
+  pub rejected: Option<NodeRejectReason>,
+}
+
+/// The selected node and the evaluated candidates, recorded for audit
+#[derive(Debug, Clone)]
+pub struct NodeDecision {
+  pub node_id: i32,
+  pub source: NodeAssignmentSource,
+  pub worst_ping: Option<u32>,
+  pub candidates: Vec<NodeCandidate>,
+}
+
+impl NodeDecision {
+  pub fn pinned(node_id: i32) -> Self {
+    NodeDecision {
+      node_id,
+      source: NodeAssignmentSource::Pinned,
+      worst_ping: None,
+      candidates: vec![],
+    }
+  }
+}
+
+/// Selects a node for the players.
+/// Preferred providers win among acceptable nodes, ties are broken by the lowest worst-case ping.
+pub fn evaluate(
+  policy: Option<&NodePolicy>,
+  nodes: &[Node],
+  snapshot: &BTreeMap<i32, BTreeMap<i32, PingStats>>,
+  player_ids: &[i32],
+) -> Option<NodeDecision> {
+  let worst_pings = get_worst_pings(snapshot, player_ids);
+
+  let candidates: Vec<NodeCandidate> = nodes
+    .iter()
+    .map(|node| {
+      let worst_ping = worst_pings.get(&node.id).cloned();
+      let rejected = match (worst_ping, policy) {
+        (None, _) => Some(NodeRejectReason::Unreachable),
+        (Some(ping), Some(policy)) => {
+          if !policy.allowed_regions.is_empty() && !policy.allowed_regions.contains(&node.region) {
+            Some(NodeRejectReason::RegionNotAllowed)
+          } else if policy.max_worst_ping.map(|max| ping > max).unwrap_or(false) {
+            Some(NodeRejectReason::PingTooHigh)
+          } else {
+            None
+          }
+        }
+        (Some(_), None) => None,
+      };
+      NodeCandidate {
+        node_id: node.id,
+        worst_ping,
+        preferred: policy
+          .map(|p| p.preferred_providers.contains(&node.provider))
+          .unwrap_or_default(),
+        rejected,
+      }
+    })
+    .collect();
+
+  let selected = nodes
+    .iter()
+    .zip(candidates.iter())
+    .filter(|(_, candidate)| candidate.rejected.is_none())
+    .min_by_key(|(node, candidate)| {
+      let rank = policy
+        .and_then(|p| {
+          p.preferred_providers
+            .iter()
+            .position(|provider| provider == &node.provider)
+        })
+        .unwrap_or(usize::MAX);
+      (rank, candidate.worst_ping)
+    })
+    .map(|(_, candidate)| (candidate.node_id, candidate.worst_ping))?;
+
+  Some(NodeDecision {
+    node_id: selected.0,
+    source: NodeAssignmentSource::Policy,
+    worst_ping: selected.1,
+    candidates,
+  })
+}
+
+/// Worst-case ping of nodes reachable by all players
+fn get_worst_pings(
+  snapshot: &BTreeMap<i32, BTreeMap<i32, PingStats>>,
+  player_ids: &[i32],
+) -> BTreeMap<i32, u32> {
+  let mut worst_ping_map: BTreeMap<i32, u32> = BTreeMap::new();
+  for (idx, player_id) in player_ids.iter().enumerate() {
+    let pings: BTreeMap<i32, u32> = match snapshot.get(player_id) {
+      Some(stats) => stats
+        .iter()
+        .filter_map(|(node_id, stats)| stats.avg.or(stats.current).map(|v| (*node_id, v)))
+        .collect(),
+      None => return BTreeMap::new(),
+    };
+    if idx == 0 {
+      worst_ping_map = pings;
+    } else {
+      worst_ping_map = worst_ping_map
+        .into_iter()
+        .filter_map(|(node_id, worst)| {
+          pings
+            .get(&node_id)
+            .map(|ping| (node_id, std::cmp::max(worst, *ping)))
+        })
+        .collect();
+    }
+  }
+  worst_ping_map
+}
+
+#[cfg(test)]
+fn test_node(id: i32, region: &str, provider: &str) -> Node {
+  use chrono::Utc;
+  Node {
+    id,
+    name: format!("node{}", id),
+    location: String::new(),
+    secret: String::new(),
+    ip_addr: String::new(),
+    created_at: Utc::now(),
+    updated_at: Utc::now(),
+    country_id: String::new(),
+    disabled: false,
+    region: region.to_string(),
+    provider: provider.to_string(),
+  }
+}
+
+#[cfg(test)]
+fn test_snapshot() -> BTreeMap<i32, BTreeMap<i32, PingStats>> {
+  fn stats(avg: u32) -> PingStats {
+    PingStats {
+      min: None,
+      max: None,
+      avg: Some(avg),
+      current: None,
+      loss_rate: 0.0,
+    }
+  }
+  let mut snapshot = BTreeMap::new();
+  snapshot.insert(
+    1,
+    vec![(10, stats(30)), (20, stats(80)), (30, stats(60))]
+      .into_iter()
+      .collect(),
+  );
+  snapshot.insert(
+    2,
+    vec![(10, stats(150)), (20, stats(90)), (30, stats(100))]
+      .into_iter()
+      .collect(),
+  );
+  snapshot
+}
+
+#[test]
+fn test_evaluate_without_policy() {
+  let nodes = vec![
+    test_node(10, "eu", "a"),
+    test_node(20, "eu", "b"),
+    test_node(30, "na", "b"),
+    test_node(40, "na", "b"),
+  ];
+  let snapshot = test_snapshot();
+
+  let decision = evaluate(None, &nodes, &snapshot, &[1, 2]).unwrap();
+  assert_eq!(decision.node_id, 20);
+  assert_eq!(decision.worst_ping, Some(90));
+  assert_eq!(decision.source, NodeAssignmentSource::Policy);
+  assert_eq!(
+    decision.candidates[3].rejected,
+    Some(NodeRejectReason::Unreachable)
+  );
+
+  assert_eq!(
+    evaluate(None, &nodes, &snapshot, &[1]).map(|d| d.node_id),
+    Some(10)
+  );
+  assert!(evaluate(None, &nodes, &snapshot, &[1, 3]).is_none());
+}
+
+#[test]
+fn test_evaluate_policy() {
+  let nodes = vec![
+    test_node(10, "eu", "a"),
+    test_node(20, "eu", "b"),
+    test_node(30, "na", "b"),
+  ];
+  let snapshot = test_snapshot();
+  let mut policy = NodePolicy {
+    mode: LadderMode::Solo,
+    allowed_regions: vec!["na".to_string()],
+    max_worst_ping: None,
+    preferred_providers: vec![],
+  };
+
+  let decision = evaluate(Some(&policy), &nodes, &snapshot, &[1, 2]).unwrap();
+  assert_eq!(decision.node_id, 30);
+  assert_eq!(
+    decision.candidates[1].rejected,
+    Some(NodeRejectReason::RegionNotAllowed)
+  );
+
+  policy.max_worst_ping = Some(95);
+  assert!(evaluate(Some(&policy), &nodes, &snapshot, &[1, 2]).is_none());
+
+  policy.allowed_regions = vec![];
+  policy.max_worst_ping = Some(120);
+  policy.preferred_providers = vec!["a".to_string(), "b".to_string()];
+  let decision = evaluate(Some(&policy), &nodes, &snapshot, &[1, 2]).unwrap();
+  assert_eq!(decision.node_id, 20);
+  assert_eq!(
+    decision.candidates[0].rejected,
+    Some(NodeRejectReason::PingTooHigh)
+  );
+  assert!(decision.candidates[1].preferred);
+
+  policy.max_worst_ping = None;
+  assert_eq!(
+    evaluate(Some(&policy), &nodes, &snapshot, &[1, 2]).map(|d| d.node_id),
+    Some(10)
+  );
+}
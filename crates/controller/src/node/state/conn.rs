@@ -1,12 +1,18 @@
 use crate::error::*;
+use crate::game::result::GameResultReport;
+use crate::game::state::registry::GetGameNode;
+use crate::game::state::replay::GameLogReceived;
 use crate::game::state::GameRegistry;
 use crate::game::state::{GameSlotClientStatusUpdate, GameStatusUpdate};
 use crate::game::{Game, GameStatus};
 use crate::node::state::request::{CreatedGameInfo, NodeRequestActor, NodeRequestExt};
+use crate::node::state::ConnectedNodes;
 use crate::node::{NodeConnConfig, PlayerLeaveResponse};
 use crate::state::ActorMapExt;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
+use bytes::BytesMut;
+use flo_net::auth::{FrameAuth, Peer};
 use flo_net::packet::*;
 use flo_net::proto::flo_node::*;
 use flo_net::stream::FloStream;
@@ -22,27 +28,39 @@ use futures::StreamExt;
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::time::sleep;
+use tokio::time::{interval_at, sleep, Instant};
 use tracing_futures::Instrument;
 
 const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const KEY_ROTATE_INTERVAL: Duration = Duration::from_secs(15 * 60);
 
 pub struct NodeConnActor {
   config: NodeConnConfig,
   reconnect_backoff: Option<ExponentialBackoff>,
   status: NodeConnStatus,
   request_actor: Option<Owner<NodeRequestActor>>,
+  frame_tx: Option<mpsc::Sender<Frame>>,
   game_reg_addr: Addr<GameRegistry>,
+  connected: ConnectedNodes,
+  // game logs being received from the node
+  game_logs: BTreeMap<i32, BytesMut>,
 }
 
 impl NodeConnActor {
-  pub fn new(config: NodeConnConfig, game_reg_addr: Addr<GameRegistry>) -> Self {
+  pub fn new(
+    config: NodeConnConfig,
+    game_reg_addr: Addr<GameRegistry>,
+    connected: ConnectedNodes,
+  ) -> Self {
     Self {
       config,
       status: NodeConnStatus::Connecting,
       reconnect_backoff: None,
       request_actor: None,
+      frame_tx: None,
       game_reg_addr,
+      connected,
+      game_logs: BTreeMap::new(),
     }
   }
 
@@ -64,26 +82,11 @@ impl Actor for NodeConnActor {
   }
 }
 
-enum NodeConnectError {
-  Retry(Error),
-  Fatal(Error),
-}
-
-impl From<Error> for NodeConnectError {
-  fn from(err: Error) -> Self {
-    NodeConnectError::Retry(err)
-  }
-}
-
-impl From<flo_net::error::Error> for NodeConnectError {
-  fn from(err: flo_net::error::Error) -> Self {
-    NodeConnectError::Retry(err.into())
-  }
-}
-
 impl NodeConnActor {
   fn schedule_reconnect(&mut self, ctx: &mut Context<Self>) {
     self.request_actor.take();
+    self.frame_tx.take();
+    self.connected.remove(self.config.id);
 
     let delay = self
       .reconnect_backoff
@@ -103,47 +106,57 @@ impl NodeConnActor {
     ip: Ipv4Addr,
     port: u16,
     secret: &str,
-  ) -> Result<FloStream, NodeConnectError> {
+  ) -> Result<(FloStream, FrameAuth)> {
     let addr = SocketAddrV4::new(ip, port);
     let mut stream = FloStream::connect(addr).await?;
 
+    let nonce = flo_net::auth::gen_nonce();
     stream
       .send(PacketControllerConnect {
         lobby_version: Some(crate::version::FLO_LOBBY_VERSION.into()),
         secret: secret.to_string(),
+        nonce: nonce.clone(),
       })
       .await?;
 
     let res = stream.recv_frame().await?;
 
-    flo_net::try_flo_packet! {
+    let auth = flo_net::try_flo_packet! {
       res => {
         packet: PacketControllerConnectAccept => {
           tracing::info!(node_id, "node connected: version = {:?}", packet.version);
+          // nodes without frame authentication
+          FrameAuth::new(Peer::Controller, secret, &nonce, &packet.nonce)?
         }
         packet: PacketControllerConnectReject => {
           tracing::error!(node_id, "node connect rejected: reason = {:?}", packet.reason());
-          return Err(NodeConnectError::Fatal(Error::NodeConnectionRejected {
+          return Err(Error::NodeConnectionRejected {
             addr,
             reason: packet.reason(),
-          }))
+          })
         }
       }
     };
 
-    Ok(stream)
+    Ok((stream, auth))
   }
 
-  async fn stream_worker(addr: Addr<Self>, mut rx: mpsc::Receiver<Frame>, mut stream: FloStream) {
+  async fn stream_worker(
+    addr: Addr<Self>,
+    mut rx: mpsc::Receiver<Frame>,
+    mut stream: FloStream,
+    mut auth: FrameAuth,
+  ) {
     let mut ping = PingStream::interval(Duration::from_secs(30), Duration::from_secs(10));
     ping.start();
+    let mut rotate_key = interval_at(Instant::now() + KEY_ROTATE_INTERVAL, KEY_ROTATE_INTERVAL);
 
     loop {
       tokio::select! {
         Some(msg) = ping.next() => {
           match msg {
             PingMsg::Ping(frame) => {
-              if let Err(err) = stream.send_frame(frame).await {
+              if let Err(err) = Self::send_signed(&mut stream, &mut auth, frame).await {
                 tracing::error!("send: {}", err);
                 addr.send(Disconnected).await.ok();
                 break;
@@ -157,14 +170,25 @@ impl NodeConnActor {
           }
         }
         Some(frame) = rx.recv() => {
-          if let Err(err) = stream.send_frame(frame).await {
+          if let Err(err) = Self::send_signed(&mut stream, &mut auth, frame).await {
             tracing::error!("send: {}", err);
             addr.send(Disconnected).await.ok();
             break;
           }
         }
+        _ = rotate_key.tick() => {
+          let res = match auth.rotate() {
+            Ok(frame) => stream.send_frame(frame).await,
+            Err(err) => Err(err),
+          };
+          if let Err(err) = res {
+            tracing::error!("rotate key: {}", err);
+            addr.send(Disconnected).await.ok();
+            break;
+          }
+        }
         res = stream.recv_frame() => {
-          match res {
+          match res.and_then(|frame| auth.verify(frame)) {
             Ok(frame) => {
               if frame.type_id == PacketTypeId::Pong {
                 ping.capture_pong(frame);
@@ -193,6 +217,14 @@ impl NodeConnActor {
       }
     }
   }
+
+  async fn send_signed(
+    stream: &mut FloStream,
+    auth: &mut FrameAuth,
+    frame: Frame,
+  ) -> Result<(), flo_net::error::Error> {
+    stream.send_frame(auth.sign(frame)?).await
+  }
 }
 
 struct Connect;
@@ -219,14 +251,14 @@ impl Handler<Connect> for NodeConnActor {
     };
     let node_id = self.config.id;
     let secret = self.config.secret.clone();
-    let stream = match Self::connect(node_id, ip, port, &secret).await {
-      Ok(stream) => stream,
-      Err(NodeConnectError::Retry(err)) => {
+    let (stream, auth) = match Self::connect(node_id, ip, port, &secret).await {
+      Ok(pair) => pair,
+      Err(err) if err.code().is_retryable() => {
         tracing::error!(node_id, "error: {}", err);
         self.schedule_reconnect(ctx);
         return;
       }
-      Err(NodeConnectError::Fatal(err)) => {
+      Err(err) => {
         self.status = NodeConnStatus::Error;
         tracing::error!(node_id, "fatal error: {}", err);
         return;
@@ -234,10 +266,12 @@ impl Handler<Connect> for NodeConnActor {
     };
     let (tx, rx) = mpsc::channel(32);
     ctx.spawn(
-      Self::stream_worker(ctx.addr(), rx, stream)
+      Self::stream_worker(ctx.addr(), rx, stream, auth)
         .instrument(tracing::debug_span!("stream_worker", node_id)),
     );
-    self.request_actor = NodeRequestActor::new(tx).start().into();
+    self.request_actor = NodeRequestActor::new(tx.clone()).start().into();
+    self.frame_tx = Some(tx);
+    self.connected.insert(node_id);
     self.reconnect_backoff.take();
   }
 }
@@ -251,6 +285,7 @@ impl Message for Disconnected {
 #[async_trait]
 impl Handler<Disconnected> for NodeConnActor {
   async fn handle(&mut self, ctx: &mut Context<Self>, _: Disconnected) {
+    self.game_logs.clear();
     self.schedule_reconnect(ctx);
   }
 }
@@ -274,6 +309,9 @@ impl Handler<IncomingFrame> for NodeConnActor {
       Response(RequestDone),
       GameSlotClientStatusUpdate(GameSlotClientStatusUpdate),
       GameStatusUpdate(Vec<GameStatusUpdate>),
+      GameResult(GameResultReport),
+      GameLog(PacketNodeGameLog),
+      StandbyPromote(i32),
     }
 
     let parsed = flo_net::try_flo_packet! {
@@ -322,6 +360,20 @@ impl Handler<IncomingFrame> for NodeConnActor {
             )
           )
         }
+        packet: PacketControllerAbortGameAccept => {
+          Parsed::Response(
+            RequestDone::new(RequestId::AbortGame(packet.game_id), Ok(Response::GameAborted))
+          )
+        }
+        packet: PacketControllerAbortGameReject => {
+          let game_id = packet.game_id;
+          Parsed::Response(
+            RequestDone::new(
+              RequestId::AbortGame(game_id),
+              Err(Error::GameAbortRejected(packet.reason()))
+            )
+          )
+        }
         packet: PacketClientUpdateSlotClientStatus => {
           Parsed::GameSlotClientStatusUpdate(S2ProtoUnpack::unpack(packet)?)
         }
@@ -331,6 +383,15 @@ impl Handler<IncomingFrame> for NodeConnActor {
         packet: PacketNodeGameStatusUpdateBulk => {
           Parsed::GameStatusUpdate(packet.games.into_iter().map(Into::into).collect())
         }
+        packet: PacketNodeGameResult => {
+          Parsed::GameResult(S2ProtoUnpack::unpack(packet)?)
+        }
+        packet: PacketNodeGameLog => {
+          Parsed::GameLog(packet)
+        }
+        packet: PacketNodeStandbyPromote => {
+          Parsed::StandbyPromote(packet.game_id)
+        }
       }
     };
 
@@ -350,6 +411,60 @@ impl Handler<IncomingFrame> for NodeConnActor {
           }
         });
       }
+      Parsed::GameResult(report) => {
+        // the node sends the result before the final status update,
+        // handle it inline so the game is not removed before the result is saved
+        let game_id = report.game_id;
+        if let Err(err) = self.game_reg_addr.send_to(game_id, report).await {
+          tracing::warn!(game_id, "game result discarded: {}", err);
+        }
+      }
+      Parsed::GameLog(packet) => {
+        let game_id = packet.game_id;
+        let buf = self.game_logs.entry(game_id).or_default();
+        buf.extend_from_slice(&packet.data);
+        if packet.last {
+          let log = self
+            .game_logs
+            .remove(&game_id)
+            .map(BytesMut::freeze)
+            .unwrap_or_default();
+          // sent before the final status update, same as the game result
+          if let Err(err) = self
+            .game_reg_addr
+            .send_to(game_id, GameLogReceived { log })
+            .await
+          {
+            tracing::warn!(game_id, "game log discarded: {}", err);
+          }
+        }
+      }
+      Parsed::StandbyPromote(game_id) => {
+        let game_reg_addr = self.game_reg_addr.clone();
+        let connected = self.connected.clone();
+        let node_id = self.config.id;
+        let frame_tx = self.frame_tx.clone();
+        ctx.spawn(async move {
+          let game_node_id = game_reg_addr
+            .send(GetGameNode { game_id })
+            .await
+            .ok()
+            .flatten();
+          let frame = if standby_promote_allowed(game_node_id, node_id, &connected) {
+            tracing::warn!(game_id, node_id, "standby node takes over the game");
+            PacketControllerStandbyPromoteAccept { game_id }.encode_as_frame()
+          } else {
+            PacketControllerStandbyPromoteReject { game_id }.encode_as_frame()
+          };
+          match (frame, frame_tx) {
+            (Ok(frame), Some(tx)) => {
+              tx.send(frame).await.ok();
+            }
+            (Err(err), _) => tracing::error!(game_id, "encode standby promote: {}", err),
+            (_, None) => {}
+          }
+        });
+      }
       Parsed::GameStatusUpdate(messages) => {
         let addr = self.game_reg_addr.clone();
         ctx.spawn(async move {
@@ -384,6 +499,7 @@ impl Handler<IncomingFrame> for NodeConnActor {
 pub struct NodeCreateGame {
   pub game: Game,
   pub ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+  pub traceparent: String,
 }
 
 impl Message for NodeCreateGame {
@@ -395,7 +511,11 @@ impl Handler<NodeCreateGame> for NodeConnActor {
   async fn handle(
     &mut self,
     ctx: &mut Context<Self>,
-    NodeCreateGame { game, ban_list_map }: NodeCreateGame,
+    NodeCreateGame {
+      game,
+      ban_list_map,
+      traceparent,
+    }: NodeCreateGame,
   ) -> Result<FutureReply<Result<CreatedGameInfo>>> {
     let addr = self
       .request_actor
@@ -404,7 +524,8 @@ impl Handler<NodeCreateGame> for NodeConnActor {
       .ok_or_else(|| Error::NodeNotReady)?;
     let (tx, rx) = FutureReply::channel();
     ctx.spawn(async move {
-      tx.send(addr.create_game(game, ban_list_map).await).ok();
+      tx.send(addr.create_game(game, ban_list_map, traceparent).await)
+        .ok();
     });
     Ok(rx)
   }
@@ -440,6 +561,48 @@ impl Handler<NodePlayerLeave> for NodeConnActor {
   }
 }
 
+pub struct NodeAbortGame {
+  pub game_id: i32,
+  pub reason: String,
+}
+
+impl Message for NodeAbortGame {
+  type Result = Result<FutureReply<Result<()>>>;
+}
+
+#[async_trait]
+impl Handler<NodeAbortGame> for NodeConnActor {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    NodeAbortGame { game_id, reason }: NodeAbortGame,
+  ) -> Result<FutureReply<Result<()>>> {
+    let addr = self
+      .request_actor
+      .as_ref()
+      .map(|v| v.addr())
+      .ok_or_else(|| Error::NodeNotReady)?;
+    let (tx, rx) = FutureReply::channel();
+    ctx.spawn(async move {
+      tx.send(addr.abort_game(game_id, reason).await).ok();
+    });
+    Ok(rx)
+  }
+}
+
+/// A standby node only takes over a game if the node hosting it is disconnected,
+/// the standby can lose its link to a primary node that still hosts the game
+fn standby_promote_allowed(
+  game_node_id: Option<i32>,
+  standby_node_id: i32,
+  connected: &ConnectedNodes,
+) -> bool {
+  match game_node_id {
+    Some(node_id) => node_id != standby_node_id && !connected.contains(node_id),
+    None => false,
+  }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum NodeConnStatus {
   Connecting,
@@ -470,3 +633,17 @@ fn parse_addr(addr: &str) -> Result<(Ipv4Addr, u16)> {
   };
   Ok((ip, port))
 }
+
+#[test]
+fn test_standby_promote_allowed() {
+  let connected = ConnectedNodes::default();
+  connected.insert(1);
+  connected.insert(2);
+  assert!(!standby_promote_allowed(Some(1), 2, &connected));
+  assert!(!standby_promote_allowed(None, 2, &connected));
+
+  connected.remove(1);
+  assert!(standby_promote_allowed(Some(1), 2, &connected));
+  // the game is already hosted by the standby node
+  assert!(!standby_promote_allowed(Some(2), 2, &connected));
+}
@@ -12,7 +12,8 @@ use conn::NodeConnActor;
 use flo_state::{
   async_trait, Actor, Addr, Context, Deferred, Handler, Message, Owner, RegistryRef, Service,
 };
-use std::collections::BTreeMap;
+use parking_lot::RwLock;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
 pub struct NodeRegistry {
@@ -21,6 +22,25 @@ pub struct NodeRegistry {
   player_reg_handle: PlayerRegistryHandle,
   map: BTreeMap<i32, Owner<NodeConnActor>>,
   nodes_snapshot: ArcSwap<Vec<Node>>,
+  connected: ConnectedNodes,
+}
+
+/// Ids of the nodes with an open connection, updated by the connection actors
+#[derive(Debug, Clone, Default)]
+pub struct ConnectedNodes(Arc<RwLock<BTreeSet<i32>>>);
+
+impl ConnectedNodes {
+  fn insert(&self, node_id: i32) {
+    self.0.write().insert(node_id);
+  }
+
+  fn remove(&self, node_id: i32) {
+    self.0.write().remove(&node_id);
+  }
+
+  pub fn contains(&self, node_id: i32) -> bool {
+    self.0.read().contains(&node_id)
+  }
 }
 
 #[async_trait]
@@ -36,6 +56,7 @@ impl Service<Data> for NodeRegistry {
       player_reg_handle: PlayerRegistryHandle::from(player_reg_addr),
       map: BTreeMap::new(),
       nodes_snapshot: ArcSwap::new(Arc::new(vec![])),
+      connected: ConnectedNodes::default(),
     })
   }
 }
@@ -58,7 +79,7 @@ impl NodeRegistry {
       tracing::debug!(node_id = node.id, "added");
       self.map.insert(
         node.id,
-        NodeConnActor::new(node.into(), game_reg_addr.clone()).start(),
+        NodeConnActor::new(node.into(), game_reg_addr.clone(), self.connected.clone()).start(),
       );
     }
 
@@ -103,6 +124,7 @@ impl Handler<Reload> for NodeRegistry {
       for id in self.map.keys().cloned().collect::<Vec<i32>>() {
         if !new_ids.contains(&id) {
           self.map.remove(&id);
+          self.connected.remove(id);
           broadcast_frames.push(PacketRemoveNode { node_id: id }.encode_as_frame()?);
           tracing::info!(id, "node removed");
         }
@@ -114,7 +136,12 @@ impl Handler<Reload> for NodeRegistry {
         tracing::info!(id = config.id, "node added: {}", config.addr);
         self.map.insert(
           config.id,
-          NodeConnActor::new(config, self.game_reg_addr.resolve().await?).start(),
+          NodeConnActor::new(
+            config,
+            self.game_reg_addr.resolve().await?,
+            self.connected.clone(),
+          )
+          .start(),
         );
         broadcast_frames.push(
           PacketAddNode {
@@ -54,12 +54,14 @@ impl Drop for PendingRequest {
 pub enum RequestId {
   CreateGame(i32),
   PlayerLeave(PlayerLeaveRequestId),
+  AbortGame(i32),
 }
 
 #[derive(Debug)]
 pub enum Response {
   GameCreated(CreatedGameInfo),
   PlayerLeave(PlayerLeaveResponse),
+  GameAborted,
 }
 
 #[derive(Debug, S2ProtoUnpack)]
@@ -231,8 +233,10 @@ pub trait NodeRequestExt {
     &self,
     game: Game,
     ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+    traceparent: String,
   ) -> Result<CreatedGameInfo>;
   async fn player_force_leave(&self, game_id: i32, player_id: i32) -> Result<PlayerLeaveResponse>;
+  async fn abort_game(&self, game_id: i32, reason: String) -> Result<()>;
 }
 
 #[async_trait]
@@ -241,6 +245,7 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
     &self,
     game: Game,
     mut ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+    traceparent: String,
   ) -> Result<CreatedGameInfo> {
     let game_id = game.id;
 
@@ -276,11 +281,21 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
           map_path: game.map.path.clone(),
           map_sha1: game.map.sha1.to_vec(),
           map_checksum: game.map.checksum,
+          map_twelve_p: game.map.twelve_p,
         }),
         slots,
         status: Default::default(),
         enable_ping_equalizer: game.enable_ping_equalizer,
+        is_private: game.is_private,
+        name: game.name.clone(),
+        random_seed: game.random_seed,
+        game_version: game.game_version.clone().unwrap_or_default(),
+        speed_percent: game.speed_percent.unwrap_or_default(),
+        chat_rules: game.chat_rules.clone().map(|v| v.pack()).transpose()?,
+        reserved_player_ids: game.reserved_player_ids.clone(),
+        feature_flags: game.feature_flags,
       }),
+      traceparent,
     };
 
     let req = Request {
@@ -323,4 +338,19 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
       }
     }
   }
+  async fn abort_game(&self, game_id: i32, reason: String) -> Result<()> {
+    let req = Request {
+      id: RequestId::AbortGame(game_id),
+      frame: PacketControllerAbortGame { game_id, reason }.encode_as_frame()?,
+    };
+
+    let res = self.send(req).await??;
+    match res.await? {
+      Response::GameAborted => Ok(()),
+      other => {
+        tracing::error!(game_id, "unexpected node response: {:?}", other);
+        Err(Error::NodeResponseUnexpected)
+      }
+    }
+  }
 }
@@ -20,6 +20,10 @@ pub struct Node {
   pub country_id: String,
   #[s2_grpc(skip_pack)]
   pub disabled: bool,
+  #[s2_grpc(skip_pack)]
+  pub region: String,
+  #[s2_grpc(skip_pack)]
+  pub provider: String,
 }
 
 pub type NodeRefColumns = (
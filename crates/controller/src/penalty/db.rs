@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl::sql;
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::GameStatus;
+use crate::penalty::rule::select_rule;
+use crate::penalty::{PenaltyAppealStatus, PenaltyReason, PenaltyRule, PenaltyType, PlayerPenalty};
+use crate::schema::{game, matchmaking_game, penalty_rule, player, player_penalty};
+
+/// Leaving a matchmaking game within this duration after the start is an offense
+const EARLY_LEAVE_SECS: i64 = 120;
+
+pub fn get(conn: &DbConn, id: i32) -> Result<PlayerPenalty> {
+  player_penalty::table
+    .inner_join(player::table)
+    .select(PlayerPenalty::COLUMNS)
+    .filter(player_penalty::id.eq(id))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::PenaltyNotFound)
+}
+
+pub fn list(conn: &DbConn, player_id: i32) -> Result<Vec<PlayerPenalty>> {
+  player_penalty::table
+    .inner_join(player::table)
+    .select(PlayerPenalty::COLUMNS)
+    .filter(player_penalty::player_id.eq(player_id))
+    .order(player_penalty::id.desc())
+    .load(conn)
+    .map_err(Into::into)
+}
+
+/// Records an offense if a player left a matchmaking game in `status`
+pub fn record_leave(
+  conn: &DbConn,
+  game_id: i32,
+  player_id: i32,
+  status: GameStatus,
+) -> Result<Option<PlayerPenalty>> {
+  let is_matchmaking = matchmaking_game::table
+    .find(game_id)
+    .select(matchmaking_game::game_id)
+    .first::<i32>(conn)
+    .optional()?
+    .is_some();
+  if !is_matchmaking {
+    return Ok(None);
+  }
+
+  let reason = match status {
+    GameStatus::Preparing | GameStatus::Created => PenaltyReason::Dodge,
+    GameStatus::Running | GameStatus::Paused => {
+      let started_at: Option<DateTime<Utc>> = game::table
+        .find(game_id)
+        .select(game::started_at)
+        .first(conn)?;
+      match started_at {
+        Some(t) if (Utc::now() - t).num_seconds() >= EARLY_LEAVE_SECS => return Ok(None),
+        _ => PenaltyReason::EarlyLeave,
+      }
+    }
+    GameStatus::Ended | GameStatus::Terminated => return Ok(None),
+  };
+
+  record_offense(conn, player_id, Some(game_id), reason)
+}
+
+/// Records an offense and applies the matching penalty rule.
+/// Returns `None` if the offense has already been recorded for the game.
+pub fn record_offense(
+  conn: &DbConn,
+  player_id: i32,
+  game_id: Option<i32>,
+  reason: PenaltyReason,
+) -> Result<Option<PlayerPenalty>> {
+  #[derive(Insertable)]
+  #[table_name = "player_penalty"]
+  struct Insert {
+    player_id: i32,
+    game_id: Option<i32>,
+    reason: PenaltyReason,
+    penalty_type: PenaltyType,
+    expires_at: DateTime<Utc>,
+  }
+
+  conn.transaction(|| {
+    let rules: Vec<PenaltyRule> = penalty_rule::table
+      .select(PenaltyRule::COLUMNS)
+      .filter(penalty_rule::reason.eq(reason))
+      .load(conn)?;
+
+    let now = Utc::now();
+    let max_window = rules.iter().map(|r| r.window_secs).max().unwrap_or(0);
+    let previous: Vec<DateTime<Utc>> = player_penalty::table
+      .select(player_penalty::created_at)
+      .filter(
+        player_penalty::player_id
+          .eq(player_id)
+          .and(player_penalty::reason.eq(reason))
+          .and(player_penalty::appeal_status.ne(PenaltyAppealStatus::Accepted))
+          .and(player_penalty::created_at.gt(now - Duration::seconds(max_window as i64))),
+      )
+      .load(conn)?;
+    let offense_ages: Vec<i64> = std::iter::once(0)
+      .chain(previous.into_iter().map(|t| (now - t).num_seconds()))
+      .collect();
+
+    let (penalty_type, expires_at) = match select_rule(&rules, &offense_ages) {
+      Some(rule) => (
+        rule.penalty_type,
+        now + Duration::seconds(rule.duration_secs as i64),
+      ),
+      None => (PenaltyType::Warning, now),
+    };
+
+    let id: Option<i32> = diesel::insert_into(player_penalty::table)
+      .values(&Insert {
+        player_id,
+        game_id,
+        reason,
+        penalty_type,
+        expires_at,
+      })
+      .on_conflict((
+        player_penalty::game_id,
+        player_penalty::player_id,
+        player_penalty::reason,
+      ))
+      .do_nothing()
+      .returning(player_penalty::id)
+      .get_result(conn)
+      .optional()?;
+
+    id.map(|id| get(conn, id)).transpose()
+  })
+}
+
+/// Fails if any of the players cannot join the matchmaking queue
+pub fn check_queue_penalty(conn: &DbConn, player_ids: &[i32]) -> Result<()> {
+  check_active(conn, player_ids, |t| t != PenaltyType::Warning)
+}
+
+/// Fails if any of the players is banned from joining games
+pub fn check_ban_penalty(conn: &DbConn, player_ids: &[i32]) -> Result<()> {
+  check_active(conn, player_ids, |t| t == PenaltyType::Ban)
+}
+
+fn check_active<F>(conn: &DbConn, player_ids: &[i32], f: F) -> Result<()>
+where
+  F: Fn(PenaltyType) -> bool,
+{
+  let rows: Vec<(i32, PenaltyType, DateTime<Utc>)> = player_penalty::table
+    .select((
+      player_penalty::player_id,
+      player_penalty::penalty_type,
+      player_penalty::expires_at,
+    ))
+    .filter(
+      player_penalty::player_id
+        .eq(any(player_ids))
+        .and(player_penalty::expires_at.gt(sql("now()")))
+        .and(player_penalty::appeal_status.ne(PenaltyAppealStatus::Accepted)),
+    )
+    .order(player_penalty::expires_at.desc())
+    .load(conn)?;
+  if let Some((player_id, _, expires_at)) = rows.into_iter().find(|(_, t, _)| f(*t)) {
+    return Err(Error::PlayerPenalized {
+      player_id,
+      expires_at,
+    });
+  }
+  Ok(())
+}
+
+/// Files an appeal, each penalty can be appealed once
+pub fn appeal(conn: &DbConn, id: i32, player_id: i32, message: &str) -> Result<PlayerPenalty> {
+  let updated = diesel::update(
+    player_penalty::table.filter(
+      player_penalty::id
+        .eq(id)
+        .and(player_penalty::player_id.eq(player_id))
+        .and(player_penalty::appeal_status.eq(PenaltyAppealStatus::None)),
+    ),
+  )
+  .set((
+    player_penalty::appeal_status.eq(PenaltyAppealStatus::Pending),
+    player_penalty::appeal_message.eq(message),
+  ))
+  .execute(conn)?;
+  if updated == 0 {
+    get(conn, id)?;
+    return Err(Error::PenaltyAppealInvalid);
+  }
+  get(conn, id)
+}
+
+/// Accepting an appeal lifts the penalty and removes the offense from the history
+pub fn resolve_appeal(conn: &DbConn, id: i32, accepted: bool) -> Result<PlayerPenalty> {
+  let status = if accepted {
+    PenaltyAppealStatus::Accepted
+  } else {
+    PenaltyAppealStatus::Rejected
+  };
+  let updated = diesel::update(
+    player_penalty::table.filter(
+      player_penalty::id
+        .eq(id)
+        .and(player_penalty::appeal_status.eq(PenaltyAppealStatus::Pending)),
+    ),
+  )
+  .set((
+    player_penalty::appeal_status.eq(status),
+    player_penalty::appeal_resolved_at.eq(sql("now()")),
+  ))
+  .execute(conn)?;
+  if updated == 0 {
+    get(conn, id)?;
+    return Err(Error::PenaltyAppealInvalid);
+  }
+  get(conn, id)
+}
@@ -0,0 +1,5 @@
+pub mod db;
+mod rule;
+mod types;
+
+pub use types::*;
@@ -0,0 +1,51 @@
+use crate::penalty::PenaltyRule;
+
+/// Selects the rule with the highest `offense_count` matched by the offenses.
+/// `offense_ages` are the ages in seconds of the offenses, including the current one.
+pub(crate) fn select_rule<'a>(
+  rules: &'a [PenaltyRule],
+  offense_ages: &[i64],
+) -> Option<&'a PenaltyRule> {
+  rules
+    .iter()
+    .filter(|rule| {
+      let count = offense_ages
+        .iter()
+        .filter(|age| **age < rule.window_secs as i64)
+        .count();
+      count >= rule.offense_count as usize
+    })
+    .max_by_key(|rule| rule.offense_count)
+}
+
+#[test]
+fn test_select_rule() {
+  use crate::penalty::{PenaltyReason, PenaltyType};
+
+  fn rule(offense_count: i32, window_secs: i32, penalty_type: PenaltyType) -> PenaltyRule {
+    PenaltyRule {
+      reason: PenaltyReason::Dodge,
+      offense_count,
+      window_secs,
+      penalty_type,
+      duration_secs: 600,
+    }
+  }
+
+  let rules = vec![
+    rule(1, 3600, PenaltyType::Warning),
+    rule(2, 3600, PenaltyType::QueueCooldown),
+    rule(5, 86400, PenaltyType::Ban),
+  ];
+
+  let select = |ages: &[i64]| select_rule(&rules, ages).map(|r| r.penalty_type);
+  assert_eq!(select(&[]), None);
+  assert_eq!(select(&[0]), Some(PenaltyType::Warning));
+  assert_eq!(select(&[0, 4000]), Some(PenaltyType::Warning));
+  assert_eq!(select(&[0, 100]), Some(PenaltyType::QueueCooldown));
+  assert_eq!(select(&[0, 4000, 5000, 6000, 7000]), Some(PenaltyType::Ban));
+  assert_eq!(
+    select(&[0, 4000, 5000, 6000, 90000]),
+    Some(PenaltyType::Warning)
+  );
+}
@@ -0,0 +1,113 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::player::{PlayerRef, PlayerRefColumns};
+use crate::schema::{penalty_rule, player_penalty};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::PenaltyReason")]
+pub enum PenaltyReason {
+  /// Left a matchmaking lobby before the game started
+  Dodge = 0,
+  /// Left a matchmaking game shortly after it started
+  EarlyLeave = 1,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::PenaltyType")]
+pub enum PenaltyType {
+  /// Recorded offense without restrictions
+  Warning = 0,
+  /// Cannot join the matchmaking queue
+  QueueCooldown = 1,
+  /// Cannot join the matchmaking queue or any game
+  Ban = 2,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::PenaltyAppealStatus")]
+pub enum PenaltyAppealStatus {
+  None = 0,
+  Pending = 1,
+  Accepted = 2,
+  Rejected = 3,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::PlayerPenalty")]
+pub struct PlayerPenalty {
+  pub id: i32,
+  pub player: PlayerRef,
+  pub game_id: Option<i32>,
+  #[s2_grpc(proto_enum)]
+  pub reason: PenaltyReason,
+  #[s2_grpc(proto_enum)]
+  pub penalty_type: PenaltyType,
+  pub expires_at: DateTime<Utc>,
+  #[s2_grpc(proto_enum)]
+  pub appeal_status: PenaltyAppealStatus,
+  pub appeal_message: Option<String>,
+  pub appeal_resolved_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type PlayerPenaltyColumns = (
+  player_penalty::id,
+  PlayerRefColumns,
+  player_penalty::game_id,
+  player_penalty::reason,
+  player_penalty::penalty_type,
+  player_penalty::expires_at,
+  player_penalty::appeal_status,
+  player_penalty::appeal_message,
+  player_penalty::appeal_resolved_at,
+  player_penalty::created_at,
+);
+
+impl PlayerPenalty {
+  pub(crate) const COLUMNS: PlayerPenaltyColumns = (
+    player_penalty::id,
+    PlayerRef::COLUMNS,
+    player_penalty::game_id,
+    player_penalty::reason,
+    player_penalty::penalty_type,
+    player_penalty::expires_at,
+    player_penalty::appeal_status,
+    player_penalty::appeal_message,
+    player_penalty::appeal_resolved_at,
+    player_penalty::created_at,
+  );
+}
+
+/// Applies a penalty when a player commits `offense_count` offenses within `window_secs`
+#[derive(Debug, Clone, Queryable)]
+pub struct PenaltyRule {
+  pub reason: PenaltyReason,
+  pub offense_count: i32,
+  pub window_secs: i32,
+  pub penalty_type: PenaltyType,
+  pub duration_secs: i32,
+}
+
+pub(crate) type PenaltyRuleColumns = (
+  penalty_rule::reason,
+  penalty_rule::offense_count,
+  penalty_rule::window_secs,
+  penalty_rule::penalty_type,
+  penalty_rule::duration_secs,
+);
+
+impl PenaltyRule {
+  pub(crate) const COLUMNS: PenaltyRuleColumns = (
+    penalty_rule::reason,
+    penalty_rule::offense_count,
+    penalty_rule::window_secs,
+    penalty_rule::penalty_type,
+    penalty_rule::duration_secs,
+  );
+}
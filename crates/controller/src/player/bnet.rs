@@ -0,0 +1,255 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+use url::Url;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::bnet_oauth_state;
+
+const STATE_EXPIRATION_SECS: i64 = 600;
+const STATE_NONCE_LEN: usize = 32;
+const STATE_SUB: &str = "flo-bnet";
+const OAUTH_URL_DEFAULT: &str = "https://oauth.battle.net";
+
+/// Battle.net OAuth is enabled when the client credentials are configured
+static CONFIG: Lazy<Option<BNetOAuthConfig>> = Lazy::new(|| {
+  Some(BNetOAuthConfig {
+    client_id: env::var("BNET_CLIENT_ID").ok()?,
+    client_secret: env::var("BNET_CLIENT_SECRET").ok()?,
+    redirect_uri: env::var("BNET_REDIRECT_URI").ok()?,
+    oauth_url: env::var("BNET_OAUTH_URL").unwrap_or_else(|_| OAUTH_URL_DEFAULT.to_string()),
+  })
+});
+
+struct BNetOAuthConfig {
+  client_id: String,
+  client_secret: String,
+  redirect_uri: String,
+  oauth_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateClaims {
+  sub: String,
+  player_id: i32,
+  nonce: String,
+  exp: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+  access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BNetUserInfo {
+  pub id: u64,
+  pub battletag: String,
+}
+
+fn config() -> Result<&'static BNetOAuthConfig> {
+  CONFIG.as_ref().ok_or_else(|| Error::BNetOAuthDisabled)
+}
+
+/// Returns the Battle.net authorize URL,
+/// the player is identified by the signed `state` parameter
+pub fn create_authorize_url(conn: &DbConn, player_id: i32) -> Result<String> {
+  let config = config()?;
+  let state = create_state(
+    conn,
+    &crate::config::JWT_SECRET_BASE64,
+    player_id,
+    Utc::now(),
+  )?;
+  authorize_url(config, &state)
+}
+
+/// Returns the player id in the `state` parameter, a state can only be validated once
+pub fn validate_state(conn: &DbConn, state: &str) -> Result<i32> {
+  consume_state(conn, &crate::config::JWT_SECRET_BASE64, state, Utc::now())
+}
+
+fn authorize_url(config: &BNetOAuthConfig, state: &str) -> Result<String> {
+  let url = Url::parse_with_params(
+    &format!("{}/authorize", config.oauth_url),
+    &[
+      ("client_id", config.client_id.as_str()),
+      ("redirect_uri", config.redirect_uri.as_str()),
+      ("response_type", "code"),
+      ("scope", "openid"),
+      ("state", state),
+    ],
+  )
+  .map_err(request_error)?;
+  Ok(url.to_string())
+}
+
+/// Stores a nonce for the player and signs it into the state
+fn create_state(conn: &DbConn, secret: &str, player_id: i32, now: DateTime<Utc>) -> Result<String> {
+  let nonce: String = rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(STATE_NONCE_LEN)
+    .map(char::from)
+    .collect();
+  let expires_at = now + Duration::seconds(STATE_EXPIRATION_SECS);
+
+  diesel::delete(bnet_oauth_state::table.filter(bnet_oauth_state::expires_at.le(now)))
+    .execute(conn)?;
+  diesel::insert_into(bnet_oauth_state::table)
+    .values((
+      bnet_oauth_state::nonce.eq(&nonce),
+      bnet_oauth_state::player_id.eq(player_id),
+      bnet_oauth_state::expires_at.eq(expires_at),
+    ))
+    .execute(conn)?;
+
+  encode_state(
+    secret,
+    &StateClaims {
+      sub: STATE_SUB.to_string(),
+      player_id,
+      nonce,
+      exp: expires_at.timestamp() as usize,
+    },
+  )
+}
+
+/// Deletes the nonce of the state, fails if it was already used or has expired
+fn consume_state(conn: &DbConn, secret: &str, state: &str, now: DateTime<Utc>) -> Result<i32> {
+  let claims = decode_state(secret, state)?;
+  let consumed = diesel::delete(
+    bnet_oauth_state::table.filter(
+      bnet_oauth_state::nonce
+        .eq(&claims.nonce)
+        .and(bnet_oauth_state::player_id.eq(claims.player_id))
+        .and(bnet_oauth_state::expires_at.gt(now)),
+    ),
+  )
+  .execute(conn)?;
+  if consumed == 0 {
+    return Err(Error::BNetOAuthStateInvalid);
+  }
+  Ok(claims.player_id)
+}
+
+fn encode_state(secret: &str, claims: &StateClaims) -> Result<String> {
+  let key = EncodingKey::from_base64_secret(secret)?;
+  Ok(encode(&Header::default(), claims, &key)?)
+}
+
+fn decode_state(secret: &str, state: &str) -> Result<StateClaims> {
+  let key = DecodingKey::from_base64_secret(secret)?;
+  let claims = decode::<StateClaims>(state, &key, &Validation::default())?.claims;
+  if claims.sub != STATE_SUB {
+    return Err(Error::BNetOAuthStateInvalid);
+  }
+  Ok(claims)
+}
+
+/// Exchanges the authorization code for the account info, blocking
+pub fn get_user_info(code: &str) -> Result<BNetUserInfo> {
+  let config = config()?;
+  let token: TokenResponse = ureq::post(&format!("{}/token", config.oauth_url))
+    .send_form(&[
+      ("grant_type", "authorization_code"),
+      ("code", code),
+      ("redirect_uri", &config.redirect_uri),
+      ("client_id", &config.client_id),
+      ("client_secret", &config.client_secret),
+    ])
+    .map_err(request_error)?
+    .into_json()
+    .map_err(request_error)?;
+
+  ureq::get(&format!("{}/userinfo", config.oauth_url))
+    .set("Authorization", &format!("Bearer {}", token.access_token))
+    .call()
+    .map_err(request_error)?
+    .into_json()
+    .map_err(request_error)
+}
+
+fn request_error<E: std::fmt::Display>(e: E) -> Error {
+  Error::BNetOAuthRequest(e.to_string())
+}
+
+#[cfg(test)]
+const TEST_SECRET: &str = "dGVzdC1zZWNyZXQ=";
+
+#[test]
+fn test_authorize_url() {
+  let config = BNetOAuthConfig {
+    client_id: "client".to_string(),
+    client_secret: "secret".to_string(),
+    redirect_uri: "https://example.com/callback?a=1".to_string(),
+    oauth_url: OAUTH_URL_DEFAULT.to_string(),
+  };
+  let url = Url::parse(&authorize_url(&config, "signed state").unwrap()).unwrap();
+  assert_eq!(
+    url.as_str().split('?').next(),
+    Some("https://oauth.battle.net/authorize")
+  );
+  let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+  assert_eq!(params["client_id"], "client");
+  assert_eq!(params["redirect_uri"], "https://example.com/callback?a=1");
+  assert_eq!(params["state"], "signed state");
+  assert!(!params.contains_key("client_secret"));
+}
+
+#[test]
+fn test_decode_state() {
+  let claims = |sub: &str, exp: i64| StateClaims {
+    sub: sub.to_string(),
+    player_id: 1,
+    nonce: "nonce".to_string(),
+    exp: exp as usize,
+  };
+  let exp = Utc::now().timestamp() + STATE_EXPIRATION_SECS;
+
+  let state = encode_state(TEST_SECRET, &claims(STATE_SUB, exp)).unwrap();
+  let decoded = decode_state(TEST_SECRET, &state).unwrap();
+  assert_eq!((decoded.player_id, decoded.nonce.as_str()), (1, "nonce"));
+  assert!(decode_state("b3RoZXItc2VjcmV0", &state).is_err());
+
+  let state = encode_state(TEST_SECRET, &claims("flo-other", exp)).unwrap();
+  assert!(matches!(
+    decode_state(TEST_SECRET, &state),
+    Err(Error::BNetOAuthStateInvalid)
+  ));
+
+  let state = encode_state(TEST_SECRET, &claims(STATE_SUB, exp - 3600)).unwrap();
+  assert!(decode_state(TEST_SECRET, &state).is_err());
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_state_single_use() {
+  use crate::db::{insert_test_api_client, insert_test_player};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "bnet")?;
+    let player_id = insert_test_player(conn, api_client_id, "player")?;
+    let now = Utc::now();
+
+    let state = create_state(conn, TEST_SECRET, player_id, now)?;
+    assert_eq!(consume_state(conn, TEST_SECRET, &state, now)?, player_id);
+    assert!(matches!(
+      consume_state(conn, TEST_SECRET, &state, now),
+      Err(Error::BNetOAuthStateInvalid)
+    ));
+
+    let state = create_state(conn, TEST_SECRET, player_id, now)?;
+    let expired_at = now + Duration::seconds(STATE_EXPIRATION_SECS);
+    assert!(matches!(
+      consume_state(conn, TEST_SECRET, &state, expired_at),
+      Err(Error::BNetOAuthStateInvalid)
+    ));
+    Ok(())
+  })
+  .await;
+}
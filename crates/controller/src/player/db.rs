@@ -1,7 +1,9 @@
 use crate::db::DbConn;
 use crate::error::*;
-use crate::player::{Player, PlayerBan, PlayerBanType, PlayerRef, PlayerSource, SourceState};
-use crate::schema::{player, player_ban, player_mute};
+use crate::player::{
+  OrganizerBan, Player, PlayerBan, PlayerBanType, PlayerRef, PlayerSource, SourceState,
+};
+use crate::schema::{organizer_ban, player, player_ban, player_mute};
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use serde_json::Value;
@@ -18,10 +20,9 @@ pub fn get(conn: &DbConn, id: i32) -> Result<Player> {
 }
 
 pub fn get_ref(conn: &DbConn, id: i32) -> Result<PlayerRef> {
-  use player::dsl;
   player::table
     .find(id)
-    .select((dsl::id, dsl::name, dsl::source, dsl::realm))
+    .select(PlayerRef::COLUMNS)
     .first::<PlayerRef>(conn)
     .optional()?
     .ok_or_else(|| Error::PlayerNotFound)
@@ -32,7 +33,7 @@ pub fn get_refs_by_ids(conn: &DbConn, ids: &[i32]) -> Result<Vec<PlayerRef>> {
   use player::dsl;
   player::table
     .filter(dsl::id.eq_any(ids))
-    .select((dsl::id, dsl::name, dsl::source, dsl::realm))
+    .select(PlayerRef::COLUMNS)
     .load(conn)
     .map_err(Into::into)
 }
@@ -46,7 +47,7 @@ pub fn get_client_refs_by_ids(
   player::table
     .filter(dsl::api_client_id.eq(api_client_id))
     .filter(dsl::id.eq_any(ids))
-    .select((dsl::id, dsl::name, dsl::source, dsl::realm))
+    .select(PlayerRef::COLUMNS)
     .load(conn)
     .map_err(Into::into)
 }
@@ -64,10 +65,7 @@ pub fn get_player_map_by_api_source_ids(
         .and(dsl::api_client_id.eq(api_client_id)),
     )
     .filter(dsl::source_id.eq_any(ids))
-    .select((
-      dsl::source_id,
-      (dsl::id, dsl::name, dsl::source, dsl::realm),
-    ))
+    .select((dsl::source_id, PlayerRef::COLUMNS))
     .load::<(String, PlayerRef)>(conn)?;
   Ok(pairs.into_iter().collect())
 }
@@ -104,6 +102,49 @@ pub fn upsert(conn: &DbConn, data: &UpsertPlayer) -> Result<Player> {
     .map_err(Into::into)
 }
 
+/// Links a verified Battle.net account, an account can only be linked to one player per API client
+pub fn set_battletag(
+  conn: &DbConn,
+  api_client_id: i32,
+  player_id: i32,
+  bnet_account_id: u64,
+  battletag: &str,
+) -> Result<Player> {
+  use player::dsl;
+  conn.transaction(|| {
+    let linked: Option<i32> = player::table
+      .select(dsl::id)
+      .filter(
+        dsl::api_client_id
+          .eq(api_client_id)
+          .and(dsl::bnet_account_id.eq(bnet_account_id as i64))
+          .and(dsl::id.ne(player_id)),
+      )
+      .first(conn)
+      .optional()?;
+    if linked.is_some() {
+      return Err(Error::BNetAccountLinked);
+    }
+
+    diesel::update(
+      player::table.filter(
+        dsl::id
+          .eq(player_id)
+          .and(dsl::api_client_id.eq(api_client_id)),
+      ),
+    )
+    .set((
+      dsl::battletag.eq(battletag),
+      dsl::bnet_account_id.eq(bnet_account_id as i64),
+      dsl::battletag_verified_at.eq(Utc::now()),
+    ))
+    .get_result::<Row>(conn)
+    .optional()?
+    .ok_or_else(|| Error::PlayerNotFound)
+    .map(Into::into)
+  })
+}
+
 pub fn add_mute(conn: &DbConn, player_id: i32, mute_player_id: i32) -> Result<()> {
   #[derive(Insertable)]
   #[table_name = "player_mute"]
@@ -312,6 +353,133 @@ pub fn check_player_api_client_id(conn: &DbConn, api_client_id: i32, player_id:
   Ok(())
 }
 
+pub struct ListOrganizerBan {
+  pub bans: Vec<OrganizerBan>,
+  pub next_id: Option<i32>,
+}
+
+pub fn list_organizer_ban(
+  conn: &DbConn,
+  api_client_id: i32,
+  query: Option<&str>,
+  next_id: Option<i32>,
+) -> Result<ListOrganizerBan> {
+  const PAGE_SIZE: i64 = 200;
+  let mut q = organizer_ban::table
+    .inner_join(player::table)
+    .select(OrganizerBan::COLUMNS)
+    .filter(organizer_ban::api_client_id.eq(api_client_id))
+    .order(organizer_ban::id)
+    .limit(PAGE_SIZE + 1)
+    .into_boxed();
+
+  if let Some(v) = query {
+    q = q.filter(player::name.ilike(format!("%{}%", v)));
+  }
+
+  if let Some(id) = next_id {
+    q = q.filter(organizer_ban::id.ge(id));
+  }
+
+  let mut rows = q.load::<OrganizerBan>(conn)?;
+  let next_id = if rows.len() > PAGE_SIZE as usize {
+    let id = rows.last().map(|row| row.id);
+    rows.truncate(PAGE_SIZE as usize);
+    id
+  } else {
+    None
+  };
+
+  Ok(ListOrganizerBan {
+    bans: rows,
+    next_id,
+  })
+}
+
+pub fn create_organizer_ban(
+  conn: &DbConn,
+  api_client_id: i32,
+  player_id: i32,
+  reason: &str,
+  ban_expires_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "organizer_ban"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    player_id: i32,
+    reason: &'a str,
+    ban_expires_at: Option<DateTime<Utc>>,
+  }
+
+  diesel::insert_into(organizer_ban::table)
+    .values(&Insert {
+      api_client_id,
+      player_id,
+      reason,
+      ban_expires_at,
+    })
+    .on_conflict((organizer_ban::api_client_id, organizer_ban::player_id))
+    .do_update()
+    .set((
+      organizer_ban::reason.eq(reason),
+      organizer_ban::ban_expires_at.eq(ban_expires_at),
+    ))
+    .execute(conn)?;
+
+  Ok(())
+}
+
+pub fn remove_organizer_ban(conn: &DbConn, api_client_id: i32, id: i32) -> Result<()> {
+  let n = diesel::delete(
+    organizer_ban::table.filter(
+      organizer_ban::id
+        .eq(id)
+        .and(organizer_ban::api_client_id.eq(api_client_id)),
+    ),
+  )
+  .execute(conn)?;
+  if n == 0 {
+    return Err(Error::OrganizerBanNotFound);
+  }
+  Ok(())
+}
+
+/// Fails if any of the players has an active ban from the organizer
+pub fn check_organizer_ban(conn: &DbConn, api_client_id: i32, player_ids: &[i32]) -> Result<()> {
+  use diesel::pg::expression::dsl::any;
+  let bans: Vec<OrganizerBanEntry> = organizer_ban::table
+    .select((
+      organizer_ban::player_id,
+      organizer_ban::reason,
+      organizer_ban::ban_expires_at,
+    ))
+    .filter(
+      organizer_ban::api_client_id
+        .eq(api_client_id)
+        .and(organizer_ban::player_id.eq(any(player_ids))),
+    )
+    .order(organizer_ban::id)
+    .load(conn)?;
+  check_active_organizer_bans(&bans, Utc::now())
+}
+
+type OrganizerBanEntry = (i32, String, Option<DateTime<Utc>>);
+
+/// Fails with the first ban that hasn't expired at `now`, bans without expiry never expire
+fn check_active_organizer_bans(bans: &[OrganizerBanEntry], now: DateTime<Utc>) -> Result<()> {
+  let ban = bans
+    .iter()
+    .find(|(_, _, expires_at)| expires_at.map(|v| v > now).unwrap_or(true));
+  match ban {
+    Some((player_id, reason, _)) => Err(Error::PlayerBannedByOrganizer {
+      player_id: *player_id,
+      reason: reason.clone(),
+    }),
+    None => Ok(()),
+  }
+}
+
 #[derive(Debug, Insertable)]
 #[table_name = "player"]
 struct Insert<'a> {
@@ -342,6 +510,9 @@ pub struct Row {
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
   pub api_client_id: i32,
+  pub battletag: Option<String>,
+  pub bnet_account_id: Option<i64>,
+  pub battletag_verified_at: Option<DateTime<Utc>>,
 }
 
 impl From<Row> for Player {
@@ -357,6 +528,7 @@ impl From<Row> for Player {
         .transpose()
         .unwrap_or(Some(SourceState::Invalid)),
       realm: row.realm,
+      battletag: row.battletag,
       created_at: row.created_at,
       updated_at: row.updated_at,
     }
@@ -370,6 +542,101 @@ impl From<Row> for PlayerRef {
       name: p.name,
       source: p.source,
       realm: p.realm,
+      battletag: p.battletag,
     }
   }
 }
+
+#[test]
+fn test_check_active_organizer_bans() {
+  let now = Utc::now();
+  let hour = chrono::Duration::hours(1);
+  let ban = |player_id, expires_at| (player_id, "spam".to_string(), expires_at);
+
+  assert!(check_active_organizer_bans(&[], now).is_ok());
+  assert!(check_active_organizer_bans(&[ban(1, Some(now - hour))], now).is_ok());
+  assert!(check_active_organizer_bans(&[ban(1, Some(now))], now).is_ok());
+  for bans in &[
+    vec![ban(2, None)],
+    vec![ban(1, Some(now - hour)), ban(2, Some(now + hour))],
+  ] {
+    assert!(matches!(
+      check_active_organizer_bans(bans, now),
+      Err(Error::PlayerBannedByOrganizer { player_id: 2, .. })
+    ));
+  }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_organizer_ban() {
+  use crate::db::{insert_test_api_client, insert_test_player};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "organizer")?;
+    let other_api_client_id = insert_test_api_client(conn, "other")?;
+    let player_id = insert_test_player(conn, api_client_id, "banned")?;
+
+    assert!(matches!(
+      remove_organizer_ban(conn, api_client_id, 0),
+      Err(Error::OrganizerBanNotFound)
+    ));
+
+    create_organizer_ban(conn, api_client_id, player_id, "spam", None)?;
+    assert!(matches!(
+      check_organizer_ban(conn, api_client_id, &[player_id]),
+      Err(Error::PlayerBannedByOrganizer { player_id: id, .. }) if id == player_id
+    ));
+    check_organizer_ban(conn, other_api_client_id, &[player_id])?;
+
+    let ban_id = list_organizer_ban(conn, api_client_id, None, None)?.bans[0].id;
+    assert!(matches!(
+      remove_organizer_ban(conn, other_api_client_id, ban_id),
+      Err(Error::OrganizerBanNotFound)
+    ));
+    remove_organizer_ban(conn, api_client_id, ban_id)?;
+    check_organizer_ban(conn, api_client_id, &[player_id])?;
+
+    let expired_at = Utc::now() - chrono::Duration::hours(1);
+    create_organizer_ban(conn, api_client_id, player_id, "spam", Some(expired_at))?;
+    check_organizer_ban(conn, api_client_id, &[player_id])?;
+    Ok(())
+  })
+  .await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_set_battletag() {
+  use crate::db::{insert_test_api_client, insert_test_player};
+  crate::db::test_transaction(|conn| {
+    let api_client_id = insert_test_api_client(conn, "bnet")?;
+    let other_api_client_id = insert_test_api_client(conn, "other")?;
+    let player_id = insert_test_player(conn, api_client_id, "player")?;
+    let other_player_id = insert_test_player(conn, api_client_id, "other")?;
+    let other_client_player_id = insert_test_player(conn, other_api_client_id, "player")?;
+
+    let player = set_battletag(conn, api_client_id, player_id, 1, "player#1234")?;
+    assert_eq!(player.battletag.as_deref(), Some("player#1234"));
+    // relinking the same account updates the battle tag
+    let player = set_battletag(conn, api_client_id, player_id, 1, "renamed#1234")?;
+    assert_eq!(player.battletag.as_deref(), Some("renamed#1234"));
+
+    assert!(matches!(
+      set_battletag(conn, api_client_id, other_player_id, 1, "player#1234"),
+      Err(Error::BNetAccountLinked)
+    ));
+    set_battletag(
+      conn,
+      other_api_client_id,
+      other_client_player_id,
+      1,
+      "player#1234",
+    )?;
+    assert!(matches!(
+      set_battletag(conn, other_api_client_id, player_id, 2, "player#1234"),
+      Err(Error::PlayerNotFound)
+    ));
+    Ok(())
+  })
+  .await;
+}
@@ -1,6 +1,8 @@
+pub mod bnet;
 pub mod db;
 pub mod session;
 pub(crate) mod state;
+pub mod stats;
 pub mod token;
 mod types;
 
@@ -21,6 +21,7 @@ impl Handler<Connect> for PlayerRegistry {
       PlayerState::new(player_id, message.game_id, message.sender),
     );
     if let Some(state) = removed {
+      crate::metrics::PLAYER_RECONNECTS.inc();
       state.shutdown().await;
     }
   }
@@ -28,18 +29,76 @@ impl Handler<Connect> for PlayerRegistry {
 
 pub struct Disconnect {
   pub player_id: i32,
+  pub conn_id: u64,
 }
 
 impl Message for Disconnect {
-  type Result = ();
+  /// `false` if the connection has been replaced by a newer one
+  type Result = bool;
 }
 
 #[async_trait]
 impl Handler<Disconnect> for PlayerRegistry {
-  async fn handle(&mut self, _: &mut Context<Self>, message: Disconnect) {
+  async fn handle(&mut self, _: &mut Context<Self>, message: Disconnect) -> bool {
     let player_id = message.player_id;
+    match self.registry.get(&player_id) {
+      Some(state) if state.sender.conn_id() == message.conn_id => {}
+      Some(_) => {
+        tracing::debug!(
+          player_id,
+          conn_id = message.conn_id,
+          "skip disconnect: connection replaced"
+        );
+        return false;
+      }
+      None => return true,
+    }
     if let Some(state) = self.registry.remove(&player_id) {
       state.shutdown().await;
     }
+    true
   }
 }
+
+#[tokio::test]
+async fn test_disconnect_replaced_connection() {
+  use flo_state::Actor;
+
+  let registry = PlayerRegistry::new().start();
+  let (old, _old_rx) = PlayerSender::new(1);
+  let (new, _new_rx) = PlayerSender::new(1);
+  let (old_conn_id, new_conn_id) = (old.conn_id(), new.conn_id());
+
+  registry
+    .send(Connect {
+      game_id: None,
+      sender: old,
+    })
+    .await
+    .unwrap();
+  registry
+    .send(Connect {
+      game_id: None,
+      sender: new,
+    })
+    .await
+    .unwrap();
+
+  let disconnected = registry
+    .send(Disconnect {
+      player_id: 1,
+      conn_id: old_conn_id,
+    })
+    .await
+    .unwrap();
+  assert!(!disconnected);
+
+  let disconnected = registry
+    .send(Disconnect {
+      player_id: 1,
+      conn_id: new_conn_id,
+    })
+    .await
+    .unwrap();
+  assert!(disconnected);
+}
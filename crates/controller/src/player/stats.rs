@@ -0,0 +1,318 @@
+use diesel::prelude::*;
+use flo_w3gs::protocol::constants::LeaveReason;
+use s2_grpc_utils::S2ProtoPack;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::result::{GameResultReport, MmdFlag};
+use crate::game::Race;
+use crate::matchmaking::{LadderMode, PlayerRatingChange};
+use crate::schema::{game, game_result, game_used_slot};
+
+const MAX_GAMES: i64 = 1000;
+const MAX_RATING_HISTORY: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack)]
+#[s2_grpc(message_type(flo_grpc::player::PlayerStats))]
+pub struct PlayerStats {
+  pub player_id: i32,
+  pub games: i32,
+  pub wins: i32,
+  pub losses: i32,
+  pub leaves: i32,
+  pub win_rate: f32,
+  pub leave_rate: f32,
+  pub average_apm: f32,
+  pub races: Vec<PlayerRaceStats>,
+  pub maps: Vec<PlayerMapStats>,
+  pub rating_history: Vec<PlayerRatingChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack)]
+#[s2_grpc(message_type(flo_grpc::player::PlayerRaceStats))]
+pub struct PlayerRaceStats {
+  #[s2_grpc(proto_enum)]
+  pub race: Race,
+  pub games: i32,
+  pub wins: i32,
+  pub losses: i32,
+  pub win_rate: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack)]
+#[s2_grpc(message_type(flo_grpc::player::PlayerMapStats))]
+pub struct PlayerMapStats {
+  pub map_name: String,
+  pub games: i32,
+  pub wins: i32,
+  pub losses: i32,
+  pub win_rate: f32,
+}
+
+pub fn get(conn: &DbConn, player_id: i32) -> Result<PlayerStats> {
+  let rows: Vec<(i32, i32, Race, String, bool, Option<i32>, i32, Value)> = game_used_slot::table
+    .inner_join(game::table.inner_join(game_result::table))
+    .select((
+      game::id,
+      game_used_slot::team,
+      game_used_slot::race,
+      game::map_name,
+      game_result::valid,
+      game_result::winner_team,
+      game_result::duration_ms,
+      game_result::report,
+    ))
+    .filter(
+      game_used_slot::player_id
+        .eq(player_id)
+        .and(game_used_slot::team.ne(24)),
+    )
+    .order(game_result::created_at.desc())
+    .limit(MAX_GAMES)
+    .load(conn)?;
+
+  let game_ids: Vec<i32> = rows.iter().map(|row| row.0).collect();
+  let mut replay_apm: BTreeMap<i32, f32> =
+    crate::game::replay::get_stats_by_game_ids(conn, &game_ids)?
+      .into_iter()
+      .filter_map(|(game_id, stats)| {
+        let player = stats
+          .players
+          .into_iter()
+          .find(|p| p.player_id == player_id)?;
+        Some((game_id, player.apm))
+      })
+      .collect();
+
+  let rows = rows
+    .into_iter()
+    .map(
+      |(game_id, team, race, map_name, valid, winner_team, duration_ms, report)| -> Result<_> {
+        Ok(StatsRow {
+          team,
+          race,
+          map_name,
+          valid,
+          winner_team,
+          duration_ms: duration_ms as u32,
+          report: serde_json::from_value(report)?,
+          replay_apm: replay_apm.remove(&game_id),
+        })
+      },
+    )
+    .collect::<Result<Vec<_>>>()?;
+
+  let mut stats = aggregate(player_id, &rows);
+  for mode in LadderMode::ALL {
+    stats
+      .rating_history
+      .extend(crate::matchmaking::db::get_rating_history(
+        conn,
+        player_id,
+        *mode,
+        MAX_RATING_HISTORY,
+      )?);
+  }
+  Ok(stats)
+}
+
+#[derive(Debug)]
+struct StatsRow {
+  team: i32,
+  race: Race,
+  map_name: String,
+  valid: bool,
+  winner_team: Option<i32>,
+  duration_ms: u32,
+  report: GameResultReport,
+  /// APM decoded from the stored replay, more accurate than the node's action count
+  replay_apm: Option<f32>,
+}
+
+#[derive(Default)]
+struct Counter {
+  games: i32,
+  wins: i32,
+  losses: i32,
+}
+
+impl Counter {
+  fn add(&mut self, won: Option<bool>) {
+    self.games += 1;
+    match won {
+      Some(true) => self.wins += 1,
+      Some(false) => self.losses += 1,
+      None => {}
+    }
+  }
+
+  fn win_rate(&self) -> f32 {
+    rate(self.wins, self.wins + self.losses)
+  }
+}
+
+fn rate(n: i32, total: i32) -> f32 {
+  if total == 0 {
+    0.0
+  } else {
+    n as f32 / total as f32
+  }
+}
+
+fn aggregate(player_id: i32, rows: &[StatsRow]) -> PlayerStats {
+  let mut total = Counter::default();
+  let mut leaves = 0;
+  let mut apm_sum = 0.0;
+  let mut apm_games = 0;
+  let mut races: BTreeMap<i32, (Race, Counter)> = BTreeMap::new();
+  let mut maps: BTreeMap<&str, Counter> = BTreeMap::new();
+
+  for row in rows {
+    let won = if row.valid {
+      row.winner_team.map(|team| team == row.team)
+    } else {
+      None
+    };
+    total.add(won);
+    races
+      .entry(row.race as i32)
+      .or_insert_with(|| (row.race, Counter::default()))
+      .1
+      .add(won);
+    maps.entry(&row.map_name).or_default().add(won);
+
+    if let Some(player) = row.report.players.iter().find(|p| p.player_id == player_id) {
+      if player.mmd_flag == MmdFlag::Leaver
+        || player.leave_reason.map(LeaveReason::from) == Some(LeaveReason::LeaveDisconnect)
+      {
+        leaves += 1;
+      }
+      let time_ms = if player.left_at_ms > 0 {
+        player.left_at_ms
+      } else {
+        row.duration_ms
+      };
+      if let Some(apm) = row.replay_apm {
+        apm_sum += apm;
+        apm_games += 1;
+      } else if time_ms > 0 {
+        apm_sum += player.action_count as f32 * 60000.0 / time_ms as f32;
+        apm_games += 1;
+      }
+    }
+  }
+
+  PlayerStats {
+    player_id,
+    games: total.games,
+    wins: total.wins,
+    losses: total.losses,
+    leaves,
+    win_rate: total.win_rate(),
+    leave_rate: rate(leaves, total.games),
+    average_apm: if apm_games > 0 {
+      apm_sum / apm_games as f32
+    } else {
+      0.0
+    },
+    races: races
+      .into_iter()
+      .map(|(_, (race, c))| PlayerRaceStats {
+        race,
+        games: c.games,
+        wins: c.wins,
+        losses: c.losses,
+        win_rate: c.win_rate(),
+      })
+      .collect(),
+    maps: maps
+      .into_iter()
+      .map(|(map_name, c)| PlayerMapStats {
+        map_name: map_name.to_string(),
+        games: c.games,
+        wins: c.wins,
+        losses: c.losses,
+        win_rate: c.win_rate(),
+      })
+      .collect(),
+    rating_history: vec![],
+  }
+}
+
+#[test]
+fn test_aggregate() {
+  use crate::game::result::GameResultPlayer;
+
+  let row = |team, race, map_name: &str, winner_team, leave_reason: Option<LeaveReason>| StatsRow {
+    team,
+    race,
+    map_name: map_name.to_string(),
+    valid: true,
+    winner_team,
+    duration_ms: 120000,
+    report: GameResultReport {
+      game_id: 1,
+      duration_ms: 120000,
+      players: vec![GameResultPlayer {
+        player_id: 1,
+        leave_reason: leave_reason.map(u32::from),
+        left_at_ms: 60000,
+        mmd_flag: MmdFlag::None,
+        desync: false,
+        action_count: 100,
+        rtt_avg_ms: 0,
+        rtt_p95_ms: 0,
+        lag_caused_ms: 0,
+        disconnect_count: 0,
+      }],
+      desyncs: vec![],
+      traceparent: String::new(),
+    },
+    replay_apm: None,
+  };
+
+  let stats = aggregate(
+    1,
+    &[
+      row(0, Race::Human, "a", Some(0), Some(LeaveReason::LeaveWon)),
+      row(
+        0,
+        Race::Human,
+        "b",
+        Some(1),
+        Some(LeaveReason::LeaveDisconnect),
+      ),
+      row(1, Race::Orc, "a", Some(1), Some(LeaveReason::LeaveWon)),
+      row(1, Race::Orc, "a", None, None),
+    ],
+  );
+
+  assert_eq!(stats.games, 4);
+  assert_eq!(stats.wins, 2);
+  assert_eq!(stats.losses, 1);
+  assert_eq!(stats.leaves, 1);
+  assert_eq!(stats.leave_rate, 0.25);
+  assert_eq!(stats.average_apm, 100.0);
+  assert_eq!(stats.races.len(), 2);
+  assert_eq!(stats.races[0].race, Race::Human);
+  assert_eq!(stats.races[0].win_rate, 0.5);
+  assert_eq!(stats.maps[0].map_name, "a");
+  assert_eq!(stats.maps[0].games, 3);
+  assert_eq!(stats.maps[0].wins, 2);
+  assert_eq!(stats.maps[1].losses, 1);
+
+  let stats = aggregate(
+    1,
+    &[
+      row(0, Race::Human, "a", Some(0), None),
+      StatsRow {
+        replay_apm: Some(200.0),
+        ..row(0, Race::Human, "a", Some(0), None)
+      },
+    ],
+  );
+  assert_eq!(stats.average_apm, 150.0);
+}
@@ -4,7 +4,7 @@ use s2_grpc_utils::result::Error as ProtoError;
 use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
 use serde::{Deserialize, Serialize};
 
-use crate::schema::{player, player_ban};
+use crate::schema::{organizer_ban, player, player_ban};
 
 #[derive(Debug, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack)]
 #[s2_grpc(message_type = "flo_grpc::player::Player")]
@@ -16,6 +16,8 @@ pub struct Player {
   pub source_id: String,
   pub source_state: Option<SourceState>,
   pub realm: Option<String>,
+  /// Verified with Battle.net OAuth
+  pub battletag: Option<String>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
 }
@@ -40,6 +42,7 @@ pub struct PlayerRef {
   #[s2_grpc(proto_enum)]
   pub source: PlayerSource,
   pub realm: Option<String>,
+  pub battletag: Option<String>,
 }
 
 pub(crate) type PlayerRefColumns = (
@@ -47,6 +50,7 @@ pub(crate) type PlayerRefColumns = (
   player::dsl::name,
   player::dsl::source,
   player::dsl::realm,
+  player::dsl::battletag,
 );
 
 impl PlayerRef {
@@ -55,6 +59,7 @@ impl PlayerRef {
     player::dsl::name,
     player::dsl::source,
     player::dsl::realm,
+    player::dsl::battletag,
   );
 }
 
@@ -133,4 +138,33 @@ impl PlayerBan {
     player_ban::ban_expires_at,
     player_ban::created_at,
   );
-}
\ No newline at end of file
+}
+
+/// A ban that prevents a player from joining games of an organizer (API client)
+#[derive(Debug, Queryable, Serialize, Deserialize, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::player::OrganizerBan")]
+pub struct OrganizerBan {
+  pub id: i32,
+  pub player: PlayerRef,
+  pub reason: String,
+  pub ban_expires_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type OrganizerBanColumns = (
+  organizer_ban::id,
+  PlayerRefColumns,
+  organizer_ban::reason,
+  organizer_ban::ban_expires_at,
+  organizer_ban::created_at,
+);
+
+impl OrganizerBan {
+  pub(crate) const COLUMNS: OrganizerBanColumns = (
+    organizer_ban::id,
+    PlayerRef::COLUMNS,
+    organizer_ban::reason,
+    organizer_ban::ban_expires_at,
+    organizer_ban::created_at,
+  );
+}
@@ -0,0 +1,213 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::error::*;
+use crate::settings::SettingsSource;
+
+/// Buckets are pruned when the map grows over this size
+const MAX_BUCKETS: usize = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitScope {
+  /// Every authenticated request
+  Request,
+  CreateGame,
+  /// Pre-game lobby chat messages relayed by the controller
+  LobbyChat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitQuota {
+  pub burst: u32,
+  pub per_minute: u32,
+}
+
+const REQUEST_DEFAULT: RateLimitQuota = RateLimitQuota {
+  burst: 100,
+  per_minute: 600,
+};
+const CREATE_GAME_DEFAULT: RateLimitQuota = RateLimitQuota {
+  burst: 10,
+  per_minute: 30,
+};
+const LOBBY_CHAT_DEFAULT: RateLimitQuota = RateLimitQuota {
+  burst: 5,
+  per_minute: 20,
+};
+
+impl RateLimitQuota {
+  fn from_source(source: &SettingsSource, prefix: &str, default: RateLimitQuota) -> Result<Self> {
+    let get = |name: &str, default: u32| -> Result<u32> {
+      let name = format!("{}_{}", prefix, name);
+      match source.parse(&name)? {
+        Some(0) => Err(Error::SettingInvalid {
+          name,
+          value: "0".to_string(),
+        }),
+        Some(v) => Ok(v),
+        None => Ok(default),
+      }
+    };
+    Ok(RateLimitQuota {
+      burst: get("BURST", default.burst)?,
+      per_minute: get("PER_MINUTE", default.per_minute)?,
+    })
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RateLimitConfig {
+  pub request: RateLimitQuota,
+  pub create_game: RateLimitQuota,
+  pub lobby_chat: RateLimitQuota,
+}
+
+impl Default for RateLimitConfig {
+  fn default() -> Self {
+    RateLimitConfig {
+      request: REQUEST_DEFAULT,
+      create_game: CREATE_GAME_DEFAULT,
+      lobby_chat: LOBBY_CHAT_DEFAULT,
+    }
+  }
+}
+
+impl RateLimitConfig {
+  pub(crate) fn from_source(source: &SettingsSource) -> Result<Self> {
+    Ok(RateLimitConfig {
+      request: RateLimitQuota::from_source(source, "FLO_RATE_LIMIT_REQUEST", REQUEST_DEFAULT)?,
+      create_game: RateLimitQuota::from_source(
+        source,
+        "FLO_RATE_LIMIT_CREATE_GAME",
+        CREATE_GAME_DEFAULT,
+      )?,
+      lobby_chat: RateLimitQuota::from_source(
+        source,
+        "FLO_RATE_LIMIT_LOBBY_CHAT",
+        LOBBY_CHAT_DEFAULT,
+      )?,
+    })
+  }
+
+  fn get(&self, scope: RateLimitScope) -> RateLimitQuota {
+    match scope {
+      RateLimitScope::Request => self.request,
+      RateLimitScope::CreateGame => self.create_game,
+      RateLimitScope::LobbyChat => self.lobby_chat,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitSubject {
+  Credential {
+    api_client_id: i32,
+    api_token_id: Option<i32>,
+  },
+  Ip(IpAddr),
+  Player(i32),
+}
+
+#[derive(Debug)]
+struct Bucket {
+  tokens: f64,
+  updated_at: Instant,
+}
+
+impl Bucket {
+  fn new(quota: RateLimitQuota, now: Instant) -> Self {
+    Bucket {
+      tokens: quota.burst as f64,
+      updated_at: now,
+    }
+  }
+
+  fn refill(&mut self, quota: RateLimitQuota, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * quota.per_minute as f64 / 60.0).min(quota.burst as f64);
+    self.updated_at = now;
+  }
+
+  /// Returns the duration until a token is available if the bucket is empty
+  fn take(&mut self, quota: RateLimitQuota, now: Instant) -> Option<Duration> {
+    self.refill(quota, now);
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      return None;
+    }
+    if quota.per_minute == 0 {
+      return Some(Duration::from_secs(60));
+    }
+    let secs = (1.0 - self.tokens) * 60.0 / quota.per_minute as f64;
+    Some(Duration::from_secs_f64(secs))
+  }
+
+  fn is_full(&self, quota: RateLimitQuota, now: Instant) -> bool {
+    let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+    self.tokens + elapsed * quota.per_minute as f64 / 60.0 >= quota.burst as f64
+  }
+}
+
+/// Token buckets shared by the gRPC interceptor and the handlers
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+  buckets: Mutex<HashMap<(RateLimitScope, RateLimitSubject), Bucket>>,
+}
+
+impl RateLimiter {
+  /// Takes a token from the bucket of every subject, fails if any of them is empty
+  pub fn check(&self, scope: RateLimitScope, subjects: &[RateLimitSubject]) -> Result<()> {
+    let settings = crate::settings::current();
+    let config = &settings.rate_limit;
+    let quota = config.get(scope);
+    let now = Instant::now();
+    let mut buckets = self.buckets.lock();
+
+    if buckets.len() > MAX_BUCKETS {
+      buckets.retain(|(scope, _), bucket| !bucket.is_full(config.get(*scope), now));
+    }
+
+    let retry_after = subjects
+      .iter()
+      .filter_map(|subject| {
+        buckets
+          .entry((scope, *subject))
+          .or_insert_with(|| Bucket::new(quota, now))
+          .take(quota, now)
+      })
+      .max();
+
+    match retry_after {
+      Some(retry_after) => Err(Error::RateLimited { retry_after }),
+      None => Ok(()),
+    }
+  }
+}
+
+#[test]
+fn test_bucket() {
+  let quota = RateLimitQuota {
+    burst: 2,
+    per_minute: 60,
+  };
+  let now = Instant::now();
+  let mut bucket = Bucket::new(quota, now);
+  assert_eq!(bucket.take(quota, now), None);
+  assert_eq!(bucket.take(quota, now), None);
+  assert_eq!(bucket.take(quota, now), Some(Duration::from_secs(1)));
+
+  let now = now + Duration::from_millis(500);
+  assert_eq!(bucket.take(quota, now), Some(Duration::from_millis(500)));
+  assert!(!bucket.is_full(quota, now));
+
+  let now = now + Duration::from_millis(500);
+  assert_eq!(bucket.take(quota, now), None);
+
+  let now = now + Duration::from_secs(10);
+  assert!(bucket.is_full(quota, now));
+  assert_eq!(bucket.take(quota, now), None);
+  assert_eq!(bucket.take(quota, now), None);
+  assert!(bucket.take(quota, now).is_some());
+}
@@ -9,6 +9,57 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    api_token (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Int4,
+        expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        api_token_id -> Nullable<Int4>,
+        actor -> Nullable<Text>,
+        action -> Int4,
+        target_type -> Int4,
+        target_id -> Nullable<Int4>,
+        detail -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    bnet_oauth_state (nonce) {
+        nonce -> Text,
+        player_id -> Int4,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    discord_channel (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        api_token_id -> Int4,
+        channel_id -> Text,
+        webhook_url -> Text,
+        events -> Int4,
+        host_map_pool_id -> Nullable<Int4>,
+        host_node_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     game (id) {
         id -> Int4,
@@ -33,6 +84,69 @@ diesel::table! {
         enable_ping_equalizer -> Bool,
         flo_tv_delay_override_secs -> Nullable<Int4>,
         map_twelve_p -> Bool,
+        password_hash -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    game_desync_report (game_id) {
+        game_id -> Int4,
+        data -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    game_invite (token) {
+        token -> Text,
+        game_id -> Int4,
+        created_by -> Int4,
+        used_by -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    game_replay (game_id) {
+        game_id -> Int4,
+        data -> Bytea,
+        size -> Int4,
+        created_at -> Timestamptz,
+        stats -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    game_result (game_id) {
+        game_id -> Int4,
+        valid -> Bool,
+        winner_team -> Nullable<Int4>,
+        source -> Nullable<Text>,
+        invalid_reason -> Nullable<Text>,
+        duration_ms -> Int4,
+        report -> Jsonb,
+        created_at -> Timestamptz,
+        ranked -> Bool,
+    }
+}
+
+diesel::table! {
+    game_slot_control (game_id, slot_index) {
+        game_id -> Int4,
+        slot_index -> Int4,
+        locked -> Bool,
+        reserved_player_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    game_timeline (game_id) {
+        game_id -> Int4,
+        data -> Jsonb,
+        created_at -> Timestamptz,
     }
 }
 
@@ -56,6 +170,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ladder_map (id) {
+        id -> Int4,
+        mode -> Int4,
+        map -> Jsonb,
+        enabled -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    leaderboard_snapshot (season_id, mode, taken_on, player_id) {
+        season_id -> Int4,
+        mode -> Int4,
+        taken_on -> Date,
+        player_id -> Int4,
+        rank -> Int4,
+    }
+}
+
 diesel::table! {
     map_checksum (id) {
         id -> Int4,
@@ -64,6 +198,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    map_pool (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        name -> Text,
+        maps -> Jsonb,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    matchmaking_game (game_id) {
+        game_id -> Int4,
+        mode -> Int4,
+        winner_team -> Nullable<Int4>,
+        reported_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     node (id) {
         id -> Int4,
@@ -75,6 +230,56 @@ diesel::table! {
         updated_at -> Timestamptz,
         country_id -> Text,
         disabled -> Bool,
+        region -> Text,
+        provider -> Text,
+    }
+}
+
+diesel::table! {
+    node_assignment (game_id) {
+        game_id -> Int4,
+        node_id -> Int4,
+        source -> Int4,
+        mode -> Nullable<Int4>,
+        tournament_id -> Nullable<Int4>,
+        worst_ping -> Nullable<Int4>,
+        candidates -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    node_policy (mode) {
+        mode -> Int4,
+        allowed_regions -> Jsonb,
+        max_worst_ping -> Nullable<Int4>,
+        preferred_providers -> Jsonb,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    organizer_ban (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        player_id -> Int4,
+        reason -> Text,
+        ban_expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    penalty_rule (id) {
+        id -> Int4,
+        reason -> Int4,
+        offense_count -> Int4,
+        window_secs -> Int4,
+        penalty_type -> Int4,
+        duration_secs -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -89,6 +294,9 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         api_client_id -> Int4,
+        battletag -> Nullable<Text>,
+        bnet_account_id -> Nullable<Int8>,
+        battletag_verified_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -102,6 +310,31 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    player_fingerprint (id) {
+        id -> Int4,
+        player_id -> Int4,
+        kind -> Int4,
+        hash -> Text,
+        seen_count -> Int4,
+        first_seen_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    player_link (id) {
+        id -> Int4,
+        player_id -> Int4,
+        linked_player_id -> Int4,
+        status -> Int4,
+        shared_fingerprints -> Int4,
+        reviewed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     player_mute (id) {
         id -> Int4,
@@ -111,20 +344,199 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    player_penalty (id) {
+        id -> Int4,
+        player_id -> Int4,
+        game_id -> Nullable<Int4>,
+        reason -> Int4,
+        penalty_type -> Int4,
+        expires_at -> Timestamptz,
+        appeal_status -> Int4,
+        appeal_message -> Nullable<Text>,
+        appeal_resolved_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    player_rating (id) {
+        id -> Int4,
+        player_id -> Int4,
+        mode -> Int4,
+        rating -> Int4,
+        wins -> Int4,
+        losses -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        season_id -> Int4,
+        race -> Nullable<Int4>,
+        region -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    player_rating_history (id) {
+        id -> Int4,
+        player_id -> Int4,
+        mode -> Int4,
+        game_id -> Int4,
+        rating_before -> Int4,
+        rating_after -> Int4,
+        created_at -> Timestamptz,
+        season_id -> Int4,
+    }
+}
+
+diesel::table! {
+    season (id) {
+        id -> Int4,
+        name -> Text,
+        status -> Int4,
+        starts_at -> Timestamptz,
+        carry_over_percent -> Int4,
+        started_at -> Nullable<Timestamptz>,
+        ended_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    stats_aggregate (period, period_start, kind, key, sub_key) {
+        period -> Int4,
+        period_start -> Timestamptz,
+        kind -> Int4,
+        key -> Text,
+        sub_key -> Text,
+        games -> Int4,
+        wins -> Int4,
+        invalid -> Int4,
+        desyncs -> Int4,
+        total_duration_ms -> Int8,
+        updated_at -> Timestamptz,
+        hosted -> Int4,
+    }
+}
+
+diesel::table! {
+    tournament (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        name -> Text,
+        format -> Int4,
+        status -> Int4,
+        map -> Jsonb,
+        node_id -> Nullable<Int4>,
+        check_in_window_secs -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        map_pool_id -> Nullable<Int4>,
+        chat_rules -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    tournament_match (id) {
+        id -> Int4,
+        tournament_id -> Int4,
+        round -> Int4,
+        position -> Int4,
+        group_index -> Nullable<Int4>,
+        player1_id -> Nullable<Int4>,
+        player2_id -> Nullable<Int4>,
+        player1_checked_in_at -> Nullable<Timestamptz>,
+        player2_checked_in_at -> Nullable<Timestamptz>,
+        scheduled_at -> Nullable<Timestamptz>,
+        status -> Int4,
+        game_id -> Nullable<Int4>,
+        winner_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::joinable!(api_token -> api_client (api_client_id));
+diesel::joinable!(audit_log -> api_client (api_client_id));
+diesel::joinable!(audit_log -> api_token (api_token_id));
+diesel::joinable!(bnet_oauth_state -> player (player_id));
+diesel::joinable!(discord_channel -> api_client (api_client_id));
+diesel::joinable!(discord_channel -> api_token (api_token_id));
+diesel::joinable!(discord_channel -> map_pool (host_map_pool_id));
+diesel::joinable!(discord_channel -> node (host_node_id));
 diesel::joinable!(game -> node (node_id));
 diesel::joinable!(game -> player (created_by));
+diesel::joinable!(game_desync_report -> game (game_id));
+diesel::joinable!(game_invite -> game (game_id));
+diesel::joinable!(game_replay -> game (game_id));
+diesel::joinable!(game_result -> game (game_id));
+diesel::joinable!(game_slot_control -> game (game_id));
+diesel::joinable!(game_slot_control -> player (reserved_player_id));
+diesel::joinable!(game_timeline -> game (game_id));
 diesel::joinable!(game_used_slot -> game (game_id));
 diesel::joinable!(game_used_slot -> player (player_id));
+diesel::joinable!(leaderboard_snapshot -> player (player_id));
+diesel::joinable!(leaderboard_snapshot -> season (season_id));
+diesel::joinable!(map_pool -> api_client (api_client_id));
+diesel::joinable!(organizer_ban -> api_client (api_client_id));
+diesel::joinable!(organizer_ban -> player (player_id));
 diesel::joinable!(player -> api_client (api_client_id));
+diesel::joinable!(matchmaking_game -> game (game_id));
+diesel::joinable!(node_assignment -> game (game_id));
+diesel::joinable!(node_assignment -> node (node_id));
+diesel::joinable!(node_assignment -> tournament (tournament_id));
 diesel::joinable!(player_ban -> player (player_id));
+diesel::joinable!(player_fingerprint -> player (player_id));
+diesel::joinable!(player_link -> player (player_id));
+diesel::joinable!(player_penalty -> game (game_id));
+diesel::joinable!(player_penalty -> player (player_id));
+diesel::joinable!(player_rating -> player (player_id));
+diesel::joinable!(player_rating -> season (season_id));
+diesel::joinable!(player_rating_history -> game (game_id));
+diesel::joinable!(player_rating_history -> player (player_id));
+diesel::joinable!(player_rating_history -> season (season_id));
+
+diesel::joinable!(tournament -> api_client (api_client_id));
+diesel::joinable!(tournament -> map_pool (map_pool_id));
+diesel::joinable!(tournament -> node (node_id));
+diesel::joinable!(tournament_match -> game (game_id));
+diesel::joinable!(tournament_match -> tournament (tournament_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     api_client,
+    api_token,
+    audit_log,
+    bnet_oauth_state,
+    discord_channel,
     game,
+    game_desync_report,
+    game_invite,
+    game_replay,
+    game_result,
+    game_slot_control,
+    game_timeline,
     game_used_slot,
+    ladder_map,
+    leaderboard_snapshot,
     map_checksum,
+    map_pool,
+    matchmaking_game,
     node,
+    node_assignment,
+    node_policy,
+    organizer_ban,
+    penalty_rule,
     player,
     player_ban,
+    player_fingerprint,
+    player_link,
     player_mute,
+    player_penalty,
+    player_rating,
+    player_rating_history,
+    season,
+    stats_aggregate,
+    tournament,
+    tournament_match,
 );
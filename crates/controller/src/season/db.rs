@@ -0,0 +1,347 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::dsl::{max, sql};
+use diesel::pg::expression::dsl::any;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Date, Integer};
+use s2_grpc_utils::S2ProtoUnpack;
+use std::collections::BTreeMap;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::Race;
+use crate::matchmaking::{LadderMode, PlayerRating};
+use crate::player::PlayerRef;
+use crate::schema::{leaderboard_snapshot, player, player_rating, season};
+use crate::season::leaderboard::LeaderboardCursor;
+use crate::season::types::soft_reset;
+use crate::season::{Leaderboard, LeaderboardEntry, Season, SeasonStatus};
+
+const MAX_LEADERBOARD_TAKE: i64 = 100;
+
+pub fn get_active_id(conn: &DbConn) -> Result<i32> {
+  season::table
+    .select(season::id)
+    .filter(season::status.eq(SeasonStatus::Active))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::SeasonNotFound)
+}
+
+pub fn list(conn: &DbConn) -> Result<Vec<Season>> {
+  season::table
+    .select(Season::COLUMNS)
+    .order(season::starts_at.desc())
+    .load(conn)
+    .map_err(Into::into)
+}
+
+#[derive(Debug, S2ProtoUnpack)]
+#[s2_grpc(message_type = "flo_grpc::controller::CreateSeasonRequest")]
+pub struct CreateSeasonParams {
+  pub name: String,
+  pub starts_at: Option<DateTime<Utc>>,
+  pub carry_over_percent: i32,
+}
+
+/// Schedules a season, it starts on the next rollover after `starts_at`
+pub fn create(conn: &DbConn, params: CreateSeasonParams) -> Result<Season> {
+  #[derive(Insertable)]
+  #[table_name = "season"]
+  struct Insert {
+    name: String,
+    starts_at: DateTime<Utc>,
+    carry_over_percent: i32,
+  }
+
+  if params.name.is_empty() || !(0..=100).contains(&params.carry_over_percent) {
+    return Err(Error::SeasonInvalid);
+  }
+
+  diesel::insert_into(season::table)
+    .values(&Insert {
+      name: params.name,
+      starts_at: params.starts_at.unwrap_or_else(Utc::now),
+      carry_over_percent: params.carry_over_percent,
+    })
+    .returning(Season::COLUMNS)
+    .get_result(conn)
+    .map_err(Into::into)
+}
+
+/// Starts the earliest due scheduled season.
+/// The active season is archived and its ratings are carried over with a soft reset.
+pub fn rollover(conn: &DbConn) -> Result<Option<Season>> {
+  #[derive(Insertable)]
+  #[table_name = "player_rating"]
+  struct Insert {
+    season_id: i32,
+    player_id: i32,
+    mode: LadderMode,
+    rating: i32,
+    wins: i32,
+    losses: i32,
+  }
+
+  conn.transaction(|| {
+    let next: Option<Season> = season::table
+      .select(Season::COLUMNS)
+      .filter(
+        season::status
+          .eq(SeasonStatus::Scheduled)
+          .and(season::starts_at.le(sql("now()"))),
+      )
+      .order(season::starts_at)
+      .for_update()
+      .first(conn)
+      .optional()?;
+    let next = match next {
+      Some(next) => next,
+      None => return Ok(None),
+    };
+
+    let prev_id: Option<i32> =
+      diesel::update(season::table.filter(season::status.eq(SeasonStatus::Active)))
+        .set((
+          season::status.eq(SeasonStatus::Ended),
+          season::ended_at.eq(sql("now()")),
+        ))
+        .returning(season::id)
+        .get_result(conn)
+        .optional()?;
+
+    if let Some(prev_id) = prev_id {
+      let ratings: Vec<PlayerRating> = player_rating::table
+        .select(PlayerRating::COLUMNS)
+        .filter(player_rating::season_id.eq(prev_id))
+        .load(conn)?;
+      let inserts: Vec<_> = ratings
+        .into_iter()
+        .map(|r| Insert {
+          season_id: next.id,
+          player_id: r.player_id,
+          mode: r.mode,
+          rating: soft_reset(r.rating, next.carry_over_percent),
+          wins: 0,
+          losses: 0,
+        })
+        .collect();
+      for chunk in inserts.chunks(1000) {
+        diesel::insert_into(player_rating::table)
+          .values(chunk)
+          .execute(conn)?;
+      }
+    }
+
+    diesel::update(season::table.find(next.id))
+      .set((
+        season::status.eq(SeasonStatus::Active),
+        season::started_at.eq(sql("now()")),
+      ))
+      .returning(Season::COLUMNS)
+      .get_result(conn)
+      .map(Some)
+      .map_err(Into::into)
+  })
+}
+
+#[derive(Debug)]
+pub struct GetLeaderboardParams {
+  /// Defaults to the active season
+  pub season_id: Option<i32>,
+  pub mode: LadderMode,
+  pub region: Option<String>,
+  pub race: Option<Race>,
+  pub cursor: Option<String>,
+  pub take: Option<i64>,
+}
+
+/// Ranked players of a season, ranks are relative to the filtered list
+pub fn get_leaderboard(conn: &DbConn, params: &GetLeaderboardParams) -> Result<Leaderboard> {
+  use player_rating::dsl;
+
+  let season_id = match params.season_id {
+    Some(id) => id,
+    None => get_active_id(conn)?,
+  };
+  let take = params.take.unwrap_or(50).clamp(1, MAX_LEADERBOARD_TAKE);
+  let cursor = params
+    .cursor
+    .as_ref()
+    .map(|v| LeaderboardCursor::decode(v))
+    .transpose()?;
+  let filtered = params.region.is_some() || params.race.is_some();
+
+  let filter = |q: BoxedRatingQuery<'static>| {
+    let mut q = q.filter(
+      dsl::season_id
+        .eq(season_id)
+        .and(dsl::mode.eq(params.mode))
+        .and((dsl::wins + dsl::losses).gt(0)),
+    );
+    if let Some(ref region) = params.region {
+      q = q.filter(dsl::region.eq(region.clone()));
+    }
+    if let Some(race) = params.race {
+      q = q.filter(dsl::race.eq(Some(race)));
+    }
+    q
+  };
+
+  let mut q = filter(player_rating::table.into_boxed());
+  let mut base_rank = 1;
+  if let Some(cursor) = cursor {
+    base_rank += filter(player_rating::table.into_boxed())
+      .filter(
+        dsl::rating.gt(cursor.rating).or(
+          dsl::rating
+            .eq(cursor.rating)
+            .and(dsl::player_id.le(cursor.player_id)),
+        ),
+      )
+      .count()
+      .get_result::<i64>(conn)? as i32;
+    q = q.filter(
+      dsl::rating.lt(cursor.rating).or(
+        dsl::rating
+          .eq(cursor.rating)
+          .and(dsl::player_id.gt(cursor.player_id)),
+      ),
+    );
+  }
+
+  let rows: Vec<(i32, i32, i32, i32)> = q
+    .select((dsl::player_id, dsl::rating, dsl::wins, dsl::losses))
+    .order((dsl::rating.desc(), dsl::player_id))
+    .limit(take + 1)
+    .load(conn)?;
+  let has_more = rows.len() > take as usize;
+  let rows = &rows[..std::cmp::min(rows.len(), take as usize)];
+
+  let player_ids: Vec<i32> = rows.iter().map(|r| r.0).collect();
+  let players: BTreeMap<i32, PlayerRef> = player::table
+    .select(PlayerRef::COLUMNS)
+    .filter(player::id.eq(any(&player_ids)))
+    .load::<PlayerRef>(conn)?
+    .into_iter()
+    .map(|p| (p.id, p))
+    .collect();
+  let prev_ranks = if filtered {
+    BTreeMap::new()
+  } else {
+    get_snapshot_ranks(conn, season_id, params.mode, &player_ids)?
+  };
+
+  let entries = rows
+    .iter()
+    .enumerate()
+    .filter_map(|(idx, (player_id, rating, wins, losses))| {
+      let rank = base_rank + idx as i32;
+      Some(LeaderboardEntry {
+        rank,
+        player: players.get(player_id)?.clone(),
+        rating: *rating,
+        wins: *wins,
+        losses: *losses,
+        rank_change: prev_ranks.get(player_id).map(|prev| prev - rank),
+      })
+    })
+    .collect();
+
+  let next_cursor = if has_more {
+    rows.last().map(|(player_id, rating, _, _)| {
+      LeaderboardCursor {
+        rating: *rating,
+        player_id: *player_id,
+      }
+      .encode()
+    })
+  } else {
+    None
+  };
+
+  Ok(Leaderboard {
+    season_id,
+    entries,
+    next_cursor,
+  })
+}
+
+type BoxedRatingQuery<'a> = player_rating::BoxedQuery<'a, Pg>;
+
+/// Ranks in the most recent snapshot taken at least a week ago
+fn get_snapshot_ranks(
+  conn: &DbConn,
+  season_id: i32,
+  mode: LadderMode,
+  player_ids: &[i32],
+) -> Result<BTreeMap<i32, i32>> {
+  let taken_on: Option<NaiveDate> = leaderboard_snapshot::table
+    .select(max(leaderboard_snapshot::taken_on))
+    .filter(
+      leaderboard_snapshot::season_id
+        .eq(season_id)
+        .and(leaderboard_snapshot::mode.eq(mode))
+        .and(leaderboard_snapshot::taken_on.le(Utc::today().naive_utc() - Duration::days(7))),
+    )
+    .first(conn)?;
+  let taken_on = match taken_on {
+    Some(v) => v,
+    None => return Ok(BTreeMap::new()),
+  };
+  leaderboard_snapshot::table
+    .select((leaderboard_snapshot::player_id, leaderboard_snapshot::rank))
+    .filter(
+      leaderboard_snapshot::season_id
+        .eq(season_id)
+        .and(leaderboard_snapshot::mode.eq(mode))
+        .and(leaderboard_snapshot::taken_on.eq(taken_on))
+        .and(leaderboard_snapshot::player_id.eq(any(player_ids))),
+    )
+    .load::<(i32, i32)>(conn)
+    .map(|rows| rows.into_iter().collect())
+    .map_err(Into::into)
+}
+
+/// Records the daily ranks of the active season, snapshots older than two weeks are removed
+pub fn take_snapshot(conn: &DbConn) -> Result<()> {
+  let season_id = get_active_id(conn)?;
+  let today = Utc::today().naive_utc();
+  conn.transaction(|| {
+    let taken = leaderboard_snapshot::table
+      .filter(
+        leaderboard_snapshot::season_id
+          .eq(season_id)
+          .and(leaderboard_snapshot::taken_on.eq(today)),
+      )
+      .count()
+      .get_result::<i64>(conn)?;
+    if taken > 0 {
+      return Ok(());
+    }
+
+    diesel::sql_query(
+      r#"
+      insert into leaderboard_snapshot(season_id, mode, taken_on, player_id, rank)
+      select
+          season_id,
+          mode,
+          $2,
+          player_id,
+          row_number() over (partition by mode order by rating desc, player_id)
+      from player_rating
+      where season_id = $1 and wins + losses > 0
+      "#,
+    )
+    .bind::<Integer, _>(season_id)
+    .bind::<Date, _>(today)
+    .execute(conn)?;
+
+    diesel::delete(
+      leaderboard_snapshot::table
+        .filter(leaderboard_snapshot::taken_on.lt(today - Duration::days(14))),
+    )
+    .execute(conn)?;
+    Ok(())
+  })
+}
@@ -0,0 +1,38 @@
+use crate::error::*;
+
+/// Position after the last entry of a page.
+/// Entries are ordered by rating descending then player id, so the cursor stays valid while ratings change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeaderboardCursor {
+  pub rating: i32,
+  pub player_id: i32,
+}
+
+impl LeaderboardCursor {
+  pub fn encode(&self) -> String {
+    format!("{}:{}", self.rating, self.player_id)
+  }
+
+  pub fn decode(value: &str) -> Result<Self> {
+    let mut parts = value.splitn(2, ':');
+    let rating = parts.next().and_then(|v| v.parse().ok());
+    let player_id = parts.next().and_then(|v| v.parse().ok());
+    match (rating, player_id) {
+      (Some(rating), Some(player_id)) => Ok(LeaderboardCursor { rating, player_id }),
+      _ => Err(Error::LeaderboardCursorInvalid),
+    }
+  }
+}
+
+#[test]
+fn test_cursor() {
+  let cursor = LeaderboardCursor {
+    rating: -12,
+    player_id: 345,
+  };
+  assert_eq!(cursor.encode(), "-12:345");
+  assert_eq!(LeaderboardCursor::decode("-12:345").unwrap(), cursor);
+  assert!(LeaderboardCursor::decode("").is_err());
+  assert!(LeaderboardCursor::decode("1500").is_err());
+  assert!(LeaderboardCursor::decode("1500:x").is_err());
+}
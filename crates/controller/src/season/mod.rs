@@ -0,0 +1,7 @@
+pub mod db;
+pub mod leaderboard;
+pub(crate) mod state;
+mod types;
+
+pub use state::SeasonScheduler;
+pub use types::*;
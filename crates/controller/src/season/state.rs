@@ -0,0 +1,69 @@
+use bs_diesel_utils::ExecutorRef;
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::error::*;
+use crate::state::Data;
+
+const ROLLOVER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Starts scheduled seasons when they are due and takes the daily leaderboard snapshots
+pub struct SeasonScheduler {
+  db: ExecutorRef,
+}
+
+impl SeasonScheduler {
+  async fn rollover(&mut self) -> Result<()> {
+    let started = self
+      .db
+      .exec(|conn| crate::season::db::rollover(conn))
+      .await?;
+    if let Some(season) = started {
+      tracing::info!(season_id = season.id, "season started: {}", season.name);
+    }
+    self
+      .db
+      .exec(|conn| crate::season::db::take_snapshot(conn))
+      .await?;
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl Actor for SeasonScheduler {
+  async fn started(&mut self, ctx: &mut Context<Self>) {
+    self.handle(ctx, RolloverTick).await;
+  }
+}
+
+#[async_trait]
+impl Service<Data> for SeasonScheduler {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    Ok(SeasonScheduler {
+      db: registry.data().db.clone(),
+    })
+  }
+}
+
+struct RolloverTick;
+
+impl Message for RolloverTick {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<RolloverTick> for SeasonScheduler {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: RolloverTick) {
+    if let Err(err) = self.rollover().await {
+      tracing::error!("season rollover: {}", err);
+    }
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(ROLLOVER_INTERVAL).await;
+      addr.notify(RolloverTick).await.ok();
+    });
+  }
+}
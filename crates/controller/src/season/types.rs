@@ -0,0 +1,90 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::matchmaking::DEFAULT_RATING;
+use crate::player::PlayerRef;
+use crate::schema::season;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::SeasonStatus")]
+pub enum SeasonStatus {
+  /// Starts at `starts_at`
+  Scheduled = 0,
+  Active = 1,
+  /// Ratings are kept as the archived leaderboard
+  Ended = 2,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::Season")]
+pub struct Season {
+  pub id: i32,
+  pub name: String,
+  #[s2_grpc(proto_enum)]
+  pub status: SeasonStatus,
+  pub starts_at: DateTime<Utc>,
+  /// Percentage of the rating difference from the default rating kept by the soft reset
+  pub carry_over_percent: i32,
+  pub started_at: Option<DateTime<Utc>>,
+  pub ended_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type SeasonColumns = (
+  season::id,
+  season::name,
+  season::status,
+  season::starts_at,
+  season::carry_over_percent,
+  season::started_at,
+  season::ended_at,
+  season::created_at,
+);
+
+impl Season {
+  pub(crate) const COLUMNS: SeasonColumns = (
+    season::id,
+    season::name,
+    season::status,
+    season::starts_at,
+    season::carry_over_percent,
+    season::started_at,
+    season::ended_at,
+    season::created_at,
+  );
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::LeaderboardEntry")]
+pub struct LeaderboardEntry {
+  pub rank: i32,
+  pub player: PlayerRef,
+  pub rating: i32,
+  pub wins: i32,
+  pub losses: i32,
+  /// Positive if the player moved up since the snapshot a week ago, unset for filtered queries
+  pub rank_change: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct Leaderboard {
+  pub season_id: i32,
+  pub entries: Vec<LeaderboardEntry>,
+  pub next_cursor: Option<String>,
+}
+
+/// Moves a rating towards the default rating at the start of a season
+pub(crate) fn soft_reset(rating: i32, carry_over_percent: i32) -> i32 {
+  DEFAULT_RATING + (rating - DEFAULT_RATING) * carry_over_percent / 100
+}
+
+#[test]
+fn test_soft_reset() {
+  assert_eq!(soft_reset(DEFAULT_RATING + 400, 50), DEFAULT_RATING + 200);
+  assert_eq!(soft_reset(DEFAULT_RATING - 300, 50), DEFAULT_RATING - 150);
+  assert_eq!(soft_reset(DEFAULT_RATING + 400, 0), DEFAULT_RATING);
+  assert_eq!(soft_reset(DEFAULT_RATING + 400, 100), DEFAULT_RATING + 400);
+}
@@ -0,0 +1,115 @@
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::game::feature::FeatureRollout;
+use crate::game::lobby::LobbyTimeout;
+use crate::game::replay::ReplayRetention;
+use crate::rate_limit::RateLimitConfig;
+use flo_util::settings::SettingsStore;
+pub use flo_util::settings::{ReloadReport, SettingsSource};
+
+/// Path of an optional env file that overrides the process environment,
+/// read at startup and on every reload
+const SETTINGS_FILE_ENV: &str = "FLO_CONTROLLER_SETTINGS_FILE";
+
+/// Settings only read when the process starts
+const RESTART_REQUIRED: &[&str] = &[
+  "DATABASE_URL",
+  "JWT_SECRET_BASE64",
+  "BNET_CLIENT_ID",
+  "BNET_CLIENT_SECRET",
+  "BNET_REDIRECT_URI",
+  "BNET_OAUTH_URL",
+];
+
+/// Settings that can be changed without restarting the process
+#[derive(Debug, Default, PartialEq)]
+pub struct Settings {
+  pub rate_limit: RateLimitConfig,
+  pub replay_retention: ReplayRetention,
+  pub feature_rollout: FeatureRollout,
+  pub lobby_timeout: LobbyTimeout,
+}
+
+impl flo_util::settings::Settings for Settings {
+  type Error = Error;
+
+  fn load(source: &SettingsSource) -> Result<Self> {
+    Ok(Settings {
+      rate_limit: RateLimitConfig::from_source(source)?,
+      replay_retention: ReplayRetention::from_source(source)?,
+      feature_rollout: FeatureRollout::from_source(source)?,
+      lobby_timeout: LobbyTimeout::from_source(source)?,
+    })
+  }
+
+  fn diff(&self, other: &Self) -> Vec<String> {
+    let mut changed = vec![];
+    if self.rate_limit.request != other.rate_limit.request {
+      changed.push("FLO_RATE_LIMIT_REQUEST".to_string());
+    }
+    if self.rate_limit.create_game != other.rate_limit.create_game {
+      changed.push("FLO_RATE_LIMIT_CREATE_GAME".to_string());
+    }
+    if self.rate_limit.lobby_chat != other.rate_limit.lobby_chat {
+      changed.push("FLO_RATE_LIMIT_LOBBY_CHAT".to_string());
+    }
+    if self.replay_retention != other.replay_retention {
+      changed.push("FLO_REPLAY_RETENTION".to_string());
+    }
+    changed.extend(self.feature_rollout.diff(&other.feature_rollout));
+    if self.lobby_timeout != other.lobby_timeout {
+      changed.push("FLO_LOBBY_IDLE_TIMEOUT_MINUTES".to_string());
+    }
+    changed
+  }
+}
+
+static SETTINGS: Lazy<SettingsStore<Settings>> =
+  Lazy::new(|| SettingsStore::new(SETTINGS_FILE_ENV, RESTART_REQUIRED));
+
+/// Loads the settings, must be called before anything reads the environment
+pub fn init() -> Result<()> {
+  SETTINGS.init()
+}
+
+/// Re-reads and validates the settings, nothing is changed if any value is invalid
+pub fn reload() -> Result<ReloadReport> {
+  SETTINGS.reload()
+}
+
+pub fn current() -> Arc<Settings> {
+  SETTINGS.current()
+}
+
+#[test]
+fn test_settings_source() {
+  use flo_util::settings::Settings as _;
+
+  let source = SettingsSource::from_env_file(
+    r#"
+    # rate limits
+    FLO_RATE_LIMIT_REQUEST_BURST=20
+    export FLO_REPLAY_RETENTION_DAYS="30"
+    "#,
+  );
+  let settings = Settings::load(&source).unwrap();
+  assert_eq!(settings.rate_limit.request.burst, 20);
+  assert_eq!(settings.replay_retention.max_age_days, Some(30));
+  assert_eq!(
+    Settings::default().diff(&settings),
+    vec!["FLO_RATE_LIMIT_REQUEST", "FLO_REPLAY_RETENTION"]
+  );
+
+  for invalid in &[
+    "FLO_RATE_LIMIT_CREATE_GAME_PER_MINUTE=0",
+    "FLO_LOBBY_IDLE_TIMEOUT_MINUTES=0",
+  ] {
+    let source = SettingsSource::from_env_file(invalid);
+    assert!(matches!(
+      Settings::load(&source),
+      Err(Error::SettingInvalid { .. })
+    ));
+  }
+}
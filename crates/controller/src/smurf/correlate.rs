@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+use crate::smurf::{Fingerprint, FingerprintKind};
+
+/// Counts the fingerprints each other player shares with `fingerprints`.
+/// `rows` are the `(player_id, kind, hash)` records of other players.
+pub(crate) fn count_shared(
+  fingerprints: &[Fingerprint],
+  rows: &[(i32, FingerprintKind, String)],
+) -> BTreeMap<i32, i32> {
+  let mut map = BTreeMap::new();
+  for (player_id, kind, hash) in rows {
+    if fingerprints
+      .iter()
+      .any(|f| f.kind == *kind && &f.hash == hash)
+    {
+      *map.entry(*player_id).or_insert(0) += 1;
+    }
+  }
+  map
+}
+
+/// Links are stored once per pair with the lower player id first
+pub(crate) fn ordered_pair(player_id: i32, other_player_id: i32) -> (i32, i32) {
+  if player_id < other_player_id {
+    (player_id, other_player_id)
+  } else {
+    (other_player_id, player_id)
+  }
+}
+
+#[test]
+fn test_count_shared() {
+  let fingerprints = vec![
+    Fingerprint {
+      kind: FingerprintKind::Install,
+      hash: "a".to_string(),
+    },
+    Fingerprint {
+      kind: FingerprintKind::Machine,
+      hash: "b".to_string(),
+    },
+  ];
+  let rows = vec![
+    (2, FingerprintKind::Install, "a".to_string()),
+    (2, FingerprintKind::Machine, "b".to_string()),
+    (3, FingerprintKind::Machine, "b".to_string()),
+    // same hash, different kind
+    (4, FingerprintKind::Install, "b".to_string()),
+    (5, FingerprintKind::Install, "c".to_string()),
+  ];
+  let map = count_shared(&fingerprints, &rows);
+  assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(2, 2), (3, 1)]);
+  assert_eq!(ordered_pair(5, 3), (3, 5));
+  assert_eq!(ordered_pair(3, 5), (3, 5));
+}
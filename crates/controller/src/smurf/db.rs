@@ -0,0 +1,203 @@
+use diesel::dsl::sql;
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::{player, player_fingerprint, player_link};
+use crate::smurf::correlate::{count_shared, ordered_pair};
+use crate::smurf::{Fingerprint, FingerprintKind, PlayerLink, PlayerLinkStatus};
+
+/// Records the fingerprints sent by a client at login
+/// and flags other players of the same API client sharing any of them.
+/// Returns the ids of the flagged players.
+pub fn record_fingerprints(
+  conn: &DbConn,
+  player_id: i32,
+  fingerprints: &[Fingerprint],
+) -> Result<Vec<i32>> {
+  use diesel::pg::upsert::excluded;
+
+  if fingerprints.is_empty() {
+    return Ok(vec![]);
+  }
+
+  conn.transaction(|| {
+    for fingerprint in fingerprints {
+      diesel::insert_into(player_fingerprint::table)
+        .values((
+          player_fingerprint::player_id.eq(player_id),
+          player_fingerprint::kind.eq(fingerprint.kind),
+          player_fingerprint::hash.eq(&fingerprint.hash),
+        ))
+        .on_conflict((
+          player_fingerprint::player_id,
+          player_fingerprint::kind,
+          player_fingerprint::hash,
+        ))
+        .do_update()
+        .set((
+          player_fingerprint::seen_count.eq(player_fingerprint::seen_count + 1),
+          player_fingerprint::last_seen_at.eq(sql("now()")),
+        ))
+        .execute(conn)?;
+    }
+
+    let api_client_id: i32 = player::table
+      .find(player_id)
+      .select(player::api_client_id)
+      .first(conn)?;
+    let hashes: Vec<&str> = fingerprints.iter().map(|f| f.hash.as_str()).collect();
+    let rows: Vec<(i32, FingerprintKind, String)> = player_fingerprint::table
+      .inner_join(player::table)
+      .select((
+        player_fingerprint::player_id,
+        player_fingerprint::kind,
+        player_fingerprint::hash,
+      ))
+      .filter(
+        player_fingerprint::hash
+          .eq(any(hashes))
+          .and(player_fingerprint::player_id.ne(player_id))
+          .and(player::api_client_id.eq(api_client_id)),
+      )
+      .load(conn)?;
+
+    let shared = count_shared(fingerprints, &rows);
+    for (other_player_id, count) in &shared {
+      let (a, b) = ordered_pair(player_id, *other_player_id);
+      diesel::insert_into(player_link::table)
+        .values((
+          player_link::player_id.eq(a),
+          player_link::linked_player_id.eq(b),
+          player_link::status.eq(PlayerLinkStatus::Suspected),
+          player_link::shared_fingerprints.eq(count),
+        ))
+        .on_conflict((player_link::player_id, player_link::linked_player_id))
+        .do_update()
+        .set(player_link::shared_fingerprints.eq(excluded(player_link::shared_fingerprints)))
+        .execute(conn)?;
+    }
+
+    Ok(shared.into_iter().map(|(id, _)| id).collect())
+  })
+}
+
+#[derive(Debug, Default)]
+pub struct ListPlayerLinksParams {
+  pub player_id: Option<i32>,
+  pub status: Option<PlayerLinkStatus>,
+  pub next_id: Option<i32>,
+}
+
+pub struct ListPlayerLinks {
+  pub links: Vec<PlayerLink>,
+  pub next_id: Option<i32>,
+}
+
+pub fn list(
+  conn: &DbConn,
+  api_client_id: i32,
+  params: &ListPlayerLinksParams,
+) -> Result<ListPlayerLinks> {
+  const PAGE_SIZE: i64 = 200;
+  let mut q = player_link::table
+    .inner_join(player::table)
+    .select(PlayerLink::COLUMNS)
+    .filter(player::api_client_id.eq(api_client_id))
+    .order(player_link::id)
+    .limit(PAGE_SIZE + 1)
+    .into_boxed();
+
+  if let Some(player_id) = params.player_id {
+    q = q.filter(
+      player_link::player_id
+        .eq(player_id)
+        .or(player_link::linked_player_id.eq(player_id)),
+    );
+  }
+
+  if let Some(status) = params.status {
+    q = q.filter(player_link::status.eq(status));
+  }
+
+  if let Some(id) = params.next_id {
+    q = q.filter(player_link::id.ge(id));
+  }
+
+  let mut links = q.load::<PlayerLink>(conn)?;
+  let next_id = if links.len() > PAGE_SIZE as usize {
+    let id = links.last().map(|row| row.id);
+    links.truncate(PAGE_SIZE as usize);
+    id
+  } else {
+    None
+  };
+
+  Ok(ListPlayerLinks { links, next_id })
+}
+
+/// Confirms two accounts are owned by the same person
+pub fn link(
+  conn: &DbConn,
+  api_client_id: i32,
+  player_id: i32,
+  linked_player_id: i32,
+) -> Result<PlayerLink> {
+  let (a, b) = check_pair(conn, api_client_id, player_id, linked_player_id)?;
+  diesel::insert_into(player_link::table)
+    .values((
+      player_link::player_id.eq(a),
+      player_link::linked_player_id.eq(b),
+      player_link::status.eq(PlayerLinkStatus::Confirmed),
+      player_link::reviewed_at.eq(sql("now()")),
+    ))
+    .on_conflict((player_link::player_id, player_link::linked_player_id))
+    .do_update()
+    .set((
+      player_link::status.eq(PlayerLinkStatus::Confirmed),
+      player_link::reviewed_at.eq(sql("now()")),
+    ))
+    .returning(PlayerLink::COLUMNS)
+    .get_result(conn)
+    .map_err(Into::into)
+}
+
+/// Dismisses a link, the pair will not be flagged again
+pub fn unlink(
+  conn: &DbConn,
+  api_client_id: i32,
+  player_id: i32,
+  linked_player_id: i32,
+) -> Result<PlayerLink> {
+  let (a, b) = check_pair(conn, api_client_id, player_id, linked_player_id)?;
+  diesel::update(
+    player_link::table.filter(
+      player_link::player_id
+        .eq(a)
+        .and(player_link::linked_player_id.eq(b)),
+    ),
+  )
+  .set((
+    player_link::status.eq(PlayerLinkStatus::Dismissed),
+    player_link::reviewed_at.eq(sql("now()")),
+  ))
+  .returning(PlayerLink::COLUMNS)
+  .get_result(conn)
+  .optional()?
+  .ok_or_else(|| Error::PlayerLinkNotFound)
+}
+
+fn check_pair(
+  conn: &DbConn,
+  api_client_id: i32,
+  player_id: i32,
+  linked_player_id: i32,
+) -> Result<(i32, i32)> {
+  if player_id == linked_player_id {
+    return Err(Error::PlayerLinkInvalid);
+  }
+  crate::player::db::check_player_api_client_id(conn, api_client_id, player_id)?;
+  crate::player::db::check_player_api_client_id(conn, api_client_id, linked_player_id)?;
+  Ok(ordered_pair(player_id, linked_player_id))
+}
@@ -0,0 +1,5 @@
+mod correlate;
+pub mod db;
+mod types;
+
+pub use types::*;
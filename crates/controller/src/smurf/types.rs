@@ -0,0 +1,114 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use flo_net::proto::flo_connect::ClientFingerprint;
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::player_link;
+
+/// Clients send at most this many fingerprints, extra ones are ignored
+pub const MAX_FINGERPRINTS: usize = 8;
+
+#[derive(
+  Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, BSDieselEnum, S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_net::proto::flo_connect::ClientFingerprintKind")]
+pub enum FingerprintKind {
+  /// Random id generated on the first run of a client installation
+  Install = 0,
+  Machine = 1,
+}
+
+/// Salted SHA-256 digest of a client signal, the raw value is never sent to the controller
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+  pub kind: FingerprintKind,
+  pub hash: String,
+}
+
+impl Fingerprint {
+  /// Returns `None` if the hash is not a hex encoded SHA-256 digest
+  pub fn from_packet(packet: ClientFingerprint) -> Option<Self> {
+    let kind = FingerprintKind::unpack_enum(packet.kind());
+    let hash = packet.hash.to_lowercase();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+      return None;
+    }
+    Some(Fingerprint { kind, hash })
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::PlayerLinkStatus")]
+pub enum PlayerLinkStatus {
+  /// Flagged by shared fingerprints, waiting for organizer review
+  Suspected = 0,
+  Confirmed = 1,
+  /// Reviewed as unrelated accounts, will not be flagged again
+  Dismissed = 2,
+}
+
+/// A pair of accounts likely owned by the same person, `player_id` is always the lower id
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::PlayerLink")]
+pub struct PlayerLink {
+  pub id: i32,
+  pub player_id: i32,
+  pub linked_player_id: i32,
+  #[s2_grpc(proto_enum)]
+  pub status: PlayerLinkStatus,
+  pub shared_fingerprints: i32,
+  pub reviewed_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+pub(crate) type PlayerLinkColumns = (
+  player_link::id,
+  player_link::player_id,
+  player_link::linked_player_id,
+  player_link::status,
+  player_link::shared_fingerprints,
+  player_link::reviewed_at,
+  player_link::created_at,
+  player_link::updated_at,
+);
+
+impl PlayerLink {
+  pub(crate) const COLUMNS: PlayerLinkColumns = (
+    player_link::id,
+    player_link::player_id,
+    player_link::linked_player_id,
+    player_link::status,
+    player_link::shared_fingerprints,
+    player_link::reviewed_at,
+    player_link::created_at,
+    player_link::updated_at,
+  );
+}
+
+#[test]
+fn test_fingerprint_from_packet() {
+  use flo_net::proto::flo_connect::ClientFingerprintKind;
+  let hash = "AB".repeat(32);
+  let fingerprint = Fingerprint::from_packet(ClientFingerprint {
+    kind: ClientFingerprintKind::Machine.into(),
+    hash: hash.clone(),
+  })
+  .unwrap();
+  assert_eq!(fingerprint.kind, FingerprintKind::Machine);
+  assert_eq!(fingerprint.hash, hash.to_lowercase());
+
+  assert!(Fingerprint::from_packet(ClientFingerprint {
+    kind: ClientFingerprintKind::Install.into(),
+    hash: "abc".to_string(),
+  })
+  .is_none());
+  assert!(Fingerprint::from_packet(ClientFingerprint {
+    kind: ClientFingerprintKind::Install.into(),
+    hash: "zz".repeat(32),
+  })
+  .is_none());
+}
@@ -5,8 +5,16 @@ use flo_state::{Addr, Message, Registry};
 
 use std::sync::Arc;
 
+use crate::discord::DiscordNotifier;
 use crate::error::*;
+use crate::game::replay::ReplayJanitor;
 use crate::game::state::GameRegistry;
+use crate::map::MapVetoRegistry;
+use crate::matchmaking::MatchmakingRegistry;
+use crate::season::SeasonScheduler;
+use crate::settings::ReloadReport;
+use crate::stats::StatsAggregator;
+use crate::tournament::TournamentScheduler;
 
 use crate::node::NodeRegistry;
 use crate::player::state::PlayerRegistry;
@@ -26,6 +34,13 @@ pub struct ControllerState {
   pub nodes: Addr<NodeRegistry>,
   pub games: Addr<GameRegistry>,
   pub players: Addr<PlayerRegistry>,
+  pub matchmaking: Addr<MatchmakingRegistry>,
+  pub tournaments: Addr<TournamentScheduler>,
+  pub seasons: Addr<SeasonScheduler>,
+  pub map_vetos: Addr<MapVetoRegistry>,
+  pub discord: Addr<DiscordNotifier>,
+  pub replays: Addr<ReplayJanitor>,
+  pub stats: Addr<StatsAggregator>,
   pub player_packet_sender: PlayerRegistryHandle,
   pub config: Addr<ConfigStorage>,
 }
@@ -34,6 +49,8 @@ pub type ControllerStateRef = Arc<ControllerState>;
 
 impl ControllerState {
   pub async fn init() -> Result<Self> {
+    crate::settings::init()?;
+
     let db = Executor::env().into_ref();
 
     #[cfg(not(debug_assertions))]
@@ -47,6 +64,13 @@ impl ControllerState {
     let games = registry.resolve().await?;
     let players = registry.resolve().await?;
     let config = registry.resolve().await?;
+    let matchmaking = registry.resolve().await?;
+    let tournaments = registry.resolve().await?;
+    let seasons = registry.resolve().await?;
+    let map_vetos = registry.resolve().await?;
+    let discord = registry.resolve().await?;
+    let replays = registry.resolve().await?;
+    let stats = registry.resolve().await?;
 
     Ok(ControllerState {
       db,
@@ -54,14 +78,29 @@ impl ControllerState {
       nodes,
       games,
       players: players.clone(),
+      matchmaking,
+      tournaments,
+      seasons,
+      map_vetos,
+      discord,
+      replays,
+      stats,
       player_packet_sender: PlayerRegistryHandle::from(players),
       config,
     })
   }
 
-  pub async fn reload(&self) -> Result<()> {
+  /// Reloads settings and cached database config
+  pub async fn reload(&self) -> Result<ReloadReport> {
+    let report = crate::settings::reload()?;
     self.config.send(Reload).await??;
     self.nodes.send(Reload).await??;
+    self.matchmaking.send(Reload).await??;
+    Ok(report)
+  }
+
+  pub async fn reload_config(&self) -> Result<()> {
+    self.config.send(Reload).await??;
     Ok(())
   }
 
@@ -0,0 +1,497 @@
+use chrono::{DateTime, Utc};
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+use s2_grpc_utils::S2ProtoUnpack;
+use std::collections::BTreeMap;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::result::InvalidReason;
+use crate::game::Race;
+use crate::map::MapSha1;
+use crate::schema::{game, game_result, game_used_slot, stats_aggregate};
+use crate::stats::{StatsAggregate, StatsKind, StatsPeriod};
+
+const MAX_AGGREGATES: i64 = 1000;
+/// Maps hosted fewer times in a period are never flagged
+const MAP_DESYNC_FLAG_MIN_HOSTED: i32 = 10;
+const MAP_DESYNC_FLAG_PERCENT: i32 = 5;
+
+/// A game with a stored result
+#[derive(Debug, Queryable)]
+pub(crate) struct ResultRow {
+  pub game_id: i32,
+  pub node_id: Option<i32>,
+  pub map_name: String,
+  pub valid: bool,
+  pub winner_team: Option<i32>,
+  pub invalid_reason: Option<String>,
+  pub duration_ms: i32,
+}
+
+/// A non-observer player slot
+#[derive(Debug, Queryable)]
+pub(crate) struct SlotRow {
+  pub game_id: i32,
+  pub team: i32,
+  pub race: Race,
+}
+
+/// The map and patch of a game
+#[derive(Debug)]
+pub(crate) struct MapRow {
+  pub game_id: i32,
+  pub map_sha1: String,
+  pub game_version: Option<String>,
+}
+
+impl MapRow {
+  /// Games with a malformed `meta` column are not counted
+  fn from_meta(
+    game_id: i32,
+    meta: &serde_json::Value,
+    game_version: Option<String>,
+  ) -> Option<Self> {
+    let sha1: MapSha1 = serde_json::from_value(meta["map"]["sha1"].clone()).ok()?;
+    Some(MapRow {
+      game_id,
+      map_sha1: sha1.to_hex(),
+      game_version,
+    })
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Counts {
+  pub hosted: i32,
+  pub games: i32,
+  pub wins: i32,
+  pub invalid: i32,
+  pub desyncs: i32,
+  pub total_duration_ms: i64,
+}
+
+impl Counts {
+  fn put(&mut self, result: &ResultRow, won: bool) {
+    if result.valid {
+      self.games += 1;
+      if won {
+        self.wins += 1;
+      }
+    } else {
+      self.invalid += 1;
+    }
+    if result.invalid_reason.as_deref() == Some(format!("{:?}", InvalidReason::Desync).as_str()) {
+      self.desyncs += 1;
+    }
+    self.total_duration_ms += result.duration_ms as i64;
+  }
+}
+
+/// Recomputes the aggregates of the period starting at `period_start`,
+/// returns the number of aggregates
+pub fn aggregate(conn: &DbConn, period: StatsPeriod, period_start: DateTime<Utc>) -> Result<usize> {
+  #[derive(Insertable)]
+  #[table_name = "stats_aggregate"]
+  struct Insert {
+    period: StatsPeriod,
+    period_start: DateTime<Utc>,
+    kind: StatsKind,
+    key: String,
+    sub_key: String,
+    hosted: i32,
+    games: i32,
+    wins: i32,
+    invalid: i32,
+    desyncs: i32,
+    total_duration_ms: i64,
+  }
+
+  let period_end = period_start + period.duration();
+  let results: Vec<ResultRow> = game::table
+    .inner_join(game_result::table)
+    .select((
+      game::id,
+      game::node_id,
+      game::map_name,
+      game_result::valid,
+      game_result::winner_team,
+      game_result::invalid_reason,
+      game_result::duration_ms,
+    ))
+    .filter(
+      game_result::created_at
+        .ge(period_start)
+        .and(game_result::created_at.lt(period_end)),
+    )
+    .load(conn)?;
+  let game_ids: Vec<i32> = results.iter().map(|r| r.game_id).collect();
+  let slots: Vec<SlotRow> = game_used_slot::table
+    .select((
+      game_used_slot::game_id,
+      game_used_slot::team,
+      game_used_slot::race,
+    ))
+    .filter(
+      game_used_slot::game_id
+        .eq(any(&game_ids))
+        .and(game_used_slot::team.ne(24))
+        .and(game_used_slot::player_id.is_not_null()),
+    )
+    .load(conn)?;
+  let hosted: Vec<i32> = game::table
+    .select(game::id)
+    .filter(
+      game::created_at
+        .ge(period_start)
+        .and(game::created_at.lt(period_end)),
+    )
+    .load(conn)?;
+  let map_game_ids: Vec<i32> = game_ids.iter().chain(hosted.iter()).cloned().collect();
+  let maps: Vec<MapRow> = game::table
+    .select((game::id, game::meta, game::game_version))
+    .filter(game::id.eq(any(&map_game_ids)))
+    .load::<(i32, serde_json::Value, Option<String>)>(conn)?
+    .into_iter()
+    .filter_map(|(game_id, meta, game_version)| MapRow::from_meta(game_id, &meta, game_version))
+    .collect();
+
+  let rows: Vec<Insert> = compute(&results, &slots, &maps, &hosted)
+    .into_iter()
+    .map(|((kind, key, sub_key), counts)| Insert {
+      period,
+      period_start,
+      kind,
+      key,
+      sub_key,
+      hosted: counts.hosted,
+      games: counts.games,
+      wins: counts.wins,
+      invalid: counts.invalid,
+      desyncs: counts.desyncs,
+      total_duration_ms: counts.total_duration_ms,
+    })
+    .collect();
+
+  conn.transaction(|| {
+    diesel::delete(
+      stats_aggregate::table.filter(
+        stats_aggregate::period
+          .eq(period)
+          .and(stats_aggregate::period_start.eq(period_start)),
+      ),
+    )
+    .execute(conn)?;
+    if !rows.is_empty() {
+      diesel::insert_into(stats_aggregate::table)
+        .values(&rows)
+        .execute(conn)?;
+    }
+    Ok(rows.len())
+  })
+}
+
+/// `hosted` are the ids of the games created in the period
+pub(crate) fn compute(
+  results: &[ResultRow],
+  slots: &[SlotRow],
+  maps: &[MapRow],
+  hosted: &[i32],
+) -> BTreeMap<(StatsKind, String, String), Counts> {
+  let mut game_slots: BTreeMap<i32, Vec<&SlotRow>> = BTreeMap::new();
+  for slot in slots {
+    game_slots.entry(slot.game_id).or_default().push(slot);
+  }
+  let game_maps: BTreeMap<i32, (String, String)> = maps
+    .iter()
+    .map(|m| {
+      (
+        m.game_id,
+        (
+          m.map_sha1.clone(),
+          m.game_version.clone().unwrap_or_default(),
+        ),
+      )
+    })
+    .collect();
+
+  let mut map: BTreeMap<(StatsKind, String, String), Counts> = BTreeMap::new();
+  for game_id in hosted {
+    if let Some((sha1, game_version)) = game_maps.get(game_id).cloned() {
+      map
+        .entry((StatsKind::Map, sha1, game_version))
+        .or_default()
+        .hosted += 1;
+    }
+  }
+  let mut put = |kind: StatsKind, key: String, sub_key: String, result: &ResultRow, won: bool| {
+    map
+      .entry((kind, key, sub_key))
+      .or_insert_with(Counts::default)
+      .put(result, won)
+  };
+  for result in results {
+    if let Some(node_id) = result.node_id {
+      put(
+        StatsKind::Node,
+        node_id.to_string(),
+        String::new(),
+        result,
+        false,
+      );
+    }
+
+    if let Some((sha1, game_version)) = game_maps.get(&result.game_id).cloned() {
+      put(StatsKind::Map, sha1, game_version, result, false);
+    }
+
+    let slots = game_slots
+      .get(&result.game_id)
+      .map(Vec::as_slice)
+      .unwrap_or_default();
+    for slot in slots {
+      put(
+        StatsKind::MapRace,
+        result.map_name.clone(),
+        format!("{:?}", slot.race),
+        result,
+        result.winner_team == Some(slot.team),
+      );
+    }
+
+    if let [a, b] = slots {
+      if a.team != b.team {
+        for (slot, opponent) in &[(a, b), (b, a)] {
+          put(
+            StatsKind::RaceMatchup,
+            format!("{:?}", slot.race),
+            format!("{:?}", opponent.race),
+            result,
+            result.winner_team == Some(slot.team),
+          );
+        }
+      }
+    }
+  }
+  map
+}
+
+#[derive(Debug, S2ProtoUnpack)]
+#[s2_grpc(message_type = "flo_grpc::controller::ListStatsAggregatesRequest")]
+pub struct ListStatsAggregatesParams {
+  #[s2_grpc(proto_enum)]
+  pub period: StatsPeriod,
+  #[s2_grpc(proto_enum)]
+  pub kind: StatsKind,
+  pub key: Option<String>,
+  pub since: Option<DateTime<Utc>>,
+  /// Only return aggregates with a high desync rate
+  pub desync_flagged: bool,
+}
+
+/// Most recent periods first
+pub fn list(conn: &DbConn, params: &ListStatsAggregatesParams) -> Result<Vec<StatsAggregate>> {
+  use stats_aggregate::dsl;
+  let mut q = stats_aggregate::table
+    .select(StatsAggregate::COLUMNS)
+    .filter(dsl::period.eq(params.period).and(dsl::kind.eq(params.kind)))
+    .into_boxed();
+  if let Some(key) = params.key.as_ref() {
+    q = q.filter(dsl::key.eq(key));
+  }
+  if let Some(since) = params.since {
+    q = q.filter(dsl::period_start.ge(since));
+  }
+  if params.desync_flagged {
+    q = q.filter(
+      dsl::hosted
+        .ge(MAP_DESYNC_FLAG_MIN_HOSTED)
+        .and((dsl::desyncs * 100).ge(dsl::hosted * MAP_DESYNC_FLAG_PERCENT)),
+    );
+  }
+  q.order((dsl::period_start.desc(), dsl::key, dsl::sub_key))
+    .limit(MAX_AGGREGATES)
+    .load(conn)
+    .map_err(Into::into)
+}
+
+#[test]
+fn test_compute() {
+  let result = |game_id: i32, valid: bool, winner_team: Option<i32>| ResultRow {
+    game_id,
+    node_id: Some(1),
+    map_name: "Map".to_string(),
+    valid,
+    winner_team,
+    invalid_reason: if valid {
+      None
+    } else {
+      Some("Desync".to_string())
+    },
+    duration_ms: 1000,
+  };
+  let slot = |game_id: i32, team: i32, race: Race| SlotRow {
+    game_id,
+    team,
+    race,
+  };
+  let results = vec![result(1, true, Some(0)), result(2, false, None)];
+  let slots = vec![
+    slot(1, 0, Race::Human),
+    slot(1, 1, Race::Orc),
+    slot(2, 0, Race::Human),
+    slot(2, 1, Race::Orc),
+  ];
+  let maps = vec![
+    MapRow {
+      game_id: 1,
+      map_sha1: "aa".to_string(),
+      game_version: Some("1.36".to_string()),
+    },
+    MapRow {
+      game_id: 2,
+      map_sha1: "aa".to_string(),
+      game_version: Some("1.36".to_string()),
+    },
+    MapRow {
+      game_id: 3,
+      map_sha1: "aa".to_string(),
+      game_version: Some("1.36".to_string()),
+    },
+  ];
+  let map = compute(&results, &slots, &maps, &[2, 3]);
+
+  let get = |kind: StatsKind, key: &str, sub_key: &str| {
+    map
+      .get(&(kind, key.to_string(), sub_key.to_string()))
+      .cloned()
+      .unwrap()
+  };
+  assert_eq!(
+    get(StatsKind::Node, "1", ""),
+    Counts {
+      hosted: 0,
+      games: 1,
+      wins: 0,
+      invalid: 1,
+      desyncs: 1,
+      total_duration_ms: 2000,
+    }
+  );
+  assert_eq!(
+    get(StatsKind::Map, "aa", "1.36"),
+    Counts {
+      hosted: 2,
+      games: 1,
+      wins: 0,
+      invalid: 1,
+      desyncs: 1,
+      total_duration_ms: 2000,
+    }
+  );
+  assert_eq!(get(StatsKind::MapRace, "Map", "Human").wins, 1);
+  assert_eq!(get(StatsKind::MapRace, "Map", "Orc").wins, 0);
+  assert_eq!(get(StatsKind::RaceMatchup, "Human", "Orc").wins, 1);
+  assert_eq!(get(StatsKind::RaceMatchup, "Orc", "Human").games, 1);
+}
+
+#[test]
+fn test_map_row_from_meta() {
+  let meta = serde_json::json!({
+    "map": {
+      "sha1": serde_json::to_value(MapSha1([0xab; 20])).unwrap(),
+    },
+  });
+  let row = MapRow::from_meta(1, &meta, Some("1.36".to_string())).unwrap();
+  assert_eq!(row.map_sha1, "ab".repeat(20));
+  assert_eq!(row.game_version.as_deref(), Some("1.36"));
+  assert!(MapRow::from_meta(1, &serde_json::json!({}), None).is_none());
+}
+
+#[test]
+fn test_compute_map_versions() {
+  let map_row = |game_id: i32, map_sha1: &str, game_version: Option<&str>| MapRow {
+    game_id,
+    map_sha1: map_sha1.to_string(),
+    game_version: game_version.map(ToString::to_string),
+  };
+  let results = vec![ResultRow {
+    game_id: 1,
+    node_id: None,
+    map_name: "Map".to_string(),
+    valid: true,
+    winner_team: Some(0),
+    invalid_reason: None,
+    duration_ms: 1000,
+  }];
+  let maps = vec![
+    map_row(1, "aa", Some("1.36")),
+    map_row(2, "aa", Some("1.35")),
+    map_row(3, "bb", Some("1.36")),
+    map_row(4, "bb", None),
+  ];
+  // game 5 has no map row
+  let map = compute(&results, &[], &maps, &[1, 2, 3, 4, 5]);
+
+  let hosted: Vec<_> = map
+    .iter()
+    .map(|((kind, key, sub_key), counts)| {
+      assert_eq!(*kind, StatsKind::Map);
+      (key.as_str(), sub_key.as_str(), counts.hosted, counts.games)
+    })
+    .collect();
+  assert_eq!(
+    hosted,
+    vec![
+      ("aa", "1.35", 1, 0),
+      ("aa", "1.36", 1, 1),
+      ("bb", "", 1, 0),
+      ("bb", "1.36", 1, 0),
+    ]
+  );
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_list_desync_flagged() {
+  crate::db::test_transaction(|conn| {
+    let period_start = StatsPeriod::Daily.start_of(Utc::now());
+    let insert = |key: &str, hosted: i32, desyncs: i32| {
+      diesel::insert_into(stats_aggregate::table)
+        .values((
+          stats_aggregate::period.eq(StatsPeriod::Daily),
+          stats_aggregate::period_start.eq(period_start),
+          stats_aggregate::kind.eq(StatsKind::Map),
+          stats_aggregate::key.eq(key),
+          stats_aggregate::sub_key.eq("1.36"),
+          stats_aggregate::hosted.eq(hosted),
+          stats_aggregate::games.eq(0),
+          stats_aggregate::wins.eq(0),
+          stats_aggregate::invalid.eq(desyncs),
+          stats_aggregate::desyncs.eq(desyncs),
+          stats_aggregate::total_duration_ms.eq(0),
+        ))
+        .execute(conn)
+    };
+    insert("flagged", 20, 1)?;
+    insert("below-rate", 100, 4)?;
+    insert("too-few-games", 9, 9)?;
+
+    let keys: Vec<String> = list(
+      conn,
+      &ListStatsAggregatesParams {
+        period: StatsPeriod::Daily,
+        kind: StatsKind::Map,
+        key: None,
+        since: Some(period_start),
+        desync_flagged: true,
+      },
+    )?
+    .into_iter()
+    .map(|aggregate| aggregate.key)
+    .collect();
+    assert_eq!(keys, vec!["flagged".to_string()]);
+    Ok(())
+  })
+  .await;
+}
@@ -0,0 +1,6 @@
+pub mod db;
+pub(crate) mod state;
+mod types;
+
+pub use state::StatsAggregator;
+pub use types::*;
@@ -0,0 +1,72 @@
+use bs_diesel_utils::ExecutorRef;
+use chrono::Utc;
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::error::*;
+use crate::state::Data;
+use crate::stats::StatsPeriod;
+
+const AGGREGATE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Rolls game results into the daily and weekly aggregates.
+/// The previous period is refreshed as well to pick up results reported late.
+pub struct StatsAggregator {
+  db: ExecutorRef,
+}
+
+impl StatsAggregator {
+  async fn aggregate(&mut self) -> Result<()> {
+    let now = Utc::now();
+    for period in [StatsPeriod::Daily, StatsPeriod::Weekly] {
+      let current = period.start_of(now);
+      for period_start in [current - period.duration(), current] {
+        let count = self
+          .db
+          .exec(move |conn| crate::stats::db::aggregate(conn, period, period_start))
+          .await?;
+        tracing::debug!(?period, %period_start, count, "stats aggregated");
+      }
+    }
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl Actor for StatsAggregator {
+  async fn started(&mut self, ctx: &mut Context<Self>) {
+    self.handle(ctx, AggregateTick).await;
+  }
+}
+
+#[async_trait]
+impl Service<Data> for StatsAggregator {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    Ok(StatsAggregator {
+      db: registry.data().db.clone(),
+    })
+  }
+}
+
+struct AggregateTick;
+
+impl Message for AggregateTick {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<AggregateTick> for StatsAggregator {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: AggregateTick) {
+    if let Err(err) = self.aggregate().await {
+      tracing::error!("aggregate stats: {}", err);
+    }
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(AGGREGATE_INTERVAL).await;
+      addr.notify(AggregateTick).await.ok();
+    });
+  }
+}
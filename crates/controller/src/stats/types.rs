@@ -0,0 +1,184 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::stats_aggregate;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::StatsPeriod")]
+pub enum StatsPeriod {
+  Daily = 0,
+  /// Weeks start on Monday
+  Weekly = 1,
+}
+
+impl StatsPeriod {
+  /// Start of the period containing `t`, in UTC
+  pub fn start_of(self, t: DateTime<Utc>) -> DateTime<Utc> {
+    let mut date = t.date_naive();
+    if self == StatsPeriod::Weekly {
+      date = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    }
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight"))
+  }
+
+  pub fn duration(self) -> Duration {
+    match self {
+      StatsPeriod::Daily => Duration::days(1),
+      StatsPeriod::Weekly => Duration::weeks(1),
+    }
+  }
+}
+
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  BSDieselEnum,
+  S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::StatsKind")]
+pub enum StatsKind {
+  /// `key` is the map name, `sub_key` the race, counted per player
+  MapRace = 0,
+  /// `key` is the race, `sub_key` the opponent race, 1v1 games only
+  RaceMatchup = 1,
+  /// `key` is the node id
+  Node = 2,
+  /// `key` is the map sha1 in hex, `sub_key` the game version
+  Map = 3,
+}
+
+/// Pre-computed stats of a period, refreshed by [`StatsAggregator`](crate::stats::StatsAggregator)
+#[derive(Debug, Serialize, Deserialize, Clone, Queryable, S2ProtoPack)]
+#[s2_grpc(message_type = "flo_grpc::controller::StatsAggregate")]
+pub struct StatsAggregate {
+  #[s2_grpc(proto_enum)]
+  pub period: StatsPeriod,
+  pub period_start: DateTime<Utc>,
+  #[s2_grpc(proto_enum)]
+  pub kind: StatsKind,
+  pub key: String,
+  pub sub_key: String,
+  /// Games created in the period, only counted for [`StatsKind::Map`]
+  pub hosted: i32,
+  /// Games with a valid result
+  pub games: i32,
+  pub wins: i32,
+  /// Games with an invalid result
+  pub invalid: i32,
+  pub desyncs: i32,
+  pub total_duration_ms: i64,
+  pub updated_at: DateTime<Utc>,
+}
+
+pub(crate) type StatsAggregateColumns = (
+  stats_aggregate::period,
+  stats_aggregate::period_start,
+  stats_aggregate::kind,
+  stats_aggregate::key,
+  stats_aggregate::sub_key,
+  stats_aggregate::hosted,
+  stats_aggregate::games,
+  stats_aggregate::wins,
+  stats_aggregate::invalid,
+  stats_aggregate::desyncs,
+  stats_aggregate::total_duration_ms,
+  stats_aggregate::updated_at,
+);
+
+impl StatsAggregate {
+  pub(crate) const COLUMNS: StatsAggregateColumns = (
+    stats_aggregate::period,
+    stats_aggregate::period_start,
+    stats_aggregate::kind,
+    stats_aggregate::key,
+    stats_aggregate::sub_key,
+    stats_aggregate::hosted,
+    stats_aggregate::games,
+    stats_aggregate::wins,
+    stats_aggregate::invalid,
+    stats_aggregate::desyncs,
+    stats_aggregate::total_duration_ms,
+    stats_aggregate::updated_at,
+  );
+
+  /// Games with a result per hosted game
+  pub fn completion_rate(&self) -> Option<f64> {
+    rate(self.games + self.invalid, self.hosted)
+  }
+
+  /// Desyncs per hosted game
+  pub fn desync_rate(&self) -> Option<f64> {
+    rate(self.desyncs, self.hosted)
+  }
+
+  pub fn average_duration_ms(&self) -> Option<i64> {
+    let count = (self.games + self.invalid) as i64;
+    if count > 0 {
+      Some(self.total_duration_ms / count)
+    } else {
+      None
+    }
+  }
+}
+
+fn rate(count: i32, total: i32) -> Option<f64> {
+  if total > 0 {
+    Some(count as f64 / total as f64)
+  } else {
+    None
+  }
+}
+
+#[test]
+fn test_stats_period_start() {
+  let t = Utc.with_ymd_and_hms(2025, 1, 8, 15, 30, 0).unwrap();
+  assert_eq!(
+    StatsPeriod::Daily.start_of(t),
+    Utc.with_ymd_and_hms(2025, 1, 8, 0, 0, 0).unwrap()
+  );
+  assert_eq!(
+    StatsPeriod::Weekly.start_of(t),
+    Utc.with_ymd_and_hms(2025, 1, 6, 0, 0, 0).unwrap()
+  );
+}
+
+#[test]
+fn test_stats_aggregate_rates() {
+  let aggregate = |hosted: i32, games: i32, invalid: i32, desyncs: i32| StatsAggregate {
+    period: StatsPeriod::Daily,
+    period_start: Utc::now(),
+    kind: StatsKind::Map,
+    key: "aa".to_string(),
+    sub_key: "1.36".to_string(),
+    hosted,
+    games,
+    wins: 0,
+    invalid,
+    desyncs,
+    total_duration_ms: ((games + invalid) * 60_000) as i64,
+    updated_at: Utc::now(),
+  };
+
+  let stats = aggregate(10, 6, 2, 1);
+  assert_eq!(stats.completion_rate(), Some(0.8));
+  assert_eq!(stats.desync_rate(), Some(0.1));
+  assert_eq!(stats.average_duration_ms(), Some(60_000));
+
+  // results of games hosted in an earlier period
+  let stats = aggregate(0, 1, 0, 0);
+  assert_eq!(stats.completion_rate(), None);
+  assert_eq!(stats.desync_rate(), None);
+  assert_eq!(stats.average_duration_ms(), Some(60_000));
+  assert_eq!(aggregate(1, 0, 0, 0).average_duration_ms(), None);
+}
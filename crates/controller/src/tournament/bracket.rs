@@ -0,0 +1,161 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSeed {
+  pub round: i32,
+  pub position: i32,
+  pub group_index: Option<i32>,
+  pub player1_id: Option<i32>,
+  pub player2_id: Option<i32>,
+}
+
+/// Generates all matches of a single elimination bracket.
+/// `player_ids` are ordered by seed, byes are given to the top seeds.
+pub fn single_elimination(player_ids: &[i32]) -> Vec<MatchSeed> {
+  let size = std::cmp::max(2, player_ids.len().next_power_of_two());
+  let order = seed_order(size);
+  let mut matches = vec![];
+
+  for position in 0..(size / 2) {
+    matches.push(MatchSeed {
+      round: 0,
+      position: position as i32,
+      group_index: None,
+      player1_id: player_ids.get(order[position * 2]).cloned(),
+      player2_id: player_ids.get(order[position * 2 + 1]).cloned(),
+    });
+  }
+
+  let mut round = 1;
+  let mut num_matches = size / 4;
+  while num_matches > 0 {
+    for position in 0..num_matches {
+      matches.push(MatchSeed {
+        round,
+        position: position as i32,
+        group_index: None,
+        player1_id: None,
+        player2_id: None,
+      });
+    }
+    round += 1;
+    num_matches /= 2;
+  }
+
+  matches
+}
+
+/// Splits players into groups (snake order by seed),
+/// every player plays every other player of the same group once.
+pub fn round_robin(player_ids: &[i32], num_groups: usize) -> Vec<MatchSeed> {
+  let num_groups = std::cmp::max(1, std::cmp::min(num_groups, player_ids.len() / 2));
+  let mut groups: Vec<Vec<i32>> = vec![vec![]; num_groups];
+  for (i, player_id) in player_ids.iter().enumerate() {
+    let lap = i / num_groups;
+    let idx = if lap % 2 == 0 {
+      i % num_groups
+    } else {
+      num_groups - 1 - i % num_groups
+    };
+    groups[idx].push(*player_id);
+  }
+
+  let mut matches = vec![];
+  let mut next_position = vec![];
+  for (group_index, group) in groups.into_iter().enumerate() {
+    // circle method, `None` is a bye
+    let mut slots: Vec<Option<i32>> = group.into_iter().map(Some).collect();
+    if slots.len() % 2 == 1 {
+      slots.push(None);
+    }
+    let n = slots.len();
+    for round in 0..(n - 1) {
+      if next_position.len() <= round {
+        next_position.push(0);
+      }
+      for i in 0..(n / 2) {
+        if let (Some(a), Some(b)) = (slots[i], slots[n - 1 - i]) {
+          matches.push(MatchSeed {
+            round: round as i32,
+            position: next_position[round],
+            group_index: Some(group_index as i32),
+            player1_id: Some(a),
+            player2_id: Some(b),
+          });
+          next_position[round] += 1;
+        }
+      }
+      slots[1..].rotate_right(1);
+    }
+  }
+
+  matches.sort_by_key(|m| (m.round, m.position));
+  matches
+}
+
+/// Returns the round and position of the match the winner advances to,
+/// and whether the winner takes the first slot.
+pub fn next_match(round: i32, position: i32) -> (i32, i32, bool) {
+  (round + 1, position / 2, position % 2 == 0)
+}
+
+// 0-based seeds in bracket order, e.g. [0, 3, 1, 2] for 4 players
+fn seed_order(size: usize) -> Vec<usize> {
+  let mut order = vec![0];
+  while order.len() < size {
+    let n = order.len() * 2;
+    order = order.iter().flat_map(|s| vec![*s, n - 1 - *s]).collect();
+  }
+  order
+}
+
+#[test]
+fn test_single_elimination() {
+  assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+
+  let matches = single_elimination(&[1, 2, 3, 4, 5, 6]);
+  assert_eq!(matches.len(), 7);
+  let first_round: Vec<_> = matches
+    .iter()
+    .filter(|m| m.round == 0)
+    .map(|m| (m.player1_id, m.player2_id))
+    .collect();
+  assert_eq!(
+    first_round,
+    vec![
+      (Some(1), None),
+      (Some(4), Some(5)),
+      (Some(2), None),
+      (Some(3), Some(6)),
+    ]
+  );
+  assert_eq!(matches.iter().filter(|m| m.round == 2).count(), 1);
+  assert_eq!(next_match(0, 3), (1, 1, false));
+}
+
+#[test]
+fn test_round_robin() {
+  let matches = round_robin(&[1, 2, 3, 4, 5], 1);
+  assert_eq!(matches.len(), 10);
+  let mut pairs: Vec<_> = matches
+    .iter()
+    .map(|m| {
+      let (a, b) = (m.player1_id.unwrap(), m.player2_id.unwrap());
+      (std::cmp::min(a, b), std::cmp::max(a, b))
+    })
+    .collect();
+  pairs.sort();
+  pairs.dedup();
+  assert_eq!(pairs.len(), 10);
+
+  let matches = round_robin(&[1, 2, 3, 4, 5, 6, 7, 8], 2);
+  assert_eq!(matches.len(), 12);
+  for m in &matches {
+    let group = m.group_index.unwrap();
+    let expected = if group == 0 {
+      [1, 4, 5, 8]
+    } else {
+      [2, 3, 6, 7]
+    };
+    assert!(expected.contains(&m.player1_id.unwrap()));
+    assert!(expected.contains(&m.player2_id.unwrap()));
+  }
+}
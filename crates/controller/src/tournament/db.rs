@@ -0,0 +1,477 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use s2_grpc_utils::S2ProtoUnpack;
+use serde_json::Value;
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::ChatRules;
+use crate::map::Map;
+use crate::schema::{map_pool, tournament, tournament_match};
+use crate::tournament::bracket::{self, MatchSeed};
+use crate::tournament::{
+  Tournament, TournamentFormat, TournamentMatch, TournamentMatchStatus, TournamentRow,
+  TournamentStatus,
+};
+
+#[derive(Debug, S2ProtoUnpack)]
+#[s2_grpc(message_type = "flo_grpc::controller::CreateTournamentRequest")]
+pub struct CreateTournamentParams {
+  pub name: String,
+  #[s2_grpc(proto_enum)]
+  pub format: TournamentFormat,
+  pub map: Map,
+  pub node_id: Option<i32>,
+  /// Players ban maps from the pool before each match instead of using `map`
+  pub map_pool_id: Option<i32>,
+  /// Ordered by seed
+  pub player_ids: Vec<i32>,
+  pub num_groups: i32,
+  pub starts_at: Option<DateTime<Utc>>,
+  pub round_interval_secs: i32,
+  pub check_in_window_secs: i32,
+  /// Pushed to the node with every match of the tournament
+  pub chat_rules: Option<ChatRules>,
+}
+
+pub fn create(
+  conn: &DbConn,
+  api_client_id: i32,
+  params: CreateTournamentParams,
+) -> Result<Tournament> {
+  #[derive(Insertable)]
+  #[table_name = "tournament"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    name: &'a str,
+    format: TournamentFormat,
+    map: Value,
+    node_id: Option<i32>,
+    map_pool_id: Option<i32>,
+    check_in_window_secs: i32,
+    chat_rules: Option<Value>,
+  }
+
+  #[derive(Insertable)]
+  #[table_name = "tournament_match"]
+  struct InsertMatch {
+    tournament_id: i32,
+    round: i32,
+    position: i32,
+    group_index: Option<i32>,
+    player1_id: Option<i32>,
+    player2_id: Option<i32>,
+    scheduled_at: Option<DateTime<Utc>>,
+    status: TournamentMatchStatus,
+  }
+
+  let player_ids = &params.player_ids;
+  let mut unique_ids = player_ids.clone();
+  unique_ids.sort();
+  unique_ids.dedup();
+  if unique_ids.len() < 2 || unique_ids.len() != player_ids.len() {
+    return Err(Error::TournamentPlayersInvalid);
+  }
+
+  if params.map.players.len() < 2 {
+    return Err(Error::MapHasNoPlayer);
+  }
+
+  conn.transaction(|| {
+    if let Some(id) = params.map_pool_id {
+      crate::map::db::get_pool(conn, api_client_id, id)?;
+    }
+
+    let players = crate::player::db::get_client_refs_by_ids(conn, api_client_id, player_ids)?;
+    if players.len() != player_ids.len() {
+      return Err(Error::PlayerNotFound);
+    }
+    crate::player::db::check_organizer_ban(conn, api_client_id, player_ids)?;
+
+    let tournament_id: i32 = diesel::insert_into(tournament::table)
+      .values(&Insert {
+        api_client_id,
+        name: &params.name,
+        format: params.format,
+        map: serde_json::to_value(&params.map)?,
+        node_id: params.node_id,
+        map_pool_id: params.map_pool_id,
+        check_in_window_secs: params.check_in_window_secs,
+        chat_rules: params
+          .chat_rules
+          .as_ref()
+          .map(serde_json::to_value)
+          .transpose()?,
+      })
+      .returning(tournament::id)
+      .get_result(conn)?;
+
+    let seeds = match params.format {
+      TournamentFormat::SingleElimination => bracket::single_elimination(player_ids),
+      TournamentFormat::RoundRobin => bracket::round_robin(player_ids, params.num_groups as usize),
+    };
+
+    let inserts: Vec<_> = seeds
+      .iter()
+      .map(|seed: &MatchSeed| InsertMatch {
+        tournament_id,
+        round: seed.round,
+        position: seed.position,
+        group_index: seed.group_index,
+        player1_id: seed.player1_id,
+        player2_id: seed.player2_id,
+        scheduled_at: params
+          .starts_at
+          .map(|t| t + Duration::seconds(seed.round as i64 * params.round_interval_secs as i64)),
+        status: if seed.player1_id.is_some() && seed.player2_id.is_some() {
+          TournamentMatchStatus::Ready
+        } else {
+          TournamentMatchStatus::Pending
+        },
+      })
+      .collect();
+
+    let matches: Vec<TournamentMatch> = diesel::insert_into(tournament_match::table)
+      .values(&inserts)
+      .returning(TournamentMatch::COLUMNS)
+      .get_results(conn)?;
+
+    // players without an opponent in the first round advance immediately
+    for m in matches {
+      if m.round == 0 && m.status == TournamentMatchStatus::Pending {
+        if let Some(winner_id) = m.player1_id.or(m.player2_id) {
+          finish_match(conn, &m, params.format, Some(winner_id))?;
+        }
+      }
+    }
+
+    get(conn, api_client_id, tournament_id)
+  })
+}
+
+pub fn get(conn: &DbConn, api_client_id: i32, id: i32) -> Result<Tournament> {
+  let row: TournamentRow = tournament::table
+    .select(TournamentRow::COLUMNS)
+    .filter(
+      tournament::id
+        .eq(id)
+        .and(tournament::api_client_id.eq(api_client_id)),
+    )
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::TournamentNotFound)?;
+  let matches = tournament_match::table
+    .select(TournamentMatch::COLUMNS)
+    .filter(tournament_match::tournament_id.eq(id))
+    .order((tournament_match::round, tournament_match::position))
+    .load(conn)?;
+  Ok(row.into_tournament(matches))
+}
+
+pub fn cancel(conn: &DbConn, api_client_id: i32, id: i32) -> Result<()> {
+  let n = diesel::update(
+    tournament::table.filter(
+      tournament::id
+        .eq(id)
+        .and(tournament::api_client_id.eq(api_client_id))
+        .and(tournament::status.eq(TournamentStatus::Running)),
+    ),
+  )
+  .set(tournament::status.eq(TournamentStatus::Cancelled))
+  .execute(conn)?;
+  if n == 0 {
+    return Err(Error::TournamentNotFound);
+  }
+  Ok(())
+}
+
+pub fn schedule_match(
+  conn: &DbConn,
+  api_client_id: i32,
+  match_id: i32,
+  scheduled_at: DateTime<Utc>,
+) -> Result<TournamentMatch> {
+  conn.transaction(|| {
+    let (m, _) = get_match_for_update(conn, api_client_id, match_id)?;
+    match m.status {
+      TournamentMatchStatus::Pending | TournamentMatchStatus::Ready => {}
+      _ => return Err(Error::TournamentMatchNotReady),
+    }
+    diesel::update(tournament_match::table.find(match_id))
+      .set((
+        tournament_match::scheduled_at.eq(scheduled_at),
+        tournament_match::player1_checked_in_at.eq(None::<DateTime<Utc>>),
+        tournament_match::player2_checked_in_at.eq(None::<DateTime<Utc>>),
+      ))
+      .returning(TournamentMatch::COLUMNS)
+      .get_result(conn)
+      .map_err(Into::into)
+  })
+}
+
+/// Check-in opens `check_in_window_secs` before the scheduled time
+pub fn check_in(
+  conn: &DbConn,
+  api_client_id: i32,
+  match_id: i32,
+  player_id: i32,
+) -> Result<TournamentMatch> {
+  conn.transaction(|| {
+    let (m, check_in_window_secs) = get_match_for_update(conn, api_client_id, match_id)?;
+    if m.status != TournamentMatchStatus::Ready {
+      return Err(Error::TournamentMatchNotReady);
+    }
+
+    let now = Utc::now();
+    let open = m
+      .scheduled_at
+      .map(|t| now >= t - Duration::seconds(check_in_window_secs as i64) && now < t)
+      .unwrap_or(false);
+    if !open {
+      return Err(Error::TournamentCheckInClosed);
+    }
+
+    let q = diesel::update(tournament_match::table.find(match_id));
+    let updated = if m.player1_id == Some(player_id) {
+      q.set(tournament_match::player1_checked_in_at.eq(now))
+        .returning(TournamentMatch::COLUMNS)
+        .get_result(conn)?
+    } else if m.player2_id == Some(player_id) {
+      q.set(tournament_match::player2_checked_in_at.eq(now))
+        .returning(TournamentMatch::COLUMNS)
+        .get_result(conn)?
+    } else {
+      return Err(Error::PlayerNotInGame);
+    };
+    Ok(updated)
+  })
+}
+
+/// Records the winner of a match and advances the winner in the bracket
+pub fn report_result(
+  conn: &DbConn,
+  api_client_id: i32,
+  match_id: i32,
+  winner_id: i32,
+) -> Result<TournamentMatch> {
+  conn.transaction(|| {
+    let (m, _) = get_match_for_update(conn, api_client_id, match_id)?;
+    match m.status {
+      TournamentMatchStatus::Pending => return Err(Error::TournamentMatchNotReady),
+      TournamentMatchStatus::Finished => return Err(Error::TournamentMatchFinished),
+      _ => {}
+    }
+    if !m.has_player(winner_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+    let format: TournamentFormat = tournament::table
+      .find(m.tournament_id)
+      .select(tournament::format)
+      .first(conn)?;
+    finish_match(conn, &m, format, Some(winner_id))
+  })
+}
+
+/// Finishes the match hosted by `game_id` using the winners resolved from the game result
+pub fn report_game_result(conn: &DbConn, game_id: i32, winner_ids: &[i32]) -> Result<()> {
+  let row: Option<(TournamentMatch, TournamentFormat)> = tournament_match::table
+    .inner_join(tournament::table)
+    .select((TournamentMatch::COLUMNS, tournament::format))
+    .filter(
+      tournament_match::game_id
+        .eq(game_id)
+        .and(tournament_match::status.eq(TournamentMatchStatus::Created))
+        .and(tournament::status.eq(TournamentStatus::Running)),
+    )
+    .for_update()
+    .first(conn)
+    .optional()?;
+  if let Some((m, format)) = row {
+    if let Some(winner_id) = m
+      .player_ids()
+      .into_iter()
+      .find(|id| winner_ids.contains(id))
+    {
+      finish_match(conn, &m, format, Some(winner_id))?;
+    }
+  }
+  Ok(())
+}
+
+#[derive(Debug)]
+pub struct DueMatch {
+  pub tournament_match: TournamentMatch,
+  pub tournament_name: String,
+  pub format: TournamentFormat,
+  pub map: Map,
+  pub map_pool: Vec<Map>,
+  pub node_id: Option<i32>,
+  pub chat_rules: Option<ChatRules>,
+}
+
+/// Matches that reached the scheduled time but have no lobby yet
+pub fn get_due_matches(conn: &DbConn) -> Result<Vec<DueMatch>> {
+  let rows: Vec<(
+    TournamentMatch,
+    String,
+    TournamentFormat,
+    Value,
+    Option<Value>,
+    Option<i32>,
+    Option<Value>,
+  )> = tournament_match::table
+    .inner_join(tournament::table.left_join(map_pool::table))
+    .select((
+      TournamentMatch::COLUMNS,
+      tournament::name,
+      tournament::format,
+      tournament::map,
+      map_pool::maps.nullable(),
+      tournament::node_id,
+      tournament::chat_rules,
+    ))
+    .filter(
+      tournament::status
+        .eq(TournamentStatus::Running)
+        .and(tournament_match::status.eq(TournamentMatchStatus::Ready))
+        .and(tournament_match::scheduled_at.le(sql("now()"))),
+    )
+    .order(tournament_match::scheduled_at)
+    .load(conn)?;
+  rows
+    .into_iter()
+    .map(
+      |(tournament_match, tournament_name, format, map, map_pool, node_id, chat_rules)| {
+        Ok(DueMatch {
+          tournament_match,
+          tournament_name,
+          format,
+          map: serde_json::from_value(map)?,
+          map_pool: match map_pool {
+            Some(maps) => serde_json::from_value(maps)?,
+            None => vec![],
+          },
+          node_id,
+          chat_rules: chat_rules.map(serde_json::from_value).transpose()?,
+        })
+      },
+    )
+    .collect()
+}
+
+pub fn set_match_game(conn: &DbConn, match_id: i32, game_id: i32) -> Result<()> {
+  diesel::update(tournament_match::table.find(match_id))
+    .set((
+      tournament_match::game_id.eq(game_id),
+      tournament_match::status.eq(TournamentMatchStatus::Created),
+    ))
+    .execute(conn)?;
+  Ok(())
+}
+
+/// Resolves a due match without a lobby:
+/// the only checked in player wins, if nobody checked in the match is cancelled.
+pub fn resolve_no_show(conn: &DbConn, m: &TournamentMatch, format: TournamentFormat) -> Result<()> {
+  let winner_id = match (m.player1_checked_in_at, m.player2_checked_in_at) {
+    (Some(_), None) => m.player1_id,
+    (None, Some(_)) => m.player2_id,
+    _ => None,
+  };
+  conn.transaction(|| finish_match(conn, m, format, winner_id).map(|_| ()))
+}
+
+fn get_match_for_update(
+  conn: &DbConn,
+  api_client_id: i32,
+  match_id: i32,
+) -> Result<(TournamentMatch, i32)> {
+  tournament_match::table
+    .inner_join(tournament::table)
+    .select((TournamentMatch::COLUMNS, tournament::check_in_window_secs))
+    .filter(
+      tournament_match::id
+        .eq(match_id)
+        .and(tournament::api_client_id.eq(api_client_id))
+        .and(tournament::status.eq(TournamentStatus::Running)),
+    )
+    .for_update()
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::TournamentMatchNotFound)
+}
+
+fn finish_match(
+  conn: &DbConn,
+  m: &TournamentMatch,
+  format: TournamentFormat,
+  winner_id: Option<i32>,
+) -> Result<TournamentMatch> {
+  let status = if winner_id.is_some() {
+    TournamentMatchStatus::Finished
+  } else {
+    TournamentMatchStatus::Cancelled
+  };
+  let updated = diesel::update(tournament_match::table.find(m.id))
+    .set((
+      tournament_match::status.eq(status),
+      tournament_match::winner_id.eq(winner_id),
+    ))
+    .returning(TournamentMatch::COLUMNS)
+    .get_result(conn)?;
+
+  if format == TournamentFormat::SingleElimination {
+    if let Some(winner_id) = winner_id {
+      let (round, position, first) = bracket::next_match(m.round, m.position);
+      let next: Option<TournamentMatch> = tournament_match::table
+        .select(TournamentMatch::COLUMNS)
+        .filter(
+          tournament_match::tournament_id
+            .eq(m.tournament_id)
+            .and(tournament_match::round.eq(round))
+            .and(tournament_match::position.eq(position)),
+        )
+        .for_update()
+        .first(conn)
+        .optional()?;
+      if let Some(next) = next {
+        let (player1_id, player2_id) = if first {
+          (Some(winner_id), next.player2_id)
+        } else {
+          (next.player1_id, Some(winner_id))
+        };
+        let status = if player1_id.is_some() && player2_id.is_some() {
+          TournamentMatchStatus::Ready
+        } else {
+          TournamentMatchStatus::Pending
+        };
+        diesel::update(tournament_match::table.find(next.id))
+          .set((
+            tournament_match::player1_id.eq(player1_id),
+            tournament_match::player2_id.eq(player2_id),
+            tournament_match::status.eq(status),
+          ))
+          .execute(conn)?;
+        return Ok(updated);
+      }
+    }
+  }
+
+  let remaining: i64 = tournament_match::table
+    .filter(
+      tournament_match::tournament_id.eq(m.tournament_id).and(
+        tournament_match::status
+          .ne(TournamentMatchStatus::Finished)
+          .and(tournament_match::status.ne(TournamentMatchStatus::Cancelled)),
+      ),
+    )
+    .count()
+    .get_result(conn)?;
+  if remaining == 0 {
+    diesel::update(tournament::table.find(m.tournament_id))
+      .set(tournament::status.eq(TournamentStatus::Finished))
+      .execute(conn)?;
+  }
+
+  Ok(updated)
+}
@@ -0,0 +1,7 @@
+mod bracket;
+pub mod db;
+pub(crate) mod state;
+mod types;
+
+pub use state::TournamentScheduler;
+pub use types::*;
@@ -0,0 +1,221 @@
+use bs_diesel_utils::ExecutorRef;
+use flo_state::{async_trait, Actor, Addr, Context, Handler, Message, RegistryRef, Service};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::error::*;
+use crate::game::db::CreateGameForPlayersParams;
+use crate::game::state::create::CreateGameForPlayers;
+use crate::game::state::GameRegistry;
+use crate::map::state::StartMapVeto;
+use crate::map::{Map, MapVetoRegistry};
+use crate::matchmaking::balance::team_slots;
+use crate::node::messages::ListNode;
+use crate::node::policy::NodeDecision;
+use crate::node::NodeRegistry;
+use crate::player::state::ping::GetPlayersPingSnapshot;
+use crate::player::state::PlayerRegistry;
+use crate::state::Data;
+use crate::tournament::db::DueMatch;
+
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Creates lobbies for tournament matches when they reach the scheduled time
+pub struct TournamentScheduler {
+  db: ExecutorRef,
+  games: Addr<GameRegistry>,
+  players: Addr<PlayerRegistry>,
+  map_vetos: Addr<MapVetoRegistry>,
+  nodes: Addr<NodeRegistry>,
+  // matches with a map veto in progress
+  vetoing: BTreeSet<i32>,
+}
+
+impl TournamentScheduler {
+  async fn run_schedule(&mut self, ctx: &mut Context<Self>) -> Result<()> {
+    let due = self
+      .db
+      .exec(|conn| crate::tournament::db::get_due_matches(conn))
+      .await?;
+
+    for item in due {
+      let m = &item.tournament_match;
+      if self.vetoing.contains(&m.id) {
+        continue;
+      }
+
+      let checked_in = m.player1_checked_in_at.is_some() && m.player2_checked_in_at.is_some();
+      if !checked_in {
+        let (m, format) = (m.clone(), item.format);
+        self
+          .db
+          .exec(move |conn| crate::tournament::db::resolve_no_show(conn, &m, format))
+          .await?;
+        continue;
+      }
+
+      if item.map_pool.len() > 1 {
+        let player_ids = m.player_ids();
+        let match_id = m.id;
+        let rx = self
+          .map_vetos
+          .send(StartMapVeto {
+            player_ids: [player_ids[0], player_ids[1]],
+            maps: item.map_pool.clone(),
+          })
+          .await??;
+        self.vetoing.insert(match_id);
+        let addr = ctx.addr();
+        ctx.spawn(async move {
+          let map = rx.await.ok();
+          addr.notify(MapVetoDone { item, map }).await.ok();
+        });
+        continue;
+      }
+
+      let map = item.map_pool.first().unwrap_or(&item.map).clone();
+      self.start_match(&item, map).await?;
+    }
+    Ok(())
+  }
+
+  async fn start_match(&self, item: &DueMatch, map: Map) -> Result<()> {
+    let match_id = item.tournament_match.id;
+    match self.create_game(item, map).await {
+      Ok(Some(game_id)) => {
+        self
+          .db
+          .exec(move |conn| crate::tournament::db::set_match_game(conn, match_id, game_id))
+          .await?;
+      }
+      Ok(None) => {
+        tracing::debug!(match_id, "no common node for tournament match");
+      }
+      Err(err) => {
+        tracing::error!(match_id, "create tournament game: {}", err);
+      }
+    }
+    Ok(())
+  }
+
+  async fn create_game(&self, item: &DueMatch, map: Map) -> Result<Option<i32>> {
+    let m = &item.tournament_match;
+    let player_ids = m.player_ids();
+
+    // organizers can pin all matches of a tournament to a node
+    let decision = match item.node_id {
+      Some(id) => NodeDecision::pinned(id),
+      None => {
+        let snapshot = self
+          .players
+          .send(GetPlayersPingSnapshot {
+            players: player_ids.clone(),
+          })
+          .await?;
+        let nodes = self.nodes.send(ListNode).await?;
+        match crate::node::policy::evaluate(None, &nodes, &snapshot.map, &player_ids) {
+          Some(decision) => decision,
+          None => return Ok(None),
+        }
+      }
+    };
+
+    let teams: Vec<Vec<i32>> = player_ids.iter().map(|id| vec![*id]).collect();
+    let slots = team_slots(&teams);
+
+    let game = self
+      .games
+      .send(CreateGameForPlayers {
+        params: CreateGameForPlayersParams {
+          name: format!(
+            "{} R{} #{}",
+            item.tournament_name,
+            m.round + 1,
+            m.position + 1
+          ),
+          map,
+          node_id: decision.node_id,
+          slots,
+          chat_rules: item.chat_rules.clone(),
+        },
+      })
+      .await??;
+
+    let (game_id, tournament_id) = (game.id, m.tournament_id);
+    self
+      .db
+      .exec(move |conn| {
+        crate::node::db::add_assignment(conn, game_id, None, Some(tournament_id), &decision)
+      })
+      .await?;
+
+    Ok(Some(game_id))
+  }
+}
+
+#[async_trait]
+impl Actor for TournamentScheduler {
+  async fn started(&mut self, ctx: &mut Context<Self>) {
+    self.handle(ctx, ScheduleTick).await;
+  }
+}
+
+#[async_trait]
+impl Service<Data> for TournamentScheduler {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    Ok(TournamentScheduler {
+      db: registry.data().db.clone(),
+      games: registry.resolve::<GameRegistry>().await?,
+      players: registry.resolve::<PlayerRegistry>().await?,
+      map_vetos: registry.resolve::<MapVetoRegistry>().await?,
+      nodes: registry.resolve::<NodeRegistry>().await?,
+      vetoing: BTreeSet::new(),
+    })
+  }
+}
+
+struct ScheduleTick;
+
+impl Message for ScheduleTick {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<ScheduleTick> for TournamentScheduler {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: ScheduleTick) {
+    if let Err(err) = self.run_schedule(ctx).await {
+      tracing::error!("tournament schedule: {}", err);
+    }
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      sleep(SCHEDULE_INTERVAL).await;
+      addr.notify(ScheduleTick).await.ok();
+    });
+  }
+}
+
+struct MapVetoDone {
+  item: DueMatch,
+  map: Option<Map>,
+}
+
+impl Message for MapVetoDone {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<MapVetoDone> for TournamentScheduler {
+  async fn handle(&mut self, _: &mut Context<Self>, MapVetoDone { item, map }: MapVetoDone) {
+    let match_id = item.tournament_match.id;
+    self.vetoing.remove(&match_id);
+    // retried on the next tick if the veto was cancelled
+    if let Some(map) = map {
+      if let Err(err) = self.start_match(&item, map).await {
+        tracing::error!(match_id, "start tournament match: {}", err);
+      }
+    }
+  }
+}
@@ -0,0 +1,170 @@
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{tournament, tournament_match};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::TournamentFormat")]
+pub enum TournamentFormat {
+  SingleElimination = 0,
+  RoundRobin = 1,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::TournamentStatus")]
+pub enum TournamentStatus {
+  Running = 0,
+  Finished = 1,
+  Cancelled = 2,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type = "flo_grpc::controller::TournamentMatchStatus")]
+pub enum TournamentMatchStatus {
+  /// Waiting for the results of previous matches
+  Pending = 0,
+  /// Both players are known, waiting for the scheduled time
+  Ready = 1,
+  /// The lobby has been created
+  Created = 2,
+  Finished = 3,
+  /// Nobody checked in
+  Cancelled = 4,
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone)]
+#[s2_grpc(message_type(flo_grpc::controller::Tournament))]
+pub struct Tournament {
+  pub id: i32,
+  pub name: String,
+  #[s2_grpc(proto_enum)]
+  pub format: TournamentFormat,
+  #[s2_grpc(proto_enum)]
+  pub status: TournamentStatus,
+  pub node_id: Option<i32>,
+  pub map_pool_id: Option<i32>,
+  pub check_in_window_secs: i32,
+  pub created_at: DateTime<Utc>,
+  pub matches: Vec<TournamentMatch>,
+}
+
+#[derive(Debug, Queryable)]
+pub(crate) struct TournamentRow {
+  pub id: i32,
+  pub name: String,
+  pub format: TournamentFormat,
+  pub status: TournamentStatus,
+  pub node_id: Option<i32>,
+  pub map_pool_id: Option<i32>,
+  pub check_in_window_secs: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+pub(crate) type TournamentColumns = (
+  tournament::id,
+  tournament::name,
+  tournament::format,
+  tournament::status,
+  tournament::node_id,
+  tournament::map_pool_id,
+  tournament::check_in_window_secs,
+  tournament::created_at,
+);
+
+impl TournamentRow {
+  pub(crate) const COLUMNS: TournamentColumns = (
+    tournament::id,
+    tournament::name,
+    tournament::format,
+    tournament::status,
+    tournament::node_id,
+    tournament::map_pool_id,
+    tournament::check_in_window_secs,
+    tournament::created_at,
+  );
+
+  pub(crate) fn into_tournament(self, matches: Vec<TournamentMatch>) -> Tournament {
+    Tournament {
+      id: self.id,
+      name: self.name,
+      format: self.format,
+      status: self.status,
+      node_id: self.node_id,
+      map_pool_id: self.map_pool_id,
+      check_in_window_secs: self.check_in_window_secs,
+      created_at: self.created_at,
+      matches,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone, Queryable)]
+#[s2_grpc(message_type(flo_grpc::controller::TournamentMatch))]
+pub struct TournamentMatch {
+  pub id: i32,
+  pub tournament_id: i32,
+  pub round: i32,
+  pub position: i32,
+  pub group_index: Option<i32>,
+  pub player1_id: Option<i32>,
+  pub player2_id: Option<i32>,
+  pub player1_checked_in_at: Option<DateTime<Utc>>,
+  pub player2_checked_in_at: Option<DateTime<Utc>>,
+  pub scheduled_at: Option<DateTime<Utc>>,
+  #[s2_grpc(proto_enum)]
+  pub status: TournamentMatchStatus,
+  pub game_id: Option<i32>,
+  pub winner_id: Option<i32>,
+}
+
+pub(crate) type TournamentMatchColumns = (
+  tournament_match::id,
+  tournament_match::tournament_id,
+  tournament_match::round,
+  tournament_match::position,
+  tournament_match::group_index,
+  tournament_match::player1_id,
+  tournament_match::player2_id,
+  tournament_match::player1_checked_in_at,
+  tournament_match::player2_checked_in_at,
+  tournament_match::scheduled_at,
+  tournament_match::status,
+  tournament_match::game_id,
+  tournament_match::winner_id,
+);
+
+impl TournamentMatch {
+  pub(crate) const COLUMNS: TournamentMatchColumns = (
+    tournament_match::id,
+    tournament_match::tournament_id,
+    tournament_match::round,
+    tournament_match::position,
+    tournament_match::group_index,
+    tournament_match::player1_id,
+    tournament_match::player2_id,
+    tournament_match::player1_checked_in_at,
+    tournament_match::player2_checked_in_at,
+    tournament_match::scheduled_at,
+    tournament_match::status,
+    tournament_match::game_id,
+    tournament_match::winner_id,
+  );
+
+  pub fn has_player(&self, player_id: i32) -> bool {
+    self.player1_id == Some(player_id) || self.player2_id == Some(player_id)
+  }
+
+  pub fn player_ids(&self) -> Vec<i32> {
+    self
+      .player1_id
+      .iter()
+      .chain(self.player2_id.iter())
+      .cloned()
+      .collect()
+  }
+}
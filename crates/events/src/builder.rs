@@ -0,0 +1,277 @@
+use chrono::{DateTime, Utc};
+use flo_observer::record::GameRecordData;
+use flo_w3gs::action::{IncomingAction, IncomingAction2};
+use flo_w3gs::actions::{Action, MMDMessage};
+use flo_w3gs::protocol::action::TimeSlot;
+use flo_w3gs::protocol::chat::{ChatFromHost, ChatMessage, MessageScope};
+use flo_w3gs::protocol::constants::PacketTypeId;
+use flo_w3gs::protocol::leave::PlayerLeft;
+use std::collections::BTreeMap;
+
+use crate::{GameEvent, GameEventKind};
+
+const MMD_FILENAME: &[u8] = b"MMD.Dat";
+
+/// Converts game records to events
+#[derive(Debug)]
+pub struct GameEventBuilder {
+  time: u32,
+  /// In-game player ids are `slot index + 1`
+  slot_players: BTreeMap<u8, i32>,
+  first_blood: bool,
+  ticks: bool,
+  action_summary: Option<ActionSummary>,
+  events: Vec<GameEvent>,
+}
+
+#[derive(Debug)]
+struct ActionSummary {
+  interval_ms: u32,
+  next_ms: u32,
+  counts: BTreeMap<i32, u32>,
+}
+
+impl GameEventBuilder {
+  /// `slot_players` maps in-game player ids to flo player ids
+  pub fn new<I>(slot_players: I) -> Self
+  where
+    I: IntoIterator<Item = (u8, i32)>,
+  {
+    Self {
+      time: 0,
+      slot_players: slot_players.into_iter().collect(),
+      first_blood: false,
+      ticks: false,
+      action_summary: None,
+      events: vec![],
+    }
+  }
+
+  /// Emits `ActionSummary` events every `interval_ms` of game time
+  pub fn with_action_summary(mut self, interval_ms: u32) -> Self {
+    self.action_summary = Some(ActionSummary {
+      interval_ms,
+      next_ms: interval_ms,
+      counts: BTreeMap::new(),
+    });
+    self
+  }
+
+  /// Emits a `Tick` event for every checksum record
+  pub fn with_ticks(mut self) -> Self {
+    self.ticks = true;
+    self
+  }
+
+  pub fn time_ms(&self) -> u32 {
+    self.time
+  }
+
+  pub fn events(&self) -> &[GameEvent] {
+    &self.events
+  }
+
+  /// Removes and returns the events built so far
+  pub fn take_events(&mut self) -> Vec<GameEvent> {
+    std::mem::replace(&mut self.events, vec![])
+  }
+
+  /// Flushes pending action counts and returns all events
+  pub fn finish(mut self) -> Vec<GameEvent> {
+    self.flush_action_summary();
+    self.events
+  }
+
+  /// Pushes an event at the current game time
+  pub fn push(&mut self, kind: GameEventKind) {
+    self.events.push(GameEvent {
+      at: None,
+      time_ms: Some(self.time),
+      kind,
+    })
+  }
+
+  /// Pushes an event that happened outside of the game, e.g. in the lobby
+  pub fn push_at(&mut self, at: DateTime<Utc>, kind: GameEventKind) {
+    self.events.push(GameEvent {
+      at: Some(at),
+      time_ms: None,
+      kind,
+    })
+  }
+
+  pub fn put_record(&mut self, record: &GameRecordData) {
+    match record {
+      GameRecordData::W3GS(packet) => match packet.type_id() {
+        PacketTypeId::IncomingAction => {
+          if let Ok(payload) = packet.decode_payload::<IncomingAction>() {
+            self.put_time_slot(&payload.0);
+          }
+        }
+        PacketTypeId::IncomingAction2 => {
+          if let Ok(payload) = packet.decode_payload::<IncomingAction2>() {
+            self.put_time_slot(&payload.0);
+          }
+        }
+        PacketTypeId::ChatFromHost => {
+          if let Ok(payload) = packet.decode_simple::<ChatFromHost>() {
+            let player_id = self.slot_player(payload.from_player());
+            let (scope, message) = match payload.0.message {
+              ChatMessage::Chat(message) => ("all".to_string(), message),
+              ChatMessage::Scoped { scope, message } => (
+                match scope {
+                  MessageScope::All => "all".to_string(),
+                  MessageScope::Allies => "allies".to_string(),
+                  MessageScope::Observers => "observers".to_string(),
+                  MessageScope::Player(id) => format!("player:{}", id),
+                },
+                message,
+              ),
+              _ => return,
+            };
+            self.push(GameEventKind::Chat {
+              player_id,
+              scope,
+              message: message.to_string_lossy().to_string(),
+            })
+          }
+        }
+        PacketTypeId::PlayerLeft => {
+          if let Ok(payload) = packet.decode_simple::<PlayerLeft>() {
+            self.push(GameEventKind::Leave {
+              player_id: self.slot_player(payload.player_id),
+              reason: format!("{:?}", payload.reason),
+            })
+          }
+        }
+        _ => {}
+      },
+      GameRecordData::StartLag(player_ids) => self.push(GameEventKind::LagStart {
+        player_ids: player_ids.clone(),
+      }),
+      GameRecordData::StopLag(player_id) => self.push(GameEventKind::LagStop {
+        player_id: *player_id,
+      }),
+      GameRecordData::GameEnd => {
+        self.flush_action_summary();
+        self.push(GameEventKind::End)
+      }
+      GameRecordData::TickChecksum { tick, checksum } => {
+        if self.ticks {
+          self.push(GameEventKind::Tick {
+            tick: *tick,
+            checksum: *checksum,
+          })
+        }
+      }
+      GameRecordData::RTTStats(_) => {}
+    }
+  }
+
+  fn slot_player(&self, slot_player_id: u8) -> Option<i32> {
+    self.slot_players.get(&slot_player_id).cloned()
+  }
+
+  fn put_time_slot(&mut self, slot: &TimeSlot) {
+    for chunk in &slot.actions {
+      let player_id = self.slot_player(chunk.player_id);
+      for item in chunk.actions() {
+        match item {
+          Ok(Action::PauseGame) => self.push(GameEventKind::Pause { player_id }),
+          Ok(Action::ResumeGame) => self.push(GameEventKind::Resume { player_id }),
+          Ok(Action::MMDMessage(msg)) => self.put_mmd_message(&msg),
+          Ok(action) if action.is_apm_action() => {
+            if let (Some(summary), Some(player_id)) = (self.action_summary.as_mut(), player_id) {
+              *summary.counts.entry(player_id).or_default() += 1;
+            }
+          }
+          Ok(_) => {}
+          Err(_) => break,
+        }
+      }
+    }
+    self.time += slot.time_increment_ms as u32;
+    if self
+      .action_summary
+      .as_ref()
+      .map(|v| self.time >= v.next_ms)
+      .unwrap_or_default()
+    {
+      self.flush_action_summary();
+    }
+  }
+
+  fn flush_action_summary(&mut self) {
+    let time = self.time;
+    let counts = match self.action_summary.as_mut() {
+      Some(summary) => {
+        while summary.next_ms <= time {
+          summary.next_ms += std::cmp::max(summary.interval_ms, 1);
+        }
+        std::mem::replace(&mut summary.counts, BTreeMap::new())
+      }
+      None => return,
+    };
+    for (player_id, action_count) in counts {
+      self.push(GameEventKind::ActionSummary {
+        player_id,
+        action_count,
+      })
+    }
+  }
+
+  // Event <name> <args...>, W3MMD player ids are 0-based slot player ids
+  fn put_mmd_message(&mut self, msg: &MMDMessage) {
+    if msg.name.as_bytes() != MMD_FILENAME {
+      return;
+    }
+    let value = msg.second_checksum.to_string_lossy();
+    let mut parts = value.split_whitespace();
+    if parts.next() != Some("Event") {
+      return;
+    }
+    let name = match parts.next() {
+      Some(v) => v.to_string(),
+      None => return,
+    };
+    let args: Vec<String> = parts.map(ToString::to_string).collect();
+    let player_id = args
+      .first()
+      .and_then(|v| v.parse::<u8>().ok())
+      .and_then(|pid| self.slot_player(pid.saturating_add(1)));
+    if !self.first_blood && name.eq_ignore_ascii_case("firstblood") {
+      self.first_blood = true;
+      self.push(GameEventKind::FirstBlood { player_id });
+    }
+    self.push(GameEventKind::MmdEvent {
+      player_id,
+      name,
+      args,
+    })
+  }
+}
+
+#[test]
+fn test_game_event_builder() {
+  let mut builder = GameEventBuilder::new(vec![(1, 10)]).with_ticks();
+  builder.put_record(&GameRecordData::StartLag(vec![10]));
+  builder.put_record(&GameRecordData::TickChecksum {
+    tick: 1,
+    checksum: 2,
+  });
+  builder.put_record(&GameRecordData::GameEnd);
+  let events = builder.finish();
+  assert_eq!(
+    events.into_iter().map(|e| e.kind).collect::<Vec<_>>(),
+    vec![
+      GameEventKind::LagStart {
+        player_ids: vec![10]
+      },
+      GameEventKind::Tick {
+        tick: 1,
+        checksum: 2
+      },
+      GameEventKind::End,
+    ]
+  );
+}
@@ -0,0 +1,104 @@
+//! Game events shared by the timeline, observer feeds and overlay APIs.
+//! Player ids are flo player ids, `None` if the in-game player is unknown.
+
+mod builder;
+pub use builder::GameEventBuilder;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct GameEvent {
+  /// Set for events before the game started
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub at: Option<DateTime<Utc>>,
+  /// Game time
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub time_ms: Option<u32>,
+  #[serde(flatten)]
+  pub kind: GameEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEventKind {
+  Join {
+    player_id: i32,
+  },
+  Loading {
+    player_id: i32,
+  },
+  Loaded {
+    player_id: i32,
+  },
+  /// A checksum the node agreed on
+  Tick {
+    tick: u32,
+    checksum: u32,
+  },
+  /// Actions counted towards APM since the previous summary of the player
+  ActionSummary {
+    player_id: i32,
+    action_count: u32,
+  },
+  Pause {
+    player_id: Option<i32>,
+  },
+  Resume {
+    player_id: Option<i32>,
+  },
+  LagStart {
+    player_ids: Vec<i32>,
+  },
+  LagStop {
+    player_id: i32,
+  },
+  Chat {
+    player_id: Option<i32>,
+    scope: String,
+    message: String,
+  },
+  /// A W3MMD `Event` message
+  MmdEvent {
+    player_id: Option<i32>,
+    name: String,
+    args: Vec<String>,
+  },
+  FirstBlood {
+    player_id: Option<i32>,
+  },
+  Leave {
+    player_id: Option<i32>,
+    reason: String,
+  },
+  End,
+  Result {
+    valid: bool,
+    winner_team: Option<i32>,
+  },
+}
+
+#[test]
+fn test_game_event_json() {
+  let event = GameEvent {
+    at: None,
+    time_ms: Some(1000),
+    kind: GameEventKind::ActionSummary {
+      player_id: 1,
+      action_count: 42,
+    },
+  };
+  let value = serde_json::to_value(&event).unwrap();
+  assert_eq!(
+    value,
+    serde_json::json!({
+      "time_ms": 1000,
+      "type": "action_summary",
+      "player_id": 1,
+      "action_count": 42,
+    })
+  );
+  assert_eq!(serde_json::from_value::<GameEvent>(value).unwrap(), event);
+}
@@ -2,10 +2,18 @@ use std::sync::Once;
 pub use tracing::{debug, error, info, instrument, span, warn, Level};
 pub use tracing_futures::Instrument;
 
+#[cfg(feature = "otel")]
+mod otel;
+
 static INIT: Once = Once::new();
 
 pub fn init() {
   INIT.call_once(|| {
+    #[cfg(feature = "otel")]
+    if otel::init() {
+      return;
+    }
+
     #[cfg(debug_assertions)]
     tracing_subscriber::fmt::init();
 
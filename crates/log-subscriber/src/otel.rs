@@ -0,0 +1,38 @@
+//! Exports spans over OTLP if `OTEL_EXPORTER_OTLP_ENDPOINT` is set,
+//! the service name is read from `OTEL_SERVICE_NAME`.
+
+use opentelemetry_otlp::OTEL_EXPORTER_OTLP_ENDPOINT;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Returns `false` if the exporter is not configured,
+/// must be called within a tokio runtime
+pub(crate) fn init() -> bool {
+  if std::env::var_os(OTEL_EXPORTER_OTLP_ENDPOINT).is_none() {
+    return false;
+  }
+
+  let tracer = match opentelemetry_otlp::new_pipeline()
+    .tracing()
+    .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+    .install_batch(opentelemetry_sdk::runtime::Tokio)
+  {
+    Ok(tracer) => tracer,
+    Err(err) => {
+      eprintln!("install otlp exporter: {}", err);
+      return false;
+    }
+  };
+
+  let fmt = tracing_subscriber::fmt::layer();
+  #[cfg(not(debug_assertions))]
+  let fmt = fmt.with_ansi(false);
+
+  tracing_subscriber::registry()
+    .with(EnvFilter::from_default_env())
+    .with(fmt)
+    .with(tracing_opentelemetry::layer().with_tracer(tracer))
+    .init();
+  true
+}
@@ -1,3 +1,5 @@
+pub mod trace;
+
 #[macro_export]
 macro_rules! result_ok {
   ($prefix:literal, $result:expr) => {
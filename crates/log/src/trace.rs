@@ -0,0 +1,67 @@
+//! W3C trace context propagation over flo packets.
+//! Spans only carry a trace context if the OpenTelemetry layer is installed
+//! (`flo-log-subscriber` with the `otel` feature), otherwise traceparents are empty.
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const TRACEPARENT: &str = "traceparent";
+
+/// Returns the `traceparent` of the span, or an empty string if the span is not traced
+pub fn traceparent(span: &Span) -> String {
+  let mut carrier = HashMap::new();
+  TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+  carrier.remove(TRACEPARENT).unwrap_or_default()
+}
+
+/// Returns the `traceparent` of the current span
+pub fn current_traceparent() -> String {
+  traceparent(&Span::current())
+}
+
+/// Makes the span a child of a remote span,
+/// must be called before the span is entered.
+/// Empty or invalid `traceparent`s are ignored.
+pub fn set_parent(span: &Span, traceparent: &str) {
+  if traceparent.is_empty() {
+    return;
+  }
+  let mut carrier = HashMap::with_capacity(1);
+  carrier.insert(TRACEPARENT.to_string(), traceparent.to_string());
+  let cx = TraceContextPropagator::new().extract(&carrier);
+  if cx.span().span_context().is_valid() {
+    span.set_parent(cx);
+  }
+}
+
+#[test]
+fn test_traceparent() {
+  use opentelemetry::trace::TracerProvider as _;
+  use tracing_subscriber::layer::SubscriberExt;
+
+  let parent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+  // no layer
+  let span = tracing::info_span!("test");
+  set_parent(&span, parent);
+  assert_eq!(traceparent(&span), "");
+
+  let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+  let subscriber = tracing_subscriber::registry()
+    .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+  tracing::subscriber::with_default(subscriber, || {
+    let span = tracing::info_span!("test");
+    set_parent(&span, parent);
+    let value = traceparent(&span);
+    assert!(value.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+    assert!(!value.contains("b7ad6b7169203331"));
+
+    let span = tracing::info_span!("test");
+    set_parent(&span, "invalid");
+    assert!(!traceparent(&span).starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+  });
+}
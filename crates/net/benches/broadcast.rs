@@ -0,0 +1,71 @@
+//! Fan-out of action ticks to the players of a 24-slot game,
+//! comparing encoding the packet per recipient with sharing the encoded payload
+//! as the node's broadcast does.
+//!
+//! cargo bench -p flo-net --bench broadcast
+
+use bytes::{Bytes, BytesMut};
+use flo_net::packet::Frame;
+use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket};
+use flo_util::alloc::CountingAlloc;
+use flo_w3gs::action::{IncomingAction, PlayerAction, TimeSlot};
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const TICKS: usize = 10_000;
+const PLAYERS: usize = 24;
+
+fn time_slot() -> TimeSlot {
+  TimeSlot {
+    time_increment_ms: 30,
+    actions: (1..=PLAYERS as u8)
+      .map(|player_id| PlayerAction {
+        player_id,
+        data: Bytes::from_static(&[0x10, 0x42, 0x00, 0x0d, 0x00, 0x03, 0x00]),
+      })
+      .collect(),
+  }
+}
+
+struct Recipient {
+  ack_q: W3GSAckQueue,
+  write_buf: BytesMut,
+}
+
+impl Recipient {
+  // mirrors `PlayerDispatchInfo::send_w3gs` followed by the stream write
+  fn send(&mut self, packet: W3GSPacket) {
+    let sid = self.ack_q.gen_next_send_sid();
+    let meta = W3GSMetadata::new(packet.type_id(), sid, self.ack_q.take_ack_received());
+    self.ack_q.push_send(meta.clone(), packet.clone());
+    Frame::from_w3gs(meta, packet).encode(&mut self.write_buf);
+    self.write_buf.clear();
+    self.ack_q.ack_sent(sid);
+  }
+}
+
+fn measure<F: FnMut(&mut [Recipient])>(name: &str, mut f: F) {
+  let mut recipients: Vec<_> = (0..PLAYERS)
+    .map(|_| Recipient {
+      ack_q: W3GSAckQueue::new(),
+      write_buf: BytesMut::with_capacity(1500),
+    })
+    .collect();
+  flo_util::alloc::measure(name, TICKS, "tick", || f(&mut recipients));
+}
+
+fn main() {
+  measure("fan-out/re-encode", |recipients| {
+    for recipient in recipients {
+      recipient.send(W3GSPacket::with_payload(IncomingAction(time_slot())).unwrap());
+    }
+  });
+
+  measure("fan-out/shared", |recipients| {
+    let packet = W3GSPacket::with_payload(IncomingAction(time_slot())).unwrap();
+    for recipient in recipients {
+      recipient.send(packet.clone());
+    }
+  });
+}
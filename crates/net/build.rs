@@ -1,6 +1,10 @@
+use std::path::Path;
+
 fn main() {
+  let out_dir = std::env::var_os("OUT_DIR").unwrap();
   let mut prost_build = prost_build::Config::new();
   prost_build.type_attribute(".", "#[derive(Serialize, Deserialize)]");
+  prost_build.file_descriptor_set_path(Path::new(&out_dir).join("flo_net_descriptor.bin"));
   prost_build
     .compile_protos(
       &[
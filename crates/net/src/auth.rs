@@ -0,0 +1,370 @@
+//! HMAC-SHA256 authentication of the frames between the controller and the nodes.
+//!
+//! Both sides derive the session keys from the node secret and the nonces
+//! exchanged in the handshake, keys are never sent over the wire.
+//! Each direction has its own key, a frame can't be reflected back to its sender.
+//! The controller rotates the key by sending a new nonce, frames signed with
+//! the previous key are accepted until the next rotation.
+//! Frames carry a sequence number, a frame is only accepted if it directly follows
+//! the previous one, so recorded frames can't be replayed, dropped or reordered.
+
+use bytes::Bytes;
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::convert::TryFrom;
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+use crate::error::{Error, Result};
+use crate::packet::{FloPacket, Frame, FramePayload, PacketTypeId};
+use crate::proto::flo_node::{PacketControllerRotateKey, PacketSignedFrame};
+
+pub const NONCE_LEN: usize = 16;
+
+const KEY_CONTEXT: &[u8] = b"flo-node-frame-key";
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn gen_nonce() -> Vec<u8> {
+  let mut nonce = vec![0; NONCE_LEN];
+  rand::thread_rng().fill_bytes(&mut nonce);
+  nonce
+}
+
+/// Compares shared secrets in constant time, only the length can be told apart
+pub fn secret_eq(a: &str, b: &str) -> bool {
+  a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+/// The side of the connection a `FrameAuth` belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Peer {
+  Controller,
+  Node,
+}
+
+impl Peer {
+  fn remote(self) -> Self {
+    match self {
+      Peer::Controller => Peer::Node,
+      Peer::Node => Peer::Controller,
+    }
+  }
+
+  fn label(self) -> &'static [u8] {
+    match self {
+      Peer::Controller => b"controller",
+      Peer::Node => b"node",
+    }
+  }
+}
+
+#[derive(Clone)]
+struct Key {
+  id: u32,
+  /// Signs the frames sent by this side
+  send: HmacSha256,
+  /// Verifies the frames sent by the remote side
+  recv: HmacSha256,
+}
+
+impl Key {
+  fn derive(secret: &[u8], id: u32, nonce: &[u8], local: Peer) -> Self {
+    Self {
+      id,
+      send: Self::derive_mac(secret, id, nonce, local),
+      recv: Self::derive_mac(secret, id, nonce, local.remote()),
+    }
+  }
+
+  fn derive_mac(secret: &[u8], id: u32, nonce: &[u8], sender: Peer) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("any key length");
+    mac.update(KEY_CONTEXT);
+    mac.update(sender.label());
+    mac.update(&id.to_le_bytes());
+    mac.update(nonce);
+    let key = mac.finalize().into_bytes();
+    HmacSha256::new_from_slice(&key).expect("any key length")
+  }
+
+  fn mac(&self, key: &HmacSha256, seq: u64, type_id: u8, payload: &[u8]) -> HmacSha256 {
+    let mut mac = key.clone();
+    mac.update(&self.id.to_le_bytes());
+    mac.update(&seq.to_le_bytes());
+    mac.update(&[type_id]);
+    mac.update(payload);
+    mac
+  }
+}
+
+/// Signs outgoing and verifies incoming frames of a node connection
+pub struct FrameAuth {
+  local: Peer,
+  secret: Vec<u8>,
+  current: Key,
+  previous: Option<Key>,
+  send_seq: u64,
+  recv_seq: u64,
+}
+
+impl fmt::Debug for FrameAuth {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("FrameAuth")
+      .field("local", &self.local)
+      .field("key_id", &self.current.id)
+      .field("send_seq", &self.send_seq)
+      .field("recv_seq", &self.recv_seq)
+      .finish()
+  }
+}
+
+impl FrameAuth {
+  pub fn new(
+    local: Peer,
+    secret: &str,
+    controller_nonce: &[u8],
+    node_nonce: &[u8],
+  ) -> Result<Self> {
+    if controller_nonce.len() != NONCE_LEN || node_nonce.len() != NONCE_LEN {
+      return Err(Error::InvalidNonce);
+    }
+    let secret = secret.as_bytes().to_vec();
+    let nonce = [controller_nonce, node_nonce].concat();
+    Ok(Self {
+      local,
+      current: Key::derive(&secret, 0, &nonce, local),
+      secret,
+      previous: None,
+      send_seq: 0,
+      recv_seq: 0,
+    })
+  }
+
+  pub fn sign(&mut self, frame: Frame) -> Result<Frame> {
+    let payload = match frame.payload {
+      FramePayload::Bytes(bytes) => bytes,
+      FramePayload::W3GS { .. } => return Err(Error::unexpected_packet_type_id(frame.type_id)),
+    };
+    let type_id = u8::from(frame.type_id);
+    self.send_seq += 1;
+    let mac = self
+      .current
+      .mac(&self.current.send, self.send_seq, type_id, &payload);
+    PacketSignedFrame {
+      key_id: self.current.id,
+      seq: self.send_seq,
+      type_id: type_id as u32,
+      payload: payload.to_vec(),
+      mac: mac.finalize().into_bytes().to_vec(),
+    }
+    .encode_as_frame()
+  }
+
+  /// Returns the inner frame of a `PacketSignedFrame`
+  pub fn verify(&mut self, frame: Frame) -> Result<Frame> {
+    let pkt: PacketSignedFrame = frame.decode()?;
+    let key = if pkt.key_id == self.current.id {
+      &self.current
+    } else {
+      self
+        .previous
+        .as_ref()
+        .filter(|key| key.id == pkt.key_id)
+        .ok_or(Error::InvalidFrameSignature)?
+    };
+    let type_id = u8::try_from(pkt.type_id).map_err(|_| Error::InvalidFrameSignature)?;
+    key
+      .mac(&key.recv, pkt.seq, type_id, &pkt.payload)
+      .verify(&pkt.mac)
+      .map_err(|_| Error::InvalidFrameSignature)?;
+    if pkt.seq <= self.recv_seq {
+      return Err(Error::FrameReplayed(pkt.seq));
+    }
+    if pkt.seq != self.recv_seq + 1 {
+      return Err(Error::FrameOutOfOrder {
+        expected: self.recv_seq + 1,
+        got: pkt.seq,
+      });
+    }
+    self.recv_seq = pkt.seq;
+    Ok(Frame::new_bytes(
+      PacketTypeId::from(type_id),
+      Bytes::from(pkt.payload),
+    ))
+  }
+
+  /// Switches to a new key, returns the signed `PacketControllerRotateKey`
+  /// which must be sent before any frame signed with the new key.
+  /// Called by the controller.
+  pub fn rotate(&mut self) -> Result<Frame> {
+    let pkt = PacketControllerRotateKey {
+      key_id: self.current.id.wrapping_add(1),
+      nonce: gen_nonce(),
+    };
+    let frame = self.sign(pkt.encode_as_frame()?)?;
+    self.set_key(pkt.key_id, &pkt.nonce);
+    Ok(frame)
+  }
+
+  /// Applies a verified `PacketControllerRotateKey`.
+  /// Called by the node.
+  pub fn apply_rotate(&mut self, pkt: &PacketControllerRotateKey) -> Result<()> {
+    if pkt.nonce.len() != NONCE_LEN || pkt.key_id != self.current.id.wrapping_add(1) {
+      return Err(Error::InvalidNonce);
+    }
+    self.set_key(pkt.key_id, &pkt.nonce);
+    Ok(())
+  }
+
+  fn set_key(&mut self, id: u32, nonce: &[u8]) {
+    let key = Key::derive(&self.secret, id, nonce, self.local);
+    self.previous = Some(std::mem::replace(&mut self.current, key));
+  }
+}
+
+#[test]
+fn test_frame_auth() {
+  use crate::proto::flo_node::PacketControllerQueryGameStatus;
+
+  let controller_nonce = gen_nonce();
+  let node_nonce = gen_nonce();
+  let mut controller =
+    FrameAuth::new(Peer::Controller, "secret", &controller_nonce, &node_nonce).unwrap();
+  let mut node = FrameAuth::new(Peer::Node, "secret", &controller_nonce, &node_nonce).unwrap();
+
+  let frame = PacketControllerQueryGameStatus::default()
+    .encode_as_frame()
+    .unwrap();
+  let signed = controller.sign(frame.clone()).unwrap();
+  assert_eq!(signed.type_id, PacketTypeId::SignedFrame);
+  let verified = node.verify(signed.clone()).unwrap();
+  assert_eq!(verified.type_id, frame.type_id);
+  assert_eq!(verified.body(), frame.body());
+
+  // replayed
+  assert!(matches!(node.verify(signed), Err(Error::FrameReplayed(1))));
+
+  // tampered
+  let mut pkt: PacketSignedFrame = controller.sign(frame.clone()).unwrap().decode().unwrap();
+  pkt.type_id = u8::from(PacketTypeId::ControllerCreateGame) as u32;
+  assert!(matches!(
+    node.verify(pkt.encode_as_frame().unwrap()),
+    Err(Error::InvalidFrameSignature)
+  ));
+
+  // wrong secret
+  let mut other =
+    FrameAuth::new(Peer::Controller, "other", &controller_nonce, &node_nonce).unwrap();
+  assert!(matches!(
+    node.verify(other.sign(frame.clone()).unwrap()),
+    Err(Error::InvalidFrameSignature)
+  ));
+
+  // unsigned
+  assert!(node.verify(frame.clone()).is_err());
+
+  // rotation, frames signed with the previous key are still accepted
+  let in_flight = node.sign(frame.clone()).unwrap();
+  let rotate = controller.rotate().unwrap();
+  let pkt: PacketControllerRotateKey = node.verify(rotate).unwrap().decode().unwrap();
+  node.apply_rotate(&pkt).unwrap();
+  controller.verify(in_flight).unwrap();
+  node
+    .verify(controller.sign(frame.clone()).unwrap())
+    .unwrap();
+  controller
+    .verify(node.sign(frame.clone()).unwrap())
+    .unwrap();
+
+  // the key before the previous one is dropped
+  let stale = FrameAuth::new(Peer::Node, "secret", &controller_nonce, &node_nonce)
+    .unwrap()
+    .sign(frame.clone())
+    .unwrap();
+  let rotate = controller.rotate().unwrap();
+  let pkt: PacketControllerRotateKey = node.verify(rotate).unwrap().decode().unwrap();
+  node.apply_rotate(&pkt).unwrap();
+  assert!(matches!(
+    controller.verify(stale),
+    Err(Error::InvalidFrameSignature)
+  ));
+}
+
+#[test]
+fn test_frame_auth_direction() {
+  use crate::proto::flo_node::PacketControllerQueryGameStatus;
+
+  let controller_nonce = gen_nonce();
+  let node_nonce = gen_nonce();
+  let mut controller =
+    FrameAuth::new(Peer::Controller, "secret", &controller_nonce, &node_nonce).unwrap();
+  let mut node = FrameAuth::new(Peer::Node, "secret", &controller_nonce, &node_nonce).unwrap();
+  let frame = PacketControllerQueryGameStatus::default()
+    .encode_as_frame()
+    .unwrap();
+
+  // a frame reflected back to its sender
+  let signed = controller.sign(frame.clone()).unwrap();
+  assert!(matches!(
+    controller.verify(signed.clone()),
+    Err(Error::InvalidFrameSignature)
+  ));
+  node.verify(signed).unwrap();
+
+  // a node can't sign frames accepted by another node
+  let mut other_node =
+    FrameAuth::new(Peer::Node, "secret", &controller_nonce, &node_nonce).unwrap();
+  assert!(matches!(
+    other_node.verify(node.sign(frame.clone()).unwrap()),
+    Err(Error::InvalidFrameSignature)
+  ));
+}
+
+#[test]
+fn test_frame_auth_sequence() {
+  use crate::proto::flo_node::PacketControllerQueryGameStatus;
+
+  let controller_nonce = gen_nonce();
+  let node_nonce = gen_nonce();
+  let mut controller =
+    FrameAuth::new(Peer::Controller, "secret", &controller_nonce, &node_nonce).unwrap();
+  let mut node = FrameAuth::new(Peer::Node, "secret", &controller_nonce, &node_nonce).unwrap();
+  let frame = PacketControllerQueryGameStatus::default()
+    .encode_as_frame()
+    .unwrap();
+
+  let first = controller.sign(frame.clone()).unwrap();
+  let second = controller.sign(frame.clone()).unwrap();
+  let third = controller.sign(frame.clone()).unwrap();
+
+  // dropped
+  assert!(matches!(
+    node.verify(second.clone()),
+    Err(Error::FrameOutOfOrder {
+      expected: 1,
+      got: 2
+    })
+  ));
+  node.verify(first.clone()).unwrap();
+  // reordered
+  assert!(matches!(
+    node.verify(third.clone()),
+    Err(Error::FrameOutOfOrder {
+      expected: 2,
+      got: 3
+    })
+  ));
+  node.verify(second).unwrap();
+  node.verify(third).unwrap();
+  // replayed
+  assert!(matches!(node.verify(first), Err(Error::FrameReplayed(1))));
+}
+
+#[test]
+fn test_secret_eq() {
+  assert!(secret_eq("secret", "secret"));
+  assert!(!secret_eq("secret", "secreT"));
+  assert!(!secret_eq("secret", "secrets"));
+  assert!(!secret_eq("", "secret"));
+}
@@ -1,4 +1,10 @@
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::ready;
+use std::collections::VecDeque;
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
 use tokio_util::codec::{Decoder, Encoder};
 
 use flo_util::binary::BinDecode;
@@ -107,3 +113,120 @@ impl FloFrameCodec {
     })
   }
 }
+
+/// Payloads shorter than this are copied next to their header
+const MIN_VECTORED_PAYLOAD_LEN: usize = 256;
+const MAX_IO_SLICES: usize = 64;
+
+/// Outgoing frames, written with vectored writes.
+/// Headers are encoded into the thread's buffer arena,
+/// payloads are written from their own buffers without copying.
+#[derive(Debug, Default)]
+pub(crate) struct FrameWriteQueue {
+  bufs: VecDeque<Bytes>,
+}
+
+impl FrameWriteQueue {
+  pub fn is_empty(&self) -> bool {
+    self.bufs.is_empty()
+  }
+
+  pub fn push(&mut self, frame: &Frame) {
+    let body = frame.body();
+    let header_len = Header::MIN_SIZE + frame.payload.len() - body.len();
+    let inline = body.len() < MIN_VECTORED_PAYLOAD_LEN;
+    let head = flo_util::buf::encode_bytes(
+      if inline {
+        header_len + body.len()
+      } else {
+        header_len
+      },
+      |buf| {
+        frame.encode_header(buf);
+        if inline {
+          buf.put_slice(body.as_ref());
+        }
+      },
+    );
+    self.bufs.push_back(head);
+    if !inline {
+      self.bufs.push_back(body.clone());
+    }
+  }
+
+  /// Writes until the queue is empty
+  pub fn poll_write<W>(&mut self, cx: &mut Context<'_>, mut io: Pin<&mut W>) -> Poll<io::Result<()>>
+  where
+    W: AsyncWrite,
+  {
+    while !self.bufs.is_empty() {
+      let n = {
+        let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+        let count = self
+          .bufs
+          .iter()
+          .zip(slices.iter_mut())
+          .map(|(buf, slice)| *slice = IoSlice::new(buf.as_ref()))
+          .count();
+        ready!(io.as_mut().poll_write_vectored(cx, &slices[..count]))?
+      };
+      if n == 0 {
+        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+      }
+      self.advance(n);
+    }
+    Poll::Ready(Ok(()))
+  }
+
+  fn advance(&mut self, mut n: usize) {
+    while n > 0 {
+      let front = self.bufs.front_mut().expect("written more than queued");
+      if front.len() > n {
+        front.advance(n);
+        return;
+      }
+      n -= front.len();
+      self.bufs.pop_front();
+    }
+  }
+}
+
+#[test]
+fn test_frame_write_queue() {
+  use crate::w3gs::{W3GSMetadata, W3GSPacketTypeId};
+
+  let frames = vec![
+    Frame::new(PacketTypeId::Ping, [1, 2, 3]),
+    Frame {
+      type_id: PacketTypeId::W3GS,
+      payload: FramePayload::W3GS {
+        metadata: W3GSMetadata::new(W3GSPacketTypeId::IncomingAction, 1, Some(0)),
+        payload: Bytes::from(vec![4; 1000]),
+      },
+    },
+  ];
+  let mut expected = BytesMut::new();
+  let mut queue = FrameWriteQueue::default();
+  for frame in &frames {
+    frame.encode(&mut expected);
+    queue.push(frame);
+  }
+  assert_eq!(queue.bufs.len(), 3);
+
+  // partial writes across buffer boundaries
+  let mut written = vec![];
+  while !queue.is_empty() {
+    let n = std::cmp::min(7, queue.bufs[0].len() + 1);
+    let mut taken = 0;
+    for buf in &queue.bufs {
+      let len = std::cmp::min(buf.len(), n - taken);
+      written.extend_from_slice(&buf[..len]);
+      taken += len;
+      if taken == n {
+        break;
+      }
+    }
+    queue.advance(taken);
+  }
+  assert_eq!(written, expected.as_ref());
+}
@@ -0,0 +1,405 @@
+//! Wire compatibility fixtures.
+//!
+//! [`export_fixtures`] writes the canonical encoding of every protobuf packet
+//! to `<dir>/<version>/<packet type>.bin`, one encoded frame per file.
+//! [`check_fixtures`] decodes the fixtures of every exported version with the
+//! current packet types and encodes them again, a removed field or a changed
+//! field type makes the bytes differ.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio_util::codec::Decoder;
+
+use crate::codec::FloFrameCodec;
+use crate::error::{Error, Result};
+use crate::packet::{FloPacket, Frame, PacketTypeId};
+use crate::proto::{flo_connect, flo_node, flo_observer, FILE_DESCRIPTOR_SET};
+
+/// Fixtures exported by this build are stored under this version
+pub const FIXTURE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Packet types without a protobuf payload
+const NOT_PROTOBUF: &[PacketTypeId] = &[
+  PacketTypeId::Ping,
+  PacketTypeId::Pong,
+  PacketTypeId::ClientShutdown,
+  PacketTypeId::ClientShutdownAck,
+  PacketTypeId::ObserverData,
+  PacketTypeId::ObserverDataEnd,
+  PacketTypeId::W3GS,
+  PacketTypeId::W3GSBatch,
+];
+
+/// Nested messages deeper than this are left empty
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct Fixture {
+  pub type_id: PacketTypeId,
+  /// The encoded frame
+  pub bytes: Bytes,
+}
+
+impl Fixture {
+  pub fn file_name(&self) -> String {
+    format!("{:?}.bin", self.type_id)
+  }
+}
+
+macro_rules! compat_packets {
+  ($($package:ident::$message:ident,)*) => {
+    fn canonical_frames(descriptors: &Descriptors) -> Result<Vec<Frame>> {
+      Ok(vec![
+        $(
+          canonical::<$package::$message>(
+            descriptors,
+            concat!(".", stringify!($package), ".", stringify!($message)),
+          )?,
+        )*
+      ])
+    }
+
+    fn reencode(frame: Frame) -> Result<Frame> {
+      match frame.type_id {
+        $(
+          <$package::$message as FloPacket>::TYPE_ID => {
+            frame.decode::<$package::$message>()?.encode_as_frame()
+          }
+        )*
+        other => Err(Error::unexpected_packet_type_id(other)),
+      }
+    }
+  };
+}
+
+compat_packets! {
+  flo_connect::PacketClientConnect,
+  flo_connect::PacketClientConnectAccept,
+  flo_connect::PacketClientConnectReject,
+  flo_connect::PacketClientDisconnect,
+  flo_connect::PacketGameInfo,
+  flo_connect::PacketGamePlayerEnter,
+  flo_connect::PacketGamePlayerLeave,
+  flo_connect::PacketGameSlotUpdate,
+  flo_connect::PacketGameSlotUpdateRequest,
+  flo_connect::PacketPlayerSessionUpdate,
+  flo_connect::PacketListNodesRequest,
+  flo_connect::PacketListNodes,
+  flo_connect::PacketGameSelectNodeRequest,
+  flo_connect::PacketGameSelectNode,
+  flo_connect::PacketPlayerPingMapUpdateRequest,
+  flo_connect::PacketPlayerPingMapUpdate,
+  flo_connect::PacketGamePlayerPingMapSnapshotRequest,
+  flo_connect::PacketGamePlayerPingMapSnapshot,
+  flo_connect::PacketGamePlayerToken,
+  flo_connect::PacketGameStartRequest,
+  flo_connect::PacketGameStarting,
+  flo_connect::PacketGameStartReject,
+  flo_connect::PacketGameStartPlayerClientInfoRequest,
+  flo_connect::PacketGameSlotClientStatusUpdate,
+  flo_connect::PacketAddNode,
+  flo_connect::PacketRemoveNode,
+  flo_connect::PacketPlayerMuteListUpdate,
+  flo_connect::PacketPlayerMuteAddRequest,
+  flo_connect::PacketPlayerMuteRemoveRequest,
+  flo_connect::PacketMatchmakingQueueJoinRequest,
+  flo_connect::PacketMatchmakingQueueLeaveRequest,
+  flo_connect::PacketMatchmakingQueueStatus,
+  flo_connect::PacketGameLobbyChatRequest,
+  flo_connect::PacketGameLobbyChat,
+  flo_connect::PacketGameResult,
+  flo_connect::PacketMapVetoStatus,
+  flo_connect::PacketMapVetoBanRequest,
+  flo_connect::PacketGameRehostRequest,
+  flo_connect::PacketGameRehostReject,
+  flo_connect::PacketGameSlotSwapRequest,
+  flo_connect::PacketGameSlotLockRequest,
+  flo_connect::PacketGameSlotReserveRequest,
+  flo_connect::PacketGameSlotManageReject,
+  flo_connect::PacketGameLeaveRequest,
+  flo_connect::PacketListLobbiesRequest,
+  flo_connect::PacketListLobbies,
+  flo_connect::PacketGameJoinRequest,
+  flo_connect::PacketGameJoinReject,
+  flo_node::PacketControllerConnect,
+  flo_node::PacketControllerConnectAccept,
+  flo_node::PacketControllerConnectReject,
+  flo_node::PacketControllerUpdateSlotStatus,
+  flo_node::PacketControllerUpdateSlotStatusAccept,
+  flo_node::PacketControllerUpdateSlotStatusReject,
+  flo_node::PacketControllerCreateGame,
+  flo_node::PacketControllerCreateGameAccept,
+  flo_node::PacketControllerCreateGameReject,
+  flo_node::PacketControllerQueryGameStatus,
+  flo_node::PacketControllerRotateKey,
+  flo_node::PacketControllerAbortGame,
+  flo_node::PacketControllerAbortGameAccept,
+  flo_node::PacketControllerAbortGameReject,
+  flo_node::PacketSignedFrame,
+  flo_node::PacketClientConnect,
+  flo_node::PacketClientConnectAccept,
+  flo_node::PacketClientConnectReject,
+  flo_node::PacketClientUpdateSlotClientStatusRequest,
+  flo_node::PacketClientUpdateSlotClientStatus,
+  flo_node::PacketClientUpdateSlotClientStatusReject,
+  flo_node::PacketClientTimeSyncRequest,
+  flo_node::PacketClientTimeSync,
+  flo_node::PacketClientIdleWarning,
+  flo_node::PacketNodeGameStatusUpdate,
+  flo_node::PacketNodeGameStatusUpdateBulk,
+  flo_node::PacketNodeGameResult,
+  flo_node::PacketNodeGameLog,
+  flo_node::PacketStandbyConnect,
+  flo_node::PacketStandbyConnectAccept,
+  flo_node::PacketStandbyGameCreate,
+  flo_node::PacketStandbyGameUpdate,
+  flo_node::PacketStandbyGameLog,
+  flo_node::PacketStandbyGameEnd,
+  flo_node::PacketNodeStandbyPromote,
+  flo_node::PacketControllerStandbyPromoteAccept,
+  flo_node::PacketControllerStandbyPromoteReject,
+  flo_observer::PacketObserverConnect,
+  flo_observer::PacketObserverConnectAccept,
+  flo_observer::PacketObserverConnectReject,
+  flo_observer::PacketObserverLiveStats,
+  flo_observer::PacketObserverChatHistory,
+}
+
+/// Canonical encodings of the protobuf packets of this build,
+/// every field is set to a value derived from its number or name
+pub fn fixtures() -> Result<Vec<Fixture>> {
+  let descriptors = Descriptors::load()?;
+  let mut fixtures: Vec<_> = canonical_frames(&descriptors)?
+    .into_iter()
+    .map(|frame| {
+      let mut buf = BytesMut::new();
+      frame.encode(&mut buf);
+      Fixture {
+        type_id: frame.type_id,
+        bytes: buf.freeze(),
+      }
+    })
+    .collect();
+  fixtures.sort_by_key(|fixture| u8::from(fixture.type_id));
+  Ok(fixtures)
+}
+
+/// Writes the fixtures to `<dir>/<version>/`, returns the version directory
+pub fn export_fixtures<P: AsRef<Path>>(dir: P, version: &str) -> Result<PathBuf> {
+  let dir = dir.as_ref().join(version);
+  fs::create_dir_all(&dir)?;
+  for fixture in fixtures()? {
+    fs::write(dir.join(fixture.file_name()), &fixture.bytes)?;
+  }
+  Ok(dir)
+}
+
+/// Checks the fixtures of every version in `dir`, returns the number of fixtures checked
+pub fn check_fixtures<P: AsRef<Path>>(dir: P) -> Result<usize> {
+  let mut checked = 0;
+  for version in fs::read_dir(dir)? {
+    let version = version?.path();
+    if !version.is_dir() {
+      continue;
+    }
+    for file in fs::read_dir(&version)? {
+      let path = file?.path();
+      check_fixture(&path, fs::read(&path)?).map_err(|err| Error::IncompatibleFixture {
+        path: path.display().to_string(),
+        reason: err.to_string(),
+      })?;
+      checked += 1;
+    }
+  }
+  Ok(checked)
+}
+
+fn check_fixture(path: &Path, bytes: Vec<u8>) -> Result<()> {
+  let mut buf = BytesMut::from(&bytes[..]);
+  let frame = FloFrameCodec::new()
+    .decode(&mut buf)?
+    .ok_or(Error::PayloadTooSmall)?;
+  if !buf.is_empty() {
+    return Err(Error::PayloadTooLarge);
+  }
+
+  let file_name = path.file_name().map(|v| v.to_string_lossy().to_string());
+  let expected = format!("{:?}.bin", frame.type_id);
+  if file_name.as_deref() != Some(expected.as_str()) {
+    return Err(Error::unexpected_packet_type_id(frame.type_id));
+  }
+
+  let mut encoded = BytesMut::new();
+  reencode(frame)?.encode(&mut encoded);
+  if encoded.as_ref() != bytes.as_slice() {
+    return Err(Error::FixtureChanged);
+  }
+  Ok(())
+}
+
+fn canonical<T>(descriptors: &Descriptors, name: &str) -> Result<Frame>
+where
+  T: FloPacket + Default,
+{
+  let mut buf = BytesMut::new();
+  descriptors.encode_message(name, 0, &mut buf);
+  // re-encoded by prost so the fixture has the field order of the generated types
+  T::decode(buf.freeze())?.encode_as_frame()
+}
+
+/// Messages of `FILE_DESCRIPTOR_SET` by full name, e.g. `.flo_node.PacketClientConnect`
+struct Descriptors(HashMap<String, DescriptorProto>);
+
+impl Descriptors {
+  fn load() -> Result<Self> {
+    let set = FileDescriptorSet::decode(FILE_DESCRIPTOR_SET)?;
+    let mut map = HashMap::new();
+    for file in set.file {
+      let prefix = format!(".{}", file.package());
+      for message in file.message_type {
+        Self::insert(&mut map, &prefix, message);
+      }
+    }
+    Ok(Self(map))
+  }
+
+  fn insert(map: &mut HashMap<String, DescriptorProto>, prefix: &str, message: DescriptorProto) {
+    let name = format!("{}.{}", prefix, message.name());
+    for nested in &message.nested_type {
+      Self::insert(map, &name, nested.clone());
+    }
+    map.insert(name, message);
+  }
+
+  fn encode_message(&self, name: &str, depth: usize, buf: &mut BytesMut) {
+    let message = self
+      .0
+      .get(name)
+      .unwrap_or_else(|| panic!("message not found in FILE_DESCRIPTOR_SET: {}", name));
+    let mut oneofs = vec![];
+    for field in &message.field {
+      if let Some(index) = field.oneof_index {
+        if !field.proto3_optional() {
+          // only the first field of a oneof is set
+          if oneofs.contains(&index) {
+            continue;
+          }
+          oneofs.push(index);
+        }
+      }
+      self.encode_field(field, depth, buf);
+    }
+  }
+
+  fn encode_field(&self, field: &FieldDescriptorProto, depth: usize, buf: &mut BytesMut) {
+    let number = field.number() as u64;
+    let repeated = field.label() == Label::Repeated;
+    match field.r#type() {
+      Type::String | Type::Bytes => {
+        key(number, WIRE_LEN, buf);
+        prost::encoding::encode_varint(field.name().len() as u64, buf);
+        buf.put_slice(field.name().as_bytes());
+      }
+      Type::Message => {
+        if depth >= MAX_DEPTH {
+          return;
+        }
+        let mut nested = BytesMut::new();
+        self.encode_message(field.type_name(), depth + 1, &mut nested);
+        key(number, WIRE_LEN, buf);
+        prost::encoding::encode_varint(nested.len() as u64, buf);
+        buf.put_slice(&nested);
+      }
+      Type::Group => {}
+      scalar => {
+        let mut value = BytesMut::new();
+        let wire_type = encode_scalar(scalar, number, &mut value);
+        if repeated {
+          // proto3 packs repeated scalars
+          key(number, WIRE_LEN, buf);
+          prost::encoding::encode_varint(value.len() as u64, buf);
+        } else {
+          key(number, wire_type, buf);
+        }
+        buf.put_slice(&value);
+      }
+    }
+  }
+}
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+fn key(number: u64, wire_type: u64, buf: &mut BytesMut) {
+  prost::encoding::encode_varint(number << 3 | wire_type, buf);
+}
+
+/// Encodes a non-default value for the field, returns the wire type
+fn encode_scalar(ty: Type, number: u64, buf: &mut BytesMut) -> u64 {
+  match ty {
+    Type::Double => {
+      buf.put_f64_le(number as f64);
+      WIRE_FIXED64
+    }
+    Type::Float => {
+      buf.put_f32_le(number as f32);
+      WIRE_FIXED32
+    }
+    Type::Fixed64 | Type::Sfixed64 => {
+      buf.put_u64_le(number);
+      WIRE_FIXED64
+    }
+    Type::Fixed32 | Type::Sfixed32 => {
+      buf.put_u32_le(number as u32);
+      WIRE_FIXED32
+    }
+    Type::Bool => {
+      prost::encoding::encode_varint(1, buf);
+      WIRE_VARINT
+    }
+    Type::Sint32 | Type::Sint64 => {
+      // zigzag
+      prost::encoding::encode_varint(number << 1, buf);
+      WIRE_VARINT
+    }
+    _ => {
+      prost::encoding::encode_varint(number, buf);
+      WIRE_VARINT
+    }
+  }
+}
+
+#[test]
+fn test_fixtures_cover_all_packet_types() {
+  let fixtures = fixtures().unwrap();
+  for v in 0..=u8::MAX {
+    let type_id = PacketTypeId::from(v);
+    if let PacketTypeId::UnknownValue(_) = type_id {
+      continue;
+    }
+    assert!(
+      NOT_PROTOBUF.contains(&type_id) || fixtures.iter().any(|f| f.type_id == type_id),
+      "{:?} has no fixture, add its message to `compat_packets!`",
+      type_id
+    );
+  }
+  for fixture in &fixtures {
+    check_fixture(Path::new(&fixture.file_name()), fixture.bytes.to_vec()).unwrap();
+  }
+}
+
+#[test]
+fn test_compat_fixtures() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+  if !dir.join(FIXTURE_VERSION).exists() {
+    export_fixtures(&dir, FIXTURE_VERSION).unwrap();
+  }
+  assert!(check_fixtures(&dir).unwrap() > 0);
+}
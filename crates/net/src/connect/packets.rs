@@ -35,3 +35,28 @@ packet_type!(RemoveNode, PacketRemoveNode);
 packet_type!(PlayerMuteListUpdate, PacketPlayerMuteListUpdate);
 packet_type!(PlayerMuteAddRequest, PacketPlayerMuteAddRequest);
 packet_type!(PlayerMuteRemoveRequest, PacketPlayerMuteRemoveRequest);
+packet_type!(
+  MatchmakingQueueJoinRequest,
+  PacketMatchmakingQueueJoinRequest
+);
+packet_type!(
+  MatchmakingQueueLeaveRequest,
+  PacketMatchmakingQueueLeaveRequest
+);
+packet_type!(MatchmakingQueueStatus, PacketMatchmakingQueueStatus);
+packet_type!(GameLobbyChatRequest, PacketGameLobbyChatRequest);
+packet_type!(GameLobbyChat, PacketGameLobbyChat);
+packet_type!(GameResult, PacketGameResult);
+packet_type!(MapVetoStatus, PacketMapVetoStatus);
+packet_type!(MapVetoBanRequest, PacketMapVetoBanRequest);
+packet_type!(GameRehostRequest, PacketGameRehostRequest);
+packet_type!(GameRehostReject, PacketGameRehostReject);
+packet_type!(GameSlotSwapRequest, PacketGameSlotSwapRequest);
+packet_type!(GameSlotLockRequest, PacketGameSlotLockRequest);
+packet_type!(GameSlotReserveRequest, PacketGameSlotReserveRequest);
+packet_type!(GameSlotManageReject, PacketGameSlotManageReject);
+packet_type!(GameLeaveRequest, PacketGameLeaveRequest);
+packet_type!(ListLobbiesRequest, PacketListLobbiesRequest);
+packet_type!(ListLobbies, PacketListLobbies);
+packet_type!(GameJoinRequest, PacketGameJoinRequest);
+packet_type!(GameJoinReject, PacketGameJoinReject);
@@ -1,5 +1,6 @@
 use thiserror::Error;
 
+use crate::error_code::ErrorCode;
 use crate::packet::PacketTypeId;
 use crate::w3gs::ParseW3GSPacketError;
 
@@ -24,6 +25,18 @@ pub enum Error {
   PacketFieldNotPresent,
   #[error("task cancelled unexpectedly")]
   Cancelled,
+  #[error("invalid frame signature")]
+  InvalidFrameSignature,
+  #[error("replayed frame: seq = {0}")]
+  FrameReplayed(u64),
+  #[error("out of order frame: expected seq = {expected}, got {got}")]
+  FrameOutOfOrder { expected: u64, got: u64 },
+  #[error("invalid nonce")]
+  InvalidNonce,
+  #[error("fixture {path} is incompatible: {reason}")]
+  IncompatibleFixture { path: String, reason: String },
+  #[error("fixture encoding changed")]
+  FixtureChanged,
   #[error("invalid W3GS frame")]
   ReadW3GSFrame(ParseW3GSPacketError),
   #[error("io: {0}")]
@@ -40,6 +53,36 @@ impl Error {
   pub fn unexpected_packet_type_id(got: PacketTypeId) -> Self {
     Self::UnexpectedPacketTypeId { got }
   }
+
+  /// The peer sent a malformed packet
+  pub fn is_decode(&self) -> bool {
+    matches!(
+      self,
+      Error::Decode(_) | Error::ProtoBufDecode(_) | Error::ReadW3GSFrame(_)
+    )
+  }
+
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      Error::StreamClosed | Error::Cancelled | Error::Io(_) => ErrorCode::Unavailable,
+      Error::StreamTimeout => ErrorCode::Timeout,
+      Error::InvalidFrameSignature
+      | Error::FrameReplayed(_)
+      | Error::FrameOutOfOrder { .. }
+      | Error::InvalidNonce => ErrorCode::PermissionDenied,
+      Error::PayloadTooLarge
+      | Error::PayloadTooSmall
+      | Error::UnexpectedPacketType { .. }
+      | Error::UnexpectedPacketTypeId { .. }
+      | Error::PacketFieldNotPresent
+      | Error::ReadW3GSFrame(_)
+      | Error::Decode(_)
+      | Error::ProtoBufDecode(_) => ErrorCode::Protocol,
+      Error::IncompatibleFixture { .. } | Error::FixtureChanged | Error::ProtoBufEncode(_) => {
+        ErrorCode::Internal
+      }
+    }
+  }
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,331 @@
+//! Error codes shared by the client, node and controller.
+//!
+//! Reject reasons sent over flo streams and errors returned by the controller
+//! gRPC API map to an [`ErrorCode`], its [`Recovery`] tells the receiver
+//! whether to retry, join again with a new token, or show the error.
+
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::proto::{flo_connect, flo_node, flo_observer};
+
+/// gRPC metadata key of the error code of a controller `Status`
+pub const GRPC_METADATA_KEY: &str = "x-flo-error-code";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum ErrorCode {
+  /// Not classified, treated as transient
+  Unknown,
+  /// Rejected by the peer without a specific reason
+  Rejected,
+  /// The stream was closed or could not be opened
+  Unavailable,
+  Timeout,
+  RateLimited,
+  Maintenance,
+  VersionTooOld,
+  InvalidToken,
+  InvalidSecretKey,
+  PermissionDenied,
+  InvalidArgument,
+  /// The player is already connected, the old connection is being replaced
+  ConnectionExists,
+  NotFound,
+  GameNotFound,
+  GameNotReady,
+  GameExists,
+  PlayerBusy,
+  InvalidStatus,
+  ObserverDelayNotOver,
+  /// The peer sent a malformed or unexpected packet
+  Protocol,
+  Internal,
+}
+
+/// What the receiver of an error should do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Recovery {
+  /// Send the same request again after a backoff
+  Retry,
+  /// Get a new token from the controller and join again
+  Rejoin,
+  /// Retrying won't help, show the error to the user
+  Surface,
+}
+
+impl ErrorCode {
+  pub const ALL: &'static [ErrorCode] = &[
+    ErrorCode::Unknown,
+    ErrorCode::Rejected,
+    ErrorCode::Unavailable,
+    ErrorCode::Timeout,
+    ErrorCode::RateLimited,
+    ErrorCode::Maintenance,
+    ErrorCode::VersionTooOld,
+    ErrorCode::InvalidToken,
+    ErrorCode::InvalidSecretKey,
+    ErrorCode::PermissionDenied,
+    ErrorCode::InvalidArgument,
+    ErrorCode::ConnectionExists,
+    ErrorCode::NotFound,
+    ErrorCode::GameNotFound,
+    ErrorCode::GameNotReady,
+    ErrorCode::GameExists,
+    ErrorCode::PlayerBusy,
+    ErrorCode::InvalidStatus,
+    ErrorCode::ObserverDelayNotOver,
+    ErrorCode::Protocol,
+    ErrorCode::Internal,
+  ];
+
+  pub fn recovery(self) -> Recovery {
+    match self {
+      ErrorCode::Unknown
+      | ErrorCode::Unavailable
+      | ErrorCode::Timeout
+      | ErrorCode::RateLimited
+      | ErrorCode::ConnectionExists
+      | ErrorCode::GameNotReady
+      | ErrorCode::ObserverDelayNotOver => Recovery::Retry,
+      ErrorCode::InvalidToken => Recovery::Rejoin,
+      ErrorCode::Rejected
+      | ErrorCode::Maintenance
+      | ErrorCode::VersionTooOld
+      | ErrorCode::InvalidSecretKey
+      | ErrorCode::PermissionDenied
+      | ErrorCode::InvalidArgument
+      | ErrorCode::NotFound
+      | ErrorCode::GameNotFound
+      | ErrorCode::GameExists
+      | ErrorCode::PlayerBusy
+      | ErrorCode::InvalidStatus
+      | ErrorCode::Protocol
+      | ErrorCode::Internal => Recovery::Surface,
+    }
+  }
+
+  pub fn is_retryable(self) -> bool {
+    self.recovery() == Recovery::Retry
+  }
+
+  /// Message shown to the user
+  pub fn hint(self) -> &'static str {
+    match self {
+      ErrorCode::Unknown => "Something went wrong, retrying.",
+      ErrorCode::Rejected => "The request was rejected by the server.",
+      ErrorCode::Unavailable => "Server unreachable, retrying.",
+      ErrorCode::Timeout => "Server did not respond in time, retrying.",
+      ErrorCode::RateLimited => "Too many requests, retrying shortly.",
+      ErrorCode::Maintenance => "The server is under maintenance, please try again later.",
+      ErrorCode::VersionTooOld => "Your Flo version is too old, please update.",
+      ErrorCode::InvalidToken => "Your session has expired, joining again.",
+      ErrorCode::InvalidSecretKey => "Invalid node secret.",
+      ErrorCode::PermissionDenied => "You don't have permission to do this.",
+      ErrorCode::InvalidArgument => "Invalid request.",
+      ErrorCode::ConnectionExists => "You are already connected, reconnecting.",
+      ErrorCode::NotFound => "Not found.",
+      ErrorCode::GameNotFound => "Game not found.",
+      ErrorCode::GameNotReady => "The game is not ready yet, retrying.",
+      ErrorCode::GameExists => "The game has already started.",
+      ErrorCode::PlayerBusy => "A player is already in another game.",
+      ErrorCode::InvalidStatus => "The game is not in a valid state for this.",
+      ErrorCode::ObserverDelayNotOver => "The observer delay is not over yet, retrying.",
+      ErrorCode::Protocol => "Unexpected data from the server, please update Flo.",
+      ErrorCode::Internal => "Internal server error.",
+    }
+  }
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      ErrorCode::Unknown => "unknown",
+      ErrorCode::Rejected => "rejected",
+      ErrorCode::Unavailable => "unavailable",
+      ErrorCode::Timeout => "timeout",
+      ErrorCode::RateLimited => "rate_limited",
+      ErrorCode::Maintenance => "maintenance",
+      ErrorCode::VersionTooOld => "version_too_old",
+      ErrorCode::InvalidToken => "invalid_token",
+      ErrorCode::InvalidSecretKey => "invalid_secret_key",
+      ErrorCode::PermissionDenied => "permission_denied",
+      ErrorCode::InvalidArgument => "invalid_argument",
+      ErrorCode::ConnectionExists => "connection_exists",
+      ErrorCode::NotFound => "not_found",
+      ErrorCode::GameNotFound => "game_not_found",
+      ErrorCode::GameNotReady => "game_not_ready",
+      ErrorCode::GameExists => "game_exists",
+      ErrorCode::PlayerBusy => "player_busy",
+      ErrorCode::InvalidStatus => "invalid_status",
+      ErrorCode::ObserverDelayNotOver => "observer_delay_not_over",
+      ErrorCode::Protocol => "protocol",
+      ErrorCode::Internal => "internal",
+    }
+  }
+}
+
+impl fmt::Display for ErrorCode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl FromStr for ErrorCode {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .iter()
+      .cloned()
+      .find(|code| code.as_str() == s)
+      .ok_or(())
+  }
+}
+
+impl From<flo_connect::ClientConnectRejectReason> for ErrorCode {
+  fn from(reason: flo_connect::ClientConnectRejectReason) -> Self {
+    use flo_connect::ClientConnectRejectReason;
+    match reason {
+      ClientConnectRejectReason::Unknown => ErrorCode::Rejected,
+      ClientConnectRejectReason::ClientVersionTooOld => ErrorCode::VersionTooOld,
+      ClientConnectRejectReason::InvalidToken => ErrorCode::InvalidToken,
+    }
+  }
+}
+
+impl From<flo_node::ClientConnectRejectReason> for ErrorCode {
+  fn from(reason: flo_node::ClientConnectRejectReason) -> Self {
+    use flo_node::ClientConnectRejectReason;
+    match reason {
+      ClientConnectRejectReason::Unknown => ErrorCode::Rejected,
+      ClientConnectRejectReason::InvalidToken => ErrorCode::InvalidToken,
+      ClientConnectRejectReason::Multi => ErrorCode::ConnectionExists,
+      ClientConnectRejectReason::Maintenance => ErrorCode::Maintenance,
+      ClientConnectRejectReason::Standby => ErrorCode::Unavailable,
+    }
+  }
+}
+
+impl From<flo_node::ControllerConnectRejectReason> for ErrorCode {
+  fn from(reason: flo_node::ControllerConnectRejectReason) -> Self {
+    use flo_node::ControllerConnectRejectReason;
+    match reason {
+      ControllerConnectRejectReason::Unknown => ErrorCode::Rejected,
+      ControllerConnectRejectReason::ControllerVersionTooOld => ErrorCode::VersionTooOld,
+      ControllerConnectRejectReason::InvalidSecretKey => ErrorCode::InvalidSecretKey,
+    }
+  }
+}
+
+impl From<flo_node::ControllerCreateGameRejectReason> for ErrorCode {
+  fn from(reason: flo_node::ControllerCreateGameRejectReason) -> Self {
+    use flo_node::ControllerCreateGameRejectReason;
+    match reason {
+      ControllerCreateGameRejectReason::Unknown => ErrorCode::Rejected,
+      ControllerCreateGameRejectReason::GameExists => ErrorCode::GameExists,
+      ControllerCreateGameRejectReason::PlayerBusy => ErrorCode::PlayerBusy,
+      ControllerCreateGameRejectReason::Maintenance => ErrorCode::Maintenance,
+      ControllerCreateGameRejectReason::PlayerNotReserved => ErrorCode::PermissionDenied,
+      ControllerCreateGameRejectReason::NodeFull => ErrorCode::Unavailable,
+    }
+  }
+}
+
+impl From<flo_node::ControllerAbortGameRejectReason> for ErrorCode {
+  fn from(reason: flo_node::ControllerAbortGameRejectReason) -> Self {
+    use flo_node::ControllerAbortGameRejectReason;
+    match reason {
+      ControllerAbortGameRejectReason::Unknown => ErrorCode::Rejected,
+      ControllerAbortGameRejectReason::NotFound => ErrorCode::GameNotFound,
+      ControllerAbortGameRejectReason::GameEnded => ErrorCode::InvalidStatus,
+    }
+  }
+}
+
+impl From<flo_node::UpdateSlotClientStatusRejectReason> for ErrorCode {
+  fn from(reason: flo_node::UpdateSlotClientStatusRejectReason) -> Self {
+    use flo_node::UpdateSlotClientStatusRejectReason;
+    match reason {
+      UpdateSlotClientStatusRejectReason::Unknown => ErrorCode::Rejected,
+      UpdateSlotClientStatusRejectReason::NotFound => ErrorCode::NotFound,
+      UpdateSlotClientStatusRejectReason::InvalidStatus => ErrorCode::InvalidStatus,
+      UpdateSlotClientStatusRejectReason::Maintenance => ErrorCode::Maintenance,
+    }
+  }
+}
+
+impl From<flo_observer::ObserverConnectRejectReason> for ErrorCode {
+  fn from(reason: flo_observer::ObserverConnectRejectReason) -> Self {
+    use flo_observer::ObserverConnectRejectReason;
+    match reason {
+      ObserverConnectRejectReason::Unknown => ErrorCode::Rejected,
+      ObserverConnectRejectReason::ObserverVersionTooOld => ErrorCode::VersionTooOld,
+      ObserverConnectRejectReason::InvalidToken => ErrorCode::InvalidToken,
+      ObserverConnectRejectReason::GameNotFound => ErrorCode::GameNotFound,
+      ObserverConnectRejectReason::GameNotReady => ErrorCode::GameNotReady,
+      ObserverConnectRejectReason::DelayNotOver => ErrorCode::ObserverDelayNotOver,
+    }
+  }
+}
+
+#[test]
+fn test_error_code_str() {
+  use std::collections::HashSet;
+
+  for code in ErrorCode::ALL {
+    assert_eq!(code.as_str().parse(), Ok(*code));
+    assert_eq!(code.to_string(), code.as_str());
+  }
+  let names: HashSet<_> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+  assert_eq!(names.len(), ErrorCode::ALL.len());
+  assert_eq!("".parse::<ErrorCode>(), Err(()));
+  assert_eq!("Unknown".parse::<ErrorCode>(), Err(()));
+}
+
+#[test]
+fn test_error_code_all() {
+  // a new variant fails to compile here until it's added to the match and to `ALL`
+  let listed = |code: ErrorCode| match code {
+    ErrorCode::Unknown
+    | ErrorCode::Rejected
+    | ErrorCode::Unavailable
+    | ErrorCode::Timeout
+    | ErrorCode::RateLimited
+    | ErrorCode::Maintenance
+    | ErrorCode::VersionTooOld
+    | ErrorCode::InvalidToken
+    | ErrorCode::InvalidSecretKey
+    | ErrorCode::PermissionDenied
+    | ErrorCode::InvalidArgument
+    | ErrorCode::ConnectionExists
+    | ErrorCode::NotFound
+    | ErrorCode::GameNotFound
+    | ErrorCode::GameNotReady
+    | ErrorCode::GameExists
+    | ErrorCode::PlayerBusy
+    | ErrorCode::InvalidStatus
+    | ErrorCode::ObserverDelayNotOver
+    | ErrorCode::Protocol
+    | ErrorCode::Internal => ErrorCode::ALL.contains(&code),
+  };
+  assert_eq!(ErrorCode::ALL.len(), 21);
+  assert!(ErrorCode::ALL.iter().all(|code| listed(*code)));
+}
+
+#[test]
+fn test_reject_reason_recovery() {
+  use flo_node::ClientConnectRejectReason;
+  let recovery = |reason: ClientConnectRejectReason| ErrorCode::from(reason).recovery();
+  assert_eq!(recovery(ClientConnectRejectReason::Multi), Recovery::Retry);
+  assert_eq!(
+    recovery(ClientConnectRejectReason::InvalidToken),
+    Recovery::Rejoin
+  );
+  assert_eq!(
+    recovery(ClientConnectRejectReason::Maintenance),
+    Recovery::Surface
+  );
+  assert_eq!(
+    recovery(ClientConnectRejectReason::Unknown),
+    Recovery::Surface
+  );
+}
@@ -0,0 +1,98 @@
+//! Fuzz entry points, called by the targets in `fuzz/`.
+
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+pub use flo_w3gs::fuzz::StreamInput;
+
+use crate::auth::{FrameAuth, Peer, NONCE_LEN};
+use crate::codec::FloFrameCodec;
+use crate::packet::{Frame, PacketTypeId};
+use crate::w3gs::W3GSFrameExt;
+
+/// Feeds the chunks to `FloFrameCodec` as they would arrive from a socket,
+/// then decodes the W3GS packets and the signed frames
+pub fn frame_codec(input: StreamInput) {
+  let mut codec = FloFrameCodec::new();
+  let mut auth =
+    FrameAuth::new(Peer::Node, "fuzz", &[0; NONCE_LEN], &[0; NONCE_LEN]).expect("nonce len");
+  let mut buf = BytesMut::new();
+  for chunk in input.chunks {
+    buf.extend_from_slice(&chunk);
+    loop {
+      match codec.decode(&mut buf) {
+        Ok(Some(frame)) => frame_payload(&mut auth, frame),
+        Ok(None) => break,
+        Err(_) => return,
+      }
+    }
+  }
+}
+
+fn frame_payload(auth: &mut FrameAuth, frame: Frame) {
+  match frame.type_id {
+    PacketTypeId::W3GS => {
+      if let Ok((_, packet)) = frame.try_into_w3gs() {
+        flo_w3gs::fuzz::payload(&packet);
+      }
+    }
+    PacketTypeId::W3GSBatch => {
+      if let Ok(items) = frame.try_into_w3gs_batch() {
+        for (_, packet) in &items {
+          flo_w3gs::fuzz::payload(packet);
+        }
+      }
+    }
+    PacketTypeId::SignedFrame => {
+      auth.verify(frame).ok();
+    }
+    _ => {}
+  }
+}
+
+#[test]
+fn test_frame_codec_malformed() {
+  use crate::w3gs::{W3GSBatch, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
+  use flo_w3gs::protocol::constants::LeaveReason;
+  use flo_w3gs::protocol::leave::LeaveReq;
+
+  let packet = W3GSPacket::simple(LeaveReq::new(LeaveReason::LeaveLost)).unwrap();
+  let mut batch = W3GSBatch::new();
+  for sid in 0..2 {
+    batch.push(
+      W3GSMetadata::new(W3GSPacketTypeId::LeaveReq, sid, None),
+      packet.clone(),
+    );
+  }
+  let frames = vec![
+    Frame::from_w3gs(
+      W3GSMetadata::new(W3GSPacketTypeId::LeaveReq, 1, Some(0)),
+      packet.clone(),
+    ),
+    batch.into_frame().unwrap(),
+    Frame::new(PacketTypeId::SignedFrame, [1, 2, 3]),
+  ];
+
+  for frame in &frames {
+    let mut buf = BytesMut::new();
+    frame.encode(&mut buf);
+    let bytes = buf.freeze();
+
+    for len in 0..=bytes.len() {
+      frame_codec(StreamInput {
+        chunks: vec![bytes[..len].to_vec(), bytes[len..].to_vec()],
+      });
+    }
+
+    let mut mutated = bytes.to_vec();
+    for i in 0..mutated.len() {
+      for v in [0x00, 0x01, 0x7F, 0xFF] {
+        let prev = std::mem::replace(&mut mutated[i], v);
+        frame_codec(StreamInput {
+          chunks: vec![mutated.clone()],
+        });
+        mutated[i] = prev;
+      }
+    }
+  }
+}
@@ -2,11 +2,17 @@ mod codec;
 mod common;
 mod version;
 
+pub use codec::FloFrameCodec;
+
 pub mod error;
+pub mod error_code;
 #[macro_use]
 pub mod packet;
 
+pub mod auth;
+pub mod compat;
 pub mod constants;
+pub mod fuzz;
 pub mod listener;
 pub mod ping;
 pub mod stream;
@@ -14,6 +20,11 @@ pub mod time;
 pub mod w3gs;
 
 pub mod proto {
+  /// Encoded `FileDescriptorSet` of the flo protocol messages,
+  /// for validating messages outside of Rust
+  pub const FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/flo_net_descriptor.bin"));
+
   pub mod flo_common {
     #[allow(unused)]
     use serde::{Deserialize, Serialize};
@@ -16,6 +16,11 @@ packet_type!(ControllerCreateGame, PacketControllerCreateGame);
 packet_type!(ControllerCreateGameAccept, PacketControllerCreateGameAccept);
 packet_type!(ControllerCreateGameReject, PacketControllerCreateGameReject);
 packet_type!(ControllerQueryGameStatus, PacketControllerQueryGameStatus);
+packet_type!(ControllerRotateKey, PacketControllerRotateKey);
+packet_type!(ControllerAbortGame, PacketControllerAbortGame);
+packet_type!(ControllerAbortGameAccept, PacketControllerAbortGameAccept);
+packet_type!(ControllerAbortGameReject, PacketControllerAbortGameReject);
+packet_type!(SignedFrame, PacketSignedFrame);
 packet_type!(ClientConnect, PacketClientConnect);
 packet_type!(ClientConnectAccept, PacketClientConnectAccept);
 packet_type!(ClientConnectReject, PacketClientConnectReject);
@@ -31,5 +36,25 @@ packet_type!(
   ClientUpdateSlotClientStatusReject,
   PacketClientUpdateSlotClientStatusReject
 );
+packet_type!(ClientTimeSyncRequest, PacketClientTimeSyncRequest);
+packet_type!(ClientTimeSync, PacketClientTimeSync);
+packet_type!(ClientIdleWarning, PacketClientIdleWarning);
 packet_type!(NodeGameStatusUpdate, PacketNodeGameStatusUpdate);
 packet_type!(NodeGameStatusUpdateBulk, PacketNodeGameStatusUpdateBulk);
+packet_type!(NodeGameResult, PacketNodeGameResult);
+packet_type!(NodeGameLog, PacketNodeGameLog);
+packet_type!(StandbyConnect, PacketStandbyConnect);
+packet_type!(StandbyConnectAccept, PacketStandbyConnectAccept);
+packet_type!(StandbyGameCreate, PacketStandbyGameCreate);
+packet_type!(StandbyGameUpdate, PacketStandbyGameUpdate);
+packet_type!(StandbyGameLog, PacketStandbyGameLog);
+packet_type!(StandbyGameEnd, PacketStandbyGameEnd);
+packet_type!(NodeStandbyPromote, PacketNodeStandbyPromote);
+packet_type!(
+  ControllerStandbyPromoteAccept,
+  PacketControllerStandbyPromoteAccept
+);
+packet_type!(
+  ControllerStandbyPromoteReject,
+  PacketControllerStandbyPromoteReject
+);
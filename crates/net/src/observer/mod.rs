@@ -2,4 +2,6 @@ pub use crate::proto::flo_observer::*;
 
 packet_type!(ObserverConnect, PacketObserverConnect);
 packet_type!(ObserverConnectAccept, PacketObserverConnectAccept);
-packet_type!(ObserverConnectReject, PacketObserverConnectReject);
\ No newline at end of file
+packet_type!(ObserverConnectReject, PacketObserverConnectReject);
+packet_type!(ObserverLiveStats, PacketObserverLiveStats);
+packet_type!(ObserverChatHistory, PacketObserverChatHistory);
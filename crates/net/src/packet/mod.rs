@@ -15,12 +15,10 @@ where
   const TYPE_ID: PacketTypeId;
 
   fn encode_as_frame(&self) -> Result<Frame> {
-    let payload_len = self.encoded_len();
-    let mut buf = BytesMut::with_capacity(payload_len);
-    self.encode(&mut buf)?;
+    let buf = flo_util::buf::try_encode_bytes(self.encoded_len(), |buf| self.encode(buf))?;
     Ok(Frame {
       type_id: Self::TYPE_ID,
-      payload: FramePayload::Bytes(buf.freeze()),
+      payload: FramePayload::Bytes(buf),
     })
   }
 }
@@ -120,19 +118,24 @@ impl Frame {
 
   pub fn encode(&self, dst: &mut BytesMut) {
     dst.reserve(Header::MIN_SIZE + self.payload.len());
+    self.encode_header(dst);
+    dst.put(self.body().as_ref());
+  }
+
+  /// Encodes the header and the W3GS metadata, everything before [`Frame::body`]
+  pub fn encode_header(&self, dst: &mut BytesMut) {
     self.type_id.encode(dst);
     (self.payload.len() as u16).encode(dst);
+    if let FramePayload::W3GS { ref metadata, .. } = self.payload {
+      metadata.encode(dst);
+    }
+  }
+
+  /// Payload bytes following the W3GS metadata
+  pub fn body(&self) -> &Bytes {
     match self.payload {
-      FramePayload::Bytes(ref bytes) => {
-        dst.put(bytes.as_ref());
-      }
-      FramePayload::W3GS {
-        ref metadata,
-        ref payload,
-      } => {
-        metadata.encode(dst);
-        dst.put(payload.as_ref());
-      }
+      FramePayload::Bytes(ref bytes) => bytes,
+      FramePayload::W3GS { ref payload, .. } => payload,
     }
   }
 }
@@ -236,6 +239,36 @@ pub enum PacketTypeId {
   PlayerMuteAddRequest,
   #[bin(value = 0x1F)]
   PlayerMuteRemoveRequest,
+  #[bin(value = 0x20)]
+  MatchmakingQueueJoinRequest,
+  #[bin(value = 0x21)]
+  MatchmakingQueueLeaveRequest,
+  #[bin(value = 0x22)]
+  MatchmakingQueueStatus,
+  #[bin(value = 0x23)]
+  GameLobbyChatRequest,
+  #[bin(value = 0x24)]
+  GameLobbyChat,
+  #[bin(value = 0x25)]
+  GameResult,
+  #[bin(value = 0x26)]
+  MapVetoStatus,
+  #[bin(value = 0x27)]
+  MapVetoBanRequest,
+  #[bin(value = 0x28)]
+  GameRehostRequest,
+  #[bin(value = 0x29)]
+  GameRehostReject,
+  #[bin(value = 0x2A)]
+  GameSlotSwapRequest,
+  #[bin(value = 0x2B)]
+  GameSlotLockRequest,
+  #[bin(value = 0x2C)]
+  GameSlotReserveRequest,
+  #[bin(value = 0x2D)]
+  GameSlotManageReject,
+  #[bin(value = 0x2E)]
+  GameLeaveRequest,
 
   // Lobby <-> Node
   #[bin(value = 0x30)]
@@ -258,6 +291,16 @@ pub enum PacketTypeId {
   ControllerUpdateSlotStatusReject,
   #[bin(value = 0x39)]
   ControllerQueryGameStatus,
+  #[bin(value = 0x3A)]
+  ControllerRotateKey,
+  #[bin(value = 0x3B)]
+  SignedFrame,
+  #[bin(value = 0x3C)]
+  ControllerAbortGame,
+  #[bin(value = 0x3D)]
+  ControllerAbortGameAccept,
+  #[bin(value = 0x3E)]
+  ControllerAbortGameReject,
 
   // Client <-> Node
   #[bin(value = 0x40)]
@@ -276,12 +319,44 @@ pub enum PacketTypeId {
   ClientShutdown,
   #[bin(value = 0x47)]
   ClientShutdownAck,
+  #[bin(value = 0x48)]
+  ClientTimeSyncRequest,
+  #[bin(value = 0x49)]
+  ClientTimeSync,
+  #[bin(value = 0x4A)]
+  ClientIdleWarning,
 
   // Node -> [Client, Controller]
   #[bin(value = 0x50)]
   NodeGameStatusUpdate,
   #[bin(value = 0x51)]
   NodeGameStatusUpdateBulk,
+  #[bin(value = 0x52)]
+  NodeGameResult,
+  #[bin(value = 0x53)]
+  NodeGameLog,
+
+  // Node -> Standby Node
+  #[bin(value = 0x54)]
+  StandbyConnect,
+  #[bin(value = 0x55)]
+  StandbyConnectAccept,
+  #[bin(value = 0x56)]
+  StandbyGameCreate,
+  #[bin(value = 0x57)]
+  StandbyGameUpdate,
+  #[bin(value = 0x58)]
+  StandbyGameLog,
+  #[bin(value = 0x59)]
+  StandbyGameEnd,
+
+  // Standby Node <-> Controller
+  #[bin(value = 0x5A)]
+  NodeStandbyPromote,
+  #[bin(value = 0x5B)]
+  ControllerStandbyPromoteAccept,
+  #[bin(value = 0x5C)]
+  ControllerStandbyPromoteReject,
 
   // Client <-> Observer
   #[bin(value = 0x60)]
@@ -294,9 +369,25 @@ pub enum PacketTypeId {
   ObserverData,
   #[bin(value = 0x64)]
   ObserverDataEnd,
+  #[bin(value = 0x65)]
+  ObserverLiveStats,
+  #[bin(value = 0x66)]
+  ObserverChatHistory,
+
+  // Client <-> Controller
+  #[bin(value = 0x70)]
+  ListLobbiesRequest,
+  #[bin(value = 0x71)]
+  ListLobbies,
+  #[bin(value = 0x72)]
+  GameJoinRequest,
+  #[bin(value = 0x73)]
+  GameJoinReject,
 
   #[bin(value = 0xF7)]
   W3GS,
+  #[bin(value = 0xF8)]
+  W3GSBatch,
   UnknownValue(u8),
 }
 
@@ -1,17 +1,18 @@
 use bytes::Bytes;
 use futures::future::poll_fn;
-use futures::sink::SinkExt;
 use futures::stream::TryStreamExt;
-use futures::{Sink, Stream};
+use futures::Stream;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::net::{TcpStream, ToSocketAddrs};
 use tokio::time::timeout;
-use tokio_util::codec::Framed;
+use tokio_util::codec::FramedRead;
 
-use crate::codec::FloFrameCodec;
+use flo_util::buf::READ_BUFFER_SIZE;
+
+use crate::codec::{FloFrameCodec, FrameWriteQueue};
 use crate::error::*;
 use crate::packet::{FloPacket, Frame};
 use tokio::io::AsyncWriteExt;
@@ -21,7 +22,8 @@ const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
 #[derive(Debug)]
 pub struct FloStream {
   pub timeout: Duration,
-  pub(crate) transport: Framed<TcpStream, FloFrameCodec>,
+  pub(crate) transport: FramedRead<TcpStream, FloFrameCodec>,
+  write_queue: FrameWriteQueue,
 }
 
 impl FloStream {
@@ -33,11 +35,7 @@ impl FloStream {
     //TODO: not supported by current tokio
     //socket.set_keepalive(None).ok();
 
-    let transport = Framed::new(socket, FloFrameCodec::new());
-    Ok(FloStream {
-      transport,
-      timeout: DEFAULT_TIMEOUT,
-    })
+    Ok(FloStream::new(socket))
   }
 
   pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
@@ -46,16 +44,13 @@ impl FloStream {
     // not supported by tokio atm
     //socket.set_keepalive(Some(Duration::from_secs(30)))?;
 
-    let transport = Framed::new(socket, FloFrameCodec::new());
-    Ok(FloStream {
-      transport,
-      timeout: DEFAULT_TIMEOUT,
-    })
+    Ok(FloStream::new(socket))
   }
 
   pub fn new(socket: TcpStream) -> Self {
     FloStream {
-      transport: Framed::new(socket, FloFrameCodec::new()),
+      transport: FramedRead::with_capacity(socket, FloFrameCodec::new(), READ_BUFFER_SIZE),
+      write_queue: FrameWriteQueue::default(),
       timeout: DEFAULT_TIMEOUT,
     }
   }
@@ -76,7 +71,8 @@ impl FloStream {
   }
 
   pub async fn send_frame_timeout(&mut self, frame: Frame) -> Result<()> {
-    timeout(self.timeout, self.transport.send(frame))
+    self.write_queue.push(&frame);
+    timeout(self.timeout, self.flush())
       .await
       .map_err(|_elapsed| Error::StreamTimeout)??;
     Ok(())
@@ -84,8 +80,8 @@ impl FloStream {
 
   #[inline]
   pub async fn send_frame(&mut self, frame: Frame) -> Result<()> {
-    self.transport.send(frame).await?;
-    Ok(())
+    self.write_queue.push(&frame);
+    self.flush().await
   }
 
   #[inline]
@@ -93,8 +89,10 @@ impl FloStream {
   where
     I: IntoIterator<Item = Frame>,
   {
-    let mut stream = tokio_stream::iter(iter.into_iter().map(Ok));
-    timeout(self.timeout, self.transport.send_all(&mut stream))
+    for frame in iter {
+      self.write_queue.push(&frame);
+    }
+    timeout(self.timeout, self.flush())
       .await
       .map_err(|_elapsed| Error::StreamTimeout)??;
     Ok(())
@@ -149,24 +147,31 @@ impl FloStream {
   }
 
   pub async fn flush(&mut self) -> Result<()> {
-    poll_fn(|ctx| Pin::new(&mut self.transport).poll_flush(ctx)).await?;
+    self.write_queued().await?;
     self.transport.get_mut().flush().await?;
     Ok(())
   }
 
   pub async fn shutdown(&mut self) -> Result<()> {
-    poll_fn(|ctx| Pin::new(&mut self.transport).poll_close(ctx)).await?;
+    self.write_queued().await?;
     self.transport.get_mut().shutdown().await?;
     Ok(())
   }
 
-  pub async fn downgrade_to_binary_stream(self) -> Result<(Bytes, TcpStream)> {
-    let parts = self.transport.into_parts();
-    let mut stream = parts.io;
-    if !parts.write_buf.is_empty() {
-      stream.write_all(parts.write_buf.as_ref()).await?;
-    }
-    Ok((parts.read_buf.freeze(), stream))
+  pub async fn downgrade_to_binary_stream(mut self) -> Result<(Bytes, TcpStream)> {
+    self.write_queued().await?;
+    let read_buf = self.transport.read_buffer_mut().split().freeze();
+    Ok((read_buf, self.transport.into_inner()))
+  }
+
+  async fn write_queued(&mut self) -> Result<()> {
+    let FloStream {
+      transport,
+      write_queue,
+      ..
+    } = self;
+    poll_fn(|cx| write_queue.poll_write(cx, Pin::new(transport.get_mut()))).await?;
+    Ok(())
   }
 }
 
@@ -1,4 +1,5 @@
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
 pub struct StopWatch {
@@ -16,3 +17,118 @@ impl StopWatch {
     self.start.elapsed().as_millis() as u32
   }
 }
+
+/// Microseconds since the unix epoch
+pub fn unix_time_micros() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_micros() as i64)
+    .unwrap_or_default()
+}
+
+/// One request-response exchange, all times in microseconds since the unix epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeSyncSample {
+  pub client_send_time: i64,
+  pub node_recv_time: i64,
+  pub node_send_time: i64,
+  pub client_recv_time: i64,
+}
+
+impl TimeSyncSample {
+  /// Node clock minus client clock
+  pub fn offset(&self) -> i64 {
+    ((self.node_recv_time - self.client_send_time) + (self.node_send_time - self.client_recv_time))
+      / 2
+  }
+
+  /// Round trip without the time spent on the node
+  pub fn rtt(&self) -> i64 {
+    ((self.client_recv_time - self.client_send_time) - (self.node_send_time - self.node_recv_time))
+      .max(0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeSyncEstimate {
+  /// Node clock minus client clock in microseconds
+  pub offset: i64,
+  /// The node clock is within `offset ± dispersion` microseconds
+  pub dispersion: i64,
+  pub rtt: i64,
+}
+
+/// Estimates the clock offset to the node from the last samples.
+/// The sample with the shortest round trip is the least affected by queuing,
+/// the error bound is half of its round trip plus the spread of the other samples.
+#[derive(Debug)]
+pub struct TimeSyncEstimator {
+  samples: VecDeque<TimeSyncSample>,
+}
+
+impl TimeSyncEstimator {
+  const MAX_SAMPLES: usize = 8;
+
+  pub fn new() -> Self {
+    Self {
+      samples: VecDeque::with_capacity(Self::MAX_SAMPLES),
+    }
+  }
+
+  pub fn push(&mut self, sample: TimeSyncSample) -> Option<TimeSyncEstimate> {
+    if self.samples.len() == Self::MAX_SAMPLES {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(sample);
+    self.estimate()
+  }
+
+  pub fn estimate(&self) -> Option<TimeSyncEstimate> {
+    let best = self.samples.iter().min_by_key(|sample| sample.rtt())?;
+    let offset = best.offset();
+    let spread = self
+      .samples
+      .iter()
+      .map(|sample| (sample.offset() - offset).abs())
+      .max()
+      .unwrap_or_default();
+    Some(TimeSyncEstimate {
+      offset,
+      dispersion: best.rtt() / 2 + spread,
+      rtt: best.rtt(),
+    })
+  }
+}
+
+#[test]
+fn test_time_sync_estimate() {
+  let sample =
+    |client_send_time, node_recv_time, node_send_time, client_recv_time| TimeSyncSample {
+      client_send_time,
+      node_recv_time,
+      node_send_time,
+      client_recv_time,
+    };
+  let mut estimator = TimeSyncEstimator::new();
+  assert_eq!(estimator.estimate(), None);
+
+  // node clock 1000 ahead, 100 each way
+  let estimate = estimator.push(sample(0, 1100, 1110, 210)).unwrap();
+  assert_eq!(estimate.offset, 1000);
+  assert_eq!(estimate.rtt, 200);
+  assert_eq!(estimate.dispersion, 100);
+
+  // queued on the way back
+  let estimate = estimator.push(sample(1000, 2100, 2110, 1610)).unwrap();
+  assert_eq!(estimate.offset, 1000);
+  assert_eq!(estimate.dispersion, 100 + 200);
+
+  for i in 0..TimeSyncEstimator::MAX_SAMPLES as i64 {
+    let t = 10_000 * (i + 1);
+    estimator.push(sample(t, t + 1050, t + 1050, t + 100));
+  }
+  let estimate = estimator.estimate().unwrap();
+  assert_eq!(estimate.offset, 1000);
+  assert_eq!(estimate.rtt, 100);
+  assert_eq!(estimate.dispersion, 50);
+}
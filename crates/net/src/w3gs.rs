@@ -1,9 +1,11 @@
 use thiserror::Error;
 
+use crate::constants::MAX_PAYLOAD_LEN;
 use crate::error::*;
 use crate::packet::{Frame, FramePayload, PacketTypeId};
 use bitflags::bitflags;
-use flo_util::binary::{BinBufExt, BinDecode, Buf};
+use bytes::Bytes;
+use flo_util::binary::{BinBufExt, BinDecode, BinEncode, Buf, BufMut};
 use flo_util::{BinDecode, BinEncode};
 use flo_w3gs::packet::Packet;
 pub use flo_w3gs::packet::{Header as W3GSHeader, Packet as W3GSPacket};
@@ -20,6 +22,7 @@ pub enum ParseW3GSPacketError {
 
 pub trait W3GSFrameExt {
   fn try_into_w3gs(self) -> Result<(W3GSMetadata, W3GSPacket)>;
+  fn try_into_w3gs_batch(self) -> Result<Vec<(W3GSMetadata, W3GSPacket)>>;
   fn from_w3gs(metadata: W3GSMetadata, packet: W3GSPacket) -> Self;
 }
 
@@ -29,6 +32,10 @@ impl W3GSFrameExt for Frame {
     frame_to_w3gs(self)
   }
 
+  fn try_into_w3gs_batch(self) -> Result<Vec<(W3GSMetadata, W3GSPacket)>> {
+    W3GSBatch::decode(self)
+  }
+
   #[inline]
   fn from_w3gs(metadata: W3GSMetadata, packet: Packet) -> Self {
     w3gs_to_frame(metadata, packet)
@@ -63,6 +70,101 @@ fn w3gs_to_frame(metadata: W3GSMetadata, packet: W3GSPacket) -> Frame {
   }
 }
 
+/// W3GS packets sent in one frame to reduce framing overhead,
+/// encoded as `[metadata] [payload_len: u16] [payload]` items
+#[derive(Debug, Default)]
+pub struct W3GSBatch {
+  items: Vec<(W3GSMetadata, W3GSPacket)>,
+  len: usize,
+}
+
+impl W3GSBatch {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn len(&self) -> usize {
+    self.items.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+
+  /// Returns `false` if the packet doesn't fit in the frame
+  pub fn can_push(&self, metadata: &W3GSMetadata, packet: &W3GSPacket) -> bool {
+    self.len + Self::item_len(metadata, packet) <= MAX_PAYLOAD_LEN
+  }
+
+  pub fn push(&mut self, metadata: W3GSMetadata, packet: W3GSPacket) {
+    self.len += Self::item_len(&metadata, &packet);
+    self.items.push((metadata, packet));
+  }
+
+  /// A single packet is sent as a plain W3GS frame
+  pub fn into_frame(mut self) -> Option<Frame> {
+    if self.items.len() < 2 {
+      return self
+        .items
+        .pop()
+        .map(|(metadata, packet)| w3gs_to_frame(metadata, packet));
+    }
+    let payload = encode_w3gs_items(&self.items);
+    Some(Frame::new_bytes(PacketTypeId::W3GSBatch, payload))
+  }
+
+  fn decode(frame: Frame) -> Result<Vec<(W3GSMetadata, W3GSPacket)>> {
+    match frame.payload {
+      FramePayload::Bytes(bytes) if frame.type_id == PacketTypeId::W3GSBatch => {
+        decode_w3gs_items(bytes)
+      }
+      _ => Err(Error::ReadW3GSFrame(ParseW3GSPacketError::NotW3GS)),
+    }
+  }
+
+  fn item_len(metadata: &W3GSMetadata, packet: &W3GSPacket) -> usize {
+    metadata.len() + 2 + packet.payload.len()
+  }
+}
+
+/// Encodes packets in the `W3GSBatch` item format, without the frame size limit
+pub fn encode_w3gs_items<'a, I>(items: I) -> Bytes
+where
+  I: IntoIterator<Item = &'a (W3GSMetadata, W3GSPacket)>,
+  I::IntoIter: Clone,
+{
+  let items = items.into_iter();
+  let len = items
+    .clone()
+    .map(|(metadata, packet)| W3GSBatch::item_len(metadata, packet))
+    .sum();
+  flo_util::buf::encode_bytes(len, |buf| {
+    for (metadata, packet) in items {
+      metadata.encode(buf);
+      buf.put_u16_le(packet.payload.len() as u16);
+      buf.put_slice(packet.payload.as_ref());
+    }
+  })
+}
+
+pub fn decode_w3gs_items(mut buf: Bytes) -> Result<Vec<(W3GSMetadata, W3GSPacket)>> {
+  let mut items = vec![];
+  while buf.has_remaining() {
+    let metadata = W3GSMetadata::decode(&mut buf)?;
+    if buf.remaining() < 2 {
+      return Err(Error::ReadW3GSFrame(ParseW3GSPacketError::EOB));
+    }
+    let len = buf.get_u16_le() as usize;
+    if buf.remaining() < len {
+      return Err(Error::ReadW3GSFrame(ParseW3GSPacketError::EOB));
+    }
+    let payload = buf.split_to(len);
+    let header = W3GSHeader::new(metadata.type_id(), (len + 4) as u16);
+    items.push((metadata, W3GSPacket { header, payload }));
+  }
+  Ok(items)
+}
+
 bitflags! {
   struct W3GSMetadataFlags: u8 {
     const ACK = 0b00000001;
@@ -132,6 +234,22 @@ impl W3GSAckQueue {
     }
   }
 
+  /// Restores a queue replicated from another node
+  pub fn restore(
+    last_send_sid: u32,
+    last_ack_sent: Option<u32>,
+    pending: VecDeque<(W3GSMetadata, W3GSPacket)>,
+    last_ack_received: Option<u32>,
+  ) -> Self {
+    Self {
+      tx_next_sid: last_send_sid,
+      tx_ack_sid: last_ack_sent,
+      tx_pending_ack_q: pending,
+      rx_ack_sid: None,
+      last_rx_ack_sid: last_ack_received,
+    }
+  }
+
   pub fn gen_next_send_sid(&mut self) -> u32 {
     self.tx_next_sid = self.tx_next_sid.wrapping_add(1);
     self.tx_next_sid
@@ -193,6 +311,14 @@ impl W3GSAckQueue {
     self.last_rx_ack_sid.clone()
   }
 
+  pub fn last_send_sid(&self) -> u32 {
+    self.tx_next_sid
+  }
+
+  pub fn last_ack_sent(&self) -> Option<u32> {
+    self.tx_ack_sid.clone()
+  }
+
   pub fn pending_ack_len(&self) -> usize {
     self.tx_pending_ack_q.len()
   }
@@ -277,3 +403,48 @@ fn test_frame_to_w3gs() {
     ] as &[_]
   );
 }
+
+#[test]
+fn test_w3gs_batch() {
+  use flo_util::binary::*;
+  use tokio_util::codec::Decoder;
+  let packet = |data: &'static [u8]| W3GSPacket {
+    header: W3GSHeader::new(W3GSPacketTypeId::OutgoingAction, (data.len() + 4) as u16),
+    payload: Bytes::from_static(data),
+  };
+
+  let mut batch = W3GSBatch::new();
+  batch.push(
+    W3GSMetadata::new(W3GSPacketTypeId::OutgoingAction, 1, Some(10)),
+    packet(&[1, 2, 3]),
+  );
+  batch.push(
+    W3GSMetadata::new(W3GSPacketTypeId::OutgoingAction, 2, None),
+    packet(&[4]),
+  );
+  let frame = batch.into_frame().unwrap();
+  assert_eq!(frame.type_id, PacketTypeId::W3GSBatch);
+
+  let mut buf = BytesMut::new();
+  frame.encode(&mut buf);
+  let frame = crate::codec::FloFrameCodec::new()
+    .decode(&mut buf)
+    .unwrap()
+    .unwrap();
+  let items = frame.try_into_w3gs_batch().unwrap();
+  assert_eq!(items.len(), 2);
+  assert_eq!(items[0].0.sid(), 1);
+  assert_eq!(items[0].0.ack_sid(), Some(10));
+  assert_eq!(items[0].1.payload.as_ref(), &[1, 2, 3]);
+  assert_eq!(items[0].1.header.len, 7);
+  assert_eq!(items[1].0.sid(), 2);
+  assert_eq!(items[1].0.ack_sid(), None);
+  assert_eq!(items[1].1.payload.as_ref(), &[4]);
+
+  let mut batch = W3GSBatch::new();
+  batch.push(
+    W3GSMetadata::new(W3GSPacketTypeId::OutgoingAction, 3, None),
+    packet(&[5]),
+  );
+  assert_eq!(batch.into_frame().unwrap().type_id, PacketTypeId::W3GS);
+}
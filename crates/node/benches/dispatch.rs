@@ -0,0 +1,87 @@
+//! Tick dispatch across concurrent games: every game encodes its tick with
+//! `encode_action_tick` and frames the packets for each player, like
+//! `Shared::dispatch_action_tick` followed by the player stream writes.
+//! A round is one tick of every game, it has to finish well within the 100ms step.
+//!
+//! cargo bench -p flo-node --bench dispatch
+//!
+//! End-to-end latency through real sockets is measured by the `loadgen` example of `flo-testlab`.
+
+use bytes::{Bytes, BytesMut};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use flo_net::packet::Frame;
+use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket};
+use flo_w3gs::action::PlayerAction;
+
+const PLAYERS: usize = 8;
+
+struct Recipient {
+  ack_q: W3GSAckQueue,
+  write_buf: BytesMut,
+}
+
+impl Recipient {
+  fn send(&mut self, packet: W3GSPacket) {
+    let sid = self.ack_q.gen_next_send_sid();
+    let meta = W3GSMetadata::new(packet.type_id(), sid, self.ack_q.take_ack_received());
+    self.ack_q.push_send(meta.clone(), packet.clone());
+    Frame::from_w3gs(meta, packet).encode(&mut self.write_buf);
+    self.write_buf.clear();
+    self.ack_q.ack_sent(sid);
+  }
+}
+
+struct Game {
+  players: Vec<Recipient>,
+}
+
+impl Game {
+  fn new() -> Self {
+    Self {
+      players: (0..PLAYERS)
+        .map(|_| Recipient {
+          ack_q: W3GSAckQueue::new(),
+          write_buf: BytesMut::with_capacity(1500),
+        })
+        .collect(),
+    }
+  }
+
+  fn dispatch_tick(&mut self, actions: Vec<PlayerAction>) {
+    for packet in flo_node::encode_action_tick(100, actions).unwrap() {
+      for player in &mut self.players {
+        player.send(packet.clone());
+      }
+    }
+  }
+}
+
+fn actions() -> Vec<PlayerAction> {
+  (1..=PLAYERS as u8)
+    .map(|player_id| PlayerAction {
+      player_id,
+      data: Bytes::from_static(&[0x10, 0x42, 0x00, 0x0d, 0x00, 0x03, 0x00]),
+    })
+    .collect()
+}
+
+fn dispatch_round(c: &mut Criterion) {
+  let mut group = c.benchmark_group("dispatch_round");
+  group.sample_size(20);
+  for games in [1_000, 5_000] {
+    let mut state: Vec<_> = (0..games).map(|_| Game::new()).collect();
+    group.throughput(Throughput::Elements(games as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(games), &games, |b, _| {
+      b.iter(|| {
+        for game in &mut state {
+          game.dispatch_tick(actions());
+        }
+      })
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, dispatch_round);
+criterion_main!(benches);
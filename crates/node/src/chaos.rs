@@ -0,0 +1,92 @@
+//! Fault injection for end-to-end tests, enabled with the `chaos` feature.
+//!
+//! Once a [`ChaosConfig`] is installed the node randomly closes player streams,
+//! holds back ticks and sends W3GS frames twice, so reconnection, lag
+//! handling and duplicate detection run in every test game.
+//! Decisions come from a RNG seeded by the config, node restarts are done
+//! by the test harness.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+static STATE: Lazy<Mutex<Option<State>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Default)]
+pub struct ChaosConfig {
+  pub seed: u64,
+  /// Chance that a player stream is closed after receiving a frame
+  pub disconnect_rate: f64,
+  /// Chance that a tick is held back for `tick_delay`
+  pub tick_delay_rate: f64,
+  pub tick_delay: Duration,
+  /// Chance that a W3GS frame is sent to the player twice
+  pub duplicate_frame_rate: f64,
+}
+
+struct State {
+  config: ChaosConfig,
+  rng: StdRng,
+}
+
+/// Installs `config` for every game hosted by this process
+pub fn set(config: ChaosConfig) {
+  let rng = StdRng::seed_from_u64(config.seed);
+  STATE.lock().replace(State { config, rng });
+}
+
+pub fn clear() {
+  STATE.lock().take();
+}
+
+pub(crate) fn disconnect() -> bool {
+  roll(|config| config.disconnect_rate)
+}
+
+pub(crate) fn tick_delay() -> Option<Duration> {
+  let delay = STATE.lock().as_ref()?.config.tick_delay;
+  if roll(|config| config.tick_delay_rate) {
+    Some(delay)
+  } else {
+    None
+  }
+}
+
+pub(crate) fn duplicate_frame() -> bool {
+  roll(|config| config.duplicate_frame_rate)
+}
+
+fn roll<F>(rate: F) -> bool
+where
+  F: FnOnce(&ChaosConfig) -> f64,
+{
+  let mut guard = STATE.lock();
+  let state = match guard.as_mut() {
+    Some(state) => state,
+    None => return false,
+  };
+  let rate = rate(&state.config);
+  rate > 0. && state.rng.gen_bool(rate.min(1.))
+}
+
+#[test]
+fn test_chaos_seeded() {
+  fn rolls(seed: u64) -> Vec<bool> {
+    set(ChaosConfig {
+      seed,
+      disconnect_rate: 0.5,
+      ..Default::default()
+    });
+    let v = (0..64).map(|_| disconnect()).collect();
+    clear();
+    v
+  }
+
+  assert_eq!(rolls(1), rolls(1));
+  assert!(rolls(1).contains(&true));
+  assert!(!disconnect());
+  assert!(tick_delay().is_none());
+  assert!(!duplicate_frame());
+}
@@ -1,4 +1,5 @@
 use futures::stream::StreamExt;
+use tracing_futures::Instrument;
 
 use flo_constants::NODE_CLIENT_PORT;
 use flo_net::listener::FloListener;
@@ -6,7 +7,7 @@ use flo_net::proto::flo_node::*;
 use flo_net::stream::FloStream;
 
 use crate::error::*;
-use crate::state::{GlobalState, GlobalStateRef, PlayerToken};
+use crate::state::{GameKey, GlobalState, GlobalStateRef, PlayerToken};
 use flo_w3gs::constants::LeaveReason;
 
 pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
@@ -34,19 +35,38 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
           }
         };
 
-        tracing::debug!(
+        // continues the trace of the game from the player token
+        let span = tracing::info_span!(
+          "player_connect",
           game_id = claim.game_id,
-          player_id = claim.player_id,
-          "connected"
+          player_id = claim.player_id
         );
+        flo_log::trace::set_parent(&span, &claim.traceparent);
+        span.in_scope(|| tracing::debug!("connected"));
 
-        let session = match state.get_game(claim.game_id) {
+        let session = match state.get_game(claim.game_key) {
+          Some(session) => Some(session),
+          None => state.promote_replica(claim.game_key).await,
+        };
+        let session = match session {
           Some(session) => session,
           None => {
+            // a standby node only hosts the game after the primary node went down
+            let (reason, message) = if state.replicas().contains(claim.game_key) {
+              (
+                ClientConnectRejectReason::Standby,
+                "Game session is hosted by the primary node.",
+              )
+            } else {
+              (
+                ClientConnectRejectReason::Unknown,
+                "Game session was not found.",
+              )
+            };
             stream
               .send(PacketClientConnectReject {
-                reason: ClientConnectRejectReason::Unknown.into(),
-                message: format!("Game session was not found."),
+                reason: reason.into(),
+                message: message.to_string(),
               })
               .await
               .ok();
@@ -57,6 +77,7 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
         if claim.shutdown_retry {
           if let Err(err) = session
             .retry_shutdown(claim.player_id, claim.leave_reason, &mut stream)
+            .instrument(span)
             .await
           {
             tracing::error!(
@@ -70,6 +91,7 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
         } else {
           if let Err((stream, err)) = session
             .register_player_stream(claim.player_id, stream)
+            .instrument(span)
             .await
           {
             tracing::error!(
@@ -121,17 +143,21 @@ async fn handshake(state: &GlobalState, stream: &mut FloStream) -> Result<Claim>
     .ok_or_else(|| Error::InvalidToken)?;
 
   Ok(Claim {
+    game_key: pending.game_key(),
     game_id: pending.game_id,
     player_id: pending.player_id,
     shutdown_retry: connect.retry_shutdown,
     leave_reason: connect.leave_reason.map(LeaveReason::from),
+    traceparent: connect.traceparent,
   })
 }
 
 #[derive(Debug)]
 pub struct Claim {
+  game_key: GameKey,
   game_id: i32,
   player_id: i32,
   shutdown_retry: bool,
   leave_reason: Option<LeaveReason>,
+  traceparent: String,
 }
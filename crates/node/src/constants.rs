@@ -4,18 +4,20 @@ use std::time::Duration;
 
 pub const PEER_CHANNEL_SIZE: usize = 250;
 pub const CONTROLLER_SENDER_BUF_SIZE: usize = 10;
+pub const STANDBY_SENDER_BUF_SIZE: usize = 4096;
+pub const STANDBY_MAX_PACKET_SIZE: usize = 12 * 1024;
+pub const STANDBY_PING_INTERVAL: Duration = Duration::from_secs(1);
+/// The primary node is considered down if nothing is received for this long
+pub const STANDBY_LINK_TIMEOUT: Duration = Duration::from_secs(5);
+/// A game is not taken over if the controller doesn't confirm the primary node is down in time
+pub const STANDBY_PROMOTE_TIMEOUT: Duration = Duration::from_secs(5);
 pub const GAME_DISPATCH_BUF_SIZE: usize = 256;
 pub const GAME_PLAYER_LAGGING_THRESHOLD_MS: u32 = 3000;
 pub const GAME_PLAYER_MAX_ACK_QUEUE: usize = 300;
-pub static GAME_DEFAULT_STEP_MS: Lazy<u16> = Lazy::new(|| {
-  std::env::var("FLO_GAME_STEP_MS")
-    .ok()
-    .and_then(|v| v.parse().ok())
-    .unwrap_or(30)
-});
 pub const GAME_PING_INTERVAL: Duration = Duration::from_secs(1);
 pub const GAME_PING_TIMEOUT: Duration = Duration::from_secs(5);
 pub const GAME_CLOCK_MAX_PAUSE: Duration = Duration::from_secs(60 - 3);
+pub const GAME_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
 #[cfg(not(debug_assertions))]
 pub const GAME_DELAY_RANGE: [Duration; 2] = [Duration::from_millis(25), Duration::from_millis(100)];
@@ -26,6 +28,8 @@ pub const GAME_DELAY_RANGE: [Duration; 2] =
 pub const OBS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 pub const OBS_CHANNEL_SIZE: usize = 10000;
 pub const OBS_MAX_CHUNK_SIZE: usize = 512 * 1024;
+pub const OBS_STREAM_CHANNEL_SIZE: usize = 4096;
+pub const OBS_STREAM_QUEUE_MAX_SIZE: usize = 16 * 1024 * 1024;
 pub static OBS_SOURCE: Lazy<ObserverRecordSource> = Lazy::new(|| {
   std::env::var("OBSERVER_SOURCE")
     .ok()
@@ -35,3 +39,11 @@ pub static OBS_SOURCE: Lazy<ObserverRecordSource> = Lazy::new(|| {
 
 pub const RTT_STATS_REPORT_DELAY: Duration = std::time::Duration::from_secs(5);
 pub const RTT_STATS_REPORT_INTERVAL: Duration = std::time::Duration::from_secs(15);
+
+pub const GAME_LOG_MAX_SIZE: usize = 32 * 1024 * 1024;
+pub const GAME_LOG_CHUNK_SIZE: usize = 15 * 1024;
+pub const GAME_CHAT_HISTORY_SIZE: usize = 256;
+
+pub const DESYNC_REPORT_CHECKSUM_HISTORY: usize = 64;
+pub const DESYNC_REPORT_TIME_SLOT_HISTORY: usize = 64;
+pub const DESYNC_REPORT_MAX: usize = 3;
@@ -6,24 +6,29 @@ use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tracing_futures::Instrument;
 
 use flo_constants::NODE_CONTROLLER_PORT;
+use flo_net::auth::{FrameAuth, Peer};
 use flo_net::listener::FloListener;
-use flo_net::packet::Frame;
+use flo_net::packet::{FloPacket, Frame};
 use flo_net::proto::flo_node::*;
 use flo_net::stream::FloStream;
 use flo_net::try_flo_packet;
 use flo_task::{SpawnScope, SpawnScopeHandle};
 
+use crate::env::Env;
 use crate::error::*;
-use crate::state::GlobalStateRef;
+use crate::state::{ControllerId, GameKey, GlobalStateRef};
 use flo_net::ping::PingStream;
 
+/// Accepts connections of every controller in `Env::controllers`,
+/// each controller is identified by its secret key
 #[derive(Debug)]
 pub struct ControllerServer {
-  state: Arc<State>,
+  controllers: Vec<Arc<State>>,
 }
 
 #[derive(Debug)]
 struct State {
+  id: ControllerId,
   g_state: GlobalStateRef,
   current: RwLock<Option<ControllerConn>>,
   frame_tx: Sender<Frame>,
@@ -32,20 +37,29 @@ struct State {
 
 impl ControllerServer {
   pub fn new(g_state: GlobalStateRef) -> ControllerServer {
-    let (frame_tx, frame_rx) = channel(crate::constants::CONTROLLER_SENDER_BUF_SIZE);
-    let state = Arc::new(State {
-      g_state,
-      current: RwLock::new(None),
-      frame_tx,
-      frame_rx: Mutex::new(frame_rx),
-    });
-    Self { state }
+    let controllers = (0..Env::get().controllers.len())
+      .map(|i| {
+        let (frame_tx, frame_rx) = channel(crate::constants::CONTROLLER_SENDER_BUF_SIZE);
+        Arc::new(State {
+          id: ControllerId(i),
+          g_state: g_state.clone(),
+          current: RwLock::new(None),
+          frame_tx,
+          frame_rx: Mutex::new(frame_rx),
+        })
+      })
+      .collect();
+    Self { controllers }
   }
 
-  pub fn handle(&self) -> ControllerServerHandle {
-    ControllerServerHandle {
-      state: self.state.clone(),
-    }
+  /// One handle for each controller, in the order of `Env::controllers`
+  pub fn handles(&self) -> Vec<ControllerServerHandle> {
+    self
+      .controllers
+      .iter()
+      .cloned()
+      .map(ControllerServerHandle::new)
+      .collect()
   }
 
   pub async fn serve(&mut self) -> Result<()> {
@@ -53,8 +67,9 @@ impl ControllerServer {
 
     while let Some(incoming) = listener.incoming().next().await {
       if let Ok(stream) = incoming {
-        if let Ok(conn) = self.handshake(stream).await {
-          self.state.current.write().replace(conn);
+        if let Ok((state, conn)) = self.handshake(stream).await {
+          tracing::info!(controller = state.id.name(), "controller connected");
+          state.current.write().replace(conn);
         }
       }
     }
@@ -62,28 +77,54 @@ impl ControllerServer {
     Ok(())
   }
 
-  async fn handshake(&self, mut stream: FloStream) -> Result<ControllerConn> {
+  async fn handshake(&self, mut stream: FloStream) -> Result<(Arc<State>, ControllerConn)> {
     use std::time::Duration;
     const RECV_TIMEOUT: Duration = Duration::from_secs(3);
 
     let connect: PacketControllerConnect = stream.recv_timeout(RECV_TIMEOUT).await?;
 
-    if connect.secret != crate::env::Env::get().secret_key {
-      stream
-        .send(PacketControllerConnectReject {
-          reason: ControllerConnectRejectReason::InvalidSecretKey.into(),
-        })
-        .await?;
-      return Err(Error::InvalidSecret);
-    }
+    let (state, secret) = match Env::get()
+      .controllers
+      .iter()
+      .position(|c| flo_net::auth::secret_eq(&c.secret_key, &connect.secret))
+    {
+      Some(i) => (
+        self.controllers[i].clone(),
+        &Env::get().controllers[i].secret_key,
+      ),
+      None => {
+        stream
+          .send(PacketControllerConnectReject {
+            reason: ControllerConnectRejectReason::InvalidSecretKey.into(),
+          })
+          .await?;
+        return Err(Error::InvalidSecret);
+      }
+    };
+
+    let nonce = flo_net::auth::gen_nonce();
+    let auth = match FrameAuth::new(Peer::Node, secret, &connect.nonce, &nonce) {
+      Ok(auth) => auth,
+      // controllers without frame authentication
+      Err(err) => {
+        stream
+          .send(PacketControllerConnectReject {
+            reason: ControllerConnectRejectReason::ControllerVersionTooOld.into(),
+          })
+          .await?;
+        return Err(err.into());
+      }
+    };
 
     stream
       .send(PacketControllerConnectAccept {
         version: Some(crate::version::FLO_NODE_VERSION.into()),
+        nonce,
       })
       .await?;
 
-    Ok(ControllerConn::new(self.state.clone(), stream))
+    let conn = ControllerConn::new(state.clone(), stream, auth);
+    Ok((state, conn))
   }
 }
 
@@ -97,6 +138,10 @@ impl ControllerServerHandle {
     Self { state }
   }
 
+  pub fn controller_id(&self) -> ControllerId {
+    self.state.id
+  }
+
   /// Sends a frame to the controller
   /// If the controller is disconnected and the send buf is full,
   /// block until the connection is restored.
@@ -117,13 +162,13 @@ struct ControllerConn {
 }
 
 impl ControllerConn {
-  fn new(state: Arc<State>, stream: FloStream) -> Self {
+  fn new(state: Arc<State>, stream: FloStream, auth: FrameAuth) -> Self {
     let scope = SpawnScope::new();
 
     tokio::spawn({
       let scope = scope.handle();
       async move {
-        if let Err(e) = handle_stream(state, stream, scope).await {
+        if let Err(e) = handle_stream(state, stream, auth, scope).await {
           tracing::debug!("handle_stream: {}", e);
         }
         tracing::debug!("exiting")
@@ -138,6 +183,7 @@ impl ControllerConn {
 async fn handle_stream(
   state: Arc<State>,
   mut stream: FloStream,
+  mut auth: FrameAuth,
   mut scope: SpawnScopeHandle,
 ) -> Result<()> {
   let mut rx = state.frame_rx.lock().await;
@@ -147,7 +193,11 @@ async fn handle_stream(
         break;
       }
       frame = stream.recv_frame() => {
-        let frame = frame?;
+        let frame = auth.verify(frame?)?;
+        if frame.type_id == PacketControllerRotateKey::TYPE_ID {
+          auth.apply_rotate(&frame.decode()?)?;
+          continue;
+        }
         let state = state.clone();
         tokio::spawn(async move {
           if let Err(e) = handle_frame(&state, frame).await {
@@ -157,7 +207,7 @@ async fn handle_stream(
       }
       next = rx.recv() => {
         if let Some(frame) = next {
-          stream.send_frame_timeout(frame).await?;
+          stream.send_frame_timeout(auth.sign(frame)?).await?;
         } else {
           break;
         }
@@ -182,9 +232,27 @@ async fn handle_frame(state: &Arc<State>, mut frame: Frame) -> Result<()> {
         flo_log::result_ok!("create game", tx.send(frame).await);
       }
       pkt: PacketControllerUpdateSlotStatus => {
-        let frame = state.g_state.handle_controller_update_slot_client_status(pkt).await?;
+        let frame = state.g_state.handle_controller_update_slot_client_status(state.id, pkt).await?;
         flo_log::result_ok!("update slot status", tx.send(frame).await);
       }
+      pkt: PacketControllerAbortGame => {
+        let frame = state.g_state.handle_controller_abort_game(state.id, pkt).await?;
+        flo_log::result_ok!("abort game", tx.send(frame).await);
+      }
+      pkt: PacketControllerStandbyPromoteAccept => {
+        let key = GameKey {
+          controller_id: state.id,
+          game_id: pkt.game_id,
+        };
+        state.g_state.replicas().resolve_promote(key, true);
+      }
+      pkt: PacketControllerStandbyPromoteReject => {
+        let key = GameKey {
+          controller_id: state.id,
+          game_id: pkt.game_id,
+        };
+        state.g_state.replicas().resolve_promote(key, false);
+      }
     }
   }
   Ok(())
@@ -1,16 +1,127 @@
 use once_cell::sync::Lazy;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 
 #[derive(Debug)]
 pub struct Env {
+  /// The first controller is the primary controller,
+  /// observers can only watch its games
+  pub controllers: Vec<ControllerConfig>,
+  /// Base address of the standby node that replicates the games of this node
+  pub standby_addr: Option<SocketAddr>,
+  /// Shared by a primary node and its standby node
+  pub standby_secret: Option<String>,
+}
+
+/// A controller allowed to host games on this node
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerConfig {
+  pub name: String,
   pub secret_key: String,
+  /// Max concurrent games of this controller, unlimited if not set
+  pub max_games: Option<usize>,
 }
 
 impl Env {
   pub fn get() -> &'static Env {
     static INSTANCE: Lazy<Env> = Lazy::new(|| Env {
-      secret_key: env::var("FLO_NODE_SECRET").unwrap_or_default(),
+      controllers: match env::var("FLO_NODE_CONTROLLERS") {
+        Ok(value) => parse_controllers(&value),
+        Err(_) => vec![ControllerConfig {
+          name: "default".to_string(),
+          secret_key: env::var("FLO_NODE_SECRET").unwrap_or_default(),
+          max_games: None,
+        }],
+      },
+      standby_addr: env::var("FLO_NODE_STANDBY_ADDR").ok().and_then(
+        |value| match parse_standby_addr(&value) {
+          Some(addr) => Some(addr),
+          None => {
+            tracing::error!("invalid standby address: {}", value);
+            None
+          }
+        },
+      ),
+      standby_secret: env::var("FLO_NODE_STANDBY_SECRET")
+        .ok()
+        .filter(|v| !v.is_empty()),
     });
     &INSTANCE
   }
 }
+
+/// `name:secret[:max_games]`, separated by commas
+fn parse_controllers(value: &str) -> Vec<ControllerConfig> {
+  value
+    .split(',')
+    .map(str::trim)
+    .filter(|item| !item.is_empty())
+    .filter_map(|item| {
+      let mut parts = item.splitn(3, ':');
+      let name = parts.next()?.trim();
+      let secret_key = parts.next()?.trim();
+      let max_games = match parts.next() {
+        Some(v) => match v.trim().parse() {
+          Ok(v) => Some(v),
+          Err(_) => {
+            tracing::error!("invalid max games of controller `{}`: {}", name, v);
+            return None;
+          }
+        },
+        None => None,
+      };
+      if name.is_empty() || secret_key.is_empty() {
+        tracing::error!("invalid controller config: {}", item);
+        return None;
+      }
+      Some(ControllerConfig {
+        name: name.to_string(),
+        secret_key: secret_key.to_string(),
+        max_games,
+      })
+    })
+    .collect()
+}
+
+/// `ip[:port]`, the port defaults to the base port of a node
+fn parse_standby_addr(value: &str) -> Option<SocketAddr> {
+  value.parse().ok().or_else(|| {
+    value
+      .parse::<IpAddr>()
+      .ok()
+      .map(|ip| SocketAddr::new(ip, flo_constants::NODE_PORT))
+  })
+}
+
+#[test]
+fn test_parse_controllers() {
+  let controllers = parse_controllers("ladder:s1:100, community:s2 ,bad,:s3,x:s4:y");
+  assert_eq!(
+    controllers,
+    vec![
+      ControllerConfig {
+        name: "ladder".to_string(),
+        secret_key: "s1".to_string(),
+        max_games: Some(100),
+      },
+      ControllerConfig {
+        name: "community".to_string(),
+        secret_key: "s2".to_string(),
+        max_games: None,
+      },
+    ]
+  );
+}
+
+#[test]
+fn test_parse_standby_addr() {
+  assert_eq!(
+    parse_standby_addr("10.0.0.2"),
+    Some(SocketAddr::from(([10, 0, 0, 2], flo_constants::NODE_PORT)))
+  );
+  assert_eq!(
+    parse_standby_addr("10.0.0.2:4552"),
+    Some(SocketAddr::from(([10, 0, 0, 2], 4552)))
+  );
+  assert_eq!(parse_standby_addr("standby.local"), None);
+}
@@ -29,10 +29,22 @@ pub enum Error {
   InvalidSecret,
   #[error("invalid token")]
   InvalidToken,
+  #[error("game not started")]
+  GameNotStarted,
+  #[error("game ended")]
+  GameEnded,
+  #[error("game log truncated")]
+  GameLogTruncated,
+  #[error("observer stream lagged: {0}")]
+  ObserverStreamLagged(u64),
   #[error("invalid client status transition: {0:?} => {1:?}")]
   InvalidClientStatusTransition(SlotClientStatus, SlotClientStatus),
   #[error("observer put record: {0}")]
   ObsPutRecord(#[from] rusoto_core::RusotoError<rusoto_kinesis::PutRecordError>),
+  #[error("invalid setting `{name}`: {value}")]
+  SettingInvalid { name: String, value: String },
+  #[error("read settings file: {0}")]
+  SettingsFile(std::io::Error),
   #[error("tokio io: {0}")]
   Tokio(#[from] tokio::io::Error),
   #[error("operation timeout")]
@@ -47,4 +59,26 @@ pub enum Error {
   Http(#[from] hyper::Error),
 }
 
+impl Error {
+  /// A player sent a malformed packet
+  pub fn is_decode(&self) -> bool {
+    use flo_w3gs::error::Error as W3GSError;
+    match self {
+      Error::W3GS(W3GSError::BinDecode(_) | W3GSError::ProtoBufDecode(_)) => true,
+      Error::Net(err) => err.is_decode(),
+      _ => false,
+    }
+  }
+}
+
+impl From<flo_util::settings::SettingsError> for Error {
+  fn from(err: flo_util::settings::SettingsError) -> Self {
+    use flo_util::settings::SettingsError;
+    match err {
+      SettingsError::Invalid { name, value } => Self::SettingInvalid { name, value },
+      SettingsError::File(err) => Self::SettingsFile(err),
+    }
+  }
+}
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
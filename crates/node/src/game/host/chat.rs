@@ -0,0 +1,128 @@
+use flo_net::proto::flo_node::{ChatRules, ObserverChatRule};
+use flo_w3gs::protocol::chat::MessageScope;
+
+/// Chat rules pushed by the controller, applied to in-game chat before it is relayed
+#[derive(Debug)]
+pub struct ChatFilter {
+  all_chat_disabled: bool,
+  observer_chat: ObserverChatRule,
+  banned_phrases: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChatFilterResult {
+  Allow,
+  /// Only observers receive the message
+  ObserversOnly,
+  /// Dropped, the reason is sent to the sender
+  Block(&'static str),
+}
+
+impl ChatFilter {
+  pub fn new(rules: Option<ChatRules>) -> Self {
+    match rules {
+      Some(rules) => Self {
+        all_chat_disabled: rules.all_chat_disabled,
+        observer_chat: rules.observer_chat(),
+        banned_phrases: rules
+          .banned_phrases
+          .into_iter()
+          .map(|v| v.trim().to_lowercase())
+          .filter(|v| !v.is_empty())
+          .collect(),
+      },
+      None => Self {
+        all_chat_disabled: false,
+        observer_chat: ObserverChatRule::Default,
+        banned_phrases: vec![],
+      },
+    }
+  }
+
+  /// Summary of the active rules for the game start messages
+  pub fn describe(&self) -> Option<String> {
+    let mut items = vec![];
+    if self.all_chat_disabled {
+      items.push("all chat is disabled".to_string());
+    }
+    match self.observer_chat {
+      ObserverChatRule::Default => {}
+      ObserverChatRule::ObserversOnly => {
+        items.push("observers can only chat with observers".to_string())
+      }
+      ObserverChatRule::Disabled => items.push("observer chat is disabled".to_string()),
+    }
+    if !self.banned_phrases.is_empty() {
+      items.push(format!("{} phrases are banned", self.banned_phrases.len()));
+    }
+    if items.is_empty() {
+      None
+    } else {
+      Some(format!("Chat rules: {}.", items.join(", ")))
+    }
+  }
+
+  pub fn check(&self, from_observer: bool, scope: MessageScope, message: &str) -> ChatFilterResult {
+    if !self.banned_phrases.is_empty() {
+      let message = message.to_lowercase();
+      if self
+        .banned_phrases
+        .iter()
+        .any(|phrase| message.contains(phrase.as_str()))
+      {
+        return ChatFilterResult::Block("Your message contains a banned phrase.");
+      }
+    }
+
+    if from_observer {
+      match self.observer_chat {
+        ObserverChatRule::Default => {}
+        ObserverChatRule::ObserversOnly => return ChatFilterResult::ObserversOnly,
+        ObserverChatRule::Disabled => {
+          return ChatFilterResult::Block("Observer chat is disabled in this game.")
+        }
+      }
+    }
+
+    if self.all_chat_disabled && scope == MessageScope::All {
+      return ChatFilterResult::Block("All chat is disabled in this game.");
+    }
+
+    ChatFilterResult::Allow
+  }
+}
+
+#[test]
+fn test_chat_filter() {
+  let mut rules = ChatRules {
+    all_chat_disabled: true,
+    banned_phrases: vec![" GG EZ ".to_string(), "".to_string()],
+    ..Default::default()
+  };
+  rules.set_observer_chat(ObserverChatRule::ObserversOnly);
+  let filter = ChatFilter::new(Some(rules));
+
+  assert_eq!(
+    filter.check(false, MessageScope::Allies, "gl hf"),
+    ChatFilterResult::Allow
+  );
+  assert!(matches!(
+    filter.check(false, MessageScope::All, "gl hf"),
+    ChatFilterResult::Block(_)
+  ));
+  assert!(matches!(
+    filter.check(false, MessageScope::Allies, "Gg Ez"),
+    ChatFilterResult::Block(_)
+  ));
+  assert_eq!(
+    filter.check(true, MessageScope::Observers, "nice"),
+    ChatFilterResult::ObserversOnly
+  );
+
+  let filter = ChatFilter::new(None);
+  assert!(filter.describe().is_none());
+  assert_eq!(
+    filter.check(true, MessageScope::All, "gg ez"),
+    ChatFilterResult::Allow
+  );
+}
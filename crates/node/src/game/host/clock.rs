@@ -1,8 +1,8 @@
 use futures::stream::Stream;
 use std::future::Future;
 use std::pin::Pin;
-use std::time::{Duration, Instant};
-use tokio::time::{sleep, Sleep};
+use std::time::Duration;
+use tokio::time::{sleep, Instant, Sleep};
 
 use flo_w3gs::protocol::action::PlayerAction;
 use futures::task::{Context, Poll};
@@ -12,6 +12,7 @@ use std::task::Waker;
 pub struct ActionTickStream {
   paused: bool,
   step: u16,
+  speed_percent: u16,
   step_duration: Duration,
   delay: Pin<Box<Sleep>>,
   actions: Vec<PlayerAction>,
@@ -22,6 +23,8 @@ pub struct ActionTickStream {
 impl ActionTickStream {
   pub const MIN_STEP: u16 = 15;
   pub const MAX_STEP: u16 = 250;
+  pub const MIN_SPEED_PERCENT: u16 = 50;
+  pub const MAX_SPEED_PERCENT: u16 = 200;
 
   pub fn new(step: u16) -> Self {
     let step = std::cmp::max(Self::MIN_STEP, step);
@@ -29,6 +32,7 @@ impl ActionTickStream {
     ActionTickStream {
       paused: false,
       step,
+      speed_percent: 100,
       step_duration,
       delay: Box::pin(sleep(step_duration)),
       actions: vec![],
@@ -37,13 +41,35 @@ impl ActionTickStream {
     }
   }
 
+  /// Ticks fire more often than their game time increment if the speed is over 100%
+  pub fn with_speed_percent(mut self, value: u16) -> Self {
+    self.speed_percent = std::cmp::min(
+      Self::MAX_SPEED_PERCENT,
+      std::cmp::max(Self::MIN_SPEED_PERCENT, value),
+    );
+    self.step_duration = self.real_duration(Duration::from_millis(self.step as u64));
+    self
+      .delay
+      .as_mut()
+      .reset(Instant::now() + self.step_duration);
+    self
+  }
+
   pub fn set_step(&mut self, value: u16) {
     self.step = std::cmp::min(Self::MAX_STEP, std::cmp::max(Self::MIN_STEP, value));
-    self.step_duration = Duration::from_millis(value as u64);
+    self.step_duration = self.real_duration(Duration::from_millis(value as u64));
     self
       .delay
       .as_mut()
-      .reset((Instant::now() + self.step_duration).into());
+      .reset(Instant::now() + self.step_duration);
+  }
+
+  pub fn speed_percent(&self) -> u16 {
+    self.speed_percent
+  }
+
+  fn real_duration(&self, game_time: Duration) -> Duration {
+    game_time * 100 / self.speed_percent as u32
   }
 
   pub fn step(&self) -> u16 {
@@ -60,7 +86,7 @@ impl ActionTickStream {
 
   pub fn pause(&mut self) {
     self.paused = true;
-    self.delay.as_mut().reset(Instant::now().into());
+    self.delay.as_mut().reset(Instant::now());
   }
 
   pub fn is_paused(&self) -> bool {
@@ -74,7 +100,7 @@ impl ActionTickStream {
     self
       .delay
       .as_mut()
-      .reset((Instant::now() + self.step_duration).into());
+      .reset(Instant::now() + self.step_duration);
     self.resume_waker.take().map(|w| w.wake());
   }
 }
@@ -84,6 +110,8 @@ pub struct Tick {
   pub time_increment_ms: u16,
   pub actions: Vec<PlayerAction>,
   pub actions_bytes_len: usize,
+  /// How late the tick fired
+  pub delay: Duration,
 }
 
 impl Stream for ActionTickStream {
@@ -102,18 +130,63 @@ impl Stream for ActionTickStream {
 
     let now = self.delay.deadline();
 
-    let delay = (tokio::time::Instant::now().saturating_duration_since(now)).as_millis() as u16;
+    let delay = Instant::now().saturating_duration_since(now);
 
     let next = now + self.step_duration;
     self.delay.as_mut().reset(next);
 
-    let actions = std::mem::replace(&mut self.actions, vec![]);
+    // sized by the previous tick, avoids growing the buffer while actions are added
+    let capacity = self.actions.len();
+    let actions = std::mem::replace(&mut self.actions, Vec::with_capacity(capacity));
     let actions_bytes_len = actions.iter().map(|a| a.byte_len()).sum();
+    // the game time of the delay
+    let delay_ms = delay.as_millis() as u64 * self.speed_percent as u64 / 100;
     let tick = Tick {
-      time_increment_ms: self.step + delay,
+      time_increment_ms: self.step + delay_ms as u16,
       actions,
       actions_bytes_len,
+      delay,
     };
     Poll::Ready(Some(tick))
   }
 }
+
+#[cfg(feature = "sim")]
+#[test]
+fn test_tick_stream_virtual_time() {
+  use futures::StreamExt;
+
+  flo_task::sim::run(1, async {
+    let started = Instant::now();
+    let mut stream = ActionTickStream::new(50);
+    for _ in 0..100 {
+      let tick = stream.next().await.unwrap();
+      assert_eq!(tick.time_increment_ms, 50);
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_secs(5) && elapsed < Duration::from_millis(5100));
+
+    stream.pause();
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    stream.resume();
+    let tick = stream.next().await.unwrap();
+    assert_eq!(tick.time_increment_ms, 50);
+  });
+}
+
+#[cfg(feature = "sim")]
+#[test]
+fn test_tick_stream_speed() {
+  use futures::StreamExt;
+
+  flo_task::sim::run(1, async {
+    let started = Instant::now();
+    let mut stream = ActionTickStream::new(50).with_speed_percent(125);
+    for _ in 0..100 {
+      let tick = stream.next().await.unwrap();
+      assert_eq!(tick.time_increment_ms, 50);
+    }
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_secs(4) && elapsed < Duration::from_millis(4100));
+  });
+}
@@ -3,8 +3,8 @@ use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
-use std::time::{Duration, Instant};
-use tokio::time::{sleep, Sleep};
+use std::time::Duration;
+use tokio::time::{sleep, Instant, Sleep};
 
 #[derive(Debug, Clone)]
 pub enum DelayedFrame {
@@ -47,7 +47,7 @@ impl DelayedFrameStream {
     }
 
     self.duration = set_value;
-    self.sleep.as_mut().reset(Instant::now().into());
+    self.sleep.as_mut().reset(Instant::now());
     self.waker.take().map(|w| w.wake());
   }
 
@@ -108,7 +108,7 @@ impl<'a, 'b> Future for ExpiredFuture<'a, 'b> {
           .saturating_duration_since(now)
           .saturating_sub(last_tick_cost);
       self.owner.last_deadline.replace(deadline);
-      self.owner.sleep.as_mut().reset(deadline.into());
+      self.owner.sleep.as_mut().reset(deadline);
       if let Poll::Ready(_) = self.owner.sleep.as_mut().poll(cx) {
         self.buf.push_back(frame);
         Poll::Ready(())
@@ -0,0 +1,152 @@
+use flo_net::proto::flo_node::{
+  DesyncChecksum, DesyncPlayerAction, DesyncPlayerChecksums, DesyncTimeSlot, GameDesyncReport,
+};
+use flo_w3gs::protocol::action::PlayerAction;
+use std::collections::{BTreeMap, VecDeque};
+
+use super::sync::PlayerDesync;
+use crate::constants::{
+  DESYNC_REPORT_CHECKSUM_HISTORY, DESYNC_REPORT_MAX, DESYNC_REPORT_TIME_SLOT_HISTORY,
+};
+
+/// Keeps the recent checksums and actions of a game
+/// to build a forensic report when a desync is detected.
+/// Reports are sent to the controller with the game result.
+#[derive(Debug)]
+pub struct DesyncReportCollector {
+  slot_player_id_lookup: BTreeMap<u8, i32>,
+  checksums: BTreeMap<i32, VecDeque<DesyncChecksum>>,
+  time_slots: VecDeque<DesyncTimeSlot>,
+  reports: Vec<GameDesyncReport>,
+}
+
+impl DesyncReportCollector {
+  /// `players` yields `(player_id, slot_player_id)` pairs.
+  pub fn new<I>(players: I) -> Self
+  where
+    I: IntoIterator<Item = (i32, u8)>,
+  {
+    let mut slot_player_id_lookup = BTreeMap::new();
+    let mut checksums = BTreeMap::new();
+    for (player_id, slot_player_id) in players {
+      slot_player_id_lookup.insert(slot_player_id, player_id);
+      checksums.insert(player_id, VecDeque::new());
+    }
+    Self {
+      slot_player_id_lookup,
+      checksums,
+      time_slots: VecDeque::new(),
+      reports: vec![],
+    }
+  }
+
+  pub fn record_checksum(&mut self, player_id: i32, tick: u32, checksum: u32) {
+    if let Some(items) = self.checksums.get_mut(&player_id) {
+      if items.len() == DESYNC_REPORT_CHECKSUM_HISTORY {
+        items.pop_front();
+      }
+      items.push_back(DesyncChecksum { tick, checksum });
+    }
+  }
+
+  pub fn record_time_slot(&mut self, tick: u32, time_ms: u32, actions: &[PlayerAction]) {
+    if self.time_slots.len() == DESYNC_REPORT_TIME_SLOT_HISTORY {
+      self.time_slots.pop_front();
+    }
+    self.time_slots.push_back(DesyncTimeSlot {
+      tick,
+      time_ms,
+      actions: actions
+        .iter()
+        .map(|action| DesyncPlayerAction {
+          player_id: self
+            .slot_player_id_lookup
+            .get(&action.player_id)
+            .cloned()
+            .unwrap_or_default(),
+          data: action.data.to_vec(),
+        })
+        .collect(),
+    });
+  }
+
+  /// Reports after the first few are discarded
+  pub fn report(&mut self, desync: &[PlayerDesync]) {
+    let (tick, time_ms) = match desync.iter().min_by_key(|item| item.tick) {
+      Some(item) => (item.tick, item.time),
+      None => return,
+    };
+    if self.reports.len() >= DESYNC_REPORT_MAX {
+      return;
+    }
+    let mut player_ids: Vec<i32> = desync.iter().map(|item| item.player_id).collect();
+    player_ids.sort();
+    player_ids.dedup();
+    self.reports.push(GameDesyncReport {
+      tick,
+      time_ms,
+      player_ids,
+      checksums: self
+        .checksums
+        .iter()
+        .map(|(player_id, items)| DesyncPlayerChecksums {
+          player_id: *player_id,
+          items: items.iter().cloned().collect(),
+        })
+        .collect(),
+      time_slots: self.time_slots.iter().cloned().collect(),
+      node_version: crate::version::FLO_NODE_VERSION.to_string(),
+    });
+  }
+
+  pub fn reports(&self) -> &[GameDesyncReport] {
+    &self.reports
+  }
+}
+
+#[test]
+fn test_desync_report() {
+  use bytes::Bytes;
+
+  let mut c = DesyncReportCollector::new(vec![(10, 1), (20, 2)]);
+  for tick in 0..(DESYNC_REPORT_TIME_SLOT_HISTORY as u32 + 1) {
+    c.record_time_slot(
+      tick,
+      tick * 30,
+      &[PlayerAction {
+        player_id: 2,
+        data: Bytes::from_static(&[0x01]),
+      }],
+    );
+    c.record_checksum(10, tick, 1);
+    c.record_checksum(20, tick, if tick == 10 { 2 } else { 1 });
+  }
+
+  c.report(&[PlayerDesync {
+    player_id: 20,
+    tick: 10,
+    time: 300,
+    checksum: 2,
+  }]);
+  let report = &c.reports()[0];
+  assert_eq!(report.tick, 10);
+  assert_eq!(report.player_ids, vec![20]);
+  assert_eq!(report.checksums.len(), 2);
+  assert_eq!(
+    report.checksums[1].items.len(),
+    DESYNC_REPORT_CHECKSUM_HISTORY
+  );
+  assert_eq!(report.time_slots.len(), DESYNC_REPORT_TIME_SLOT_HISTORY);
+  assert_eq!(report.time_slots[0].tick, 1);
+  assert_eq!(report.time_slots[0].actions[0].player_id, 20);
+
+  for _ in 0..DESYNC_REPORT_MAX {
+    c.report(&[PlayerDesync {
+      player_id: 20,
+      tick: 11,
+      time: 330,
+      checksum: 2,
+    }]);
+  }
+  assert_eq!(c.reports().len(), DESYNC_REPORT_MAX);
+}
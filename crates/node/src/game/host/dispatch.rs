@@ -1,26 +1,34 @@
+use super::chat::{ChatFilter, ChatFilterResult};
 use super::clock::ActionTickStream;
 use super::delay::{DelayedFrame, DelayedFrameStream};
 use super::delay_equalizer::DelayEqualizer;
+use super::desync::DesyncReportCollector;
+use super::log::GameLog;
 use super::player::{PlayerDispatchInfo, PlayerSendError};
+use super::result::GameResultCollector;
+use super::score::{score_screen_packets, ScorePlayer};
 use super::sync::SyncMap;
-use super::{broadcast, GameHostOptions};
+use super::{broadcast, GameHostOptions, GameResume};
 use crate::error::*;
 use crate::game::host::clock::Tick;
 use crate::game::host::stream::{PlayerStream, PlayerStreamCmd, PlayerStreamHandle};
 use crate::game::host::sync::{ClockResult, PlayerDesync};
 use crate::game::{
-  AckError, GameEvent, GameEventSender, PlayerBanType, PlayerSlot, SlotClientStatus,
-  SlotClientStatusUpdateSource,
+  AckError, GameEvent, GameEventSender, NodeGameStatus, PlayerBanType, PlayerSlot,
+  SlotClientStatus, SlotClientStatusUpdateSource,
 };
 use crate::observer::ObserverPublisherHandle;
-use flo_net::packet::{Frame, PacketTypeId};
+use crate::standby::{GameReplica, StandbyHandle};
+use flo_net::observer::{ObserverChatMessage, ObserverChatScope};
+use flo_net::packet::{FloPacket, Frame, PacketTypeId};
 use flo_net::ping::{PingMsg, PingStream};
+use flo_net::proto::flo_node::PacketNodeGameResult;
 use flo_net::w3gs::{W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
-use flo_observer::record::{RTTStats, RTTStatsItem};
+use flo_observer::record::{GameRecordData, RTTStats, RTTStatsItem};
 use flo_util::chat::{parse_chat_command, ChatCommand};
 use flo_w3gs::action::{IncomingAction, IncomingAction2, OutgoingKeepAlive};
 use flo_w3gs::protocol::action::{OutgoingAction, PlayerAction, TimeSlot};
-use flo_w3gs::protocol::chat::ChatToHost;
+use flo_w3gs::protocol::chat::{ChatMessage, ChatToHost, MessageScope};
 use flo_w3gs::protocol::constants::LeaveReason;
 use flo_w3gs::protocol::lag::{LagPlayer, StartLag, StopLag};
 use flo_w3gs::protocol::leave::LeaveReq;
@@ -31,11 +39,11 @@ use parking_lot::Mutex;
 use s2_grpc_utils::S2ProtoEnum;
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::{oneshot, watch, Notify};
-use tokio::time::{interval_at, sleep, MissedTickBehavior};
+use tokio::time::{interval_at, sleep, Instant, MissedTickBehavior};
 use tokio_util::sync::CancellationToken;
 use tracing_futures::Instrument;
 
@@ -91,11 +99,13 @@ pub struct Dispatcher {
   ct: CancellationToken,
   cmd_tx: Sender<Cmd>,
   start_notify: Arc<Notify>,
+  shared: Arc<Mutex<Shared>>,
 }
 
 impl Drop for Dispatcher {
   fn drop(&mut self) {
     self.ct.cancel();
+    self.shared.lock().standby.end();
   }
 }
 
@@ -113,6 +123,8 @@ impl Dispatcher {
     let (cmd_tx, cmd_rx) = channel(10);
     let (action_tx, action_rx) = channel(32);
     let enabled_ping_equalizer = opts.enabled_ping_equalizer;
+    let speed_percent = opts.speed_percent;
+    let span = opts.span.clone();
 
     let state = State::new(
       game_id,
@@ -124,12 +136,20 @@ impl Dispatcher {
       ct.clone(),
     );
 
+    let shared = state.shared.clone();
     let mut start_messages = vec![];
 
     if enabled_ping_equalizer {
       start_messages.push("Ping equalizer is enabled.".to_string());
     }
 
+    if speed_percent != 100 {
+      start_messages.push(format!(
+        "Practice game running at {}% speed, the result is unranked.",
+        speed_percent
+      ));
+    }
+
     let chat_banned_player_names: Vec<String> = state
       .chat_banned_player_ids
       .iter()
@@ -143,22 +163,28 @@ impl Dispatcher {
       ));
     }
 
+    if let Some(message) = state.chat_filter.describe() {
+      start_messages.push(message);
+    }
+
     tokio::spawn(
       Self::tick(
         game_id,
         state.shared.clone(),
         start_messages,
         start_notify.clone(),
+        speed_percent,
         status_tx,
         action_rx,
+        out_tx.clone(),
         ct.clone(),
       )
-      .instrument(tracing::debug_span!("tick", game_id)),
+      .instrument(tracing::info_span!(parent: &span, "tick", game_id)),
     );
 
     tokio::spawn(
       Self::serve(state, cmd_rx, action_tx, out_tx, ct.clone())
-        .instrument(tracing::debug_span!("serve", game_id)),
+        .instrument(tracing::debug_span!(parent: &span, "serve", game_id)),
     );
 
     Dispatcher {
@@ -166,9 +192,30 @@ impl Dispatcher {
       game_id,
       cmd_tx,
       start_notify,
+      shared,
     }
   }
 
+  pub fn game_result(&self) -> PacketNodeGameResult {
+    self.shared.lock().game_result()
+  }
+
+  pub fn game_log(&self) -> GameLog {
+    self.shared.lock().log.clone()
+  }
+
+  pub fn push_game_end(&self) {
+    self.shared.lock().push_game_end()
+  }
+
+  pub fn broadcast_message(&self, message: String) {
+    self.shared.lock().broadcast_message(message)
+  }
+
+  pub fn send_score_screen(&self, winner_player_ids: &[i32]) -> Result<()> {
+    self.shared.lock().send_score_screen(winner_player_ids)
+  }
+
   pub fn start(&mut self) {
     tracing::info!(game_id = self.game_id, "game started.");
     self.start_notify.notify_one();
@@ -215,11 +262,15 @@ impl Dispatcher {
           break;
         }
         Some(msg) = peer_rx.recv() => {
+          flo_task::yield_point().await;
           let player_id = msg.player_id();
           match state.dispatch_peer(msg, &mut action_tx, &mut out_tx).await {
             Ok(_) => {},
             Err(Error::Cancelled) => {},
             Err(err) => {
+              if err.is_decode() {
+                crate::metrics::DECODE_ERRORS.inc();
+              }
               tracing::error!(player_id, "player removed: dispatch peer: {}", err);
               state.shared.lock().remove_player_and_broadcast(player_id, None).ok();
             },
@@ -245,8 +296,10 @@ impl Dispatcher {
     shared: Arc<Mutex<Shared>>,
     start_messages: Vec<String>,
     start_notify: Arc<Notify>,
+    speed_percent: u16,
     status_tx: watch::Sender<DispatchStatus>,
     mut rx: Receiver<ActionMsg>,
+    out_tx: GameEventSender,
     ct: CancellationToken,
   ) {
     let started = {
@@ -267,7 +320,10 @@ impl Dispatcher {
         }
       }
 
-      let mut tick_stream = ActionTickStream::new(*crate::constants::GAME_DEFAULT_STEP_MS);
+      let settings = crate::settings::current();
+      let mut tick_stream =
+        ActionTickStream::new(settings.game_step_ms).with_speed_percent(speed_percent);
+      let game_metrics = crate::metrics::GameMetrics::new(game_id);
       let pause_timeout = sleep(Duration::from_secs(0));
       tokio::pin!(pause_timeout);
 
@@ -275,7 +331,7 @@ impl Dispatcher {
         let ct = ct.clone();
         let shared = shared.clone();
         tokio::spawn(async move {
-          let base_time = tokio::time::Instant::from_std(Instant::now());
+          let base_time = Instant::now();
           let mut stream = interval_at(
             base_time + crate::constants::RTT_STATS_REPORT_DELAY,
             crate::constants::RTT_STATS_REPORT_INTERVAL,
@@ -295,6 +351,49 @@ impl Dispatcher {
         });
       }
 
+      let (progress, mut watchdog) = flo_task::watchdog(
+        settings.game_stall_timeout,
+        TickProgress {
+          tick: 0,
+          time: 0,
+          paused: false,
+          step: tick_stream.step(),
+        },
+      );
+      {
+        let ct = ct.clone();
+        let shared = shared.clone();
+        tokio::spawn(
+          async move {
+            let last = tokio::select! {
+              _ = ct.cancelled() => return,
+              last = watchdog.stalled() => last,
+            };
+            if let Some(last) = last {
+              crate::metrics::GAME_STALLS.inc();
+              tracing::error!(
+                game_id,
+                tick = last.tick,
+                time = last.time,
+                paused = last.paused,
+                step = last.step,
+                "no tick dispatched for {:?}, ending game",
+                watchdog.timeout()
+              );
+              if let Err(err) = shared.lock().send_score_screen(&[]) {
+                tracing::error!(game_id, "send score screen: {}", err);
+              }
+              out_tx
+                .send(GameEvent::GameStatusChange(NodeGameStatus::Ended))
+                .await
+                .ok();
+              ct.cancel();
+            }
+          }
+          .in_current_span(),
+        );
+      }
+
       loop {
         tokio::select! {
           _ = ct.cancelled() => {
@@ -307,6 +406,7 @@ impl Dispatcher {
               }
               ActionMsg::SetStep(step) => {
                 tick_stream.set_step(step);
+                progress.feed(|p| p.step = tick_stream.step());
                 shared
                   .lock()
                   .broadcast_message(format!("Game step has been set to {}ms.", tick_stream.step()));
@@ -316,6 +416,7 @@ impl Dispatcher {
                   match shared.lock().check_stop_lag() {
                     Ok(true) => {
                       tick_stream.resume();
+                      progress.feed(|p| p.paused = false);
                       status_tx.send(DispatchStatus::Running).ok();
                       tracing::info!(
                         game_id,
@@ -335,17 +436,35 @@ impl Dispatcher {
                   "resume clock"
                 );
                 tick_stream.resume();
+                progress.feed(|p| p.paused = false);
                 status_tx.send(DispatchStatus::Running).ok();
               }
             }
           }
           Some(tick) = tick_stream.next() => {
-            match shared.lock().dispatch_action_tick(tick) {
-              Ok(DispatchResult::Continue) => {},
+            flo_task::yield_point().await;
+            #[cfg(feature = "chaos")]
+            if let Some(delay) = crate::chaos::tick_delay() {
+              tracing::debug!(game_id, "chaos: tick delayed by {:?}", delay);
+              sleep(delay).await;
+            }
+            game_metrics.observe_tick_delay(tick.delay);
+            let time_increment_ms = tick.time_increment_ms as u32;
+            let dispatch_started = std::time::Instant::now();
+            let res = shared.lock().dispatch_action_tick(tick);
+            crate::metrics::TICK_DISPATCH.observe(dispatch_started.elapsed().as_secs_f64());
+            match res {
+              Ok(DispatchResult::Continue) => {
+                progress.feed(|p| {
+                  p.tick += 1;
+                  p.time += time_increment_ms;
+                });
+              },
               Ok(DispatchResult::Lag(tick)) => {
                 tick_stream.replace_actions(tick.actions);
-                pause_timeout.as_mut().reset((Instant::now() + crate::constants::GAME_CLOCK_MAX_PAUSE).into());
+                pause_timeout.as_mut().reset(Instant::now() + crate::constants::GAME_CLOCK_MAX_PAUSE);
                 tick_stream.pause();
+                progress.feed(|p| p.paused = true);
                 status_tx.send(DispatchStatus::Paused).ok();
               }
               Err(err) => {
@@ -366,6 +485,7 @@ impl Dispatcher {
               break;
             }
             tick_stream.resume();
+            progress.feed(|p| p.paused = false);
           }
         }
       }
@@ -373,6 +493,15 @@ impl Dispatcher {
   }
 }
 
+/// Last state of the tick loop, reported if it stalls
+#[derive(Debug, Clone)]
+struct TickProgress {
+  tick: u32,
+  time: u32,
+  paused: bool,
+  step: u16,
+}
+
 #[derive(Debug)]
 enum ActionMsg {
   PlayerAction(PlayerAction),
@@ -390,6 +519,8 @@ struct State {
   game_player_id_lookup: BTreeMap<u8, i32>,
   _player_name_lookup: BTreeMap<i32, String>,
   chat_banned_player_ids: Vec<i32>,
+  chat_filter: ChatFilter,
+  observer_player_ids: BTreeSet<i32>,
   left_players: BTreeSet<i32>,
 }
 
@@ -410,6 +541,11 @@ impl State {
     } else {
       None
     };
+    let left_players = opts
+      .resume
+      .as_ref()
+      .map(|resume| resume.left_player_ids.iter().cloned().collect())
+      .unwrap_or_default();
     State {
       game_id,
       ct,
@@ -418,6 +554,8 @@ impl State {
         slots,
         obs,
         delay_equalizer,
+        opts.standby,
+        opts.resume,
       ))),
       status_rx,
       game_player_id_lookup: slots
@@ -438,7 +576,13 @@ impl State {
           }
         })
         .collect(),
-      left_players: BTreeSet::new(),
+      chat_filter: ChatFilter::new(opts.chat_rules),
+      observer_player_ids: slots
+        .into_iter()
+        .filter(|slot| slot.settings.team == 24)
+        .map(|slot| slot.player.player_id)
+        .collect(),
+      left_players,
     }
   }
 
@@ -486,7 +630,12 @@ impl State {
   ) -> Result<PlayerStreamHandle> {
     let game_id = self.game_id;
     let player_id = stream.player_id();
-    tracing::info!(player_id, stream_id = stream.id(), "player connected");
+    tracing::info!(
+      player_id,
+      stream_id = stream.id(),
+      addr = ?stream.peer_addr(),
+      "player connected"
+    );
 
     if self.left_players.contains(&player_id) {
       return Err(Error::PlayerAlreadyLeft);
@@ -503,6 +652,19 @@ impl State {
         .ok_or_else(|| Error::PlayerAlreadyLeft)?;
       let reconnected = !player.pristine();
       let delay = player.delay().cloned();
+      // a new connection replaces a stream that hasn't been closed yet,
+      // the old worker's close message will be ignored because of the stream id
+      if let Some(old) = player.take_stream() {
+        tracing::warn!(
+          game_id,
+          player_id,
+          old_stream_id = old.stream_id(),
+          "player stream replaced"
+        );
+        player.set_last_disconnect();
+        old.close();
+        crate::metrics::PLAYER_SESSION_TAKEOVERS.inc();
+      }
       player.register_sender(sender.clone());
       if reconnected {
         let resend_frames = player.get_resend_frames();
@@ -525,6 +687,7 @@ impl State {
     };
 
     if reconnected {
+      crate::metrics::PLAYER_RECONNECTS.inc();
       tracing::info!(game_id = self.game_id, player_id, "reconnected");
     }
 
@@ -587,6 +750,13 @@ impl State {
             .dispatch_incoming_w3gs(player_id, meta, pkt, action_tx, out_tx)
             .await?;
         }
+        PacketTypeId::W3GSBatch => {
+          for (meta, pkt) in frame.try_into_w3gs_batch()? {
+            self
+              .dispatch_incoming_w3gs(player_id, meta, pkt, action_tx, out_tx)
+              .await?;
+          }
+        }
         _ => {
           self.dispatch_incoming_flo(player_id, frame, out_tx).await?;
         }
@@ -602,7 +772,7 @@ impl State {
 
         let res = {
           let mut guard = self.shared.lock();
-          guard.handle_peer_stream_close(player_id)?
+          guard.handle_peer_stream_close(player_id, stream_id)?
         };
 
         let next_status = match res {
@@ -611,6 +781,10 @@ impl State {
             self.left_players.insert(player_id);
             SlotClientStatus::Left
           }
+          ClosePlayerStreamResult::Replaced => {
+            tracing::debug!(player_id, "replaced player stream closed: {}", stream_id);
+            return Ok(());
+          }
           ClosePlayerStreamResult::Skipped => {
             tracing::warn!(
               game_id = self.game_id,
@@ -688,6 +862,7 @@ impl State {
     } else {
       None
     };
+    shared.result.record_rtt(player_id, rtt);
     shared.get_player(player_id).map(|info| {
       info.push_rtt(rtt);
       if let Some(delay) = delay {
@@ -868,10 +1043,27 @@ impl State {
       return Ok(());
     }
 
+    let mut observers_only = false;
+    if let ChatMessage::Scoped { scope, ref message } = chat.message {
+      let from_observer = self.observer_player_ids.contains(&player_id);
+      match self
+        .chat_filter
+        .check(from_observer, scope, &message.to_string_lossy())
+      {
+        ChatFilterResult::Allow => {}
+        ChatFilterResult::ObserversOnly => observers_only = true,
+        ChatFilterResult::Block(reason) => {
+          self.shared.lock().private_message(player_id, reason);
+          return Ok(());
+        }
+      }
+    }
+
     packet.header.type_id = PacketTypeId::ChatFromHost;
     {
       let mut guard = self.shared.lock();
-      guard.obs.push_w3gs(self.game_id, packet.clone());
+      guard.push_w3gs(packet.clone());
+      guard.push_chat(player_id, &chat);
       guard.broadcast(
         packet,
         broadcast::AllowList(
@@ -880,7 +1072,7 @@ impl State {
             .into_iter()
             .filter_map(|id| {
               if let Some(id) = self.game_player_id_lookup.get(&id).cloned() {
-                if id != player_id {
+                if id != player_id && (!observers_only || self.observer_player_ids.contains(&id)) {
                   Some(id)
                 } else {
                   None
@@ -1097,6 +1289,10 @@ struct Shared {
   obs: ObserverPublisherHandle,
   active_players: BTreeSet<i32>,
   delay_equalizer: Option<DelayEqualizer>,
+  result: GameResultCollector,
+  desync: DesyncReportCollector,
+  log: GameLog,
+  standby: StandbyHandle,
 }
 
 impl Shared {
@@ -1105,11 +1301,24 @@ impl Shared {
     slots: &[PlayerSlot],
     obs: ObserverPublisherHandle,
     delay_equalizer: Option<DelayEqualizer>,
+    standby: StandbyHandle,
+    resume: Option<GameResume>,
   ) -> Self {
     let sync = SyncMap::new(slots.iter().map(|s| s.player.player_id).collect());
     let mut slot_id_lookup = BTreeMap::new();
     let mut active_players = BTreeSet::new();
-    Self {
+    let result = GameResultCollector::new(
+      slots
+        .iter()
+        .filter(|slot| slot.settings.team != 24)
+        .map(|slot| (slot.player.player_id, (slot.id + 1) as u8)),
+    );
+    let desync = DesyncReportCollector::new(
+      slots
+        .iter()
+        .map(|slot| (slot.player.player_id, (slot.id + 1) as u8)),
+    );
+    let mut shared = Self {
       game_id,
       started: false,
       map: slots
@@ -1130,20 +1339,152 @@ impl Shared {
       obs,
       active_players,
       delay_equalizer,
+      result,
+      desync,
+      log: GameLog::default(),
+      standby,
+    };
+    if let Some(resume) = resume {
+      shared.resume(resume);
     }
+    shared
+  }
+
+  /// Continues a game taken over from the primary node
+  fn resume(&mut self, resume: GameResume) {
+    for player_id in resume.left_player_ids {
+      self
+        .delay_equalizer
+        .as_mut()
+        .map(|de| de.remove_player(player_id));
+      if self.map.remove(&player_id).is_some() {
+        self.active_players.remove(&player_id);
+        self.sync.remove_player(player_id);
+        self
+          .result
+          .record_leave(player_id, None, resume.game_time_ms);
+      }
+    }
+    for (player_id, ack_queue) in resume.ack_queues {
+      if let Some(player) = self.map.get_mut(&player_id) {
+        player.resume(ack_queue);
+      }
+    }
+    self.sync.resume_at(resume.game_time_ms);
+    self.log = GameLog::restore(resume.log);
+  }
+
+  /// Sends the changes since the last tick to the standby node
+  fn replicate(&mut self) {
+    let sync = match self.standby.begin() {
+      Some(sync) => sync,
+      None => {
+        if self.standby.is_enabled() {
+          for player in self.map.values_mut() {
+            player.clear_replica();
+          }
+        }
+        return;
+      }
+    };
+    let left_player_ids = self
+      .slot_id_lookup
+      .keys()
+      .filter(|player_id| !self.map.contains_key(player_id))
+      .cloned()
+      .collect();
+    let replica = GameReplica {
+      started: self.started,
+      game_time_ms: self.sync.time(),
+      left_player_ids,
+      players: self
+        .map
+        .iter_mut()
+        .map(|(player_id, player)| player.take_replica(*player_id, sync.reset))
+        .collect(),
+      log: self.log.replicate(sync.reset),
+    };
+    self.standby.send(sync, replica);
   }
 
   fn set_started(&mut self) {
     self.started = true;
   }
 
+  fn game_result(&self) -> PacketNodeGameResult {
+    let mut pkt = self.result.to_packet(self.game_id, self.sync.time());
+    pkt.desyncs = self.desync.reports().to_vec();
+    pkt
+  }
+
+  fn push_w3gs(&mut self, packet: Packet) {
+    self.log.push(&GameRecordData::W3GS(packet.clone()));
+    self.obs.push_w3gs(self.game_id, packet);
+  }
+
+  // private messages are not kept
+  fn push_chat(&mut self, player_id: i32, chat: &ChatToHost) {
+    let (scope, message) = match chat.message {
+      ChatMessage::Scoped { scope, ref message } => (scope, message),
+      _ => return,
+    };
+    let scope = match scope {
+      MessageScope::All => ObserverChatScope::All,
+      MessageScope::Allies => ObserverChatScope::Allies,
+      MessageScope::Observers => ObserverChatScope::Observers,
+      MessageScope::Player(_) => return,
+    };
+    let mut message = ObserverChatMessage {
+      player_id,
+      game_time_ms: self.sync.time(),
+      message: message.to_string_lossy().into_owned(),
+      ..Default::default()
+    };
+    message.set_scope(scope);
+    self.log.push_chat(message);
+  }
+
+  fn push_tick_checksum(&mut self, tick: u32, checksum: u32) {
+    self
+      .log
+      .push(&GameRecordData::TickChecksum { tick, checksum });
+    self.obs.push_tick_checksum(self.game_id, tick, checksum);
+  }
+
+  fn push_start_lag(&mut self, player_ids: Vec<i32>) {
+    self.log.push(&GameRecordData::StartLag(player_ids.clone()));
+    self.obs.push_start_lag(self.game_id, player_ids);
+  }
+
+  fn push_end_lag(&mut self, player_id: i32) {
+    self.log.push(&GameRecordData::StopLag(player_id));
+    self.obs.push_end_lag(self.game_id, player_id);
+  }
+
+  fn push_game_end(&mut self) {
+    self.log.push(&GameRecordData::GameEnd);
+    self.log.end();
+    self.obs.push_game_end(self.game_id);
+  }
+
   fn get_player(&mut self, player_id: i32) -> Option<&mut PlayerDispatchInfo> {
     self.map.get_mut(&player_id)
   }
 
   #[must_use]
-  pub fn dispatch_action_tick(&mut self, mut tick: Tick) -> Result<DispatchResult> {
+  pub fn dispatch_action_tick(&mut self, tick: Tick) -> Result<DispatchResult> {
     let time_increment_ms = tick.time_increment_ms;
+    let backlogged = self.backlogged_player_ids();
+    if !backlogged.is_empty() {
+      tracing::warn!(
+        game_id = self.game_id,
+        "send queue backlogged: {:?}",
+        backlogged
+      );
+      if self.handle_lag(backlogged)? {
+        return Ok(DispatchResult::Lag(tick));
+      }
+    }
     if let ClockResult::Lag(timeouts) = self.sync.clock(time_increment_ms) {
       let player_ids: Vec<_> = timeouts.into_iter().map(|t| t.player_id).collect();
       if self.handle_lag(player_ids)? {
@@ -1151,6 +1492,13 @@ impl Shared {
       }
     }
 
+    for action in &tick.actions {
+      self.result.record_action(action);
+    }
+    self
+      .desync
+      .record_time_slot(self.sync.tick(), self.sync.time(), &tick.actions);
+
     if tick.actions_bytes_len > DISPATCH_ACTIONS_MTU {
       tracing::debug!(
         "over-sized actions: tick = {}, size = {}, len = {}",
@@ -1158,55 +1506,12 @@ impl Shared {
         tick.actions_bytes_len,
         tick.actions.len(),
       );
-      let mut remaining_size = tick.actions_bytes_len;
-      while remaining_size > DISPATCH_ACTIONS_MTU {
-        let mut actions_size = 0;
-        let mut time_slot = TimeSlot {
-          time_increment_ms: 0,
-          actions: vec![],
-        };
-        while let Some((action_player_id, size)) =
-          tick.actions.first().map(|a| (a.player_id, a.byte_len()))
-        {
-          if size > DISPATCH_ACTIONS_MTU {
-            tick.actions.remove(0);
-            remaining_size -= size;
-            tracing::warn!(
-              game_id = self.game_id,
-              action_player_id,
-              "over-sized action dropped: {}",
-              size
-            );
-            break;
-          }
-
-          if actions_size + size > DISPATCH_ACTIONS_MTU {
-            tracing::debug!(
-              "fragment actions: tick = {}, size = {}, len = {}, remaining_size = {}",
-              self.sync.tick(),
-              actions_size,
-              time_slot.actions.len(),
-              remaining_size
-            );
-            let action_packet = Packet::with_payload(IncomingAction2(time_slot))?;
-            self.obs.push_w3gs(self.game_id, action_packet.clone());
-            self.broadcast(action_packet, broadcast::Everyone)?;
-            break;
-          }
-
-          let action = tick.actions.remove(0);
-          remaining_size -= size;
-          actions_size += size;
-          time_slot.actions.push(action);
-        }
-      }
     }
-    let action_packet = Packet::with_payload(IncomingAction(TimeSlot {
-      time_increment_ms,
-      actions: tick.actions,
-    }))?;
-    self.obs.push_w3gs(self.game_id, action_packet.clone());
-    self.broadcast(action_packet, broadcast::Everyone)?;
+    for action_packet in encode_action_tick(time_increment_ms, tick.actions)? {
+      self.push_w3gs(action_packet.clone());
+      self.broadcast(action_packet, broadcast::Everyone)?;
+    }
+    self.replicate();
     Ok(DispatchResult::Continue)
   }
 
@@ -1227,12 +1532,26 @@ impl Shared {
       .push_rtt_stat(self.game_id, RTTStats::new(time, items))
   }
 
+  // players not lagging yet whose send queue is over the lag threshold
+  fn backlogged_player_ids(&self) -> Vec<i32> {
+    let threshold = crate::settings::current().send_queue_lag_threshold;
+    self
+      .map
+      .iter()
+      .filter(|(player_id, info)| {
+        !self.lagging_player_ids.contains(*player_id) && info.send_queue_len() >= threshold
+      })
+      .map(|(player_id, _)| *player_id)
+      .collect()
+  }
+
   fn handle_lag(&mut self, add_player_ids: Vec<i32>) -> Result<bool> {
+    let now = Instant::now();
+    for player_id in &add_player_ids {
+      self.result.record_lag_start(*player_id, now);
+    }
     self.lagging_player_ids.extend(add_player_ids);
-    self.obs.push_start_lag(
-      self.game_id,
-      self.lagging_player_ids.iter().cloned().collect(),
-    );
+    self.push_start_lag(self.lagging_player_ids.iter().cloned().collect());
     if let Some(items) = self.refresh_lag_packet()? {
       self.drop_votes.clear();
       let mut send_errors = vec![];
@@ -1280,13 +1599,18 @@ impl Shared {
     if self.lagging_player_ids.is_empty() {
       return Ok(false);
     }
+    let lag_threshold = crate::settings::current().send_queue_lag_threshold;
     let mut stop_lag_players = vec![];
     let mut packets = vec![];
     for id in self.lagging_player_ids.clone() {
       let info = if let Some(info) = self.map.get_mut(&id) {
-        let reconnected =
-          info.stream_id().is_some() && self.sync.player_pending_ticks(id) == Some(0);
-        if reconnected {
+        let recovered = is_lag_recovered(
+          info.stream_id().is_some(),
+          self.sync.player_pending_ticks(id),
+          info.send_queue_len(),
+          lag_threshold,
+        );
+        if recovered {
           Some((info.slot_player_id(), info.end_lag()))
         } else {
           None
@@ -1296,7 +1620,8 @@ impl Shared {
         self.slot_id_lookup.get(&id).cloned().map(|slot| (slot, 0))
       };
       if let Some((slot, lag_duration_ms)) = info {
-        self.obs.push_end_lag(self.game_id, id);
+        self.push_end_lag(id);
+        self.result.record_lag_end(id, Instant::now());
         self.lagging_player_ids.remove(&id);
         stop_lag_players.push(id);
         packets.push((
@@ -1355,13 +1680,24 @@ impl Shared {
     Ok(Some(items))
   }
 
-  fn handle_peer_stream_close(&mut self, player_id: i32) -> Result<ClosePlayerStreamResult> {
+  fn handle_peer_stream_close(
+    &mut self,
+    player_id: i32,
+    stream_id: u64,
+  ) -> Result<ClosePlayerStreamResult> {
+    if let Some(current) = self.map.get(&player_id).and_then(|v| v.stream_id()) {
+      if current != stream_id {
+        return Ok(ClosePlayerStreamResult::Replaced);
+      }
+    }
+
     if let Some(stream) = self.map.get_mut(&player_id).and_then(|v| {
       v.set_last_disconnect();
       v.take_stream()
     }) {
       if self.started {
         tracing::warn!(game_id = self.game_id, player_id, "player disconnected");
+        self.result.record_disconnect(player_id);
         stream.close();
         // don't need to check `lagging_player_ids`
         // because disconnect does not change lag status
@@ -1400,6 +1736,9 @@ impl Shared {
     };
 
     tracing::info!(game_id = self.game_id, player_id, "remove player");
+    self
+      .result
+      .record_leave(player_id, reason, self.sync.time());
 
     for p in self.map.values_mut() {
       p.remove_lag_slot(player.slot_player_id());
@@ -1408,10 +1747,11 @@ impl Shared {
       player_id: player.slot_player_id(),
       reason: reason.unwrap_or(LeaveReason::LeaveDisconnect),
     })?;
-    self.obs.push_w3gs(self.game_id, pkt.clone());
+    self.push_w3gs(pkt.clone());
 
     self.broadcast(pkt, broadcast::DenyList(&[player_id]))?;
     if let Some(desync) = self.sync.remove_player(player_id) {
+      self.desync.report(&desync);
       tracing::warn!(
         player_id,
         "desync detected after disconnecting player: {:?}",
@@ -1423,11 +1763,15 @@ impl Shared {
     Ok(())
   }
 
+  /// `packet` is encoded once, recipients share its payload bytes
+  /// and only get their own W3GS metadata
   pub fn broadcast<T: broadcast::BroadcastTarget>(
     &mut self,
     packet: Packet,
     target: T,
   ) -> Result<()> {
+    let type_id = packet.type_id();
+    let drop_threshold = crate::settings::current().send_queue_drop_threshold;
     let errors: Vec<_> = {
       self
         .map
@@ -1437,6 +1781,11 @@ impl Shared {
             return None;
           }
 
+          if is_droppable(type_id, info.send_queue_len(), drop_threshold) {
+            tracing::debug!(player_id, "send queue backlogged, packet dropped");
+            return None;
+          }
+
           let res = if info.stream_id().is_some() {
             info.send_w3gs(packet.clone()).err().map(|err| {
               info.close_stream();
@@ -1488,6 +1837,25 @@ impl Shared {
     });
   }
 
+  /// Sends every player to the score screen, see [`score_screen_packets`]
+  pub fn send_score_screen(&mut self, winner_player_ids: &[i32]) -> Result<()> {
+    let players: Vec<_> = self
+      .map
+      .iter()
+      .map(|(player_id, info)| ScorePlayer {
+        player_id: *player_id,
+        slot_player_id: info.slot_player_id(),
+        observer: info.is_observer(),
+      })
+      .collect();
+    for (player_id, info) in self.map.iter_mut() {
+      for pkt in score_screen_packets(*player_id, &players, winner_player_ids)? {
+        info.send_w3gs(pkt).ok();
+      }
+    }
+    Ok(())
+  }
+
   pub fn private_message<T: AsRef<str> + Send + 'static>(&mut self, player_id: i32, message: T) {
     if let Some(info) = self.map.get_mut(&player_id) {
       info.send_private_message(message.as_ref());
@@ -1519,7 +1887,9 @@ impl Shared {
 
   pub fn drop_all_lag_players(&mut self) -> Result<()> {
     let drop_player_ids: Vec<_> = self.lagging_player_ids.iter().cloned().collect();
+    let now = Instant::now();
     for drop_player_id in &drop_player_ids {
+      self.result.record_lag_end(*drop_player_id, now);
       tracing::info!(
         game_id = self.game_id,
         player_id = *drop_player_id,
@@ -1534,10 +1904,11 @@ impl Shared {
   pub fn ack(&mut self, player_id: i32, checksum: u32) -> Result<AckAction> {
     let res = match self.sync.ack(player_id, checksum) {
       Ok(res) => {
+        self
+          .desync
+          .record_checksum(player_id, res.player_tick, checksum);
         if let Some(checksum) = res.agreed_checksum.clone() {
-          self
-            .obs
-            .push_tick_checksum(self.game_id, res.game_tick, checksum);
+          self.push_tick_checksum(res.game_tick, checksum);
         }
         res
       }
@@ -1553,6 +1924,9 @@ impl Shared {
         match err {
           AckError::PlayerNotFound(_) => {}
           AckError::TickNotFound(desync) => {
+            self
+              .desync
+              .record_checksum(player_id, desync.tick, desync.checksum);
             self.handle_desync(vec![desync])?;
           }
         }
@@ -1576,11 +1950,13 @@ impl Shared {
   }
 
   fn handle_desync(&mut self, desync: Vec<PlayerDesync>) -> Result<()> {
+    self.desync.report(&desync);
     let mut handled = BTreeSet::new();
     let mut targets = vec![];
     for item in desync {
       if !handled.contains(&item.player_id) {
         handled.insert(item.player_id);
+        self.result.record_desync(item.player_id);
 
         tracing::warn!(
           game_id = self.game_id,
@@ -1611,6 +1987,54 @@ impl Shared {
   }
 }
 
+/// Encodes the actions of a tick as the packets broadcast to the players.
+/// Actions over `DISPATCH_ACTIONS_MTU` are sent ahead in `IncomingAction2` fragments,
+/// the last packet is always an `IncomingAction` with `time_increment_ms`.
+pub fn encode_action_tick(
+  time_increment_ms: u16,
+  mut actions: Vec<PlayerAction>,
+) -> Result<Vec<Packet>> {
+  let mut packets = vec![];
+  let mut remaining_size: usize = actions.iter().map(|a| a.byte_len()).sum();
+  while remaining_size > DISPATCH_ACTIONS_MTU {
+    let mut actions_size = 0;
+    let mut time_slot = TimeSlot {
+      time_increment_ms: 0,
+      actions: vec![],
+    };
+    while let Some((action_player_id, size)) = actions.first().map(|a| (a.player_id, a.byte_len()))
+    {
+      if size > DISPATCH_ACTIONS_MTU {
+        actions.remove(0);
+        remaining_size -= size;
+        tracing::warn!(action_player_id, "over-sized action dropped: {}", size);
+        break;
+      }
+
+      if actions_size + size > DISPATCH_ACTIONS_MTU {
+        tracing::debug!(
+          "fragment actions: size = {}, len = {}, remaining_size = {}",
+          actions_size,
+          time_slot.actions.len(),
+          remaining_size
+        );
+        packets.push(Packet::with_payload(IncomingAction2(time_slot))?);
+        break;
+      }
+
+      let action = actions.remove(0);
+      remaining_size -= size;
+      actions_size += size;
+      time_slot.actions.push(action);
+    }
+  }
+  packets.push(Packet::with_payload(IncomingAction(TimeSlot {
+    time_increment_ms,
+    actions,
+  }))?);
+  Ok(packets)
+}
+
 enum AckAction {
   Continue,
   CheckStopLag,
@@ -1713,6 +2137,16 @@ impl PeerWorker {
         next = self.stream.get_mut().recv_frame() => {
           match next {
             Ok(frame) => {
+              flo_task::yield_point().await;
+              #[cfg(feature = "chaos")]
+              if crate::chaos::disconnect() {
+                tracing::info!(
+                  game_id = self.game_id,
+                  player_id,
+                  "chaos: disconnect"
+                );
+                break;
+              }
               match frame.type_id {
                 PacketTypeId::ClientShutdown => {
                   self.shutdown(&mut ping, player_id, None).await;
@@ -1730,6 +2164,12 @@ impl PeerWorker {
                   self.shutdown(&mut ping, player_id, Some(req.reason())).await;
                   break;
                 }
+                // answered here so the dispatcher doesn't add to the round trip
+                PacketTypeId::ClientTimeSyncRequest => {
+                  let reply = time_sync_reply(frame)?;
+                  self.stream.get_mut().send_frame(reply).await?;
+                  continue;
+                }
                 PingStream::PONG_TYPE_ID => {
                   if !self.delay.enabled() {
                     if ping.started() {
@@ -1773,6 +2213,10 @@ impl PeerWorker {
                 self.delay.insert(DelayedFrame::Out(frame));
                 continue;
               }
+              #[cfg(feature = "chaos")]
+              if frame.type_id == PacketTypeId::W3GS && crate::chaos::duplicate_frame() {
+                self.stream.get_mut().send_frame(frame.clone()).await?;
+              }
               self.stream.get_mut().send_frame(frame).await?;
             }
             PlayerStreamCmd::SetDelay(delay) => {
@@ -1961,9 +2405,95 @@ enum DispatchResult {
 }
 
 #[derive(Debug)]
+fn time_sync_reply(frame: Frame) -> Result<Frame> {
+  use flo_net::proto::flo_node::{PacketClientTimeSync, PacketClientTimeSyncRequest};
+  use flo_net::time::unix_time_micros;
+
+  let node_recv_time = unix_time_micros();
+  let req: PacketClientTimeSyncRequest = frame.decode()?;
+  Ok(
+    PacketClientTimeSync {
+      client_send_time: req.client_send_time,
+      node_recv_time,
+      node_send_time: unix_time_micros(),
+    }
+    .encode_as_frame()?,
+  )
+}
+
+/// A lagging player is back once connected, caught up with the ticks,
+/// and its send queue is below the threshold that started the lag
+fn is_lag_recovered(
+  connected: bool,
+  pending_ticks: Option<u32>,
+  send_queue_len: usize,
+  lag_threshold: usize,
+) -> bool {
+  connected && pending_ticks == Some(0) && send_queue_len < lag_threshold
+}
+
+/// Chat is dropped for players with a backed up send queue, game packets never are
+fn is_droppable(type_id: W3GSPacketTypeId, send_queue_len: usize, drop_threshold: usize) -> bool {
+  type_id == W3GSPacketTypeId::ChatFromHost && send_queue_len >= drop_threshold
+}
+
 enum ClosePlayerStreamResult {
   ClosedDisconnected,
   ClosedLeft,
   ClosedLagging,
+  Replaced,
   Skipped,
 }
+
+#[test]
+fn test_encode_action_tick_fragments() {
+  use bytes::Bytes;
+
+  let actions: Vec<_> = (0..10_u8)
+    .map(|player_id| PlayerAction {
+      player_id,
+      data: Bytes::from(vec![0x16; 300]),
+    })
+    .collect();
+  let packets = encode_action_tick(100, actions.clone()).unwrap();
+  assert!(packets.len() > 1);
+
+  let mut decoded = vec![];
+  for (i, pkt) in packets.iter().enumerate() {
+    let slot = if i + 1 == packets.len() {
+      let IncomingAction(slot) = pkt.decode_payload().unwrap();
+      assert_eq!(slot.time_increment_ms, 100);
+      slot
+    } else {
+      let IncomingAction2(slot) = pkt.decode_payload().unwrap();
+      slot
+    };
+    assert!(slot.actions.iter().map(|a| a.byte_len()).sum::<usize>() <= DISPATCH_ACTIONS_MTU);
+    decoded.extend(slot.actions);
+  }
+  assert_eq!(decoded, actions);
+}
+
+#[test]
+fn test_send_queue_lag() {
+  let threshold = 100;
+  // backlogged players stay on the lag screen until the queue drains
+  assert!(!is_lag_recovered(true, Some(0), threshold, threshold));
+  assert!(!is_lag_recovered(true, Some(0), threshold + 1, threshold));
+  assert!(is_lag_recovered(true, Some(0), threshold - 1, threshold));
+  assert!(is_lag_recovered(true, Some(0), 0, threshold));
+  // still behind or disconnected
+  assert!(!is_lag_recovered(true, Some(2), 0, threshold));
+  assert!(!is_lag_recovered(true, None, 0, threshold));
+  assert!(!is_lag_recovered(false, Some(0), 0, threshold));
+}
+
+#[test]
+fn test_send_queue_drop() {
+  let threshold = 200;
+  let droppable = |type_id, send_queue_len| is_droppable(type_id, send_queue_len, threshold);
+  assert!(!droppable(W3GSPacketTypeId::ChatFromHost, threshold - 1));
+  assert!(droppable(W3GSPacketTypeId::ChatFromHost, threshold));
+  assert!(!droppable(W3GSPacketTypeId::IncomingAction, threshold * 2));
+  assert!(!droppable(W3GSPacketTypeId::StartLag, threshold * 2));
+}
@@ -0,0 +1,290 @@
+use bytes::{Bytes, BytesMut};
+use flo_net::observer::ObserverChatMessage;
+use flo_observer::record::GameRecordData;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use crate::constants::{
+  GAME_CHAT_HISTORY_SIZE, GAME_LOG_CHUNK_SIZE, GAME_LOG_MAX_SIZE, OBS_STREAM_CHANNEL_SIZE,
+};
+
+/// Records sent to the observer publisher, kept in memory
+/// so the controller can generate a replay after the game ends
+/// and observers connected to the node can catch up from the beginning.
+/// Chunks always end on a record boundary.
+#[derive(Debug, Clone)]
+pub struct GameLog(Arc<Mutex<State>>);
+
+#[derive(Debug)]
+struct State {
+  chunks: Vec<GameLogChunk>,
+  pending: BytesMut,
+  last_push: Instant,
+  size: usize,
+  /// Size of the log already sent to the standby node
+  replicated: usize,
+  truncated: bool,
+  // dropped after the game ended or the log was truncated
+  tx: Option<broadcast::Sender<GameLogChunk>>,
+  chat: VecDeque<GameChatEntry>,
+}
+
+/// Encoded records and the time the last one was pushed
+#[derive(Debug, Clone)]
+pub struct GameLogChunk {
+  pub time: Instant,
+  pub data: Bytes,
+}
+
+/// In-game chat message and the time it was sent
+#[derive(Debug, Clone)]
+pub struct GameChatEntry {
+  pub time: Instant,
+  pub message: ObserverChatMessage,
+}
+
+#[derive(Debug)]
+pub struct GameLogSubscription {
+  /// Records pushed before subscribing
+  pub history: Vec<GameLogChunk>,
+  /// The last `GAME_CHAT_HISTORY_SIZE` chat messages, also included in the records
+  pub chat: Vec<GameChatEntry>,
+  /// Records pushed after subscribing, one record per chunk,
+  /// closed when the game ends
+  pub rx: broadcast::Receiver<GameLogChunk>,
+}
+
+impl Default for GameLog {
+  fn default() -> Self {
+    let (tx, _) = broadcast::channel(OBS_STREAM_CHANNEL_SIZE);
+    Self(Arc::new(Mutex::new(State {
+      chunks: vec![],
+      pending: BytesMut::new(),
+      last_push: Instant::now(),
+      size: 0,
+      replicated: 0,
+      truncated: false,
+      tx: Some(tx),
+      chat: VecDeque::new(),
+    })))
+  }
+}
+
+impl GameLog {
+  /// Restores a log replicated from another node
+  pub fn restore(pieces: Vec<Bytes>) -> Self {
+    let log = Self::default();
+    {
+      let mut state = log.0.lock();
+      for piece in pieces {
+        if !state.pending.is_empty() && state.pending.len() + piece.len() > GAME_LOG_CHUNK_SIZE {
+          state.flush();
+        }
+        state.pending.extend_from_slice(&piece);
+        state.size += piece.len();
+      }
+      state.replicated = state.size;
+    }
+    log
+  }
+
+  pub fn push(&self, record: &GameRecordData) {
+    let mut state = self.0.lock();
+    if state.truncated {
+      return;
+    }
+    let len = record.encode_len();
+    if state.size + len > GAME_LOG_MAX_SIZE {
+      tracing::warn!("game log truncated at {} bytes", state.size);
+      state.truncated = true;
+      state.tx.take();
+      return;
+    }
+    if !state.pending.is_empty() && state.pending.len() + len > GAME_LOG_CHUNK_SIZE {
+      state.flush();
+    }
+    let mut buf = BytesMut::with_capacity(len);
+    record.encode(&mut buf);
+    let data = buf.freeze();
+    let time = Instant::now();
+    state.pending.extend_from_slice(&data);
+    state.last_push = time;
+    state.size += len;
+    if let Some(tx) = state.tx.as_ref() {
+      // no receiver is not an error
+      tx.send(GameLogChunk { time, data }).ok();
+    }
+  }
+
+  /// Keeps a chat message for observers that connect late,
+  /// the oldest message is dropped when the history is full
+  pub fn push_chat(&self, message: ObserverChatMessage) {
+    let mut state = self.0.lock();
+    if state.chat.len() == GAME_CHAT_HISTORY_SIZE {
+      state.chat.pop_front();
+    }
+    state.chat.push_back(GameChatEntry {
+      time: Instant::now(),
+      message,
+    });
+  }
+
+  /// Closes the live tail of all subscriptions
+  pub fn end(&self) {
+    self.0.lock().tx.take();
+  }
+
+  /// Returns `None` if the log was truncated
+  pub fn subscribe(&self) -> Option<GameLogSubscription> {
+    let mut state = self.0.lock();
+    if state.truncated {
+      return None;
+    }
+    state.flush();
+    let rx = match state.tx.as_ref() {
+      Some(tx) => tx.subscribe(),
+      None => broadcast::channel(1).1,
+    };
+    Some(GameLogSubscription {
+      history: state.chunks.clone(),
+      chat: state.chat.iter().cloned().collect(),
+      rx,
+    })
+  }
+
+  /// The recorded log split into packet-sized chunks,
+  /// returns `None` if the log was truncated
+  pub fn chunks(&self) -> Option<Vec<Bytes>> {
+    let mut state = self.0.lock();
+    if state.truncated {
+      return None;
+    }
+    state.flush();
+    if state.chunks.is_empty() {
+      return Some(vec![Bytes::new()]);
+    }
+    Some(
+      state
+        .chunks
+        .iter()
+        .map(|chunk| chunk.data.clone())
+        .collect(),
+    )
+  }
+}
+
+impl GameLog {
+  /// Records pushed since the last call, or the whole log if `reset` is set,
+  /// in pieces that end on a record boundary and fit in a chunk
+  pub fn replicate(&self, reset: bool) -> Vec<Bytes> {
+    let mut state = self.0.lock();
+    if state.truncated {
+      return vec![];
+    }
+    if reset {
+      state.replicated = 0;
+    }
+    let mut pieces = vec![];
+    let mut offset = 0;
+    for data in state
+      .chunks
+      .iter()
+      .map(|chunk| chunk.data.as_ref())
+      .chain(std::iter::once(state.pending.as_ref()))
+    {
+      let end = offset + data.len();
+      if end > state.replicated {
+        let start = state.replicated.saturating_sub(offset);
+        pieces.push(Bytes::copy_from_slice(&data[start..]));
+      }
+      offset = end;
+    }
+    state.replicated = state.size;
+    pieces
+  }
+}
+
+impl State {
+  fn flush(&mut self) {
+    if self.pending.is_empty() {
+      return;
+    }
+    let data = std::mem::take(&mut self.pending).freeze();
+    self.chunks.push(GameLogChunk {
+      time: self.last_push,
+      data,
+    });
+  }
+}
+
+#[test]
+fn test_game_log_chunks() {
+  let log = GameLog::default();
+  let n = GAME_LOG_CHUNK_SIZE / 9 + 1;
+  for tick in 0..n {
+    log.push(&GameRecordData::TickChecksum {
+      tick: tick as u32,
+      checksum: 0,
+    });
+  }
+  let chunks = log.chunks().unwrap();
+  assert_eq!(chunks.len(), 2);
+  assert_eq!(chunks.iter().map(|c| c.len()).sum::<usize>(), n * 9);
+  assert!(chunks.iter().all(|c| c.len() % 9 == 0));
+}
+
+#[test]
+fn test_game_log_subscribe() {
+  let log = GameLog::default();
+  log.push(&GameRecordData::TickChecksum {
+    tick: 0,
+    checksum: 0,
+  });
+  let mut sub = log.subscribe().unwrap();
+  assert_eq!(sub.history.len(), 1);
+  log.push(&GameRecordData::GameEnd);
+  log.end();
+  assert_eq!(sub.rx.try_recv().unwrap().data.len(), 1);
+  assert!(sub.rx.try_recv().is_err());
+  assert_eq!(log.subscribe().unwrap().history.len(), 2);
+}
+
+#[test]
+fn test_game_log_chat_history() {
+  let log = GameLog::default();
+  for i in 0..(GAME_CHAT_HISTORY_SIZE + 1) {
+    log.push_chat(ObserverChatMessage {
+      player_id: i as i32,
+      ..Default::default()
+    });
+  }
+  let chat = log.subscribe().unwrap().chat;
+  assert_eq!(chat.len(), GAME_CHAT_HISTORY_SIZE);
+  assert_eq!(chat[0].message.player_id, 1);
+}
+
+#[test]
+fn test_game_log_replicate() {
+  let log = GameLog::default();
+  let n = GAME_LOG_CHUNK_SIZE / 9 + 1;
+  for tick in 0..n {
+    log.push(&GameRecordData::TickChecksum {
+      tick: tick as u32,
+      checksum: 0,
+    });
+  }
+  log.chunks();
+  log.push(&GameRecordData::GameEnd);
+  let pieces = log.replicate(false);
+  assert_eq!(pieces.len(), 3);
+  assert!(log.replicate(false).is_empty());
+
+  let restored = GameLog::restore(log.replicate(true));
+  assert_eq!(
+    restored.chunks().unwrap().concat(),
+    log.chunks().unwrap().concat()
+  );
+}
@@ -1,21 +1,33 @@
 use s2_grpc_utils::S2ProtoEnum;
+use std::collections::BTreeMap;
 
+pub use dispatch::encode_action_tick;
 use dispatch::Dispatcher;
 use flo_net::packet::*;
+pub use log::{GameChatEntry, GameLog, GameLogChunk, GameLogSubscription};
 pub use sync::AckError;
 
 use crate::error::*;
 use crate::game::host::stream::{PlayerStream, PlayerStreamHandle};
 use crate::game::{GameEventSender, NodeGameStatusSnapshot, PlayerSlot};
 use crate::observer::ObserverPublisherHandle;
+use crate::standby::StandbyHandle;
+use bytes::Bytes;
+use flo_net::w3gs::W3GSAckQueue;
+use flo_types::feature::{FeatureFlag, FeatureFlags};
 use flo_w3gs::constants::LeaveReason;
 
 mod broadcast;
+mod chat;
 mod clock;
 mod delay;
 mod delay_equalizer;
+mod desync;
 mod dispatch;
+mod log;
 mod player;
+mod result;
+mod score;
 pub mod stream;
 mod sync;
 
@@ -23,11 +35,32 @@ mod sync;
 pub struct GameHost {
   game_id: i32,
   dispatcher: Dispatcher,
+  /// Accept `W3GSBatch` frames from players
+  w3gs_batch: bool,
 }
 
 #[derive(Debug)]
 pub struct GameHostOptions {
   pub enabled_ping_equalizer: bool,
+  /// Tick rate in percent of the normal speed, not 100 in practice games only
+  pub speed_percent: u16,
+  /// Set by tournament organizers
+  pub chat_rules: Option<flo_net::proto::flo_node::ChatRules>,
+  pub feature_flags: FeatureFlags,
+  /// Parent of the dispatch task spans
+  pub span: tracing::Span,
+  pub standby: StandbyHandle,
+  /// Set if the game is taken over from the primary node
+  pub resume: Option<GameResume>,
+}
+
+/// Replicated state of a started game, see [`crate::standby::ReplicaRegistry`]
+#[derive(Debug)]
+pub struct GameResume {
+  pub game_time_ms: u32,
+  pub left_player_ids: Vec<i32>,
+  pub ack_queues: BTreeMap<i32, W3GSAckQueue>,
+  pub log: Vec<Bytes>,
 }
 
 impl GameHost {
@@ -38,10 +71,12 @@ impl GameHost {
     obs: ObserverPublisherHandle,
     event_sender: GameEventSender,
   ) -> Self {
+    let w3gs_batch = !opts.feature_flags.contains(FeatureFlag::NoW3GSBatch);
     let dispatcher = Dispatcher::new(game_id, opts, slots, obs, event_sender);
     Self {
       game_id,
       dispatcher,
+      w3gs_batch,
     }
   }
 
@@ -49,6 +84,30 @@ impl GameHost {
     self.dispatcher.start();
   }
 
+  pub fn game_result(&self) -> flo_net::proto::flo_node::PacketNodeGameResult {
+    self.dispatcher.game_result()
+  }
+
+  pub fn game_log(&self) -> GameLog {
+    self.dispatcher.game_log()
+  }
+
+  /// Records the end of the game and closes the live tail of the game log
+  pub fn push_game_end(&self) {
+    self.dispatcher.push_game_end()
+  }
+
+  /// Sends a host chat message to every player
+  pub fn broadcast_message(&self, message: String) {
+    self.dispatcher.broadcast_message(message)
+  }
+
+  /// Ends the game of the connected players with the score screen,
+  /// there is a draw if `winner_player_ids` is empty
+  pub fn send_score_screen(&self, winner_player_ids: &[i32]) -> Result<()> {
+    self.dispatcher.send_score_screen(winner_player_ids)
+  }
+
   pub async fn register_player_stream(
     &mut self,
     mut stream: PlayerStream,
@@ -62,6 +121,11 @@ impl GameHost {
           version: Some(crate::version::FLO_NODE_VERSION.into()),
           game_id: self.game_id,
           player_id,
+          w3gs_batch: self.w3gs_batch,
+          time_sync: true,
+          standby_addr: crate::standby::client_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default(),
           ..Default::default()
         };
         pkt.set_game_status(snapshot.game_status.into_proto_enum());
@@ -1,12 +1,14 @@
 use crate::error::Result;
 use crate::game::host::stream::PlayerStreamHandle;
 use crate::game::{PlayerBanType, PlayerSlot};
+use crate::standby::PlayerReplica;
 use flo_net::packet::Frame;
 use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket};
 use flo_w3gs::protocol::chat::ChatFromHost;
 use std::collections::BTreeSet;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::Instant;
 
 #[derive(Debug)]
 pub struct PlayerDispatchInfo {
@@ -24,6 +26,10 @@ pub struct PlayerDispatchInfo {
   rtt_stats: PlayerRTTStats,
   last_rtt_stats: Option<PlayerRTTStats>,
   is_observer: bool,
+  /// Packets queued since the last replication to the standby node
+  replica_sent: Option<Vec<(W3GSMetadata, W3GSPacket)>>,
+  /// Taken over from the primary node by a standby node
+  resumed: bool,
 }
 
 impl PlayerDispatchInfo {
@@ -43,9 +49,19 @@ impl PlayerDispatchInfo {
       rtt_stats: PlayerRTTStats::default(),
       last_rtt_stats: None,
       is_observer: slot.settings.team == 24,
+      replica_sent: None,
+      resumed: false,
     }
   }
 
+  /// Continues with the ack queue replicated from the primary node,
+  /// the player has to reconnect and gets the unacknowledged packets resent
+  pub fn resume(&mut self, w3gs_ack_q: W3GSAckQueue) {
+    self.w3gs_ack_q = w3gs_ack_q;
+    self.resumed = true;
+    self.set_last_disconnect();
+  }
+
   pub fn player_name(&self) -> &str {
     self.player_name.as_str()
   }
@@ -109,6 +125,15 @@ impl PlayerDispatchInfo {
     self.tx.as_ref().map(|v| v.stream_id())
   }
 
+  pub fn send_queue_len(&self) -> usize {
+    self.tx.as_ref().map(|v| v.queue_len()).unwrap_or_default()
+  }
+
+  /// Non-critical packets are dropped for this player
+  pub fn is_send_backlogged(&self) -> bool {
+    self.send_queue_len() >= crate::settings::current().send_queue_drop_threshold
+  }
+
   pub fn send_w3gs(&mut self, pkt: W3GSPacket) -> Result<(), PlayerSendError> {
     let meta = self.enqueue_w3gs(pkt.clone());
     self.send(Frame::from_w3gs(meta, pkt))
@@ -118,12 +143,43 @@ impl PlayerDispatchInfo {
     let sid = self.w3gs_ack_q.gen_next_send_sid();
     let ack_sid = self.w3gs_ack_q.take_ack_received();
     let meta = W3GSMetadata::new(pkt.type_id(), sid, ack_sid.clone());
+    if let Some(sent) = self.replica_sent.as_mut() {
+      sent.push((meta.clone(), pkt.clone()));
+    }
     self.w3gs_ack_q.push_send(meta.clone(), pkt);
     meta
   }
 
+  /// Changes since the last call, the whole ack queue if `reset`
+  pub fn take_replica(&mut self, player_id: i32, reset: bool) -> PlayerReplica {
+    let sent = self.replica_sent.get_or_insert_with(Vec::new);
+    let sent = if reset {
+      sent.clear();
+      self
+        .w3gs_ack_q
+        .pending_ack_queue()
+        .iter()
+        .cloned()
+        .collect()
+    } else {
+      std::mem::replace(sent, vec![])
+    };
+    PlayerReplica {
+      player_id,
+      sent,
+      last_send_sid: self.w3gs_ack_q.last_send_sid(),
+      ack_sent: self.w3gs_ack_q.last_ack_sent(),
+      ack_received: self.w3gs_ack_q.last_ack_received(),
+    }
+  }
+
+  /// The standby node is not connected, the next replication is a reset
+  pub fn clear_replica(&mut self) {
+    self.replica_sent.take();
+  }
+
   pub fn send_private_message(&mut self, msg: &str) {
-    if self.stream_id().is_some() {
+    if self.stream_id().is_some() && !self.is_send_backlogged() {
       let payload = ChatFromHost::private_to_self(self.slot_player_id, format!("[FLO] {}", msg));
       let pkt = match W3GSPacket::simple(payload) {
         Ok(pkt) => pkt,
@@ -193,7 +249,7 @@ impl PlayerDispatchInfo {
   }
 
   pub fn pristine(&self) -> bool {
-    self.last_stream_id.is_none()
+    self.last_stream_id.is_none() && !self.resumed
   }
 
   pub fn delay(&self) -> Option<&Duration> {
@@ -0,0 +1,277 @@
+use flo_net::proto::flo_node::{GameResultMmdFlag, GameResultPlayer, PacketNodeGameResult};
+use flo_w3gs::actions::{Action, MMDMessage};
+use flo_w3gs::protocol::action::PlayerAction;
+use flo_w3gs::protocol::constants::LeaveReason;
+use std::collections::BTreeMap;
+use tokio::time::Instant;
+
+const MMD_FILENAME: &[u8] = b"MMD.Dat";
+/// RTT samples are counted in buckets of this size, the last bucket is open-ended
+const RTT_BUCKET_MS: u32 = 5;
+const RTT_BUCKETS: usize = 200;
+const RTT_PERCENTILE: u64 = 95;
+
+/// Collects per-player outcome information (leaves, W3MMD flags, desyncs, action counts,
+/// connection quality) reported to the controller when the game ends.
+#[derive(Debug)]
+pub struct GameResultCollector {
+  // W3MMD pid -> flo player id
+  mmd_pid_lookup: BTreeMap<u32, i32>,
+  slot_player_id_lookup: BTreeMap<u8, i32>,
+  players: BTreeMap<i32, PlayerResult>,
+}
+
+#[derive(Debug, Default)]
+struct PlayerResult {
+  leave_reason: Option<LeaveReason>,
+  left_at_ms: u32,
+  mmd_flag: Option<GameResultMmdFlag>,
+  desync: bool,
+  action_count: u32,
+  connection: ConnectionStats,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionStats {
+  rtt_count: u64,
+  rtt_total: u64,
+  rtt_buckets: Vec<u32>,
+  lag_started_at: Option<Instant>,
+  lag_caused_ms: u32,
+  disconnect_count: u32,
+}
+
+impl ConnectionStats {
+  fn rtt_avg_ms(&self) -> u32 {
+    if self.rtt_count == 0 {
+      return 0;
+    }
+    (self.rtt_total / self.rtt_count) as u32
+  }
+
+  // upper bound of the bucket the percentile falls in
+  fn rtt_percentile_ms(&self, percentile: u64) -> u32 {
+    let mut count = 0;
+    for (i, n) in self.rtt_buckets.iter().enumerate() {
+      count += *n as u64;
+      if count * 100 >= self.rtt_count * percentile {
+        return (i as u32 + 1) * RTT_BUCKET_MS;
+      }
+    }
+    0
+  }
+}
+
+impl GameResultCollector {
+  /// `players` yields `(player_id, slot_player_id)` pairs of non-observer players.
+  pub fn new<I>(players: I) -> Self
+  where
+    I: IntoIterator<Item = (i32, u8)>,
+  {
+    let mut mmd_pid_lookup = BTreeMap::new();
+    let mut slot_player_id_lookup = BTreeMap::new();
+    let mut map = BTreeMap::new();
+    for (player_id, slot_player_id) in players {
+      // W3MMD uses the 0-based player index
+      mmd_pid_lookup.insert(slot_player_id.saturating_sub(1) as u32, player_id);
+      slot_player_id_lookup.insert(slot_player_id, player_id);
+      map.insert(player_id, PlayerResult::default());
+    }
+    Self {
+      mmd_pid_lookup,
+      slot_player_id_lookup,
+      players: map,
+    }
+  }
+
+  pub fn record_action(&mut self, action: &PlayerAction) {
+    let mut count = 0;
+    for item in action.actions() {
+      match item {
+        Ok(Action::MMDMessage(msg)) => self.record_mmd_message(&msg),
+        Ok(_) => count += 1,
+        Err(_) => break,
+      }
+    }
+    if let Some(player) = self
+      .slot_player_id_lookup
+      .get(&action.player_id)
+      .and_then(|player_id| self.players.get_mut(player_id))
+    {
+      player.action_count += count;
+    }
+  }
+
+  fn record_mmd_message(&mut self, msg: &MMDMessage) {
+    if msg.name.as_bytes() != MMD_FILENAME {
+      return;
+    }
+    let value = msg.second_checksum.to_string_lossy();
+    if let Some((pid, flag)) = parse_mmd_flag(&value) {
+      if let Some(player) = self
+        .mmd_pid_lookup
+        .get(&pid)
+        .and_then(|player_id| self.players.get_mut(player_id))
+      {
+        player.mmd_flag.replace(flag);
+      }
+    }
+  }
+
+  pub fn record_leave(&mut self, player_id: i32, reason: Option<LeaveReason>, time_ms: u32) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      if player.leave_reason.is_none() {
+        player.leave_reason = Some(reason.unwrap_or(LeaveReason::LeaveDisconnect));
+        player.left_at_ms = time_ms;
+      }
+    }
+  }
+
+  pub fn record_desync(&mut self, player_id: i32) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      player.desync = true;
+    }
+  }
+
+  pub fn record_rtt(&mut self, player_id: i32, rtt_ms: u32) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      let stats = &mut player.connection;
+      if stats.rtt_buckets.is_empty() {
+        stats.rtt_buckets.resize(RTT_BUCKETS, 0);
+      }
+      let bucket = std::cmp::min((rtt_ms / RTT_BUCKET_MS) as usize, RTT_BUCKETS - 1);
+      stats.rtt_buckets[bucket] += 1;
+      stats.rtt_count += 1;
+      stats.rtt_total += rtt_ms as u64;
+    }
+  }
+
+  /// The player caused the lag screen to show
+  pub fn record_lag_start(&mut self, player_id: i32, at: Instant) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      if player.connection.lag_started_at.is_none() {
+        player.connection.lag_started_at = Some(at);
+      }
+    }
+  }
+
+  pub fn record_lag_end(&mut self, player_id: i32, at: Instant) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      let stats = &mut player.connection;
+      if let Some(started_at) = stats.lag_started_at.take() {
+        let ms = at.saturating_duration_since(started_at).as_millis() as u32;
+        stats.lag_caused_ms = stats.lag_caused_ms.saturating_add(ms);
+      }
+    }
+  }
+
+  pub fn record_disconnect(&mut self, player_id: i32) {
+    if let Some(player) = self.players.get_mut(&player_id) {
+      player.connection.disconnect_count += 1;
+    }
+  }
+
+  pub fn to_packet(&self, game_id: i32, duration_ms: u32) -> PacketNodeGameResult {
+    PacketNodeGameResult {
+      game_id,
+      duration_ms,
+      players: self
+        .players
+        .iter()
+        .map(|(player_id, player)| {
+          let mut item = GameResultPlayer {
+            player_id: *player_id,
+            leave_reason: player.leave_reason.map(u32::from),
+            left_at_ms: player.left_at_ms,
+            desync: player.desync,
+            action_count: player.action_count,
+            rtt_avg_ms: player.connection.rtt_avg_ms(),
+            rtt_p95_ms: player.connection.rtt_percentile_ms(RTT_PERCENTILE),
+            lag_caused_ms: player.connection.lag_caused_ms,
+            disconnect_count: player.connection.disconnect_count,
+            ..Default::default()
+          };
+          item.set_mmd_flag(player.mmd_flag.unwrap_or(GameResultMmdFlag::None));
+          item
+        })
+        .collect(),
+      ..Default::default()
+    }
+  }
+}
+
+// FlagP <pid> <flag>
+fn parse_mmd_flag(value: &str) -> Option<(u32, GameResultMmdFlag)> {
+  let mut parts = value.split_whitespace();
+  if parts.next()? != "FlagP" {
+    return None;
+  }
+  let pid = parts.next()?.parse().ok()?;
+  let flag = match parts.next()? {
+    "winner" => GameResultMmdFlag::Winner,
+    "loser" => GameResultMmdFlag::Loser,
+    "drawer" => GameResultMmdFlag::Drawer,
+    "leaver" => GameResultMmdFlag::Leaver,
+    "practicing" => GameResultMmdFlag::Practicing,
+    _ => return None,
+  };
+  Some((pid, flag))
+}
+
+#[test]
+fn test_parse_mmd_flag() {
+  assert_eq!(
+    parse_mmd_flag("FlagP 0 winner"),
+    Some((0, GameResultMmdFlag::Winner))
+  );
+  assert_eq!(
+    parse_mmd_flag("FlagP 3 loser"),
+    Some((3, GameResultMmdFlag::Loser))
+  );
+  assert_eq!(parse_mmd_flag("FlagP x winner"), None);
+  assert_eq!(parse_mmd_flag("VarP 0 kills = 1"), None);
+  assert_eq!(parse_mmd_flag("FlagP 0 unknown"), None);
+}
+
+#[test]
+fn test_record_leave_keeps_first() {
+  let mut c = GameResultCollector::new(vec![(10, 1), (20, 2)]);
+  c.record_leave(10, Some(LeaveReason::LeaveLost), 1000);
+  c.record_leave(10, None, 2000);
+  c.record_desync(20);
+  let pkt = c.to_packet(1, 3000);
+  assert_eq!(
+    pkt.players[0].leave_reason,
+    Some(u32::from(LeaveReason::LeaveLost))
+  );
+  assert_eq!(pkt.players[0].left_at_ms, 1000);
+  assert!(pkt.players[1].desync);
+}
+
+#[test]
+fn test_connection_stats() {
+  use std::time::Duration;
+
+  let mut c = GameResultCollector::new(vec![(10, 1), (20, 2)]);
+  for rtt in 1..=100 {
+    c.record_rtt(10, rtt);
+  }
+  c.record_rtt(10, 5000);
+
+  let now = Instant::now();
+  c.record_lag_start(20, now);
+  c.record_lag_start(20, now + Duration::from_secs(1));
+  c.record_lag_end(20, now + Duration::from_secs(3));
+  c.record_lag_end(20, now + Duration::from_secs(5));
+  c.record_disconnect(20);
+  c.record_disconnect(20);
+
+  let pkt = c.to_packet(1, 10000);
+  assert_eq!(pkt.players[0].rtt_avg_ms, 99);
+  assert_eq!(pkt.players[0].rtt_p95_ms, 100);
+  assert_eq!(pkt.players[0].lag_caused_ms, 0);
+  assert_eq!(pkt.players[1].rtt_avg_ms, 0);
+  assert_eq!(pkt.players[1].rtt_p95_ms, 0);
+  assert_eq!(pkt.players[1].lag_caused_ms, 3000);
+  assert_eq!(pkt.players[1].disconnect_count, 2);
+}
@@ -0,0 +1,87 @@
+use flo_w3gs::protocol::constants::LeaveReason;
+use flo_w3gs::protocol::leave::{LeaveAck, PlayerLeft};
+use flo_w3gs::protocol::packet::Packet;
+
+use crate::error::*;
+
+/// A player still in a game ended by the node
+#[derive(Debug, Clone, Copy)]
+pub struct ScorePlayer {
+  pub player_id: i32,
+  pub slot_player_id: u8,
+  pub observer: bool,
+}
+
+/// Packets that send `player_id` to the score screen: every other player leaves,
+/// as a winner if in `winner_player_ids`, as a loser otherwise, or with a draw if there are no winners,
+/// then the leave of `player_id` is acknowledged
+pub fn score_screen_packets(
+  player_id: i32,
+  players: &[ScorePlayer],
+  winner_player_ids: &[i32],
+) -> Result<Vec<Packet>> {
+  let mut packets = Vec::with_capacity(players.len());
+  for player in players.iter().filter(|p| p.player_id != player_id) {
+    let reason = if player.observer {
+      LeaveReason::LeaveObserver
+    } else if winner_player_ids.is_empty() {
+      LeaveReason::LeaveDraw
+    } else if winner_player_ids.contains(&player.player_id) {
+      LeaveReason::LeaveWon
+    } else {
+      LeaveReason::LeaveLost
+    };
+    packets.push(Packet::simple(PlayerLeft {
+      player_id: player.slot_player_id,
+      reason,
+    })?);
+  }
+  packets.push(Packet::simple(LeaveAck)?);
+  Ok(packets)
+}
+
+#[test]
+fn test_score_screen_packets() {
+  use flo_w3gs::protocol::constants::PacketTypeId;
+
+  let players = [
+    ScorePlayer {
+      player_id: 10,
+      slot_player_id: 1,
+      observer: false,
+    },
+    ScorePlayer {
+      player_id: 20,
+      slot_player_id: 2,
+      observer: false,
+    },
+    ScorePlayer {
+      player_id: 30,
+      slot_player_id: 3,
+      observer: true,
+    },
+  ];
+  let left = |packets: &[Packet]| -> Vec<(u8, LeaveReason)> {
+    packets
+      .iter()
+      .filter(|pkt| pkt.type_id() == PacketTypeId::PlayerLeft)
+      .map(|pkt| {
+        let left: PlayerLeft = pkt.decode_simple().unwrap();
+        (left.player_id, left.reason)
+      })
+      .collect()
+  };
+
+  let packets = score_screen_packets(20, &players, &[10]).unwrap();
+  assert_eq!(
+    left(&packets),
+    vec![(1, LeaveReason::LeaveWon), (3, LeaveReason::LeaveObserver)]
+  );
+  assert_eq!(packets.last().unwrap().type_id(), PacketTypeId::LeaveAck);
+
+  let packets = score_screen_packets(10, &players, &[]).unwrap();
+  assert_eq!(
+    left(&packets),
+    vec![(2, LeaveReason::LeaveDraw), (3, LeaveReason::LeaveObserver)]
+  );
+}
@@ -3,6 +3,7 @@ use flo_net::stream::FloStream;
 
 use crate::error::*;
 use once_cell::sync::Lazy;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::sync::mpsc::{error::TrySendError, Sender};
@@ -14,17 +15,20 @@ pub struct PlayerStream {
   player_id: i32,
   stream: FloStream,
   ct: CancellationToken,
+  peer_addr: Option<SocketAddr>,
 }
 
 impl PlayerStream {
   pub fn new(player_id: i32, stream: FloStream) -> Self {
     static ID_GEN: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::from(0));
 
+    let peer_addr = stream.peer_addr().ok();
     let stream = Self {
       id: ID_GEN.fetch_add(1, Ordering::Relaxed),
       player_id,
       stream,
       ct: CancellationToken::new(),
+      peer_addr,
     };
     stream
   }
@@ -37,6 +41,10 @@ impl PlayerStream {
     self.player_id
   }
 
+  pub fn peer_addr(&self) -> Option<SocketAddr> {
+    self.peer_addr
+  }
+
   pub fn get_mut(&mut self) -> &mut FloStream {
     &mut self.stream
   }
@@ -63,6 +71,7 @@ pub struct PlayerStreamHandle {
   stream_id: u64,
   tx: Sender<PlayerStreamCmd>,
   ct: CancellationToken,
+  peer_addr: Option<SocketAddr>,
 }
 
 impl PlayerStreamHandle {
@@ -71,6 +80,7 @@ impl PlayerStreamHandle {
       stream_id: stream.id(),
       tx,
       ct: stream.ct.clone(),
+      peer_addr: stream.peer_addr,
     }
   }
 
@@ -78,6 +88,16 @@ impl PlayerStreamHandle {
     self.stream_id
   }
 
+  /// Address the player connected from
+  pub fn peer_addr(&self) -> Option<SocketAddr> {
+    self.peer_addr
+  }
+
+  /// Number of commands waiting to be sent
+  pub fn queue_len(&self) -> usize {
+    self.tx.max_capacity() - self.tx.capacity()
+  }
+
   pub fn close(&self) {
     self.ct.cancel();
   }
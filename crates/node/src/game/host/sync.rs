@@ -1,7 +1,9 @@
 use slab::Slab;
+use smallvec::SmallVec;
 use std::collections::BTreeMap;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use thiserror::Error;
+use tokio::time::Instant;
 
 #[derive(Debug)]
 pub struct SyncMap {
@@ -35,6 +37,11 @@ impl SyncMap {
     self.tick
   }
 
+  /// Continues the game time of a game replicated from another node
+  pub fn resume_at(&mut self, time: u32) {
+    self.time = time;
+  }
+
   #[must_use]
   pub fn clock(&mut self, time_increment: u16) -> ClockResult {
     let timeout_players = self.check_timeout(time_increment);
@@ -148,7 +155,7 @@ impl SyncMap {
           agreed_checksum: pending
             .checksums
             .iter()
-            .find(|(k, _v)| self.desync_buf.iter().all(|v| v.player_id != **k))
+            .find(|(k, _v)| self.desync_buf.iter().all(|v| v.player_id != *k))
             .map(|t| t.1.clone()),
           desync,
         })
@@ -209,7 +216,7 @@ pub enum AckError {
 struct Pending {
   tick: u32,
   time: u32,
-  checksums: BTreeMap<i32, u32>,
+  checksums: TickChecksums,
   t: Instant,
 }
 
@@ -218,7 +225,7 @@ impl Pending {
     Self {
       tick,
       time,
-      checksums: BTreeMap::new(),
+      checksums: TickChecksums::default(),
       t: Instant::now(),
     }
   }
@@ -326,6 +333,46 @@ impl Pending {
 #[must_use]
 struct CheckDesyncToken;
 
+/// Checksums of a tick sorted by player id, created every tick,
+/// stored inline since a game has at most 24 players
+#[derive(Debug, Default)]
+struct TickChecksums(SmallVec<[(i32, u32); 24]>);
+
+impl TickChecksums {
+  fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  fn contains_key(&self, player_id: &i32) -> bool {
+    self.position(*player_id).is_ok()
+  }
+
+  fn insert(&mut self, player_id: i32, checksum: u32) {
+    match self.position(player_id) {
+      Ok(idx) => self.0[idx].1 = checksum,
+      Err(idx) => self.0.insert(idx, (player_id, checksum)),
+    }
+  }
+
+  fn remove(&mut self, player_id: &i32) {
+    if let Ok(idx) = self.position(*player_id) {
+      self.0.remove(idx);
+    }
+  }
+
+  fn iter(&self) -> impl Iterator<Item = &(i32, u32)> {
+    self.0.iter()
+  }
+
+  fn values(&self) -> impl Iterator<Item = &u32> {
+    self.0.iter().map(|(_, checksum)| checksum)
+  }
+
+  fn position(&self, player_id: i32) -> Result<usize, usize> {
+    self.0.binary_search_by_key(&player_id, |(id, _)| *id)
+  }
+}
+
 #[derive(Debug)]
 pub struct PlayerState {
   tick: u32,
@@ -457,3 +504,23 @@ fn test_sync_map() {
   assert!(map.pending_tick.is_empty());
   dbg!(&map.pending_slab.capacity());
 }
+
+#[test]
+fn test_tick_checksums() {
+  let mut checksums = TickChecksums::default();
+  checksums.insert(3, 30);
+  checksums.insert(1, 10);
+  checksums.insert(2, 20);
+  checksums.insert(1, 11);
+  assert_eq!(checksums.len(), 3);
+  assert_eq!(
+    checksums.iter().cloned().collect::<Vec<_>>(),
+    vec![(1, 11), (2, 20), (3, 30)]
+  );
+  checksums.remove(&2);
+  assert!(!checksums.contains_key(&2));
+  assert_eq!(
+    checksums.values().cloned().collect::<Vec<_>>(),
+    vec![11, 30]
+  );
+}
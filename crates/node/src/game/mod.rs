@@ -1,5 +1,7 @@
 use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::lock::Mutex;
 use futures::FutureExt;
@@ -13,15 +15,20 @@ use flo_net::proto::flo_node as proto;
 use flo_net::stream::FloStream;
 use flo_task::SpawnScope;
 pub use flo_types::node::*;
+pub use host::encode_action_tick;
 use host::stream::PlayerStreamHandle;
 pub use host::AckError;
 use host::GameHost;
+pub use host::{GameChatEntry, GameLogChunk, GameLogSubscription, GameResume};
 
+use crate::constants::GAME_IDLE_CHECK_INTERVAL;
 use crate::controller::ControllerServerHandle;
 use crate::error::*;
 use crate::observer::ObserverPublisherHandle;
+use crate::standby::StandbyHandle;
 use crate::state::event::GlobalEventSender;
-use crate::state::GlobalEvent;
+use crate::state::{GameKey, GlobalEvent};
+use flo_types::feature::{FeatureFlag, FeatureFlags};
 use flo_w3gs::constants::LeaveReason;
 
 use self::host::GameHostOptions;
@@ -50,29 +57,80 @@ pub struct GameSession {
 impl GameSession {
   pub fn new(
     game: proto::Game,
+    traceparent: &str,
     ctrl: ControllerServerHandle,
     obs: ObserverPublisherHandle,
     g_event_sender: GlobalEventSender,
+    standby: StandbyHandle,
+  ) -> Result<Self> {
+    Self::create(game, traceparent, ctrl, obs, g_event_sender, standby, None)
+  }
+
+  /// Continues a started game replicated from the primary node
+  pub fn resume(
+    game: proto::Game,
+    traceparent: &str,
+    ctrl: ControllerServerHandle,
+    obs: ObserverPublisherHandle,
+    g_event_sender: GlobalEventSender,
+    resume: GameResume,
+  ) -> Result<Self> {
+    Self::create(
+      game,
+      traceparent,
+      ctrl,
+      obs,
+      g_event_sender,
+      StandbyHandle::disabled(),
+      Some(resume),
+    )
+  }
+
+  fn create(
+    game: proto::Game,
+    traceparent: &str,
+    ctrl: ControllerServerHandle,
+    obs: ObserverPublisherHandle,
+    g_event_sender: GlobalEventSender,
+    standby: StandbyHandle,
+    resume: Option<GameResume>,
   ) -> Result<Self> {
     let scope = SpawnScope::new();
     let game_id = game.id;
+    let span = tracing::info_span!("game_session", game_id);
+    flo_log::trace::set_parent(&span, traceparent);
     let (tx, mut rx) = GameEvent::channel(32);
+    let observer_game = make_observer_game_info(&game);
+    let feature_flags = FeatureFlags::from_bits(game.feature_flags);
     let slots: Vec<_> = Vec::<GameSlot>::unpack(game.slots)?
       .into_iter()
       .filter_map(PlayerSlot::from_game_slot)
       .collect();
+    let resumed_left_player_ids = resume.as_ref().map(|resume| resume.left_player_ids.clone());
 
     let mut scope_handle = scope.handle();
-    let state = Arc::new(Mutex::new(State {
+    let mut idle_scope_handle = scope.handle();
+    let mut state = State {
       game_id,
       g_event_sender,
       host: GameHost::new(
         game_id,
         GameHostOptions {
-          enabled_ping_equalizer: game.enable_ping_equalizer,
+          enabled_ping_equalizer: game.enable_ping_equalizer
+            || feature_flags.contains(FeatureFlag::PingEqualizer),
+          speed_percent: if game.speed_percent > 0 {
+            game.speed_percent as u16
+          } else {
+            100
+          },
+          chat_rules: game.chat_rules.clone(),
+          feature_flags,
+          span: span.clone(),
+          standby,
+          resume,
         },
         &slots,
-        obs.clone(),
+        obs,
         tx.clone(),
       ),
       status: NodeGameStatus::Created,
@@ -82,8 +140,24 @@ impl GameSession {
         .collect(),
       tx,
       ctrl,
-      obs,
-    }));
+      observer_game,
+      span: span.clone(),
+      loading_since: None,
+    };
+
+    // the players reconnect to this node
+    if let Some(left_player_ids) = resumed_left_player_ids {
+      state.status = NodeGameStatus::Running;
+      for slot in state.player_slots.values_mut() {
+        slot.client_status = if left_player_ids.contains(&slot.player.player_id) {
+          SlotClientStatus::Left
+        } else {
+          SlotClientStatus::Disconnected
+        };
+      }
+      state.host.start();
+    }
+    let state = Arc::new(Mutex::new(state));
 
     let sess = Self {
       _scope: scope,
@@ -112,7 +186,31 @@ impl GameSession {
         }
         tracing::debug!("exiting");
       }
-        .instrument(tracing::debug_span!("event_worker", game_id))
+        .instrument(tracing::debug_span!(parent: &span, "event_worker", game_id))
+    });
+
+    tokio::spawn({
+      let handle = sess.handle();
+      async move {
+        let mut interval = tokio::time::interval(GAME_IDLE_CHECK_INTERVAL);
+        loop {
+          tokio::select! {
+            _ = idle_scope_handle.left() => {
+              break;
+            }
+            _ = interval.tick() => {
+              match handle.check_idle_players().await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                  tracing::error!("check idle players: {}", err);
+                }
+              }
+            }
+          }
+        }
+      }
+      .instrument(tracing::debug_span!(parent: &span, "idle_check", game_id))
     });
 
     Ok(sess)
@@ -131,17 +229,21 @@ impl GameSession {
       }
       GameEvent::GameStatusChange(status) => {
         let mut guard = handle.0.lock().await;
-        let game_id = guard.game_id;
+        let key = guard.game_key();
         guard.status = status;
         guard.broadcast_status_update(StatusUpdate::Full).await?;
         match status {
           NodeGameStatus::Running => {
+            guard.observer_game.start_time_millis = std::time::SystemTime::now()
+              .duration_since(std::time::UNIX_EPOCH)
+              .map(|d| d.as_millis() as i64)
+              .unwrap_or_default();
             guard.host.start();
           }
           NodeGameStatus::Ended => {
             guard
               .g_event_sender
-              .send(GlobalEvent::GameEnded(game_id))
+              .send(GlobalEvent::GameEnded(key))
               .await
               .ok();
           }
@@ -164,6 +266,7 @@ impl GameSessionHandle {
   ) -> Result<(), (Option<FloStream>, Error)> {
     use host::stream::PlayerStream;
 
+    let peer_addr = stream.peer_addr().ok();
     let mut guard = self.0.lock().await;
     {
       let slot = if let Some(v) = guard.player_slots.get_mut(&player_id) {
@@ -172,15 +275,39 @@ impl GameSessionHandle {
         return Err((stream.into(), Error::PlayerNotFoundInGame));
       };
 
-      if slot.sender.is_some() {
-        return Err((stream.into(), Error::PlayerConnectionExists));
-      }
-
-      match slot.client_status {
-        SlotClientStatus::Pending
-        | SlotClientStatus::Connected
-        | SlotClientStatus::Disconnected => {}
-        other => {
+      match (slot.client_status, slot.sender.as_ref()) {
+        (
+          SlotClientStatus::Pending | SlotClientStatus::Connected | SlotClientStatus::Disconnected,
+          _,
+        ) => {}
+        // the token is valid, the player switched networks and the old stream is dead
+        (
+          SlotClientStatus::Joined | SlotClientStatus::Loading | SlotClientStatus::Loaded,
+          Some(sender),
+        ) if address_changed(sender.peer_addr(), peer_addr) => {
+          tracing::warn!(
+            player_id,
+            stream_id = sender.stream_id(),
+            old_addr = ?sender.peer_addr(),
+            new_addr = ?peer_addr,
+            "player address changed, replacing old stream"
+          );
+          crate::metrics::PLAYER_ADDRESS_CHANGES.inc();
+        }
+        // the old stream is a ghost session if the client connects again,
+        // the dispatcher closes it and the new stream takes over
+        (SlotClientStatus::Loaded, Some(sender)) => {
+          tracing::warn!(
+            player_id,
+            stream_id = sender.stream_id(),
+            "player connected again, replacing old stream"
+          );
+        }
+        // the game can't be reconnected while it's loading
+        (_, Some(_)) => {
+          return Err((stream.into(), Error::PlayerConnectionExists));
+        }
+        (other, None) => {
           return Err((stream.into(), Error::InvalidPlayerSlotClientStatus(other)));
         }
       };
@@ -200,6 +327,23 @@ impl GameSessionHandle {
     Ok(())
   }
 
+  /// Game info and recorded game log for observers connected to the node
+  pub async fn subscribe_observer(
+    &self,
+  ) -> Result<(flo_net::observer::GameInfo, GameLogSubscription)> {
+    let guard = self.0.lock().await;
+    match guard.status {
+      NodeGameStatus::Running | NodeGameStatus::Ended => {}
+      _ => return Err(Error::GameNotStarted),
+    }
+    let subscription = guard
+      .host
+      .game_log()
+      .subscribe()
+      .ok_or(Error::GameLogTruncated)?;
+    Ok((guard.observer_game.clone(), subscription))
+  }
+
   pub async fn retry_shutdown(
     &self,
     player_id: i32,
@@ -218,6 +362,88 @@ impl GameSessionHandle {
     Ok(())
   }
 
+  /// Ends the game without reporting a result, the controller voids it
+  pub async fn abort(&self, reason: &str) -> Result<()> {
+    let mut guard = self.0.lock().await;
+    if guard.status == NodeGameStatus::Ended {
+      return Err(Error::GameEnded);
+    }
+    guard.end_aborted(reason).await
+  }
+
+  /// Warns and then removes the players that don't finish loading in time,
+  /// returns false once the game is past loading
+  async fn check_idle_players(&self) -> Result<bool> {
+    let settings = crate::settings::current();
+    let mut removed = vec![];
+    {
+      let mut guard = self.0.lock().await;
+      let loading_since = match (guard.status, guard.loading_since) {
+        (NodeGameStatus::Loading, Some(v)) => v,
+        (NodeGameStatus::Created | NodeGameStatus::Waiting, _) => return Ok(true),
+        _ => return Ok(false),
+      };
+      let elapsed = loading_since.elapsed();
+      let game_id = guard.game_id;
+
+      for slot in guard.player_slots.values_mut() {
+        if !matches!(
+          slot.client_status,
+          SlotClientStatus::Connected | SlotClientStatus::Joined | SlotClientStatus::Loading
+        ) {
+          continue;
+        }
+        let player_id = slot.player.player_id;
+        match idle_action(
+          elapsed,
+          slot.idle_warned,
+          settings.load_timeout,
+          settings.idle_warning,
+        ) {
+          IdleAction::None => {}
+          IdleAction::Warn { kick_in } => {
+            tracing::info!(player_id, "player hasn't loaded, kick in {:?}", kick_in);
+            slot.idle_warned = true;
+            if let Some(sender) = slot.sender.as_mut() {
+              let frame = proto::PacketClientIdleWarning {
+                game_id,
+                player_id,
+                kick_in_secs: kick_in.as_secs() as u32,
+              }
+              .encode_as_frame()?;
+              sender.send(frame).await.ok();
+            }
+          }
+          IdleAction::Kick => {
+            removed.push((player_id, slot.sender.is_some()));
+          }
+        }
+      }
+
+      for (player_id, connected) in &removed {
+        tracing::warn!(player_id, "player didn't load in time, removing from game");
+        crate::metrics::IDLE_PLAYERS_REMOVED.inc();
+        if *connected {
+          guard
+            .host
+            .notify_player_shutdown(*player_id, Some(LeaveReason::LeaveDisconnect))
+            .await?;
+        }
+      }
+    }
+
+    for (player_id, _) in removed {
+      self
+        .update_player_client_status(
+          SlotClientStatusUpdateSource::Node,
+          player_id,
+          SlotClientStatus::Left,
+        )
+        .await?;
+    }
+    Ok(true)
+  }
+
   pub async fn update_player_client_status(
     &self,
     source: SlotClientStatusUpdateSource,
@@ -277,33 +503,34 @@ impl GameSessionHandle {
       return Ok(());
     }
 
-    if source == SlotClientStatusUpdateSource::Client {
-      if next_status == SlotClientStatus::Disconnected {
-        tracing::error!(
-          player_id,
-          "peer cannot send disconnected if it is disconnected"
-        );
-        return Err(Error::InvalidClientStatusTransition(
-          slot.client_status,
-          next_status,
-        ));
-      }
+    // a stream replaced before the game started, the player is still in the lobby
+    if source == SlotClientStatusUpdateSource::Node
+      && next_status == SlotClientStatus::Connected
+      && matches!(
+        slot.client_status,
+        SlotClientStatus::Joined | SlotClientStatus::Loading
+      )
+    {
+      return Ok(());
+    }
 
-      match (slot.client_status, next_status) {
-        // player joined lan game
-        (SlotClientStatus::Connected, SlotClientStatus::Joined) => {}
-        // player left the lan game
-        (SlotClientStatus::Joined, SlotClientStatus::Connected) => {}
-        (SlotClientStatus::Joined, SlotClientStatus::Loading) => {}
-        (SlotClientStatus::Loading, SlotClientStatus::Loaded) => {}
-        (_, SlotClientStatus::Left) => {}
-        _ => {
-          return Err(Error::InvalidClientStatusTransition(
-            slot.client_status,
-            next_status,
-          ));
-        }
-      }
+    let valid = if source == SlotClientStatusUpdateSource::Client {
+      slot.client_status.client_can_transition_to(next_status)
+    } else {
+      slot.client_status.can_transition_to(next_status)
+    };
+    if !valid {
+      tracing::error!(
+        player_id,
+        "invalid client status transition: {:?}: {:?} => {:?}",
+        source,
+        slot.client_status,
+        next_status
+      );
+      return Err(Error::InvalidClientStatusTransition(
+        slot.client_status,
+        next_status,
+      ));
     }
 
     slot.client_status = next_status;
@@ -323,6 +550,14 @@ impl GameSessionHandle {
           }
         }
       }
+      SlotClientStatus::LoadFailed => {
+        if load_failed_aborts_game(guard.status) {
+          tracing::warn!(player_id, "game client exited while loading, abort game");
+          guard.end_aborted("a player failed to load").await?;
+        } else {
+          guard.check_game_end().await;
+        }
+      }
       SlotClientStatus::Pending => {}
       SlotClientStatus::Connected => {
         if guard.status == NodeGameStatus::Created {
@@ -393,6 +628,83 @@ pub enum SlotClientStatusUpdateSource {
   Client,
 }
 
+#[derive(Debug, PartialEq)]
+enum IdleAction {
+  None,
+  Warn { kick_in: Duration },
+  Kick,
+}
+
+/// What to do with a player that hasn't loaded `elapsed` after the game started loading
+fn idle_action(
+  elapsed: Duration,
+  warned: bool,
+  load_timeout: Duration,
+  idle_warning: Duration,
+) -> IdleAction {
+  if elapsed >= load_timeout {
+    return IdleAction::Kick;
+  }
+  let kick_in = load_timeout - elapsed;
+  if !warned && kick_in <= idle_warning {
+    IdleAction::Warn { kick_in }
+  } else {
+    IdleAction::None
+  }
+}
+
+fn address_changed(old: Option<SocketAddr>, new: Option<SocketAddr>) -> bool {
+  match (old, new) {
+    (Some(old), Some(new)) => old.ip() != new.ip(),
+    _ => false,
+  }
+}
+
+// In-game slots are restored from the slot ids, unoccupied slots are closed
+fn make_observer_game_info(game: &proto::Game) -> flo_net::observer::GameInfo {
+  use flo_net::observer::{GameInfo, Map, PlayerInfo, Slot};
+  use flo_net::proto::flo_common::{SlotSettings, SlotStatus};
+
+  let settings = game.settings.clone().unwrap_or_default();
+  let slot_count = if settings.map_twelve_p { 24 } else { 12 };
+  let mut slots: Vec<Slot> = (0..slot_count)
+    .map(|_| Slot {
+      player: None,
+      settings: Some({
+        let mut settings = SlotSettings::default();
+        settings.set_status(SlotStatus::Closed);
+        settings
+      }),
+    })
+    .collect();
+  for slot in &game.slots {
+    if let Some(target) = slots.get_mut(slot.id as usize) {
+      *target = Slot {
+        player: slot.player.as_ref().map(|player| PlayerInfo {
+          id: player.player_id,
+          name: player.name.clone(),
+        }),
+        settings: slot.settings.clone(),
+      };
+    }
+  }
+
+  GameInfo {
+    id: game.id,
+    name: game.name.clone(),
+    map: Some(Map {
+      sha1: settings.map_sha1,
+      checksum: settings.map_checksum,
+      path: settings.map_path,
+      twelve_p: settings.map_twelve_p,
+    }),
+    slots,
+    random_seed: game.random_seed,
+    game_version: game.game_version.clone(),
+    start_time_millis: 0,
+  }
+}
+
 #[derive(Debug)]
 struct State {
   game_id: i32,
@@ -402,7 +714,9 @@ struct State {
   player_slots: BTreeMap<i32, PlayerSlot>,
   ctrl: ControllerServerHandle,
   tx: GameEventSender,
-  obs: ObserverPublisherHandle,
+  observer_game: flo_net::observer::GameInfo,
+  span: tracing::Span,
+  loading_since: Option<Instant>,
 }
 
 impl State {
@@ -510,6 +824,8 @@ pub struct PlayerSlot {
   pub player: GamePlayer,
   pub client_status: SlotClientStatus,
   pub sender: Option<PlayerStreamHandle>,
+  /// Sent a warning for not loading
+  pub idle_warned: bool,
 }
 
 impl PlayerSlot {
@@ -521,23 +837,66 @@ impl PlayerSlot {
       player,
       client_status: slot.client_status,
       sender: None,
+      idle_warned: false,
     })
   }
 }
 
 impl State {
+  fn game_key(&self) -> GameKey {
+    GameKey {
+      controller_id: self.ctrl.controller_id(),
+      game_id: self.game_id,
+    }
+  }
+
+  /// Ends the game without reporting a result, the controller voids it
+  async fn end_aborted(&mut self, reason: &str) -> Result<()> {
+    tracing::info!(game_id = self.game_id, "abort game: {}", reason);
+
+    self
+      .host
+      .broadcast_message(format!("Game aborted: {}", reason));
+    if self.status == NodeGameStatus::Running {
+      if let Err(err) = self.host.send_score_screen(&[]) {
+        tracing::error!(game_id = self.game_id, "send score screen: {}", err);
+      }
+    }
+    self.status = NodeGameStatus::Ended;
+    self.host.push_game_end();
+    self.send_game_log().await;
+    self
+      .tx
+      .send(GameEvent::GameStatusChange(NodeGameStatus::Ended))
+      .await
+      .map_err(|_| Error::Cancelled)?;
+    Ok(())
+  }
+
   async fn check_game_end(&mut self) -> bool {
     if self.player_slots.values().all(|slot| {
       (slot.client_status == SlotClientStatus::Left
-        || slot.client_status == SlotClientStatus::Disconnected)
+        || slot.client_status == SlotClientStatus::Disconnected
+        || slot.client_status == SlotClientStatus::LoadFailed)
         || slot.settings.team == 24
     }) {
       self.status = NodeGameStatus::Ended;
       tracing::debug!("all player left, end game");
-      self.obs.push_game_end(self.game_id);
+      self.host.push_game_end();
+      let mut result = self.host.game_result();
+      result.traceparent = flo_log::trace::traceparent(&self.span);
+      match result.encode_as_frame() {
+        Ok(frame) => {
+          self.ctrl.send(frame).await.ok();
+        }
+        Err(err) => {
+          tracing::error!("encode game result: {}", err);
+        }
+      }
+      self.send_game_log().await;
       self
         .g_event_sender
-        .send(GlobalEvent::GameEnded(self.game_id))
+        .send(GlobalEvent::GameEnded(self.game_key()))
         .await
         .ok();
       true
@@ -546,6 +905,33 @@ impl State {
     }
   }
 
+  async fn send_game_log(&mut self) {
+    let chunks = match self.host.game_log().chunks() {
+      Some(chunks) => chunks,
+      None => return,
+    };
+    let n = chunks.len();
+    for (i, data) in chunks.into_iter().enumerate() {
+      let pkt = proto::PacketNodeGameLog {
+        game_id: self.game_id,
+        data: data.to_vec(),
+        last: i + 1 == n,
+      };
+      match pkt.encode_as_frame() {
+        Ok(frame) => {
+          if self.ctrl.send(frame).await.is_err() {
+            tracing::error!("send game log: controller disconnected");
+            return;
+          }
+        }
+        Err(err) => {
+          tracing::error!("encode game log: {}", err);
+          return;
+        }
+      }
+    }
+  }
+
   async fn check_game_all_joined(&mut self) {
     if self
       .player_slots
@@ -554,6 +940,7 @@ impl State {
     {
       tracing::debug!("all joined");
       self.status = NodeGameStatus::Loading;
+      self.loading_since = Some(Instant::now());
     }
   }
 
@@ -652,3 +1039,61 @@ impl<'a> From<&'a State> for NodeGameStatusSnapshot {
 pub enum PlayerBanType {
   Chat = 0,
 }
+
+/// The game can't start without a player whose game client exited during the load screen,
+/// it's ended so the lobby can be hosted again instead of waiting for the load timeout
+fn load_failed_aborts_game(status: NodeGameStatus) -> bool {
+  status == NodeGameStatus::Loading
+}
+
+#[test]
+fn test_load_failed() {
+  use SlotClientStatus::*;
+
+  assert!(load_failed_aborts_game(NodeGameStatus::Loading));
+  // the remaining players keep playing
+  assert!(!load_failed_aborts_game(NodeGameStatus::Running));
+  assert!(!load_failed_aborts_game(NodeGameStatus::Ended));
+
+  // reported by the client from the lobby or the load screen
+  assert!(Joined.client_can_transition_to(LoadFailed));
+  assert!(Loading.client_can_transition_to(LoadFailed));
+  assert!(!Loaded.client_can_transition_to(LoadFailed));
+  assert!(!Connected.client_can_transition_to(LoadFailed));
+  // final unless the stream drops or the player leaves
+  assert!(!LoadFailed.client_can_transition_to(Loaded));
+  assert!(!LoadFailed.client_can_transition_to(Connected));
+  assert!(LoadFailed.can_transition_to(Disconnected));
+  assert!(LoadFailed.can_transition_to(Left));
+}
+
+#[test]
+fn test_address_changed() {
+  let addr = |s: &str| Some(s.parse::<SocketAddr>().unwrap());
+  assert!(address_changed(
+    addr("10.0.0.1:5000"),
+    addr("10.0.0.2:5000")
+  ));
+  // a new port from the same network
+  assert!(!address_changed(
+    addr("10.0.0.1:5000"),
+    addr("10.0.0.1:6000")
+  ));
+  assert!(!address_changed(None, addr("10.0.0.1:5000")));
+}
+
+#[test]
+fn test_idle_action() {
+  let secs = Duration::from_secs;
+  let action = |elapsed, warned| idle_action(secs(elapsed), warned, secs(300), secs(60));
+  assert_eq!(action(0, false), IdleAction::None);
+  assert_eq!(action(239, false), IdleAction::None);
+  assert_eq!(action(250, false), IdleAction::Warn { kick_in: secs(50) });
+  assert_eq!(action(250, true), IdleAction::None);
+  assert_eq!(action(300, true), IdleAction::Kick);
+  // warned by the next check if the warning is longer than the timeout
+  assert_eq!(
+    idle_action(secs(0), false, secs(60), secs(90)),
+    IdleAction::Warn { kick_in: secs(60) }
+  );
+}
@@ -4,13 +4,21 @@ mod echo;
 mod env;
 mod game;
 mod metrics;
+mod settings;
+mod standby;
 mod state;
 mod version;
 
+#[cfg(feature = "chaos")]
+pub mod chaos;
 mod constants;
 pub mod error;
 mod observer;
 
+/// Used by the golden capture tests in `flo-testlab`
+pub use game::encode_action_tick;
+pub use settings::{reload as reload_settings, ReloadReport};
+
 use error::Result;
 
 use flo_event::*;
@@ -18,27 +26,28 @@ use flo_event::*;
 use self::client::serve_client;
 use self::echo::serve_echo;
 use self::metrics::serve_metrics;
+use self::observer::{serve_observer, serve_observer_http};
+use self::standby::StandbyServer;
 use crate::state::GlobalState;
 use state::event::{handle_global_events, FloNodeEventContext, GlobalEvent};
 
 pub async fn serve() -> Result<()> {
+  settings::init()?;
+
   let (event_sender, event_receiver) = GlobalEvent::channel(30);
   let state = GlobalState::new(event_sender).into_ref();
   let mut ctrl = controller::ControllerServer::new(state.clone());
-  let ctrl_handle = ctrl.handle();
+  state.replicas().set_controllers(ctrl.handles());
 
   tokio::try_join!(
     ctrl.serve(),
     serve_client(state.clone()),
+    serve_observer(state.clone()),
+    serve_observer_http(state.clone()),
     serve_metrics(),
     serve_echo(),
-    handle_global_events(
-      FloNodeEventContext {
-        state,
-        ctrl: ctrl_handle,
-      },
-      event_receiver
-    )
+    StandbyServer::new(state.clone()).serve(),
+    handle_global_events(FloNodeEventContext { state }, event_receiver)
   )
   .map(|_| ())
 }
@@ -1,11 +1,24 @@
 use once_cell::sync::Lazy;
-use prometheus::{register_int_gauge, Encoder, IntGauge, TextEncoder};
+use prometheus::{
+  exponential_buckets, register_histogram, register_int_counter, register_int_gauge,
+  register_int_gauge_vec, Encoder, Histogram, IntCounter, IntGauge, IntGaugeVec, TextEncoder,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use crate::error::*;
 use hyper::header::CONTENT_TYPE;
 
 pub static GAME_SESSIONS: Lazy<IntGauge> =
   Lazy::new(|| register_int_gauge!("flonode_game_sessions", "Number of game sessions").unwrap());
+pub static CONTROLLER_GAME_SESSIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+  register_int_gauge_vec!(
+    "flonode_controller_game_sessions",
+    "Number of game sessions of each controller",
+    &["controller"]
+  )
+  .unwrap()
+});
 pub static PLAYERS_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
   register_int_gauge!(
     "flonode_player_connections",
@@ -21,6 +34,138 @@ pub static PLAYER_TOKENS: Lazy<IntGauge> = Lazy::new(|| {
   .unwrap()
 });
 
+pub static PLAYER_RECONNECTS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_player_reconnects_total",
+    "Number of player reconnects"
+  )
+  .unwrap()
+});
+
+pub static PLAYER_SESSION_TAKEOVERS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_player_session_takeovers_total",
+    "Number of player streams replaced by a new connection"
+  )
+  .unwrap()
+});
+pub static PLAYER_ADDRESS_CHANGES: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_player_address_changes_total",
+    "Number of player streams replaced by a connection from another address"
+  )
+  .unwrap()
+});
+pub static STANDBY_HANDOVERS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_standby_handovers_total",
+    "Number of games taken over from the primary node"
+  )
+  .unwrap()
+});
+pub static IDLE_PLAYERS_REMOVED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_idle_players_removed_total",
+    "Number of players removed from games for not loading in time"
+  )
+  .unwrap()
+});
+pub static DECODE_ERRORS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_decode_errors_total",
+    "Number of players removed for sending malformed packets"
+  )
+  .unwrap()
+});
+pub static GAME_STALLS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_game_stalls_total",
+    "Number of games ended by the stall watchdog"
+  )
+  .unwrap()
+});
+pub static OBSERVER_DROPPED_CHUNKS: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_observer_dropped_chunks_total",
+    "Number of game log chunks dropped for slow delayed observers"
+  )
+  .unwrap()
+});
+pub static TICK_DELAY: Lazy<Histogram> = Lazy::new(|| {
+  register_histogram!(
+    "flonode_tick_delay_seconds",
+    "Delay of action ticks behind their schedule",
+    exponential_buckets(0.001, 2.0, 10).unwrap()
+  )
+  .unwrap()
+});
+pub static TICK_DISPATCH: Lazy<Histogram> = Lazy::new(|| {
+  register_histogram!(
+    "flonode_tick_dispatch_seconds",
+    "Time spent dispatching an action tick",
+    exponential_buckets(0.0001, 2.0, 10).unwrap()
+  )
+  .unwrap()
+});
+static GAME_TICK_DELAY: Lazy<IntGaugeVec> = Lazy::new(|| {
+  register_int_gauge_vec!(
+    "flonode_game_tick_delay_ms",
+    "Delay of the last action tick of a game",
+    &["game_id"]
+  )
+  .unwrap()
+});
+
+/// Games with per-game series at the same time,
+/// further games are only counted in the node-wide metrics
+const MAX_GAME_LABELS: usize = 64;
+static GAME_LABELS: AtomicUsize = AtomicUsize::new(0);
+
+/// Per-game series of a running game, removed on drop
+#[derive(Debug)]
+pub struct GameMetrics {
+  game_id: Option<String>,
+}
+
+impl GameMetrics {
+  pub fn new(game_id: i32) -> Self {
+    let labeled = GAME_LABELS
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+        if n < MAX_GAME_LABELS {
+          Some(n + 1)
+        } else {
+          None
+        }
+      })
+      .is_ok();
+    Self {
+      game_id: if labeled {
+        Some(game_id.to_string())
+      } else {
+        None
+      },
+    }
+  }
+
+  pub fn observe_tick_delay(&self, delay: Duration) {
+    TICK_DELAY.observe(delay.as_secs_f64());
+    if let Some(game_id) = self.game_id.as_ref() {
+      GAME_TICK_DELAY
+        .with_label_values(&[game_id])
+        .set(delay.as_millis() as i64);
+    }
+  }
+}
+
+impl Drop for GameMetrics {
+  fn drop(&mut self) {
+    if let Some(game_id) = self.game_id.take() {
+      GAME_TICK_DELAY.remove_label_values(&[&game_id]).ok();
+      GAME_LABELS.fetch_sub(1, Ordering::SeqCst);
+    }
+  }
+}
+
 pub async fn serve_metrics() -> Result<()> {
   use bytes::Bytes;
   use http_body_util::Full;
@@ -76,3 +221,16 @@ pub async fn serve_metrics() -> Result<()> {
     });
   }
 }
+
+#[test]
+fn test_game_metrics_label_limit() {
+  let labeled: Vec<_> = (0..MAX_GAME_LABELS as i32).map(GameMetrics::new).collect();
+  assert!(labeled.iter().all(|m| m.game_id.is_some()));
+  let unlabeled = GameMetrics::new(-1);
+  assert!(unlabeled.game_id.is_none());
+  drop(unlabeled);
+  assert_eq!(GAME_LABELS.load(Ordering::SeqCst), MAX_GAME_LABELS);
+  drop(labeled);
+  assert_eq!(GAME_LABELS.load(Ordering::SeqCst), 0);
+  assert!(GameMetrics::new(1).game_id.is_some());
+}
@@ -0,0 +1,446 @@
+//! Serves the game log over HTTP for browser-based viewers.
+//! `GET /games/{game_id}/records?token={token}&offset={offset}` responds with a chunked body
+//! of encoded records, sent as they pass the delay point.
+//! `offset` is a byte offset into the log, a viewer can resume a broken download
+//! by passing the number of bytes it already received.
+//! `GET /openapi.json` describes the endpoint.
+
+use bytes::{Bytes, BytesMut};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{
+  HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, CACHE_CONTROL,
+  CONTENT_TYPE, ORIGIN, RETRY_AFTER,
+};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::time::{sleep_until, Instant};
+use utoipa::{OpenApi, ToSchema};
+
+use flo_constants::{CLIENT_ORIGINS, NODE_OBSERVER_HTTP_PORT};
+
+use crate::constants::GAME_LOG_CHUNK_SIZE;
+use crate::error::*;
+use crate::game::{GameLogChunk, GameLogSubscription};
+use crate::state::{ControllerId, GameKey, GlobalStateRef};
+
+const HEADER_OFFSET: &str = "x-flo-offset";
+const HEADER_DELAY_SECS: &str = "x-flo-delay-secs";
+const OPENAPI_PATH: &str = "/openapi.json";
+
+type Body = UnsyncBoxBody<Bytes, Error>;
+
+#[derive(OpenApi)]
+#[openapi(paths(serve_records, serve_openapi), components(schemas(GameLog)))]
+struct ApiDoc;
+
+/// Encoded game log records
+#[derive(ToSchema)]
+#[schema(value_type = String, format = Binary)]
+#[allow(dead_code)]
+struct GameLog(Vec<u8>);
+
+pub async fn serve_observer_http(state: GlobalStateRef) -> Result<()> {
+  if std::env::var("JWT_SECRET_BASE64").is_err() {
+    tracing::warn!("JWT_SECRET_BASE64 not set, observer http disabled");
+    return Ok(());
+  }
+
+  let addr = SocketAddr::from(SocketAddrV4::new(
+    Ipv4Addr::UNSPECIFIED,
+    NODE_OBSERVER_HTTP_PORT,
+  ));
+
+  let listener = tokio::net::TcpListener::bind(&addr).await?;
+
+  loop {
+    let (stream, _) = listener.accept().await?;
+
+    let io = TokioIo::new(stream);
+    let state = state.clone();
+
+    tokio::spawn(async move {
+      let service = service_fn(move |req| serve_req(state.clone(), req));
+      if let Err(err) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+        .serve_connection(io, service)
+        .await
+      {
+        tracing::debug!("observer http: {}", err);
+      }
+    });
+  }
+}
+
+async fn serve_req(
+  state: GlobalStateRef,
+  req: Request<Incoming>,
+) -> Result<Response<Body>, hyper::Error> {
+  let origin = req
+    .headers()
+    .get(ORIGIN)
+    .filter(|v| {
+      v.to_str()
+        .map(|v| CLIENT_ORIGINS.contains(&v))
+        .unwrap_or_default()
+    })
+    .cloned();
+
+  let mut res = match parse_request(&req) {
+    _ if req.uri().path() == OPENAPI_PATH => match *req.method() {
+      Method::GET => serve_openapi(),
+      _ => empty(StatusCode::METHOD_NOT_ALLOWED),
+    },
+    Some(params) if req.method() == Method::GET => serve_records(&state, params).await,
+    Some(_) => empty(StatusCode::METHOD_NOT_ALLOWED),
+    None => empty(StatusCode::NOT_FOUND),
+  };
+
+  if let Some(origin) = origin {
+    let headers = res.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.insert(
+      ACCESS_CONTROL_EXPOSE_HEADERS,
+      HeaderValue::from_static("x-flo-offset, x-flo-delay-secs, retry-after"),
+    );
+  }
+
+  Ok(res)
+}
+
+#[derive(Debug, PartialEq)]
+struct RecordsParams {
+  game_id: i32,
+  token: String,
+  offset: usize,
+}
+
+fn parse_request<B>(req: &Request<B>) -> Option<RecordsParams> {
+  let mut segments = req.uri().path().trim_matches('/').split('/');
+  let game_id = match (segments.next(), segments.next(), segments.next()) {
+    (Some("games"), Some(id), Some("records")) => id.parse().ok()?,
+    _ => return None,
+  };
+  if segments.next().is_some() {
+    return None;
+  }
+  let mut token = None;
+  let mut offset = 0;
+  for pair in req.uri().query().unwrap_or_default().split('&') {
+    match pair.split_once('=') {
+      Some(("token", value)) => token = Some(value.to_string()),
+      Some(("offset", value)) => offset = value.parse().ok()?,
+      _ => {}
+    }
+  }
+  Some(RecordsParams {
+    game_id,
+    token: token?,
+    offset,
+  })
+}
+
+/// Streams the encoded game log, delayed by the token's delay
+#[utoipa::path(
+  get,
+  path = "/games/{game_id}/records",
+  params(
+    ("game_id" = i32, Path),
+    (
+      "token" = String,
+      Query,
+      description = "Observer token issued by the controller for this game"
+    ),
+    (
+      "offset" = Option<u64>,
+      Query,
+      description = "Number of bytes to skip, used to resume a broken download"
+    ),
+  ),
+  responses(
+    (
+      status = 200,
+      description = "Chunked game log, the body ends when the game ends",
+      body = GameLog,
+      content_type = "application/octet-stream",
+      headers(
+        ("x-flo-offset" = u64, description = "The requested offset"),
+        ("x-flo-delay-secs" = i64, description = "Observer delay in seconds"),
+      )
+    ),
+    (status = 401, description = "Invalid token or token issued for another game"),
+    (status = 404, description = "Game not found on this node"),
+    (status = 405, description = "Method not allowed"),
+    (
+      status = 503,
+      description = "Game not available yet",
+      headers(
+        (
+          "Retry-After" = i64,
+          description = "Seconds until the delayed game becomes available"
+        ),
+      )
+    ),
+  )
+)]
+async fn serve_records(state: &GlobalStateRef, params: RecordsParams) -> Response<Body> {
+  let token = match flo_observer::token::validate_observer_token(&params.token) {
+    Ok(v) if v.game_id == params.game_id => v,
+    _ => return empty(StatusCode::UNAUTHORIZED),
+  };
+
+  // observer tokens are issued by the primary controller
+  let game = match state.get_game(GameKey {
+    controller_id: ControllerId::PRIMARY,
+    game_id: token.game_id,
+  }) {
+    Some(v) => v,
+    None => return empty(StatusCode::NOT_FOUND),
+  };
+
+  let (game, subscription) = match game.subscribe_observer().await {
+    Ok(v) => v,
+    Err(err) => {
+      tracing::debug!(game_id = token.game_id, "subscribe observer: {}", err);
+      return empty(StatusCode::SERVICE_UNAVAILABLE);
+    }
+  };
+
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default();
+  let expected = game.start_time_millis / 1000 + token.delay_secs.unwrap_or_default();
+  if expected > now {
+    let mut res = empty(StatusCode::SERVICE_UNAVAILABLE);
+    res
+      .headers_mut()
+      .insert(RETRY_AFTER, HeaderValue::from(expected - now));
+    return res;
+  }
+
+  let delay = token
+    .delay_secs
+    .filter(|v| *v > 0)
+    .map(|v| Duration::from_secs(v as u64));
+  let records = RecordStream::new(delay, params.offset, subscription);
+  let body = futures::stream::unfold(records, |mut records| async move {
+    records
+      .next_segment()
+      .await
+      .map(|r| (r.map(Frame::data), records))
+  });
+
+  let mut res = Response::new(StreamBody::new(body).boxed_unsync());
+  let headers = res.headers_mut();
+  headers.insert(
+    CONTENT_TYPE,
+    HeaderValue::from_static("application/octet-stream"),
+  );
+  headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+  headers.insert(HEADER_OFFSET, HeaderValue::from(params.offset));
+  headers.insert(
+    HEADER_DELAY_SECS,
+    HeaderValue::from(token.delay_secs.unwrap_or_default()),
+  );
+  res
+}
+
+/// OpenAPI document of this server
+#[utoipa::path(
+  get,
+  path = "/openapi.json",
+  responses(
+    (
+      status = 200,
+      description = "OpenAPI 3 document",
+      body = Object,
+      content_type = "application/json"
+    ),
+  )
+)]
+fn serve_openapi() -> Response<Body> {
+  let json = match ApiDoc::openapi().to_json() {
+    Ok(json) => json,
+    Err(err) => {
+      tracing::error!("serialize openapi: {}", err);
+      return empty(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+  };
+  let mut res = Response::new(
+    Full::new(Bytes::from(json))
+      .map_err(|never| match never {})
+      .boxed_unsync(),
+  );
+  res
+    .headers_mut()
+    .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+  res
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+  let mut res = Response::new(
+    Full::new(Bytes::new())
+      .map_err(|never| match never {})
+      .boxed_unsync(),
+  );
+  *res.status_mut() = status;
+  res
+}
+
+/// The game log starting at a byte offset, delayed
+struct RecordStream {
+  delay: Option<Duration>,
+  skip: usize,
+  queue: VecDeque<GameLogChunk>,
+  rx: Receiver<GameLogChunk>,
+  live: bool,
+}
+
+impl RecordStream {
+  fn new(delay: Option<Duration>, offset: usize, subscription: GameLogSubscription) -> Self {
+    Self {
+      delay,
+      skip: offset,
+      queue: subscription.history.into(),
+      rx: subscription.rx,
+      live: true,
+    }
+  }
+
+  /// Returns `None` after the game ended and all records were sent
+  async fn next_segment(&mut self) -> Option<Result<Bytes>> {
+    loop {
+      if let Some(data) = self.take_segment(Instant::now()) {
+        return Some(Ok(data));
+      }
+
+      if !self.live && self.queue.is_empty() {
+        return None;
+      }
+
+      let deadline = self.queue.front().map(|chunk| self.deadline(chunk));
+
+      tokio::select! {
+        r = self.rx.recv(), if self.live => {
+          match r {
+            Ok(chunk) => self.queue.push_back(chunk),
+            Err(RecvError::Lagged(n)) => {
+              self.live = false;
+              self.queue.clear();
+              return Some(Err(Error::ObserverStreamLagged(n)));
+            }
+            Err(RecvError::Closed) => self.live = false,
+          }
+        }
+        _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+      }
+    }
+  }
+
+  fn deadline(&self, chunk: &GameLogChunk) -> Instant {
+    match self.delay {
+      Some(delay) => chunk.time + delay,
+      None => chunk.time,
+    }
+  }
+
+  // Merges records past the delay point, dropping the bytes before the offset
+  fn take_segment(&mut self, now: Instant) -> Option<Bytes> {
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = self.queue.front() {
+      if self.deadline(chunk) > now
+        || (!buf.is_empty() && buf.len() + chunk.data.len() > GAME_LOG_CHUNK_SIZE)
+      {
+        break;
+      }
+      if let Some(chunk) = self.queue.pop_front() {
+        if self.skip >= chunk.data.len() {
+          self.skip -= chunk.data.len();
+          continue;
+        }
+        buf.extend_from_slice(&chunk.data[self.skip..]);
+        self.skip = 0;
+      }
+    }
+    if buf.is_empty() {
+      None
+    } else {
+      Some(buf.freeze())
+    }
+  }
+}
+
+#[test]
+fn test_parse_request() {
+  let parse = |uri: &str| parse_request(&Request::get(uri).body(()).unwrap());
+  assert_eq!(
+    parse("/games/1/records?token=abc&offset=10"),
+    Some(RecordsParams {
+      game_id: 1,
+      token: "abc".to_string(),
+      offset: 10,
+    })
+  );
+  assert_eq!(
+    parse("/games/1/records?token=abc").map(|p| p.offset),
+    Some(0)
+  );
+  assert_eq!(parse("/games/1/records"), None);
+  assert_eq!(parse("/games/x/records?token=abc"), None);
+  assert_eq!(parse("/games/1?token=abc"), None);
+}
+
+#[test]
+fn test_record_stream_offset() {
+  let now = Instant::now();
+  let chunk = |data: &'static [u8]| GameLogChunk {
+    time: now,
+    data: Bytes::from_static(data),
+  };
+  let (_tx, rx) = tokio::sync::broadcast::channel(1);
+  let mut records = RecordStream::new(
+    None,
+    4,
+    GameLogSubscription {
+      history: vec![chunk(b"abc"), chunk(b"def"), chunk(b"ghi")],
+      rx,
+    },
+  );
+  assert_eq!(
+    records.take_segment(now),
+    Some(Bytes::from_static(b"efghi"))
+  );
+  assert_eq!(records.take_segment(now), None);
+}
+
+#[test]
+fn test_openapi() {
+  let spec = ApiDoc::openapi();
+  let paths: Vec<_> = spec.paths.paths.keys().cloned().collect();
+  assert_eq!(paths, vec!["/games/{game_id}/records", OPENAPI_PATH]);
+
+  let json: serde_json::Value = serde_json::from_str(&spec.to_json().unwrap()).unwrap();
+  let records = &json["paths"]["/games/{game_id}/records"]["get"];
+  let params: Vec<_> = records["parameters"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|p| p["name"].as_str().unwrap())
+    .collect();
+  assert_eq!(params, vec!["game_id", "token", "offset"]);
+  for status in &["200", "401", "404", "405", "503"] {
+    assert!(records["responses"][*status]["description"].is_string());
+  }
+  assert!(records["responses"]["200"]["headers"][HEADER_OFFSET].is_object());
+  assert!(records["responses"]["200"]["headers"][HEADER_DELAY_SECS].is_object());
+
+  let req = Request::get("/games/1/records?token=t").body(()).unwrap();
+  assert!(parse_request(&req).is_some());
+  let req = Request::get(OPENAPI_PATH).body(()).unwrap();
+  assert_eq!(parse_request(&req), None);
+}
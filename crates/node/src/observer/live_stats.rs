@@ -0,0 +1,358 @@
+use bytes::{Buf, Bytes};
+use std::collections::{BTreeMap, VecDeque};
+
+use flo_net::observer::{
+  GameInfo, LiveStatsPlayer, LiveStatsPlayerStatus, LiveStatsProduction, LiveStatsProductionKind,
+  PacketObserverLiveStats,
+};
+use flo_observer::record::GameRecordData;
+use flo_w3gs::action::{IncomingAction, IncomingAction2};
+use flo_w3gs::actions::Action;
+use flo_w3gs::protocol::action::TimeSlot;
+use flo_w3gs::protocol::constants::PacketTypeId;
+use flo_w3gs::protocol::leave::PlayerLeft;
+use flo_w3replay::analysis::{get_production, ItemId, ProductionKind};
+
+const CURRENT_APM_WINDOW_MS: u32 = 60_000;
+
+/// Per-player numbers for caster overlays,
+/// computed from the records sent to an observer so they match the delayed stream
+#[derive(Debug)]
+pub struct LiveStatsCollector {
+  game_id: i32,
+  time: u32,
+  paused: bool,
+  pause_count: u32,
+  /// In-game player ids are `slot index + 1`
+  slot_players: BTreeMap<u8, i32>,
+  players: BTreeMap<i32, PlayerStats>,
+  changed: bool,
+}
+
+#[derive(Debug, Default)]
+struct PlayerStats {
+  action_count: u32,
+  recent_action_times: VecDeque<u32>,
+  lagging: bool,
+  left_at_ms: Option<u32>,
+  built: BTreeMap<ItemId, u32>,
+  trained: BTreeMap<ItemId, u32>,
+}
+
+impl PlayerStats {
+  fn put_production(&mut self, kind: ProductionKind, item_id: ItemId) {
+    match kind {
+      ProductionKind::Build => *self.built.entry(item_id).or_default() += 1,
+      ProductionKind::Train => *self.trained.entry(item_id).or_default() += 1,
+      ProductionKind::Cancel => {
+        if let Some(count) = self.trained.get_mut(&item_id) {
+          *count = count.saturating_sub(1);
+        }
+      }
+    }
+  }
+
+  fn production(&self) -> Vec<LiveStatsProduction> {
+    let built = self
+      .built
+      .iter()
+      .map(|(id, count)| (*id, LiveStatsProductionKind::Building, *count));
+    let trained = self
+      .trained
+      .iter()
+      .map(|(id, count)| (*id, get_trained_kind(*id), *count));
+    built
+      .chain(trained)
+      .filter(|(_, _, count)| *count > 0)
+      .map(|(id, kind, count)| {
+        let mut item = LiveStatsProduction {
+          item_id: id.to_string(),
+          count,
+          ..Default::default()
+        };
+        item.set_kind(kind);
+        item
+      })
+      .collect()
+  }
+}
+
+// Object ids of upgrades start with `R`, heroes with other upper case letters
+fn get_trained_kind(item_id: ItemId) -> LiveStatsProductionKind {
+  match item_id.0.to_be_bytes()[0] {
+    b'R' => LiveStatsProductionKind::Upgrade,
+    c if c.is_ascii_uppercase() => LiveStatsProductionKind::Hero,
+    _ => LiveStatsProductionKind::Unit,
+  }
+}
+
+impl LiveStatsCollector {
+  pub fn new(game: &GameInfo) -> Self {
+    let slot_players: BTreeMap<u8, i32> = game
+      .slots
+      .iter()
+      .enumerate()
+      .filter(|(_, slot)| {
+        // observers
+        slot.settings.as_ref().map(|s| s.team != 24).unwrap_or(true)
+      })
+      .filter_map(|(idx, slot)| Some(((idx + 1) as u8, slot.player.as_ref()?.id)))
+      .collect();
+    let players = slot_players
+      .values()
+      .map(|id| (*id, PlayerStats::default()))
+      .collect();
+    Self {
+      game_id: game.id,
+      time: 0,
+      paused: false,
+      pause_count: 0,
+      slot_players,
+      players,
+      changed: true,
+    }
+  }
+
+  /// Feeds encoded records
+  pub fn push_bytes(&mut self, mut data: Bytes) {
+    while data.has_remaining() {
+      match GameRecordData::decode(&mut data) {
+        Ok(record) => self.push(&record),
+        Err(_) => break,
+      }
+    }
+  }
+
+  pub fn push(&mut self, record: &GameRecordData) {
+    match record {
+      GameRecordData::W3GS(packet) => match packet.type_id() {
+        PacketTypeId::IncomingAction => {
+          if let Ok(payload) = packet.decode_payload::<IncomingAction>() {
+            self.put_time_slot(&payload.0);
+          }
+        }
+        PacketTypeId::IncomingAction2 => {
+          if let Ok(payload) = packet.decode_payload::<IncomingAction2>() {
+            self.put_time_slot(&payload.0);
+          }
+        }
+        PacketTypeId::PlayerLeft => {
+          if let Ok(payload) = packet.decode_simple::<PlayerLeft>() {
+            let time = self.time;
+            if let Some(player) = self.get_slot_player(payload.player_id) {
+              player.left_at_ms.get_or_insert(time);
+              self.changed = true;
+            }
+          }
+        }
+        _ => {}
+      },
+      GameRecordData::StartLag(player_ids) => {
+        for player in self.players.values_mut() {
+          player.lagging = false;
+        }
+        for id in player_ids {
+          if let Some(player) = self.players.get_mut(id) {
+            player.lagging = true;
+          }
+        }
+        self.changed = true;
+      }
+      GameRecordData::StopLag(player_id) => {
+        if let Some(player) = self.players.get_mut(player_id) {
+          player.lagging = false;
+          self.changed = true;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Returns `None` if nothing changed since the last call
+  pub fn take_packet(&mut self) -> Option<PacketObserverLiveStats> {
+    if !self.changed {
+      return None;
+    }
+    self.changed = false;
+
+    let time = self.time;
+    let players = self
+      .players
+      .iter_mut()
+      .map(|(player_id, stats)| {
+        while let Some(t) = stats.recent_action_times.front().cloned() {
+          if t + CURRENT_APM_WINDOW_MS > time {
+            break;
+          }
+          stats.recent_action_times.pop_front();
+        }
+        let in_game_ms = stats.left_at_ms.unwrap_or(time);
+        let mut item = LiveStatsPlayer {
+          player_id: *player_id,
+          action_count: stats.action_count,
+          apm: if in_game_ms == 0 {
+            0
+          } else {
+            (stats.action_count as u64 * 60_000 / in_game_ms as u64) as u32
+          },
+          current_apm: if stats.left_at_ms.is_some() {
+            0
+          } else {
+            stats.recent_action_times.len() as u32 * 60_000
+              / std::cmp::max(std::cmp::min(time, CURRENT_APM_WINDOW_MS), 1)
+          },
+          production: stats.production(),
+          ..Default::default()
+        };
+        item.set_status(if stats.left_at_ms.is_some() {
+          LiveStatsPlayerStatus::Left
+        } else if stats.lagging {
+          LiveStatsPlayerStatus::Lagging
+        } else {
+          LiveStatsPlayerStatus::Playing
+        });
+        item
+      })
+      .collect();
+
+    Some(PacketObserverLiveStats {
+      game_id: self.game_id,
+      game_time_ms: time,
+      paused: self.paused,
+      pause_count: self.pause_count,
+      players,
+    })
+  }
+
+  fn get_slot_player(&mut self, slot_player_id: u8) -> Option<&mut PlayerStats> {
+    let player_id = self.slot_players.get(&slot_player_id)?;
+    self.players.get_mut(player_id)
+  }
+
+  fn put_time_slot(&mut self, slot: &TimeSlot) {
+    let time = self.time;
+    for chunk in &slot.actions {
+      for action in chunk.actions() {
+        let action = match action {
+          Ok(v) => v,
+          Err(_) => break,
+        };
+        match action {
+          Action::PauseGame => {
+            self.paused = true;
+            self.pause_count += 1;
+          }
+          Action::ResumeGame => {
+            self.paused = false;
+          }
+          action if action.is_apm_action() => {
+            if let Some(player) = self.get_slot_player(chunk.player_id) {
+              player.action_count += 1;
+              player.recent_action_times.push_back(time);
+              if let Some((kind, item_id)) = get_production(&action) {
+                player.put_production(kind, item_id);
+              }
+            }
+          }
+          _ => continue,
+        }
+        self.changed = true;
+      }
+    }
+    if slot.time_increment_ms > 0 {
+      self.time += slot.time_increment_ms as u32;
+      self.changed = true;
+    }
+  }
+}
+
+#[test]
+fn test_live_stats() {
+  use flo_net::observer::{PlayerInfo, Slot};
+  use flo_w3gs::packet::Packet;
+
+  let mut collector = LiveStatsCollector::new(&GameInfo {
+    id: 1,
+    slots: vec![Slot {
+      player: Some(PlayerInfo {
+        id: 10,
+        name: "A".to_string(),
+      }),
+      settings: None,
+    }],
+    ..Default::default()
+  });
+
+  collector.push(&GameRecordData::W3GS(
+    Packet::with_payload(IncomingAction(TimeSlot {
+      time_increment_ms: 30_000,
+      actions: vec![],
+    }))
+    .unwrap(),
+  ));
+  collector.push(&GameRecordData::StartLag(vec![10]));
+
+  let packet = collector.take_packet().unwrap();
+  assert_eq!(packet.game_time_ms, 30_000);
+  assert_eq!(packet.players.len(), 1);
+  assert_eq!(packet.players[0].player_id, 10);
+  assert_eq!(packet.players[0].status(), LiveStatsPlayerStatus::Lagging);
+  assert!(collector.take_packet().is_none());
+}
+
+#[test]
+fn test_live_stats_production() {
+  use flo_net::observer::{PlayerInfo, Slot};
+  use flo_w3gs::action::PlayerAction;
+  use flo_w3gs::packet::Packet;
+
+  let mut collector = LiveStatsCollector::new(&GameInfo {
+    id: 1,
+    slots: vec![Slot {
+      player: Some(PlayerInfo {
+        id: 10,
+        name: "A".to_string(),
+      }),
+      settings: None,
+    }],
+    ..Default::default()
+  });
+
+  // object ids are little endian
+  let train = |id: &[u8; 4]| {
+    let mut data = vec![0x10, 0, 0];
+    data.extend_from_slice(id);
+    data.extend_from_slice(&[0; 8]);
+    data
+  };
+  let cancel = |id: &[u8; 4]| {
+    let mut data = vec![0x1E, 0];
+    data.extend_from_slice(id);
+    data
+  };
+  let mut data = vec![];
+  data.extend(train(b"oofh"));
+  data.extend(train(b"oofh"));
+  data.extend(cancel(b"oofh"));
+  data.extend(train(b"emhR"));
+  collector.push(&GameRecordData::W3GS(
+    Packet::with_payload(IncomingAction(TimeSlot {
+      time_increment_ms: 100,
+      actions: vec![PlayerAction {
+        player_id: 1,
+        data: data.into(),
+      }],
+    }))
+    .unwrap(),
+  ));
+
+  let packet = collector.take_packet().unwrap();
+  let production = &packet.players[0].production;
+  assert_eq!(packet.players[0].action_count, 4);
+  assert_eq!(production.len(), 2);
+  assert_eq!(production[0].item_id, "Rhme");
+  assert_eq!(production[0].kind(), LiveStatsProductionKind::Upgrade);
+  assert_eq!(production[1].item_id, "hfoo");
+  assert_eq!(production[1].kind(), LiveStatsProductionKind::Unit);
+  assert_eq!(production[1].count, 1);
+}
@@ -1,3 +1,8 @@
+mod http;
+mod live_stats;
+mod queue;
+mod stream;
+
 use crate::error::Result;
 use backoff::backoff::Backoff;
 use bytes::{BufMut, Bytes, BytesMut};
@@ -13,6 +18,9 @@ use tokio::sync::Notify;
 use tokio::time::{sleep, Instant};
 use tokio_util::sync::CancellationToken;
 
+pub use http::serve_observer_http;
+pub use stream::serve_observer;
+
 const BUFFER_TIMEOUT: Duration = Duration::from_secs(15 * 60);
 
 #[derive(Debug)]
@@ -47,6 +55,17 @@ impl ObserverPublisher {
   }
 }
 
+impl ObserverPublisherHandle {
+  /// A handle that drops all records
+  pub fn disabled() -> Self {
+    let (tx, _) = channel(1);
+    ObserverPublisherHandle {
+      broken: Cell::new(true),
+      tx,
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct ObserverPublisherHandle {
   broken: Cell<bool>,
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use crate::game::GameLogChunk;
+
+/// Chunks waiting for the delay point of one observer.
+/// Bounded by size if `max_size` is set, the oldest chunks are dropped
+/// so a slow observer can't hold on to an unbounded amount of memory.
+#[derive(Debug)]
+pub struct ObserverQueue {
+  chunks: VecDeque<GameLogChunk>,
+  size: usize,
+  max_size: Option<usize>,
+  dropped: u64,
+}
+
+impl ObserverQueue {
+  pub fn new(history: Vec<GameLogChunk>, max_size: Option<usize>) -> Self {
+    let size = history.iter().map(|chunk| chunk.data.len()).sum();
+    Self {
+      chunks: history.into(),
+      size,
+      max_size,
+      dropped: 0,
+    }
+  }
+
+  pub fn is_bounded(&self) -> bool {
+    self.max_size.is_some()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.chunks.is_empty()
+  }
+
+  pub fn front(&self) -> Option<&GameLogChunk> {
+    self.chunks.front()
+  }
+
+  pub fn pop_front(&mut self) -> Option<GameLogChunk> {
+    let chunk = self.chunks.pop_front()?;
+    self.size -= chunk.data.len();
+    Some(chunk)
+  }
+
+  pub fn push_back(&mut self, chunk: GameLogChunk) {
+    self.size += chunk.data.len();
+    self.chunks.push_back(chunk);
+    if let Some(max_size) = self.max_size {
+      while self.size > max_size && self.chunks.len() > 1 {
+        self.pop_front();
+        self.record_dropped(1);
+      }
+    }
+  }
+
+  /// Chunks dropped before they were queued
+  pub fn record_dropped(&mut self, n: u64) {
+    self.dropped += n;
+    crate::metrics::OBSERVER_DROPPED_CHUNKS.inc_by(n);
+  }
+
+  /// Number of chunks that will never be sent
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+}
+
+#[test]
+fn test_observer_queue_drop_oldest() {
+  use bytes::Bytes;
+  use tokio::time::Instant;
+
+  let chunk = |data: &'static [u8]| GameLogChunk {
+    time: Instant::now(),
+    data: Bytes::from_static(data),
+  };
+  let mut queue = ObserverQueue::new(vec![chunk(b"ab")], Some(4));
+  queue.push_back(chunk(b"cd"));
+  assert_eq!(queue.dropped(), 0);
+  queue.push_back(chunk(b"e"));
+  assert_eq!(queue.dropped(), 1);
+  assert_eq!(queue.pop_front().unwrap().data, Bytes::from_static(b"cd"));
+  assert_eq!(queue.pop_front().unwrap().data, Bytes::from_static(b"e"));
+  assert!(queue.is_empty());
+
+  let mut queue = ObserverQueue::new(vec![], None);
+  queue.push_back(chunk(b"abcdef"));
+  assert_eq!(queue.dropped(), 0);
+}
@@ -0,0 +1,342 @@
+//! Streams the game log to observers connected directly to the node.
+//! Records older than the delay are sent at once so the client can fast-forward,
+//! the rest are sent as they pass the delay point.
+//! Live stats of the sent records are pushed periodically.
+//! Chat sent before the delay point is pushed once after connecting,
+//! so a late observer can read it without replaying the records.
+//! The broadcast is drained while frames are sent, a delayed observer
+//! that can't keep up loses the oldest records instead of the connection.
+
+use bytes::BytesMut;
+use futures::stream::StreamExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::broadcast::Receiver;
+use tokio::time::{interval, sleep_until, Instant, MissedTickBehavior};
+
+use flo_constants::NODE_OBSERVER_PORT;
+use flo_net::listener::FloListener;
+use flo_net::observer::{
+  ObserverConnectRejectReason, PacketObserverChatHistory, PacketObserverConnect,
+  PacketObserverConnectAccept, PacketObserverConnectReject,
+};
+use flo_net::packet::{Frame, PacketTypeId};
+use flo_net::ping::{PingMsg, PingStream};
+use flo_net::stream::FloStream;
+
+use crate::constants::{GAME_LOG_CHUNK_SIZE, OBS_STREAM_QUEUE_MAX_SIZE};
+use crate::error::*;
+use crate::game::{GameChatEntry, GameLogChunk, GameLogSubscription};
+use crate::state::{ControllerId, GameKey, GlobalStateRef};
+
+use super::live_stats::LiveStatsCollector;
+use super::queue::ObserverQueue;
+
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+const LIVE_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub async fn serve_observer(state: GlobalStateRef) -> Result<()> {
+  if std::env::var("JWT_SECRET_BASE64").is_err() {
+    tracing::warn!("JWT_SECRET_BASE64 not set, observer stream disabled");
+    return Ok(());
+  }
+
+  let mut listener = FloListener::bind_v4(NODE_OBSERVER_PORT).await?;
+
+  while let Some(incoming) = listener.incoming().next().await {
+    if let Ok(stream) = incoming {
+      let state = state.clone();
+      tokio::spawn(async move {
+        if let Err(err) = handle_stream(state, stream).await {
+          tracing::debug!("observer stream: {}", err);
+        }
+      });
+    }
+  }
+
+  Ok(())
+}
+
+async fn handle_stream(state: GlobalStateRef, mut stream: FloStream) -> Result<()> {
+  let accepted = match accept(&state, &mut stream).await? {
+    Some(v) => v,
+    None => return Ok(()),
+  };
+  tracing::debug!(
+    game_id = accepted.game_id,
+    "observer connected: history = {} chunks",
+    accepted.subscription.history.len()
+  );
+  ObserverStream::new(accepted.delay, accepted.subscription, accepted.live_stats)
+    .run(accepted.game_id, stream)
+    .await
+}
+
+struct Accepted {
+  game_id: i32,
+  delay: Option<Duration>,
+  subscription: GameLogSubscription,
+  live_stats: LiveStatsCollector,
+}
+
+async fn accept(state: &GlobalStateRef, stream: &mut FloStream) -> Result<Option<Accepted>> {
+  let connect: PacketObserverConnect = stream.recv().await?;
+  let token = match flo_observer::token::validate_observer_token(&connect.token) {
+    Ok(v) => v,
+    Err(_) => {
+      reject(stream, ObserverConnectRejectReason::InvalidToken, None).await?;
+      return Ok(None);
+    }
+  };
+
+  // observer tokens are issued by the primary controller
+  let game = match state.get_game(GameKey {
+    controller_id: ControllerId::PRIMARY,
+    game_id: token.game_id,
+  }) {
+    Some(v) => v,
+    None => {
+      reject(stream, ObserverConnectRejectReason::GameNotFound, None).await?;
+      return Ok(None);
+    }
+  };
+
+  let (game, subscription) = match game.subscribe_observer().await {
+    Ok(v) => v,
+    Err(err) => {
+      tracing::debug!(game_id = token.game_id, "subscribe observer: {}", err);
+      reject(stream, ObserverConnectRejectReason::GameNotReady, None).await?;
+      return Ok(None);
+    }
+  };
+
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default();
+  let expected = game.start_time_millis / 1000 + token.delay_secs.unwrap_or_default();
+  if expected > now {
+    reject(
+      stream,
+      ObserverConnectRejectReason::DelayNotOver,
+      Some(expected),
+    )
+    .await?;
+    return Ok(None);
+  }
+
+  let live_stats = LiveStatsCollector::new(&game);
+
+  stream
+    .send(PacketObserverConnectAccept {
+      version: Some(crate::version::FLO_NODE_VERSION.into()),
+      game: Some(game),
+      delay_secs: token.delay_secs,
+    })
+    .await?;
+
+  Ok(Some(Accepted {
+    game_id: token.game_id,
+    delay: token
+      .delay_secs
+      .filter(|v| *v > 0)
+      .map(|v| Duration::from_secs(v as u64)),
+    subscription,
+    live_stats,
+  }))
+}
+
+async fn reject(
+  stream: &mut FloStream,
+  reason: ObserverConnectRejectReason,
+  delay_ends_at: Option<i64>,
+) -> Result<()> {
+  stream
+    .send({
+      let mut pkt = PacketObserverConnectReject {
+        delay_ends_at,
+        ..Default::default()
+      };
+      pkt.set_reason(reason);
+      pkt
+    })
+    .await?;
+  Ok(())
+}
+
+struct ObserverStream {
+  delay: Option<Duration>,
+  queue: ObserverQueue,
+  chat: Vec<GameChatEntry>,
+  rx: Receiver<GameLogChunk>,
+  live: bool,
+  live_stats: LiveStatsCollector,
+}
+
+impl ObserverStream {
+  fn new(
+    delay: Option<Duration>,
+    subscription: GameLogSubscription,
+    live_stats: LiveStatsCollector,
+  ) -> Self {
+    // without a delay the observer reconnects to catch up without gaps
+    let max_size = delay.map(|_| OBS_STREAM_QUEUE_MAX_SIZE);
+    Self {
+      delay,
+      queue: ObserverQueue::new(subscription.history, max_size),
+      chat: subscription.chat,
+      rx: subscription.rx,
+      live: true,
+      live_stats,
+    }
+  }
+
+  async fn run(mut self, game_id: i32, mut stream: FloStream) -> Result<()> {
+    let mut ping = PingStream::interval(PING_INTERVAL, PING_TIMEOUT);
+    ping.start();
+    let mut live_stats_interval = interval(LIVE_STATS_INTERVAL);
+    live_stats_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    if let Some(pkt) = self.take_chat_history(game_id) {
+      stream.send(pkt).await?;
+    }
+
+    loop {
+      while let Some(frame) = self.next_frame() {
+        stream.send_frame(frame).await?;
+        self.drain()?;
+      }
+
+      if !self.live && self.queue.is_empty() {
+        if let Some(pkt) = self.live_stats.take_packet() {
+          stream.send(pkt).await?;
+        }
+        stream
+          .send_frame(Frame::new_empty(PacketTypeId::ObserverDataEnd))
+          .await?;
+        stream.flush().await?;
+        break;
+      }
+
+      let deadline = self.queue.front().map(|chunk| self.deadline(chunk));
+
+      tokio::select! {
+        r = self.rx.recv(), if self.live => {
+          match r {
+            Ok(chunk) => self.queue.push_back(chunk),
+            Err(RecvError::Lagged(n)) => self.lagged(n)?,
+            Err(RecvError::Closed) => self.live = false,
+          }
+        }
+        _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {}
+        _ = live_stats_interval.tick() => {
+          if let Some(pkt) = self.live_stats.take_packet() {
+            stream.send(pkt).await?;
+          }
+        }
+        r = stream.recv_frame() => {
+          match r {
+            Ok(frame) => {
+              if frame.type_id == PacketTypeId::Pong {
+                ping.capture_pong(frame);
+              }
+            }
+            Err(flo_net::error::Error::StreamClosed) => break,
+            Err(err) => return Err(err.into()),
+          }
+        }
+        Some(next) = ping.next() => {
+          match next {
+            PingMsg::Ping(frame) => {
+              stream.send_frame(frame).await?;
+            }
+            PingMsg::Timeout => {
+              tracing::debug!(game_id, "observer ping timeout");
+              break;
+            }
+          }
+        }
+      }
+    }
+
+    if self.queue.dropped() > 0 {
+      tracing::debug!(
+        game_id,
+        "observer stream dropped {} chunks",
+        self.queue.dropped()
+      );
+    }
+
+    Ok(())
+  }
+
+  // Moves received chunks to the queue so the broadcast doesn't lag behind
+  fn drain(&mut self) -> Result<()> {
+    while self.live {
+      match self.rx.try_recv() {
+        Ok(chunk) => self.queue.push_back(chunk),
+        Err(TryRecvError::Lagged(n)) => self.lagged(n)?,
+        Err(TryRecvError::Closed) => self.live = false,
+        Err(TryRecvError::Empty) => break,
+      }
+    }
+    Ok(())
+  }
+
+  fn lagged(&mut self, n: u64) -> Result<()> {
+    if self.queue.is_bounded() {
+      self.queue.record_dropped(n);
+      Ok(())
+    } else {
+      Err(Error::ObserverStreamLagged(n))
+    }
+  }
+
+  fn deadline(&self, chunk: &GameLogChunk) -> Instant {
+    match self.delay {
+      Some(delay) => chunk.time + delay,
+      None => chunk.time,
+    }
+  }
+
+  // Messages newer than the delay point are left to the records
+  fn take_chat_history(&mut self, game_id: i32) -> Option<PacketObserverChatHistory> {
+    let now = Instant::now();
+    let messages: Vec<_> = std::mem::take(&mut self.chat)
+      .into_iter()
+      .filter(|entry| match self.delay {
+        Some(delay) => entry.time + delay <= now,
+        None => true,
+      })
+      .map(|entry| entry.message)
+      .collect();
+    if messages.is_empty() {
+      return None;
+    }
+    Some(PacketObserverChatHistory { game_id, messages })
+  }
+
+  // Merges records past the delay point into a data frame
+  fn next_frame(&mut self) -> Option<Frame> {
+    let now = Instant::now();
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = self.queue.front() {
+      if self.deadline(chunk) > now
+        || (!buf.is_empty() && buf.len() + chunk.data.len() > GAME_LOG_CHUNK_SIZE)
+      {
+        break;
+      }
+      if let Some(chunk) = self.queue.pop_front() {
+        buf.extend_from_slice(&chunk.data);
+      }
+    }
+    if buf.is_empty() {
+      None
+    } else {
+      let data = buf.freeze();
+      self.live_stats.push_bytes(data.clone());
+      Some(Frame::new_bytes(PacketTypeId::ObserverData, data))
+    }
+  }
+}
@@ -0,0 +1,167 @@
+use once_cell::sync::Lazy;
+use std::time::Duration;
+
+use crate::constants::{GAME_CLOCK_MAX_PAUSE, PEER_CHANNEL_SIZE};
+use crate::error::{Error, Result};
+pub use flo_util::settings::ReloadReport;
+use flo_util::settings::{SettingsSource, SettingsStore};
+
+/// Path of an optional env file that overrides the process environment,
+/// read at startup and on every reload
+const SETTINGS_FILE_ENV: &str = "FLO_NODE_SETTINGS_FILE";
+
+/// Settings only read when the process starts
+const RESTART_REQUIRED: &[&str] = &[
+  "FLO_NODE_SECRET",
+  "FLO_NODE_CONTROLLERS",
+  "FLO_NODE_STANDBY_ADDR",
+  "FLO_NODE_STANDBY_SECRET",
+  "JWT_SECRET_BASE64",
+  "OBSERVER_SOURCE",
+];
+
+/// Settings that can be changed without restarting the process,
+/// games read the step and stall timeout when they start
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+  /// Chat is not sent to players with more frames waiting in the send queue
+  pub send_queue_drop_threshold: usize,
+  /// Players with more frames waiting in the send queue are shown in the lag screen
+  pub send_queue_lag_threshold: usize,
+  pub game_step_ms: u16,
+  /// A running game that hasn't dispatched a tick for this long is ended,
+  /// always longer than `GAME_CLOCK_MAX_PAUSE`
+  pub game_stall_timeout: Duration,
+  /// Players that haven't loaded this long after the game started loading are removed
+  pub load_timeout: Duration,
+  /// Players are warned this long before they are removed for not loading
+  pub idle_warning: Duration,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Settings {
+      send_queue_drop_threshold: PEER_CHANNEL_SIZE / 4,
+      send_queue_lag_threshold: PEER_CHANNEL_SIZE / 2,
+      game_step_ms: 30,
+      game_stall_timeout: Duration::from_secs(90),
+      load_timeout: Duration::from_secs(300),
+      idle_warning: Duration::from_secs(60),
+    }
+  }
+}
+
+impl flo_util::settings::Settings for Settings {
+  type Error = Error;
+
+  fn load(source: &SettingsSource) -> Result<Self> {
+    let default = Settings::default();
+    let min_stall_timeout = (GAME_CLOCK_MAX_PAUSE + Duration::from_secs(10)).as_secs();
+    Ok(Settings {
+      send_queue_drop_threshold: source.parse_or(
+        "FLO_NODE_SEND_QUEUE_DROP_THRESHOLD",
+        default.send_queue_drop_threshold,
+        |v| *v > 0 && *v <= PEER_CHANNEL_SIZE,
+      )?,
+      send_queue_lag_threshold: source.parse_or(
+        "FLO_NODE_SEND_QUEUE_LAG_THRESHOLD",
+        default.send_queue_lag_threshold,
+        |v| *v > 0 && *v <= PEER_CHANNEL_SIZE,
+      )?,
+      game_step_ms: source.parse_or("FLO_GAME_STEP_MS", default.game_step_ms, |v| *v > 0)?,
+      game_stall_timeout: source
+        .parse_or(
+          "FLO_NODE_GAME_STALL_TIMEOUT_SECS",
+          default.game_stall_timeout.as_secs(),
+          |v| *v >= min_stall_timeout,
+        )
+        .map(Duration::from_secs)?,
+      load_timeout: source
+        .parse_or(
+          "FLO_NODE_LOAD_TIMEOUT_SECS",
+          default.load_timeout.as_secs(),
+          |v| *v >= 60,
+        )
+        .map(Duration::from_secs)?,
+      idle_warning: source
+        .parse_or(
+          "FLO_NODE_IDLE_WARNING_SECS",
+          default.idle_warning.as_secs(),
+          |v| *v > 0,
+        )
+        .map(Duration::from_secs)?,
+    })
+  }
+
+  fn diff(&self, other: &Self) -> Vec<String> {
+    let mut changed = vec![];
+    if self.send_queue_drop_threshold != other.send_queue_drop_threshold {
+      changed.push("FLO_NODE_SEND_QUEUE_DROP_THRESHOLD".to_string());
+    }
+    if self.send_queue_lag_threshold != other.send_queue_lag_threshold {
+      changed.push("FLO_NODE_SEND_QUEUE_LAG_THRESHOLD".to_string());
+    }
+    if self.game_step_ms != other.game_step_ms {
+      changed.push("FLO_GAME_STEP_MS".to_string());
+    }
+    if self.game_stall_timeout != other.game_stall_timeout {
+      changed.push("FLO_NODE_GAME_STALL_TIMEOUT_SECS".to_string());
+    }
+    if self.load_timeout != other.load_timeout {
+      changed.push("FLO_NODE_LOAD_TIMEOUT_SECS".to_string());
+    }
+    if self.idle_warning != other.idle_warning {
+      changed.push("FLO_NODE_IDLE_WARNING_SECS".to_string());
+    }
+    changed
+  }
+}
+
+static SETTINGS: Lazy<SettingsStore<Settings>> =
+  Lazy::new(|| SettingsStore::new(SETTINGS_FILE_ENV, RESTART_REQUIRED));
+
+/// Loads the settings, must be called before anything reads the environment
+pub fn init() -> Result<()> {
+  SETTINGS.init()
+}
+
+/// Re-reads and validates the settings, nothing is changed if any value is invalid
+pub fn reload() -> Result<ReloadReport> {
+  SETTINGS.reload()
+}
+
+pub fn current() -> Settings {
+  *SETTINGS.current()
+}
+
+#[test]
+fn test_settings_source() {
+  use flo_util::settings::Settings as _;
+
+  let source = SettingsSource::from_env_file(
+    r#"
+    FLO_NODE_SEND_QUEUE_LAG_THRESHOLD=100
+    FLO_GAME_STEP_MS="20"
+    "#,
+  );
+  let settings = Settings::load(&source).unwrap();
+  assert_eq!(settings.send_queue_lag_threshold, 100);
+  assert_eq!(settings.game_step_ms, 20);
+  assert_eq!(
+    Settings::default().diff(&settings),
+    vec!["FLO_NODE_SEND_QUEUE_LAG_THRESHOLD", "FLO_GAME_STEP_MS"]
+  );
+
+  for invalid in &[
+    "FLO_NODE_SEND_QUEUE_DROP_THRESHOLD=1000",
+    "FLO_GAME_STEP_MS=fast",
+    "FLO_NODE_GAME_STALL_TIMEOUT_SECS=10",
+    "FLO_NODE_LOAD_TIMEOUT_SECS=5",
+  ] {
+    let source = SettingsSource::from_env_file(invalid);
+    assert!(matches!(
+      Settings::load(&source),
+      Err(Error::SettingInvalid { .. })
+    ));
+  }
+}
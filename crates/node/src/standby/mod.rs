@@ -0,0 +1,398 @@
+mod replica;
+
+pub use replica::{ReplicaRegistry, StandbyServer};
+
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::time::{interval, sleep};
+use tokio_util::sync::CancellationToken;
+
+use flo_constants::{NODE_CLIENT_PORT_OFFSET, NODE_STANDBY_PORT_OFFSET};
+use flo_net::packet::{FloPacket, Frame, Message, PacketTypeId};
+use flo_net::proto::flo_node::{
+  PacketStandbyConnect, PacketStandbyConnectAccept, PacketStandbyGameCreate, PacketStandbyGameEnd,
+  PacketStandbyGameLog, PacketStandbyGameUpdate, StandbyPlayerUpdate,
+};
+use flo_net::stream::FloStream;
+use flo_net::w3gs::{encode_w3gs_items, W3GSMetadata, W3GSPacket};
+
+use crate::constants::{STANDBY_MAX_PACKET_SIZE, STANDBY_PING_INTERVAL, STANDBY_SENDER_BUF_SIZE};
+use crate::env::Env;
+use crate::error::*;
+use crate::state::GameKey;
+
+/// Client address of the standby node, sent to players so they can reconnect to it
+pub fn client_addr() -> Option<SocketAddr> {
+  let env = Env::get();
+  env.standby_secret.as_ref()?;
+  env
+    .standby_addr
+    .map(|addr| SocketAddr::new(addr.ip(), addr.port() + NODE_CLIENT_PORT_OFFSET))
+}
+
+/// Streams the started games of this node to the standby node
+#[derive(Debug)]
+pub struct StandbyReplicator {
+  enabled: bool,
+  ct: CancellationToken,
+  tx: Sender<Frame>,
+  /// Increased on every connection to the standby node, 0 while disconnected
+  epoch: Arc<AtomicU64>,
+}
+
+impl Drop for StandbyReplicator {
+  fn drop(&mut self) {
+    self.ct.cancel()
+  }
+}
+
+impl StandbyReplicator {
+  pub fn new() -> Self {
+    let (tx, rx) = channel(STANDBY_SENDER_BUF_SIZE);
+    let ct = CancellationToken::new();
+    let epoch = Arc::new(AtomicU64::new(0));
+    let env = Env::get();
+    let enabled = match (env.standby_addr, env.standby_secret.clone()) {
+      (Some(addr), Some(secret)) => {
+        let addr = SocketAddr::new(addr.ip(), addr.port() + NODE_STANDBY_PORT_OFFSET);
+        tokio::spawn(replicate(addr, secret, rx, epoch.clone(), ct.clone()));
+        true
+      }
+      _ => false,
+    };
+    Self {
+      enabled,
+      ct,
+      tx,
+      epoch,
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled
+  }
+
+  pub fn handle(&self, key: GameKey, create: PacketStandbyGameCreate) -> Result<StandbyHandle> {
+    if !self.enabled {
+      return Ok(StandbyHandle::disabled());
+    }
+    Ok(StandbyHandle(Some(HandleInner {
+      key,
+      create: create.encode_as_frame()?,
+      tx: self.tx.clone(),
+      epoch: self.epoch.clone(),
+      synced_epoch: None,
+    })))
+  }
+}
+
+async fn replicate(
+  addr: SocketAddr,
+  secret: String,
+  mut rx: Receiver<Frame>,
+  epoch: Arc<AtomicU64>,
+  ct: CancellationToken,
+) {
+  let mut backoff = ExponentialBackoff {
+    max_elapsed_time: None,
+    ..Default::default()
+  };
+  let mut next_epoch = 1;
+  loop {
+    let res = tokio::select! {
+      _ = ct.cancelled() => break,
+      res = connect(addr, &secret) => res,
+    };
+    match res {
+      Ok(mut stream) => {
+        tracing::info!(%addr, "standby node connected");
+        backoff.reset();
+        epoch.store(next_epoch, Ordering::SeqCst);
+        next_epoch += 1;
+        let res = tokio::select! {
+          _ = ct.cancelled() => break,
+          res = forward(&mut stream, &mut rx) => res,
+        };
+        epoch.store(0, Ordering::SeqCst);
+        // updates of the closed connection, games are sent again after reconnecting
+        while rx.try_recv().is_ok() {}
+        if let Err(err) = res {
+          tracing::error!(%addr, "standby node disconnected: {}", err);
+        }
+      }
+      Err(err) => {
+        tracing::warn!(%addr, "connect standby node: {}", err);
+      }
+    }
+    if let Some(delay) = backoff.next_backoff() {
+      sleep(delay).await;
+    }
+  }
+}
+
+async fn connect(addr: SocketAddr, secret: &str) -> Result<FloStream> {
+  use std::time::Duration;
+  const RECV_TIMEOUT: Duration = Duration::from_secs(3);
+
+  let mut stream = FloStream::connect_no_delay(addr).await?;
+  stream
+    .send(PacketStandbyConnect {
+      secret: secret.to_string(),
+    })
+    .await?;
+  let _: PacketStandbyConnectAccept = stream.recv_timeout(RECV_TIMEOUT).await?;
+  Ok(stream)
+}
+
+async fn forward(stream: &mut FloStream, rx: &mut Receiver<Frame>) -> Result<()> {
+  let mut ping = interval(STANDBY_PING_INTERVAL);
+  loop {
+    tokio::select! {
+      _ = ping.tick() => {
+        stream.send_frame_timeout(Frame::new_empty(PacketTypeId::Ping)).await?;
+      }
+      next = rx.recv() => {
+        match next {
+          Some(frame) => stream.send_frame_timeout(frame).await?,
+          None => return Ok(()),
+        }
+      }
+      // the standby node never sends anything, this only detects a closed connection
+      res = stream.recv_frame() => {
+        res?;
+      }
+    }
+  }
+}
+
+/// Replicates one game, disabled if no standby node is configured
+#[derive(Debug)]
+pub struct StandbyHandle(Option<HandleInner>);
+
+#[derive(Debug)]
+struct HandleInner {
+  key: GameKey,
+  create: Frame,
+  tx: Sender<Frame>,
+  epoch: Arc<AtomicU64>,
+  /// The connection the game has been fully sent over
+  synced_epoch: Option<u64>,
+}
+
+/// Returned by [`StandbyHandle::begin`]
+#[derive(Debug, Clone, Copy)]
+pub struct ReplicaSync {
+  epoch: u64,
+  /// The standby node doesn't have the game, everything has to be sent
+  pub reset: bool,
+}
+
+/// Changes of a game since the last replication
+#[derive(Debug)]
+pub struct GameReplica {
+  pub started: bool,
+  pub game_time_ms: u32,
+  pub left_player_ids: Vec<i32>,
+  pub players: Vec<PlayerReplica>,
+  /// Game log pieces, see [`crate::game::GameLog::replicate`]
+  pub log: Vec<Bytes>,
+}
+
+/// Changes of the W3GS ack queue of a player
+#[derive(Debug)]
+pub struct PlayerReplica {
+  pub player_id: i32,
+  /// Packets queued since the last replication,
+  /// or all packets not acknowledged yet after a reset
+  pub sent: Vec<(W3GSMetadata, W3GSPacket)>,
+  pub last_send_sid: u32,
+  pub ack_sent: Option<u32>,
+  pub ack_received: Option<u32>,
+}
+
+impl StandbyHandle {
+  pub fn disabled() -> Self {
+    StandbyHandle(None)
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.0.is_some()
+  }
+
+  /// Returns `None` if nothing should be replicated now
+  pub fn begin(&self) -> Option<ReplicaSync> {
+    let inner = self.0.as_ref()?;
+    let epoch = inner.epoch.load(Ordering::SeqCst);
+    if epoch == 0 {
+      return None;
+    }
+    Some(ReplicaSync {
+      epoch,
+      reset: inner.synced_epoch != Some(epoch),
+    })
+  }
+
+  pub fn send(&mut self, sync: ReplicaSync, replica: GameReplica) {
+    let inner = match self.0.as_mut() {
+      Some(inner) => inner,
+      None => return,
+    };
+    let frames = match encode_replica(inner.key, sync.reset, replica) {
+      Ok(frames) => frames,
+      Err(err) => {
+        tracing::error!(game_id = inner.key.game_id, "encode game replica: {}", err);
+        return;
+      }
+    };
+    let create = if sync.reset {
+      Some(inner.create.clone())
+    } else {
+      None
+    };
+    for frame in create.into_iter().chain(frames) {
+      if inner.tx.try_send(frame).is_err() {
+        // sent again in full after the queue drained
+        inner.synced_epoch.take();
+        return;
+      }
+    }
+    inner.synced_epoch.replace(sync.epoch);
+  }
+
+  /// Removes the game from the standby node
+  pub fn end(&self) {
+    let inner = match self.0.as_ref() {
+      Some(inner) => inner,
+      None => return,
+    };
+    if inner.synced_epoch.is_none() {
+      return;
+    }
+    let frame = PacketStandbyGameEnd {
+      controller_id: inner.key.controller_id.0 as i32,
+      game_id: inner.key.game_id,
+    }
+    .encode_as_frame();
+    if let Ok(frame) = frame {
+      inner.tx.try_send(frame).ok();
+    }
+  }
+}
+
+fn encode_replica(key: GameKey, reset: bool, replica: GameReplica) -> Result<Vec<Frame>> {
+  let controller_id = key.controller_id.0 as i32;
+  let game_id = key.game_id;
+  let new_update = || PacketStandbyGameUpdate {
+    controller_id,
+    game_id,
+    started: replica.started,
+    game_time_ms: replica.game_time_ms,
+    left_player_ids: replica.left_player_ids.clone(),
+    players: vec![],
+  };
+
+  let mut frames = vec![];
+  let mut update = new_update();
+  for player in &replica.players {
+    for item in player_updates(player, reset) {
+      if !update.players.is_empty()
+        && update.encoded_len() + item.encoded_len() > STANDBY_MAX_PACKET_SIZE
+      {
+        frames.push(std::mem::replace(&mut update, new_update()).encode_as_frame()?);
+      }
+      update.players.push(item);
+    }
+  }
+  frames.push(update.encode_as_frame()?);
+
+  if reset && replica.log.is_empty() {
+    frames.push(
+      PacketStandbyGameLog {
+        controller_id,
+        game_id,
+        reset,
+        data: vec![],
+      }
+      .encode_as_frame()?,
+    );
+  }
+  for (i, data) in replica.log.iter().enumerate() {
+    frames.push(
+      PacketStandbyGameLog {
+        controller_id,
+        game_id,
+        reset: reset && i == 0,
+        data: data.to_vec(),
+      }
+      .encode_as_frame()?,
+    );
+  }
+  Ok(frames)
+}
+
+/// Splits the sent packets so every update fits in a packet
+fn player_updates(player: &PlayerReplica, reset: bool) -> Vec<StandbyPlayerUpdate> {
+  let max_len = STANDBY_MAX_PACKET_SIZE / 2;
+  let mut groups: Vec<&[(W3GSMetadata, W3GSPacket)]> = vec![];
+  let mut start = 0;
+  let mut len = 0;
+  for (i, (meta, packet)) in player.sent.iter().enumerate() {
+    let item_len = meta.len() + 2 + packet.payload.len();
+    if i > start && len + item_len > max_len {
+      groups.push(&player.sent[start..i]);
+      start = i;
+      len = 0;
+    }
+    len += item_len;
+  }
+  groups.push(&player.sent[start..]);
+
+  groups
+    .into_iter()
+    .enumerate()
+    .map(|(i, items)| StandbyPlayerUpdate {
+      player_id: player.player_id,
+      reset: reset && i == 0,
+      sent: encode_w3gs_items(items).to_vec(),
+      last_send_sid: player.last_send_sid,
+      ack_sent: player.ack_sent,
+      ack_received: player.ack_received,
+    })
+    .collect()
+}
+
+#[test]
+fn test_player_updates() {
+  use flo_net::w3gs::{decode_w3gs_items, W3GSPacketTypeId};
+
+  let packet = W3GSPacket::simple(flo_w3gs::protocol::leave::LeaveAck).unwrap();
+  let player = PlayerReplica {
+    player_id: 1,
+    sent: (1..=2000)
+      .map(|sid| {
+        (
+          W3GSMetadata::new(W3GSPacketTypeId::LeaveAck, sid, None),
+          packet.clone(),
+        )
+      })
+      .collect(),
+    last_send_sid: 2000,
+    ack_sent: None,
+    ack_received: None,
+  };
+  let updates = player_updates(&player, true);
+  assert!(updates.len() > 1);
+  assert!(updates[0].reset);
+  assert!(updates[1..].iter().all(|u| !u.reset));
+  let sids: Vec<u32> = updates
+    .into_iter()
+    .flat_map(|u| decode_w3gs_items(u.sent.into()).unwrap())
+    .map(|(meta, _)| meta.sid())
+    .collect();
+  assert_eq!(sids, (1..=2000).collect::<Vec<_>>());
+}
@@ -0,0 +1,401 @@
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::stream::StreamExt;
+use once_cell::sync::OnceCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::oneshot;
+
+use flo_constants::NODE_STANDBY_PORT;
+use flo_net::listener::FloListener;
+use flo_net::packet::{FloPacket, PacketTypeId};
+use flo_net::proto::flo_node::*;
+use flo_net::stream::FloStream;
+use flo_net::try_flo_packet;
+use flo_net::w3gs::{decode_w3gs_items, W3GSAckQueue, W3GSMetadata, W3GSPacket};
+
+use crate::constants::{STANDBY_LINK_TIMEOUT, STANDBY_PROMOTE_TIMEOUT};
+use crate::controller::ControllerServerHandle;
+use crate::env::Env;
+use crate::error::*;
+use crate::game::GameResume;
+use crate::state::{ControllerId, GameKey, GlobalStateRef};
+
+/// Games replicated from the primary node, taken over if the primary node goes down
+#[derive(Debug)]
+pub struct ReplicaRegistry {
+  games: DashMap<GameKey, ReplicaGame>,
+  primary_connected: AtomicBool,
+  controllers: OnceCell<Vec<ControllerServerHandle>>,
+  /// Players waiting for the controller to confirm the primary node is down
+  promotes: DashMap<GameKey, Vec<oneshot::Sender<bool>>>,
+}
+
+#[derive(Debug)]
+struct ReplicaGame {
+  game: Game,
+  traceparent: String,
+  started: bool,
+  game_time_ms: u32,
+  left_player_ids: Vec<i32>,
+  players: BTreeMap<i32, ReplicaPlayer>,
+  log: Vec<Bytes>,
+}
+
+#[derive(Debug, Default)]
+struct ReplicaPlayer {
+  pending: VecDeque<(W3GSMetadata, W3GSPacket)>,
+  last_send_sid: u32,
+  ack_sent: Option<u32>,
+  ack_received: Option<u32>,
+}
+
+impl ReplicaPlayer {
+  fn apply(&mut self, update: StandbyPlayerUpdate) -> Result<()> {
+    if update.reset {
+      self.pending.clear();
+    }
+    self
+      .pending
+      .extend(decode_w3gs_items(Bytes::from(update.sent))?);
+    if let Some(ack_sid) = update.ack_sent {
+      if let Some(idx) = self
+        .pending
+        .iter()
+        .position(|(meta, _)| meta.sid() == ack_sid)
+      {
+        self.pending.drain(..=idx);
+      }
+    }
+    self.last_send_sid = update.last_send_sid;
+    self.ack_sent = update.ack_sent;
+    self.ack_received = update.ack_received;
+    Ok(())
+  }
+}
+
+impl ReplicaRegistry {
+  pub fn new() -> Self {
+    ReplicaRegistry {
+      games: DashMap::new(),
+      primary_connected: AtomicBool::new(false),
+      controllers: OnceCell::new(),
+      promotes: DashMap::new(),
+    }
+  }
+
+  /// Promoted games report to the same controllers as the primary node
+  pub fn set_controllers(&self, controllers: Vec<ControllerServerHandle>) {
+    self.controllers.set(controllers).ok();
+  }
+
+  pub fn controller(&self, id: ControllerId) -> Option<ControllerServerHandle> {
+    self.controllers.get()?.get(id.0).cloned()
+  }
+
+  pub fn set_primary_connected(&self, connected: bool) {
+    self.primary_connected.store(connected, Ordering::SeqCst);
+  }
+
+  pub fn contains(&self, key: GameKey) -> bool {
+    self.games.contains_key(&key)
+  }
+
+  pub fn keys(&self) -> Vec<GameKey> {
+    self.games.iter().map(|r| *r.key()).collect()
+  }
+
+  pub fn insert(&self, key: GameKey, game: Game, traceparent: String) {
+    self.games.insert(
+      key,
+      ReplicaGame {
+        game,
+        traceparent,
+        started: false,
+        game_time_ms: 0,
+        left_player_ids: vec![],
+        players: BTreeMap::new(),
+        log: vec![],
+      },
+    );
+  }
+
+  pub fn update(&self, key: GameKey, packet: PacketStandbyGameUpdate) -> Result<()> {
+    let mut game = match self.games.get_mut(&key) {
+      Some(game) => game,
+      None => return Ok(()),
+    };
+    game.started = packet.started;
+    game.game_time_ms = packet.game_time_ms;
+    game.left_player_ids = packet.left_player_ids;
+    for update in packet.players {
+      game
+        .players
+        .entry(update.player_id)
+        .or_default()
+        .apply(update)?;
+    }
+    Ok(())
+  }
+
+  pub fn append_log(&self, key: GameKey, packet: PacketStandbyGameLog) {
+    let mut game = match self.games.get_mut(&key) {
+      Some(game) => game,
+      None => return,
+    };
+    if packet.reset {
+      game.log.clear();
+    }
+    if !packet.data.is_empty() {
+      game.log.push(Bytes::from(packet.data));
+    }
+  }
+
+  pub fn remove(&self, key: GameKey) -> bool {
+    self.games.remove(&key).is_some()
+  }
+
+  /// Returns `true` if the game has started and the link to the primary node is down
+  pub fn can_resume(&self, key: GameKey) -> bool {
+    !self.primary_connected.load(Ordering::SeqCst)
+      && self.games.get(&key).map(|game| game.started) == Some(true)
+  }
+
+  /// Asks the controller if the primary node of the game is down.
+  /// The link to the primary node can be down while the primary node still hosts the game,
+  /// taking over the game then would give its players a second session.
+  pub async fn confirm_primary_down(&self, ctrl: &ControllerServerHandle, key: GameKey) -> bool {
+    let (first, rx) = self.wait_promote(key);
+    let confirmed = tokio::time::timeout(STANDBY_PROMOTE_TIMEOUT, async {
+      if first {
+        let frame = PacketNodeStandbyPromote {
+          game_id: key.game_id,
+        }
+        .encode_as_frame()
+        .ok()?;
+        ctrl.send(frame).await.ok()?;
+      }
+      rx.await.ok()
+    })
+    .await;
+    if first {
+      self.promotes.remove(&key);
+    }
+    matches!(confirmed, Ok(Some(true)))
+  }
+
+  /// Registers a waiter for the answer of the controller,
+  /// returns `true` if no request was sent for the game yet
+  fn wait_promote(&self, key: GameKey) -> (bool, oneshot::Receiver<bool>) {
+    let (tx, rx) = oneshot::channel();
+    let mut waiters = self.promotes.entry(key).or_default();
+    waiters.push(tx);
+    (waiters.len() == 1, rx)
+  }
+
+  /// Called with the answer of the controller to `PacketNodeStandbyPromote`
+  pub fn resolve_promote(&self, key: GameKey, primary_down: bool) {
+    if let Some((_, waiters)) = self.promotes.remove(&key) {
+      for tx in waiters {
+        tx.send(primary_down).ok();
+      }
+    }
+  }
+
+  /// Removes a started game if the primary node is down
+  pub fn take_resume(&self, key: GameKey) -> Option<(Game, String, GameResume)> {
+    if self.primary_connected.load(Ordering::SeqCst) {
+      return None;
+    }
+    let (_, game) = self.games.remove_if(&key, |_, game| game.started)?;
+    let resume = GameResume {
+      game_time_ms: game.game_time_ms,
+      left_player_ids: game.left_player_ids,
+      ack_queues: game
+        .players
+        .into_iter()
+        .map(|(player_id, player)| {
+          (
+            player_id,
+            W3GSAckQueue::restore(
+              player.last_send_sid,
+              player.ack_sent,
+              player.pending,
+              player.ack_received,
+            ),
+          )
+        })
+        .collect(),
+      log: game.log,
+    };
+    Some((game.game, game.traceparent, resume))
+  }
+}
+
+/// Receives the games of the primary node, one primary node at a time
+pub struct StandbyServer {
+  state: GlobalStateRef,
+}
+
+impl StandbyServer {
+  pub fn new(state: GlobalStateRef) -> Self {
+    StandbyServer { state }
+  }
+
+  pub async fn serve(self) -> Result<()> {
+    let secret = match Env::get().standby_secret.as_ref() {
+      Some(secret) => secret,
+      None => return Ok(()),
+    };
+
+    let mut listener = FloListener::bind_v4(NODE_STANDBY_PORT).await?;
+
+    while let Some(incoming) = listener.incoming().next().await {
+      let mut stream = match incoming {
+        Ok(stream) => stream,
+        Err(_) => continue,
+      };
+      if let Err(err) = handshake(&mut stream, secret).await {
+        tracing::warn!("standby handshake: {}", err);
+        continue;
+      }
+
+      tracing::info!("primary node connected");
+      // the primary node sends all its games again
+      self.state.clear_standby_replicas();
+      self.state.replicas().set_primary_connected(true);
+      if let Err(err) = self.recv(&mut stream).await {
+        tracing::error!("primary node disconnected: {}", err);
+      }
+      self.state.replicas().set_primary_connected(false);
+    }
+
+    Ok(())
+  }
+
+  async fn recv(&self, stream: &mut FloStream) -> Result<()> {
+    stream.set_timeout(STANDBY_LINK_TIMEOUT);
+    loop {
+      let frame = stream.recv_frame_timeout().await?;
+      if frame.type_id == PacketTypeId::Ping {
+        continue;
+      }
+      try_flo_packet! {
+        frame => {
+          pkt: PacketStandbyGameCreate => {
+            self.state.handle_standby_game_create(pkt)?;
+          }
+          pkt: PacketStandbyGameUpdate => {
+            self.state.handle_standby_game_update(pkt)?;
+          }
+          pkt: PacketStandbyGameLog => {
+            self.state.handle_standby_game_log(pkt);
+          }
+          pkt: PacketStandbyGameEnd => {
+            self.state.handle_standby_game_end(pkt);
+          }
+        }
+      }
+    }
+  }
+}
+
+async fn handshake(stream: &mut FloStream, secret: &str) -> Result<()> {
+  use std::time::Duration;
+  const RECV_TIMEOUT: Duration = Duration::from_secs(3);
+
+  let connect: PacketStandbyConnect = stream.recv_timeout(RECV_TIMEOUT).await?;
+  if !flo_net::auth::secret_eq(&connect.secret, secret) {
+    return Err(Error::InvalidSecret);
+  }
+  stream.send(PacketStandbyConnectAccept {}).await?;
+  Ok(())
+}
+
+#[test]
+fn test_replica_player_apply() {
+  use flo_net::w3gs::{encode_w3gs_items, W3GSPacketTypeId};
+
+  let packet = W3GSPacket::simple(flo_w3gs::protocol::leave::LeaveAck).unwrap();
+  let items = |sids: std::ops::RangeInclusive<u32>| {
+    let items: Vec<_> = sids
+      .map(|sid| {
+        (
+          W3GSMetadata::new(W3GSPacketTypeId::LeaveAck, sid, None),
+          packet.clone(),
+        )
+      })
+      .collect();
+    encode_w3gs_items(&items).to_vec()
+  };
+  let mut player = ReplicaPlayer::default();
+  player
+    .apply(StandbyPlayerUpdate {
+      player_id: 1,
+      reset: true,
+      sent: items(1..=5),
+      last_send_sid: 5,
+      ack_sent: None,
+      ack_received: Some(2),
+    })
+    .unwrap();
+  assert_eq!(player.pending.len(), 5);
+
+  player
+    .apply(StandbyPlayerUpdate {
+      player_id: 1,
+      reset: false,
+      sent: items(6..=7),
+      last_send_sid: 7,
+      ack_sent: Some(3),
+      ack_received: Some(4),
+    })
+    .unwrap();
+  let sids: Vec<_> = player.pending.iter().map(|(meta, _)| meta.sid()).collect();
+  assert_eq!(sids, vec![4, 5, 6, 7]);
+  assert_eq!(player.ack_received, Some(4));
+
+  // sent again in full after the primary node reconnected
+  player
+    .apply(StandbyPlayerUpdate {
+      player_id: 1,
+      reset: true,
+      sent: items(6..=7),
+      last_send_sid: 7,
+      ack_sent: Some(5),
+      ack_received: Some(4),
+    })
+    .unwrap();
+  assert_eq!(player.pending.len(), 2);
+}
+
+#[test]
+fn test_replica_promote() {
+  let key = GameKey {
+    controller_id: ControllerId::PRIMARY,
+    game_id: 1,
+  };
+  let registry = ReplicaRegistry::new();
+  registry.insert(key, Game::default(), String::new());
+  assert!(!registry.can_resume(key));
+  registry.games.get_mut(&key).unwrap().started = true;
+  assert!(registry.can_resume(key));
+  registry.set_primary_connected(true);
+  assert!(!registry.can_resume(key));
+
+  // players connecting at the same time share one request
+  let (first, mut rx1) = registry.wait_promote(key);
+  assert!(first);
+  let (first, mut rx2) = registry.wait_promote(key);
+  assert!(!first);
+  registry.resolve_promote(key, true);
+  assert_eq!(rx1.try_recv(), Ok(true));
+  assert_eq!(rx2.try_recv(), Ok(true));
+
+  let (first, mut rx) = registry.wait_promote(key);
+  assert!(first);
+  registry.resolve_promote(key, false);
+  assert_eq!(rx.try_recv(), Ok(false));
+  registry.resolve_promote(key, true);
+  assert!(registry.promotes.is_empty());
+}
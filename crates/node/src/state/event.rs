@@ -2,9 +2,8 @@ use tokio::sync::mpsc::{Receiver, Sender};
 
 use flo_event::*;
 
-use crate::controller::ControllerServerHandle;
 use crate::error::*;
-use crate::state::GlobalStateRef;
+use crate::state::{GameKey, GlobalStateRef};
 
 pub type GlobalEventSender = Sender<GlobalEvent>;
 
@@ -12,7 +11,7 @@ pub type GlobalEventSender = Sender<GlobalEvent>;
 #[derive(Debug)]
 pub enum GlobalEvent {
   // A game has ended, remove the session from global state
-  GameEnded(i32),
+  GameEnded(GameKey),
 }
 
 impl FloEvent for GlobalEvent {
@@ -22,7 +21,6 @@ impl FloEvent for GlobalEvent {
 #[derive(Debug)]
 pub struct FloNodeEventContext {
   pub state: GlobalStateRef,
-  pub ctrl: ControllerServerHandle,
 }
 
 pub async fn handle_global_events(
@@ -31,9 +29,14 @@ pub async fn handle_global_events(
 ) -> Result<()> {
   while let Some(event) = event_receiver.recv().await {
     match event {
-      GlobalEvent::GameEnded(game_id) => {
-        tracing::debug!(game_id, "game ended: {}", game_id);
-        ctx.state.end_game(game_id);
+      GlobalEvent::GameEnded(key) => {
+        tracing::debug!(
+          game_id = key.game_id,
+          controller = key.controller_id.name(),
+          "game ended: {}",
+          key.game_id
+        );
+        ctx.state.end_game(key);
       }
     }
   }
@@ -5,17 +5,17 @@ mod types;
 pub use types::*;
 
 use dashmap::DashMap;
-use parking_lot::RwLock;
 use s2_grpc_utils::S2ProtoEnum;
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use flo_net::packet::{FloPacket, Frame, OptionalFieldExt};
 use flo_net::proto::flo_node::{
-  ControllerCreateGameRejectReason, Game, PacketControllerCreateGame,
-  PacketControllerCreateGameAccept, PacketControllerCreateGameReject,
+  ControllerAbortGameRejectReason, ControllerCreateGameRejectReason, Game,
+  PacketControllerAbortGame, PacketControllerAbortGameAccept, PacketControllerAbortGameReject,
+  PacketControllerCreateGame, PacketControllerCreateGameAccept, PacketControllerCreateGameReject,
   PacketControllerUpdateSlotStatus, PacketControllerUpdateSlotStatusAccept,
-  PacketControllerUpdateSlotStatusReject,
+  PacketControllerUpdateSlotStatusReject, PacketStandbyGameCreate, PacketStandbyGameEnd,
+  PacketStandbyGameLog, PacketStandbyGameUpdate,
 };
 
 use crate::controller::ControllerServerHandle;
@@ -23,6 +23,7 @@ use crate::error::*;
 use crate::game::{GameSession, GameSessionHandle, SlotClientStatusUpdateSource};
 use crate::metrics;
 use crate::observer::{ObserverPublisher, ObserverPublisherHandle};
+use crate::standby::{ReplicaRegistry, StandbyHandle, StandbyReplicator};
 
 #[derive(Debug)]
 pub struct GlobalState {
@@ -30,6 +31,8 @@ pub struct GlobalState {
   players: PlayerRegistry,
   games: GameRegistry,
   obs: ObserverPublisher,
+  standby: StandbyReplicator,
+  replicas: ReplicaRegistry,
 }
 
 pub type GlobalStateRef = Arc<GlobalState>;
@@ -41,6 +44,8 @@ impl GlobalState {
       players: PlayerRegistry::new(),
       games: GameRegistry::new(),
       obs: ObserverPublisher::new(),
+      standby: StandbyReplicator::new(),
+      replicas: ReplicaRegistry::new(),
     }
   }
 
@@ -52,13 +57,144 @@ impl GlobalState {
     self.players.get_by_token(token)
   }
 
-  pub fn get_game(&self, id: i32) -> Option<GameSessionHandle> {
-    self.games.get(id)
+  pub fn get_game(&self, key: GameKey) -> Option<GameSessionHandle> {
+    self.games.get(key)
   }
 
-  pub fn end_game(&self, id: i32) {
-    self.players.remove_game(id);
-    self.games.remove(id);
+  pub fn end_game(&self, key: GameKey) {
+    self.players.remove_game(key);
+    self.games.remove(key);
+  }
+
+  pub fn replicas(&self) -> &ReplicaRegistry {
+    &self.replicas
+  }
+
+  /// Takes over a game replicated from the primary node
+  /// if the controller confirms the primary node is down
+  pub async fn promote_replica(&self, key: GameKey) -> Option<GameSessionHandle> {
+    let ctrl = self.replicas.controller(key.controller_id)?;
+    if !self.replicas.can_resume(key) {
+      return None;
+    }
+    if !self.replicas.confirm_primary_down(&ctrl, key).await {
+      tracing::warn!(
+        game_id = key.game_id,
+        controller = key.controller_id.name(),
+        "primary node is not confirmed down, game not taken over"
+      );
+      return None;
+    }
+    let (game, traceparent, resume) = match self.replicas.take_resume(key) {
+      Some(v) => v,
+      // taken over for another player of the game
+      None => return self.games.get(key),
+    };
+    let obs = if key.controller_id == ControllerId::PRIMARY {
+      self.obs.handle()
+    } else {
+      ObserverPublisherHandle::disabled()
+    };
+    let session = match GameSession::resume(
+      game,
+      &traceparent,
+      ctrl,
+      obs,
+      self.event_sender.clone().into(),
+      resume,
+    ) {
+      Ok(session) => session,
+      Err(err) => {
+        tracing::error!(game_id = key.game_id, "resume replicated game: {}", err);
+        return None;
+      }
+    };
+    tracing::warn!(
+      game_id = key.game_id,
+      controller = key.controller_id.name(),
+      "primary node is down, game taken over"
+    );
+    metrics::STANDBY_HANDOVERS.inc();
+    self.games.insert(key, session);
+    self.games.get(key)
+  }
+
+  pub fn handle_standby_game_create(&self, packet: PacketStandbyGameCreate) -> Result<()> {
+    let game = packet.game.extract()?;
+    let controller_id = ControllerId(packet.controller_id as usize);
+    let game_id = game.id;
+    let key = GameKey {
+      controller_id,
+      game_id,
+    };
+
+    if controller_id.0 >= crate::env::Env::get().controllers.len() {
+      tracing::warn!(game_id, "replicated game of an unknown controller");
+      return Ok(());
+    }
+
+    // already taken over
+    if self.games.contains(key) {
+      return Ok(());
+    }
+
+    let pairs = packet
+      .player_tokens
+      .into_iter()
+      .filter_map(|item| {
+        Some((
+          PlayerToken::from_vec(item.token)?,
+          RegisteredPlayer {
+            controller_id,
+            player_id: item.player_id,
+            game_id,
+          },
+        ))
+      })
+      .collect();
+    self.players.register(GamePlayerTokens {
+      key,
+      is_private: game.is_private,
+      pairs,
+    });
+    self.replicas.insert(key, game, packet.traceparent);
+    Ok(())
+  }
+
+  pub fn handle_standby_game_update(&self, packet: PacketStandbyGameUpdate) -> Result<()> {
+    let key = GameKey {
+      controller_id: ControllerId(packet.controller_id as usize),
+      game_id: packet.game_id,
+    };
+    self.replicas.update(key, packet)
+  }
+
+  pub fn handle_standby_game_log(&self, packet: PacketStandbyGameLog) {
+    let key = GameKey {
+      controller_id: ControllerId(packet.controller_id as usize),
+      game_id: packet.game_id,
+    };
+    self.replicas.append_log(key, packet)
+  }
+
+  pub fn handle_standby_game_end(&self, packet: PacketStandbyGameEnd) {
+    self.remove_replica(GameKey {
+      controller_id: ControllerId(packet.controller_id as usize),
+      game_id: packet.game_id,
+    });
+  }
+
+  /// Replicas of the last connection to the primary node
+  pub fn clear_standby_replicas(&self) {
+    for key in self.replicas.keys() {
+      self.remove_replica(key);
+    }
+  }
+
+  fn remove_replica(&self, key: GameKey) {
+    if self.replicas.remove(key) {
+      self.players.remove_game(key);
+    }
   }
 
   pub fn handle_controller_create_game(
@@ -68,7 +204,13 @@ impl GlobalState {
   ) -> Result<Frame> {
     let game = packet.game.extract()?;
 
+    let controller_id = ctrl.controller_id();
     let game_id = game.id;
+    let key = GameKey {
+      controller_id,
+      game_id,
+    };
+    let is_private = game.is_private;
     let player_ids: Vec<i32> = game
       .slots
       .iter()
@@ -79,6 +221,35 @@ impl GlobalState {
       return Err(Error::NoPlayer);
     }
 
+    // reserved-only games, the controller rejects the joins of other players
+    if let Some(player_id) = find_unreserved_player(&game.reserved_player_ids, &player_ids) {
+      tracing::warn!(game_id, player_id, "player without a reserved slot");
+      return Ok(
+        PacketControllerCreateGameReject {
+          game_id,
+          reason: ControllerCreateGameRejectReason::PlayerNotReserved.into(),
+        }
+        .encode_as_frame()?,
+      );
+    }
+
+    if let Some(max_games) = crate::env::Env::get().controllers[controller_id.0].max_games {
+      if !self.games.contains(key) && self.games.count(controller_id) >= max_games {
+        tracing::warn!(
+          game_id,
+          controller = controller_id.name(),
+          "controller game quota reached"
+        );
+        return Ok(
+          PacketControllerCreateGameReject {
+            game_id,
+            reason: ControllerCreateGameRejectReason::NodeFull.into(),
+          }
+          .encode_as_frame()?,
+        );
+      }
+    }
+
     let pending: Vec<(PlayerToken, RegisteredPlayer)> = {
       let players: Vec<_> = game
         .slots
@@ -91,6 +262,7 @@ impl GlobalState {
           (
             PlayerToken::new_uuid(),
             RegisteredPlayer {
+              controller_id,
               player_id: p.player_id,
               game_id,
             },
@@ -99,11 +271,43 @@ impl GlobalState {
         .collect()
     };
 
+    // the observer stream is shared, game ids of other controllers would collide
+    let obs = if controller_id == ControllerId::PRIMARY {
+      self.obs.handle()
+    } else {
+      ObserverPublisherHandle::disabled()
+    };
+
+    let player_tokens: Vec<_> = pending
+      .iter()
+      .map(|(token, player)| flo_net::proto::flo_node::PlayerToken {
+        player_id: player.player_id,
+        token: token.to_vec(),
+      })
+      .collect();
+
+    let standby = if self.standby.is_enabled() {
+      self.standby.handle(
+        key,
+        PacketStandbyGameCreate {
+          controller_id: controller_id.0 as i32,
+          game: Some(game.clone()),
+          traceparent: packet.traceparent.clone(),
+          player_tokens: player_tokens.clone(),
+        },
+      )?
+    } else {
+      StandbyHandle::disabled()
+    };
+
     if let Err(err) = self.games.register(
+      key,
       game,
+      &packet.traceparent,
       ctrl,
-      self.obs.handle(),
+      obs,
       self.event_sender.clone().into(),
+      standby,
     ) {
       let reason = match err {
         Error::GameExists => ControllerCreateGameRejectReason::GameExists,
@@ -118,16 +322,9 @@ impl GlobalState {
       );
     }
 
-    let player_tokens: Vec<_> = pending
-      .iter()
-      .map(|(token, player)| flo_net::proto::flo_node::PlayerToken {
-        player_id: player.player_id,
-        token: token.to_vec(),
-      })
-      .collect();
-
     let stale_pending_players = self.players.register(GamePlayerTokens {
-      game_id,
+      key,
+      is_private,
       pairs: pending,
     });
     if !stale_pending_players.is_empty() {
@@ -151,6 +348,7 @@ impl GlobalState {
 
   pub async fn handle_controller_update_slot_client_status(
     &self,
+    controller_id: ControllerId,
     packet: PacketControllerUpdateSlotStatus,
   ) -> Result<Frame> {
     use flo_net::proto::flo_common::SlotClientStatus;
@@ -174,7 +372,11 @@ impl GlobalState {
       );
     }
 
-    let game = match self.games.get(game_id) {
+    let key = GameKey {
+      controller_id,
+      game_id,
+    };
+    let game = match self.games.get(key) {
       Some(game) => game,
       None => {
         return Ok(
@@ -196,14 +398,18 @@ impl GlobalState {
       )
       .await
     {
-      Ok(_) => Ok(
-        PacketControllerUpdateSlotStatusAccept {
-          player_id,
-          game_id,
-          status: client_status.into(),
-        }
-        .encode_as_frame()?,
-      ),
+      Ok(_) => {
+        // players removed from a private game can't reconnect with their old token
+        self.players.revoke_private(key, player_id);
+        Ok(
+          PacketControllerUpdateSlotStatusAccept {
+            player_id,
+            game_id,
+            status: client_status.into(),
+          }
+          .encode_as_frame()?,
+        )
+      }
       Err(err) => match err {
         Error::InvalidClientStatusTransition(_, _) => Ok(
           PacketControllerUpdateSlotStatusReject {
@@ -227,53 +433,100 @@ impl GlobalState {
       },
     }
   }
+
+  pub async fn handle_controller_abort_game(
+    &self,
+    controller_id: ControllerId,
+    packet: PacketControllerAbortGame,
+  ) -> Result<Frame> {
+    let game_id = packet.game_id;
+    let reject = |reason: ControllerAbortGameRejectReason| {
+      PacketControllerAbortGameReject {
+        game_id,
+        reason: reason.into(),
+      }
+      .encode_as_frame()
+    };
+
+    let game = match self.games.get(GameKey {
+      controller_id,
+      game_id,
+    }) {
+      Some(game) => game,
+      None => return Ok(reject(ControllerAbortGameRejectReason::NotFound)?),
+    };
+
+    match game.abort(&packet.reason).await {
+      Ok(_) => Ok(PacketControllerAbortGameAccept { game_id }.encode_as_frame()?),
+      Err(Error::GameEnded) => Ok(reject(ControllerAbortGameRejectReason::GameEnded)?),
+      Err(err) => {
+        tracing::error!(game_id, "abort game: {}", err);
+        Ok(reject(ControllerAbortGameRejectReason::Unknown)?)
+      }
+    }
+  }
 }
 
+/// Sharded by key, players joining a game don't contend with
+/// the token lookups and registrations of other games
 #[derive(Debug)]
 struct PlayerRegistry {
-  state: RwLock<PlayerTokenRegistryState>,
+  tokens: DashMap<PlayerToken, RegisteredPlayer>,
+  player_token: DashMap<(ControllerId, i32), PlayerToken>,
+  games: DashMap<GameKey, GameTokens>,
 }
 
-#[derive(Debug, Default)]
-struct PlayerTokenRegistryState {
-  map: HashMap<PlayerToken, RegisteredPlayer>,
-  player_token: HashMap<i32, PlayerToken>,
-  // game_id => [(player_id, tokens)]
-  game_tokens: HashMap<i32, Vec<(i32, PlayerToken)>>,
+#[derive(Debug)]
+struct GameTokens {
+  is_private: bool,
+  // [(player_id, token)]
+  tokens: Vec<(i32, PlayerToken)>,
 }
 
 impl PlayerRegistry {
   fn new() -> Self {
     PlayerRegistry {
-      state: RwLock::new(PlayerTokenRegistryState::default()),
+      tokens: DashMap::new(),
+      player_token: DashMap::new(),
+      games: DashMap::new(),
     }
   }
 
   // for controller
   fn register(
     &self,
-    GamePlayerTokens { game_id, pairs }: GamePlayerTokens,
+    GamePlayerTokens {
+      key,
+      is_private,
+      pairs,
+    }: GamePlayerTokens,
   ) -> Vec<RegisteredPlayer> {
-    let mut state = self.state.write();
     let mut stale_players = vec![];
 
-    state.game_tokens.insert(game_id, {
-      pairs
-        .iter()
-        .map(|(token, player)| (player.player_id, token.clone()))
-        .collect()
-    });
+    self.games.insert(
+      key,
+      GameTokens {
+        is_private,
+        tokens: pairs
+          .iter()
+          .map(|(token, player)| (player.player_id, token.clone()))
+          .collect(),
+      },
+    );
 
     for (token, player) in pairs {
       let player_id = player.player_id;
-      let stale_player = if let Some(old) = state.player_token.insert(player_id, token.clone()) {
-        state.map.remove(&old)
+      let stale_player = if let Some(old) = self
+        .player_token
+        .insert((key.controller_id, player_id), token.clone())
+      {
+        self.tokens.remove(&old).map(|(_, player)| player)
       } else {
         tracing::debug!("player token inc: {}: {:?}", player_id, token);
         metrics::PLAYER_TOKENS.inc();
         None
       };
-      state.map.insert(token.clone(), player);
+      self.tokens.insert(token, player);
       if let Some(stale_player) = stale_player {
         stale_players.push(stale_player)
       }
@@ -282,44 +535,56 @@ impl PlayerRegistry {
     stale_players
   }
 
-  fn remove_game(&self, game_id: i32) {
-    let mut state = self.state.write();
+  fn remove_game(&self, key: GameKey) {
     // remove game_id => tokens
-    if let Some(tokens) = state.game_tokens.remove(&game_id) {
-      for (player_id, token) in tokens {
-        use std::collections::hash_map::Entry;
-        // remove token => player
-        if state.map.remove(&token).is_some() {
-          tracing::debug!("player token dec: {}: {:?}", player_id, token);
-          metrics::PLAYER_TOKENS.dec();
-        }
-        // remote player_id => token
-        match state.player_token.entry(player_id) {
-          Entry::Occupied(entry) => {
-            if entry.get() == &token {
-              entry.remove();
-            }
-          }
-          Entry::Vacant(_) => {}
-        }
+    if let Some((_, game)) = self.games.remove(&key) {
+      for (player_id, token) in game.tokens {
+        self.remove_token(key.controller_id, player_id, &token);
       }
     }
   }
 
+  fn revoke_private(&self, key: GameKey, player_id: i32) {
+    let token = {
+      let mut game = match self.games.get_mut(&key) {
+        Some(game) if game.is_private => game,
+        _ => return,
+      };
+      match game.tokens.iter().position(|(id, _)| *id == player_id) {
+        Some(idx) => game.tokens.remove(idx).1,
+        None => return,
+      }
+    };
+    self.remove_token(key.controller_id, player_id, &token);
+  }
+
+  fn remove_token(&self, controller_id: ControllerId, player_id: i32, token: &PlayerToken) {
+    // remove token => player
+    if self.tokens.remove(token).is_some() {
+      tracing::debug!("player token dec: {}: {:?}", player_id, token);
+      metrics::PLAYER_TOKENS.dec();
+    }
+    // remove player_id => token
+    self
+      .player_token
+      .remove_if(&(controller_id, player_id), |_, current| current == token);
+  }
+
   pub fn get_by_token(&self, token: &PlayerToken) -> Option<RegisteredPlayer> {
-    self.state.read().map.get(&token).cloned()
+    self.tokens.get(token).map(|r| r.value().clone())
   }
 }
 
 #[derive(Debug)]
 struct GamePlayerTokens {
-  game_id: i32,
+  key: GameKey,
+  is_private: bool,
   pairs: Vec<(PlayerToken, RegisteredPlayer)>,
 }
 
 #[derive(Debug)]
 struct GameRegistry {
-  map: DashMap<i32, GameSession>,
+  map: DashMap<GameKey, GameSession>,
 }
 
 impl GameRegistry {
@@ -332,31 +597,140 @@ impl GameRegistry {
   // for controller
   fn register(
     &self,
+    key: GameKey,
     game: Game,
+    traceparent: &str,
     ctrl: ControllerServerHandle,
     obs: ObserverPublisherHandle,
     g_event_sender: GlobalEventSender,
+    standby: StandbyHandle,
   ) -> Result<()> {
+    if self.map.contains_key(&key) {
+      return Ok(());
+    }
+
+    // created outside of the entry, which holds the shard lock
+    let session = GameSession::new(game, traceparent, ctrl, obs, g_event_sender, standby)?;
+    self.insert(key, session);
+    Ok(())
+  }
+
+  fn insert(&self, key: GameKey, session: GameSession) {
     use dashmap::mapref::entry::Entry;
-    let game_id = game.id;
 
-    match self.map.entry(game_id) {
+    match self.map.entry(key) {
       Entry::Vacant(entry) => {
-        entry.insert(GameSession::new(game, ctrl, obs, g_event_sender)?);
+        entry.insert(session);
         metrics::GAME_SESSIONS.inc();
+        metrics::CONTROLLER_GAME_SESSIONS
+          .with_label_values(&[key.controller_id.name()])
+          .inc();
       }
       Entry::Occupied(_) => {}
     }
-    Ok(())
   }
 
-  fn get(&self, game_id: i32) -> Option<GameSessionHandle> {
-    self.map.get(&game_id).map(|r| r.value().handle())
+  fn get(&self, key: GameKey) -> Option<GameSessionHandle> {
+    self.map.get(&key).map(|r| r.value().handle())
+  }
+
+  fn contains(&self, key: GameKey) -> bool {
+    self.map.contains_key(&key)
+  }
+
+  fn count(&self, controller_id: ControllerId) -> usize {
+    self
+      .map
+      .iter()
+      .filter(|r| r.key().controller_id == controller_id)
+      .count()
   }
 
-  fn remove(&self, id: i32) {
-    if let Some(_) = self.map.remove(&id) {
+  fn remove(&self, key: GameKey) {
+    if let Some(_) = self.map.remove(&key) {
       metrics::GAME_SESSIONS.dec();
+      metrics::CONTROLLER_GAME_SESSIONS
+        .with_label_values(&[key.controller_id.name()])
+        .dec();
     }
   }
 }
+
+/// Returns a player not in the reserved list, an empty list accepts anyone
+fn find_unreserved_player(reserved_player_ids: &[i32], player_ids: &[i32]) -> Option<i32> {
+  if reserved_player_ids.is_empty() {
+    return None;
+  }
+  player_ids
+    .iter()
+    .find(|id| !reserved_player_ids.contains(id))
+    .cloned()
+}
+
+#[test]
+fn test_find_unreserved_player() {
+  assert_eq!(find_unreserved_player(&[], &[1, 2]), None);
+  assert_eq!(find_unreserved_player(&[1, 2, 3], &[1, 2]), None);
+  assert_eq!(find_unreserved_player(&[1, 3], &[1, 2, 3]), Some(2));
+}
+
+#[test]
+fn test_player_registry() {
+  let player = |player_id: i32, game_id: i32| RegisteredPlayer {
+    controller_id: ControllerId::PRIMARY,
+    player_id,
+    game_id,
+  };
+  let key = |game_id: i32| GameKey {
+    controller_id: ControllerId::PRIMARY,
+    game_id,
+  };
+  let registry = PlayerRegistry::new();
+  let (t1, t2) = (PlayerToken::new_uuid(), PlayerToken::new_uuid());
+  let stale = registry.register(GamePlayerTokens {
+    key: key(1),
+    is_private: true,
+    pairs: vec![(t1.clone(), player(1, 1)), (t2.clone(), player(2, 1))],
+  });
+  assert!(stale.is_empty());
+
+  // player 1 moved to another game
+  let t3 = PlayerToken::new_uuid();
+  let stale = registry.register(GamePlayerTokens {
+    key: key(2),
+    is_private: false,
+    pairs: vec![(t3.clone(), player(1, 2))],
+  });
+  assert_eq!(stale.len(), 1);
+  assert!(registry.get_by_token(&t1).is_none());
+  assert_eq!(registry.get_by_token(&t3).map(|p| p.game_id), Some(2));
+
+  registry.revoke_private(key(1), 2);
+  assert!(registry.get_by_token(&t2).is_none());
+
+  // the token of game 2 is kept
+  registry.remove_game(key(1));
+  assert_eq!(registry.get_by_token(&t3).map(|p| p.game_id), Some(2));
+  registry.remove_game(key(2));
+  assert!(registry.get_by_token(&t3).is_none());
+  assert!(registry.player_token.is_empty());
+
+  // the same player id in a game of another controller
+  let t4 = PlayerToken::new_uuid();
+  let other = RegisteredPlayer {
+    controller_id: ControllerId(1),
+    ..player(1, 1)
+  };
+  registry.register(GamePlayerTokens {
+    key: other.game_key(),
+    is_private: false,
+    pairs: vec![(t4.clone(), other)],
+  });
+  let stale = registry.register(GamePlayerTokens {
+    key: key(1),
+    is_private: false,
+    pairs: vec![(PlayerToken::new_uuid(), player(1, 1))],
+  });
+  assert!(stale.is_empty());
+  assert!(registry.get_by_token(&t4).is_some());
+}
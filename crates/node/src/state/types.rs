@@ -25,6 +25,39 @@ impl PlayerToken {
 
 #[derive(Debug, Clone)]
 pub struct RegisteredPlayer {
+  pub controller_id: ControllerId,
   pub player_id: i32,
   pub game_id: i32,
 }
+
+impl RegisteredPlayer {
+  pub fn game_key(&self) -> GameKey {
+    GameKey {
+      controller_id: self.controller_id,
+      game_id: self.game_id,
+    }
+  }
+}
+
+/// Index of a controller in `Env::controllers`
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+pub struct ControllerId(pub usize);
+
+impl ControllerId {
+  pub const PRIMARY: ControllerId = ControllerId(0);
+
+  pub fn name(&self) -> &'static str {
+    crate::env::Env::get()
+      .controllers
+      .get(self.0)
+      .map(|c| c.name.as_str())
+      .unwrap_or_default()
+  }
+}
+
+/// Game ids are only unique within a controller
+#[derive(Debug, PartialEq, Hash, Eq, Clone, Copy)]
+pub struct GameKey {
+  pub controller_id: ControllerId,
+  pub game_id: i32,
+}
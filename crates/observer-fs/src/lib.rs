@@ -313,6 +313,31 @@ impl GameDataArchiveReader {
   }
 }
 
+/// Writes records as an archive without going through the data folder,
+/// for archives built from other sources such as replays
+pub fn write_archive<'a, I, W>(game_id: i32, records: I, w: W) -> Result<()>
+where
+  I: IntoIterator<Item = &'a GameRecordData>,
+  W: std::io::Write,
+{
+  use flate2::write::GzEncoder;
+  use flate2::Compression;
+  use std::io::prelude::*;
+  let mut encoder = GzEncoder::new(w, Compression::default());
+  encoder.write_all(&FileHeader::new(game_id).bytes())?;
+  let mut buf = BytesMut::with_capacity(MAX_CHUNK_SIZE);
+  for record in records {
+    record.encode(&mut buf);
+    if buf.len() >= MAX_CHUNK_SIZE {
+      encoder.write_all(&buf)?;
+      buf.clear();
+    }
+  }
+  encoder.write_all(&buf)?;
+  encoder.finish()?;
+  Ok(())
+}
+
 pub struct GameDataReaderRecords {
   inner: GameDataReaderRecordsInner,
   current_chunk: Option<usize>,
@@ -460,3 +485,20 @@ async fn test_fs() {
   assert_eq!(records.len(), N);
   validate_records(records);
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_write_archive() {
+  const N: usize = 10000;
+  let records: Vec<_> = (0..N)
+    .map(|id| GameRecordData::StopLag(id as i32))
+    .collect();
+
+  let mut buf = vec![];
+  write_archive(1, &records, &mut buf).unwrap();
+
+  let r = GameDataArchiveReader::open_bytes(&buf).await.unwrap();
+  assert_eq!(r.game_id(), 1);
+  let items = r.records().collect_vec().await.unwrap();
+  assert_eq!(items.len(), N);
+  assert!(matches!(items[N - 1], GameRecordData::StopLag(id) if id as usize == N - 1));
+}
@@ -0,0 +1,108 @@
+//! Python bindings of the replay parser and the game timeline,
+//! build with `maturin build -m crates/python/Cargo.toml`.
+//! Results are plain lists and dicts that load directly into pandas,
+//! e.g. `pandas.DataFrame(flo_py.analyze_replay(path)["players"])`.
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use flo_events::{GameEvent, GameEventBuilder};
+use flo_observer_fs::GameDataArchiveReader;
+use flo_w3replay::analysis::analyze;
+use flo_w3replay::W3Replay;
+
+/// Analyzes a `.w3g` file, returns the game name, duration,
+/// per player APM curves and production events, and the chat log
+#[pyfunction]
+fn analyze_replay(py: Python<'_>, path: &str) -> PyResult<PyObject> {
+  let analysis = py
+    .allow_threads(|| analyze(W3Replay::open(path)?))
+    .map_err(|err| match err {
+      flo_w3replay::error::Error::Io(err) => PyIOError::new_err(err.to_string()),
+      err => PyValueError::new_err(err.to_string()),
+    })?;
+  to_py(py, &to_value(&analysis)?)
+}
+
+/// Builds the event timeline of a flo game archive.
+///
+/// `slot_players` maps in-game player ids (`slot index + 1`) to flo player ids,
+/// player ids of the events are `None` without it.
+#[pyfunction]
+#[pyo3(signature = (path, slot_players = None, action_summary_interval_ms = None))]
+fn load_timeline(
+  py: Python<'_>,
+  path: &str,
+  slot_players: Option<BTreeMap<u8, i32>>,
+  action_summary_interval_ms: Option<u32>,
+) -> PyResult<PyObject> {
+  let events = py
+    .allow_threads(|| -> flo_observer_fs::error::Result<Vec<GameEvent>> {
+      // the archive reader blocks in place, which requires a multi-thread runtime
+      let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .build()?;
+      rt.block_on(async {
+        let mut records = GameDataArchiveReader::open(path).await?.records();
+        let mut builder = GameEventBuilder::new(slot_players.unwrap_or_default());
+        if let Some(interval_ms) = action_summary_interval_ms {
+          builder = builder.with_action_summary(interval_ms);
+        }
+        while let Some(record) = records.next().await? {
+          builder.put_record(&record);
+        }
+        Ok(builder.finish())
+      })
+    })
+    .map_err(|err| match err {
+      flo_observer_fs::error::Error::Io(err) => PyIOError::new_err(err.to_string()),
+      err => PyValueError::new_err(err.to_string()),
+    })?;
+  to_py(py, &to_value(&events)?)
+}
+
+#[pymodule]
+fn flo_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_function(wrap_pyfunction!(analyze_replay, m)?)?;
+  m.add_function(wrap_pyfunction!(load_timeline, m)?)?;
+  Ok(())
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> PyResult<Value> {
+  serde_json::to_value(value).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+  let obj = match value {
+    Value::Null => py.None(),
+    Value::Bool(v) => v.to_object(py),
+    Value::Number(v) => {
+      if let Some(v) = v.as_i64() {
+        v.to_object(py)
+      } else if let Some(v) = v.as_u64() {
+        v.to_object(py)
+      } else {
+        v.as_f64().unwrap_or_default().to_object(py)
+      }
+    }
+    Value::String(v) => v.to_object(py),
+    Value::Array(items) => {
+      let list = PyList::empty_bound(py);
+      for item in items {
+        list.append(to_py(py, item)?)?;
+      }
+      list.into_any().unbind()
+    }
+    Value::Object(map) => {
+      let dict = PyDict::new_bound(py);
+      for (key, value) in map {
+        dict.set_item(key, to_py(py, value)?)?;
+      }
+      dict.into_any().unbind()
+    }
+  };
+  Ok(obj)
+}
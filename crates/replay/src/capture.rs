@@ -0,0 +1,285 @@
+//! Converts `.w3g` replays to flo game records,
+//! the reverse of [`generate_replay_from_records`](crate::generate_replay_from_records).
+//! Replay player ids are remapped to `slot index + 1` like in flo games.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use flo_observer::record::GameRecordData;
+use flo_types::game::{SlotSettings, SlotStatus};
+use flo_types::observer::{GameInfo, Map, PlayerInfo, Slot};
+use flo_w3gs::action::{IncomingAction, IncomingAction2, PlayerAction};
+use flo_w3gs::packet::Packet;
+use flo_w3gs::protocol::chat::{ChatFromHost, ChatToHost};
+use flo_w3gs::protocol::leave::PlayerLeft;
+use flo_w3replay::{Header, Record, ReplayDecoder, SlotInfo, TimeSlot};
+
+use crate::error::{Error, Result};
+use crate::{index_to_player_id, FLO_OB_SLOT, FLO_PLAYER_ID};
+
+pub struct ReplayToCaptureOptions {
+  pub game_id: i32,
+  /// Defaults to the version in the replay header,
+  /// watching in-game requires the exact version of the local client
+  pub game_version: Option<String>,
+}
+
+/// A game decoded from a replay, can be written as an observer archive
+/// with [`flo_observer_fs::write_archive`]
+#[derive(Debug)]
+pub struct ReplayCapture {
+  pub game: GameInfo,
+  pub records: Vec<GameRecordData>,
+}
+
+pub fn replay_to_capture<R: Read>(options: ReplayToCaptureOptions, r: R) -> Result<ReplayCapture> {
+  let decoder = ReplayDecoder::new(r)?;
+  let game_version = options
+    .game_version
+    .unwrap_or_else(|| get_game_version(decoder.header()));
+
+  let mut game_info = None;
+  let mut player_names = BTreeMap::new();
+  let mut converter = None;
+
+  for record in decoder.into_records() {
+    let record = record?;
+    if let Some(ref mut converter) = converter {
+      converter.put_record(record)?;
+      continue;
+    }
+    match record {
+      Record::GameInfo(info) => {
+        player_names.insert(
+          info.host_player_info.id,
+          info.host_player_info.name.to_string_lossy().to_string(),
+        );
+        game_info = Some(info);
+      }
+      Record::PlayerInfo(info) => {
+        player_names.insert(
+          info.player_info.id,
+          info.player_info.name.to_string_lossy().to_string(),
+        );
+      }
+      Record::SlotInfo(slots) => {
+        let info = game_info.take().ok_or(Error::NoGameInfoRecord)?;
+        converter = Some(RecordConverter::new(
+          options.game_id,
+          &game_version,
+          &info,
+          &slots,
+          &player_names,
+        ));
+      }
+      _ => {}
+    }
+  }
+
+  let mut converter = converter.ok_or(Error::NoSlotInfoRecord)?;
+  converter.records.push(GameRecordData::GameEnd);
+
+  Ok(ReplayCapture {
+    game: converter.game,
+    records: converter.records,
+  })
+}
+
+struct RecordConverter {
+  game: GameInfo,
+  /// replay player id -> in-game player id
+  player_ids: BTreeMap<u8, u8>,
+  tick: u32,
+  records: Vec<GameRecordData>,
+}
+
+impl RecordConverter {
+  fn new(
+    game_id: i32,
+    game_version: &str,
+    info: &flo_w3replay::GameInfo,
+    slot_info: &SlotInfo,
+    player_names: &BTreeMap<u8, String>,
+  ) -> Self {
+    let mut player_ids = BTreeMap::new();
+    let slots = slot_info
+      .slots()
+      .iter()
+      .enumerate()
+      .map(|(idx, slot)| {
+        use flo_w3gs::slot::SlotStatus as W3GSSlotStatus;
+        let name = player_names.get(&slot.player_id);
+        // the observer slot added by flo replays
+        let is_flo_ob =
+          idx == FLO_OB_SLOT && slot.team == 24 && name.map(|v| v == "FLO").unwrap_or_default();
+        let status = match slot.slot_status {
+          W3GSSlotStatus::Occupied if !is_flo_ob => SlotStatus::Occupied,
+          W3GSSlotStatus::Closed => SlotStatus::Closed,
+          _ => SlotStatus::Open,
+        };
+        let player = if status == SlotStatus::Occupied && !slot.computer {
+          let id = index_to_player_id(idx);
+          player_ids.insert(slot.player_id, id);
+          Some(PlayerInfo {
+            id: id as i32,
+            name: name.cloned().unwrap_or_default(),
+          })
+        } else {
+          None
+        };
+        Slot {
+          player,
+          settings: SlotSettings {
+            team: slot.team as i32,
+            color: slot.color as i32,
+            computer: slot.computer_type.into(),
+            handicap: slot.handicap as i32,
+            status,
+            race: slot.race.into(),
+          },
+        }
+      })
+      .collect::<Vec<_>>();
+
+    let settings = &info.game_settings;
+    let game = GameInfo {
+      id: game_id,
+      name: info.game_name.to_string_lossy().to_string(),
+      map: Map {
+        sha1: settings.map_sha1.to_vec(),
+        checksum: settings.map_checksum,
+        path: settings.map_path.to_string_lossy().to_string(),
+        twelve_p: slots.len() <= 12,
+      },
+      slots,
+      random_seed: i32::from_le_bytes(slot_info.random_seed.to_le_bytes()),
+      game_version: game_version.to_string(),
+      start_time_millis: 0,
+    };
+
+    Self {
+      game,
+      player_ids,
+      tick: 0,
+      records: vec![],
+    }
+  }
+
+  fn put_record(&mut self, record: Record) -> Result<()> {
+    let packet = match record {
+      Record::TimeSlot(slot) => Packet::with_payload(IncomingAction(self.time_slot(slot)))?,
+      Record::TimeSlotFragment(slot) => {
+        Packet::with_payload(IncomingAction2(self.time_slot(slot.0)))?
+      }
+      Record::ChatMessage(msg) => {
+        let mut to_players: Vec<u8> = self.player_ids.values().cloned().collect();
+        if !to_players.contains(&FLO_PLAYER_ID) {
+          to_players.push(FLO_PLAYER_ID);
+        }
+        Packet::simple(ChatFromHost(ChatToHost {
+          to_players_len: to_players.len() as u8,
+          to_players,
+          from_player: self.player_id(msg.player_id),
+          message: msg.message,
+        }))?
+      }
+      Record::PlayerLeft(left) => Packet::simple(PlayerLeft {
+        player_id: self.player_id(left.player_id),
+        reason: left.reason,
+      })?,
+      Record::TimeSlotAck(ack) => {
+        self.records.push(GameRecordData::TickChecksum {
+          tick: self.tick,
+          checksum: ack.checksum,
+        });
+        self.tick += 1;
+        return Ok(());
+      }
+      _ => return Ok(()),
+    };
+    self.records.push(GameRecordData::W3GS(packet));
+    Ok(())
+  }
+
+  fn player_id(&self, replay_player_id: u8) -> u8 {
+    self
+      .player_ids
+      .get(&replay_player_id)
+      .cloned()
+      .unwrap_or(replay_player_id)
+  }
+
+  fn time_slot(&self, slot: TimeSlot) -> flo_w3gs::protocol::action::TimeSlot {
+    flo_w3gs::protocol::action::TimeSlot {
+      time_increment_ms: slot.time_increment_ms,
+      actions: slot
+        .actions
+        .into_iter()
+        .map(|action| PlayerAction {
+          player_id: self.player_id(action.player_id),
+          data: action.data,
+        })
+        .collect(),
+    }
+  }
+}
+
+// e.g. 10036 -> 1.36
+fn get_game_version(header: &Header) -> String {
+  format!(
+    "1.{}.0.{}",
+    header.game_version.version.saturating_sub(10000),
+    header.game_version.build_number
+  )
+}
+
+#[test]
+fn test_replay_to_capture() {
+  use crate::{generate_replay_from_records, ReplayChatPolicy};
+  use flo_w3replay::W3Replay;
+  use std::io::Cursor;
+
+  let path = flo_util::sample_path!("replay", "grubby_happy.w3g");
+  let capture = replay_to_capture(
+    ReplayToCaptureOptions {
+      game_id: 1,
+      game_version: None,
+    },
+    std::fs::File::open(&path).unwrap(),
+  )
+  .unwrap();
+
+  let players = capture
+    .game
+    .slots
+    .iter()
+    .filter(|slot| slot.player.is_some())
+    .count();
+  assert!(players > 0);
+  assert!(matches!(
+    capture.records.last(),
+    Some(GameRecordData::GameEnd)
+  ));
+
+  let (_, original) = W3Replay::inspect(&path).unwrap();
+  let time_slots = original
+    .filter(|r| matches!(r, Ok(Record::TimeSlot(_))))
+    .count();
+
+  let mut buf = Cursor::new(vec![]);
+  generate_replay_from_records(
+    capture.game,
+    capture.records,
+    ReplayChatPolicy::IncludeAllChats,
+    None,
+    &mut buf,
+  )
+  .unwrap();
+
+  let generated = ReplayDecoder::new(Cursor::new(buf.into_inner())).unwrap();
+  let generated_time_slots = generated
+    .into_records()
+    .filter(|r| matches!(r, Ok(Record::TimeSlot(_))))
+    .count();
+  assert_eq!(time_slots, generated_time_slots);
+}
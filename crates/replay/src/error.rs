@@ -6,10 +6,16 @@ pub enum Error {
   GameHasNoPlayer,
   #[error("flo observer slot occupied")]
   FloObserverSlotOccupied,
+  #[error("Replay has no game info record")]
+  NoGameInfoRecord,
+  #[error("Replay has no slot info record")]
+  NoSlotInfoRecord,
   #[error("w3gs: {0}")]
   W3GS(#[from] flo_w3gs::error::Error),
   #[error("observer fs: {0}")]
   ObserverFs(#[from] flo_observer_fs::error::Error),
+  #[error("game record: {0}")]
+  GameRecord(#[from] flo_observer::record::RecordError),
   #[error("w3replay: {0}")]
   W3Replay(#[from] flo_w3replay::error::Error),
 }
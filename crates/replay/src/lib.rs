@@ -1,5 +1,6 @@
+pub mod capture;
 pub mod error;
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use error::{Error, Result};
 
 use flo_net::w3gs::W3GSPacketTypeId;
@@ -10,20 +11,23 @@ use flo_types::observer::Slot;
 use flo_w3gs::leave::LeaveReason;
 use flo_w3gs::packet::Packet;
 use flo_w3gs::player::{PlayerProfileMessage, PlayerSkinsMessage, PlayerUnknown5Message};
-use flo_w3replay::Record;
 use flo_w3replay::{
   GameInfo, PlayerChatMessage, PlayerInfo, PlayerLeft, ProtoBufPayload, RacePref, ReplayEncoder,
-  SlotInfo, TimeSlot, TimeSlotAck,
+  SlotInfo, TimeSlot, TimeSlotAck, TimeSlotFragment,
 };
+use flo_w3replay::{Record, RecordTypeId};
 use std::io::{Seek, Write};
 
 const FLO_OB_SLOT: usize = 23;
 const FLO_PLAYER_ID: u8 = index_to_player_id(FLO_OB_SLOT);
 
+pub use flo_w3replay::{FloMetadata, FloMetadataPlayer};
+
 pub struct GenerateReplayOptions {
   pub game: flo_types::observer::GameInfo,
   pub archive: Bytes,
   pub chat_policy: ReplayChatPolicy,
+  pub metadata: Option<FloMetadata>,
 }
 
 fn regenerate_game_info(
@@ -267,7 +271,10 @@ fn build_initial_records(
   Ok(records)
 }
 
-fn initialize_replay(game: &flo_types::observer::GameInfo) -> Result<(Vec<Record>, Vec<u8>)> {
+fn initialize_replay(
+  game: &flo_types::observer::GameInfo,
+  metadata: Option<&FloMetadata>,
+) -> Result<(Vec<Record>, Vec<u8>)> {
   let (game_info, occupied_slots, first_player_id, first_player_name) = regenerate_game_info(game)?;
 
   let flo_ob_occupied = check_flo_ob_slot_not_occupied(&occupied_slots)?;
@@ -275,7 +282,7 @@ fn initialize_replay(game: &flo_types::observer::GameInfo) -> Result<(Vec<Record
   let (player_infos, player_skins, player_profiles, active_player_ids) =
     fill_out_player_details(game)?;
 
-  let records = build_initial_records(
+  let mut records = build_initial_records(
     game_info,
     player_infos,
     player_skins,
@@ -287,6 +294,15 @@ fn initialize_replay(game: &flo_types::observer::GameInfo) -> Result<(Vec<Record
     &occupied_slots,
   )?;
 
+  if let Some(metadata) = metadata {
+    // next to the other protobuf records
+    let index = records
+      .iter()
+      .position(|r| r.type_id() == RecordTypeId::SlotInfo)
+      .unwrap_or(records.len());
+    records.insert(index, metadata.to_record());
+  }
+
   Ok((records, active_player_ids))
 }
 
@@ -345,6 +361,14 @@ fn convert_packet_to_record(
       });
       (Some(record), None)
     }
+    W3GSPacketTypeId::IncomingAction2 => {
+      let payload: flo_w3gs::protocol::action::IncomingAction2 = p.decode_payload()?;
+      let record = Record::TimeSlotFragment(TimeSlotFragment(TimeSlot {
+        time_increment_ms: payload.0.time_increment_ms,
+        actions: payload.0.actions,
+      }));
+      (Some(record), None)
+    }
     _ => (None, None),
   };
 
@@ -367,7 +391,7 @@ pub async fn generate_replay_from_packets<W>(
 where
   W: Write + Seek,
 {
-  let (mut records, mut active_player_ids) = initialize_replay(&game)?;
+  let (mut records, mut active_player_ids) = initialize_replay(&game, None)?;
   for packet in packets.into_iter() {
     let (record, dropped_player_id) = convert_packet_to_record(packet, chat_policy)?;
     if let Some(rec) = record {
@@ -389,14 +413,13 @@ pub async fn generate_replay<W>(
     game,
     archive,
     chat_policy,
+    metadata,
   }: GenerateReplayOptions,
   w: W,
 ) -> Result<()>
 where
   W: Write + Seek,
 {
-  let (mut records, mut active_player_ids) = initialize_replay(&game)?;
-
   let rdr = GameDataArchiveReader::open_bytes(&archive).await?;
   let archive_records = rdr.records().collect_vec().await?;
 
@@ -406,8 +429,32 @@ where
     archive_records.len()
   );
 
-  // archive records
-  for r in archive_records {
+  generate_replay_from_records(game, archive_records, chat_policy, metadata.as_ref(), w)
+}
+
+/// Decodes a game log recorded by the node,
+/// the log is a sequence of encoded [`GameRecordData`]
+pub fn decode_game_log(mut log: Bytes) -> Result<Vec<GameRecordData>> {
+  let mut records = vec![];
+  while log.has_remaining() {
+    records.push(GameRecordData::decode(&mut log)?);
+  }
+  Ok(records)
+}
+
+pub fn generate_replay_from_records<W>(
+  game: flo_types::observer::GameInfo,
+  game_records: Vec<GameRecordData>,
+  chat_policy: ReplayChatPolicy,
+  metadata: Option<&FloMetadata>,
+  w: W,
+) -> Result<()>
+where
+  W: Write + Seek,
+{
+  let (mut records, mut active_player_ids) = initialize_replay(&game, metadata)?;
+
+  for r in game_records {
     match r {
       GameRecordData::W3GS(p) => {
         let (record, dropped_player_id) = convert_packet_to_record(p, chat_policy)?;
@@ -1,2 +1,15 @@
 mod spawn_scope;
 pub use spawn_scope::{SpawnScope, SpawnScopeHandle};
+mod watchdog;
+pub use watchdog::{watchdog, Feeder, Watchdog};
+
+#[cfg(feature = "sim")]
+pub mod sim;
+
+/// Lets the simulation reschedule the current task here, see `sim`.
+/// A no-op without the `sim` feature.
+#[inline]
+pub async fn yield_point() {
+  #[cfg(feature = "sim")]
+  sim::yield_point().await
+}
@@ -0,0 +1,110 @@
+//! Deterministic simulation of the node and the client.
+//!
+//! `run` drives a future on a single thread with the tokio clock paused,
+//! timers fire as soon as every task is idle, so a 10 minute game runs in
+//! milliseconds and tick timing no longer depends on the machine.
+//! Interleavings are chosen by the seed: each `yield_point` reschedules the
+//! current task a seeded number of times, a failing seed replays the same ordering.
+//!
+//! With `RUSTFLAGS="--cfg tokio_unstable"` the seed is also used for `tokio::select!`
+//! branch order, otherwise that stays random.
+
+use std::cell::Cell;
+use std::future::Future;
+
+/// Upper bound of the reschedules at a single yield point
+const MAX_YIELDS: u64 = 3;
+
+thread_local! {
+  static RNG: Cell<Option<u64>> = Cell::new(None);
+}
+
+/// Runs `future` to completion under virtual time with seeded scheduling
+pub fn run<F: Future>(seed: u64, future: F) -> F::Output {
+  let mut builder = tokio::runtime::Builder::new_current_thread();
+  builder.enable_all().start_paused(true);
+  #[cfg(tokio_unstable)]
+  builder.rng_seed(tokio::runtime::RngSeed::from_bytes(&seed.to_le_bytes()));
+  let rt = builder.build().expect("build simulation runtime");
+
+  let prev = RNG.with(|rng| rng.replace(Some(seed)));
+  let output = rt.block_on(future);
+  RNG.with(|rng| rng.set(prev));
+  output
+}
+
+/// Whether the current thread is running a simulation
+pub fn is_active() -> bool {
+  RNG.with(|rng| rng.get().is_some())
+}
+
+/// Next value of the simulation's random sequence, `None` outside of `run`
+pub fn next_u64() -> Option<u64> {
+  RNG.with(|rng| {
+    let state = rng.get()?.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    rng.set(Some(state));
+    Some(splitmix64(state))
+  })
+}
+
+pub(crate) async fn yield_point() {
+  let n = match next_u64() {
+    Some(v) => v % (MAX_YIELDS + 1),
+    None => return,
+  };
+  for _ in 0..n {
+    tokio::task::yield_now().await;
+  }
+}
+
+fn splitmix64(mut z: u64) -> u64 {
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  z ^ (z >> 31)
+}
+
+#[test]
+fn test_sim_virtual_time() {
+  use std::time::Duration;
+  use tokio::time::Instant;
+
+  let started = std::time::Instant::now();
+  let elapsed = run(1, async {
+    let t = Instant::now();
+    tokio::time::sleep(Duration::from_secs(600)).await;
+    t.elapsed()
+  });
+  assert!(elapsed >= Duration::from_secs(600));
+  assert!(started.elapsed() < Duration::from_secs(10));
+  assert!(!is_active());
+}
+
+#[test]
+fn test_sim_seeded_scheduling() {
+  use std::sync::{Arc, Mutex};
+
+  fn trace(seed: u64) -> Vec<u8> {
+    run(seed, async {
+      let trace = Arc::new(Mutex::new(vec![]));
+      let tasks: Vec<_> = (0..4_u8)
+        .map(|id| {
+          let trace = trace.clone();
+          tokio::spawn(async move {
+            for _ in 0..8 {
+              crate::yield_point().await;
+              trace.lock().unwrap().push(id);
+            }
+          })
+        })
+        .collect();
+      for task in tasks {
+        task.await.unwrap();
+      }
+      let trace = trace.lock().unwrap().clone();
+      trace
+    })
+  }
+
+  assert_eq!(trace(7), trace(7));
+  assert!((0..16).map(trace).any(|v| v != trace(7)));
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+use tokio::sync::watch::{channel, Receiver, Sender};
+use tokio::time::{sleep_until, Instant};
+
+/// Creates a watchdog that fires if `Feeder::feed` isn't called for `timeout`.
+/// `initial` is the progress reported if the loop stalls before its first feed.
+pub fn watchdog<T>(timeout: Duration, initial: T) -> (Feeder<T>, Watchdog<T>) {
+  let (tx, rx) = channel(initial);
+  (
+    Feeder(tx),
+    Watchdog {
+      rx,
+      timeout,
+      deadline: Instant::now() + timeout,
+    },
+  )
+}
+
+/// Held by the watched loop, records its progress
+#[derive(Debug)]
+pub struct Feeder<T>(Sender<T>);
+
+impl<T> Feeder<T> {
+  pub fn feed(&self, update: impl FnOnce(&mut T)) {
+    self.0.send_modify(update)
+  }
+}
+
+/// Watches a loop from another task
+#[derive(Debug)]
+pub struct Watchdog<T> {
+  rx: Receiver<T>,
+  timeout: Duration,
+  deadline: Instant,
+}
+
+impl<T: Clone> Watchdog<T> {
+  pub fn timeout(&self) -> Duration {
+    self.timeout
+  }
+
+  /// Resolves with the last progress once the loop stopped feeding for `timeout`,
+  /// `None` after the `Feeder` is dropped.
+  /// Cancel safe, fires again after another `timeout` without a feed.
+  pub async fn stalled(&mut self) -> Option<T> {
+    loop {
+      tokio::select! {
+        res = self.rx.changed() => {
+          res.ok()?;
+          self.deadline = Instant::now() + self.timeout;
+        }
+        _ = sleep_until(self.deadline) => {
+          self.deadline = Instant::now() + self.timeout;
+          return Some(self.rx.borrow().clone())
+        }
+      }
+    }
+  }
+}
+
+#[tokio::test]
+async fn test_watchdog() {
+  use tokio::time::sleep;
+
+  let (feeder, mut watchdog) = watchdog(Duration::from_millis(100), 0);
+  let task = tokio::spawn(async move {
+    for tick in 1..=5 {
+      sleep(Duration::from_millis(20)).await;
+      feeder.feed(|v| *v = tick);
+    }
+    sleep(Duration::from_millis(150)).await;
+    drop(feeder);
+  });
+
+  let started = Instant::now();
+  assert_eq!(watchdog.stalled().await, Some(5));
+  assert!(started.elapsed() >= Duration::from_millis(200));
+  assert_eq!(watchdog.stalled().await, None);
+  task.await.unwrap();
+}
@@ -0,0 +1,28 @@
+//! Plays concurrent bot games against an in-process controller and node
+//! and prints the tick jitter seen by the bots.
+//! Every bot holds a controller and a node connection, raise `ulimit -n` for 1k+ games.
+//!
+//! cargo run -p flo-testlab --release --example loadgen -- <games> [ticks]
+
+use flo_testlab::{LoadConfig, TestLab, TestLabConfig};
+
+#[tokio::main]
+async fn main() {
+  dotenv::dotenv().ok();
+  flo_log_subscriber::init_env_override("flo_testlab=info,flo_node=warn,flo_controller=warn");
+
+  let mut args = std::env::args().skip(1);
+  let games = args.next().and_then(|v| v.parse().ok()).unwrap_or(1_000);
+  let ticks = args.next().and_then(|v| v.parse().ok()).unwrap_or(600);
+
+  let lab = TestLab::start(TestLabConfig::from_env()).await.unwrap();
+  let report = lab
+    .run_load(LoadConfig {
+      games,
+      ticks,
+      action_interval: Some(3),
+    })
+    .await
+    .unwrap();
+  println!("{:#?}", report);
+}
@@ -0,0 +1,420 @@
+use s2_grpc_utils::S2ProtoEnum;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Instant};
+
+use flo_constants::{CONTROLLER_SOCKET_PORT, MIN_FLO_VERSION, NODE_CLIENT_PORT};
+use flo_net::packet::{FloPacket, Frame, PacketTypeId};
+use flo_net::proto::{flo_connect, flo_node};
+use flo_net::stream::FloStream;
+use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
+use flo_types::node::{NodeGameStatus, SlotClientStatus};
+use flo_w3gs::protocol::action::{IncomingAction, OutgoingAction, OutgoingKeepAlive};
+use flo_w3gs::protocol::constants::LeaveReason;
+use flo_w3gs::protocol::leave::LeaveReq;
+
+use crate::error::{Error, Result};
+use crate::playback::TimedAction;
+
+/// Reported by every bot, so the version and map checks always pass
+const BOT_WAR3_VERSION: &str = "testlab";
+const PLAYER_TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RECONNECTS: u32 = 20;
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// What a bot does after the game is loaded
+#[derive(Debug, Clone)]
+pub struct BotScript {
+  /// Sends `LeaveReq` after receiving this many ticks
+  pub leave_at_tick: u32,
+  pub leave_reason: LeaveReason,
+  /// Sends an action every `n` ticks
+  pub action_interval: Option<u32>,
+  /// Actions sent once the game time reaches their time, ordered by time
+  pub actions: Vec<TimedAction>,
+  /// Sends `LeaveReq` once the game time reaches this,
+  /// or after `leave_at_tick` ticks, whichever comes first
+  pub leave_at_ms: Option<u32>,
+  /// Gives up if the bot hasn't left by then
+  pub timeout: Duration,
+}
+
+impl BotScript {
+  pub fn leave_at_tick(tick: u32) -> Self {
+    Self {
+      leave_at_tick: tick,
+      leave_reason: LeaveReason::LeaveLost,
+      action_interval: None,
+      actions: vec![],
+      leave_at_ms: None,
+      timeout: Duration::from_secs(60),
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct BotReport {
+  pub player_id: i32,
+  /// `IncomingAction` packets received
+  pub ticks: u32,
+  pub time_ms: u32,
+  pub actions_sent: u32,
+  /// The node acked the `LeaveReq`
+  pub leave_acked: bool,
+  /// Tick at which the bot saw each other player leave
+  pub left_players: BTreeMap<i32, u32>,
+  /// Times the node stream was lost and connected again
+  pub reconnects: u32,
+  /// For each tick after the first, how far its arrival interval was
+  /// from its time increment
+  pub tick_jitter_ms: Vec<u32>,
+}
+
+enum ControllerEvent {
+  PlayerToken(flo_connect::PacketGamePlayerToken),
+  GameResult(flo_connect::PacketGameResult),
+}
+
+/// A headless player, connects to the controller like a flo client
+/// and plays through the node without a game client
+pub struct Bot {
+  player_id: i32,
+  events: mpsc::Receiver<ControllerEvent>,
+  results: Vec<flo_connect::PacketGameResult>,
+  task: JoinHandle<()>,
+}
+
+impl Bot {
+  pub async fn connect(player_id: i32) -> Result<Self> {
+    let token = flo_controller::player::token::create_player_token(player_id)?;
+    let mut stream =
+      FloStream::connect_no_delay(format!("127.0.0.1:{}", CONTROLLER_SOCKET_PORT)).await?;
+    stream
+      .send(flo_connect::PacketClientConnect {
+        connect_version: Some(MIN_FLO_VERSION.into()),
+        token,
+        fingerprints: vec![],
+      })
+      .await?;
+
+    let frame = stream.recv_frame().await?;
+    match frame.type_id {
+      PacketTypeId::ConnectControllerAccept => {}
+      PacketTypeId::ConnectControllerReject => {
+        let p: flo_connect::PacketClientConnectReject = frame.decode()?;
+        return Err(Error::ControllerConnectionRejected(p.reason()));
+      }
+      other => return Err(flo_net::error::Error::unexpected_packet_type_id(other).into()),
+    }
+
+    let (tx, rx) = mpsc::channel(8);
+    let task = tokio::spawn(async move {
+      if let Err(err) = serve_controller(stream, tx).await {
+        tracing::debug!(player_id, "bot controller stream: {}", err);
+      }
+    });
+
+    Ok(Self {
+      player_id,
+      events: rx,
+      results: vec![],
+      task,
+    })
+  }
+
+  pub fn player_id(&self) -> i32 {
+    self.player_id
+  }
+
+  /// Waits for the game to start and plays it with `script`
+  pub async fn play(&mut self, script: BotScript) -> Result<BotReport> {
+    let token = timeout(PLAYER_TOKEN_TIMEOUT, self.recv_player_token())
+      .await
+      .map_err(|_| Error::Timeout("player token"))??;
+    let duration = script.timeout;
+    timeout(duration, play(self.player_id, token, script))
+      .await
+      .map_err(|_| Error::Timeout("play"))?
+  }
+
+  /// Waits for the result of the game the bot played
+  pub async fn wait_result(&mut self, duration: Duration) -> Result<flo_connect::PacketGameResult> {
+    if let Some(result) = self.results.pop() {
+      return Ok(result);
+    }
+    timeout(duration, async {
+      loop {
+        match self.events.recv().await {
+          Some(ControllerEvent::GameResult(result)) => return Ok(result),
+          Some(ControllerEvent::PlayerToken(_)) => {}
+          None => return Err(Error::StreamClosed),
+        }
+      }
+    })
+    .await
+    .map_err(|_| Error::Timeout("game result"))?
+  }
+
+  async fn recv_player_token(&mut self) -> Result<flo_connect::PacketGamePlayerToken> {
+    loop {
+      match self.events.recv().await {
+        Some(ControllerEvent::PlayerToken(token)) => return Ok(token),
+        Some(ControllerEvent::GameResult(result)) => self.results.push(result),
+        None => return Err(Error::StreamClosed),
+      }
+    }
+  }
+}
+
+impl Drop for Bot {
+  fn drop(&mut self) {
+    self.task.abort();
+  }
+}
+
+async fn serve_controller(mut stream: FloStream, tx: mpsc::Sender<ControllerEvent>) -> Result<()> {
+  loop {
+    let mut frame = stream.recv_frame().await?;
+    let event = match frame.type_id {
+      PacketTypeId::Ping => {
+        frame.type_id = PacketTypeId::Pong;
+        stream.send_frame(frame).await?;
+        continue;
+      }
+      PacketTypeId::GameStarting => {
+        let p: flo_connect::PacketGameStarting = frame.decode()?;
+        stream
+          .send(flo_connect::PacketGameStartPlayerClientInfoRequest {
+            game_id: p.game_id,
+            war3_version: BOT_WAR3_VERSION.to_string(),
+            map_sha1: vec![0; 20],
+          })
+          .await?;
+        continue;
+      }
+      PacketTypeId::GamePlayerToken => ControllerEvent::PlayerToken(frame.decode()?),
+      PacketTypeId::GameResult => ControllerEvent::GameResult(frame.decode()?),
+      _ => continue,
+    };
+    if tx.send(event).await.is_err() {
+      return Ok(());
+    }
+  }
+}
+
+async fn play(
+  player_id: i32,
+  token: flo_connect::PacketGamePlayerToken,
+  script: BotScript,
+) -> Result<BotReport> {
+  let (stream, game_status) = connect_node(&token).await?;
+  let mut session = NodeSession {
+    stream,
+    ack_q: W3GSAckQueue::new(),
+    script,
+    report: BotReport {
+      player_id,
+      ..Default::default()
+    },
+    loaded: false,
+    last_tick_at: None,
+    next_action: 0,
+    leave_sent: false,
+  };
+  session.send_status(SlotClientStatus::Joined).await?;
+  session.update_game_status(game_status).await?;
+  loop {
+    match session.run().await {
+      Ok(()) => return Ok(session.report),
+      Err(Error::Net(err)) if session.report.reconnects < MAX_RECONNECTS => {
+        tracing::debug!(player_id, "bot node stream: {}, reconnecting", err);
+        session.report.reconnects += 1;
+        sleep(RECONNECT_DELAY).await;
+        session.reconnect(&token).await?;
+      }
+      Err(err) => return Err(err),
+    }
+  }
+}
+
+async fn connect_node(
+  token: &flo_connect::PacketGamePlayerToken,
+) -> Result<(FloStream, NodeGameStatus)> {
+  let mut stream = FloStream::connect_no_delay(format!("127.0.0.1:{}", NODE_CLIENT_PORT)).await?;
+  stream
+    .send(flo_node::PacketClientConnect {
+      version: Some(MIN_FLO_VERSION.into()),
+      token: token.player_token.clone(),
+      traceparent: token.traceparent.clone(),
+      ..Default::default()
+    })
+    .await?;
+
+  let frame = stream.recv_frame().await?;
+  match frame.type_id {
+    PacketTypeId::ClientConnectAccept => {
+      let p: flo_node::PacketClientConnectAccept = frame.decode()?;
+      Ok((stream, NodeGameStatus::unpack_enum(p.game_status())))
+    }
+    PacketTypeId::ClientConnectReject => {
+      let p: flo_node::PacketClientConnectReject = frame.decode()?;
+      Err(Error::NodeConnectionRejected(p.reason(), p.message))
+    }
+    other => Err(flo_net::error::Error::unexpected_packet_type_id(other).into()),
+  }
+}
+
+struct NodeSession {
+  stream: FloStream,
+  ack_q: W3GSAckQueue,
+  script: BotScript,
+  report: BotReport,
+  loaded: bool,
+  last_tick_at: Option<Instant>,
+  /// Index of the next `script.actions` entry to send
+  next_action: usize,
+  leave_sent: bool,
+}
+
+impl NodeSession {
+  async fn run(&mut self) -> Result<()> {
+    loop {
+      let mut frame = self.stream.recv_frame().await?;
+      match frame.type_id {
+        PacketTypeId::Ping => {
+          frame.type_id = PacketTypeId::Pong;
+          self.stream.send_frame(frame).await?;
+        }
+        PacketTypeId::W3GS => {
+          let (meta, pkt) = frame.try_into_w3gs()?;
+          if !self.ack_q.ack_received(meta.sid()) {
+            continue;
+          }
+          if let Some(ack_sid) = meta.ack_sid() {
+            self.ack_q.ack_sent(ack_sid);
+          }
+          if pkt.type_id() == W3GSPacketTypeId::IncomingAction {
+            self.handle_tick(&pkt).await?;
+          }
+        }
+        PacketTypeId::NodeGameStatusUpdate => {
+          let p: flo_node::PacketNodeGameStatusUpdate = frame.decode()?;
+          for (player_id, status) in &p.updated_player_game_client_status_map {
+            self.update_player_status(*player_id, SlotClientStatus::unpack_enum(*status));
+          }
+          self
+            .update_game_status(NodeGameStatus::unpack_enum(p.status()))
+            .await?;
+        }
+        PacketTypeId::ClientUpdateSlotClientStatus => {
+          let p: flo_node::PacketClientUpdateSlotClientStatus = frame.decode()?;
+          self.update_player_status(p.player_id, SlotClientStatus::unpack_enum(p.status()));
+        }
+        PacketTypeId::ClientShutdownAck => {
+          self.report.leave_acked = true;
+          return Ok(());
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Connects again with the same token and resends the unacked packets
+  async fn reconnect(&mut self, token: &flo_connect::PacketGamePlayerToken) -> Result<()> {
+    let (stream, game_status) = connect_node(token).await?;
+    self.stream = stream;
+    self.last_tick_at = None;
+    let frames: Vec<_> = self
+      .ack_q
+      .pending_ack_queue()
+      .iter()
+      .cloned()
+      .map(|(meta, pkt)| Frame::from_w3gs(meta, pkt))
+      .collect();
+    if !frames.is_empty() {
+      self.stream.send_frames(frames).await?;
+    }
+    self.update_game_status(game_status).await
+  }
+
+  async fn handle_tick(&mut self, pkt: &W3GSPacket) -> Result<()> {
+    let time_increment_ms = IncomingAction::peek_time_increment_ms(pkt.payload.as_ref())? as u32;
+    let now = Instant::now();
+    if let Some(last) = self.last_tick_at.replace(now) {
+      let interval_ms = (now - last).as_millis() as i64;
+      let jitter_ms = (interval_ms - time_increment_ms as i64).abs();
+      self.report.tick_jitter_ms.push(jitter_ms as u32);
+    }
+    self.report.ticks += 1;
+    self.report.time_ms += time_increment_ms;
+    self
+      .send_w3gs(W3GSPacket::simple(OutgoingKeepAlive {
+        unknown: 0,
+        checksum: 0,
+      })?)
+      .await?;
+
+    let tick = self.report.ticks;
+    if let Some(interval) = self.script.action_interval.filter(|v| *v > 0) {
+      if tick % interval == 0 {
+        self
+          .send_w3gs(W3GSPacket::with_payload(OutgoingAction::new(&[
+            0x33, 0x99, 0xFF, 0x00,
+          ]))?)
+          .await?;
+        self.report.actions_sent += 1;
+      }
+    }
+    while let Some(action) = self.script.actions.get(self.next_action) {
+      if action.time_ms > self.report.time_ms {
+        break;
+      }
+      let pkt = W3GSPacket::with_payload(OutgoingAction::new(&action.data))?;
+      self.next_action += 1;
+      self.send_w3gs(pkt).await?;
+      self.report.actions_sent += 1;
+    }
+    let leave_at_ms = self.script.leave_at_ms.unwrap_or(u32::MAX);
+    let leave = tick >= self.script.leave_at_tick || self.report.time_ms >= leave_at_ms;
+    if leave && !self.leave_sent {
+      self.leave_sent = true;
+      self
+        .send_w3gs(W3GSPacket::simple(LeaveReq::new(self.script.leave_reason))?)
+        .await?;
+    }
+    Ok(())
+  }
+
+  async fn update_game_status(&mut self, status: NodeGameStatus) -> Result<()> {
+    if status == NodeGameStatus::Loading && !self.loaded {
+      self.loaded = true;
+      self.send_status(SlotClientStatus::Loading).await?;
+      self.send_status(SlotClientStatus::Loaded).await?;
+    }
+    Ok(())
+  }
+
+  fn update_player_status(&mut self, player_id: i32, status: SlotClientStatus) {
+    if player_id != self.report.player_id && status == SlotClientStatus::Left {
+      let tick = self.report.ticks;
+      self.report.left_players.entry(player_id).or_insert(tick);
+    }
+  }
+
+  async fn send_status(&mut self, status: SlotClientStatus) -> Result<()> {
+    let mut pkt = flo_node::PacketClientUpdateSlotClientStatusRequest::default();
+    pkt.set_status(status.into_proto_enum());
+    self.stream.send(pkt).await?;
+    Ok(())
+  }
+
+  async fn send_w3gs(&mut self, pkt: W3GSPacket) -> Result<()> {
+    let sid = self.ack_q.gen_next_send_sid();
+    let meta = W3GSMetadata::new(pkt.type_id(), sid, self.ack_q.take_ack_received());
+    self.ack_q.push_send(meta.clone(), pkt.clone());
+    self.stream.send_frame(Frame::from_w3gs(meta, pkt)).await?;
+    Ok(())
+  }
+}
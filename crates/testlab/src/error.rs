@@ -0,0 +1,46 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+  #[error("Timeout: {0}")]
+  Timeout(&'static str),
+  #[error("Task cancelled")]
+  Cancelled,
+  #[error("Stream closed unexpectedly")]
+  StreamClosed,
+  #[error("Controller connection rejected: {0:?}")]
+  ControllerConnectionRejected(flo_net::proto::flo_connect::ClientConnectRejectReason),
+  #[error("Node connection rejected: {1} ({0:?})")]
+  NodeConnectionRejected(flo_net::proto::flo_node::ClientConnectRejectReason, String),
+  #[error("Game start rejected: {0}")]
+  GameStartRejected(String),
+  #[error("Golden capture `{name}` mismatch at line {line}: expected `{expected}`, got `{got}`")]
+  GoldenMismatch {
+    name: String,
+    line: usize,
+    expected: String,
+    got: String,
+  },
+  #[error("Replay has no player")]
+  ReplayHasNoPlayer,
+  #[error("Golden capture relay left {0} undecoded bytes")]
+  GoldenTrailingBytes(usize),
+  #[error("Controller: {0}")]
+  Controller(#[from] flo_controller::error::Error),
+  #[error("Node: {0}")]
+  Node(#[from] flo_node::error::Error),
+  #[error("Net: {0}")]
+  Net(#[from] flo_net::error::Error),
+  #[error("W3GS: {0}")]
+  W3GS(#[from] flo_w3gs::error::Error),
+  #[error("Replay: {0}")]
+  Replay(#[from] flo_replay::error::Error),
+  #[error("Observer archive: {0}")]
+  ObserverFs(#[from] flo_observer_fs::error::Error),
+  #[error("Packet conversion: {0}")]
+  PacketConversion(#[from] s2_grpc_utils::result::Error),
+  #[error("Io: {0}")]
+  Io(#[from] std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,198 @@
+//! Golden capture tests.
+//!
+//! Recorded games are replayed through the node's tick encoder and the
+//! node to client relay, the packets the game client would receive are
+//! compared with the expectations stored in `golden/`.
+//! A missing expectation is recorded on the first run, set `FLO_GOLDEN_BLESS=1`
+//! to record them again after an intended protocol change.
+
+use bytes::BytesMut;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tokio_util::codec::{Decoder, Encoder};
+
+use flo_net::packet::Frame;
+use flo_net::w3gs::{W3GSAckQueue, W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
+use flo_net::FloFrameCodec;
+use flo_observer::record::GameRecordData;
+use flo_observer_fs::GameDataArchiveReader;
+use flo_replay::capture::{replay_to_capture, ReplayToCaptureOptions};
+use flo_w3gs::action::{IncomingAction, IncomingAction2};
+use flo_w3gs::net::W3GSCodec;
+
+use crate::error::{Error, Result};
+
+const BLESS_ENV: &str = "FLO_GOLDEN_BLESS";
+
+/// The records of a recorded game
+#[derive(Debug)]
+pub struct GoldenCapture {
+  name: String,
+  records: Vec<GameRecordData>,
+}
+
+impl GoldenCapture {
+  /// Loads a `.w3g` replay, any patch supported by `flo-w3replay`
+  pub fn load_replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let capture = replay_to_capture(
+      ReplayToCaptureOptions {
+        game_id: 0,
+        game_version: None,
+      },
+      File::open(path)?,
+    )?;
+    Ok(Self {
+      name: capture_name(path),
+      records: capture.records,
+    })
+  }
+
+  /// Loads an observer archive written by the node
+  pub async fn load_archive<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref();
+    let records = GameDataArchiveReader::open(path)
+      .await?
+      .records()
+      .collect_vec()
+      .await?;
+    Ok(Self {
+      name: capture_name(path),
+      records,
+    })
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Packets broadcast by the node, every tick is encoded again
+  /// from its actions, including the fragments recorded before it
+  pub fn dispatch(&self) -> Result<Vec<W3GSPacket>> {
+    let mut packets = vec![];
+    let mut fragments = vec![];
+    for record in &self.records {
+      let pkt = match record {
+        GameRecordData::W3GS(pkt) => pkt,
+        _ => continue,
+      };
+      match pkt.type_id() {
+        W3GSPacketTypeId::IncomingAction2 => {
+          let IncomingAction2(slot) = pkt.decode_payload()?;
+          fragments.extend(slot.actions);
+        }
+        W3GSPacketTypeId::IncomingAction => {
+          let IncomingAction(slot) = pkt.decode_payload()?;
+          let mut actions = std::mem::take(&mut fragments);
+          actions.extend(slot.actions);
+          packets.extend(flo_node::encode_action_tick(
+            slot.time_increment_ms,
+            actions,
+          )?);
+        }
+        _ => packets.push(pkt.clone()),
+      }
+    }
+    Ok(packets)
+  }
+
+  /// Packets received by the game client: the dispatched packets are framed
+  /// like the node sends them, decoded like the client and sent over the LAN codec
+  pub fn relay(&self) -> Result<Vec<W3GSPacket>> {
+    let mut node_ack_q = W3GSAckQueue::new();
+    let mut frame_codec = FloFrameCodec::new();
+    let mut buf = BytesMut::new();
+    for pkt in self.dispatch()? {
+      let meta = W3GSMetadata::new(pkt.type_id(), node_ack_q.gen_next_send_sid(), None);
+      frame_codec.encode(Frame::from_w3gs(meta, pkt), &mut buf)?;
+    }
+
+    let mut client_ack_q = W3GSAckQueue::new();
+    let mut lan_buf = BytesMut::new();
+    while let Some(frame) = frame_codec.decode(&mut buf)? {
+      let (meta, pkt) = frame.try_into_w3gs()?;
+      if client_ack_q.ack_received(meta.sid()) {
+        pkt.encode(&mut lan_buf);
+      }
+    }
+
+    let mut lan_codec = W3GSCodec::new();
+    let mut packets = vec![];
+    while let Some(pkt) = lan_codec.decode(&mut lan_buf)? {
+      packets.push(pkt);
+    }
+    if !buf.is_empty() || !lan_buf.is_empty() {
+      return Err(Error::GoldenTrailingBytes(buf.len() + lan_buf.len()));
+    }
+    Ok(packets)
+  }
+
+  /// Compares the relayed packets with `golden/<name>.txt`
+  pub fn check(&self) -> Result<()> {
+    let got: Vec<String> = self.relay()?.iter().map(digest).collect();
+    let path = golden_dir().join(format!("{}.txt", self.name));
+
+    if env::var_os(BLESS_ENV).is_some() || !path.exists() {
+      fs::create_dir_all(golden_dir())?;
+      fs::write(&path, got.join("\n") + "\n")?;
+      tracing::warn!("golden expectation recorded: {}", path.display());
+      return Ok(());
+    }
+
+    let expected = fs::read_to_string(&path)?;
+    let expected: Vec<&str> = expected.lines().collect();
+    for line in 0..std::cmp::max(expected.len(), got.len()) {
+      let expected = expected.get(line).cloned().unwrap_or("<end>");
+      let got = got.get(line).map(String::as_str).unwrap_or("<end>");
+      if expected != got {
+        return Err(Error::GoldenMismatch {
+          name: self.name.clone(),
+          line: line + 1,
+          expected: expected.to_string(),
+          got: got.to_string(),
+        });
+      }
+    }
+    Ok(())
+  }
+}
+
+fn golden_dir() -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+fn capture_name(path: &Path) -> String {
+  path
+    .file_name()
+    .map(|v| v.to_string_lossy().to_string())
+    .unwrap_or_default()
+}
+
+/// `<type id> <payload len> <payload crc32>`
+fn digest(pkt: &W3GSPacket) -> String {
+  format!(
+    "{:?} {} {:08x}",
+    pkt.type_id(),
+    pkt.payload.len(),
+    crc32fast::hash(pkt.payload.as_ref())
+  )
+}
+
+#[test]
+fn test_golden_replays() {
+  for name in &["grubby_happy.w3g", "16k.w3g", "bn.w3g", "3506801.w3g"] {
+    let capture = GoldenCapture::load_replay(flo_util::sample_path!("replay", name)).unwrap();
+    let dispatched = capture.dispatch().unwrap();
+    assert_eq!(capture.relay().unwrap().len(), dispatched.len());
+    capture.check().unwrap();
+  }
+}
+
+#[tokio::test]
+async fn test_golden_archive() {
+  let capture = GoldenCapture::load_archive(flo_util::sample_path!("replay", "703450.gz"))
+    .await
+    .unwrap();
+  capture.check().unwrap();
+}
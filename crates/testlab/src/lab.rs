@@ -0,0 +1,403 @@
+use std::env;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Instant};
+
+use flo_constants::{CONTROLLER_SOCKET_PORT, NODE_CLIENT_PORT};
+use flo_controller::game::db::CreateGameAsBotParams;
+use flo_controller::game::messages::{
+  CreateGameAsBot, StartGameCheckAsBot, StartGameCheckAsBotResult,
+};
+use flo_controller::game::{CreateGameSlot, Game, GameStatus, SlotSettings, SlotStatus};
+use flo_controller::map::{Map, MapForce, MapPlayer, MapSha1};
+use flo_controller::player::db::UpsertPlayer;
+use flo_controller::player::PlayerSource;
+use flo_controller::{serve_socket, ActorMapExt, ControllerState, ControllerStateRef};
+
+use crate::error::{Error, Result};
+
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub struct TestLabConfig {
+  pub api_client_id: i32,
+  pub node_id: i32,
+  /// Faults injected into the games hosted by the node
+  #[cfg(feature = "chaos")]
+  pub chaos: Option<flo_node::chaos::ChaosConfig>,
+}
+
+impl TestLabConfig {
+  /// Reads `FLO_TESTLAB_API_CLIENT_ID` and `FLO_TESTLAB_NODE_ID`, both default to `1`
+  pub fn from_env() -> Self {
+    fn get(name: &str) -> i32 {
+      env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1)
+    }
+    Self {
+      api_client_id: get("FLO_TESTLAB_API_CLIENT_ID"),
+      node_id: get("FLO_TESTLAB_NODE_ID"),
+      #[cfg(feature = "chaos")]
+      chaos: None,
+    }
+  }
+}
+
+/// A controller and a node running in the current process.
+///
+/// Both bind their default ports, only one lab can run at a time.
+/// The controller database must contain the api client and
+/// a node at `127.0.0.1` with `FLO_NODE_SECRET` as its secret, see `INSTALL.md`.
+pub struct TestLab {
+  config: TestLabConfig,
+  state: ControllerStateRef,
+  host_player_id: i32,
+  node_task: JoinHandle<()>,
+  tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestLab {
+  pub async fn start(config: TestLabConfig) -> Result<Self> {
+    #[cfg(feature = "chaos")]
+    match config.chaos.clone() {
+      Some(chaos) => flo_node::chaos::set(chaos),
+      None => flo_node::chaos::clear(),
+    }
+
+    let node_task = spawn_node();
+    wait_port(NODE_CLIENT_PORT).await?;
+
+    let state = ControllerState::init().await?.into_ref();
+    let tasks = vec![tokio::spawn({
+      let state = state.clone();
+      async move {
+        if let Err(err) = serve_socket(state).await {
+          tracing::error!("controller: {}", err);
+        }
+      }
+    })];
+    wait_port(CONTROLLER_SOCKET_PORT).await?;
+
+    let mut lab = Self {
+      config,
+      state,
+      host_player_id: 0,
+      node_task,
+      tasks,
+    };
+    lab.host_player_id = lab.create_player("host").await?;
+    Ok(lab)
+  }
+
+  pub fn state(&self) -> &ControllerStateRef {
+    &self.state
+  }
+
+  /// Stops the node and starts it again, the games it hosted are lost
+  pub async fn restart_node(&mut self) -> Result<()> {
+    self.node_task.abort();
+    // wait for the listeners to be dropped
+    (&mut self.node_task).await.ok();
+    self.node_task = spawn_node();
+    wait_port(NODE_CLIENT_PORT).await
+  }
+
+  /// Creates or updates a test player, returns the player id
+  pub async fn create_player(&self, name: &str) -> Result<i32> {
+    let data = UpsertPlayer {
+      api_client_id: self.config.api_client_id,
+      name: format!("testlab-{}", name),
+      source: PlayerSource::Test,
+      source_id: format!("testlab-{}", name),
+      source_state: None,
+      realm: None,
+    };
+    let player = self
+      .state
+      .db
+      .exec(move |conn| flo_controller::player::db::upsert(conn, &data))
+      .await?;
+    Ok(player.id)
+  }
+
+  /// Creates a game with each player in its own team
+  pub async fn create_game(&self, player_ids: &[i32]) -> Result<Game> {
+    let slots = player_ids
+      .iter()
+      .enumerate()
+      .map(|(i, player_id)| CreateGameSlot {
+        player_id: Some(*player_id),
+        settings: SlotSettings {
+          team: i as i32,
+          color: i as i32,
+          status: SlotStatus::Occupied,
+          ..Default::default()
+        },
+      })
+      .collect();
+    let game = self
+      .state
+      .games
+      .send(CreateGameAsBot {
+        api_client_id: self.config.api_client_id,
+        api_player_id: self.host_player_id,
+        params: CreateGameAsBotParams {
+          name: format!("testlab-{}", player_ids.len()),
+          map: test_map(player_ids.len()),
+          is_private: true,
+          is_live: false,
+          node_id: self.config.node_id,
+          slots,
+          mask_player_names: false,
+          enable_ping_equalizer: false,
+          flo_tv_delay_override_secs: None,
+          map_config: None,
+          feature_flags: None,
+          auto_start: None,
+        },
+      })
+      .await
+      .map_err(flo_controller::error::Error::from)??;
+    Ok(game)
+  }
+
+  /// Starts a game, the players must be connected bots
+  pub async fn start_game(&self, game_id: i32) -> Result<()> {
+    let (tx, rx) = oneshot::channel();
+    self
+      .state
+      .games
+      .send_to(game_id, StartGameCheckAsBot { tx })
+      .await?;
+    match timeout(START_TIMEOUT, rx).await {
+      Ok(Ok(StartGameCheckAsBotResult::Started(_))) => Ok(()),
+      Ok(Ok(StartGameCheckAsBotResult::Rejected(pkt))) => {
+        Err(Error::GameStartRejected(pkt.message))
+      }
+      Ok(Err(_)) => Err(Error::Cancelled),
+      Err(_) => Err(Error::Timeout("start game")),
+    }
+  }
+
+  pub async fn get_game(&self, game_id: i32) -> Result<Game> {
+    let game = self
+      .state
+      .db
+      .exec(move |conn| flo_controller::game::db::get_full(conn, game_id))
+      .await?;
+    Ok(game)
+  }
+
+  /// Polls the database until the game reaches `status`
+  pub async fn wait_game_status(
+    &self,
+    game_id: i32,
+    status: GameStatus,
+    duration: Duration,
+  ) -> Result<Game> {
+    let deadline = Instant::now() + duration;
+    loop {
+      let game = self.get_game(game_id).await?;
+      if game.status == status {
+        return Ok(game);
+      }
+      if Instant::now() >= deadline {
+        return Err(Error::Timeout("game status"));
+      }
+      sleep(POLL_INTERVAL).await;
+    }
+  }
+}
+
+impl Drop for TestLab {
+  fn drop(&mut self) {
+    self.node_task.abort();
+    for task in &self.tasks {
+      task.abort();
+    }
+    #[cfg(feature = "chaos")]
+    flo_node::chaos::clear();
+  }
+}
+
+fn spawn_node() -> JoinHandle<()> {
+  tokio::spawn(async {
+    if let Err(err) = flo_node::serve().await {
+      tracing::error!("node: {}", err);
+    }
+  })
+}
+
+async fn wait_port(port: u16) -> Result<()> {
+  let deadline = Instant::now() + READY_TIMEOUT;
+  loop {
+    if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+      return Ok(());
+    }
+    if Instant::now() >= deadline {
+      return Err(Error::Timeout("listen"));
+    }
+    sleep(POLL_INTERVAL).await;
+  }
+}
+
+/// The node never reads the map file, bots agree on the checksum
+fn test_map(num_players: usize) -> Map {
+  Map {
+    sha1: MapSha1([0; 20]),
+    checksum: 0,
+    name: "testlab".to_string(),
+    description: String::new(),
+    author: String::new(),
+    path: "maps\\testlab.w3x".to_string(),
+    width: 64,
+    height: 64,
+    players: (0..num_players)
+      .map(|i| MapPlayer {
+        name: format!("Player {}", i + 1),
+        r#type: 1,
+        race: 0,
+        flags: 0,
+      })
+      .collect(),
+    forces: (0..num_players)
+      .map(|i| MapForce {
+        name: format!("Force {}", i + 1),
+        flags: 0,
+        player_set: 1 << i,
+      })
+      .collect(),
+    twelve_p: false,
+  }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_leave_before_last_tick() {
+  use crate::bot::{Bot, BotScript};
+
+  dotenv::dotenv().ok();
+  flo_log_subscriber::init_env_override("flo_testlab=debug,flo_node=debug,flo_controller=debug");
+
+  let lab = TestLab::start(TestLabConfig::from_env()).await.unwrap();
+  let a = lab.create_player("a").await.unwrap();
+  let b = lab.create_player("b").await.unwrap();
+  let mut bot_a = Bot::connect(a).await.unwrap();
+  let mut bot_b = Bot::connect(b).await.unwrap();
+
+  let game = lab.create_game(&[a, b]).await.unwrap();
+  lab.start_game(game.id).await.unwrap();
+
+  let (report_a, report_b) = tokio::join!(
+    bot_a.play(BotScript::leave_at_tick(20)),
+    bot_b.play(BotScript::leave_at_tick(40))
+  );
+  let report_a = report_a.unwrap();
+  let report_b = report_b.unwrap();
+
+  assert!(report_a.leave_acked);
+  assert!(report_a.ticks >= 20);
+  assert!(report_b.leave_acked);
+  assert!(report_b.ticks >= 40);
+  // b kept receiving ticks after a left
+  assert!(report_b.left_players[&a] >= 20);
+
+  lab
+    .wait_game_status(game.id, GameStatus::Ended, Duration::from_secs(10))
+    .await
+    .unwrap();
+  let result = bot_b.wait_result(Duration::from_secs(10)).await.unwrap();
+  assert_eq!(result.game_id, game.id);
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_node_restart() {
+  use crate::bot::{Bot, BotScript};
+
+  dotenv::dotenv().ok();
+  flo_log_subscriber::init_env_override("flo_testlab=debug,flo_node=debug,flo_controller=debug");
+
+  let mut lab = TestLab::start(TestLabConfig::from_env()).await.unwrap();
+  let a = lab.create_player("a").await.unwrap();
+  let b = lab.create_player("b").await.unwrap();
+  let mut bot_a = Bot::connect(a).await.unwrap();
+  let mut bot_b = Bot::connect(b).await.unwrap();
+
+  let game = lab.create_game(&[a, b]).await.unwrap();
+  lab.start_game(game.id).await.unwrap();
+
+  let mut script = BotScript::leave_at_tick(u32::MAX);
+  script.timeout = Duration::from_secs(60);
+  let (report_a, report_b, restarted) =
+    tokio::join!(bot_a.play(script.clone()), bot_b.play(script), async {
+      sleep(Duration::from_secs(3)).await;
+      lab.restart_node().await
+    });
+  restarted.unwrap();
+
+  // the bots give up instead of waiting for ticks that never come
+  for report in [report_a, report_b] {
+    assert!(!matches!(report, Ok(_) | Err(Error::Timeout(_))));
+  }
+}
+
+#[cfg(feature = "chaos")]
+#[tokio::test]
+#[ignore]
+async fn test_chaos() {
+  use crate::bot::{Bot, BotScript};
+  use flo_node::chaos::ChaosConfig;
+
+  dotenv::dotenv().ok();
+  flo_log_subscriber::init_env_override("flo_testlab=debug,flo_node=info,flo_controller=debug");
+
+  let lab = TestLab::start(TestLabConfig {
+    chaos: Some(ChaosConfig {
+      seed: 1,
+      disconnect_rate: 0.005,
+      tick_delay_rate: 0.05,
+      tick_delay: Duration::from_millis(300),
+      duplicate_frame_rate: 0.05,
+    }),
+    ..TestLabConfig::from_env()
+  })
+  .await
+  .unwrap();
+  let a = lab.create_player("a").await.unwrap();
+  let b = lab.create_player("b").await.unwrap();
+  let mut bot_a = Bot::connect(a).await.unwrap();
+  let mut bot_b = Bot::connect(b).await.unwrap();
+
+  let game = lab.create_game(&[a, b]).await.unwrap();
+  lab.start_game(game.id).await.unwrap();
+
+  let mut script_a = BotScript::leave_at_tick(300);
+  script_a.action_interval = Some(5);
+  let mut script_b = BotScript::leave_at_tick(400);
+  script_b.action_interval = Some(7);
+  let (report_a, report_b) = tokio::join!(bot_a.play(script_a), bot_b.play(script_b));
+  let report_a = report_a.unwrap();
+  let report_b = report_b.unwrap();
+  tracing::info!(
+    "reconnects: a = {}, b = {}",
+    report_a.reconnects,
+    report_b.reconnects
+  );
+
+  assert!(report_a.leave_acked);
+  assert!(report_a.ticks >= 300);
+  assert!(report_b.leave_acked);
+  assert!(report_b.ticks >= 400);
+
+  lab
+    .wait_game_status(game.id, GameStatus::Ended, Duration::from_secs(10))
+    .await
+    .unwrap();
+}
@@ -0,0 +1,24 @@
+//! In-process controller, node and headless bot clients for end-to-end game tests.
+//!
+//! Bots speak the flo protocols directly, no game client or map file is needed.
+//! The controller still needs its database, tests using the lab are `#[ignore]`d
+//! and run with `cargo test -p flo-testlab -- --ignored`.
+//! With the `chaos` feature the node injects faults into the games, see `flo_node::chaos`.
+//!
+//! [`GoldenCapture`] replays recorded games through the node's encoders
+//! and compares the output with stored expectations.
+//! [`TestLab::run_load`] plays many bot games at once, see `examples/loadgen.rs`.
+//! [`TestLab::run_playback`] plays the actions of a `.w3g` replay with one bot per player.
+
+mod bot;
+pub mod error;
+mod golden;
+mod lab;
+mod load;
+mod playback;
+
+pub use bot::{Bot, BotReport, BotScript};
+pub use golden::GoldenCapture;
+pub use lab::{TestLab, TestLabConfig};
+pub use load::{LoadConfig, LoadReport};
+pub use playback::{PlaybackPlayer, ReplayPlayback, TimedAction};
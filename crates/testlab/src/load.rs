@@ -0,0 +1,100 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::bot::{Bot, BotScript};
+use crate::error::{Error, Result};
+use crate::lab::TestLab;
+
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+  /// Concurrent games, each played by 2 bots
+  pub games: usize,
+  /// Ticks played before the bots leave
+  pub ticks: u32,
+  /// Each bot sends an action every `n` ticks
+  pub action_interval: Option<u32>,
+}
+
+/// Tick jitter seen by the bots of all games, see `BotReport::tick_jitter_ms`
+#[derive(Debug)]
+pub struct LoadReport {
+  pub games: usize,
+  pub failed_bots: usize,
+  pub elapsed: Duration,
+  pub ticks: u64,
+  pub jitter_p50_ms: u32,
+  pub jitter_p99_ms: u32,
+  pub jitter_max_ms: u32,
+}
+
+impl TestLab {
+  /// Plays `config.games` bot games at the same time
+  pub async fn run_load(&self, config: LoadConfig) -> Result<LoadReport> {
+    let mut bots = Vec::with_capacity(config.games * 2);
+    let mut game_ids = Vec::with_capacity(config.games);
+    for i in 0..config.games {
+      let a = self.create_player(&format!("load-{}-a", i)).await?;
+      let b = self.create_player(&format!("load-{}-b", i)).await?;
+      bots.push(Bot::connect(a).await?);
+      bots.push(Bot::connect(b).await?);
+      game_ids.push(self.create_game(&[a, b]).await?.id);
+    }
+
+    let timeout = Duration::from_millis(config.ticks as u64 * 200) + Duration::from_secs(60);
+    let started = Instant::now();
+    let tasks: Vec<_> = bots
+      .into_iter()
+      .map(|mut bot| {
+        let mut script = BotScript::leave_at_tick(config.ticks);
+        script.action_interval = config.action_interval;
+        script.timeout = timeout;
+        tokio::spawn(async move { bot.play(script).await })
+      })
+      .collect();
+    for game_id in game_ids {
+      self.start_game(game_id).await?;
+    }
+
+    let mut failed_bots = 0;
+    let mut ticks = 0;
+    let mut jitter = vec![];
+    for task in tasks {
+      match task.await.map_err(|_| Error::Cancelled)? {
+        Ok(report) => {
+          ticks += report.ticks as u64;
+          jitter.extend(report.tick_jitter_ms);
+        }
+        Err(err) => {
+          tracing::error!("load bot: {}", err);
+          failed_bots += 1;
+        }
+      }
+    }
+    jitter.sort_unstable();
+
+    Ok(LoadReport {
+      games: config.games,
+      failed_bots,
+      elapsed: started.elapsed(),
+      ticks,
+      jitter_p50_ms: percentile(&jitter, 50),
+      jitter_p99_ms: percentile(&jitter, 99),
+      jitter_max_ms: jitter.last().cloned().unwrap_or_default(),
+    })
+  }
+}
+
+fn percentile(sorted: &[u32], p: usize) -> u32 {
+  if sorted.is_empty() {
+    return 0;
+  }
+  sorted[(sorted.len() - 1) * p / 100]
+}
+
+#[test]
+fn test_percentile() {
+  let values: Vec<u32> = (1..=100).collect();
+  assert_eq!(percentile(&values, 50), 50);
+  assert_eq!(percentile(&values, 99), 99);
+  assert_eq!(percentile(&[], 99), 0);
+}
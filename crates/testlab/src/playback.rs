@@ -0,0 +1,235 @@
+//! Replays the action stream of a recorded game against the node.
+//!
+//! Every player of the replay is played by a bot that sends the player's
+//! actions once its game time reaches their original time, and leaves
+//! when the original player left, turning a real game into a full-load test.
+
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use flo_observer::record::GameRecordData;
+use flo_replay::capture::{replay_to_capture, ReplayToCaptureOptions};
+use flo_w3gs::action::{IncomingAction, IncomingAction2, PlayerAction};
+use flo_w3gs::protocol::constants::{LeaveReason, PacketTypeId};
+use flo_w3gs::protocol::leave::PlayerLeft;
+
+use crate::bot::{Bot, BotReport, BotScript};
+use crate::error::{Error, Result};
+use crate::lab::TestLab;
+
+/// An action sent by a bot once its game time reaches `time_ms`
+#[derive(Debug, Clone)]
+pub struct TimedAction {
+  pub time_ms: u32,
+  pub data: Bytes,
+}
+
+/// A player of the replay
+#[derive(Debug, Clone)]
+pub struct PlaybackPlayer {
+  /// In-game player id, `slot index + 1`
+  pub player_id: u8,
+  pub name: String,
+  pub actions: Vec<TimedAction>,
+  /// Game time the player left at, the end of the game if not recorded
+  pub leave_at_ms: u32,
+  pub leave_reason: LeaveReason,
+}
+
+impl PlaybackPlayer {
+  pub fn script(&self) -> BotScript {
+    let mut script = BotScript::leave_at_tick(u32::MAX);
+    script.actions = self.actions.clone();
+    script.leave_at_ms = Some(self.leave_at_ms);
+    script.leave_reason = self.leave_reason;
+    script.timeout = Duration::from_millis(self.leave_at_ms as u64) + Duration::from_secs(60);
+    script
+  }
+}
+
+/// The players of a `.w3g` replay and their actions
+#[derive(Debug)]
+pub struct ReplayPlayback {
+  pub players: Vec<PlaybackPlayer>,
+  /// Game time of the last tick
+  pub duration_ms: u32,
+}
+
+impl ReplayPlayback {
+  /// Loads a `.w3g` replay, any patch supported by `flo-w3replay`
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let capture = replay_to_capture(
+      ReplayToCaptureOptions {
+        game_id: 0,
+        game_version: None,
+      },
+      File::open(path)?,
+    )?;
+
+    let mut players: BTreeMap<u8, PlaybackPlayer> = capture
+      .game
+      .slots
+      .iter()
+      .filter_map(|slot| slot.player.as_ref())
+      .map(|player| {
+        (
+          player.id as u8,
+          PlaybackPlayer {
+            player_id: player.id as u8,
+            name: player.name.clone(),
+            actions: vec![],
+            leave_at_ms: 0,
+            leave_reason: LeaveReason::LeaveLost,
+          },
+        )
+      })
+      .collect();
+
+    let mut time_ms = 0;
+    let mut left = BTreeMap::new();
+    for record in &capture.records {
+      let pkt = match record {
+        GameRecordData::W3GS(pkt) => pkt,
+        _ => continue,
+      };
+      match pkt.type_id() {
+        PacketTypeId::IncomingAction2 => {
+          let IncomingAction2(slot) = pkt.decode_payload()?;
+          push_actions(&mut players, time_ms, slot.actions);
+        }
+        PacketTypeId::IncomingAction => {
+          let IncomingAction(slot) = pkt.decode_payload()?;
+          // the actions were sent before the tick that carries them
+          push_actions(&mut players, time_ms, slot.actions);
+          time_ms += slot.time_increment_ms as u32;
+        }
+        PacketTypeId::PlayerLeft => {
+          let p: PlayerLeft = pkt.decode_simple()?;
+          left.entry(p.player_id).or_insert((time_ms, p.reason));
+        }
+        _ => {}
+      }
+    }
+
+    let players: Vec<_> = players
+      .into_iter()
+      .map(|(id, mut player)| {
+        let (leave_at_ms, reason) = left
+          .get(&id)
+          .cloned()
+          .unwrap_or((time_ms, LeaveReason::LeaveLost));
+        player.leave_at_ms = leave_at_ms;
+        player.leave_reason = reason;
+        player
+          .actions
+          .retain(|action| action.time_ms <= leave_at_ms);
+        player
+      })
+      .collect();
+    if players.is_empty() {
+      return Err(Error::ReplayHasNoPlayer);
+    }
+
+    Ok(Self {
+      players,
+      duration_ms: time_ms,
+    })
+  }
+
+  pub fn total_actions(&self) -> usize {
+    self.players.iter().map(|p| p.actions.len()).sum()
+  }
+}
+
+fn push_actions(
+  players: &mut BTreeMap<u8, PlaybackPlayer>,
+  time_ms: u32,
+  actions: Vec<PlayerAction>,
+) {
+  for action in actions {
+    if action.data.is_empty() {
+      continue;
+    }
+    if let Some(player) = players.get_mut(&action.player_id) {
+      player.actions.push(TimedAction {
+        time_ms,
+        data: action.data,
+      });
+    }
+  }
+}
+
+impl TestLab {
+  /// Plays `playback` with one bot per replay player,
+  /// returns the bot reports in the order of `playback.players`
+  pub async fn run_playback(&self, playback: &ReplayPlayback) -> Result<Vec<BotReport>> {
+    let mut bots = Vec::with_capacity(playback.players.len());
+    let mut player_ids = Vec::with_capacity(playback.players.len());
+    for player in &playback.players {
+      let player_id = self
+        .create_player(&format!("playback-{}", player.player_id))
+        .await?;
+      bots.push(Bot::connect(player_id).await?);
+      player_ids.push(player_id);
+    }
+    let game = self.create_game(&player_ids).await?;
+
+    let tasks: Vec<_> = bots
+      .into_iter()
+      .zip(&playback.players)
+      .map(|(mut bot, player)| {
+        let script = player.script();
+        tokio::spawn(async move { bot.play(script).await })
+      })
+      .collect();
+    self.start_game(game.id).await?;
+
+    let mut reports = Vec::with_capacity(tasks.len());
+    for task in tasks {
+      reports.push(task.await.map_err(|_| Error::Cancelled)??);
+    }
+    Ok(reports)
+  }
+}
+
+#[test]
+fn test_replay_playback() {
+  let playback =
+    ReplayPlayback::load(flo_util::sample_path!("replay", "grubby_happy.w3g")).unwrap();
+  assert!(playback.players.len() >= 2);
+  assert!(playback.total_actions() > 0);
+  for player in &playback.players {
+    assert!(player.leave_at_ms <= playback.duration_ms);
+    assert!(player
+      .actions
+      .windows(2)
+      .all(|w| w[0].time_ms <= w[1].time_ms));
+    assert!(player
+      .actions
+      .iter()
+      .all(|action| action.time_ms <= player.leave_at_ms));
+  }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_replay_playback_game() {
+  use crate::lab::TestLabConfig;
+
+  dotenv::dotenv().ok();
+  flo_log_subscriber::init_env_override("flo_testlab=debug,flo_node=info,flo_controller=debug");
+
+  let playback =
+    ReplayPlayback::load(flo_util::sample_path!("replay", "grubby_happy.w3g")).unwrap();
+  let lab = TestLab::start(TestLabConfig::from_env()).await.unwrap();
+  let reports = lab.run_playback(&playback).await.unwrap();
+
+  for (player, report) in playback.players.iter().zip(&reports) {
+    assert_eq!(report.actions_sent as usize, player.actions.len());
+    assert!(report.leave_acked);
+    assert!(report.time_ms >= player.leave_at_ms);
+  }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// Experimental behaviors the controller enables per game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FeatureFlag {
+  /// The node runs the ping equalizer even if the game didn't enable it
+  PingEqualizer = 0,
+  /// Game packets are sent to the node one frame each instead of `W3GSBatch` frames
+  NoW3GSBatch = 1,
+  /// In-game chat commands that are not generally available yet
+  ChatCommands = 2,
+}
+
+impl FeatureFlag {
+  pub const ALL: [FeatureFlag; 3] = [
+    FeatureFlag::PingEqualizer,
+    FeatureFlag::NoW3GSBatch,
+    FeatureFlag::ChatCommands,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      FeatureFlag::PingEqualizer => "ping_equalizer",
+      FeatureFlag::NoW3GSBatch => "no_w3gs_batch",
+      FeatureFlag::ChatCommands => "chat_commands",
+    }
+  }
+
+  fn bit(self) -> u32 {
+    1 << (self as u32)
+  }
+}
+
+/// Set of `FeatureFlag`, unknown bits from newer controllers are kept
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FeatureFlags(u32);
+
+impl FeatureFlags {
+  pub fn from_bits(bits: u32) -> Self {
+    FeatureFlags(bits)
+  }
+
+  pub fn bits(self) -> u32 {
+    self.0
+  }
+
+  pub fn contains(self, flag: FeatureFlag) -> bool {
+    self.0 & flag.bit() != 0
+  }
+
+  pub fn insert(&mut self, flag: FeatureFlag) {
+    self.0 |= flag.bit()
+  }
+
+  pub fn is_empty(self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn to_vec(self) -> Vec<FeatureFlag> {
+    FeatureFlag::ALL
+      .iter()
+      .cloned()
+      .filter(|flag| self.contains(*flag))
+      .collect()
+  }
+}
+
+#[test]
+fn test_feature_flags() {
+  let mut flags = FeatureFlags::default();
+  assert!(flags.is_empty());
+  flags.insert(FeatureFlag::ChatCommands);
+  flags.insert(FeatureFlag::PingEqualizer);
+  assert!(flags.contains(FeatureFlag::ChatCommands));
+  assert!(!flags.contains(FeatureFlag::NoW3GSBatch));
+  assert_eq!(
+    flags.to_vec(),
+    vec![FeatureFlag::PingEqualizer, FeatureFlag::ChatCommands]
+  );
+  let flags = FeatureFlags::from_bits(flags.bits() | 1 << 31);
+  assert_eq!(flags.to_vec().len(), 2);
+}
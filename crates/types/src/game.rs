@@ -1,4 +1,6 @@
+use crate::feature::FeatureFlags;
 use crate::node::*;
+use flo_w3gs::protocol::constants::GameSettingFlags;
 use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -39,6 +41,7 @@ pub struct PlayerInfo {
   pub id: i32,
   pub name: String,
   pub source: PlayerSource,
+  pub battletag: Option<String>,
 }
 
 #[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -71,6 +74,8 @@ pub struct GameInfo {
   pub random_seed: i32,
   pub created_by: Option<PlayerInfo>,
   pub mask_player_names: bool,
+  pub map_config: Option<MapConfigOverrides>,
+  pub feature_flags: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +93,108 @@ pub struct LocalGameInfo {
   pub slots: Vec<Slot>,
   pub host_player: Option<PlayerInfo>,
   pub mask_player_names: bool,
+  pub map_config: MapConfigOverrides,
+  pub feature_flags: FeatureFlags,
+}
+
+/// Host-time overrides of the map-derived game settings.
+#[derive(Debug, Default, S2ProtoUnpack, S2ProtoPack, Serialize, Deserialize, Clone)]
+#[s2_grpc(message_type = "flo_net::proto::flo_connect::MapConfigOverrides")]
+pub struct MapConfigOverrides {
+  /// Lock teams, alliances can't be changed in game
+  pub fixed_teams: Option<bool>,
+  pub teams_together: Option<bool>,
+  #[s2_grpc(proto_enum)]
+  pub observers: MapObserverMode,
+  #[s2_grpc(proto_enum)]
+  pub visibility: MapVisibility,
+  /// Full shared unit control between allies
+  pub shared_control: Option<bool>,
+  /// Races are picked by the controller and written into the slots
+  pub random_races: bool,
+  pub random_hero: bool,
+}
+
+impl MapConfigOverrides {
+  pub fn observers_allowed(&self) -> bool {
+    self.observers != MapObserverMode::None
+  }
+
+  pub fn apply_game_setting_flags(&self, mut flags: GameSettingFlags) -> GameSettingFlags {
+    if let Some(fixed_teams) = self.fixed_teams {
+      flags.set(GameSettingFlags::TEAMS_FIXED, fixed_teams);
+    }
+
+    if let Some(teams_together) = self.teams_together {
+      flags.set(GameSettingFlags::TEAMS_TOGETHER, teams_together);
+    }
+
+    if let Some(shared_control) = self.shared_control {
+      flags.set(GameSettingFlags::SHARED_CONTROL, shared_control);
+    }
+
+    if self.random_hero {
+      flags.insert(GameSettingFlags::RANDOM_HERO);
+    }
+
+    let observers = match self.observers {
+      MapObserverMode::Default => None,
+      MapObserverMode::None => Some(GameSettingFlags::OBS_NONE),
+      MapObserverMode::OnDefeat => {
+        Some(GameSettingFlags::OBS_ENABLED | GameSettingFlags::OBS_ON_DEFEAT)
+      }
+      MapObserverMode::Full => Some(GameSettingFlags::OBS_ENABLED | GameSettingFlags::OBS_FULL),
+      MapObserverMode::Referees => Some(GameSettingFlags::OBS_REFEREES),
+    };
+    if let Some(observers) = observers {
+      flags.remove(GameSettingFlags::OBS_MASK);
+      flags.insert(observers);
+    }
+
+    let visibility = match self.visibility {
+      MapVisibility::Default => None,
+      MapVisibility::Hidden => Some(GameSettingFlags::TERRAIN_HIDDEN),
+      MapVisibility::Explored => Some(GameSettingFlags::TERRAIN_EXPLORED),
+      MapVisibility::AlwaysVisible => Some(GameSettingFlags::TERRAIN_VISIBLE),
+    };
+    if let Some(visibility) = visibility {
+      flags.remove(GameSettingFlags::TERRAIN_MASK);
+      flags.insert(visibility);
+    }
+
+    flags
+  }
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[s2_grpc(proto_enum_type = "flo_net::proto::flo_connect::MapObserverMode")]
+pub enum MapObserverMode {
+  Default = 0,
+  None = 1,
+  OnDefeat = 2,
+  Full = 3,
+  Referees = 4,
+}
+
+impl Default for MapObserverMode {
+  fn default() -> Self {
+    MapObserverMode::Default
+  }
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[s2_grpc(proto_enum_type = "flo_net::proto::flo_connect::MapVisibility")]
+pub enum MapVisibility {
+  Default = 0,
+  Hidden = 1,
+  Explored = 2,
+  AlwaysVisible = 3,
+}
+
+impl Default for MapVisibility {
+  fn default() -> Self {
+    MapVisibility::Default
+  }
 }
 
 #[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize)]
@@ -127,6 +234,8 @@ pub struct Slot {
   pub settings: SlotSettings,
   #[s2_grpc(proto_enum)]
   pub client_status: SlotClientStatus,
+  pub locked: bool,
+  pub reserved_player_id: Option<i32>,
 }
 
 impl Default for Slot {
@@ -135,6 +244,8 @@ impl Default for Slot {
       player: None,
       settings: SlotSettings::default(),
       client_status: SlotClientStatus::Pending,
+      locked: false,
+      reserved_player_id: None,
     }
   }
 }
@@ -161,7 +272,7 @@ impl<'a> From<&'a Slot> for LanGameSlot<'a> {
   }
 }
 
-#[derive(Debug, S2ProtoUnpack, S2ProtoPack, Serialize, Deserialize, Clone)]
+#[derive(Debug, S2ProtoUnpack, S2ProtoPack, Serialize, Deserialize, Clone, PartialEq)]
 #[s2_grpc(message_type(
   flo_net::proto::flo_connect::SlotSettings,
   flo_grpc::game::SlotSettings,
@@ -210,6 +321,17 @@ impl From<Computer> for flo_w3gs::slot::AI {
   }
 }
 
+impl From<flo_w3gs::slot::AI> for Computer {
+  fn from(ai: flo_w3gs::slot::AI) -> Self {
+    use flo_w3gs::slot::AI;
+    match ai {
+      AI::ComputerNormal => Computer::Normal,
+      AI::ComputerInsane => Computer::Insane,
+      _ => Computer::Easy,
+    }
+  }
+}
+
 #[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize, Deserialize)]
 #[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::Race, flo_grpc::game::Race,))]
 pub enum Race {
@@ -233,6 +355,25 @@ impl From<Race> for flo_w3gs::slot::RacePref {
   }
 }
 
+impl From<flo_w3gs::slot::RacePref> for Race {
+  fn from(race: flo_w3gs::slot::RacePref) -> Self {
+    use flo_w3gs::slot::RacePref;
+    if race.contains(RacePref::RANDOM) {
+      Race::Random
+    } else if race.contains(RacePref::HUMAN) {
+      Race::Human
+    } else if race.contains(RacePref::ORC) {
+      Race::Orc
+    } else if race.contains(RacePref::NIGHTELF) {
+      Race::NightElf
+    } else if race.contains(RacePref::UNDEAD) {
+      Race::Undead
+    } else {
+      Race::Random
+    }
+  }
+}
+
 #[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize, Deserialize)]
 #[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::SlotStatus, flo_grpc::game::SlotStatus,))]
 pub enum SlotStatus {
@@ -287,6 +428,22 @@ pub struct MapDetail {
   pub twelve_p: bool,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct MapScanEntry {
+  pub path: String,
+  pub sha1: Option<String>,
+  pub checksum: Option<u32>,
+  pub name: String,
+  pub author: String,
+  pub suggested_players: String,
+  pub width: u32,
+  pub height: u32,
+  pub num_players: usize,
+  pub twelve_p: bool,
+  pub melee: bool,
+  pub error: Option<String>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct MapPlayerOwned {
   pub name: String,
@@ -301,3 +458,22 @@ pub struct MapForceOwned {
   pub flags: u32,
   pub player_set: u32,
 }
+
+#[test]
+fn test_apply_game_setting_flags() {
+  let flags = MapConfigOverrides {
+    fixed_teams: Some(true),
+    shared_control: Some(true),
+    ..Default::default()
+  }
+  .apply_game_setting_flags(GameSettingFlags::SPEED_FAST);
+  assert!(flags.contains(GameSettingFlags::TEAMS_FIXED | GameSettingFlags::SHARED_CONTROL));
+
+  let flags = MapConfigOverrides {
+    shared_control: Some(false),
+    ..Default::default()
+  }
+  .apply_game_setting_flags(flags);
+  assert!(!flags.contains(GameSettingFlags::SHARED_CONTROL));
+  assert!(flags.contains(GameSettingFlags::TEAMS_FIXED));
+}
@@ -1,3 +1,4 @@
+pub mod feature;
 pub mod game;
 pub mod node;
 pub mod ping;
@@ -22,6 +22,50 @@ pub enum SlotClientStatus {
   Loaded = 4,
   Disconnected = 5,
   Left = 6,
+  /// The game client exited during the load screen
+  LoadFailed = 7,
+}
+
+impl SlotClientStatus {
+  /// Whether a slot can move from `self` to `next`,
+  /// repeating the current status is always allowed
+  ///
+  /// Pending -> Connected -> Joined -> Loading -> Loaded,
+  /// any status can drop to Disconnected or Left, Left is final
+  pub fn can_transition_to(self, next: Self) -> bool {
+    use SlotClientStatus::*;
+    if self == next {
+      return true;
+    }
+    match (self, next) {
+      (Left, _) => false,
+      (_, Left) => true,
+      (LoadFailed, Disconnected) => true,
+      (LoadFailed, _) => false,
+      (_, Disconnected) => true,
+      (Pending, Connected) => true,
+      // reconnected before or after the game started
+      (Disconnected, Connected | Loaded) => true,
+      (Connected, Joined) => true,
+      // left the lan lobby
+      (Joined, Connected) => true,
+      (Joined, Loading) => true,
+      (Loading, Loaded) => true,
+      (Joined | Loading, LoadFailed) => true,
+      _ => false,
+    }
+  }
+
+  /// Whether a client can report `next` after `self`,
+  /// the node decides about the other transitions
+  pub fn client_can_transition_to(self, next: Self) -> bool {
+    use SlotClientStatus::*;
+    match (self, next) {
+      (_, Pending | Disconnected) => false,
+      (Pending | Disconnected, Connected | Loaded) => false,
+      _ => self.can_transition_to(next),
+    }
+  }
 }
 
 #[derive(Debug, S2ProtoUnpack)]
@@ -4,9 +4,9 @@ use crate::game::LocalGameInfo;
 //use flo_client::game::LocalGameInfo;
 //use crate::game::LocalGameInfo;
 use flo_grpc::game::Game;
-use s2_grpc_utils::S2ProtoUnpack;
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
 use serde::{Deserialize, Serialize};
-#[derive(Debug, S2ProtoUnpack, Serialize)]
+#[derive(Debug, S2ProtoUnpack, Serialize, Deserialize)]
 #[s2_grpc(message_type(flo_net::proto::flo_observer::GameInfo))]
 pub struct GameInfo {
   pub id: i32,
@@ -49,7 +49,7 @@ impl S2ProtoUnpack<Game> for GameInfo {
   }
 }
 
-#[derive(Debug, S2ProtoUnpack, Serialize)]
+#[derive(Debug, S2ProtoUnpack, Serialize, Deserialize)]
 #[s2_grpc(message_type(flo_net::proto::flo_observer::Map, flo_grpc::game::Map))]
 pub struct Map {
   pub sha1: Vec<u8>,
@@ -70,7 +70,7 @@ impl Map {
   }
 }
 
-#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[derive(Debug, S2ProtoUnpack, Serialize, Deserialize, Clone)]
 #[s2_grpc(message_type(flo_net::proto::flo_observer::Slot, flo_grpc::game::Slot))]
 pub struct Slot {
   pub player: Option<PlayerInfo>,
@@ -93,6 +93,55 @@ pub struct PlayerInfo {
   pub name: String,
 }
 
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type(flo_net::proto::flo_observer::PacketObserverLiveStats))]
+pub struct LiveStats {
+  pub game_id: i32,
+  pub game_time_ms: u32,
+  pub paused: bool,
+  pub pause_count: u32,
+  pub players: Vec<LiveStatsPlayer>,
+}
+
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type(flo_net::proto::flo_observer::LiveStatsPlayer))]
+pub struct LiveStatsPlayer {
+  pub player_id: i32,
+  pub action_count: u32,
+  pub apm: u32,
+  pub current_apm: u32,
+  #[s2_grpc(proto_enum)]
+  pub status: LiveStatsPlayerStatus,
+  pub production: Vec<LiveStatsProduction>,
+}
+
+/// Approximated from the player's commands
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type(flo_net::proto::flo_observer::LiveStatsProduction))]
+pub struct LiveStatsProduction {
+  pub item_id: String,
+  #[s2_grpc(proto_enum)]
+  pub kind: LiveStatsProductionKind,
+  pub count: u32,
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize)]
+#[s2_grpc(proto_enum_type = "flo_net::proto::flo_observer::LiveStatsProductionKind")]
+pub enum LiveStatsProductionKind {
+  Unit = 0,
+  Hero = 1,
+  Upgrade = 2,
+  Building = 3,
+}
+
+#[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize)]
+#[s2_grpc(proto_enum_type = "flo_net::proto::flo_observer::LiveStatsPlayerStatus")]
+pub enum LiveStatsPlayerStatus {
+  Playing = 0,
+  Lagging = 1,
+  Left = 2,
+}
+
 impl<'a> From<&'a Slot> for LanGameSlot<'a> {
   fn from(slot: &'a Slot) -> Self {
     Self {
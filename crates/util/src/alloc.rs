@@ -0,0 +1,41 @@
+//! Heap allocation counting for the benchmarks,
+//! a bench installs [`CountingAlloc`] as its global allocator and reports with [`measure`].
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+/// Runs `f` `iterations` times and prints the allocations and the elapsed time,
+/// `unit` names what a single iteration handles.
+pub fn measure<F: FnMut()>(name: &str, iterations: usize, unit: &str, mut f: F) {
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+  let t = Instant::now();
+  for _ in 0..iterations {
+    f();
+  }
+  let elapsed = t.elapsed();
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+  println!(
+    "{:<24} {:>8} allocations, {:.3} per {}, {:?}",
+    name,
+    allocations,
+    allocations as f64 / iterations as f64,
+    unit,
+    elapsed
+  );
+}
@@ -125,9 +125,13 @@ where
   where
     TItem: BinDecode,
   {
-    self.check_size(TItem::MIN_SIZE * len)?;
+    let size = TItem::MIN_SIZE
+      .checked_mul(len)
+      .ok_or_else(BinDecodeError::incomplete)?;
+    self.check_size(size)?;
 
-    let mut items = Vec::with_capacity(len);
+    // `len` comes from the input, items without a min size could claim anything
+    let mut items = Vec::with_capacity(len.min(self.remaining()));
     for _ in 0..len {
       items.push(TItem::decode(self)?)
     }
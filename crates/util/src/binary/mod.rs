@@ -40,6 +40,7 @@ macro_rules! impl_fixed {
       const FIXED_SIZE: bool = true;
       #[inline]
       fn decode<T: Buf>(buf: &mut T) -> Result<Self, BinDecodeError> {
+        buf.check_size(Self::MIN_SIZE)?;
         Ok(buf.$get())
       }
     }
@@ -64,6 +65,7 @@ impl BinDecode for bool {
   const FIXED_SIZE: bool = true;
   #[inline]
   fn decode<T: Buf>(buf: &mut T) -> Result<Self, BinDecodeError> {
+    buf.check_size(Self::MIN_SIZE)?;
     Ok(buf.get_u8() == 1)
   }
 }
@@ -526,3 +528,20 @@ fn test_derive_enum_encode() {
 
   assert_eq!(bytes, buf);
 }
+
+#[test]
+fn test_decode_short_buf() {
+  let mut buf: &[u8] = &[1, 2, 1];
+  assert!(u32::decode(&mut buf).unwrap_err().is_incomplete());
+  assert!(f64::decode(&mut buf).unwrap_err().is_incomplete());
+  assert_eq!(u16::decode(&mut buf).unwrap(), 0x0201);
+  assert!(u16::decode(&mut buf).unwrap_err().is_incomplete());
+  assert!(bool::decode(&mut buf).unwrap());
+  assert!(bool::decode(&mut buf).unwrap_err().is_incomplete());
+
+  let mut buf: &[u8] = &[0; 8];
+  assert!(buf
+    .get_repeated::<u32, Vec<_>>(usize::MAX)
+    .unwrap_err()
+    .is_incomplete());
+}
@@ -0,0 +1,116 @@
+//! Buffer reuse for the packet relay path.
+//! Packets are encoded into a per-thread arena and split off as `Bytes`,
+//! so encoding a packet does not allocate. The arena memory is reclaimed
+//! once all packets split from it are dropped.
+
+use bytes::{Bytes, BytesMut};
+use std::cell::RefCell;
+use std::convert::Infallible;
+
+/// Packets larger than this get their own allocation
+pub const ARENA_CHUNK_SIZE: usize = 64 * 1024;
+/// Initial read buffer size of framed streams,
+/// decoded packets are split from the read buffer without copying
+pub const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+thread_local! {
+  static ARENA: RefCell<BytesArena> = RefCell::new(BytesArena::new(ARENA_CHUNK_SIZE));
+}
+
+/// Encodes into the current thread's arena,
+/// `len_hint` is the expected encoded length, or 0 if unknown
+pub fn encode_bytes<F>(len_hint: usize, f: F) -> Bytes
+where
+  F: FnOnce(&mut BytesMut),
+{
+  match try_encode_bytes(len_hint, |buf| -> Result<(), Infallible> {
+    f(buf);
+    Ok(())
+  }) {
+    Ok(bytes) => bytes,
+    Err(never) => match never {},
+  }
+}
+
+pub fn try_encode_bytes<F, E>(len_hint: usize, f: F) -> Result<Bytes, E>
+where
+  F: FnOnce(&mut BytesMut) -> Result<(), E>,
+{
+  ARENA.with(|arena| match arena.try_borrow_mut() {
+    Ok(mut arena) => arena.try_encode(len_hint, f),
+    // re-entered from `f`
+    Err(_) => {
+      let mut buf = BytesMut::with_capacity(len_hint);
+      f(&mut buf)?;
+      Ok(buf.freeze())
+    }
+  })
+}
+
+#[derive(Debug)]
+pub struct BytesArena {
+  buf: BytesMut,
+  chunk_size: usize,
+}
+
+impl BytesArena {
+  pub fn new(chunk_size: usize) -> Self {
+    Self {
+      buf: BytesMut::new(),
+      chunk_size,
+    }
+  }
+
+  pub fn encode<F>(&mut self, len_hint: usize, f: F) -> Bytes
+  where
+    F: FnOnce(&mut BytesMut),
+  {
+    match self.try_encode(len_hint, |buf| -> Result<(), Infallible> {
+      f(buf);
+      Ok(())
+    }) {
+      Ok(bytes) => bytes,
+      Err(never) => match never {},
+    }
+  }
+
+  pub fn try_encode<F, E>(&mut self, len_hint: usize, f: F) -> Result<Bytes, E>
+  where
+    F: FnOnce(&mut BytesMut) -> Result<(), E>,
+  {
+    if self.buf.capacity() < std::cmp::max(len_hint, 1) {
+      // reuses the current chunk if nothing split from it is alive
+      self.buf.reserve(std::cmp::max(len_hint, self.chunk_size));
+    }
+    if let Err(err) = f(&mut self.buf) {
+      self.buf.clear();
+      return Err(err);
+    }
+    Ok(self.buf.split().freeze())
+  }
+}
+
+#[test]
+fn test_bytes_arena() {
+  use bytes::BufMut;
+
+  let mut arena = BytesArena::new(16);
+  let a = arena.encode(4, |buf| buf.put_u32(1));
+  let b = arena.encode(0, |buf| buf.put_u32(2));
+  assert_eq!(a.as_ref(), &[0, 0, 0, 1]);
+  assert_eq!(b.as_ref(), &[0, 0, 0, 2]);
+  // split from the same chunk
+  assert_eq!(a.as_ptr() as usize + a.len(), b.as_ptr() as usize);
+
+  let err: Result<Bytes, ()> = arena.try_encode(0, |buf| {
+    buf.put_u32(3);
+    Err(())
+  });
+  assert!(err.is_err());
+  let c = arena.encode(0, |buf| buf.put_u32(4));
+  assert_eq!(c.as_ref(), &[0, 0, 0, 4]);
+
+  // larger than a chunk
+  let d = arena.encode(32, |buf| buf.put_slice(&[5; 32]));
+  assert_eq!(d.len(), 32);
+}
@@ -1,7 +1,11 @@
+#[cfg(feature = "bench")]
+pub mod alloc;
 pub mod binary;
+pub mod buf;
 pub mod chat;
 pub mod dword_string;
 pub mod error;
+pub mod settings;
 pub mod stat_string;
 pub mod uptime;
 
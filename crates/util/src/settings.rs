@@ -0,0 +1,289 @@
+//! Settings read from the process environment with an optional env file on top,
+//! reloadable without restarting the process.
+//!
+//! The environment is captured once by [`SettingsStore::init`], which is also the only
+//! place the env file is written to the environment. Reloads read the file on top of the
+//! captured environment, so removing a key from the file restores its previous value.
+
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SettingsError {
+  #[error("invalid setting `{name}`: {value}")]
+  Invalid { name: String, value: String },
+  #[error("read settings file: {0}")]
+  File(std::io::Error),
+}
+
+/// A snapshot of the settings that can be changed without restarting the process
+pub trait Settings: Default + Send + Sync + 'static {
+  type Error: From<SettingsError>;
+
+  fn load(source: &SettingsSource) -> Result<Self, Self::Error>;
+
+  /// Names of the settings changed in `other`
+  fn diff(&self, other: &Self) -> Vec<String>;
+}
+
+/// Changed settings found by a reload
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+  /// In effect immediately
+  pub applied: Vec<String>,
+  /// Only read at startup, the process must be restarted
+  pub restart_required: Vec<String>,
+}
+
+/// The captured environment with the settings file on top
+pub struct SettingsSource {
+  env: Arc<BTreeMap<String, String>>,
+  overrides: BTreeMap<String, String>,
+}
+
+impl SettingsSource {
+  fn read(file_env: &str, env: Arc<BTreeMap<String, String>>) -> Result<Self, SettingsError> {
+    let overrides = match env.get(file_env) {
+      Some(path) => parse_env_file(&std::fs::read_to_string(path).map_err(SettingsError::File)?),
+      None => BTreeMap::new(),
+    };
+    Ok(SettingsSource { env, overrides })
+  }
+
+  /// A source with only the values of an env file
+  pub fn from_env_file(content: &str) -> Self {
+    SettingsSource {
+      env: Default::default(),
+      overrides: parse_env_file(content),
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<String> {
+    self
+      .overrides
+      .get(name)
+      .or_else(|| self.env.get(name))
+      .cloned()
+  }
+
+  pub fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, SettingsError> {
+    match self.get(name) {
+      Some(value) => match value.parse() {
+        Ok(v) => Ok(Some(v)),
+        Err(_) => Err(SettingsError::Invalid {
+          name: name.to_string(),
+          value,
+        }),
+      },
+      None => Ok(None),
+    }
+  }
+
+  /// Returns `default` if the setting is absent, fails if `valid` rejects the value
+  pub fn parse_or<T, F>(&self, name: &str, default: T, valid: F) -> Result<T, SettingsError>
+  where
+    T: FromStr,
+    F: Fn(&T) -> bool,
+  {
+    match self.get(name) {
+      Some(value) => match value.parse() {
+        Ok(v) if valid(&v) => Ok(v),
+        _ => Err(SettingsError::Invalid {
+          name: name.to_string(),
+          value,
+        }),
+      },
+      None => Ok(default),
+    }
+  }
+}
+
+/// Holds the current settings of a process
+pub struct SettingsStore<S> {
+  /// Name of the variable with the path of the settings file
+  file_env: &'static str,
+  /// Settings only read when the process starts
+  restart_required: &'static [&'static str],
+  state: RwLock<State<S>>,
+}
+
+struct State<S> {
+  env: Arc<BTreeMap<String, String>>,
+  startup_values: BTreeMap<&'static str, Option<String>>,
+  current: Arc<S>,
+}
+
+impl<S: Settings> SettingsStore<S> {
+  pub fn new(file_env: &'static str, restart_required: &'static [&'static str]) -> Self {
+    SettingsStore {
+      file_env,
+      restart_required,
+      state: RwLock::new(State {
+        env: Default::default(),
+        startup_values: Default::default(),
+        current: Default::default(),
+      }),
+    }
+  }
+
+  /// Loads the settings and writes the settings file to the environment for the code
+  /// that reads it directly, must be called before other threads read the environment
+  pub fn init(&self) -> Result<(), S::Error> {
+    let env = env::vars_os()
+      .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+      .collect();
+    let source = self.start(env)?;
+    for (name, value) in &source.overrides {
+      env::set_var(name, value);
+    }
+    Ok(())
+  }
+
+  fn start(&self, env: BTreeMap<String, String>) -> Result<SettingsSource, S::Error> {
+    let source = SettingsSource::read(self.file_env, Arc::new(env))?;
+    let settings = S::load(&source)?;
+    *self.state.write() = State {
+      env: source.env.clone(),
+      startup_values: self
+        .restart_required
+        .iter()
+        .map(|name| (*name, source.get(name)))
+        .collect(),
+      current: Arc::new(settings),
+    };
+    Ok(source)
+  }
+
+  /// Re-reads and validates the settings, nothing is changed if any value is invalid
+  pub fn reload(&self) -> Result<ReloadReport, S::Error> {
+    let env = self.state.read().env.clone();
+    let source = SettingsSource::read(self.file_env, env)?;
+    let settings = S::load(&source)?;
+    let mut state = self.state.write();
+    let restart_required = self
+      .restart_required
+      .iter()
+      .filter(|name| state.startup_values.get(*name).cloned().flatten() != source.get(name))
+      .map(|name| name.to_string())
+      .collect();
+    let applied = state.current.diff(&settings);
+    state.current = Arc::new(settings);
+    Ok(ReloadReport {
+      applied,
+      restart_required,
+    })
+  }
+
+  pub fn current(&self) -> Arc<S> {
+    self.state.read().current.clone()
+  }
+}
+
+/// `KEY=VALUE` lines, blank lines and `#` comments are skipped
+fn parse_env_file(content: &str) -> BTreeMap<String, String> {
+  content
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .filter_map(|line| {
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let (name, value) = line.split_once('=')?;
+      let value = value.trim();
+      let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+      Some((name.trim().to_string(), value.to_string()))
+    })
+    .collect()
+}
+
+#[test]
+fn test_settings_source() {
+  let source = SettingsSource {
+    env: Arc::new(
+      vec![("A", "1"), ("B", "2")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect(),
+    ),
+    overrides: parse_env_file(
+      r#"
+      # comment
+      export B="3"
+      C = 4
+      invalid
+      "#,
+    ),
+  };
+  assert_eq!(source.get("A").as_deref(), Some("1"));
+  assert_eq!(source.get("B").as_deref(), Some("3"));
+  assert_eq!(source.parse::<u8>("C").unwrap(), Some(4));
+  assert_eq!(source.parse::<u8>("D").unwrap(), None);
+  assert!(source.get("invalid").is_none());
+  assert_eq!(source.parse_or("D", 5, |v: &u8| *v > 0).unwrap(), 5);
+  assert!(matches!(
+    source.parse_or("A", 5, |v: &u8| *v > 1),
+    Err(SettingsError::Invalid { .. })
+  ));
+}
+
+#[cfg(test)]
+#[derive(Debug, Default, PartialEq)]
+struct TestSettings {
+  value: u8,
+}
+
+#[cfg(test)]
+impl Settings for TestSettings {
+  type Error = SettingsError;
+
+  fn load(source: &SettingsSource) -> Result<Self, SettingsError> {
+    Ok(TestSettings {
+      value: source.parse_or("FLO_TEST_VALUE", 1, |_| true)?,
+    })
+  }
+
+  fn diff(&self, other: &Self) -> Vec<String> {
+    if self == other {
+      vec![]
+    } else {
+      vec!["FLO_TEST_VALUE".to_string()]
+    }
+  }
+}
+
+#[test]
+fn test_settings_store_reload() {
+  let path = env::temp_dir().join(format!("flo-settings-{}.env", std::process::id()));
+  let store = SettingsStore::<TestSettings>::new("FLO_TEST_SETTINGS_FILE", &["FLO_TEST_SECRET"]);
+  let env = vec![
+    ("FLO_TEST_SETTINGS_FILE", path.to_str().unwrap()),
+    ("FLO_TEST_SECRET", "a"),
+  ]
+  .into_iter()
+  .map(|(k, v)| (k.to_string(), v.to_string()))
+  .collect();
+
+  std::fs::write(&path, "FLO_TEST_VALUE=2").unwrap();
+  store.start(env).unwrap();
+  assert_eq!(store.current().value, 2);
+
+  std::fs::write(&path, "FLO_TEST_VALUE=x").unwrap();
+  assert!(store.reload().is_err());
+  assert_eq!(store.current().value, 2);
+
+  // a removed key falls back to the default
+  std::fs::write(&path, "FLO_TEST_SECRET=b").unwrap();
+  let report = store.reload().unwrap();
+  assert_eq!(report.applied, vec!["FLO_TEST_VALUE"]);
+  assert_eq!(report.restart_required, vec!["FLO_TEST_SECRET"]);
+  assert_eq!(store.current().value, 1);
+  assert!(env::var("FLO_TEST_VALUE").is_err());
+
+  std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,70 @@
+//! Counts heap allocations of encoding and decoding action packets,
+//! comparing a fresh buffer per packet with the shared arena used by `Packet::with_payload`.
+//!
+//! cargo bench -p flo-w3gs --bench alloc
+
+use flo_util::alloc::CountingAlloc;
+use flo_util::binary::{Bytes, BytesMut};
+use flo_w3gs::action::{IncomingAction, PlayerAction, TimeSlot};
+use flo_w3gs::packet::{Packet, PacketPayloadEncode};
+use tokio_util::codec::Decoder;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const PACKETS: usize = 100_000;
+
+fn time_slot() -> TimeSlot {
+  TimeSlot {
+    time_increment_ms: 30,
+    actions: vec![
+      PlayerAction {
+        player_id: 1,
+        data: Bytes::from_static(&[0x10, 0x42, 0x00, 0x0d, 0x00, 0x03, 0x00]),
+      },
+      PlayerAction {
+        player_id: 2,
+        data: Bytes::from_static(&[0x16, 0x01, 0x01, 0x00, 0x1d, 0x00, 0x00, 0x00]),
+      },
+    ],
+  }
+}
+
+fn time_slots() -> impl Iterator<Item = TimeSlot> {
+  (0..PACKETS)
+    .map(|_| time_slot())
+    .collect::<Vec<_>>()
+    .into_iter()
+}
+
+fn measure<F: FnMut()>(name: &str, f: F) {
+  flo_util::alloc::measure(name, PACKETS, "packet", f)
+}
+
+fn main() {
+  let mut slots = time_slots();
+
+  // fresh buffer per packet
+  measure("encode/buffer", || {
+    let payload = IncomingAction(slots.next().unwrap());
+    let mut buf = BytesMut::new();
+    payload.encode(&mut buf);
+    drop(buf.freeze());
+  });
+
+  let mut slots = time_slots();
+  measure("encode/arena", || {
+    drop(Packet::with_payload(IncomingAction(slots.next().unwrap())).unwrap());
+  });
+
+  let mut encoded = BytesMut::new();
+  for _ in 0..PACKETS {
+    Packet::with_payload(IncomingAction(time_slot()))
+      .unwrap()
+      .encode(&mut encoded);
+  }
+  let mut codec = flo_w3gs::net::W3GSCodec::new();
+  measure("decode", || {
+    drop(codec.decode(&mut encoded).unwrap().unwrap());
+  });
+}
@@ -1,3 +1,9 @@
+use std::path::Path;
+
 fn main() {
-  prost_build::compile_protos(&["src/protocol/w3gs.proto"], &["src/"]).unwrap();
+  let out_dir = std::env::var_os("OUT_DIR").unwrap();
+  prost_build::Config::new()
+    .file_descriptor_set_path(Path::new(&out_dir).join("w3gs_descriptor.bin"))
+    .compile_protos(&["src/protocol/w3gs.proto"], &["src/"])
+    .unwrap();
 }
@@ -205,6 +205,30 @@ action_enum! {
   }
 }
 
+impl Action {
+  /// Counted the same way as w3gjs:
+  /// selections only count when units are added, sub-group switches and triggers are ignored
+  pub fn is_apm_action(&self) -> bool {
+    match self {
+      Action::UnitBuildingAbility(_)
+      | Action::UnitBuildingAbilityTargeted(_)
+      | Action::UnitBuildingAbilityTargetedId(_)
+      | Action::ItemGivenDropped(_)
+      | Action::UnitBuildingAbility2Targets2Items(_)
+      | Action::AssignGroupHotkey(_)
+      | Action::SelectGroupHotkey(_)
+      | Action::SelectGroundItem(_)
+      | Action::CancelHeroRevival(_)
+      | Action::RemoveUnitFromBuildingQueue(_)
+      | Action::EscPressed
+      | Action::EnterChooseHeroSkillSubmenu
+      | Action::EnterChooseBuildingSubmenu => true,
+      Action::ChangeSelection(selection) => selection.select_mode == 1,
+      _ => false,
+    }
+  }
+}
+
 #[derive(Debug, BinDecode)]
 pub struct GameSpeed {
   pub speed: u8,
@@ -0,0 +1,239 @@
+//! Fuzz entry points, called by the targets in `fuzz/`.
+//!
+//! W3GS packets come from game clients, every decoder must return an error
+//! on malformed input instead of panicking.
+
+use flo_util::binary::*;
+
+use crate::protocol::action::{
+  IncomingAction, IncomingAction2, OutgoingAction, OutgoingKeepAlive, PlayerAction, TimeSlot,
+};
+use crate::protocol::chat::{ChatFromHost, ChatFromOthers, ChatHeader, ChatToHost};
+use crate::protocol::constants::{PacketTypeId, ProtoBufMessageTypeId};
+use crate::protocol::desync::Desync;
+use crate::protocol::game::{
+  CountDownEnd, CountDownStart, GameLoadedSelf, GameSettings, PlayerLoaded,
+};
+use crate::protocol::join::{RejectJoin, ReqJoin, SlotInfoJoin};
+use crate::protocol::lag::{StartLag, StopLag};
+use crate::protocol::leave::{LeaveAck, LeaveReq, PlayerKicked, PlayerLeft};
+use crate::protocol::map::{MapCheck, MapSize};
+use crate::protocol::packet::{Packet, PacketPayload, PacketProtoBufMessage, ProtoBufPayload};
+use crate::protocol::ping::{PingFromHost, PongToHost};
+use crate::protocol::player::{
+  PlayerInfo, PlayerProfileListMessage, PlayerProfileMessage, PlayerSkinsMessage,
+  PlayerUnknown5Message,
+};
+use crate::protocol::slot::SlotInfo;
+
+/// Bytes received from a socket, split across reads
+#[derive(Debug)]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct StreamInput {
+  pub chunks: Vec<Vec<u8>>,
+}
+
+/// Decodes the packets in `data` and their payloads
+pub fn packets(data: &[u8]) {
+  let mut buf = BytesMut::from(data);
+  while let Ok(header) = Packet::decode_header(&mut buf) {
+    match Packet::decode(header, &mut buf) {
+      Ok(packet) => payload(&packet),
+      Err(_) => break,
+    }
+  }
+}
+
+/// Feeds the chunks to `W3GSCodec` as they would arrive from a socket
+#[cfg(feature = "net")]
+pub fn codec(input: StreamInput) {
+  use crate::net::W3GSCodec;
+  use tokio_util::codec::Decoder;
+
+  let mut codec = W3GSCodec::new();
+  let mut buf = BytesMut::new();
+  for chunk in input.chunks {
+    buf.extend_from_slice(&chunk);
+    loop {
+      match codec.decode(&mut buf) {
+        Ok(Some(packet)) => payload(&packet),
+        Ok(None) => break,
+        Err(_) => return,
+      }
+    }
+  }
+}
+
+/// Decodes the payload of a packet with the decoder of its type
+pub fn payload(packet: &Packet) {
+  match packet.type_id() {
+    PacketTypeId::PingFromHost => simple::<PingFromHost>(packet),
+    PacketTypeId::SlotInfoJoin => simple::<SlotInfoJoin>(packet),
+    PacketTypeId::RejectJoin => simple::<RejectJoin>(packet),
+    PacketTypeId::PlayerInfo => simple::<PlayerInfo>(packet),
+    PacketTypeId::PlayerLeft => simple::<PlayerLeft>(packet),
+    PacketTypeId::PlayerLoaded => simple::<PlayerLoaded>(packet),
+    PacketTypeId::SlotInfo => simple::<SlotInfo>(packet),
+    PacketTypeId::CountDownStart => simple::<CountDownStart>(packet),
+    PacketTypeId::CountDownEnd => simple::<CountDownEnd>(packet),
+    PacketTypeId::IncomingAction => {
+      IncomingAction::peek_time_increment_ms(packet.payload.as_ref()).ok();
+      if let Ok(IncomingAction(slot)) = packet.decode_payload() {
+        time_slot(&slot);
+      }
+    }
+    PacketTypeId::IncomingAction2 => {
+      if let Ok(IncomingAction2(slot)) = packet.decode_payload() {
+        time_slot(&slot);
+      }
+    }
+    PacketTypeId::Desync => simple::<Desync>(packet),
+    PacketTypeId::ChatFromHost => {
+      ChatFromHost::peek_header(packet.payload.as_ref()).ok();
+      simple::<ChatFromHost>(packet);
+    }
+    PacketTypeId::StartLag => simple::<StartLag>(packet),
+    PacketTypeId::StopLag => simple::<StopLag>(packet),
+    PacketTypeId::PlayerKicked => simple::<PlayerKicked>(packet),
+    PacketTypeId::LeaveAck => simple::<LeaveAck>(packet),
+    PacketTypeId::ReqJoin => simple::<ReqJoin>(packet),
+    PacketTypeId::LeaveReq => simple::<LeaveReq>(packet),
+    PacketTypeId::GameLoadedSelf => simple::<GameLoadedSelf>(packet),
+    PacketTypeId::OutgoingAction => {
+      if let Ok(action) = packet.decode_payload::<OutgoingAction>() {
+        actions(&PlayerAction {
+          player_id: 0,
+          data: action.data,
+        });
+      }
+    }
+    PacketTypeId::OutgoingKeepAlive => simple::<OutgoingKeepAlive>(packet),
+    PacketTypeId::ChatToHost => {
+      ChatHeader::peek(packet.payload.as_ref()).ok();
+      simple::<ChatToHost>(packet);
+    }
+    PacketTypeId::MapCheck => simple::<MapCheck>(packet),
+    PacketTypeId::MapSize => simple::<MapSize>(packet),
+    PacketTypeId::PongToHost => simple::<PongToHost>(packet),
+    PacketTypeId::ChatFromOthers => {
+      ChatHeader::peek(packet.payload.as_ref()).ok();
+      simple::<ChatFromOthers>(packet);
+    }
+    PacketTypeId::ProtoBuf => protobuf(packet),
+    _ => {}
+  }
+}
+
+/// Decodes a game settings stat string
+pub fn game_settings(data: &[u8]) {
+  let mut buf = data;
+  GameSettings::decode(&mut buf).ok();
+}
+
+fn simple<T>(packet: &Packet)
+where
+  T: PacketPayload + BinDecode,
+{
+  packet.decode_simple::<T>().ok();
+}
+
+fn protobuf(packet: &Packet) {
+  let payload: ProtoBufPayload = match packet.decode_simple() {
+    Ok(payload) => payload,
+    Err(_) => return,
+  };
+  match payload.message_type_id() {
+    ProtoBufMessageTypeId::PlayerProfile => {
+      message::<PlayerProfileMessage>(&payload);
+      message::<PlayerProfileListMessage>(&payload);
+    }
+    ProtoBufMessageTypeId::PlayerSkins => message::<PlayerSkinsMessage>(&payload),
+    ProtoBufMessageTypeId::PlayerUnknown5 => message::<PlayerUnknown5Message>(&payload),
+    _ => {}
+  }
+}
+
+fn message<T: PacketProtoBufMessage>(payload: &ProtoBufPayload) {
+  payload.decode_message::<T>().ok();
+}
+
+fn time_slot(slot: &TimeSlot) {
+  for action in &slot.actions {
+    actions(action);
+  }
+}
+
+fn actions(action: &PlayerAction) {
+  action.peek_action_id();
+  for item in action.actions() {
+    if item.is_err() {
+      break;
+    }
+  }
+}
+
+#[test]
+fn test_truncated_and_mutated_packets() {
+  use crate::protocol::chat::MessageScope;
+  use crate::protocol::lag::LagPlayer;
+  use crate::protocol::leave::LeaveReason;
+
+  let samples = vec![
+    Packet::simple(ChatToHost::in_game(MessageScope::All, 1, &[1, 2], "gl hf")).unwrap(),
+    Packet::simple(ChatFromHost::lobby(1, &[2], "hi")).unwrap(),
+    Packet::simple(StartLag::new(vec![LagPlayer {
+      player_id: 1,
+      lag_duration_ms: 100,
+    }]))
+    .unwrap(),
+    Packet::simple(LeaveReq::new(LeaveReason::LeaveLost)).unwrap(),
+    Packet::simple(OutgoingKeepAlive {
+      unknown: 0,
+      checksum: 1,
+    })
+    .unwrap(),
+    Packet::with_payload(OutgoingAction::new(&[
+      0x16, 0x01, 0x01, 0x00, 1, 0, 0, 0, 2, 0, 0, 0,
+    ]))
+    .unwrap(),
+    Packet::simple(ProtoBufPayload::new(PlayerProfileMessage::new(1, "flo"))).unwrap(),
+    Packet::with_payload(IncomingAction(TimeSlot {
+      time_increment_ms: 100,
+      actions: vec![PlayerAction {
+        player_id: 1,
+        data: Bytes::from_static(&[0x01, 0x02, 0x03, 0x04, 0x00]),
+      }],
+    }))
+    .unwrap(),
+  ];
+
+  for packet in &samples {
+    let mut buf = BytesMut::new();
+    packet.encode(&mut buf);
+    let bytes = buf.freeze();
+
+    for len in 0..=bytes.len() {
+      packets(&bytes[..len]);
+      packets(&bytes[len..]);
+    }
+
+    let mut mutated = bytes.to_vec();
+    for i in 0..mutated.len() {
+      for v in [0x00, 0x01, 0x7F, 0xFF] {
+        let prev = std::mem::replace(&mut mutated[i], v);
+        packets(&mutated);
+        mutated[i] = prev;
+      }
+    }
+
+    #[cfg(feature = "net")]
+    codec(StreamInput {
+      chunks: bytes.chunks(3).map(|c| c.to_vec()).collect(),
+    });
+  }
+
+  for len in 0..64 {
+    game_settings(&vec![0xFF; len]);
+    game_settings(&vec![0x01; len]);
+  }
+}
@@ -0,0 +1,269 @@
+//! Human-readable rendering of W3GS packets, for frame tracing and debugging tools.
+//!
+//! Packets of unknown types, or with bytes the decoders don't understand,
+//! are rendered as hex instead of failing.
+
+use std::fmt;
+
+use flo_util::binary::*;
+
+use crate::error::Result;
+use crate::protocol::action::{IncomingAction, IncomingAction2, OutgoingAction, OutgoingKeepAlive};
+use crate::protocol::chat::{ChatFromHost, ChatFromOthers, ChatToHost};
+use crate::protocol::constants::{PacketTypeId, ProtoBufMessageTypeId};
+use crate::protocol::desync::Desync;
+use crate::protocol::game::{CountDownEnd, CountDownStart, GameLoadedSelf, PlayerLoaded};
+use crate::protocol::join::{RejectJoin, ReqJoin, SlotInfoJoin};
+use crate::protocol::lag::{StartLag, StopLag};
+use crate::protocol::leave::{LeaveAck, LeaveReq, PlayerKicked, PlayerLeft};
+use crate::protocol::map::{MapCheck, MapSize};
+use crate::protocol::packet::{
+  Packet, PacketPayload, PacketPayloadDecode, PacketProtoBufMessage, ProtoBufPayload, SimplePayload,
+};
+use crate::protocol::ping::{PingFromHost, PongToHost};
+use crate::protocol::player::{
+  PlayerInfo, PlayerProfileMessage, PlayerSkinsMessage, PlayerUnknown5Message,
+};
+use crate::protocol::slot::SlotInfo;
+
+/// `tracing` target of the traced frames, enabled with `RUST_LOG=w3gs_frames=trace`
+pub const TRACE_TARGET: &str = "w3gs_frames";
+
+const HEX_ROW_LEN: usize = 16;
+
+/// A packet decoded as far as possible
+#[derive(Debug, Clone)]
+pub struct PacketInspection {
+  pub type_id: PacketTypeId,
+  pub len: u16,
+  pub payload: InspectedPayload,
+  /// Bytes after the decoded payload, the whole payload if it was not decoded
+  pub tail: Bytes,
+}
+
+#[derive(Debug, Clone)]
+pub enum InspectedPayload {
+  /// The fields of the payload, rendered by its `Debug` impl
+  Decoded(String),
+  /// No decoder for this packet type
+  Unknown,
+  /// The decoder of this packet type rejected the payload
+  Invalid(String),
+}
+
+/// Decodes the payload of a packet with the decoder of its type
+pub fn inspect(packet: &Packet) -> PacketInspection {
+  let decoded = match packet.type_id() {
+    PacketTypeId::PingFromHost => simple::<PingFromHost>(packet),
+    PacketTypeId::SlotInfoJoin => simple::<SlotInfoJoin>(packet),
+    PacketTypeId::RejectJoin => simple::<RejectJoin>(packet),
+    PacketTypeId::PlayerInfo => simple::<PlayerInfo>(packet),
+    PacketTypeId::PlayerLeft => simple::<PlayerLeft>(packet),
+    PacketTypeId::PlayerLoaded => simple::<PlayerLoaded>(packet),
+    PacketTypeId::SlotInfo => simple::<SlotInfo>(packet),
+    PacketTypeId::CountDownStart => simple::<CountDownStart>(packet),
+    PacketTypeId::CountDownEnd => simple::<CountDownEnd>(packet),
+    PacketTypeId::IncomingAction => payload::<IncomingAction>(packet),
+    PacketTypeId::IncomingAction2 => payload::<IncomingAction2>(packet),
+    PacketTypeId::Desync => simple::<Desync>(packet),
+    PacketTypeId::ChatFromHost => simple::<ChatFromHost>(packet),
+    PacketTypeId::StartLag => simple::<StartLag>(packet),
+    PacketTypeId::StopLag => simple::<StopLag>(packet),
+    PacketTypeId::PlayerKicked => simple::<PlayerKicked>(packet),
+    PacketTypeId::LeaveAck => simple::<LeaveAck>(packet),
+    PacketTypeId::ReqJoin => simple::<ReqJoin>(packet),
+    PacketTypeId::LeaveReq => simple::<LeaveReq>(packet),
+    PacketTypeId::GameLoadedSelf => simple::<GameLoadedSelf>(packet),
+    PacketTypeId::OutgoingAction => payload::<OutgoingAction>(packet),
+    PacketTypeId::OutgoingKeepAlive => simple::<OutgoingKeepAlive>(packet),
+    PacketTypeId::ChatToHost => simple::<ChatToHost>(packet),
+    PacketTypeId::MapCheck => simple::<MapCheck>(packet),
+    PacketTypeId::MapSize => simple::<MapSize>(packet),
+    PacketTypeId::PongToHost => simple::<PongToHost>(packet),
+    PacketTypeId::ChatFromOthers => simple::<ChatFromOthers>(packet),
+    PacketTypeId::ProtoBuf => protobuf(packet),
+    _ => None,
+  };
+
+  let (payload, tail) = match decoded {
+    Some(Ok((fields, tail))) => (InspectedPayload::Decoded(fields), tail),
+    Some(Err(err)) => (
+      InspectedPayload::Invalid(err.to_string()),
+      packet.payload.clone(),
+    ),
+    None => (InspectedPayload::Unknown, packet.payload.clone()),
+  };
+
+  PacketInspection {
+    type_id: packet.type_id(),
+    len: packet.len(),
+    payload,
+    tail,
+  }
+}
+
+/// Inspects all complete packets in `data`, and returns the number of bytes consumed
+pub fn inspect_bytes(data: &[u8]) -> (Vec<PacketInspection>, usize) {
+  let mut buf = BytesMut::from(data);
+  let mut items = vec![];
+  let mut consumed = 0;
+  while buf.remaining() >= 4 {
+    let mut next = buf.clone();
+    let packet = match Packet::decode_header(&mut next).and_then(|h| Packet::decode(h, &mut next)) {
+      Ok(packet) => packet,
+      Err(_) => break,
+    };
+    consumed += packet.get_encode_len();
+    items.push(inspect(&packet));
+    buf = next;
+  }
+  (items, consumed)
+}
+
+impl fmt::Display for PacketInspection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?} ({} bytes)", self.type_id, self.len)?;
+    match self.payload {
+      InspectedPayload::Decoded(ref fields) => write!(f, "\n{}", fields)?,
+      InspectedPayload::Unknown => {}
+      InspectedPayload::Invalid(ref err) => write!(f, "\ninvalid payload: {}", err)?,
+    }
+    if !self.tail.is_empty() {
+      write!(
+        f,
+        "\nundecoded {} bytes:\n{}",
+        self.tail.len(),
+        hex(&self.tail)
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// `offset: bytes ascii` rows of 16 bytes
+pub fn hex(data: &[u8]) -> String {
+  let mut rows = Vec::with_capacity((data.len() + HEX_ROW_LEN - 1) / HEX_ROW_LEN);
+  for (i, chunk) in data.chunks(HEX_ROW_LEN).enumerate() {
+    let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+    let ascii: String = chunk
+      .iter()
+      .map(|b| {
+        if b.is_ascii_graphic() || *b == b' ' {
+          *b as char
+        } else {
+          '.'
+        }
+      })
+      .collect();
+    rows.push(format!(
+      "{:04x}: {:<width$} {}",
+      i * HEX_ROW_LEN,
+      bytes.join(" "),
+      ascii,
+      width = HEX_ROW_LEN * 3 - 1
+    ));
+  }
+  rows.join("\n")
+}
+
+type Decoded = Option<Result<(String, Bytes)>>;
+
+fn payload<T>(packet: &Packet) -> Decoded
+where
+  T: PacketPayload + PacketPayloadDecode + fmt::Debug,
+{
+  let mut buf = packet.payload.clone();
+  Some(T::decode(&mut buf).map(|value| (format!("{:#?}", value), buf)))
+}
+
+fn simple<T>(packet: &Packet) -> Decoded
+where
+  T: PacketPayload + BinDecode + fmt::Debug,
+{
+  let mut buf = packet.payload.clone();
+  Some(
+    SimplePayload::<T>::decode(&mut buf).map(|value| (format!("{:#?}", value.into_inner()), buf)),
+  )
+}
+
+fn protobuf(packet: &Packet) -> Decoded {
+  let mut buf = packet.payload.clone();
+  let payload = match SimplePayload::<ProtoBufPayload>::decode(&mut buf) {
+    Ok(payload) => payload.into_inner(),
+    Err(err) => return Some(Err(err)),
+  };
+  let fields = match payload.message_type_id() {
+    ProtoBufMessageTypeId::PlayerProfile => message::<PlayerProfileMessage>(&payload),
+    ProtoBufMessageTypeId::PlayerSkins => message::<PlayerSkinsMessage>(&payload),
+    ProtoBufMessageTypeId::PlayerUnknown5 => message::<PlayerUnknown5Message>(&payload),
+    // the message bytes are kept in the rendered payload
+    _ => Ok(format!("{:#?}", payload)),
+  };
+  Some(fields.map(|fields| (fields, buf)))
+}
+
+fn message<T>(payload: &ProtoBufPayload) -> Result<String>
+where
+  T: PacketProtoBufMessage + fmt::Debug,
+{
+  payload
+    .decode_message::<T>()
+    .map(|message| format!("{:?} {:#?}", payload.message_type_id(), message))
+}
+
+#[test]
+fn test_inspect() {
+  use crate::protocol::leave::LeaveReason;
+
+  let packet = Packet::simple(LeaveReq::new(LeaveReason::LeaveLost)).unwrap();
+  let inspection = inspect(&packet);
+  assert!(matches!(inspection.payload, InspectedPayload::Decoded(_)));
+  assert!(inspection.tail.is_empty());
+  let rendered = inspection.to_string();
+  assert!(rendered.starts_with("LeaveReq (8 bytes)"));
+  assert!(rendered.contains("LeaveLost"));
+
+  // trailing bytes are kept
+  let mut packet = Packet::simple(LeaveAck).unwrap();
+  packet.payload = Bytes::from_static(&[0xAB, 0x41]);
+  packet.header.len += 2;
+  let inspection = inspect(&packet);
+  assert!(matches!(inspection.payload, InspectedPayload::Decoded(_)));
+  assert_eq!(inspection.tail.as_ref(), &[0xAB, 0x41]);
+  assert!(inspection.to_string().contains("\n0000: ab 41"));
+
+  // unknown packet types are rendered as hex
+  let mut packet = Packet::simple(LeaveAck).unwrap();
+  packet.header.type_id = PacketTypeId::MapPart;
+  packet.payload = Bytes::from_static(b"flo");
+  packet.header.len += 3;
+  let inspection = inspect(&packet);
+  assert!(matches!(inspection.payload, InspectedPayload::Unknown));
+  assert_eq!(
+    inspection.to_string(),
+    format!(
+      "MapPart (7 bytes)\nundecoded 3 bytes:\n0000: 66 6c 6f{} flo",
+      " ".repeat(39)
+    )
+  );
+
+  // truncated payloads
+  let mut packet = Packet::simple(LeaveReq::new(LeaveReason::LeaveLost)).unwrap();
+  packet.payload = packet.payload.slice(0..2);
+  assert!(matches!(
+    inspect(&packet).payload,
+    InspectedPayload::Invalid(_)
+  ));
+}
+
+#[test]
+fn test_inspect_bytes() {
+  let mut buf = BytesMut::new();
+  Packet::simple(LeaveAck).unwrap().encode(&mut buf);
+  Packet::simple(CountDownStart).unwrap().encode(&mut buf);
+  buf.extend_from_slice(&[0xF7, 0x0C]);
+  let (items, consumed) = inspect_bytes(&buf);
+  assert_eq!(items.len(), 2);
+  assert_eq!(items[1].type_id, PacketTypeId::CountDownStart);
+  assert_eq!(consumed, 8);
+}
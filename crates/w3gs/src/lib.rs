@@ -1,4 +1,7 @@
 pub mod error;
+pub mod fuzz;
+pub mod inspect;
+#[cfg(feature = "net")]
 pub mod net;
 pub mod protocol;
 
@@ -8,11 +8,13 @@ use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tokio_stream::Stream;
 use tokio_util::codec::Framed;
 
+use flo_util::buf::READ_BUFFER_SIZE;
+
 use crate::error::*;
 use crate::protocol::packet::{Packet, PacketPayload, PacketPayloadDecode};
 
 mod codec;
-use self::codec::W3GSCodec;
+pub use self::codec::W3GSCodec;
 
 #[derive(Debug)]
 pub struct W3GSListener {
@@ -63,7 +65,7 @@ impl W3GSStream {
     Ok(W3GSStream {
       local_addr: socket.local_addr()?,
       peer_addr: None,
-      transport: Framed::new(socket, W3GSCodec::new()),
+      transport: Framed::with_capacity(socket, W3GSCodec::new(), READ_BUFFER_SIZE),
     })
   }
 
@@ -142,7 +144,7 @@ impl Incoming<'_> {
     let stream = W3GSStream {
       local_addr: socket.local_addr()?,
       peer_addr: Some(addr),
-      transport: Framed::new(socket, W3GSCodec::new()),
+      transport: Framed::with_capacity(socket, W3GSCodec::new(), READ_BUFFER_SIZE),
     };
 
     Poll::Ready(Ok(stream))
@@ -356,7 +356,12 @@ impl Iterator for ActionIter {
 
   fn next(&mut self) -> Option<Self::Item> {
     if self.data.has_remaining() {
-      Some(Action::decode(&mut self.data).map_err(Into::into))
+      let item = Action::decode(&mut self.data).map_err(Into::into);
+      if item.is_err() {
+        // the next action can't be located after a malformed one
+        self.data.clear();
+      }
+      Some(item)
     } else {
       None
     }
@@ -448,3 +453,15 @@ fn test_incoming_action2_split_chunk_30() {
     30_usize
   )
 }
+
+#[test]
+fn test_action_iter_stops_on_error() {
+  let action = PlayerAction {
+    player_id: 1,
+    data: Bytes::from_static(&[0x01, 0xFF, 0x01, 0x02]),
+  };
+  let items: Vec<_> = action.actions().collect();
+  assert_eq!(items.len(), 2);
+  assert!(items[0].is_ok());
+  assert!(items[1].is_err());
+}
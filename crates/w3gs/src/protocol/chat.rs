@@ -1,6 +1,7 @@
 use flo_util::binary::*;
 use flo_util::{BinDecode, BinEncode};
 
+use crate::error::*;
 use crate::protocol::constants::{MessageType, PacketTypeId};
 use crate::protocol::packet::PacketPayload;
 
@@ -159,7 +160,7 @@ impl BinDecode for MessageScope {
       0x00 => Ok(Self::All),
       0x01 => Ok(Self::Allies),
       0x02 => Ok(Self::Observers),
-      n if n <= (u8::MAX - 0x03) as u32 => Ok(Self::Player((n - 0x03) as u8)),
+      n if n <= u8::MAX as u32 + 0x02 => Ok(Self::Player((n - 0x02) as u8)),
       n => Err(BinDecodeError::failure(format!(
         "invalid chat message scope value: {}",
         n
@@ -179,6 +180,30 @@ impl BinEncode for MessageScope {
   }
 }
 
+/// The leading fields of a chat payload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatHeader {
+  pub from_player: u8,
+  /// `None` if not an in-game message
+  pub scope: Option<MessageScope>,
+}
+
+impl ChatHeader {
+  /// Reads the header of a `ChatToHost`, `ChatFromHost` or `ChatFromOthers` payload,
+  /// skipping the receiver list and the message text
+  pub fn peek(mut data: &[u8]) -> Result<Self> {
+    let to_players_len = u8::decode(&mut data)? as usize;
+    data.check_size(to_players_len)?;
+    data.advance(to_players_len);
+    let from_player = u8::decode(&mut data)?;
+    let scope = match MessageType::decode(&mut data)? {
+      MessageType::Scoped => Some(MessageScope::decode(&mut data)?),
+      _ => None,
+    };
+    Ok(Self { from_player, scope })
+  }
+}
+
 #[derive(Debug, BinEncode, BinDecode, PartialEq)]
 pub struct ChatFromHost(pub ChatToHost);
 
@@ -193,6 +218,12 @@ impl ChatFromHost {
     self.0.from_player
   }
 
+  /// Reads the sender and scope without decoding the message
+  #[inline]
+  pub fn peek_header(data: &[u8]) -> Result<ChatHeader> {
+    ChatHeader::peek(data)
+  }
+
   pub fn lobby(from: u8, to: &[u8], message: impl IntoCStringLossy) -> Self {
     ChatToHost::lobby(from, to, message).into()
   }
@@ -224,3 +255,37 @@ impl ChatFromOthers {
 impl PacketPayload for ChatFromOthers {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::ChatFromOthers;
 }
+
+#[test]
+fn test_chat_header_peek() {
+  let encode = |msg: ChatToHost| {
+    let mut buf = vec![];
+    msg.encode(&mut buf);
+    buf
+  };
+
+  let data = encode(ChatToHost::in_game(
+    MessageScope::Allies,
+    2,
+    &[1, 3, 4],
+    "hello",
+  ));
+  assert_eq!(
+    ChatFromHost::peek_header(&data).unwrap(),
+    ChatHeader {
+      from_player: 2,
+      scope: Some(MessageScope::Allies),
+    }
+  );
+
+  let data = encode(ChatToHost::lobby(5, &[1], "hello"));
+  assert_eq!(
+    ChatHeader::peek(&data).unwrap(),
+    ChatHeader {
+      from_player: 5,
+      scope: None,
+    }
+  );
+
+  assert!(ChatHeader::peek(&data[..3]).is_err());
+}
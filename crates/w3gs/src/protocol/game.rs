@@ -94,7 +94,7 @@ impl BinDecode for GameSettings {
 
     let mut buf = &data[..];
 
-    let game_setting_flags = buf.get_u32_le();
+    let game_setting_flags = u32::decode(&mut buf)?;
     let game_setting_flags = GameSettingFlags::from_bits(game_setting_flags).ok_or_else(|| {
       BinDecodeError::failure(format!(
         "unknown game flags value: 0x{:x}",
@@ -102,11 +102,11 @@ impl BinDecode for GameSettings {
       ))
     })?;
 
-    let unk_1 = buf.get_u8();
+    let unk_1 = u8::decode(&mut buf)?;
 
-    let map_width = buf.get_u16_le();
-    let map_height = buf.get_u16_le();
-    let map_xoro = buf.get_u32_le();
+    let map_width = u16::decode(&mut buf)?;
+    let map_height = u16::decode(&mut buf)?;
+    let map_xoro = u32::decode(&mut buf)?;
     let map_path = CString::decode(&mut buf)?;
 
     let host_name = CString::decode(&mut buf)?;
@@ -186,3 +186,24 @@ fn test_game_loaded_self() {
 fn test_player_loaded() {
   crate::packet::test_simple_payload_type("player_loaded.bin", &PlayerLoaded { player_id: 2 })
 }
+
+#[test]
+fn test_game_settings_flags() {
+  let settings = GameSettings::new(
+    GameSettingFlags::default() | GameSettingFlags::SHARED_CONTROL,
+    GameSettingsMap {
+      path: "Maps/(4)twistedmeadows.w3x".to_string(),
+      width: 116,
+      height: 116,
+      sha1: [1; 20],
+      checksum: 0x12345678,
+    },
+  );
+  let mut buf = vec![];
+  settings.encode(&mut buf);
+  let decoded = GameSettings::decode(&mut buf.as_slice()).unwrap();
+  assert_eq!(decoded, settings);
+  assert!(decoded
+    .game_setting_flags
+    .contains(GameSettingFlags::SHARED_CONTROL | GameSettingFlags::TEAMS_FIXED));
+}
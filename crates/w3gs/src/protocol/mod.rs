@@ -12,6 +12,13 @@ pub mod ping;
 pub mod player;
 pub mod slot;
 
+#[cfg(test)]
+mod round_trip_test;
+
 mod protobuf {
   include!(concat!(env!("OUT_DIR"), "/w3gs.rs"));
 }
+
+/// Encoded `FileDescriptorSet` of the protobuf payloads
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+  include_bytes!(concat!(env!("OUT_DIR"), "/w3gs_descriptor.bin"));
@@ -71,15 +71,11 @@ impl Packet {
   where
     T: PacketPayload + PacketPayloadEncode + std::fmt::Debug,
   {
-    let mut buf = if let Some(len) = payload.encode_len() {
-      BytesMut::with_capacity(len)
-    } else {
-      BytesMut::new()
-    };
-
     // dbg!(&payload);
 
-    payload.encode(&mut buf);
+    let buf = flo_util::buf::encode_bytes(payload.encode_len().unwrap_or_default(), |buf| {
+      payload.encode(buf)
+    });
 
     if buf.len() > (std::u16::MAX - 4) as usize {
       return Err(Error::PayloadSizeOverflow);
@@ -87,7 +83,7 @@ impl Packet {
 
     Ok(Packet {
       header: Header::new(T::PACKET_TYPE_ID, (buf.len() as u16) + 4),
-      payload: buf.freeze(),
+      payload: buf,
     })
   }
 
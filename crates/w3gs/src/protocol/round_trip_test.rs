@@ -0,0 +1,298 @@
+//! encode -> decode -> encode round trip tests for every packet payload.
+//!
+//! Each payload is decoded from seeded random bytes and from mutations of
+//! its seed value, every decoded value must encode to bytes that decode
+//! to the same value again. `test_round_trip_covers_all_packet_types` fails
+//! if a packet type is added without an entry in `round_trip!`.
+
+use flo_util::binary::*;
+
+use crate::protocol::action::{
+  IncomingAction, IncomingAction2, OutgoingAction, OutgoingKeepAlive, PlayerAction, TimeSlot,
+};
+use crate::protocol::chat::{ChatFromHost, ChatFromOthers, ChatToHost, MessageScope};
+use crate::protocol::constants::{LeaveReason, PacketTypeId};
+use crate::protocol::desync::Desync;
+use crate::protocol::game::{
+  self, CountDownEnd, CountDownStart, GameLoadedSelf, GameSettings, GameSettingsMap,
+};
+use crate::protocol::join::{RejectJoin, ReqJoin, SlotInfoJoin};
+use crate::protocol::lag::{LagPlayer, StartLag, StopLag};
+use crate::protocol::leave::{LeaveAck, LeaveReq, PlayerKicked, PlayerLeft};
+use crate::protocol::map::{MapCheck, MapSize};
+use crate::protocol::packet::{
+  Packet, PacketPayload, PacketPayloadDecode, PacketPayloadEncode, ProtoBufPayload, SimplePayload,
+};
+use crate::protocol::ping::{PingFromHost, PongToHost};
+use crate::protocol::player::{self, PlayerInfo, PlayerProfileMessage};
+use crate::protocol::slot::SlotInfo;
+
+const SEED: u64 = 0x666C_6F5F_7733_6773;
+const RANDOM_INPUTS: usize = 2000;
+const MUTATIONS: usize = 500;
+const MAX_INPUT_LEN: usize = 128;
+
+/// Types without a payload struct yet, adding one requires a `round_trip!` entry
+const NO_PAYLOAD: &[PacketTypeId] = &[
+  PacketTypeId::GameOver,
+  PacketTypeId::DropReq,
+  PacketTypeId::SearchGame,
+  PacketTypeId::GameInfo,
+  PacketTypeId::CreateGame,
+  PacketTypeId::RefreshGame,
+  PacketTypeId::DecreateGame,
+  PacketTypeId::PingFromOthers,
+  PacketTypeId::PongToOthers,
+  PacketTypeId::ClientInfo,
+  PacketTypeId::PeerSet,
+  PacketTypeId::StartDownload,
+  PacketTypeId::MapPart,
+  PacketTypeId::MapPartOK,
+  PacketTypeId::MapPartError,
+];
+
+/// Generates a test per payload type.
+///
+/// `simple` types implement `BinEncode` + `BinDecode`, `payload` types
+/// `PacketPayloadEncode` + `PacketPayloadDecode`. The optional seed is needed by
+/// types random bytes rarely decode to, e.g. checksums or `#[bin(eq)]` fields.
+macro_rules! round_trip {
+  (
+    simple { $($simple:ident: $simple_ty:ty $(= $simple_seed:expr)?,)* }
+    payload { $($payload:ident: $payload_ty:ty $(= $payload_seed:expr)?,)* }
+  ) => {
+    const COVERED: &[PacketTypeId] = &[
+      $(<$simple_ty as PacketPayload>::PACKET_TYPE_ID,)*
+      $(<$payload_ty as PacketPayload>::PACKET_TYPE_ID,)*
+    ];
+
+    $(
+      #[test]
+      fn $simple() {
+        let seed: Option<Bytes> = None $(.or(Some(Packet::simple($simple_seed).unwrap().payload)))?;
+        check::<SimplePayload<$simple_ty>>(stringify!($simple_ty), seed);
+      }
+    )*
+
+    $(
+      #[test]
+      fn $payload() {
+        let seed: Option<Bytes> = None $(.or(Some(Packet::with_payload($payload_seed).unwrap().payload)))?;
+        check::<$payload_ty>(stringify!($payload_ty), seed);
+      }
+    )*
+  };
+}
+
+round_trip! {
+  simple {
+    leave_req: LeaveReq = LeaveReq::new(LeaveReason::LeaveLost),
+    leave_ack: LeaveAck,
+    player_left: PlayerLeft,
+    player_kicked: PlayerKicked,
+    start_lag: StartLag = StartLag::new(vec![LagPlayer {
+      player_id: 1,
+      lag_duration_ms: 100,
+    }]),
+    stop_lag: StopLag,
+    req_join: ReqJoin = ReqJoin::new("flo", 1, 2),
+    slot_info_join: SlotInfoJoin = SlotInfoJoin {
+      slot_info: SlotInfo::default(),
+      player_id: 1,
+      external_addr: SockAddr::new_ipv4([127, 0, 0, 1], 6112),
+    },
+    reject_join: RejectJoin = RejectJoin::FULL,
+    outgoing_keep_alive: OutgoingKeepAlive,
+    slot_info: SlotInfo = SlotInfo::default(),
+    protobuf: ProtoBufPayload = ProtoBufPayload::new(PlayerProfileMessage::new(1, "flo")),
+    ping_from_host: PingFromHost,
+    pong_to_host: PongToHost,
+    player_info: PlayerInfo = PlayerInfo::new(1, "flo"),
+    player_loaded: player::PlayerLoaded,
+    chat_to_host: ChatToHost = ChatToHost::in_game(MessageScope::Player(2), 1, &[2], "gl hf"),
+    chat_from_host: ChatFromHost = ChatFromHost::lobby(1, &[2, 3], "hi"),
+    chat_from_others: ChatFromOthers = ChatFromOthers::new(ChatToHost::lobby(1, &[2], "hi")),
+    count_down_start: CountDownStart,
+    count_down_end: CountDownEnd,
+    game_loaded_self: GameLoadedSelf,
+    game_player_loaded: game::PlayerLoaded,
+    map_check: MapCheck = MapCheck::new(
+      127172,
+      1444344839,
+      &GameSettings::new(
+        Default::default(),
+        GameSettingsMap {
+          path: "Maps/(2)bootybay.w3m".to_string(),
+          width: 84,
+          height: 84,
+          sha1: [1; 20],
+          checksum: 2039165270,
+        },
+      ),
+    ),
+    map_size: MapSize = MapSize::new(127172),
+    desync: Desync = Desync {
+      unknown_1: 1,
+      unknown_2: 4,
+      unknown_3: 2,
+      unknown_4: 0,
+    },
+  }
+  payload {
+    outgoing_action: OutgoingAction = OutgoingAction::new(&[0x16, 0x01, 0x01, 0x00, 1, 0, 0, 0]),
+    incoming_action: IncomingAction = IncomingAction(time_slot()),
+    incoming_action_2: IncomingAction2 = IncomingAction2(time_slot()),
+  }
+}
+
+fn time_slot() -> TimeSlot {
+  TimeSlot {
+    time_increment_ms: 100,
+    actions: vec![
+      PlayerAction {
+        player_id: 1,
+        data: Bytes::from_static(&[0x16, 0x01, 0x01, 0x00]),
+      },
+      PlayerAction {
+        player_id: 2,
+        data: Bytes::new(),
+      },
+    ],
+  }
+}
+
+/// Round trips the seed, its mutations and random inputs
+fn check<T>(name: &str, seed: Option<Bytes>)
+where
+  T: PacketPayload + PacketPayloadEncode + PacketPayloadDecode,
+{
+  let mut rng = Rng(SEED);
+  let mut decoded = 0;
+
+  if let Some(seed) = seed {
+    assert!(
+      round_trip::<T>(name, seed.clone()),
+      "{}: seed does not decode",
+      name
+    );
+    decoded += 1;
+
+    for _ in 0..MUTATIONS {
+      let mut input = seed.to_vec();
+      if input.is_empty() {
+        break;
+      }
+      for _ in 0..=rng.below(3) {
+        let i = rng.below(input.len());
+        input[i] = rng.byte();
+      }
+      if round_trip::<T>(name, Bytes::from(input)) {
+        decoded += 1;
+      }
+    }
+  }
+
+  for i in 0..RANDOM_INPUTS {
+    let len = rng.below(1 + i * MAX_INPUT_LEN / RANDOM_INPUTS);
+    let input: Vec<u8> = (0..len).map(|_| rng.byte()).collect();
+    if round_trip::<T>(name, Bytes::from(input)) {
+      decoded += 1;
+    }
+  }
+
+  assert!(decoded > 0, "{}: no input decoded", name);
+}
+
+/// Returns `false` if `input` does not decode, panics if the encoding
+/// of the decoded value does not decode to the same encoding
+fn round_trip<T>(name: &str, input: Bytes) -> bool
+where
+  T: PacketPayloadEncode + PacketPayloadDecode,
+{
+  let value = match T::decode(&mut input.clone()) {
+    Ok(value) => value,
+    Err(_) => return false,
+  };
+  let encoded = value.encode_to_bytes();
+
+  let mut buf = encoded.clone();
+  let value = T::decode(&mut buf).unwrap_or_else(|err| {
+    panic!(
+      "{}: decode re-encoded {:?} (input {:?}): {}",
+      name, encoded, input, err
+    )
+  });
+  assert!(
+    !buf.has_remaining(),
+    "{}: {} bytes left decoding {:?} (input {:?})",
+    name,
+    buf.remaining(),
+    encoded,
+    input
+  );
+  assert_eq!(
+    value.encode_to_bytes(),
+    encoded,
+    "{}: encoding changed (input {:?})",
+    name,
+    input
+  );
+  true
+}
+
+/// xorshift64*, biased towards the bytes common in payloads
+struct Rng(u64);
+
+impl Rng {
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 >> 12;
+    self.0 ^= self.0 << 25;
+    self.0 ^= self.0 >> 27;
+    self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+  }
+
+  fn below(&mut self, n: usize) -> usize {
+    (self.next() % n.max(1) as u64) as usize
+  }
+
+  fn byte(&mut self) -> u8 {
+    match self.below(10) {
+      0..=3 => 0,
+      4 => 1,
+      5 => 2,
+      6 => 4,
+      7 => 0xFF,
+      _ => self.next() as u8,
+    }
+  }
+}
+
+#[test]
+fn test_round_trip_covers_all_packet_types() {
+  for v in 0..=u8::MAX {
+    let type_id = PacketTypeId::from(v);
+    if let PacketTypeId::UnknownValue(_) = type_id {
+      continue;
+    }
+    assert!(
+      COVERED.contains(&type_id) || NO_PAYLOAD.contains(&type_id),
+      "{:?} has no round trip test, add its payload to `round_trip!`",
+      type_id
+    );
+  }
+}
+
+#[test]
+fn test_message_scope_round_trip() {
+  for scope in &[
+    MessageScope::All,
+    MessageScope::Allies,
+    MessageScope::Observers,
+    MessageScope::Player(1),
+    MessageScope::Player(u8::MAX),
+  ] {
+    let mut buf = vec![];
+    scope.encode(&mut buf);
+    assert_eq!(&MessageScope::decode(&mut buf.as_slice()).unwrap(), scope);
+  }
+}
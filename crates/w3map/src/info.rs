@@ -2,8 +2,9 @@ use crate::trigger_string::TriggerStringRef;
 use flo_util::binary::*;
 use flo_util::dword_string::DwordString;
 use flo_util::{BinDecode, BinEncode};
+use std::cmp::Ordering;
 
-#[derive(Debug, BinEncode, BinDecode, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, BinEncode, BinDecode, Clone, Copy)]
 #[bin(enum_repr(u32))]
 pub enum MapFormatVersion {
   #[bin(value = 18)]
@@ -17,6 +18,32 @@ pub enum MapFormatVersion {
   UnknownValue(u32),
 }
 
+impl MapFormatVersion {
+  pub fn value(&self) -> u32 {
+    match *self {
+      MapFormatVersion::ROC => 18,
+      MapFormatVersion::TFT => 25,
+      MapFormatVersion::TFT131 => 28,
+      MapFormatVersion::Reforged => 31,
+      MapFormatVersion::UnknownValue(v) => v,
+    }
+  }
+}
+
+// Versions are compared by their raw value so that unknown (newer) versions
+// are decoded using the layout of the closest known version.
+impl PartialEq for MapFormatVersion {
+  fn eq(&self, other: &Self) -> bool {
+    self.value() == other.value()
+  }
+}
+
+impl PartialOrd for MapFormatVersion {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    self.value().partial_cmp(&other.value())
+  }
+}
+
 #[derive(Debug, BinDecode)]
 pub struct MapInfo {
   pub version: MapFormatVersion,
@@ -162,6 +189,138 @@ pub struct Force {
   pub name: TriggerStringRef,
 }
 
+#[cfg(test)]
+fn build_w3i_fixture(version: u32, code_format: u32) -> Vec<u8> {
+  fn put_str(buf: &mut Vec<u8>, value: &str) {
+    buf.put_slice(value.as_bytes());
+    buf.put_u8(0);
+  }
+
+  let mut buf = vec![];
+  buf.put_u32_le(version);
+  if version >= 18 {
+    buf.put_u32_le(1); // save_count
+    buf.put_u32_le(6105); // editor_version
+  }
+  if version >= 28 {
+    for v in &[1, 32, 6, 6105] {
+      buf.put_u32_le(*v);
+    }
+  }
+  put_str(&mut buf, "TRIGSTR_001");
+  put_str(&mut buf, "TRIGSTR_002");
+  put_str(&mut buf, "TRIGSTR_003");
+  put_str(&mut buf, "TRIGSTR_004");
+  for _ in 0..8 {
+    buf.put_f32_le(0.0);
+  }
+  for _ in 0..4 {
+    buf.put_u32_le(0);
+  }
+  buf.put_u32_le(96); // width
+  buf.put_u32_le(96); // height
+  buf.put_u32_le(0x0004); // flags: melee
+  buf.put_u8(b'L');
+  buf.put_i32_le(-1);
+  if version != 18 {
+    buf.put_u8(0);
+  }
+  for _ in 0..3 {
+    buf.put_u8(0);
+  }
+  if version >= 18 {
+    buf.put_u32_le(0);
+  }
+  if version != 18 {
+    buf.put_u8(0);
+  }
+  for _ in 0..3 {
+    buf.put_u8(0);
+  }
+  if version >= 25 {
+    buf.put_u32_le(0);
+    for _ in 0..3 {
+      buf.put_f32_le(0.0);
+    }
+    buf.put_slice(&[0, 0, 0, 0]);
+    buf.put_u32_le(0);
+    buf.put_u8(0);
+    buf.put_u8(0);
+    buf.put_slice(&[0, 0, 0, 0]);
+  }
+  if version >= 28 {
+    buf.put_u32_le(code_format);
+  }
+  if version >= 31 {
+    buf.put_u32_le(3); // SD & HD
+    buf.put_u32_le(2); // TFT
+  }
+  buf.put_u32_le(2);
+  for id in 0..2 {
+    buf.put_u32_le(id);
+    buf.put_u32_le(1);
+    buf.put_u32_le(0);
+    buf.put_u32_le(0);
+    put_str(&mut buf, "");
+    buf.put_f32_le(0.0);
+    buf.put_f32_le(0.0);
+    buf.put_u32_le(0);
+    buf.put_u32_le(0);
+    if version >= 31 {
+      buf.put_u32_le(0);
+      buf.put_u32_le(0);
+    }
+  }
+  buf.put_u32_le(1);
+  buf.put_u32_le(0);
+  buf.put_u32_le(0b11);
+  put_str(&mut buf, "TRIGSTR_005");
+  buf
+}
+
+#[test]
+fn test_parse_w3i_fixtures() {
+  let cases = &[
+    (25, 0, MapFormatVersion::TFT, None, false),
+    (28, 0, MapFormatVersion::TFT131, Some(CodeLanguage::Jass), false),
+    (28, 1, MapFormatVersion::TFT131, Some(CodeLanguage::Lua), false),
+    (31, 1, MapFormatVersion::Reforged, Some(CodeLanguage::Lua), true),
+    (
+      33,
+      0,
+      MapFormatVersion::UnknownValue(33),
+      Some(CodeLanguage::Jass),
+      true,
+    ),
+  ];
+
+  for (version, code_format, expected_version, expected_code_format, reforged) in cases {
+    let bytes = build_w3i_fixture(*version, *code_format);
+    let mut buf = bytes.as_slice();
+    let info = MapInfo::decode(&mut buf).unwrap();
+    assert!(!buf.has_remaining(), "version {}", version);
+    assert_eq!(info.version, *expected_version);
+    assert_eq!(info.version.value(), *version);
+    assert_eq!(info.code_format, *expected_code_format);
+    assert_eq!(info.num_players, 2);
+    assert_eq!(info.players_reforged.is_some(), *reforged);
+    assert_eq!(info.players_classic.is_some(), !*reforged);
+    if *reforged {
+      assert_eq!(info.asset_modes, Some(AssetMode::SDAndHD));
+      assert_eq!(info.data_version, Some(GameDataVersion::TFT));
+    }
+    assert_eq!(info.num_forces, 1);
+    assert_eq!(info.forces[0].player_set, 0b11);
+  }
+}
+
+#[test]
+fn test_map_format_version_ordering() {
+  assert!(MapFormatVersion::UnknownValue(33) > MapFormatVersion::Reforged);
+  assert!(MapFormatVersion::UnknownValue(8) < MapFormatVersion::ROC);
+  assert_eq!(MapFormatVersion::UnknownValue(25), MapFormatVersion::TFT);
+}
+
 #[test]
 fn test_parse_w3i_reforged() {
   let mut map = crate::open_archive(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
@@ -171,6 +330,10 @@ fn test_parse_w3i_reforged() {
   assert_eq!(info.version, MapFormatVersion::Reforged);
   assert_eq!(info.num_players, 2);
   assert_eq!(info.num_forces, 1);
+  assert!(info.code_format.is_some());
+  assert!(info.asset_modes.is_some());
+  assert!(info.players_classic.is_none());
+  assert_eq!(info.players_reforged.as_ref().map(Vec::len), Some(2));
   dbg!("{:#?}", info);
 }
 
@@ -195,6 +358,8 @@ fn test_parse_w3i_tft() {
   assert_eq!(info.version, MapFormatVersion::TFT);
   //assert_eq!(info.num_players, 0);
   assert_eq!(info.num_forces, 1);
+  assert!(info.code_format.is_none());
+  assert!(info.players_classic.is_some());
   dbg!("{:#?}", info);
 }
 
@@ -11,6 +11,8 @@ mod checksum;
 mod constants;
 mod info;
 mod minimap;
+#[cfg(feature = "w3storage")]
+pub mod scan;
 mod trigger_string;
 
 pub use self::checksum::MapChecksum;
@@ -220,6 +222,18 @@ impl W3Map {
   pub fn flags(&self) -> MapFlags {
     MapFlags::from_bits_truncate(self.info.flags)
   }
+
+  pub fn format_version(&self) -> MapFormatVersion {
+    self.info.version
+  }
+
+  pub fn script_language(&self) -> CodeLanguage {
+    self.info.code_format.unwrap_or(CodeLanguage::Jass)
+  }
+
+  pub fn asset_mode(&self) -> AssetMode {
+    self.info.asset_modes.unwrap_or(AssetMode::SD)
+  }
 }
 
 pub(crate) fn open_archive<P: AsRef<Path>>(path: P) -> Result<stormlib::Archive> {
@@ -286,8 +300,15 @@ impl W3Map {
               Some(bytes)
             }
           })
-          .map(|bytes| BLPImage::decode(&mut bytes.as_slice()).map_err(Error::ReadImage))
-          .transpose()?
+          .and_then(|bytes| match BLPImage::decode(&mut bytes.as_slice()) {
+            Ok(image) => Some(image),
+            // Reforged maps may ship palettized or DXT encoded previews which are not
+            // supported by the BLP decoder, a missing preview must not fail the whole map
+            Err(err) => {
+              tracing::warn!("decode map preview: {}", err);
+              None
+            }
+          })
       },
       minimap_icons: {
         let bytes = archive.read_file_all_opt("war3map.mmp")?;
@@ -0,0 +1,214 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use flo_w3storage::W3Storage;
+
+use crate::{MapChecksum, MapFlags, W3Map};
+
+/// Parses metadata and checksums of many maps in parallel.
+/// Maps in override directories are read in parallel, reads from the CASC storage
+/// are serialized by `W3Storage` and only their parsing is parallel.
+/// Results are cached and only recomputed if the map file changed.
+#[derive(Debug)]
+pub struct MapScanner {
+  num_workers: usize,
+  cache: Mutex<HashMap<String, CacheEntry>>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+  fingerprint: Option<Fingerprint>,
+  item: Arc<MapScanItem>,
+}
+
+// Files inside the CASC storage have no fingerprint,
+// they are cached until `MapScanner::clear` is called.
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+  size: u64,
+  modified: Option<SystemTime>,
+}
+
+#[derive(Debug)]
+pub struct MapScanItem {
+  pub path: String,
+  pub result: Result<MapSummary, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MapSummary {
+  pub name: String,
+  pub author: String,
+  pub suggested_players: String,
+  pub width: u32,
+  pub height: u32,
+  pub num_players: usize,
+  pub twelve_p: bool,
+  pub melee: bool,
+  pub checksum: MapChecksum,
+}
+
+impl MapScanner {
+  pub fn new() -> Self {
+    let num_workers = std::thread::available_parallelism()
+      .map(|v| v.get())
+      .unwrap_or(4);
+    Self::with_workers(num_workers)
+  }
+
+  pub fn with_workers(num_workers: usize) -> Self {
+    MapScanner {
+      num_workers: std::cmp::max(1, num_workers),
+      cache: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub fn clear(&self) {
+    self.cache.lock().clear();
+  }
+
+  pub fn scan<T>(&self, storage: &W3Storage, paths: &[T]) -> Vec<Arc<MapScanItem>>
+  where
+    T: AsRef<str> + Sync,
+  {
+    let next = AtomicUsize::new(0);
+    let mut items: Vec<(usize, Arc<MapScanItem>)> = std::thread::scope(|s| {
+      let workers: Vec<_> = (0..std::cmp::min(self.num_workers, paths.len()))
+        .map(|_| {
+          s.spawn(|| {
+            let mut items = vec![];
+            loop {
+              let idx = next.fetch_add(1, Ordering::SeqCst);
+              if let Some(path) = paths.get(idx) {
+                items.push((idx, self.scan_one(storage, path.as_ref())));
+              } else {
+                break;
+              }
+            }
+            items
+          })
+        })
+        .collect();
+      workers
+        .into_iter()
+        .flat_map(|w| w.join().expect("map scan worker panicked"))
+        .collect()
+    });
+    items.sort_by_key(|(idx, _)| *idx);
+    items.into_iter().map(|(_, item)| item).collect()
+  }
+
+  fn scan_one(&self, storage: &W3Storage, path: &str) -> Arc<MapScanItem> {
+    let fingerprint = match storage.resolve_override(path) {
+      Ok(v) => v.map(|(_, m)| Fingerprint {
+        size: m.len(),
+        modified: m.modified().ok(),
+      }),
+      Err(err) => {
+        return Arc::new(MapScanItem {
+          path: path.to_string(),
+          result: Err(err.to_string()),
+        })
+      }
+    };
+
+    if let Some(entry) = self.cache.lock().get(path) {
+      if entry.fingerprint == fingerprint {
+        return entry.item.clone();
+      }
+    }
+
+    let item = Arc::new(MapScanItem {
+      path: path.to_string(),
+      result: W3Map::open_storage_with_checksum(storage, path)
+        .map(|(map, checksum)| {
+          let (width, height) = map.dimension();
+          MapSummary {
+            name: map.name().to_string(),
+            author: map.author().to_string(),
+            suggested_players: map.suggested_players().to_string(),
+            width,
+            height,
+            num_players: map.num_players(),
+            twelve_p: map.is_twelve_p(),
+            melee: map.flags().contains(MapFlags::MELEE),
+            checksum,
+          }
+        })
+        .map_err(|err| err.to_string()),
+    });
+
+    self.cache.lock().insert(
+      path.to_string(),
+      CacheEntry {
+        fingerprint,
+        item: item.clone(),
+      },
+    );
+
+    item
+  }
+}
+
+#[test]
+fn test_scan_storage_maps() {
+  let storage = W3Storage::from_env().unwrap();
+  let paths = storage.list_files("maps\\*").unwrap();
+  let scanner = MapScanner::new();
+  let items = scanner.scan(&storage, &paths);
+  assert_eq!(items.len(), paths.len());
+  for (item, path) in items.iter().zip(paths.iter()) {
+    assert_eq!(&item.path, path);
+  }
+  let cached = scanner.scan(&storage, &paths);
+  for (a, b) in items.iter().zip(cached.iter()) {
+    assert!(Arc::ptr_eq(a, b));
+  }
+}
+
+#[test]
+fn test_scan_override_dir() {
+  use flo_platform::ClientPlatformInfo;
+
+  let dir = std::env::temp_dir().join(format!("flo_map_scan_test_{}", std::process::id()));
+  std::fs::create_dir_all(dir.join("maps")).unwrap();
+  std::fs::write(dir.join("maps").join("a.w3x"), b"not a map").unwrap();
+  std::fs::write(dir.join("maps").join("b.w3x"), b"not a map").unwrap();
+
+  let storage = W3Storage::new(&ClientPlatformInfo {
+    user_data_path: dir.clone(),
+    installation_path: dir.join("missing"),
+    version: String::new(),
+    executable_path: std::path::PathBuf::new(),
+    ptr: false,
+    user_battlenet_id: String::new(),
+  })
+  .unwrap();
+  let paths = vec!["maps/a.w3x", "maps/b.w3x", "maps/c.w3x"];
+  let scanner = MapScanner::with_workers(2);
+
+  let items = scanner.scan(&storage, &paths);
+  assert_eq!(
+    items
+      .iter()
+      .map(|item| item.path.as_str())
+      .collect::<Vec<_>>(),
+    paths
+  );
+  assert!(items.iter().all(|item| item.result.is_err()));
+
+  let cached = scanner.scan(&storage, &paths);
+  for (a, b) in items.iter().zip(cached.iter()) {
+    assert!(Arc::ptr_eq(a, b));
+  }
+
+  std::fs::write(dir.join("maps").join("a.w3x"), b"still not a map").unwrap();
+  let rescanned = scanner.scan(&storage, &paths);
+  assert!(!Arc::ptr_eq(&items[0], &rescanned[0]));
+  assert!(Arc::ptr_eq(&items[1], &rescanned[1]));
+
+  std::fs::remove_dir_all(&dir).ok();
+}
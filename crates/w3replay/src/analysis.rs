@@ -0,0 +1,299 @@
+//! Extracts APM, production timelines and chat from replay records.
+//! Works for both native and flo-generated replays.
+
+use flo_w3gs::actions::Action;
+use flo_w3gs::protocol::chat::MessageScope;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use crate::error::*;
+use crate::{ChatMessage, PlayerInfo, Record, SlotInfo, TimeSlot, W3Replay};
+
+/// Length of one APM curve bucket
+pub const APM_INTERVAL_MS: u32 = 60_000;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReplayAnalysis {
+  pub game_name: String,
+  pub duration_ms: u32,
+  pub players: Vec<PlayerAnalysis>,
+  pub chat: Vec<ChatEntry>,
+}
+
+impl ReplayAnalysis {
+  pub fn player(&self, player_id: u8) -> Option<&PlayerAnalysis> {
+    self.players.iter().find(|p| p.player_id == player_id)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PlayerAnalysis {
+  pub player_id: u8,
+  pub name: String,
+  pub observer: bool,
+  pub action_count: u32,
+  /// Action counts of each `APM_INTERVAL_MS` bucket
+  pub apm_curve: Vec<u32>,
+  pub production: Vec<ProductionEvent>,
+  pub left_at_ms: Option<u32>,
+}
+
+impl PlayerAnalysis {
+  /// Average APM over the time the player stayed in the game
+  pub fn apm(&self, duration_ms: u32) -> f32 {
+    let time_ms = self.left_at_ms.unwrap_or(duration_ms);
+    if time_ms == 0 {
+      0.0
+    } else {
+      self.action_count as f32 * 60000.0 / time_ms as f32
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProductionEvent {
+  pub time_ms: u32,
+  pub kind: ProductionKind,
+  pub item_id: ItemId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ProductionKind {
+  /// A building placed on the map
+  Build,
+  /// A unit, hero or upgrade queued in a building
+  Train,
+  /// An item removed from a building queue
+  Cancel,
+}
+
+/// A four character object id such as `hpea`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId(pub u32);
+
+impl ItemId {
+  /// Abilities without an object id use numeric order ids instead
+  pub fn is_object(&self) -> bool {
+    self.0.to_be_bytes().iter().all(u8::is_ascii_alphanumeric)
+  }
+}
+
+impl fmt::Display for ItemId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.is_object() {
+      f.write_str(&String::from_utf8_lossy(&self.0.to_be_bytes()))
+    } else {
+      write!(f, "0x{:08X}", self.0)
+    }
+  }
+}
+
+/// Serialized as the display form, e.g. `"hpea"`
+#[cfg(feature = "serde")]
+impl serde::Serialize for ItemId {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl fmt::Debug for ItemId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "ItemId({})", self)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChatEntry {
+  pub time_ms: u32,
+  pub player_id: u8,
+  #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_scope"))]
+  pub scope: MessageScope,
+  pub message: String,
+}
+
+#[cfg(feature = "serde")]
+fn serialize_scope<S: serde::Serializer>(
+  scope: &MessageScope,
+  serializer: S,
+) -> Result<S::Ok, S::Error> {
+  serializer.collect_str(&format_args!("{:?}", scope))
+}
+
+pub fn analyze<R: Read>(replay: W3Replay<R>) -> Result<ReplayAnalysis> {
+  let mut analyzer = ReplayAnalyzer::default();
+  for record in replay.into_records() {
+    analyzer.push(&record?);
+  }
+  Ok(analyzer.finish())
+}
+
+/// Accumulates statistics record by record,
+/// can be fed directly without an encoded replay
+#[derive(Debug, Default)]
+pub struct ReplayAnalyzer {
+  time: u32,
+  game_name: String,
+  players: BTreeMap<u8, PlayerAnalysis>,
+  observer_ids: Vec<u8>,
+  chat: Vec<ChatEntry>,
+}
+
+impl ReplayAnalyzer {
+  pub fn push(&mut self, record: &Record) {
+    match record {
+      Record::GameInfo(info) => {
+        self.game_name = info.game_name.to_string_lossy().to_string();
+        self.add_player(&info.host_player_info);
+      }
+      Record::PlayerInfo(info) => self.add_player(&info.player_info),
+      Record::SlotInfo(info) => self.set_slots(info),
+      Record::PlayerLeft(left) => {
+        if let Some(player) = self.players.get_mut(&left.player_id) {
+          player.left_at_ms.get_or_insert(self.time);
+        }
+      }
+      Record::TimeSlotFragment(fragment) => self.put_time_slot(&fragment.0),
+      Record::TimeSlot(slot) => self.put_time_slot(slot),
+      Record::ChatMessage(chat) => {
+        let (scope, message) = match chat.message {
+          ChatMessage::Chat(ref message) => (MessageScope::All, message),
+          ChatMessage::Scoped { scope, ref message } => (scope, message),
+          _ => return,
+        };
+        self.chat.push(ChatEntry {
+          time_ms: self.time,
+          player_id: chat.player_id,
+          scope,
+          message: message.to_string_lossy().to_string(),
+        })
+      }
+      _ => {}
+    }
+  }
+
+  pub fn finish(self) -> ReplayAnalysis {
+    let observer_ids = self.observer_ids;
+    ReplayAnalysis {
+      game_name: self.game_name,
+      duration_ms: self.time,
+      players: self
+        .players
+        .into_iter()
+        .map(|(_, mut player)| {
+          player.observer = observer_ids.contains(&player.player_id);
+          player
+        })
+        .collect(),
+      chat: self.chat,
+    }
+  }
+
+  fn add_player(&mut self, info: &PlayerInfo) {
+    self
+      .players
+      .entry(info.id)
+      .or_insert_with(|| PlayerAnalysis {
+        player_id: info.id,
+        name: info.name.to_string_lossy().to_string(),
+        observer: false,
+        action_count: 0,
+        apm_curve: vec![],
+        production: vec![],
+        left_at_ms: None,
+      });
+  }
+
+  fn set_slots(&mut self, info: &SlotInfo) {
+    // observers are on the team after the last playable one
+    let observer_team = info.slots().len();
+    self.observer_ids = info
+      .slots()
+      .iter()
+      .filter(|slot| slot.player_id != 0 && slot.team as usize == observer_team)
+      .map(|slot| slot.player_id)
+      .collect();
+  }
+
+  fn put_time_slot(&mut self, slot: &TimeSlot) {
+    let time = self.time;
+    for chunk in &slot.actions {
+      let player = match self.players.get_mut(&chunk.player_id) {
+        Some(v) => v,
+        None => continue,
+      };
+      for action in chunk.actions() {
+        // the remaining actions of the chunk can't be located after an unknown one
+        let action = match action {
+          Ok(v) => v,
+          Err(_) => break,
+        };
+        if action.is_apm_action() {
+          player.action_count += 1;
+          let bucket = (time / APM_INTERVAL_MS) as usize;
+          if player.apm_curve.len() <= bucket {
+            player.apm_curve.resize(bucket + 1, 0);
+          }
+          player.apm_curve[bucket] += 1;
+        }
+        if let Some((kind, item_id)) = get_production(&action) {
+          player.production.push(ProductionEvent {
+            time_ms: time,
+            kind,
+            item_id,
+          });
+        }
+      }
+    }
+    self.time += slot.time_increment_ms as u32;
+  }
+}
+
+/// Classifies a production command, `None` for other actions
+pub fn get_production(action: &Action) -> Option<(ProductionKind, ItemId)> {
+  let (kind, item_id) = match action {
+    Action::UnitBuildingAbility(ability) => (ProductionKind::Train, ItemId(ability.item_id)),
+    Action::UnitBuildingAbilityTargeted(ability) => {
+      (ProductionKind::Build, ItemId(ability.item_id))
+    }
+    Action::RemoveUnitFromBuildingQueue(remove) => (ProductionKind::Cancel, ItemId(remove.item_id)),
+    _ => return None,
+  };
+  if item_id.is_object() {
+    Some((kind, item_id))
+  } else {
+    None
+  }
+}
+
+#[test]
+fn test_analyze() {
+  let path = flo_util::sample_path!("replay", "grubby_happy.w3g");
+  let analysis = analyze(W3Replay::open(&path).unwrap()).unwrap();
+  assert!(analysis.duration_ms > 0);
+
+  let players: Vec<_> = analysis.players.iter().filter(|p| !p.observer).collect();
+  assert_eq!(players.len(), 2);
+  for player in players {
+    assert!(player.action_count > 0);
+    assert_eq!(player.apm_curve.iter().sum::<u32>(), player.action_count);
+    assert!(player.production.len() > 0);
+    assert!(player
+      .production
+      .windows(2)
+      .all(|w| w[0].time_ms <= w[1].time_ms));
+  }
+}
+
+#[test]
+fn test_item_id() {
+  let id = ItemId(u32::from_le_bytes(*b"aeph"));
+  assert!(id.is_object());
+  assert_eq!(id.to_string(), "hpea");
+  assert!(!ItemId(0x000D0003).is_object());
+}
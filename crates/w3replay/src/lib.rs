@@ -8,16 +8,21 @@ use std::path::Path;
 mod block;
 mod constants;
 mod header;
+mod metadata;
 mod records;
 
+pub mod analysis;
 pub mod error;
 use block::Blocks;
 pub use constants::*;
 use error::*;
 pub use header::Header;
+pub use metadata::*;
 pub use records::*;
 pub mod replay;
 pub use replay::*;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[derive(Debug)]
 pub struct W3Replay<R> {
@@ -52,6 +57,7 @@ impl W3Replay<BufReader<File>> {
     let mut game = None;
     let mut players = vec![];
     let mut slots = None;
+    let mut flo = None;
     let mut iter = replay.into_records();
     while let Some(record) = iter.next() {
       match record? {
@@ -59,7 +65,12 @@ impl W3Replay<BufReader<File>> {
         Record::SlotInfo(info) => slots = Some(info),
         Record::PlayerInfo(info) => players.push(info.player_info),
         Record::GameStart(_) => break,
-        _ => {}
+        // the replay is still readable without it
+        record => {
+          flo = FloMetadata::from_record(&record)
+            .and_then(|v| v.ok())
+            .or(flo)
+        }
       }
     }
     Ok((
@@ -68,6 +79,7 @@ impl W3Replay<BufReader<File>> {
         game: game.ok_or_else(|| Error::NoGameInfoRecord)?,
         players,
         slots: slots.ok_or_else(|| Error::NoSlotInfoRecord)?,
+        flo,
       },
       iter,
     ))
@@ -100,6 +112,8 @@ pub struct ReplayInfo {
   pub game: GameInfo,
   pub players: Vec<PlayerInfo>,
   pub slots: SlotInfo,
+  /// Set for replays generated by flo
+  pub flo: Option<FloMetadata>,
 }
 
 #[test]
@@ -0,0 +1,155 @@
+//! Flo details embedded in generated replays, so shared replays can be traced back to their game.
+//!
+//! Stored in a `ProtoBuf` record with a message type id the game doesn't use.
+//! Fields are only ever appended, trailing bytes written by newer versions are ignored.
+
+use flo_util::binary::*;
+use flo_w3gs::constants::ProtoBufMessageTypeId;
+
+use crate::records::{ProtoBufPayload, Record};
+
+pub const FLO_METADATA_MESSAGE_TYPE_ID: u8 = 0x46;
+const FLO_METADATA_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FloMetadata {
+  pub game_id: i32,
+  /// Name of the ladder mode, not set for non-ladder games
+  pub ladder: Option<String>,
+  pub node_region: Option<String>,
+  pub players: Vec<FloMetadataPlayer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloMetadataPlayer {
+  /// In-game player id
+  pub player_id: u8,
+  pub flo_player_id: i32,
+  /// Ladder rating when the game was played
+  pub rating: Option<i32>,
+}
+
+impl FloMetadata {
+  pub fn to_record(&self) -> Record {
+    let data = self.encode_to_bytes().to_vec();
+    Record::ProtoBuf(ProtoBufPayload {
+      type_id: ProtoBufMessageTypeId::UnknownValue(FLO_METADATA_MESSAGE_TYPE_ID),
+      len: data.len() as u32,
+      data,
+    })
+  }
+
+  /// Returns `None` if the record is not a flo metadata record
+  pub fn from_record(record: &Record) -> Option<Result<Self, BinDecodeError>> {
+    match *record {
+      Record::ProtoBuf(ref payload)
+        if payload.type_id == ProtoBufMessageTypeId::UnknownValue(FLO_METADATA_MESSAGE_TYPE_ID) =>
+      {
+        Some(Self::decode(&mut payload.data.as_slice()))
+      }
+      _ => None,
+    }
+  }
+}
+
+impl BinEncode for FloMetadata {
+  fn encode<T: BufMut>(&self, buf: &mut T) {
+    buf.put_u8(FLO_METADATA_VERSION);
+    buf.put_i32_le(self.game_id);
+    self
+      .ladder
+      .as_deref()
+      .unwrap_or_default()
+      .into_c_string_lossy()
+      .encode(buf);
+    self
+      .node_region
+      .as_deref()
+      .unwrap_or_default()
+      .into_c_string_lossy()
+      .encode(buf);
+    buf.put_u8(self.players.len() as u8);
+    for player in &self.players {
+      buf.put_u8(player.player_id);
+      buf.put_i32_le(player.flo_player_id);
+      player.rating.is_some().encode(buf);
+      buf.put_i32_le(player.rating.unwrap_or_default());
+    }
+  }
+}
+
+impl BinDecode for FloMetadata {
+  const MIN_SIZE: usize = 1 + 4 + 1 + 1 + 1;
+  const FIXED_SIZE: bool = false;
+
+  fn decode<T: Buf>(buf: &mut T) -> Result<Self, BinDecodeError> {
+    buf.check_size(Self::MIN_SIZE)?;
+    let version = buf.get_u8();
+    if version == 0 {
+      return Err(BinDecodeError::failure("invalid flo metadata version"));
+    }
+    let game_id = buf.get_i32_le();
+    let ladder = non_empty(CString::decode(buf)?);
+    let node_region = non_empty(CString::decode(buf)?);
+    let num_players: u8 = BinDecode::decode(buf)?;
+    let mut players = Vec::with_capacity(num_players as usize);
+    for _ in 0..num_players {
+      buf.check_size(1 + 4 + 1 + 4)?;
+      let player_id = buf.get_u8();
+      let flo_player_id = buf.get_i32_le();
+      let rated: bool = BinDecode::decode(buf)?;
+      let rating = buf.get_i32_le();
+      players.push(FloMetadataPlayer {
+        player_id,
+        flo_player_id,
+        rating: if rated { Some(rating) } else { None },
+      });
+    }
+    Ok(FloMetadata {
+      game_id,
+      ladder,
+      node_region,
+      players,
+    })
+  }
+}
+
+fn non_empty(value: CString) -> Option<String> {
+  Some(value.to_string_lossy().to_string()).filter(|v| !v.is_empty())
+}
+
+#[test]
+fn test_flo_metadata() {
+  let metadata = FloMetadata {
+    game_id: 42,
+    ladder: Some("Solo".to_string()),
+    node_region: None,
+    players: vec![
+      FloMetadataPlayer {
+        player_id: 1,
+        flo_player_id: 100,
+        rating: Some(1650),
+      },
+      FloMetadataPlayer {
+        player_id: 24,
+        flo_player_id: 200,
+        rating: None,
+      },
+    ],
+  };
+
+  let record = metadata.to_record();
+  let bytes = record.encode_to_bytes();
+  let decoded = Record::decode(&mut bytes.freeze()).unwrap();
+  assert_eq!(
+    FloMetadata::from_record(&decoded).unwrap().unwrap(),
+    metadata
+  );
+
+  // written by a newer version
+  let mut data = metadata.encode_to_bytes();
+  data.put_u32_le(7);
+  assert_eq!(FloMetadata::decode(&mut data.freeze()).unwrap(), metadata);
+
+  assert!(FloMetadata::from_record(&Record::GameStart(Default::default())).is_none());
+}
@@ -0,0 +1,37 @@
+//! JS API for browser tools, build with
+//! `--target wasm32-unknown-unknown --no-default-features --features wasm`.
+//! Results are returned as JSON strings.
+
+use bytes::BytesMut;
+use flo_util::binary::BinDecode;
+use flo_w3gs::packet::{Header, Packet};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+use crate::analysis::analyze;
+use crate::W3Replay;
+
+/// Analyzes the content of a `.w3g` file,
+/// see [`ReplayAnalysis`](crate::analysis::ReplayAnalysis)
+#[wasm_bindgen(js_name = analyzeReplay)]
+pub fn analyze_replay(bytes: &[u8]) -> Result<String, JsError> {
+  let analysis = analyze(W3Replay::from_buf(bytes)?)?;
+  Ok(serde_json::to_string(&analysis)?)
+}
+
+/// Splits a captured W3GS stream into packets,
+/// returns the type and payload of each packet
+#[wasm_bindgen(js_name = decodePackets)]
+pub fn decode_packets(bytes: &[u8]) -> Result<String, JsError> {
+  let mut buf = BytesMut::from(bytes);
+  let mut packets = vec![];
+  while buf.len() >= Header::MIN_SIZE {
+    let header = Packet::decode_header(&mut buf)?;
+    let packet = Packet::decode(header, &mut buf)?;
+    packets.push(json!({
+      "type_id": format!("{:?}", packet.type_id()),
+      "payload": packet.payload.as_ref(),
+    }));
+  }
+  Ok(serde_json::to_string(&packets)?)
+}
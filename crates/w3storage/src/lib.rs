@@ -4,6 +4,8 @@ use bytes::Bytes;
 use casclib::Storage;
 use glob::Pattern;
 use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::fs::Metadata;
 use std::path::PathBuf;
 use walkdir::WalkDir;
 
@@ -13,10 +15,15 @@ pub mod error;
 
 use error::*;
 
+/// CASC mods searched by default, ordered from the highest priority to the lowest.
+const DEFAULT_CASC_MODS: &[&str] = &["war3.w3mod:_locales\\enus.w3mod:", BASE_CASC_MOD];
+const BASE_CASC_MOD: &str = "war3.w3mod:";
+
 #[derive(Debug)]
 pub struct W3Storage {
   storage_path: PathBuf,
   overrides: Vec<OverridePath>,
+  casc_mods: Vec<String>,
   handle: Mutex<Option<Storage>>,
 }
 
@@ -25,6 +32,7 @@ impl W3Storage {
     let mut inst = Self {
       storage_path: platform.installation_path.join("Data"),
       overrides: vec![],
+      casc_mods: DEFAULT_CASC_MODS.iter().map(|v| v.to_string()).collect(),
       handle: Mutex::new(None),
     };
 
@@ -47,17 +55,34 @@ impl W3Storage {
     Ok(())
   }
 
-  pub fn list_storage_files(&self, mask: &str) -> Result<Vec<String>> {
-    let cast_mask = Self::get_storage_path(mask);
-    let mut paths: Vec<_> = self.with_storage(|s| -> Result<_, casclib::CascError> {
-      use std::iter::FromIterator;
-      Result::<_, casclib::CascError>::from_iter(
-        s.files_with_mask(cast_mask)
-          .into_iter()
-          .map(|f| f.map(|f| f.get_name().to_string())),
-      )
-    })??;
+  /// Lists files in all CASC mods, with the mod prefix removed.
+  /// A file present in several mods is only listed once.
+  pub fn list_files(&self, mask: &str) -> Result<Vec<String>> {
+    let mut paths: Vec<String> = vec![];
+    let mut seen = HashSet::new();
+    for prefix in &self.casc_mods {
+      let cast_mask = format!("{}{}", prefix, mask);
+      let names = self.with_storage(|s| -> Result<Vec<_>, casclib::CascError> {
+        use std::iter::FromIterator;
+        Result::<_, casclib::CascError>::from_iter(
+          s.files_with_mask(cast_mask)
+            .into_iter()
+            .map(|f| f.map(|f| f.get_name().to_string())),
+        )
+      })??;
+      for name in names {
+        let name = Self::strip_storage_prefix(prefix, &name).to_string();
+        if seen.insert(name.to_lowercase()) {
+          paths.push(name);
+        }
+      }
+    }
+    paths.extend(self.list_override_files(mask)?);
+    Ok(paths)
+  }
 
+  fn list_override_files(&self, mask: &str) -> Result<Vec<String>> {
+    let mut paths = vec![];
     for override_path in &self.overrides {
       let fs_mask = Pattern::new(mask)?;
       for entry in WalkDir::new(&override_path.base_path.join(&override_path.sub_path))
@@ -75,56 +100,68 @@ impl W3Storage {
         }
       }
     }
-
     Ok(paths)
   }
 
   pub fn resolve_file(&self, path: &str) -> Result<Option<File>> {
+    if let Some((resolved_path, m)) = self.resolve_override(path)? {
+      return Ok(Some(File {
+        source: FileSource::Override,
+        size: m.len(),
+        data: Data::Path(resolved_path),
+      }));
+    }
+    for prefix in &self.casc_mods {
+      let storage_path = format!("{}{}", prefix, path);
+      let file = self.with_storage(|s| -> Result<_, casclib::CascError> {
+        s.entry(&storage_path)
+          .open()
+          .and_then(|e| e.read_all())
+          .map(|bytes| {
+            Some(File {
+              source: FileSource::Storage,
+              size: bytes.len() as u64,
+              data: Data::Bytes(Bytes::from(bytes)),
+            })
+          })
+          .or_else(|e| match e {
+            casclib::CascError::FileNotFound => Ok(None),
+            e => Err(e),
+          })
+      })??;
+      if file.is_some() {
+        return Ok(file);
+      }
+    }
+    Ok(None)
+  }
+
+  /// Returns the file system path and metadata of a file if it is
+  /// provided by an override path, without reading its content.
+  pub fn resolve_override(&self, path: &str) -> Result<Option<(PathBuf, Metadata)>> {
     let lower = path.to_lowercase();
     #[cfg(not(windows))]
     let lower = lower.replace('\\', "/");
-    let overrides = self.find_overrides(&lower);
-    if !overrides.is_empty() {
-      for base in overrides {
-        #[cfg(not(windows))]
-        let path = path.replace('\\', "/");
-        let resolved_path = base.join(path);
-        match std::fs::metadata(&resolved_path) {
-          Ok(m) => {
-            return Ok(Some(File {
-              source: FileSource::Override,
-              size: m.len(),
-              data: Data::Path(resolved_path),
-            }))
-          }
-          Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
-            continue;
-          }
-          Err(e) => return Err(e.into()),
+    for base in self.find_overrides(&lower) {
+      #[cfg(not(windows))]
+      let path = path.replace('\\', "/");
+      let resolved_path = base.join(path);
+      match std::fs::metadata(&resolved_path) {
+        Ok(m) => return Ok(Some((resolved_path, m))),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+          continue;
         }
+        Err(e) => return Err(e.into()),
       }
     }
-    let bytes = self.with_storage(|s| -> Result<_, casclib::CascError> {
-      s.entry(&Self::get_storage_path(path))
-        .open()
-        .and_then(|e| e.read_all())
-        .map(|bytes| {
-          Some(File {
-            source: FileSource::Storage,
-            size: bytes.len() as u64,
-            data: Data::Bytes(Bytes::from(bytes)),
-          })
-        })
-        .or_else(|e| match e {
-          casclib::CascError::FileNotFound => Ok(None),
-          e => Err(e),
-        })
-    })??;
-    Ok(bytes)
+    Ok(None)
   }
 
-  fn get_storage_path(path: &str) -> String {
-    format!("war3.w3mod:{}", path)
+  fn strip_storage_prefix<'a>(prefix: &str, name: &'a str) -> &'a str {
+    match name.get(..prefix.len()) {
+      Some(head) if head.eq_ignore_ascii_case(prefix) => &name[prefix.len()..],
+      _ => name,
+    }
   }
 
   fn find_overrides(&self, path: &str) -> Vec<PathBuf> {
@@ -256,6 +293,30 @@ fn test_fs_map() {
     .is_some());
 }
 
+#[test]
+fn test_strip_storage_prefix() {
+  assert_eq!(
+    W3Storage::strip_storage_prefix("war3.w3mod:", "war3.w3mod:maps\\(2)bootybay.w3m"),
+    "maps\\(2)bootybay.w3m"
+  );
+  assert_eq!(
+    W3Storage::strip_storage_prefix("war3.w3mod:", "War3.w3mod:maps\\(2)bootybay.w3m"),
+    "maps\\(2)bootybay.w3m"
+  );
+  assert_eq!(
+    W3Storage::strip_storage_prefix("war3.w3mod:", "maps\\(2)bootybay.w3m"),
+    "maps\\(2)bootybay.w3m"
+  );
+}
+
+#[test]
+fn test_casc_storage_melee_maps() {
+  let s = W3Storage::from_env().unwrap();
+  let paths = s.list_files("maps\\*").unwrap();
+  assert!(paths.iter().all(|p| !p.contains(".w3mod:")));
+  assert!(s.resolve_file("maps\\(2)bootybay.w3m").unwrap().is_some());
+}
+
 // #[test]
 // fn test_local_override() {
 //   let p = ClientPlatformInfo::from_env().unwrap();
@@ -116,9 +116,7 @@ fn test_path_tree() {
   use flo_platform::ClientPlatformInfo;
   let p = ClientPlatformInfo::from_env().unwrap();
   let s = W3Storage::new(&p).unwrap();
-  let mut paths = s
-    .list_storage_files(&format!("maps{}*", MAIN_SEPARATOR))
-    .unwrap();
+  let mut paths = s.list_files(&format!("maps{}*", MAIN_SEPARATOR)).unwrap();
   paths.sort();
   paths.dedup();
 
@@ -0,0 +1,7 @@
+#![no_main]
+use flo_net::fuzz::StreamInput;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: StreamInput| {
+  flo_net::fuzz::frame_codec(input);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use flo_w3gs::fuzz::StreamInput;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: StreamInput| {
+  flo_w3gs::fuzz::codec(input);
+});
@@ -0,0 +1,3 @@
+drop table matchmaking_game;
+drop table ladder_map;
+drop table player_rating;
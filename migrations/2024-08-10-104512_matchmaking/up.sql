@@ -0,0 +1,29 @@
+create table player_rating (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    mode integer not null,
+    rating integer not null,
+    wins integer not null default 0,
+    losses integer not null default 0,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null,
+    unique(player_id, mode)
+);
+
+create index player_rating_mode_rating on player_rating(mode, rating);
+
+create table ladder_map (
+    id serial not null primary key,
+    mode integer not null,
+    map jsonb not null,
+    enabled boolean not null default true,
+    created_at timestamp with time zone default now() not null
+);
+
+create table matchmaking_game (
+    game_id integer not null primary key references game(id),
+    mode integer not null,
+    winner_team integer,
+    reported_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null
+);
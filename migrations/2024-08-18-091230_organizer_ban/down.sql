@@ -0,0 +1 @@
+drop table organizer_ban;
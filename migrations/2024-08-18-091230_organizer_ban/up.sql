@@ -0,0 +1,11 @@
+create table organizer_ban (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id),
+    player_id integer not null references player(id),
+    reason text not null default '',
+    ban_expires_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    unique(api_client_id, player_id)
+);
+
+create index organizer_ban_player_id on organizer_ban(player_id);
@@ -0,0 +1,13 @@
+create table api_token (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id),
+    name text not null,
+    token_hash text not null unique,
+    scopes integer not null default 0,
+    expires_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('api_token');
+
+create index api_token_api_client_id on api_token(api_client_id);
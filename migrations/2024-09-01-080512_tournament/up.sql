@@ -0,0 +1,35 @@
+create table tournament (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id),
+    name text not null,
+    format integer not null,
+    status integer not null default 0,
+    map jsonb not null,
+    node_id integer references node(id),
+    check_in_window_secs integer not null default 600,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('tournament');
+
+create table tournament_match (
+    id serial not null primary key,
+    tournament_id integer not null references tournament(id),
+    round integer not null,
+    position integer not null,
+    group_index integer,
+    player1_id integer references player(id),
+    player2_id integer references player(id),
+    player1_checked_in_at timestamp with time zone,
+    player2_checked_in_at timestamp with time zone,
+    scheduled_at timestamp with time zone,
+    status integer not null default 0,
+    game_id integer references game(id),
+    winner_id integer references player(id),
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null,
+    unique(tournament_id, round, position)
+);
+SELECT diesel_manage_updated_at('tournament_match');
+
+create index tournament_match_status_scheduled_at on tournament_match(status, scheduled_at);
@@ -0,0 +1,10 @@
+create table game_result (
+    game_id integer not null primary key references game(id),
+    valid boolean not null,
+    winner_team integer,
+    source text,
+    invalid_reason text,
+    duration_ms integer not null,
+    report jsonb not null,
+    created_at timestamp with time zone default now() not null
+);
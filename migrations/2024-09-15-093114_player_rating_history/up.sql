@@ -0,0 +1,11 @@
+create table player_rating_history (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    mode integer not null,
+    game_id integer not null references game(id),
+    rating_before integer not null,
+    rating_after integer not null,
+    created_at timestamp with time zone default now() not null
+);
+
+create index player_rating_history_player_mode on player_rating_history(player_id, mode, created_at);
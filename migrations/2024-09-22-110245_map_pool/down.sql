@@ -0,0 +1,2 @@
+alter table tournament drop column map_pool_id;
+drop table map_pool;
@@ -0,0 +1,11 @@
+create table map_pool (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id),
+    name text not null,
+    maps jsonb not null,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('map_pool');
+
+alter table tournament add column map_pool_id integer references map_pool(id);
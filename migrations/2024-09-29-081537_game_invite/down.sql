@@ -0,0 +1,3 @@
+drop table game_invite;
+
+alter table game drop column password_hash;
@@ -0,0 +1,13 @@
+create extension if not exists pgcrypto;
+
+alter table game add column password_hash text;
+
+create table game_invite (
+    token text not null primary key,
+    game_id integer not null references game(id) on delete cascade,
+    created_by integer not null references player(id),
+    used_by integer references player(id),
+    created_at timestamp with time zone default now() not null,
+    used_at timestamp with time zone
+);
+create index game_invite_game_id on game_invite(game_id);
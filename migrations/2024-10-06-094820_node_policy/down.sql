@@ -0,0 +1,4 @@
+drop table node_assignment;
+drop table node_policy;
+alter table node drop column provider;
+alter table node drop column region;
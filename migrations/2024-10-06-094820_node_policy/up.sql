@@ -0,0 +1,23 @@
+alter table node add column region text not null default '';
+alter table node add column provider text not null default '';
+
+create table node_policy (
+    mode integer not null primary key,
+    allowed_regions jsonb not null default '[]',
+    max_worst_ping integer,
+    preferred_providers jsonb not null default '[]',
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('node_policy');
+
+create table node_assignment (
+    game_id integer not null primary key references game(id) on delete cascade,
+    node_id integer not null references node(id),
+    source integer not null,
+    mode integer,
+    tournament_id integer references tournament(id) on delete set null,
+    worst_ping integer,
+    candidates jsonb not null,
+    created_at timestamp with time zone default now() not null
+);
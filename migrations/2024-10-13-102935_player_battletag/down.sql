@@ -0,0 +1,4 @@
+drop index player_bnet_account_id;
+alter table player drop column battletag_verified_at;
+alter table player drop column bnet_account_id;
+alter table player drop column battletag;
@@ -0,0 +1,5 @@
+alter table player add column battletag text;
+alter table player add column bnet_account_id bigint;
+alter table player add column battletag_verified_at timestamp with time zone;
+
+create unique index player_bnet_account_id on player (api_client_id, bnet_account_id);
@@ -0,0 +1,2 @@
+drop table player_penalty;
+drop table penalty_rule;
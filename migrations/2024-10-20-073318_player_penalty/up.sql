@@ -0,0 +1,29 @@
+create table penalty_rule (
+    id serial not null primary key,
+    reason integer not null,
+    offense_count integer not null,
+    window_secs integer not null,
+    penalty_type integer not null,
+    duration_secs integer not null,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('penalty_rule');
+create unique index penalty_rule_reason_offense_count on penalty_rule(reason, offense_count);
+
+create table player_penalty (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    game_id integer references game(id) on delete set null,
+    reason integer not null,
+    penalty_type integer not null,
+    expires_at timestamp with time zone not null,
+    appeal_status integer not null default 0,
+    appeal_message text,
+    appeal_resolved_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('player_penalty');
+create index player_penalty_player_id on player_penalty(player_id);
+create unique index player_penalty_game_player_reason on player_penalty(game_id, player_id, reason);
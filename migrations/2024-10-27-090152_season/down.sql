@@ -0,0 +1,11 @@
+delete from player_rating_history where season_id <> (select id from season where status = 1);
+alter table player_rating_history drop column season_id;
+
+delete from player_rating where season_id <> (select id from season where status = 1);
+drop index player_rating_season_id_mode_rating;
+alter table player_rating drop constraint player_rating_season_id_player_id_mode_key;
+alter table player_rating drop column season_id;
+alter table player_rating add constraint player_rating_player_id_mode_key unique (player_id, mode);
+create index player_rating_mode_rating on player_rating(mode, rating);
+
+drop table season;
@@ -0,0 +1,28 @@
+create table season (
+    id serial not null primary key,
+    name text not null,
+    status integer not null default 0,
+    starts_at timestamp with time zone not null,
+    carry_over_percent integer not null,
+    started_at timestamp with time zone,
+    ended_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('season');
+create unique index season_active on season(status) where status = 1;
+
+insert into season (name, status, starts_at, carry_over_percent, started_at)
+values ('Season 1', 1, now(), 100, now());
+
+alter table player_rating add column season_id integer references season(id);
+update player_rating set season_id = (select id from season);
+alter table player_rating alter column season_id set not null;
+alter table player_rating drop constraint player_rating_player_id_mode_key;
+alter table player_rating add constraint player_rating_season_id_player_id_mode_key unique (season_id, player_id, mode);
+drop index player_rating_mode_rating;
+create index player_rating_season_id_mode_rating on player_rating(season_id, mode, rating);
+
+alter table player_rating_history add column season_id integer references season(id);
+update player_rating_history set season_id = (select id from season);
+alter table player_rating_history alter column season_id set not null;
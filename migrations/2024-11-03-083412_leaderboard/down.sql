@@ -0,0 +1,5 @@
+drop table leaderboard_snapshot;
+
+drop index player_rating_season_id_mode_rating_player_id;
+alter table player_rating drop column region;
+alter table player_rating drop column race;
@@ -0,0 +1,12 @@
+alter table player_rating add column race integer;
+alter table player_rating add column region text;
+create index player_rating_season_id_mode_rating_player_id on player_rating(season_id, mode, rating desc, player_id);
+
+create table leaderboard_snapshot (
+    season_id integer not null references season(id),
+    mode integer not null,
+    taken_on date not null,
+    player_id integer not null references player(id),
+    rank integer not null,
+    primary key (season_id, mode, taken_on, player_id)
+);
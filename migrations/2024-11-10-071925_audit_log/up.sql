@@ -0,0 +1,13 @@
+create table audit_log (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id),
+    api_token_id integer references api_token(id) on delete set null,
+    actor text,
+    action integer not null,
+    target_type integer not null,
+    target_id integer,
+    detail jsonb not null default '{}',
+    created_at timestamp with time zone default now() not null
+);
+create index audit_log_api_client_id on audit_log(api_client_id, id);
+create index audit_log_target on audit_log(target_type, target_id);
@@ -0,0 +1,10 @@
+create table game_slot_control (
+    game_id integer not null references game(id) on delete cascade,
+    slot_index integer not null,
+    locked boolean not null default false,
+    reserved_player_id integer references player(id) on delete set null,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null,
+    primary key (game_id, slot_index)
+);
+SELECT diesel_manage_updated_at('game_slot_control');
@@ -0,0 +1,2 @@
+drop table player_link;
+drop table player_fingerprint;
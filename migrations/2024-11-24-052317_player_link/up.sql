@@ -0,0 +1,26 @@
+create table player_fingerprint (
+    id serial not null primary key,
+    player_id integer not null references player(id) on delete cascade,
+    kind integer not null,
+    hash text not null,
+    seen_count integer not null default 1,
+    first_seen_at timestamp with time zone default now() not null,
+    last_seen_at timestamp with time zone default now() not null,
+    unique(player_id, kind, hash)
+);
+create index player_fingerprint_hash on player_fingerprint(hash);
+
+create table player_link (
+    id serial not null primary key,
+    player_id integer not null references player(id) on delete cascade,
+    linked_player_id integer not null references player(id) on delete cascade,
+    status integer not null default 0,
+    shared_fingerprints integer not null default 0,
+    reviewed_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null,
+    unique(player_id, linked_player_id),
+    check(player_id < linked_player_id)
+);
+SELECT diesel_manage_updated_at('player_link');
+create index player_link_linked_player_id on player_link(linked_player_id);
@@ -0,0 +1 @@
+drop table discord_channel;
@@ -0,0 +1,14 @@
+create table discord_channel (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id) on delete cascade,
+    api_token_id integer not null references api_token(id) on delete cascade,
+    channel_id text not null unique,
+    webhook_url text not null,
+    events integer not null default 0,
+    host_map_pool_id integer references map_pool(id) on delete set null,
+    host_node_id integer references node(id) on delete set null,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+SELECT diesel_manage_updated_at('discord_channel');
+create index discord_channel_api_client_id on discord_channel(api_client_id);
@@ -0,0 +1,6 @@
+create table game_replay (
+    game_id integer not null primary key references game(id) on delete cascade,
+    data bytea not null,
+    size integer not null,
+    created_at timestamp with time zone default now() not null
+);
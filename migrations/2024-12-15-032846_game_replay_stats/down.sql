@@ -0,0 +1,2 @@
+alter table game_replay
+    drop column stats;
@@ -0,0 +1,2 @@
+alter table game_replay
+    add column stats jsonb;
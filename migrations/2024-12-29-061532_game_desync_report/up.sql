@@ -0,0 +1,5 @@
+create table game_desync_report (
+    game_id integer not null primary key references game(id) on delete cascade,
+    data jsonb not null,
+    created_at timestamp with time zone default now() not null
+);
@@ -0,0 +1,14 @@
+create table stats_aggregate (
+    period integer not null,
+    period_start timestamp with time zone not null,
+    kind integer not null,
+    key text not null,
+    sub_key text not null,
+    games integer not null,
+    wins integer not null,
+    invalid integer not null,
+    desyncs integer not null,
+    total_duration_ms bigint not null,
+    updated_at timestamp with time zone default now() not null,
+    primary key (period, period_start, kind, key, sub_key)
+);
@@ -0,0 +1 @@
+alter table stats_aggregate drop column hosted;
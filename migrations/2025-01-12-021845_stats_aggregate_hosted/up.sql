@@ -0,0 +1 @@
+alter table stats_aggregate add column hosted integer not null default 0;
@@ -0,0 +1 @@
+alter table game_result drop column ranked;
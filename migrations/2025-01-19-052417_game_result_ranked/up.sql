@@ -0,0 +1 @@
+alter table game_result add column ranked boolean not null default true;
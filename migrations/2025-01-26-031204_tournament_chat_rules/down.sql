@@ -0,0 +1 @@
+alter table tournament drop column chat_rules;
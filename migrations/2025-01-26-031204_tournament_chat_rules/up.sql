@@ -0,0 +1 @@
+alter table tournament add column chat_rules jsonb;
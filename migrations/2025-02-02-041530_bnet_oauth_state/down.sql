@@ -0,0 +1 @@
+drop table bnet_oauth_state;
@@ -0,0 +1,7 @@
+create table bnet_oauth_state (
+    nonce text not null primary key,
+    player_id integer not null references player(id) on delete cascade,
+    expires_at timestamp with time zone not null,
+    created_at timestamp with time zone default now() not null
+);
+create index bnet_oauth_state_expires_at on bnet_oauth_state(expires_at);